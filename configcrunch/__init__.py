@@ -1,11 +1,44 @@
-from configcrunch._main import YamlConfigDocument, DocReference, load_multiple_yml, \
+from configcrunch._main import YamlConfigDocument, DocReference, YcdList, DirectivePolicy, ResolutionContext, CancellationToken, \
+    EncryptedValue, WalkNode, TemplateAuditEntry, MergeStats, DocumentView, Workspace, \
+    load_multiple_yml, load_from_manifest, parse_set_overrides, \
+    OneOf, Range, Pattern, Length, Coerce, Secret, UniqueList, KeyedList, TypedSchema, Field, \
+    register_template, unregister_template, register_document, unregister_document, \
+    register_schema, unregister_schema, register_directive, unregister_directive, \
+    register_policy, unregister_policy, register_transformer, unregister_transformer, set_transformer_enabled, \
+    register_key_provider, unregister_key_provider, \
     ConfigcrunchError, ReferencedDocumentNotFound, CircularDependencyError, \
-    VariableProcessingError, InvalidDocumentError, InvalidHeaderError, InvalidRemoveError
+    VariableProcessingError, InvalidDocumentError, InvalidHeaderError, InvalidRemoveError, \
+    LockViolationError, DocumentLimitError, DuplicateKeyError, SignatureError, RuleViolationError, UnknownKeyError, \
+    LayerValidationError, MergedValidationError, WorkspaceValidationError, \
+    PolicyViolationError, ResolutionCancelledError, EncryptionError, \
+    DeprecatedKeyWarning, UnknownKeyWarning, DuplicateKeyWarning, CoercionWarning
+from configcrunch._tracing import enable_tracing, disable_tracing
+from configcrunch._async import load_multiple_yml_async, resolve_async
+from collections.abc import Sequence
+
+Sequence.register(YcdList)
 
 # Constants
 REF = "$ref"
+EXTENDS = "$extends"
+VARS = "$vars"
+IMPORT_ALL = "$import_all"
+SCHEMA = "$schema"
+DEPRECATED = "$deprecated"
+ANCHOR = "$anchor"
+ALIAS = "$alias"
+REF_FIRST = "$ref_first"
+COMMENT = "$comment"
+DEFAULT = "$default"
+LOCK = "$lock"
+REMOVE_IF = "$remove_if"
+FEATURES = "$features"
+EXEC = "$exec"
+ENCRYPTED = "$encrypted"
 REMOVE = "$remove"
 REMOVE_FROM_LIST_PREFIX = REMOVE + "::"
+INSERT_BEFORE_PREFIX = "$insert_before::"
+INSERT_AFTER_PREFIX = "$insert_after::"
 
 
 def variable_helper(func):
@@ -21,12 +54,37 @@ def variable_helper(func):
     return func
 
 
+def helper(func=None, *, name=None, pass_document=False):
+    """
+    Marks a function as variable helper, like :func:`variable_helper`. Can be used for methods of
+    documents and for functions passed to ``process_vars_for``::
+
+        @helper(name="project_name", pass_document=True)
+        def get_project_name(document):
+            return document.internal_get("name")
+
+    :param name:          Name of the helper in templates. Defaults to the name of the function.
+                          Required for lambdas and callables without ``__name__`` (e.g. ``functools.partial``).
+    :param pass_document: Whether the document, whose template is rendered, is passed as first argument.
+    """
+    def decorator(func):
+        variable_helper(func)
+        func.__helper_name = name
+        func.__helper_pass_document = pass_document
+        return func
+    if func is not None:
+        return decorator(func)
+    return decorator
+
+
 try:
     import yaml
     def ycd_representer(dumper, data):
         return dumper.represent_mapping('!' + data.__class__.__name__, data.items())
 
     yaml.add_multi_representer(YamlConfigDocument, ycd_representer)
+    yaml.add_representer(YcdList, lambda dumper, data: dumper.represent_list(list(data)))
+    yaml.add_representer(EncryptedValue, lambda dumper, data: dumper.represent_scalar('!encrypted', data.ciphertext))
 except ImportError:
     pass
 
@@ -35,8 +93,50 @@ except ImportError:
 __all__ = [
     'YamlConfigDocument',
     'DocReference',
+    'YcdList',
+    'DirectivePolicy',
+    'ResolutionContext',
+    'CancellationToken',
+    'EncryptedValue',
+    'WalkNode',
+    'TemplateAuditEntry',
+    'MergeStats',
+    'DocumentView',
+    'Workspace',
+    'OneOf',
+    'Range',
+    'Pattern',
+    'Length',
+    'Coerce',
+    'Secret',
+    'UniqueList',
+    'KeyedList',
+    'TypedSchema',
+    'Field',
     'variable_helper',
+    'helper',
     'load_multiple_yml',
+    'load_from_manifest',
+    'load_multiple_yml_async',
+    'resolve_async',
+    'parse_set_overrides',
+    'register_template',
+    'unregister_template',
+    'register_document',
+    'unregister_document',
+    'register_schema',
+    'unregister_schema',
+    'register_directive',
+    'unregister_directive',
+    'register_policy',
+    'unregister_policy',
+    'register_transformer',
+    'unregister_transformer',
+    'set_transformer_enabled',
+    'register_key_provider',
+    'unregister_key_provider',
+    'enable_tracing',
+    'disable_tracing',
 
     'ConfigcrunchError',
     'ReferencedDocumentNotFound',
@@ -44,5 +144,21 @@ __all__ = [
     'VariableProcessingError',
     'InvalidDocumentError',
     'InvalidHeaderError',
-    'InvalidRemoveError'
+    'InvalidRemoveError',
+    'LockViolationError',
+    'DocumentLimitError',
+    'DuplicateKeyError',
+    'SignatureError',
+    'PolicyViolationError',
+    'ResolutionCancelledError',
+    'EncryptionError',
+    'RuleViolationError',
+    'UnknownKeyError',
+    'LayerValidationError',
+    'MergedValidationError',
+    'WorkspaceValidationError',
+    'DeprecatedKeyWarning',
+    'UnknownKeyWarning',
+    'DuplicateKeyWarning',
+    'CoercionWarning'
 ]
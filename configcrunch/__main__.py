@@ -0,0 +1,54 @@
+"""
+Command line interface of configcrunch::
+
+    python -m configcrunch lint-override myapp.config:Config base.yml override.yml [later.yml ...] [-l lookup_path]
+
+The document type is given as ``module:class``. Commands exit with status 1 if they report any findings.
+"""
+import argparse
+import importlib
+import sys
+
+
+def document_type(spec):
+    """Imports the document type ``module:class``."""
+    module, _, name = spec.partition(':')
+    if not module or not name:
+        raise argparse.ArgumentTypeError(f"Expected the document type as module:class, got: {spec}")
+    try:
+        return getattr(importlib.import_module(module), name)
+    except (ImportError, AttributeError) as e:
+        raise argparse.ArgumentTypeError(f"Unable to import the document type {spec}: {e}")
+
+
+def lint_override(args):
+    """Prints the findings of YamlConfigDocument.lint_override for the override over the resolved base."""
+    base = args.type.from_yaml(args.base)
+    base.resolve_and_merge_references(args.lookup_path)
+    override = args.type.from_yaml(args.override)
+    later_layers = [args.type.from_yaml(path) for path in args.later]
+    findings = base.lint_override(override, later_layers)
+    for kind, path, message in findings:
+        print(f"{args.override}: {path}: {kind}: {message}")
+    return 1 if findings else 0
+
+
+def main(argv=None):
+    parser = argparse.ArgumentParser(prog='configcrunch', description='Tools for configcrunch documents.')
+    commands = parser.add_subparsers(dest='command', required=True)
+
+    lint = commands.add_parser('lint-override', help='Report ineffective or suspicious entries of an override.')
+    lint.add_argument('type', type=document_type, help='Document type (module:class)')
+    lint.add_argument('base', help='The document the override is merged over')
+    lint.add_argument('override', help='The override to lint')
+    lint.add_argument('later', nargs='*', help='Layers that are merged over the override afterwards')
+    lint.add_argument('-l', '--lookup-path', action='append', default=[],
+                      help='Lookup path for references of the base (can be repeated)')
+    lint.set_defaults(func=lint_override)
+
+    args = parser.parse_args(argv)
+    return args.func(args)
+
+
+if __name__ == '__main__':
+    sys.exit(main())
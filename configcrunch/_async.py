@@ -0,0 +1,46 @@
+"""
+asyncio variants of loading and resolving. The work is done in a worker thread (the default executor of
+the event loop, unless one is passed), so the event loop isn't blocked while files are read and parsed.
+The GIL is released while files are read and parsed, so other threads, including the event loop, keep running.
+"""
+import asyncio
+from functools import partial
+
+from configcrunch._main import CancellationToken, load_multiple_yml
+
+
+async def _run_in_executor(func, *args, executor=None, **kwargs):
+    # If the awaiting task is cancelled, the work in the worker thread is cancelled as well. A cancel_token
+    # passed by the caller is left alone, the caller has to cancel it.
+    token = None
+    if kwargs.get('cancel_token') is None:
+        token = kwargs['cancel_token'] = CancellationToken()
+    loop = asyncio.get_running_loop()
+    try:
+        return await loop.run_in_executor(executor, partial(func, *args, **kwargs))
+    except asyncio.CancelledError:
+        if token is not None:
+            token.cancel()
+        raise
+
+
+async def load_multiple_yml_async(doc_type, *args, executor=None, **kwargs):
+    """
+    Like ``load_multiple_yml``, but awaitable. Accepts the same keyword arguments.
+
+    :param executor: The ``concurrent.futures.Executor`` to load the documents in.
+                     Defaults to the default executor of the running event loop.
+    """
+    return await _run_in_executor(load_multiple_yml, doc_type, *args, executor=executor, **kwargs)
+
+
+async def resolve_async(document, lookup_paths, *, executor=None, **kwargs):
+    """
+    Like ``document.resolve_and_merge_references(lookup_paths)``, but awaitable. Accepts the same keyword
+    arguments. The document must not be accessed by other threads until the resolution is done.
+
+    :param executor: The ``concurrent.futures.Executor`` to resolve the document in.
+                     Defaults to the default executor of the running event loop.
+    """
+    return await _run_in_executor(document.resolve_and_merge_references, lookup_paths,
+                                  executor=executor, **kwargs)
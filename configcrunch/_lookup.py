@@ -0,0 +1,216 @@
+"""
+Lookup paths that are not directories on disk. They are resolved to (cache) directories, that are then
+searched like normal lookup paths. Called from Rust code (lookup::lookup_dir).
+"""
+import hashlib
+import importlib
+import json
+import os
+import pathlib
+import subprocess
+from typing import Dict, List, Optional, Tuple
+from urllib.error import HTTPError, URLError
+from urllib.parse import quote
+from urllib.request import Request, urlopen
+
+from configcrunch._main import ReferencedDocumentNotFound
+
+REMOTE_TIMEOUT = 30
+# URLs of files (with the file in the cache) that were already fetched or revalidated by this process, and
+# whether they exist.
+_checked_urls: Dict[Tuple[str, str], bool] = {}
+# Git lookup paths (with the cache directory) that were already checked out by this process, and their
+# directories.
+_checked_out: Dict[Tuple[str, Optional[str]], str] = {}
+# Package lookup paths that were already resolved by this process, and their directories.
+_package_dirs: Dict[str, str] = {}
+
+
+def default_cache_dir() -> str:
+    """The cache directory used if the ``cache_dir`` loading option is not set."""
+    if 'CONFIGCRUNCH_CACHE_DIR' in os.environ:
+        return os.environ['CONFIGCRUNCH_CACHE_DIR']
+    base = os.environ.get('XDG_CACHE_HOME') or os.path.join(os.path.expanduser('~'), '.cache')
+    return os.path.join(base, 'configcrunch')
+
+
+def _cache_subdir(cache_dir: Optional[str], kind: str, key: str) -> str:
+    digest = hashlib.sha256(key.encode('utf-8')).hexdigest()[:32]
+    return os.path.join(cache_dir or default_cache_dir(), kind, digest)
+
+
+def fetch_remote(base_url: str, path_in_repo: str, extensions: List[str], cache_dir: Optional[str],
+                 signatures: bool = False) -> str:
+    """
+    Fetches the documents at ``path_in_repo`` (without extension, one request per extension) below the HTTPS
+    base URL into the cache and returns the cache directory of the base URL. With ``signatures``, the
+    signature (``<file>.minisig``) of every existing document is fetched as well.
+
+    Cached files are revalidated (once per process) with ``If-None-Match`` / ``If-Modified-Since``. If the
+    server can't be reached, the cached files are used. Files that don't exist (anymore) are removed from
+    the cache.
+    """
+    directory = _cache_subdir(cache_dir, 'remote', base_url)
+    path_in_repo = path_in_repo.strip('/')
+    if not path_in_repo or '..' in path_in_repo.split('/'):
+        return directory
+    for ext in extensions:
+        relative = f'{path_in_repo}.{ext}'
+        url = base_url.rstrip('/') + '/' + quote(relative)
+        target = os.path.join(directory, *relative.split('/'))
+        if (url, target) not in _checked_urls:
+            _checked_urls[(url, target)] = _fetch_file(url, target)
+        if signatures and _checked_urls[(url, target)]:
+            signature_url, signature_target = url + '.minisig', target + '.minisig'
+            if (signature_url, signature_target) not in _checked_urls:
+                _checked_urls[(signature_url, signature_target)] = _fetch_file(signature_url, signature_target)
+    return directory
+
+
+def _fetch_file(url: str, target: str) -> bool:
+    """Fetches url into target, revalidating an existing copy. Returns whether the file exists."""
+    meta_path = target + '.meta.json'
+    meta = {}
+    if os.path.exists(target) and os.path.exists(meta_path):
+        with open(meta_path) as f:
+            meta = json.load(f)
+    request = Request(url)
+    if meta.get('etag'):
+        request.add_header('If-None-Match', meta['etag'])
+    if meta.get('last_modified'):
+        request.add_header('If-Modified-Since', meta['last_modified'])
+    try:
+        with urlopen(request, timeout=REMOTE_TIMEOUT) as response:
+            content = response.read()
+            headers = response.headers
+    except HTTPError as e:
+        if e.code == 304:
+            return True
+        if e.code in (404, 410):
+            for path in (target, meta_path):
+                if os.path.exists(path):
+                    os.remove(path)
+            return False
+        # Other errors are handled like an unreachable server.
+        return os.path.exists(target)
+    except (URLError, OSError):
+        # Offline: Use the cached copy, if there is one.
+        return os.path.exists(target)
+    os.makedirs(os.path.dirname(target), exist_ok=True)
+    tmp = target + '.tmp'
+    with open(tmp, 'wb') as f:
+        f.write(content)
+    os.replace(tmp, target)
+    with open(meta_path, 'w') as f:
+        json.dump({'url': url, 'etag': headers.get('ETag'), 'last_modified': headers.get('Last-Modified')}, f)
+    return True
+
+
+def parse_git_lookup_path(lookup_path: str) -> Tuple[str, Optional[str], str]:
+    """
+    Splits a git lookup path ``git+<url>[@<ref>][//<subdir>]`` into the URL of the repository, the ref
+    (None for the default branch) and the sub-directory ('' for the root of the repository).
+    """
+    url = lookup_path[len('git+'):]
+    scheme_end = url.find('://')
+    scheme_end = 0 if scheme_end < 0 else scheme_end + len('://')
+    subdir = ''
+    subdir_start = url.find('//', scheme_end)
+    if subdir_start >= 0:
+        url, subdir = url[:subdir_start], url[subdir_start + 2:].strip('/')
+    ref = None
+    # The user of scp-like URLs (user@host:path) is not a ref.
+    last_part_start = max(url.rfind('/'), url.rfind(':')) + 1
+    if '@' in url[last_part_start:]:
+        url, ref = url.rsplit('@', 1)
+    return url, ref or None, subdir
+
+
+def checkout_git(lookup_path: str, cache_dir: Optional[str]) -> str:
+    """
+    Fetches the ref of a git lookup path (once per process) into the cache and returns the directory of its
+    sub-directory in the checkout. If fetching fails, an existing checkout of the ref is used.
+    """
+    key = (lookup_path, cache_dir)
+    if key in _checked_out:
+        return _checked_out[key]
+    url, ref, subdir = parse_git_lookup_path(lookup_path)
+    if (ref or '').startswith('-') or url.startswith('-'):
+        # Would be passed to git as an option.
+        raise ValueError(f"Invalid git lookup path {lookup_path}: The URL and ref must not start with '-'.")
+    checkout = _cache_subdir(cache_dir, 'git', f'{url}@{ref or ""}')
+    try:
+        if not os.path.isdir(os.path.join(checkout, '.git')):
+            os.makedirs(checkout, exist_ok=True)
+            _git(checkout, 'init', '--quiet')
+            _git(checkout, 'remote', 'add', '--', 'origin', url)
+        _git(checkout, 'fetch', '--quiet', '--tags', '--', 'origin', ref or 'HEAD')
+        # The revision comes before the --, that ends the revisions.
+        _git(checkout, 'checkout', '--quiet', '--force', '--detach', 'FETCH_HEAD', '--')
+    except (subprocess.CalledProcessError, OSError) as e:
+        # Offline: Use the existing checkout, if there is one.
+        if not _has_checkout(checkout):
+            details = e.stderr.decode('utf-8', 'replace').strip() if getattr(e, 'stderr', None) else str(e)
+            raise ReferencedDocumentNotFound(f"Unable to fetch the git lookup path {lookup_path}: {details}") from e
+    directory = os.path.join(checkout, *subdir.split('/')) if subdir else checkout
+    _checked_out[key] = directory
+    return directory
+
+
+def _git(checkout: str, *args: str):
+    subprocess.run(['git', '-C', checkout, *args], check=True, capture_output=True)
+
+
+def _has_checkout(checkout: str) -> bool:
+    try:
+        _git(checkout, 'rev-parse', '--verify', '--quiet', 'HEAD')
+        return True
+    except (subprocess.CalledProcessError, OSError):
+        return False
+
+
+def package_dir(lookup_path: str, cache_dir: Optional[str]) -> str:
+    """
+    Returns the directory of the resources of a package lookup path ``pkg://<package>[/<subdir>]``, found with
+    importlib.resources. Resources, that are not files on disk (e.g. of packages imported from zip files), are
+    copied into the cache once per process.
+    """
+    if lookup_path in _package_dirs:
+        return _package_dirs[lookup_path]
+    package, _, subdir = lookup_path[len('pkg://'):].partition('/')
+    try:
+        resources = _package_files(package)
+    except ImportError as e:
+        raise ReferencedDocumentNotFound(f"Unable to find the package of the lookup path {lookup_path}: {e}") from e
+    for part in subdir.split('/'):
+        if part:
+            resources = resources / part
+    if isinstance(resources, pathlib.Path):
+        directory = str(resources)
+    else:
+        directory = _cache_subdir(cache_dir, 'pkg', lookup_path)
+        if resources.is_dir():
+            _copy_resources(resources, directory)
+    _package_dirs[lookup_path] = directory
+    return directory
+
+
+def _package_files(package: str):
+    try:
+        from importlib.resources import files
+    except ImportError:
+        # Python < 3.9
+        module = importlib.import_module(package)
+        return pathlib.Path(os.path.dirname(module.__file__))
+    return files(package)
+
+
+def _copy_resources(resources, directory: str):
+    os.makedirs(directory, exist_ok=True)
+    for entry in resources.iterdir():
+        target = os.path.join(directory, entry.name)
+        if entry.is_dir():
+            _copy_resources(entry, target)
+        else:
+            with open(target, 'wb') as f:
+                f.write(entry.read_bytes())
@@ -2,9 +2,9 @@
 from __future__ import annotations
 
 from abc import abstractmethod
-from typing import List, Callable, Type, Optional, Union, TypeVar, final, Tuple, Any, ContextManager
+from typing import List, Callable, Type, Optional, Union, TypeVar, final, Tuple, Any, ContextManager, Dict, Sequence, Iterator
 
-from schema import Schema
+from schema import Schema, SchemaError
 
 from configcrunch import variable_helper
 
@@ -15,13 +15,86 @@ T = TypeVar('T', bound=YamlConfigDocument)
 class ConfigcrunchError(Exception): ...
 class ReferencedDocumentNotFound(ConfigcrunchError): ...
 class CircularDependencyError(ConfigcrunchError): ...
-class VariableProcessingError(ConfigcrunchError): ...
+class VariableProcessingError(ConfigcrunchError):
+    errors: List[Tuple[str, Exception]]
 class InvalidDocumentError(ConfigcrunchError): ...
-class InvalidHeaderError(InvalidDocumentError):...
+class InvalidHeaderError(InvalidDocumentError):
+    suggestions: Dict[str, List[str]]
 class InvalidRemoveError(InvalidDocumentError): ...
+class LockViolationError(InvalidDocumentError): ...
+class DocumentLimitError(InvalidDocumentError): ...
+class DuplicateKeyError(InvalidDocumentError): ...
+class SignatureError(InvalidDocumentError): ...
+class PolicyViolationError(ConfigcrunchError):
+    violations: List[Tuple[str, str, str]]
+class ResolutionCancelledError(ConfigcrunchError): ...
+class EncryptionError(ConfigcrunchError): ...
+class UnknownKeyError(SchemaError):
+    suggestions: Dict[str, List[str]]
+class LayerValidationError(SchemaError): ...
+class MergedValidationError(SchemaError): ...
+class WorkspaceValidationError(SchemaError):
+    errors: List[Tuple[str, SchemaError]]
+class UnknownKeyWarning(UserWarning): ...
+class DuplicateKeyWarning(UserWarning): ...
+class CoercionWarning(UserWarning): ...
+class RuleViolationError(SchemaError):
+    rules: List[str]
+class DeprecatedKeyWarning(UserWarning): ...
 
 
-def load_multiple_yml(doc_type: Type[T], *in_args: str) -> T: ...
+def load_multiple_yml(doc_type: Type[T], *in_args: str, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> T: ...
+
+
+def load_from_manifest(doc_type: Type[T], manifest_path: str, variables: Optional[Dict[str, Any]] = None, *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> T: ...
+
+
+def parse_set_overrides(assignments: List[str]) -> Dict: ...
+
+
+def register_template(name: str, template: Union[dict, 'YamlConfigDocument']) -> None: ...
+
+
+def unregister_template(name: str) -> bool: ...
+
+
+def register_document(name: str, document: Union[str, dict, 'YamlConfigDocument']) -> None: ...
+
+
+def unregister_document(name: str) -> bool: ...
+
+
+def register_schema(name: str, schema: Union[Schema, Type['YamlConfigDocument'], Any]) -> None: ...
+
+
+def unregister_schema(name: str) -> bool: ...
+
+
+def register_directive(name: str, handler: Callable[[Any, str, 'YamlConfigDocument'], Any]) -> None: ...
+
+
+def unregister_directive(name: str) -> bool: ...
+
+
+def register_policy(name: str, rule: Union[str, Callable[['YamlConfigDocument'], Union[None, bool, str, List[str]]]], doc_type: Optional[Type['YamlConfigDocument']] = None) -> None: ...
+
+
+def unregister_policy(name: str) -> bool: ...
+
+
+def register_transformer(name: str, transformer: Callable[[dict, str], Optional[dict]], order: int = 0, enabled: bool = True) -> None: ...
+
+
+def unregister_transformer(name: str) -> bool: ...
+
+
+def set_transformer_enabled(name: str, enabled: bool) -> bool: ...
+
+
+def register_key_provider(provider: Any) -> None: ...
+
+
+def unregister_key_provider() -> bool: ...
 
 
 class YamlConfigDocument:
@@ -42,29 +115,63 @@ class YamlConfigDocument:
 
     @classmethod
     @final
-    def from_yaml(cls, path_to_yaml: str) -> YamlConfigDocument: ...
+    def from_yaml(cls, path_to_yaml: str, *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> YamlConfigDocument: ...
     @classmethod
     @final
     def from_dict(cls, dict: dict) -> YamlConfigDocument: ...
+    @classmethod
+    @final
+    def from_msgpack(cls, data: bytes) -> YamlConfigDocument: ...
+    @classmethod
+    @final
+    def documents_from_dicts(cls, dicts: List[dict], lookup_paths: Optional[List[Union[str, Dict[str, str]]]] = None, *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> List[YamlConfigDocument]: ...
     @final
     def freeze(self): ...
     @classmethod
     @abstractmethod
     def header(cls) -> str: ...
     @classmethod
-    @abstractmethod
+    def header_aliases(cls) -> List[str]: ...
+    @classmethod
+    def allow_headerless(cls) -> bool: ...
+    @classmethod
     def schema(cls) -> Schema: ...
     @classmethod
-    @abstractmethod
     def subdocuments(cls) -> List[Tuple[str, Type[YamlConfigDocument]]]: ...
-    def validate(self) -> bool: ...
+    @classmethod
+    def metadata_keys(cls) -> List[str]: ...
+    @classmethod
+    def key_order(cls) -> List[str]: ...
+    @classmethod
+    def secret_paths(cls) -> List[str]: ...
+    @classmethod
+    def merge_strategies(cls) -> Dict[str, str]: ...
+    @classmethod
+    def validation_rules(cls) -> Dict[str, Union[str, Callable[[Dict], bool]]]: ...
+    @classmethod
+    def unknown_keys(cls) -> Optional[str]: ...
+    @classmethod
+    def typed_schema(cls) -> Optional[TypedSchema]: ...
+    def validate(self, unknown_keys: Optional[str] = None) -> bool: ...
     @final
-    def resolve_and_merge_references(self, lookup_paths: List[str]) -> YamlConfigDocument: ...
+    def resolve_and_merge_references(self, lookup_paths: List[Union[str, Dict[str, str]]], *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> YamlConfigDocument: ...
+    def overlay(self, layer: Union[Dict, YamlConfigDocument], *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> YamlConfigDocument: ...
+    @classmethod
+    def compose(cls, layers: List[Union[Dict, YamlConfigDocument]], *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> YamlConfigDocument: ...
+    def lint_override(self, layer: Union[Dict, YamlConfigDocument], later_layers: Optional[List[Union[Dict, YamlConfigDocument]]] = None) -> List[Tuple[str, str, str]]: ...
+    @final
+    def extract_override(self, target: Union[Dict, YamlConfigDocument]) -> Dict: ...
     @final
-    def process_vars(self) -> YamlConfigDocument: ...
+    def process_vars(self, *, compose_interpolation: bool = False, inherit_vars: bool = False, values: Optional[List[Union[str, Dict]]] = None, globals: Optional[Dict] = None, inherit_helpers: bool = True, collect_errors: bool = False, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, audit: bool = False, safe: bool = False) -> YamlConfigDocument: ...
     def process_vars_for(self, target: str, additional_helpers: List[Callable] = None) -> str: ...
+    def process_vars_at(self, path: str) -> Any: ...
+    def template_audit_log(self) -> List[TemplateAuditEntry]: ...
+    def merge_stats(self) -> MergeStats: ...
+    def view(self) -> DocumentView: ...
     @variable_helper
     def parent(self) -> Optional[YamlConfigDocument]: ...
+    @variable_helper
+    def path_in_parent(self) -> Optional[str]: ...
     def __repr__(self) -> str: ...
     def __str__(self): ...
     def error_str(self) -> str: ...
@@ -75,7 +182,27 @@ class YamlConfigDocument:
     def __iter__(self): ...
     def items(self): ...
     def to_dict(self): ...
+    def to_msgpack(self) -> bytes: ...
+    def normalize(self) -> str: ...
+    def render_annotated_yaml(self, relative_to: Optional[str] = None) -> str: ...
+    def to_yaml(self, redact: bool = True, float_precision: Optional[int] = None) -> str: ...
+    def to_json(self, redact: bool = True, float_precision: Optional[int] = None) -> str: ...
+    @property
+    def merge_sources(self) -> List[Tuple[str, str]]: ...
+    def render_reference_graph(self, format: str = "dot") -> str: ...
+    def reference_graph(self) -> Dict[str, List[Dict[str, Any]]]: ...
+    def affected_paths(self, changed_file: str) -> List[str]: ...
+    def source_file(self, path: str) -> Optional[str]: ...
+    def origins(self, lines: bool = False, *, virtual_files: Optional[Dict[str, str]] = None) -> Dict[str, Union[None, str, Tuple[str, Optional[int]]]]: ...
+    def write_back(self, path: str, value: Any) -> str: ...
+    def walk(self, callback: Callable[[WalkNode], Optional[bool]]) -> None: ...
+    def check_policies(self) -> List[Tuple[str, str, str]]: ...
+    def subdocument_items(self) -> List[Tuple[str, YamlConfigDocument]]: ...
+    def reorder(self, path: str, key_order: List[str]) -> YamlConfigDocument: ...
+    def to_env(self, prefix: Optional[str] = None, separator: str = "__", float_precision: Optional[int] = None, redact: bool = True) -> Dict[str, str]: ...
+    def write_env_file(self, path: str, prefix: Optional[str] = None, separator: str = "__", float_precision: Optional[int] = None, redact: bool = True): ...
     def internal_get(self, key: str) -> Any: ...
+    def internal_get_lazy(self, key: str) -> Any: ...
     def internal_set(self, key: str, val: Any): ...
     def internal_contains(self, key: str) -> bool: ...
     def internal_delete(self, key: str): ...
@@ -122,9 +249,176 @@ class YamlConfigDocument:
         ...
 
 
+class YcdList(Sequence[Any]):
+    def __len__(self) -> int: ...
+    def __getitem__(self, index: Union[int, slice]) -> Any: ...
+    def __iter__(self) -> Iterator[Any]: ...
+    def __contains__(self, value: Any) -> bool: ...
+    def count(self, value: Any) -> int: ...
+    def index(self, value: Any) -> int: ...
+
+
 class DocReference:
-    def __init__(self, referenced_doc_type: Type[YamlConfigDocument]): ...
+    referenced_type: Type[YamlConfigDocument]
+    container: Optional[str]
+    def __init__(self, referenced_doc_type: Type[YamlConfigDocument], container: Optional[str] = None): ...
+    @staticmethod
+    def list_of(referenced_doc_type: Type[YamlConfigDocument]) -> DocReference: ...
+    @staticmethod
+    def map_of(referenced_doc_type: Type[YamlConfigDocument]) -> DocReference: ...
     def validate(self, data): ...
 
 
+class DirectivePolicy:
+    prefix: str
+    disabled: List[str]
+    allow_exec: bool
+    def __init__(self, prefix: str = "$", disabled: Optional[List[str]] = None, allow_exec: bool = False): ...
+
+
+class CancellationToken:
+    cancelled: bool
+    def __init__(self): ...
+    def cancel(self) -> None: ...
+
+
+class WalkNode:
+    event: str
+    path: str
+    type: str
+    value: Any
+    files: List[str]
+
+
+class TemplateAuditEntry:
+    path: str
+    template: str
+    result: Any
+    variables: Dict[str, Any]
+    helpers: List[str]
+
+
+class MergeStats:
+    documents_loaded: int
+    refs_resolved: int
+    keys_overridden: int
+    keys_removed: int
+    list_entries_merged: int
+    templates_rendered: int
+    cache_hits: int
+    def to_dict(self) -> Dict[str, int]: ...
+
+
+class DocumentView:
+    path: str
+    document: YamlConfigDocument
+    modified: bool
+    def __getitem__(self, key: Union[str, int]) -> Any: ...
+    def __setitem__(self, key: Union[str, int], value: Any) -> None: ...
+    def __delitem__(self, key: Union[str, int]) -> None: ...
+    def __len__(self) -> int: ...
+    def __contains__(self, value: Any) -> bool: ...
+    def __iter__(self) -> Iterator[Any]: ...
+    def keys(self) -> List[str]: ...
+    def get(self, key: Union[str, int], default: Any = None) -> Any: ...
+    def to_dict(self) -> Union[Dict, List]: ...
+
+
+class EncryptedValue:
+    value: str
+    ciphertext: str
+    decrypted: bool
+    def __init__(self, plaintext: str): ...
+    @staticmethod
+    def from_ciphertext(ciphertext: str) -> EncryptedValue: ...
+
+
+class ResolutionContext:
+    lookup_paths: List[Union[str, Dict[str, str]]]
+    globals: Dict
+    def __init__(self, lookup_paths: List[Union[str, Dict[str, str]]], globals: Optional[Dict] = None): ...
+    def register_template(self, name: str, template: Union[dict, YamlConfigDocument]) -> None: ...
+    def unregister_template(self, name: str) -> bool: ...
+    def register_document(self, name: str, document: Union[str, dict, YamlConfigDocument]) -> None: ...
+    def unregister_document(self, name: str) -> bool: ...
+    def load(self, doc_type: Type[T], *in_args: str, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, features: Optional[List[str]] = None) -> T: ...
+    def resolve(self, document: T, *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, features: Optional[List[str]] = None) -> T: ...
+    def process_vars(self, document: T, *, compose_interpolation: bool = False, inherit_vars: bool = False, values: Optional[List[Union[str, Dict]]] = None, globals: Optional[Dict] = None, inherit_helpers: bool = True, collect_errors: bool = False, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, audit: bool = False, safe: bool = False) -> T: ...
+
+
+class Workspace:
+    documents: Dict[str, YamlConfigDocument]
+    @staticmethod
+    def load(documents: Dict[str, Tuple[Type[YamlConfigDocument], Union[str, List[str]]]], lookup_paths: Optional[List[Union[str, Dict[str, str]]]] = None, process_vars: bool = True, validate: bool = True, *, ini_infer_types: bool = True, directive_policy: Optional[DirectivePolicy] = None, lock_violation: str = 'error', profile: Optional[str] = None, context: Optional[ResolutionContext] = None, globals: Optional[Dict] = None, env_interpolation: bool = False, core_schema: bool = False, decimal_floats: bool = False, duplicate_keys: str = 'ignore', report_coercions: bool = False, validation: Optional[str] = None, max_file_size: Optional[int] = None, max_depth: Optional[int] = None, max_nodes: Optional[int] = None, virtual_files: Optional[Dict[str, str]] = None, cache_dir: Optional[str] = None, trusted_keys: Optional[Dict[str, str]] = None, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, safe: bool = False, identity_map: bool = False, features: Optional[List[str]] = None) -> Workspace: ...
+    def process_vars(self, *, compose_interpolation: bool = False, inherit_vars: bool = False, values: Optional[List[Union[str, Dict]]] = None, globals: Optional[Dict] = None, inherit_helpers: bool = True, collect_errors: bool = False, timeout: Optional[float] = None, cancel_token: Optional[CancellationToken] = None, progress: Optional[Callable[[str, int, Optional[str]], Any]] = None, audit: bool = False, safe: bool = False) -> None: ...
+    def validate(self) -> bool: ...
+    def files(self) -> List[str]: ...
+    def affected_paths(self, changed_file: str) -> Dict[str, List[str]]: ...
+    def reference_graph(self) -> Dict[str, List[Dict[str, Any]]]: ...
+    def render_reference_graph(self, format: str = "dot") -> str: ...
+    def __getitem__(self, name: str) -> YamlConfigDocument: ...
+    def __contains__(self, name: str) -> bool: ...
+    def __len__(self) -> int: ...
+
+class OneOf:
+    def __init__(self, *choices: Any): ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class Range:
+    min: Optional[Union[int, float]]
+    max: Optional[Union[int, float]]
+    def __init__(self, min: Optional[Union[int, float]] = None, max: Optional[Union[int, float]] = None): ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class Pattern:
+    pattern: str
+    def __init__(self, pattern: str): ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class Coerce:
+    to: str
+    def __init__(self, to: str): ...
+    def coerce(self, data: Any) -> Any: ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class Secret:
+    schema: Optional[Any]
+    def __init__(self, schema: Optional[Any] = None): ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class UniqueList:
+    schema: Optional[Any]
+    def __init__(self, schema: Optional[Any] = None): ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class KeyedList:
+    key: str
+    schema: Optional[Any]
+    def __init__(self, key: str, schema: Optional[Any] = None): ...
+    def validate(self, data: Any) -> Any: ...
+
+
+class TypedSchema:
+    allow_unknown: bool
+    def __init__(self, spec: Dict[str, Any], allow_unknown: bool = False): ...
+
+
+class Field:
+    required: bool
+    def __init__(self, spec: Any, required: bool = True): ...
+
+
+class Length:
+    min: Optional[int]
+    max: Optional[int]
+    def __init__(self, min: Optional[int] = None, max: Optional[int] = None): ...
+    def validate(self, data: Any) -> Any: ...
+
+
 def _test__subdoc_specs(path: str, type: Type[Any], input: dict) -> Tuple[dict, str, Any, bool, Type[Any]]: ...
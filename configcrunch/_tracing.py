@@ -0,0 +1,43 @@
+"""
+Optional OpenTelemetry integration. Spans are created from Rust code (see tracing.rs)
+for loading, merging and variable processing, if tracing was enabled via ``enable_tracing``.
+"""
+from contextlib import nullcontext
+
+from configcrunch._main import set_tracing_enabled
+
+_tracer = None
+
+
+def enable_tracing(tracer_provider=None):
+    """
+    Enables emitting OpenTelemetry spans for loading files, resolving references and processing variables.
+    Requires the ``opentelemetry-api`` package. If no tracer provider is given, the global one is used.
+    """
+    from opentelemetry import trace
+    _set_tracer(trace.get_tracer("configcrunch", tracer_provider=tracer_provider))
+
+
+def disable_tracing():
+    """Disables emitting OpenTelemetry spans again."""
+    _set_tracer(None)
+
+
+def _set_tracer(tracer):
+    global _tracer
+    # The Rust code only asks is_enabled, if tracing was enabled.
+    if (tracer is None) != (_tracer is None):
+        set_tracing_enabled(tracer is not None)
+    _tracer = tracer
+
+
+# Called from Rust code.
+def is_enabled() -> bool:
+    return _tracer is not None
+
+
+# Called from Rust code.
+def span(name: str, attributes: dict):
+    if _tracer is None:
+        return nullcontext()
+    return _tracer.start_as_current_span(name, attributes=attributes)
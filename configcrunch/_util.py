@@ -1,3 +1,5 @@
+import weakref
+
 from configcrunch import YamlConfigDocument
 
 
@@ -21,3 +23,387 @@ def frozen_ycd_to_dict_rec(input):
             new_list.append(frozen_ycd_to_dict_rec(item))
         return new_list
     return input
+
+
+# Called from Rust code (YamlConfigDocument::validate).
+def find_unknown_keys(schema, data, path=()):
+    """
+    Returns the paths (tuples of keys) of all keys in data, that are not part of the schema.
+    Only literal keys (strings, optionally wrapped in Optional etc.) are checked. If the schema
+    of a dict contains a non-literal key (eg. ``str``), all keys of that dict are considered known.
+    """
+    from schema import Schema
+    if isinstance(schema, Schema):
+        schema = schema.schema
+    if not isinstance(schema, dict) or not isinstance(data, dict):
+        return []
+    literal_keys, other_keys = _split_schema_keys(schema)
+    unknown = []
+    for key, value in data.items():
+        if key in literal_keys:
+            unknown += find_unknown_keys(literal_keys[key], value, path + (key,))
+        elif len(other_keys) == 0:
+            unknown.append(path + (key,))
+    return unknown
+
+
+def _implements(cls, name):
+    """Whether the document type (or one of its bases) implements the class method name of YamlConfigDocument."""
+    return any(name in c.__dict__ for c in cls.__mro__[:cls.__mro__.index(YamlConfigDocument)])
+
+
+# Called from Rust code (schema::schema_of).
+def implemented_schema(cls):
+    """
+    Returns the schema of the document type, or None, if it has a typed schema and doesn't implement
+    one (types with a typed schema don't need to).
+    """
+    if not _implements(cls, 'schema') and cls.typed_schema() is not None:
+        return None
+    return cls.schema()
+
+
+# Called from Rust code (suggestions::invalid_header_error).
+def document_headers():
+    """Returns a dict of the headers of all (imported) document types to the names of the types."""
+    headers = {}
+    pending = list(YamlConfigDocument.__subclasses__())
+    while pending:
+        cls = pending.pop(0)
+        pending += cls.__subclasses__()
+        # Skip types that don't implement header (e.g. abstract base classes).
+        if not _implements(cls, 'header'):
+            continue
+        try:
+            headers.setdefault(cls.header(), cls.__name__)
+        except Exception:
+            continue
+    return headers
+
+
+# Called from Rust code (YamlConfigDocument::validate).
+def schema_keys_at(schema, path):
+    """
+    Returns the literal keys of the dict schema at path (a tuple of literal keys, like the parent of a
+    path returned by find_unknown_keys).
+    """
+    from schema import Schema
+    for key in path:
+        if isinstance(schema, Schema):
+            schema = schema.schema
+        if not isinstance(schema, dict):
+            return []
+        schema = _split_schema_keys(schema)[0].get(key)
+    if isinstance(schema, Schema):
+        schema = schema.schema
+    if not isinstance(schema, dict):
+        return []
+    return list(_split_schema_keys(schema)[0].keys())
+
+
+def _split_schema_keys(schema_dict):
+    """
+    Splits the keys of a dict schema into literal keys (key -> value schema) and all other keys
+    (a list of (key schema, value schema) tuples).
+    """
+    from schema import Schema, Literal
+    literal_keys = {}
+    other_keys = []
+    for key, value in schema_dict.items():
+        if isinstance(key, Schema):
+            key = key.schema
+        if isinstance(key, Literal):
+            key = key.schema
+        if isinstance(key, str):
+            literal_keys[key] = value
+        else:
+            other_keys.append((key, value))
+    return literal_keys, other_keys
+
+
+# Called from Rust code (YamlConfigDocument::validate).
+def apply_coercions(schema, data):
+    """
+    Returns a copy of data, where all values with a Coerce schema are coerced.
+    Coerce schemas are found in dicts (with literal keys or type keys, like ``str``),
+    single element lists and ``And``.
+    """
+    from schema import Schema, And
+    from configcrunch._main import Coerce
+    if isinstance(schema, Coerce):
+        return schema.coerce(data)
+    if isinstance(schema, And):
+        for sub_schema in schema.args:
+            data = apply_coercions(sub_schema, data)
+        return data
+    if isinstance(schema, Schema):
+        schema = schema.schema
+    if isinstance(schema, list) and len(schema) == 1 and isinstance(data, list):
+        return [apply_coercions(schema[0], value) for value in data]
+    if isinstance(schema, dict) and isinstance(data, dict):
+        literal_keys, other_keys = _split_schema_keys(schema)
+        data = data.copy()
+        for key, value in data.items():
+            if key in literal_keys:
+                data[key] = apply_coercions(literal_keys[key], value)
+            else:
+                for key_schema, value_schema in other_keys:
+                    if isinstance(key_schema, type) and isinstance(key, key_schema):
+                        data[key] = apply_coercions(value_schema, value)
+                        break
+    return data
+
+
+# Called from Rust code (redaction::redact_error).
+def redact_error(error, secrets, replacement):
+    """
+    Replaces the secrets (their str and repr) in the message of the error and its causes with replacement.
+    The error is changed in place, so its type and attributes are kept.
+    """
+    texts = set()
+    for secret in secrets:
+        texts.update((str(secret), repr(secret)))
+    texts = sorted((text for text in texts if text != ''), key=len, reverse=True)
+
+    def redact(value):
+        if isinstance(value, str):
+            for text in texts:
+                value = value.replace(text, replacement)
+            return value
+        if isinstance(value, (list, tuple)):
+            return type(value)(redact(v) for v in value)
+        return value
+
+    seen = set()
+    while error is not None and id(error) not in seen:
+        seen.add(id(error))
+        error.args = redact(error.args)
+        for attr in ('autos', 'errors', 'violations'):
+            if isinstance(getattr(error, attr, None), list):
+                setattr(error, attr, redact(getattr(error, attr)))
+        error = error.__cause__ or error.__context__
+
+
+# Called from Rust code (redaction::own_secret_patterns).
+def find_secret_paths(schema, data, path=()):
+    """
+    Returns the paths (tuples of keys and list indices) of all values in data, that have a Secret schema.
+    Secret schemas are found like Coerce schemas (see apply_coercions).
+    """
+    from schema import Schema, And
+    from configcrunch._main import Secret
+    if isinstance(schema, Secret):
+        return [path]
+    if isinstance(schema, And):
+        paths = []
+        for sub_schema in schema.args:
+            paths += find_secret_paths(sub_schema, data, path)
+        return paths
+    if isinstance(schema, Schema):
+        schema = schema.schema
+    if isinstance(schema, list) and len(schema) == 1 and isinstance(data, list):
+        paths = []
+        for i, value in enumerate(data):
+            paths += find_secret_paths(schema[0], value, path + (i,))
+        return paths
+    paths = []
+    if isinstance(schema, dict) and isinstance(data, dict):
+        literal_keys, other_keys = _split_schema_keys(schema)
+        for key, value in data.items():
+            if key in literal_keys:
+                paths += find_secret_paths(literal_keys[key], value, path + (key,))
+            else:
+                for key_schema, value_schema in other_keys:
+                    if isinstance(key_schema, type) and isinstance(key, key_schema):
+                        paths += find_secret_paths(value_schema, value, path + (key,))
+                        break
+    return paths
+
+
+_default_merge_strategies = weakref.WeakKeyDictionary()
+
+
+# Called from Rust code (YamlConfigDocument::merge_strategies).
+def default_merge_strategies(cls):
+    """
+    Returns the merge strategies taken from the schema of the document type (see find_merge_strategies),
+    or no strategies, if the type doesn't implement a schema. They are derived once per type.
+    """
+    if cls not in _default_merge_strategies:
+        if _implements(cls, 'schema'):
+            _default_merge_strategies[cls] = find_merge_strategies(cls.schema())
+        else:
+            _default_merge_strategies[cls] = {}
+    return dict(_default_merge_strategies[cls])
+
+
+def find_merge_strategies(schema, path=()):
+    """
+    Returns the merge strategies (path pattern -> strategy, see YamlConfigDocument.merge_strategies) for all
+    UniqueList and KeyedList schemas. They are found in dicts (list entries and values of dicts with
+    non-literal keys match any key, ``*``), single element lists, ``And`` and in the schemas of
+    UniqueLists and KeyedLists.
+    """
+    from schema import Schema, And
+    from configcrunch._main import UniqueList, KeyedList
+    strategies = {}
+    if isinstance(schema, (UniqueList, KeyedList)):
+        if isinstance(schema, UniqueList):
+            strategies['.'.join(path)] = 'set'
+        else:
+            strategies['.'.join(path)] = 'keyed:' + schema.key
+        if schema.schema is not None:
+            strategies.update(find_merge_strategies(schema.schema, path + ('*',)))
+        return strategies
+    if isinstance(schema, And):
+        for sub_schema in schema.args:
+            strategies.update(find_merge_strategies(sub_schema, path))
+        return strategies
+    if isinstance(schema, Schema):
+        schema = schema.schema
+    if isinstance(schema, list) and len(schema) == 1:
+        return find_merge_strategies(schema[0], path + ('*',))
+    if isinstance(schema, dict):
+        literal_keys, other_keys = _split_schema_keys(schema)
+        for key, value in literal_keys.items():
+            strategies.update(find_merge_strategies(value, path + (key,)))
+        for _, value in other_keys:
+            strategies.update(find_merge_strategies(value, path + ('*',)))
+    return strategies
+
+
+# Called from Rust code (validation::validate_layer).
+def partial_schema(schema):
+    """
+    Returns a copy of the schema, in which all literal keys of dicts are optional, so it can validate
+    layers that only contain some of the keys. Applies to dicts in single element lists, ``And``, ``Or``,
+    the schemas of constraints (e.g. ``Secret``) and to the schemas of sub-documents (DocReference).
+    """
+    import copy
+    from schema import Schema, And, Or, Optional, Literal
+    from configcrunch._main import DocReference, Secret, UniqueList, KeyedList
+    if isinstance(schema, DocReference):
+        return _PartialDocReference(schema)
+    if isinstance(schema, Secret):
+        return Secret(None if schema.schema is None else partial_schema(schema.schema))
+    if isinstance(schema, UniqueList):
+        return UniqueList(None if schema.schema is None else partial_schema(schema.schema))
+    if isinstance(schema, KeyedList):
+        return KeyedList(schema.key, None if schema.schema is None else partial_schema(schema.schema))
+    if isinstance(schema, (And, Or)):
+        schema = copy.copy(schema)
+        schema._args = tuple(partial_schema(sub_schema) for sub_schema in schema.args)
+        return schema
+    if type(schema) is Schema:
+        return Schema(partial_schema(schema.schema), ignore_extra_keys=schema.ignore_extra_keys)
+    if isinstance(schema, list):
+        return [partial_schema(sub_schema) for sub_schema in schema]
+    if isinstance(schema, dict):
+        partial = {}
+        for key, value in schema.items():
+            if isinstance(key, str) or type(key) is Literal:
+                key = Optional(key)
+            partial[key] = partial_schema(value)
+        return partial
+    return schema
+
+
+class _PartialDocReference:
+    """Validates sub-documents of a layer with the partial schema of their type (see partial_schema)."""
+    def __init__(self, reference):
+        self.reference = reference
+
+    def validate(self, data):
+        from schema import Schema, SchemaError
+        # Types with only a typed schema are checked after merging.
+        referenced = implemented_schema(self.reference.referenced_type)
+        schema = Schema(object if referenced is None else partial_schema(referenced))
+        if self.reference.container == 'list':
+            if not isinstance(data, list):
+                raise SchemaError(f"Expected a list of documents, got: {data!r}")
+            return [schema.validate(entry) for entry in data]
+        if self.reference.container == 'map':
+            if not isinstance(data, dict):
+                raise SchemaError(f"Expected a dict of documents, got: {data!r}")
+            return {key: schema.validate(entry) for key, entry in data.items()}
+        return schema.validate(data)
+
+
+# Called from Rust code (YamlConfigDocument::subdocuments).
+def find_subdocument_specs(schema, path=''):
+    """
+    Returns the sub-document specifications (see YamlConfigDocument.subdocuments) for all
+    DocReferences in the schema. DocReferences are found in dicts with literal keys, single element
+    lists, as values of dicts with non-literal keys and in ``And``.
+    """
+    from schema import Schema, And
+    from configcrunch._main import DocReference
+    if isinstance(schema, DocReference):
+        if path == '':
+            return []
+        suffix = {None: '', 'list': '[list]', 'map': '[map]'}[schema.container]
+        return [(path + suffix, schema.referenced_type)]
+    if isinstance(schema, And):
+        specs = []
+        for sub_schema in schema.args:
+            specs += find_subdocument_specs(sub_schema, path)
+        return specs
+    if isinstance(schema, Schema):
+        schema = schema.schema
+    if isinstance(schema, list) and len(schema) == 1:
+        return _find_subdocument_specs_in_container(schema[0], path)
+    specs = []
+    if isinstance(schema, dict):
+        literal_keys, other_keys = _split_schema_keys(schema)
+        for key, value in literal_keys.items():
+            # The dicts on the path may be missing (see YamlConfigDocument.subdocuments).
+            specs += find_subdocument_specs(value, path + '?/' + key if path else key)
+        for _, value in other_keys:
+            specs += _find_subdocument_specs_in_container(value, path)
+    return specs
+
+
+def _find_subdocument_specs_in_container(schema, path):
+    """Specifications for the entries of a list or dict at path, if they are sub-documents."""
+    from schema import And
+    from configcrunch._main import DocReference
+    if isinstance(schema, And):
+        specs = []
+        for sub_schema in schema.args:
+            specs += _find_subdocument_specs_in_container(sub_schema, path)
+        return specs
+    if isinstance(schema, DocReference) and schema.container is None and path != '':
+        return [(path + '[]', schema.referenced_type)]
+    return []
+
+
+# Called from Rust code (YamlConfigDocument::validate).
+def without_keys(data, paths):
+    """Returns a copy of data, without the keys at the given paths."""
+    data = data.copy()
+    by_first_key = {}
+    for path in paths:
+        by_first_key.setdefault(path[0], []).append(path[1:])
+    for key, sub_paths in by_first_key.items():
+        if any(len(p) == 0 for p in sub_paths):
+            del data[key]
+        else:
+            data[key] = without_keys(data[key], sub_paths)
+    return data
+
+
+# Called from Rust code (YamlConfigDocument::to_json).
+def json_default(value):
+    """Writes encrypted values with their ciphertext (``{"$encrypted": <ciphertext>}``)."""
+    from configcrunch import EncryptedValue, ENCRYPTED
+    if isinstance(value, EncryptedValue):
+        return {ENCRYPTED: value.ciphertext}
+    raise TypeError(f"Object of type {type(value).__name__} is not JSON serializable")
+
+
+# Called from Rust code (lookup::select_lookup_paths).
+def lookup_path_globals():
+    """The variables available in the conditions of lookup paths, besides the globals passed."""
+    import socket
+    import sys
+    return {'platform': sys.platform, 'hostname': socket.gethostname()}
@@ -0,0 +1,173 @@
+"""
+Helpers for testing projects that use configcrunch, usable with pytest and unittest.
+Failed assertions raise ``AssertionError``.
+
+Snapshots are stored in the canonical form of :meth:`YamlConfigDocument.normalize`. Missing or outdated
+snapshots are (re-)written instead of compared, if the environment variable ``CONFIGCRUNCH_UPDATE_SNAPSHOTS``
+is set to ``1``. Comparing snapshots requires the ``PyYAML`` package (install ``configcrunch[testing]``)::
+
+    from configcrunch.testing import resolve_fixture, assert_matches_snapshot, assert_path_equals
+
+    def test_service():
+        doc = resolve_fixture(Project, 'fixtures/project.yml', ['fixtures/repo'])
+        assert_matches_snapshot(doc, 'snapshots/project.yml')
+        assert_path_equals(doc, 'app.services.web.port', 8080)
+"""
+import os
+from typing import Any, List, Optional, Sequence, Type
+
+from configcrunch import YamlConfigDocument
+
+UPDATE_SNAPSHOTS_ENV = "CONFIGCRUNCH_UPDATE_SNAPSHOTS"
+
+_MISSING = object()
+
+
+def resolve_fixture(
+        doc_type: Type[YamlConfigDocument], path: str, lookup_paths: Sequence[str] = (),
+        process_vars: bool = True, **kwargs
+) -> YamlConfigDocument:
+    """
+    Loads the document at path, resolves its references using the given lookup paths and processes its variables
+    (unless ``process_vars`` is False). Relative paths are relative to the current working directory.
+    Additional keyword arguments are passed to ``from_yaml`` and ``resolve_and_merge_references``
+    as loading options.
+    """
+    doc = doc_type.from_yaml(os.path.abspath(path), **kwargs)
+    doc.resolve_and_merge_references([os.path.abspath(p) for p in lookup_paths], **kwargs)
+    if process_vars:
+        doc.process_vars()
+    return doc
+
+
+def structural_diff(expected: Any, actual: Any, path: str = "") -> List[str]:
+    """
+    Returns the differences between two nested structures of dicts, lists and scalars, one line
+    per difference, prefixed with the path of the difference (eg. ``services.web.ports.0``).
+    Returns an empty list, if both are equal.
+    """
+    if isinstance(expected, dict) and isinstance(actual, dict):
+        diff = []
+        for key in sorted(set(expected) | set(actual), key=str):
+            sub_path = _join_path(path, key)
+            if key not in actual:
+                diff.append(f"{sub_path}: missing (expected {expected[key]!r})")
+            elif key not in expected:
+                diff.append(f"{sub_path}: unexpected (got {actual[key]!r})")
+            else:
+                diff += structural_diff(expected[key], actual[key], sub_path)
+        return diff
+    if isinstance(expected, list) and isinstance(actual, list):
+        diff = []
+        for i in range(max(len(expected), len(actual))):
+            sub_path = _join_path(path, i)
+            if i >= len(actual):
+                diff.append(f"{sub_path}: missing (expected {expected[i]!r})")
+            elif i >= len(expected):
+                diff.append(f"{sub_path}: unexpected (got {actual[i]!r})")
+            else:
+                diff += structural_diff(expected[i], actual[i], sub_path)
+        return diff
+    if type(expected) != type(actual) or expected != actual:
+        return [f"{path or '<root>'}: expected {expected!r}, got {actual!r}"]
+    return []
+
+
+def assert_matches_snapshot(doc: YamlConfigDocument, snapshot_path: str, update: Optional[bool] = None):
+    """
+    Asserts that the document matches the snapshot stored at snapshot_path. On mismatch, the
+    error message lists the differing paths.
+
+    If ``update`` is True (default: whether ``CONFIGCRUNCH_UPDATE_SNAPSHOTS`` is set to ``1``),
+    the snapshot is written instead, if it is missing or doesn't match.
+    """
+    try:
+        import yaml
+    except ImportError as e:
+        raise ImportError("Comparing snapshots requires PyYAML, install configcrunch[testing].") from e
+    if update is None:
+        update = os.environ.get(UPDATE_SNAPSHOTS_ENV) == "1"
+    actual = doc.normalize()
+    if os.path.exists(snapshot_path):
+        with open(snapshot_path, 'r') as f:
+            expected = f.read()
+        if expected == actual:
+            return
+        if not update:
+            diff = structural_diff(yaml.safe_load(expected), yaml.safe_load(actual))
+            if len(diff) == 0:
+                # Only the formatting differs, eg. because the snapshot was edited by hand.
+                diff = ["<formatting>: the snapshot is not in the form written by normalize()"]
+            raise AssertionError(
+                f"Document doesn't match the snapshot {snapshot_path}:\n  " + "\n  ".join(diff) +
+                f"\nSet {UPDATE_SNAPSHOTS_ENV}=1 to update the snapshot."
+            )
+    elif not update:
+        raise AssertionError(
+            f"Snapshot {snapshot_path} does not exist. Set {UPDATE_SNAPSHOTS_ENV}=1 to create it."
+        )
+    snapshot_dir = os.path.dirname(snapshot_path)
+    if snapshot_dir != "":
+        os.makedirs(snapshot_dir, exist_ok=True)
+    with open(snapshot_path, 'w') as f:
+        f.write(actual)
+
+
+def get_path(doc: YamlConfigDocument, path: str) -> Any:
+    """
+    Returns the value at path in the body of the document, as plain Python values (like ``to_dict``).
+    The parts of path are separated by ".", list entries are addressed by their index (``ports.0``).
+    Raises a KeyError if the path doesn't exist.
+    """
+    value = _get_path(doc.to_dict()[doc.header()], path)
+    if value is _MISSING:
+        raise KeyError(f"Path not found: {path}")
+    return value
+
+
+def assert_path_equals(doc: YamlConfigDocument, path: str, expected: Any):
+    """
+    Asserts that the value at path in the body of the document (see :func:`get_path`) equals expected.
+    Nested dicts and lists are compared structurally.
+    """
+    actual = _get_path(doc.to_dict()[doc.header()], path)
+    if actual is _MISSING:
+        raise AssertionError(f"{path}: missing (expected {expected!r})")
+    diff = structural_diff(expected, actual, path)
+    if len(diff) > 0:
+        raise AssertionError("\n".join(diff))
+
+
+def assert_path_missing(doc: YamlConfigDocument, path: str):
+    """Asserts that there is no value at path in the body of the document (see :func:`get_path`)."""
+    actual = _get_path(doc.to_dict()[doc.header()], path)
+    if actual is not _MISSING:
+        raise AssertionError(f"{path}: unexpected (got {actual!r})")
+
+
+def _get_path(value, path: str):
+    for part in path.split("."):
+        if isinstance(value, dict) and part in value:
+            value = value[part]
+        elif isinstance(value, list) and part.isdigit() and int(part) < len(value):
+            value = value[int(part)]
+        else:
+            return _MISSING
+    return value
+
+
+def _join_path(path: str, key) -> str:
+    if path == "":
+        return str(key)
+    return f"{path}.{key}"
+
+
+__all__ = [
+    'UPDATE_SNAPSHOTS_ENV',
+    'resolve_fixture',
+    'structural_diff',
+    'assert_matches_snapshot',
+    'get_path',
+    'assert_path_equals',
+    'assert_path_missing',
+]
@@ -0,0 +1,48 @@
+import os
+
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class AffectedPathsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'affected_paths'
+
+    def setUp(self):
+        super().setUp()
+        self.doc = self.load_base('base.yml', ['repo'])
+
+    def test_base(self):
+        self.assertEqual(
+            ['level_direct.name', 'more.list', 'str_field'],
+            self.doc.affected_paths(self.fix_get_path('base.yml'))
+        )
+
+    def test_referenced(self):
+        self.assertEqual(
+            ['int_field', 'more.from_default', 'more.list', 'more.only_reference'],
+            self.doc.affected_paths(self.fix_get_path('repo/folder/reference.yml'))
+        )
+
+    def test_subdocument_reference(self):
+        self.assertEqual(
+            ['level_direct.more.x'],
+            self.doc.affected_paths(self.fix_get_path('repo/level.yml'))
+        )
+
+    def test_relative_path(self):
+        cwd = os.getcwd()
+        try:
+            os.chdir(self.fix_get_path('repo'))
+            self.assertEqual(['level_direct.more.x'], self.doc.affected_paths('level.yml'))
+        finally:
+            os.chdir(cwd)
+
+    def test_unrelated_file(self):
+        self.assertEqual([], self.doc.affected_paths(self.fix_get_path('unrelated.yml')))
+
+    def test_not_from_files(self):
+        doc = Base.from_dict({'str_field': 'x'})
+        doc.resolve_and_merge_references([])
+        self.assertEqual([], doc.affected_paths(self.fix_get_path('base.yml')))
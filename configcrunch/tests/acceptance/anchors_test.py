@@ -0,0 +1,23 @@
+from configcrunch import InvalidDocumentError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class AnchorsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'anchors'
+
+    def test_alias_across_files(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_undefined(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('undefined.yml', ['repo'])
+
+    def test_recursive(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('recursive.yml', ['repo'])
@@ -0,0 +1,29 @@
+import yaml
+
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class AnnotatedYamlTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'annotated_yaml'
+
+    def test_relative(self):
+        doc = self.load_base('base.yml', ['repo'])
+        with open(self.fix_get_path('expected.yml'), 'r') as f:
+            expected = f.read()
+        self.assertEqual(expected, doc.render_annotated_yaml(relative_to=self.fix_get_path('')))
+
+    def test_absolute(self):
+        doc = self.load_base('base.yml', ['repo'])
+        rendered = doc.render_annotated_yaml()
+        self.assertIn('str_field: "from base"  # ' + self.fix_get_path('base.yml'), rendered)
+
+    def test_same_content_as_normalize(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertEqual(yaml.safe_load(doc.normalize()), yaml.safe_load(doc.render_annotated_yaml()))
+
+    def test_not_loaded_from_file(self):
+        doc = Base.from_dict({'str_field': 'value', 'more': {'list': [1]}})
+        self.assertEqual(doc.normalize(), doc.render_annotated_yaml())
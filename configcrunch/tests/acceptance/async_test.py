@@ -0,0 +1,72 @@
+import asyncio
+from concurrent.futures import ThreadPoolExecutor
+
+from configcrunch import load_multiple_yml_async, resolve_async, CancellationToken, ResolutionCancelledError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class AsyncTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'async'
+
+    def test_load_and_resolve(self):
+        async def load():
+            doc = await load_multiple_yml_async(Base, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'))
+            return await resolve_async(doc, [self.fix_get_path('repo')])
+
+        doc = asyncio.run(load())
+        self.assertIsInstance(doc, Base)
+        self.assertEqual('from override', doc['str_field'])
+        self.assertEqual(12, doc['int_field'])
+
+    def test_concurrent(self):
+        async def load_all():
+            return await asyncio.gather(*[
+                load_multiple_yml_async(Base, self.fix_get_path('base.yml')) for _ in range(10)
+            ])
+
+        docs = asyncio.run(load_all())
+        self.assertEqual(['from base'] * 10, [doc['str_field'] for doc in docs])
+
+    def test_executor(self):
+        async def load(executor):
+            return await load_multiple_yml_async(Base, self.fix_get_path('base.yml'), executor=executor)
+
+        with ThreadPoolExecutor(max_workers=1) as executor:
+            self.assertEqual('from base', asyncio.run(load(executor))['str_field'])
+
+    def test_loading_options(self):
+        async def load():
+            await load_multiple_yml_async(Base, self.fix_get_path('base.yml'), timeout=0)
+
+        with self.assertRaises(ResolutionCancelledError):
+            asyncio.run(load())
+
+    def test_own_cancel_token(self):
+        token = CancellationToken()
+        token.cancel()
+
+        async def resolve():
+            doc = await load_multiple_yml_async(Base, self.fix_get_path('base.yml'))
+            await resolve_async(doc, [self.fix_get_path('repo')], cancel_token=token)
+
+        with self.assertRaises(ResolutionCancelledError):
+            asyncio.run(resolve())
+
+    def test_event_loop_not_blocked(self):
+        ticks = []
+
+        async def tick():
+            while True:
+                ticks.append(None)
+                await asyncio.sleep(0)
+
+        async def load():
+            ticker = asyncio.ensure_future(tick())
+            await load_multiple_yml_async(Base, self.fix_get_path('base.yml'))
+            ticker.cancel()
+
+        asyncio.run(load())
+        self.assertNotEqual([], ticks)
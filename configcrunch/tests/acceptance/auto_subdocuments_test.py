@@ -0,0 +1,81 @@
+from schema import Schema, Optional, And
+
+from configcrunch import YamlConfigDocument, DocReference
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class AutoLevel(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "level"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            Optional('$name'): str,
+            'name': str,
+            Optional('child'): DocReference(AutoLevel),
+        })
+
+
+class Auto(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "auto"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            Optional('direct'): DocReference(AutoLevel),
+            Optional('list'): [DocReference(AutoLevel)],
+            Optional('map'): {str: And(DocReference(AutoLevel))},
+            Optional('nested'): {
+                Optional('inner'): DocReference(AutoLevel),
+                Optional('levels'): DocReference.list_of(AutoLevel),
+            },
+            Optional('missing'): {
+                'inner': DocReference(AutoLevel),
+            },
+        })
+
+
+class Manual(Auto):
+    @classmethod
+    def subdocuments(cls):
+        return [("direct", AutoLevel)]
+
+
+class AutoSubdocumentsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'auto_subdocuments'
+
+    def test_specs(self):
+        self.assertEqual([
+            ('direct', AutoLevel),
+            ('list[]', AutoLevel),
+            ('map[]', AutoLevel),
+            ('nested?/inner', AutoLevel),
+            ('nested?/levels[list]', AutoLevel),
+            ('missing?/inner', AutoLevel),
+        ], Auto.subdocuments())
+        self.assertEqual([('child', AutoLevel)], AutoLevel.subdocuments())
+
+    def test_load(self):
+        doc = Auto.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertIsInstance(doc['direct'], AutoLevel)
+        self.assertIsInstance(doc['direct']['child'], AutoLevel)
+        self.assertEqual('child', doc['direct']['child']['name'])
+        self.assertIsInstance(doc['list'][0], AutoLevel)
+        self.assertIsInstance(doc['map']['one'], AutoLevel)
+        self.assertEqual('one', doc['map']['one']['$name'])
+        self.assertIsInstance(doc['nested']['inner'], AutoLevel)
+        self.assertTrue(doc.validate())
+
+    def test_override(self):
+        doc = Manual.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertIsInstance(doc['direct'], AutoLevel)
+        self.assertNotIsInstance(doc['list'][0], AutoLevel)
+        self.assertNotIsInstance(doc['map']['one'], AutoLevel)
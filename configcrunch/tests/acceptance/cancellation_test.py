@@ -0,0 +1,72 @@
+import threading
+import time
+
+from configcrunch import CancellationToken, ResolutionCancelledError, ConfigcrunchError, load_multiple_yml, \
+    helper, ResolutionContext
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class SlowBase(Base):
+    @helper
+    def slow(self):
+        time.sleep(0.05)
+        return 'slow'
+
+
+class CancellationTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'cancellation'
+
+    def test_timeout_loading(self):
+        with self.assertRaises(ResolutionCancelledError):
+            load_multiple_yml(Base, self.fix_get_path('base.yml'), timeout=0)
+
+    def test_timeout_resolving(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        with self.assertRaises(ResolutionCancelledError):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], timeout=0)
+
+    def test_no_timeout(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), timeout=10)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], timeout=10)
+        self.assertEqual(12, doc['int_field'])
+
+    def test_invalid_timeout(self):
+        with self.assertRaises(ValueError):
+            Base.from_yaml(self.fix_get_path('base.yml'), timeout=-1)
+
+    def test_cancel_token(self):
+        token = CancellationToken()
+        self.assertFalse(token.cancelled)
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), cancel_token=token)
+        token.cancel()
+        self.assertTrue(token.cancelled)
+        with self.assertRaises(ResolutionCancelledError):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], cancel_token=token)
+
+    def test_cancel_template_rendering(self):
+        doc = SlowBase.from_dict({'more': {str(i): '{{ slow() }}' for i in range(100)}})
+        doc.resolve_and_merge_references([])
+        token = CancellationToken()
+        threading.Timer(0.2, token.cancel).start()
+        start = time.monotonic()
+        with self.assertRaises(ResolutionCancelledError):
+            doc.process_vars(cancel_token=token)
+        self.assertLess(time.monotonic() - start, 2)
+
+    def test_timeout_template_rendering(self):
+        doc = SlowBase.from_dict({'more': {str(i): '{{ slow() }}' for i in range(100)}})
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(ResolutionCancelledError):
+            doc.process_vars(timeout=0.1)
+
+    def test_context_load(self):
+        context = ResolutionContext([self.fix_get_path('repo')])
+        with self.assertRaises(ResolutionCancelledError):
+            context.load(Base, self.fix_get_path('base.yml'), timeout=0)
+        self.assertEqual(12, context.load(Base, self.fix_get_path('base.yml'), timeout=10)['int_field'])
+
+    def test_is_configcrunch_error(self):
+        self.assertTrue(issubclass(ResolutionCancelledError, ConfigcrunchError))
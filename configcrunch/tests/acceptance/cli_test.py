@@ -0,0 +1,41 @@
+import contextlib
+import io
+
+from configcrunch.__main__ import main
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+BASE = 'configcrunch.tests.fixtures.documents:Base'
+
+
+class CliTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'lint_override'
+
+    def run_main(self, *argv):
+        out = io.StringIO()
+        with contextlib.redirect_stdout(out):
+            status = main(list(argv))
+        return status, out.getvalue().splitlines()
+
+    def test_lint_override(self):
+        status, lines = self.run_main(
+            'lint-override', BASE, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'),
+            '-l', self.fix_get_path('repo')
+        )
+        self.assertEqual(1, status)
+        self.assertEqual(6, len(lines))
+        self.assertIn(': typo_field: unknown_key: ', lines[5])
+
+    def test_later_layers(self):
+        status, lines = self.run_main(
+            'lint-override', BASE, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'),
+            self.fix_get_path('later.yml'), '--lookup-path', self.fix_get_path('repo')
+        )
+        self.assertEqual(1, status)
+        self.assertTrue(any(': str_field: shadowed_key: ' in line for line in lines))
+
+    def test_invalid_type(self):
+        with contextlib.redirect_stderr(io.StringIO()):
+            with self.assertRaises(SystemExit):
+                main(['lint-override', 'no_such_module', 'base.yml', 'override.yml'])
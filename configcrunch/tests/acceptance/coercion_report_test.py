@@ -0,0 +1,43 @@
+import warnings
+
+from configcrunch import CoercionWarning
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class CoercionReportTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'coercion_report'
+
+    def load(self, **kwargs):
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter('always')
+            doc = Base.from_yaml(self.fix_get_path('doc.yml'), **kwargs)
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], **kwargs)
+        return doc, [str(w.message) for w in caught if issubclass(w.category, CoercionWarning)]
+
+    def test_report(self):
+        doc, messages = self.load(report_coercions=True)
+        self.assertEqual(4, len(messages))
+        self.assertIn('base.more.country', messages[0])
+        self.assertIn('doc.yml (line 5)', messages[0])
+        self.assertIn('the boolean false', messages[0])
+        self.assertIn('the string "no"', messages[0])
+        self.assertIn('base.more.permissions', messages[1])
+        self.assertIn('the integer 18', messages[1])
+        self.assertIn('the integer 22', messages[1])
+        self.assertIn('base.more.ports[1]', messages[2])
+        self.assertIn('base.more.debug', messages[3])
+        self.assertIn('reference.yml (line 4)', messages[3])
+        # The values are not changed.
+        self.assertIs(False, doc.internal_get('more')['country'])
+
+    def test_off_by_default(self):
+        _, messages = self.load()
+        self.assertEqual([], messages)
+
+    def test_core_schema(self):
+        doc, messages = self.load(report_coercions=True, core_schema=True)
+        self.assertEqual([], messages)
+        self.assertEqual('no', doc.internal_get('more')['country'])
@@ -0,0 +1,63 @@
+from decimal import Decimal
+
+from schema import Schema, SchemaError, And, Optional
+
+from configcrunch import Coerce, Range, YamlConfigDocument
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class Service(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "service"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            'port': And(Coerce('int'), Range(min=1)),
+            Optional('debug'): Coerce('bool'),
+            Optional('ratio'): Coerce('float'),
+            Optional('name'): Coerce('str'),
+            Optional('hosts'): And(Coerce('list'), [str]),
+            Optional('ports'): [Coerce('int')],
+            Optional('env'): {str: Coerce('str')},
+        })
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class CoercionTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'coercion'
+
+    def test_coercion(self):
+        doc = Service.from_yaml(self.fix_get_path('loose.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertTrue(doc.validate())
+        self.assertDictEqual(self.fix_get_yml('expected.yml'), doc.to_dict())
+
+    def test_not_coercible(self):
+        doc = Service.from_dict({'port': 'eighty'})
+        with self.assertRaises(SchemaError):
+            doc.validate()
+
+    def test_out_of_range(self):
+        coerce = Coerce('int')
+        self.assertEqual(2 ** 63 - 1, coerce.coerce(str(2 ** 63 - 1)))
+        for value in [str(2 ** 63), '-1e19', 1e19, -1e300]:
+            with self.assertRaisesRegex(SchemaError, 'out of range', msg=repr(value)):
+                coerce.coerce(value)
+
+    def test_decimals(self):
+        coerce = Coerce('int')
+        self.assertEqual(9007199254740993, coerce.coerce(Decimal('9007199254740993.0')))
+        self.assertEqual(Decimal('1.0000000000000000001'), coerce.coerce(Decimal('1.0000000000000000001')))
+        with self.assertRaisesRegex(SchemaError, 'out of range'):
+            coerce.coerce(Decimal('1e30'))
+
+    def test_invalid_target(self):
+        with self.assertRaises(ValueError):
+            Coerce('dict')
@@ -0,0 +1,60 @@
+import socket
+import sys
+
+from configcrunch import load_from_manifest
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class ConditionalLookupPathsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'conditional_lookup_paths'
+
+    def load(self, lookup_paths, **kwargs):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references(lookup_paths, **kwargs)
+        return doc
+
+    def entry(self, path, when):
+        return {'path': self.fix_get_path(path), 'when': when}
+
+    def test_platform(self):
+        doc = self.load([
+            self.fix_get_path('repo'),
+            self.entry('repo_selected', f"platform == '{sys.platform}'"),
+            self.entry('repo_skipped', "platform == 'no-such-platform'"),
+        ])
+        self.assertEqual(1, doc['int_field'])
+        self.assertEqual({'selected': True}, doc['more'])
+
+    def test_hostname(self):
+        doc = self.load([
+            self.entry('repo_selected', f"hostname == '{socket.gethostname()}'"),
+            self.entry('repo_skipped', f"hostname != '{socket.gethostname()}'"),
+        ])
+        self.assertEqual({'selected': True}, doc['more'])
+
+    def test_globals(self):
+        lookup_paths = [
+            self.entry('repo_selected', "env == 'dev'"),
+            self.entry('repo_skipped', "env == 'prod'"),
+        ]
+        self.assertEqual({'selected': True}, self.load(lookup_paths, globals={'env': 'dev'})['more'])
+        self.assertEqual({'skipped': True}, self.load(lookup_paths, globals={'env': 'prod'})['more'])
+        # Passed globals take priority over the injected ones.
+        doc = self.load([self.entry('repo_selected', "platform == 'mine'")], globals={'platform': 'mine'})
+        self.assertEqual({'selected': True}, doc['more'])
+
+    def test_manifest(self):
+        doc = load_from_manifest(Base, self.fix_get_path('layers.yml'), globals={'env': 'dev'})
+        self.assertEqual(1, doc.internal_get('int_field'))
+        self.assertEqual({'selected': True}, doc.internal_get('more'))
+
+    def test_invalid(self):
+        with self.assertRaises(ValueError):
+            self.load([{'when': 'true'}])
+        with self.assertRaises(ValueError):
+            self.load([{'path': self.fix_get_path('repo'), 'if': 'true'}])
+        with self.assertRaises(TypeError):
+            self.load([5])
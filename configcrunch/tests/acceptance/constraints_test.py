@@ -0,0 +1,45 @@
+from schema import Schema, SchemaError, And, Optional
+
+from configcrunch import OneOf, Range, Pattern, Length
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+SCHEMA = Schema({
+    'mode': OneOf('dev', 'prod'),
+    'port': And(int, Range(min=1, max=65535)),
+    Optional('ratio'): Range(max=1.0),
+    'name': And(Pattern(r'[a-z][a-z0-9-]*'), Length(max=63)),
+    'tags': Length(min=1)
+})
+
+
+class ConstraintsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'constraints'
+
+    def test_valid(self):
+        data = self.fix_get_yml('valid.yml')['service']
+        self.assertDictEqual(data, SCHEMA.validate(data))
+
+    def test_invalid(self):
+        valid = self.fix_get_yml('valid.yml')['service']
+        invalid = self.fix_get_yml('invalid.yml')['service']
+        for key in invalid.keys():
+            with self.subTest(key=key):
+                data = valid.copy()
+                data[key] = invalid[key]
+                with self.assertRaises(SchemaError) as ctx:
+                    SCHEMA.validate(data)
+                self.assertIn(repr(key), str(ctx.exception))
+                self.assertIn(repr(invalid[key]), str(ctx.exception))
+
+    def test_not_a_number(self):
+        with self.assertRaises(SchemaError):
+            Range(min=1).validate("1")
+        with self.assertRaises(SchemaError):
+            Range(min=0).validate(True)
+
+    def test_invalid_pattern(self):
+        with self.assertRaises(Exception):
+            Pattern('(')
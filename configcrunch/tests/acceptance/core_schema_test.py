@@ -0,0 +1,38 @@
+import math
+
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class CoreSchemaTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'core_schema'
+
+    def test_yaml(self):
+        more = Base.from_yaml(self.fix_get_path('base.yml'), core_schema=True).internal_get('more')
+        self.assertEqual('no', more['norway'])
+        self.assertEqual('yes', more['yes_value'])
+        self.assertEqual('on', more['on_value'])
+        self.assertIs(True, more['true_value'])
+        self.assertIs(True, more['upper_true'])
+        self.assertEqual(17, more['leading_zero'])
+        self.assertEqual(15, more['octal'])
+        self.assertEqual(31, more['hex'])
+        self.assertEqual(1500.0, more['float'])
+        self.assertTrue(math.isinf(more['inf']))
+        self.assertEqual('nan', more['nan_string'])
+        self.assertEqual('true', more['quoted'])
+        self.assertEqual('12', more['tagged'])
+
+    def test_ini(self):
+        more = Base.from_yaml(self.fix_get_path('base.ini'), core_schema=True).internal_get('more')
+        self.assertEqual({'enabled': 'yes', 'disabled': 'off', 'flag': True}, more)
+
+    def test_ini_default(self):
+        more = Base.from_yaml(self.fix_get_path('base.ini')).internal_get('more')
+        self.assertEqual({'enabled': True, 'disabled': False, 'flag': True}, more)
+
+    def test_with_limits(self):
+        more = Base.from_yaml(self.fix_get_path('base.yml'), core_schema=True, max_nodes=100).internal_get('more')
+        self.assertEqual('no', more['norway'])
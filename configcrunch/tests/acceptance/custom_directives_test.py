@@ -0,0 +1,33 @@
+from configcrunch import register_directive, unregister_directive
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class CustomDirectivesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'custom_directives'
+
+    def setUp(self):
+        register_directive('$upper', lambda value, path, doc: value.upper())
+        register_directive('join', lambda value, path, doc: '-'.join(value))
+        register_directive('$path', lambda value, path, doc: f'{path} in {doc.header()}')
+
+    def tearDown(self):
+        unregister_directive('$upper')
+        unregister_directive('$join')
+        unregister_directive('path')
+
+    def test_custom_directives(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            []
+        )
+
+    def test_builtin_directive(self):
+        with self.assertRaises(ValueError):
+            register_directive('$ref', lambda value, path, doc: value)
+
+    def test_unregister(self):
+        self.assertTrue(unregister_directive('upper'))
+        self.assertFalse(unregister_directive('upper'))
@@ -0,0 +1,64 @@
+import os
+import tempfile
+
+from configcrunch import REMOVE
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+# Far deeper than the stack allows for recursive merging, processing or converting.
+DEPTH = 100_000
+# Far deeper than the recursion limit of serde_yaml (128 levels).
+FILE_DEPTH = 2_000
+
+
+def nested(leaf):
+    value = leaf
+    for _ in range(DEPTH):
+        value = {'child': value}
+    return value
+
+
+def innermost(value, depth=DEPTH):
+    for _ in range(depth):
+        value = value['child']
+    return value
+
+
+class DeepMergeTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'deep_merge'
+
+    def test_deep_dicts(self):
+        doc = Base.from_dict({'more': nested({'a': 1, 'b': 2, 'list': ['x']})})
+        doc.overlay({'more': nested({'b': 3, 'c': 4, 'list': ['y']})})
+        self.assertEqual({'a': 1, 'b': 3, 'list': ['x', 'y'], 'c': 4}, innermost(doc.internal_get('more')))
+
+    def test_deep_remove(self):
+        doc = Base.from_dict({'more': nested({'a': 1, 'b': 2})})
+        doc.overlay({'more': nested({'a': REMOVE})})
+        self.assertEqual(1, doc.merge_stats().keys_removed)
+        self.assertEqual(2, innermost(doc.internal_get('more'))['b'])
+
+    def test_deep_process_vars(self):
+        doc = Base.from_dict({'str_field': 'value', 'more': nested({'template': '{{ str_field }}'})})
+        doc.process_vars()
+        self.assertEqual({'template': 'value'}, innermost(doc.internal_get('more')))
+
+    def test_deep_to_dict(self):
+        doc = Base.from_dict({'more': nested({'a': 1})})
+        doc.overlay({'more': nested({'b': [2]})})
+        self.assertEqual({'a': 1, 'b': [2]}, innermost(doc.to_dict()['base']['more']))
+
+    def test_deep_yaml_file(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, 'deep.yml')
+            with open(path, 'w') as f:
+                f.write('base:\n  more:\n')
+                for level in range(FILE_DEPTH):
+                    f.write(' ' * (level + 3) + 'child:\n')
+                f.write(' ' * (FILE_DEPTH + 3) + 'a: 1\n')
+            for core_schema in (False, True):
+                doc = Base.from_yaml(path, core_schema=core_schema)
+                doc.resolve_and_merge_references([])
+                self.assertEqual({'a': 1}, innermost(doc.internal_get('more'), FILE_DEPTH))
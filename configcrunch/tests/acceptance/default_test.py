@@ -0,0 +1,14 @@
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class DefaultTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'default'
+
+    def test_default(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
@@ -0,0 +1,30 @@
+import warnings
+
+from configcrunch import DeprecatedKeyWarning
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class DeprecatedTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'deprecated'
+
+    def test_warns_for_overridden_keys(self):
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter('always')
+            self.assertDocEqualMerging(
+                'expected.yml',
+                'base.yml',
+                ['repo']
+            )
+        messages = sorted(str(w.message) for w in caught if issubclass(w.category, DeprecatedKeyWarning))
+        self.assertEqual(2, len(messages))
+        self.assertIn('base.yml: The key int_field is deprecated: use str_field instead', messages[0])
+        self.assertIn('base.yml: The key more/old is deprecated: use more/new instead', messages[1])
+
+    def test_no_warning_if_not_overridden(self):
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter('always')
+            doc = self.load_base('not_overridden.yml', ['repo'])
+        self.assertFalse([w for w in caught if issubclass(w.category, DeprecatedKeyWarning)])
+        self.assertNotIn('$deprecated', doc.to_dict()['base'])
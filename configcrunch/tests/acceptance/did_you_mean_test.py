@@ -0,0 +1,34 @@
+from configcrunch import InvalidHeaderError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class DidYouMeanTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'did_you_mean'
+
+    def test_header_typo(self):
+        with self.assertRaises(InvalidHeaderError) as ctx:
+            Base.from_yaml(self.fix_get_path('typo_header.yml'))
+        self.assertIn('bsae (did you mean base?)', str(ctx.exception))
+        self.assertEqual({'bsae': ['base']}, ctx.exception.suggestions)
+
+    def test_header_of_other_type(self):
+        with self.assertRaises(InvalidHeaderError) as ctx:
+            Base.from_yaml(self.fix_get_path('other_type.yml'))
+        self.assertRegex(str(ctx.exception), r'level \(the header of \w*Level documents\)')
+        self.assertEqual({}, ctx.exception.suggestions)
+
+    def test_documents_from_dicts(self):
+        with self.assertRaises(InvalidHeaderError) as ctx:
+            Base.documents_from_dicts([{'Base': {}}])
+        self.assertEqual({'Base': ['base']}, ctx.exception.suggestions)
+
+    def test_lint_override(self):
+        doc = Base.from_dict({})
+        findings = doc.lint_override({'str_feild': 'x', 'completely_unknown': 1})
+        self.assertEqual([
+            ('unknown_key', 'completely_unknown', 'The key is not part of the schema.'),
+            ('unknown_key', 'str_feild', 'The key is not part of the schema (did you mean str_field?).'),
+        ], findings)
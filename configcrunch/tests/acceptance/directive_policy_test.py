@@ -0,0 +1,39 @@
+from configcrunch import DirectivePolicy, InvalidDocumentError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class DirectivePolicyTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'directive_policy'
+
+    def test_custom_prefix(self):
+        policy = DirectivePolicy(prefix='%')
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), directive_policy=policy)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], directive_policy=policy)
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+
+    def test_export_unresolved(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), directive_policy=DirectivePolicy(prefix='%'))
+        more = doc.to_dict()['base']['more']
+        self.assertEqual('kept', more['$literal'])
+        self.assertEqual('$remove', more['list'][1])
+        self.assertIn('$literal: "kept"', doc.normalize())
+        self.assertIn('- "$remove"', doc.normalize())
+        self.assertIn('$literal: "kept"', doc.to_yaml())
+        self.assertIn('- "$remove"', doc.to_yaml())
+        self.assertNotIn('\\u0001', doc.to_json())
+
+    def test_disabled_directive(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('remove.yml'), directive_policy=DirectivePolicy(disabled=['remove']))
+
+    def test_other_directives_still_allowed(self):
+        doc = Base.from_yaml(self.fix_get_path('remove.yml'), directive_policy=DirectivePolicy(disabled=['extends']))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertNotIn('b', doc.to_dict()['base']['more'])
+
+    def test_empty_prefix(self):
+        with self.assertRaises(ValueError):
+            DirectivePolicy(prefix='')
@@ -0,0 +1,111 @@
+from configcrunch import DocumentView
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class DocumentViewTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'document_view'
+
+    def load(self):
+        doc = Base.from_dict({
+            'str_field': 'hello',
+            'level_direct': {'name': 'direct'},
+            'more': {'nested': {'a': 1, 'b': 2}, 'list': [1, 2, {'x': 'y'}]},
+        })
+        doc.resolve_and_merge_references([])
+        return doc
+
+    def test_read(self):
+        doc = self.load()
+        view = doc.view()
+        self.assertIsInstance(view, DocumentView)
+        self.assertIs(doc, view.document)
+        self.assertEqual('', view.path)
+        self.assertEqual('hello', view['str_field'])
+        self.assertIsInstance(view['more'], DocumentView)
+        self.assertEqual('more.nested', view['more']['nested'].path)
+        self.assertEqual(1, view['more']['nested']['a'])
+        self.assertEqual(2, view['more']['list'][-2])
+        self.assertEqual('y', view['more']['list'][2]['x'])
+        self.assertEqual(['str_field', 'level_direct', 'more'], view.keys())
+        self.assertEqual(['a', 'b'], list(view['more']['nested']))
+        self.assertEqual(3, len(view['more']['list']))
+        self.assertIn('more', view)
+        self.assertIn(2, view['more']['list'])
+        self.assertIsNone(view.get('int_field'))
+        self.assertEqual(12, view.get('int_field', 12))
+        self.assertFalse(view.modified)
+        with self.assertRaises(KeyError):
+            view['int_field']
+        with self.assertRaises(IndexError):
+            view['more']['list'][3]
+
+    def test_subdocuments(self):
+        view = self.load().view()
+        self.assertEqual('direct', view['level_direct']['name'])
+        view['level_direct']['name'] = 'changed'
+        self.assertEqual('changed', view['level_direct']['name'])
+        self.assertEqual({'name': 'changed'}, view['level_direct'].to_dict())
+
+    def test_set(self):
+        doc = self.load()
+        view = doc.view()
+        nested = view['more']['nested']
+        nested['a'] = 'changed'
+        nested['c'] = {'new': True}
+        view['more']['list'][0] = 'first'
+        self.assertTrue(view.modified)
+        self.assertEqual('changed', view['more']['nested']['a'])
+        self.assertEqual(True, view['more']['nested']['c']['new'])
+        self.assertEqual(['a', 'b', 'c'], nested.keys())
+        self.assertEqual('first', view['more']['list'][0])
+        # Setting values inside of values set on the view
+        nested['c']['new'] = False
+        self.assertEqual({'new': False}, nested['c'].to_dict())
+        # The document is not changed
+        self.assertEqual({'a': 1, 'b': 2}, doc.internal_get('more')['nested'])
+        self.assertEqual([1, 2, {'x': 'y'}], doc.internal_get('more')['list'])
+
+    def test_delete(self):
+        doc = self.load()
+        view = doc.view()
+        del view['more']['nested']['a']
+        self.assertNotIn('a', view['more']['nested'])
+        self.assertEqual({'b': 2}, view['more']['nested'].to_dict())
+        with self.assertRaises(KeyError):
+            del view['more']['nested']['a']
+        with self.assertRaises(TypeError):
+            del view['more']['list'][0]
+        view['more']['nested']['a'] = 'again'
+        self.assertEqual(['b', 'a'], view['more']['nested'].keys())
+        self.assertEqual({'a': 1, 'b': 2}, doc.internal_get('more')['nested'])
+
+    def test_to_dict(self):
+        view = self.load().view()
+        view['more']['nested']['b'] = 3
+        del view['str_field']
+        self.assertEqual({
+            'level_direct': {'name': 'direct'},
+            'more': {'nested': {'a': 1, 'b': 3}, 'list': [1, 2, {'x': 'y'}]},
+        }, view.to_dict())
+        self.assertEqual([1, 2, {'x': 'y'}], view['more']['list'].to_dict())
+
+    def test_reflects_document(self):
+        doc = self.load()
+        view = doc.view()
+        view['more']['nested']['a'] = 'view'
+        doc.internal_set('int_field', 12)
+        more = doc.internal_get('more')
+        more['nested']['b'] = 'document'
+        doc.internal_set('more', more)
+        self.assertEqual(12, view['int_field'])
+        self.assertEqual({'a': 'view', 'b': 'document'}, view['more']['nested'].to_dict())
+
+    def test_independent_views(self):
+        doc = self.load()
+        view = doc.view()
+        view['str_field'] = 'changed'
+        self.assertEqual('hello', doc.view()['str_field'])
+        self.assertFalse(doc.view().modified)
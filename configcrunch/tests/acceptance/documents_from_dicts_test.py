@@ -0,0 +1,56 @@
+from configcrunch import InvalidHeaderError, InvalidDocumentError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+DICTS = [
+    {'base': {'$ref': '/folder/reference', 'str_field': 'one', 'more': {'b': 22}}},
+    {'base': {'str_field': 'two', 'profiles': {'dev': {'str_field': 'two (dev)'}}}},
+]
+
+
+class DocumentsFromDictsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'documents_from_dicts'
+
+    def test_without_resolving(self):
+        docs = Base.documents_from_dicts(DICTS)
+        self.assertEqual(2, len(docs))
+        self.assertIsInstance(docs[0], Base)
+        self.assertEqual({'base': {'$ref': '/folder/reference', 'str_field': 'one', 'more': {'b': 22}}},
+                         docs[0].to_dict())
+        # profiles are always removed, like for files
+        self.assertEqual({'base': {'str_field': 'two'}}, docs[1].to_dict())
+        # Not loaded from a file.
+        self.assertEqual([], docs[0].absolute_paths)
+
+    def test_resolving(self):
+        docs = Base.documents_from_dicts(DICTS, [self.fix_get_path('repo')], profile='dev')
+        self.assertEqual({'base': {'str_field': 'one', 'int_field': 1234, 'more': {'b': 22, 'c': -1}}},
+                         docs[0].to_dict())
+        self.assertEqual({'base': {'str_field': 'two (dev)'}}, docs[1].to_dict())
+        for doc in docs:
+            self.assertValidDoc(doc)
+
+    def test_merge_stats(self):
+        docs = Base.documents_from_dicts(DICTS, [self.fix_get_path('repo')])
+        self.assertEqual(1, docs[0].merge_stats().refs_resolved)
+        self.assertEqual(0, docs[1].merge_stats().refs_resolved)
+
+    def test_input_not_changed(self):
+        dicts = [{'base': {'str_field': 'one', 'more': {'b': 22}}}]
+        docs = Base.documents_from_dicts(dicts)
+        docs[0]['str_field'] = 'changed'
+        self.assertEqual([{'base': {'str_field': 'one', 'more': {'b': 22}}}], dicts)
+
+    def test_empty(self):
+        self.assertEqual([], Base.documents_from_dicts([]))
+
+    def test_invalid_header(self):
+        with self.assertRaisesRegex(InvalidHeaderError, r'<dicts\[1\]>'):
+            Base.documents_from_dicts([DICTS[0], {'level': {}}])
+
+    def test_invalid_body(self):
+        with self.assertRaisesRegex(InvalidDocumentError, r'<dicts\[0\]>'):
+            Base.documents_from_dicts([{'base': ['not', 'a', 'dict']}])
@@ -0,0 +1,38 @@
+import warnings
+
+from configcrunch import DuplicateKeyError, DuplicateKeyWarning
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class DuplicateKeysTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'duplicate_keys'
+
+    def test_ignore(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        self.assertEqual('second', doc.internal_get('str_field'))
+
+    def test_error(self):
+        with self.assertRaisesRegex(DuplicateKeyError, r'base\.more\.list\[0\]\.x .*line 6 .*line 7'):
+            Base.from_yaml(self.fix_get_path('base.yml'), duplicate_keys='error')
+
+    def test_warn(self):
+        with warnings.catch_warnings(record=True) as caught:
+            warnings.simplefilter('always')
+            doc = Base.from_yaml(self.fix_get_path('base.yml'), duplicate_keys='warn')
+        messages = [str(w.message) for w in caught if issubclass(w.category, DuplicateKeyWarning)]
+        self.assertEqual(2, len(messages))
+        self.assertIn('base.str_field', messages[1])
+        self.assertIn('line 2', messages[1])
+        self.assertIn('line 8', messages[1])
+        self.assertEqual('second', doc.internal_get('str_field'))
+
+    def test_unique(self):
+        doc = Base.from_yaml(self.fix_get_path('unique.yml'), duplicate_keys='error')
+        self.assertEqual('first', doc.internal_get('str_field'))
+
+    def test_invalid_option(self):
+        with self.assertRaises(ValueError):
+            Base.from_yaml(self.fix_get_path('unique.yml'), duplicate_keys='sometimes')
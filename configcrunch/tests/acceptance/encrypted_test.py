@@ -0,0 +1,107 @@
+import base64
+import json
+
+from schema import Schema, Optional, Or
+
+from configcrunch import YamlConfigDocument, EncryptedValue, EncryptionError, load_multiple_yml, \
+    register_key_provider, unregister_key_provider
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class FakeKeyProvider:
+    """Not an encryption, but reversible: ``ENC:`` followed by the base64 encoded plaintext."""
+    def __init__(self):
+        self.decrypted = []
+
+    def encrypt(self, plaintext):
+        return 'ENC:' + base64.b64encode(plaintext.encode()).decode()
+
+    def decrypt(self, ciphertext):
+        self.decrypted.append(ciphertext)
+        if not ciphertext.startswith('ENC:'):
+            raise ValueError('invalid ciphertext')
+        return base64.b64decode(ciphertext[4:]).decode()
+
+
+class Credentials(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "credentials"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            'password': Or(str, EncryptedValue),
+            Optional('user'): str,
+        })
+
+
+class EncryptedTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'encrypted'
+
+    def setUp(self):
+        super().setUp()
+        self.provider = FakeKeyProvider()
+        register_key_provider(self.provider)
+
+    def tearDown(self):
+        unregister_key_provider()
+
+    def test_decrypted_lazily_on_access(self):
+        doc = self.load_base('base.yml', [])
+        doc.freeze()
+        password = doc['more']['password']
+        self.assertIsInstance(password, EncryptedValue)
+        self.assertFalse(password.decrypted)
+        self.assertEqual([], self.provider.decrypted)
+        self.assertEqual('hunter2', password.value)
+        self.assertEqual('hunter2', password.value)
+        self.assertEqual(['ENC:aHVudGVyMg=='], self.provider.decrypted)
+        self.assertEqual('token', doc['more']['token'].value)
+        self.assertEqual('first', doc['more']['list'][0].value)
+        self.assertEqual('not encrypted', doc['more']['list'][1])
+
+    def test_export_keeps_ciphertext(self):
+        doc = self.load_base('base.yml', [])
+        self.assertEqual({'$encrypted': 'ENC:aHVudGVyMg=='}, json.loads(doc.to_json())['base']['more']['password'])
+        self.assertNotIn('hunter2', doc.to_yaml())
+        self.assertEqual([], self.provider.decrypted)
+
+    def test_merge(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'))
+        doc.freeze()
+        self.assertEqual('first', doc['more']['token'].value)
+        self.assertEqual('hunter2', doc['more']['password'].value)
+
+    def test_reencrypted_on_save(self):
+        doc = self.load_base('base.yml', [])
+        doc.freeze()
+        doc['more']['password'].value = 'correct horse'
+        doc['more']['new'] = EncryptedValue('battery staple')
+        doc.unfreeze()
+        more = doc.to_dict()['base']['more']
+        self.assertEqual('ENC:Y29ycmVjdCBob3JzZQ==', more['password'].ciphertext)
+        self.assertEqual('ENC:YmF0dGVyeSBzdGFwbGU=', more['new'].ciphertext)
+
+    def test_schema(self):
+        doc = Credentials.from_dict({'password': {'$encrypted': 'ENC:aHVudGVyMg=='}})
+        doc.validate()
+        doc.freeze()
+        self.assertEqual('hunter2', doc['password'].value)
+
+    def test_no_key_provider(self):
+        unregister_key_provider()
+        doc = self.load_base('base.yml', [])
+        doc.freeze()
+        with self.assertRaises(EncryptionError):
+            doc['more']['password'].value
+
+    def test_provider_error(self):
+        doc = Credentials.from_dict({'password': {'$encrypted': 'garbage'}})
+        doc.freeze()
+        with self.assertRaises(EncryptionError) as ctx:
+            doc['password'].value
+        self.assertIsInstance(ctx.exception.__cause__, ValueError)
@@ -0,0 +1,36 @@
+import os
+from unittest import mock
+
+from configcrunch import InvalidDocumentError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+from configcrunch.tests.fixtures.documents import Base
+
+
+class EnvInterpolationTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'env_interpolation'
+
+    def load(self, **kwargs):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), **kwargs)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], **kwargs)
+        return doc
+
+    @mock.patch.dict(os.environ, {"CONFIGCRUNCH_TEST_REGION": "eu", "CONFIGCRUNCH_TEST_NAME": "value"})
+    def test_interpolation(self):
+        doc = self.load(env_interpolation=True)
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+
+    @mock.patch.dict(os.environ, {"CONFIGCRUNCH_TEST_NAME": "value"})
+    def test_default(self):
+        doc = self.load(env_interpolation=True)
+        self.assertEqual(0, doc.internal_get('int_field'))
+
+    @mock.patch.dict(os.environ, {"CONFIGCRUNCH_TEST_REGION": "eu"})
+    def test_disabled(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        self.assertEqual('/overrides/${CONFIGCRUNCH_TEST_REGION:-default}', doc.internal_get('$ref'))
+
+    def test_required_missing(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('required.yml'), env_interpolation=True)
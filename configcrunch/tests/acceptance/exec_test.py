@@ -0,0 +1,75 @@
+import json
+import sys
+import time
+
+from configcrunch import DirectivePolicy, InvalidDocumentError, ResolutionCancelledError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+ALLOW_EXEC = DirectivePolicy(allow_exec=True)
+
+
+def python(code):
+    return [sys.executable, '-c', code]
+
+
+class ExecTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'exec'
+
+    def load_dict(self, more, **kwargs):
+        return Base.documents_from_dicts([{'base': {'more': more}}], **kwargs)[0].internal_get('more')
+
+    def test_disabled_by_default(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'allow_exec'):
+            Base.from_yaml(self.fix_get_path('doc.yml'))
+        with self.assertRaisesRegex(InvalidDocumentError, 'allow_exec'):
+            Base.from_yaml(self.fix_get_path('doc.yml'), directive_policy=DirectivePolicy())
+
+    def test_exec(self):
+        more = self.load_dict({
+            '$exec': python('print("a: 1\\nb: 2")'),
+            'b': 3,
+            'nested': {'$exec': python('print("c: [x, y]")')}
+        }, directive_policy=ALLOW_EXEC)
+        self.assertEqual({'a': 1, 'b': 3, 'nested': {'c': ['x', 'y']}}, more)
+
+    def test_working_directory(self):
+        path = self.fix_get_path('virtual.yml')
+        content = 'base:\n  more:\n    $exec: %s\n    region: us\n' % json.dumps([sys.executable, 'inventory.py'])
+        doc = Base.from_yaml(path, directive_policy=ALLOW_EXEC, virtual_files={path: content})
+        self.assertEqual({'hosts': ['web1', 'web2'], 'region': 'us'}, doc.internal_get('more'))
+
+    def test_custom_prefix(self):
+        policy = DirectivePolicy(prefix='%', allow_exec=True)
+        more = self.load_dict({'%exec': python('print("a: 1")')}, directive_policy=policy)
+        self.assertEqual({'a': 1}, more)
+
+    def test_disabled_directive(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_dict({'$exec': python('print("a: 1")')},
+                           directive_policy=DirectivePolicy(disabled=['exec'], allow_exec=True))
+
+    def test_failing_command(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'inventory unavailable'):
+            self.load_dict({'$exec': python('import sys; sys.exit("inventory unavailable")')},
+                           directive_policy=ALLOW_EXEC)
+
+    def test_timeout(self):
+        start = time.monotonic()
+        with self.assertRaises(ResolutionCancelledError):
+            self.load_dict({'$exec': python('import time; time.sleep(30)')},
+                           directive_policy=ALLOW_EXEC, timeout=0.5)
+        # The command was killed instead of waited for.
+        self.assertLess(time.monotonic() - start, 10)
+
+    def test_invalid_output(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_dict({'$exec': python('print("- a")')}, directive_policy=ALLOW_EXEC)
+
+    def test_invalid_command(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_dict({'$exec': 'echo a: 1'}, directive_policy=ALLOW_EXEC)
+        with self.assertRaises(InvalidDocumentError):
+            self.load_dict({'$exec': []}, directive_policy=ALLOW_EXEC)
@@ -0,0 +1,69 @@
+import os
+import tempfile
+import unittest
+
+from configcrunch.tests.fixtures.documents import Base
+
+
+class ExportTest(unittest.TestCase):
+    def setUp(self) -> None:
+        self.doc = Base.from_dict({
+            "str_field": "hello world",
+            "int_field": 12,
+            "more": {
+                "enabled": True,
+                "list": ["a", "b"]
+            }
+        })
+
+    def test_to_env(self):
+        self.assertDictEqual({
+            "MYAPP__STR_FIELD": "hello world",
+            "MYAPP__INT_FIELD": "12",
+            "MYAPP__MORE__ENABLED": "true",
+            "MYAPP__MORE__LIST__0": "a",
+            "MYAPP__MORE__LIST__1": "b",
+        }, self.doc.to_env(prefix="myapp"))
+
+    def test_to_env_without_prefix(self):
+        self.assertDictEqual({
+            "STR_FIELD": "hello world",
+            "INT_FIELD": "12",
+            "MORE_ENABLED": "true",
+            "MORE_LIST_0": "a",
+            "MORE_LIST_1": "b",
+        }, self.doc.to_env(separator="_"))
+
+    def test_to_env_frozen(self):
+        self.doc.freeze()
+        self.assertEqual("12", self.doc.to_env(prefix="MYAPP")["MYAPP__INT_FIELD"])
+
+    def test_write_env_file(self):
+        with tempfile.TemporaryDirectory() as tmp:
+            path = os.path.join(tmp, '.env')
+            self.doc.write_env_file(path, prefix="MYAPP")
+            with open(path) as f:
+                self.assertEqual(
+                    'MYAPP__INT_FIELD=12\n'
+                    'MYAPP__MORE__ENABLED=true\n'
+                    'MYAPP__MORE__LIST__0=a\n'
+                    'MYAPP__MORE__LIST__1=b\n'
+                    'MYAPP__STR_FIELD="hello world"\n',
+                    f.read()
+                )
+
+    def test_msgpack_roundtrip(self):
+        data = self.doc.to_msgpack()
+        self.assertIsInstance(data, bytes)
+        loaded = Base.from_msgpack(data)
+        self.assertDictEqual(self.doc.to_dict(), loaded.to_dict())
+        # Not loaded from a file.
+        self.assertEqual([], loaded.absolute_paths)
+
+    def test_msgpack_roundtrip_subdocuments(self):
+        doc = Base.from_dict({"level_direct": {"name": "level"}})
+        doc.resolve_and_merge_references([])
+        loaded = Base.from_msgpack(doc.to_msgpack())
+        loaded.resolve_and_merge_references([])
+        self.assertEqual("Level", loaded.internal_get("level_direct").__class__.__name__)
+        self.assertDictEqual(doc.to_dict(), loaded.to_dict())
@@ -0,0 +1,39 @@
+from configcrunch import register_template, unregister_template, ReferencedDocumentNotFound, \
+    CircularDependencyError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class ExtendsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'extends'
+
+    def setUp(self):
+        register_template('registered', {'more': {'b': 'registered'}})
+        register_template('circular', {'$extends': 'circular'})
+
+    def tearDown(self):
+        unregister_template('registered')
+        unregister_template('circular')
+
+    def test_extends(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_unregister(self):
+        self.assertTrue(unregister_template('registered'))
+        self.assertFalse(unregister_template('registered'))
+
+    def test_circular(self):
+        doc = Base.from_yaml(self.fix_get_path('circular.yml'))
+        with self.assertRaises(CircularDependencyError):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_not_found(self):
+        doc = Base.from_yaml(self.fix_get_path('not_found.yml'))
+        with self.assertRaises(ReferencedDocumentNotFound):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
@@ -0,0 +1,31 @@
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class ExtractOverrideTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'extract_override'
+
+    def test_extract(self):
+        doc = self.load_base('base.yml', ['repo'])
+        override = doc.extract_override(self.fix_get_yml('target.yml')['base'])
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(override))
+
+    def test_roundtrip(self):
+        doc = self.load_base('base.yml', ['repo'])
+        override = doc.extract_override(self.fix_get_yml('target.yml')['base'])
+        doc.overlay(override)
+        doc.resolve_and_merge_references([])
+        self.assertDictEqual(deep_sort(self.fix_get_yml('target.yml')), deep_sort(doc.to_dict()))
+
+    def test_unchanged(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertEqual({}, doc.extract_override(self.load_base('base.yml', ['repo'])))
+
+    def test_reordered_list(self):
+        doc = self.load_base('base.yml', ['repo'])
+        target = self.fix_get_yml('target.yml')['base']
+        target['more']['list'] = ['c', 'a']
+        with self.assertRaises(ValueError):
+            doc.extract_override(target)
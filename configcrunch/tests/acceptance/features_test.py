@@ -0,0 +1,64 @@
+from configcrunch import InvalidDocumentError, load_multiple_yml
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class FeaturesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'features'
+
+    def test_no_templates(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-templates'):
+            Base.from_yaml(self.fix_get_path('no_templates.yml'))
+
+    def test_no_references(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-references'):
+            Base.from_yaml(self.fix_get_path('no_references.yml'))
+
+    def test_no_remove(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-remove'):
+            Base.from_yaml(self.fix_get_path('no_remove.yml'))
+
+    def test_invalid_feature(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-everything'):
+            Base.from_yaml(self.fix_get_path('invalid_feature.yml'))
+
+    def test_allowed(self):
+        self.assertDocEqualMerging('expected_allowed.yml', 'allowed.yml', ['repo'])
+
+    def test_strict_types(self):
+        doc = Base.from_yaml(self.fix_get_path('strict_types.yml'))
+        with self.assertRaisesRegex(InvalidDocumentError, 'more.a from string to list'):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_strict_types_load_multiple_yml(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'strict-types'):
+            load_multiple_yml(
+                Base, self.fix_get_path('repo/base.yml'), self.fix_get_path('strict_types.yml')
+            )
+
+    def test_referenced_file(self):
+        doc = Base.from_yaml(self.fix_get_path('references_restricted.yml'))
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-templates'):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_imposed_features(self):
+        # allowed.yml doesn't declare no-references, its own $features can't lift imposed features.
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-references'):
+            Base.from_yaml(self.fix_get_path('allowed.yml'), features=['no-references'])
+        doc = Base.from_yaml(self.fix_get_path('imposed.yml'), features=['strict-types'])
+        with self.assertRaisesRegex(InvalidDocumentError, 'more.a from string to list'):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_imposed_features_referenced_file(self):
+        doc = Base.from_yaml(self.fix_get_path('references_templated.yml'))
+        with self.assertRaisesRegex(InvalidDocumentError, 'no-templates'):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], features=['no-templates'])
+        doc = Base.from_yaml(self.fix_get_path('references_templated.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual("{{ 'rendered' }}", doc['str_field'])
+
+    def test_invalid_imposed_feature(self):
+        with self.assertRaisesRegex(ValueError, 'no-everything'):
+            Base.from_yaml(self.fix_get_path('allowed.yml'), features=['no-everything'])
@@ -0,0 +1,60 @@
+import json
+from decimal import Decimal
+
+import yaml
+
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class FloatPrecisionTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'float_precision'
+
+    def load(self, **kwargs):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'), **kwargs)
+        doc.resolve_and_merge_references([], **kwargs)
+        return doc
+
+    def test_floats_by_default(self):
+        more = self.load().internal_get('more')
+        self.assertIsInstance(more['cpu'], float)
+        self.assertEqual(0.30000000000000004, more['ratio'])
+
+    def test_decimal_floats(self):
+        more = self.load(decimal_floats=True).internal_get('more')
+        self.assertEqual(Decimal('0.1'), more['cpu'])
+        self.assertEqual(Decimal('0.30000000000000004'), more['ratio'])
+        self.assertEqual([Decimal('2.5'), Decimal('1.125')], more['limits'])
+        self.assertEqual(3, more['replicas'])
+
+    def test_decimal_floats_source_text(self):
+        doc = Base.from_yaml(self.fix_get_path('source_text.yml'), decimal_floats=True)
+        more = doc.internal_get('more')
+        self.assertEqual('19.990000000000000001', str(more['price']))
+        self.assertEqual('1.10', str(more['rounded']))
+        self.assertEqual(['2.50', '0.125'], [str(d) for d in more['limits']])
+
+    def test_to_yaml(self):
+        for kwargs in [{}, {'decimal_floats': True}]:
+            text = self.load(**kwargs).to_yaml()
+            self.assertIn('cpu: 0.1\n', text)
+            self.assertIn('ratio: 0.30000000000000004\n', text)
+
+    def test_to_yaml_float_precision(self):
+        for kwargs in [{}, {'decimal_floats': True}]:
+            more = yaml.safe_load(self.load(**kwargs).to_yaml(float_precision=2))['base']['more']
+            self.assertEqual({'cpu': 0.1, 'ratio': 0.3, 'replicas': 3, 'limits': [2.5, 1.13]}, more)
+
+    def test_to_json(self):
+        more = json.loads(self.load(decimal_floats=True).to_json())['base']['more']
+        self.assertEqual(0.30000000000000004, more['ratio'])
+        more = json.loads(self.load(decimal_floats=True).to_json(float_precision=1))['base']['more']
+        self.assertEqual({'cpu': 0.1, 'ratio': 0.3, 'replicas': 3, 'limits': [2.5, 1.1]}, more)
+
+    def test_to_env(self):
+        env = self.load().to_env(prefix='app', float_precision=2)
+        self.assertEqual('0.3', env['APP__MORE__RATIO'])
+        self.assertEqual('1.13', env['APP__MORE__LIMITS__1'])
+        self.assertEqual('0.30000000000000004', self.load().to_env(prefix='app')['APP__MORE__RATIO'])
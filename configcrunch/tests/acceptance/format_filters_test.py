@@ -0,0 +1,38 @@
+import unittest
+
+from configcrunch import VariableProcessingError
+from configcrunch.tests.fixtures.documents import Base
+
+
+def render(template):
+    doc = Base.from_dict({'more': {'value': template}})
+    doc.process_vars()
+    return doc.to_dict()['base']['more']['value']
+
+
+class FormatFiltersTest(unittest.TestCase):
+    def test_format_number(self):
+        self.assertEqual('1,234,567', render('{{ 1234567|format_number }}'))
+        self.assertEqual('-1,234.5', render('{{ (-1234.5)|format_number }}'))
+        self.assertEqual('1,234,567.00', render('{{ 1234567|format_number(2) }}'))
+        self.assertEqual('1.234.567,89', render('{{ 1234567.891|format_number(2, ".", ",") }}'))
+        self.assertEqual('12 345', render('{{ "12345"|format_number(0, " ") }}'))
+
+    def test_format_bytes(self):
+        self.assertEqual('512 B', render('{{ 512|format_bytes }}'))
+        self.assertEqual('1.5 KiB', render('{{ 1536|format_bytes }}'))
+        self.assertEqual('1.5 kB', render('{{ 1500|format_bytes(1000) }}'))
+        self.assertEqual('2.00 GiB', render('{{ 2147483648|format_bytes(1024, 2) }}'))
+        self.assertEqual('1.0 MiB', render('{{ 1048575|format_bytes }}'))
+        with self.assertRaises(VariableProcessingError):
+            render('{{ 1|format_bytes(512) }}')
+
+    def test_format_duration(self):
+        self.assertEqual('0s', render('{{ 0|format_duration }}'))
+        self.assertEqual('250ms', render('{{ 0.25|format_duration }}'))
+        self.assertEqual('1m 30s', render('{{ 90|format_duration }}'))
+        self.assertEqual('1d 2h 5s', render('{{ 93605|format_duration }}'))
+
+    def test_not_a_number(self):
+        with self.assertRaises(VariableProcessingError):
+            render('{{ "abc"|format_number }}')
@@ -0,0 +1,40 @@
+import gc
+
+from configcrunch import YamlConfigDocument
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class GarbageCollectionTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'garbage_collection'
+
+    def count_documents(self):
+        gc.collect()
+        return sum(1 for o in gc.get_objects() if isinstance(o, YamlConfigDocument))
+
+    def load(self):
+        doc = self.load_base('base.yml', [])
+        doc.process_vars()
+        return doc
+
+    def test_tracked(self):
+        doc = self.load()
+        self.assertTrue(gc.is_tracked(doc))
+        self.assertTrue(gc.is_tracked(doc.internal_get('level_array')))
+
+    def test_resolved_documents_are_collected(self):
+        before = self.count_documents()
+        for _ in range(3):
+            doc = self.load()
+            doc.freeze()
+            self.assertEqual('simple-b', doc['level_array'][1]['name'])
+            del doc
+        self.assertEqual(before, self.count_documents())
+
+    def test_self_reference_is_collected(self):
+        before = self.count_documents()
+        doc = self.load_base('base.yml', [])
+        doc.process_vars(globals={'itself': doc})
+        del doc
+        self.assertEqual(before, self.count_documents())
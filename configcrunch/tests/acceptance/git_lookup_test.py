@@ -0,0 +1,101 @@
+import os
+import shutil
+import subprocess
+import tempfile
+import unittest
+
+from configcrunch import _lookup, ReferencedDocumentNotFound
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+def git(repo, *args):
+    subprocess.run(['git', '-C', repo, '-c', 'user.name=Test', '-c', 'user.email=test@example.org', *args],
+                   check=True, capture_output=True)
+
+
+@unittest.skipUnless(shutil.which('git'), 'git is not installed')
+class GitLookupTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'git_lookup'
+
+    def setUp(self):
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        self.cache_dir = os.path.join(tmp.name, 'cache')
+        self.repo = os.path.join(tmp.name, 'repo')
+        os.makedirs(os.path.join(self.repo, 'configs'))
+        git(self.repo, 'init', '--quiet')
+        self.commit(1)
+        git(self.repo, 'tag', 'v1')
+        self.commit(2)
+        _lookup._checked_out.clear()
+
+    def commit(self, int_field):
+        with open(os.path.join(self.repo, 'configs', 'shared.yml'), 'w') as f:
+            f.write(f'base:\n  int_field: {int_field}\n')
+        git(self.repo, 'add', '--all')
+        git(self.repo, 'commit', '--quiet', '-m', f'Version {int_field}')
+
+    def url(self, suffix=''):
+        return 'git+file://' + self.repo + suffix
+
+    def load(self, lookup_path):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([lookup_path], cache_dir=self.cache_dir)
+        return doc
+
+    def test_parse(self):
+        self.assertEqual(('https://example.org/configs.git', 'v1.2', 'base/dir'),
+                         _lookup.parse_git_lookup_path('git+https://example.org/configs.git@v1.2//base/dir'))
+        self.assertEqual(('https://example.org/configs.git', None, ''),
+                         _lookup.parse_git_lookup_path('git+https://example.org/configs.git'))
+        self.assertEqual(('ssh://git@example.org/configs.git', 'main', ''),
+                         _lookup.parse_git_lookup_path('git+ssh://git@example.org/configs.git@main'))
+
+    def test_parse_scp_like(self):
+        self.assertEqual(('git@example.org:org/configs.git', None, ''),
+                         _lookup.parse_git_lookup_path('git+git@example.org:org/configs.git'))
+        self.assertEqual(('git@example.org:org/configs.git', 'v1', 'base'),
+                         _lookup.parse_git_lookup_path('git+git@example.org:org/configs.git@v1//base'))
+        self.assertEqual(('git@example.org:configs.git', 'main', ''),
+                         _lookup.parse_git_lookup_path('git+git@example.org:configs.git@main'))
+
+    def test_option_as_ref(self):
+        with self.assertRaises(ValueError):
+            self.load(self.url('@--upload-pack=touch pwned//configs'))
+        self.assertFalse(os.path.exists('pwned'))
+
+    def test_cache_dir_per_call(self):
+        self.load(self.url('//configs'))
+        other_cache_dir = self.cache_dir + '-other'
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([self.url('//configs')], cache_dir=other_cache_dir)
+        self.assertEqual(2, doc.internal_get('int_field'))
+        self.assertTrue(os.path.isdir(os.path.join(other_cache_dir, 'git')))
+
+    def test_default_branch(self):
+        doc = self.load(self.url('//configs'))
+        self.assertEqual(2, doc.internal_get('int_field'))
+        self.assertEqual('local', doc.internal_get('str_field'))
+
+    def test_tag(self):
+        self.assertEqual(1, self.load(self.url('@v1//configs')).internal_get('int_field'))
+
+    def test_fetched_once_per_process(self):
+        self.load(self.url('//configs'))
+        self.commit(3)
+        self.assertEqual(2, self.load(self.url('//configs')).internal_get('int_field'))
+        _lookup._checked_out.clear()
+        self.assertEqual(3, self.load(self.url('//configs')).internal_get('int_field'))
+
+    def test_offline_fallback(self):
+        self.load(self.url('@v1//configs'))
+        _lookup._checked_out.clear()
+        shutil.rmtree(self.repo)
+        self.assertEqual(1, self.load(self.url('@v1//configs')).internal_get('int_field'))
+
+    def test_unknown_ref(self):
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.load(self.url('@v9//configs'))
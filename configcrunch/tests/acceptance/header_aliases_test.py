@@ -0,0 +1,46 @@
+from typing import List
+
+from configcrunch import load_multiple_yml, InvalidHeaderError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class AliasedBase(Base):
+    @classmethod
+    def header_aliases(cls) -> List[str]:
+        return ['old_base', 'vendor.base']
+
+
+class HeaderAliasesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'header_aliases'
+
+    def test_alias(self):
+        doc = AliasedBase.from_yaml(self.fix_get_path('old.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual('old', doc['str_field'])
+        self.assertEqual(12, doc['int_field'])
+        self.assertEqual(['base'], list(doc.to_dict()))
+
+    def test_load_multiple_yml(self):
+        doc = load_multiple_yml(AliasedBase, self.fix_get_path('repo/reference.yml'), self.fix_get_path('old.yml'))
+        self.assertEqual({'$ref': '/reference', 'str_field': 'old', 'int_field': 12}, doc.to_dict()['base'])
+
+    def test_documents_from_dicts(self):
+        docs = AliasedBase.documents_from_dicts([{'vendor.base': {'str_field': 'vendor'}}, {'base': {}}])
+        self.assertEqual('vendor', docs[0]['str_field'])
+        self.assertEqual({}, docs[1].to_dict()['base'])
+
+    def test_no_aliases_by_default(self):
+        self.assertEqual([], Base.header_aliases())
+        with self.assertRaises(InvalidHeaderError):
+            Base.from_yaml(self.fix_get_path('old.yml'))
+
+    def test_header_and_alias(self):
+        with self.assertRaises(InvalidHeaderError):
+            AliasedBase.from_yaml(self.fix_get_path('both.yml'))
+
+    def test_unknown_header(self):
+        with self.assertRaises(InvalidHeaderError):
+            AliasedBase.from_yaml(self.fix_get_path('unknown.yml'))
@@ -0,0 +1,46 @@
+from configcrunch import load_multiple_yml, InvalidHeaderError, InvalidDocumentError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class HeaderlessBase(Base):
+    @classmethod
+    def allow_headerless(cls) -> bool:
+        return True
+
+
+class HeaderlessTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'headerless'
+
+    def test_headerless(self):
+        doc = HeaderlessBase.from_yaml(self.fix_get_path('plain.yml'))
+        self.assertEqual({'base': {'str_field': 'plain', 'int_field': 3}}, doc.to_dict())
+
+    def test_headed(self):
+        doc = HeaderlessBase.from_yaml(self.fix_get_path('headed.yml'))
+        self.assertEqual({'base': {'str_field': 'headed'}}, doc.to_dict())
+
+    def test_reference(self):
+        doc = HeaderlessBase.from_yaml(self.fix_get_path('with_ref.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual('plain', doc['str_field'])
+        self.assertEqual(12, doc['int_field'])
+
+    def test_load_multiple_yml(self):
+        doc = load_multiple_yml(HeaderlessBase, self.fix_get_path('repo/reference.yml'), self.fix_get_path('plain.yml'))
+        self.assertEqual({'str_field': 'plain', 'int_field': 3}, doc.to_dict()['base'])
+
+    def test_documents_from_dicts(self):
+        docs = HeaderlessBase.documents_from_dicts([{'str_field': 'dict'}])
+        self.assertEqual('dict', docs[0]['str_field'])
+
+    def test_features(self):
+        with self.assertRaises(InvalidDocumentError):
+            HeaderlessBase.from_yaml(self.fix_get_path('features.yml'))
+
+    def test_not_allowed_by_default(self):
+        self.assertFalse(Base.allow_headerless())
+        with self.assertRaises(InvalidHeaderError):
+            Base.from_yaml(self.fix_get_path('plain.yml'))
@@ -0,0 +1,81 @@
+import functools
+
+from schema import Schema
+
+from configcrunch import YamlConfigDocument, helper
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class Project(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "project"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({str: str})
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+    @helper(name="project_name")
+    def get_project_name(self):
+        return self.internal_get('name')
+
+    @helper(pass_document=True)
+    def describe(self, document, value):
+        return f"{document.internal_get('name')}: {value}"
+
+    @helper
+    def plain(self):
+        return "plain"
+
+
+def suffix(document, value):
+    return f"{document.internal_get('name')}-{value}"
+
+
+class HelperDecoratorTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'helper_decorator'
+
+    def load(self):
+        doc = Project.from_yaml(self.fix_get_path('project.yml'))
+        doc.resolve_and_merge_references([])
+        return doc
+
+    def test_bound_helpers(self):
+        doc = self.load()
+        doc.process_vars()
+        self.assertDictEqual(self.fix_get_yml('expected.yml'), doc.to_dict())
+
+    def test_additional_helpers(self):
+        doc = self.load()
+        self.assertEqual('acme-a', doc.process_vars_for(
+            '{{ name_suffix("a") }}',
+            [helper(name="name_suffix", pass_document=True)(suffix)]
+        ))
+        self.assertEqual('acme-b', doc.process_vars_for(
+            '{{ suffix("b") }}',
+            [helper(pass_document=True)(suffix)]
+        ))
+
+    def test_partial_and_lambda(self):
+        doc = self.load()
+        self.assertEqual('acme-c', doc.process_vars_for(
+            '{{ fixed() }}',
+            [helper(name="fixed", pass_document=True)(functools.partial(suffix, value='c'))]
+        ))
+        self.assertEqual('lambda', doc.process_vars_for(
+            '{{ from_lambda() }}',
+            [helper(name="from_lambda")(lambda: 'lambda')]
+        ))
+
+    def test_unnamed(self):
+        doc = self.load()
+        with self.assertRaises(TypeError):
+            doc.process_vars_for('{{ x() }}', [lambda: 'x'])
+        with self.assertRaises(TypeError):
+            doc.process_vars_for('{{ x() }}', [functools.partial(suffix, value='x')])
@@ -0,0 +1,85 @@
+import gc
+import weakref
+
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base, Level
+
+
+class IdentityMapTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'identity_map'
+
+    def load(self, **kwargs):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], **kwargs)
+        return doc
+
+    def test_shared(self):
+        doc = self.load(identity_map=True)
+        direct = doc.internal_get('level_direct')
+        levels = doc.internal_get('level_array')
+        self.assertIs(direct, levels[0])
+        self.assertIs(direct, levels[1])
+        self.assertEqual('shared', direct.internal_get('name'))
+        self.assertEqual('level_direct', direct.path_in_parent())
+        self.assertIs(doc, direct.parent())
+
+    def test_not_shared(self):
+        doc = self.load(identity_map=True)
+        direct = doc.internal_get('level_direct')
+        levels = doc.internal_get('level_array')
+        # Documents with more than a $ref and other files.
+        self.assertIsNot(direct, levels[2])
+        self.assertEqual('overridden', levels[2].internal_get('name'))
+        self.assertIsNot(direct, levels[3])
+        self.assertEqual('other', levels[3].internal_get('name'))
+        # Documents in dicts have different keys ($name).
+        level_dict = doc.internal_get('level_dict')
+        self.assertIsNot(level_dict['a'], level_dict['b'])
+        self.assertEqual('a', level_dict['a'].internal_get('$name'))
+        self.assertEqual('b', level_dict['b'].internal_get('$name'))
+
+    def test_disabled_by_default(self):
+        doc = self.load()
+        levels = doc.internal_get('level_array')
+        self.assertIsNot(doc.internal_get('level_direct'), levels[0])
+        self.assertIsNot(levels[0], levels[1])
+        self.assertEqual(levels[0].to_dict(), levels[1].to_dict())
+
+    def test_processing(self):
+        doc = self.load(identity_map=True)
+        doc.process_vars()
+        doc.freeze()
+        self.assertEqual('shared', doc['level_direct']['name'])
+        self.assertEqual('shared', doc['level_array'][1]['name'])
+        self.assertTrue(doc.validate())
+
+    def test_override_one_reference(self):
+        shared = self.load(identity_map=True)
+        doc = Base.from_dict({'level_direct': {'name': 'base', 'more': {'local': True}}})
+        doc.resolve_and_merge_references([])
+        # Merges the shared level_direct over the local one, the other references stay unchanged.
+        doc.overlay(shared)
+        direct = doc.internal_get('level_direct')
+        self.assertEqual('shared', direct.internal_get('name'))
+        self.assertEqual({'local': True}, direct.internal_get('more'))
+        levels = shared.internal_get('level_array')
+        self.assertIs(shared.internal_get('level_direct'), levels[0])
+        self.assertIsNot(direct, levels[0])
+        self.assertEqual({'name': 'shared'}, levels[0].to_dict()['level'])
+        self.assertEqual({'name': 'shared'}, levels[1].to_dict()['level'])
+
+    def test_overlay_itself(self):
+        doc = self.load(identity_map=True)
+        doc.overlay(doc)
+        self.assertEqual('shared', doc.internal_get('level_direct').internal_get('name'))
+        self.assertEqual('overridden', doc.internal_get('level_array')[2].internal_get('name'))
+
+    def test_weakref(self):
+        doc = Level.from_dict({'name': 'level'})
+        ref = weakref.ref(doc)
+        self.assertIs(doc, ref())
+        del doc
+        gc.collect()
+        self.assertIsNone(ref())
@@ -0,0 +1,21 @@
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class ImportAllTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'import_all'
+
+    def test_import_all(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo1', 'repo2']
+        )
+
+    def test_no_match(self):
+        self.assertDocEqualMerging(
+            'expected_no_match.yml',
+            'no_match.yml',
+            ['repo1', 'repo2']
+        )
@@ -0,0 +1,35 @@
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class IniLoaderTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'ini_loader'
+
+    def test_ref_to_ini(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_from_ini(self):
+        doc = Base.from_yaml(self.fix_get_path('repo/legacy.ini'))
+        self.assertDictEqual({
+            'base': {
+                'str_field': 'from ini',
+                'int_field': 12,
+                'more': {'enabled': True, 'ratio': 0.5, 'name': 'legacy'}
+            }
+        }, deep_sort(doc.to_dict()))
+
+    def test_from_ini_without_type_inference(self):
+        doc = Base.from_yaml(self.fix_get_path('repo/legacy.ini'), ini_infer_types=False)
+        self.assertDictEqual({
+            'base': {
+                'str_field': 'from ini',
+                'int_field': '12',
+                'more': {'enabled': 'yes', 'ratio': '0.5', 'name': 'legacy'}
+            }
+        }, deep_sort(doc.to_dict()))
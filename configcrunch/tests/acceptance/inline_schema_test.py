@@ -0,0 +1,46 @@
+import sys
+
+from schema import Schema, Optional, SchemaError
+
+from configcrunch import register_schema, unregister_schema, InvalidDocumentError
+from configcrunch.tests.fixtures.documents import Base, Level
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class InlineSchemaTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'inline_schema'
+
+    def setUp(self):
+        register_schema('service', Schema({'str_field': str, Optional('int_field'): int}))
+
+    def tearDown(self):
+        unregister_schema('service')
+
+    def test_valid(self):
+        doc = Base.from_yaml(self.fix_get_path('valid.yml'))
+        self.assertDictEqual({'base': {'str_field': 'hello', 'int_field': 12}}, doc.to_dict())
+
+    def test_invalid(self):
+        with self.assertRaises(InvalidDocumentError) as ctx:
+            Base.from_yaml(self.fix_get_path('invalid.yml'))
+        self.assertIn('invalid.yml', str(ctx.exception))
+        self.assertIsInstance(ctx.exception.__cause__, SchemaError)
+
+    def test_unknown_schema(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('unknown.yml'))
+
+    def test_document_class(self):
+        register_schema('level', Level)
+        try:
+            with self.assertRaises(InvalidDocumentError):
+                Level.from_yaml(self.fix_get_path('level.yml'))
+        finally:
+            unregister_schema('level')
+
+    def test_import_path_not_imported(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('import_path.yml'))
+        self.assertNotIn('configcrunch.tests.fixtures.schema_module', sys.modules)
@@ -0,0 +1,40 @@
+from configcrunch import load_multiple_yml, InvalidDocumentError, DirectivePolicy
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class InsertListTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'insert_list'
+
+    def test_insert(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertEqual(
+            ['session', 'cors', 'auth', 'rate_limit', 'cache', 'router', 'logging'],
+            doc['more']['middleware']
+        )
+
+    def test_multiple_files(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('repo/reference.yml'), self.fix_get_path('no_base.yml'))
+        self.assertEqual(['session', 'auth', 'rate_limit', 'router'], doc['more']['middleware'])
+
+    def test_missing_entry(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('missing.yml', ['repo'])
+
+    def test_nothing_to_merge_over(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('no_base.yml', [])
+
+    def test_directive_policy(self):
+        policy = DirectivePolicy(prefix='@')
+        doc = load_multiple_yml(Base, self.fix_get_path('repo/reference.yml'), self.fix_get_path('custom_prefix.yml'),
+                                directive_policy=policy)
+        self.assertEqual(['session', 'auth', 'auth', 'router'], doc['more']['middleware'])
+
+    def test_disabled(self):
+        policy = DirectivePolicy(prefix='@', disabled=['insert_after'])
+        with self.assertRaises(InvalidDocumentError):
+            load_multiple_yml(Base, self.fix_get_path('repo/reference.yml'), self.fix_get_path('custom_prefix.yml'),
+                              directive_policy=policy)
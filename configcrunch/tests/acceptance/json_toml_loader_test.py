@@ -0,0 +1,58 @@
+from configcrunch import InvalidDocumentError, load_multiple_yml
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class JsonTomlLoaderTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'json_toml_loader'
+
+    def test_ref_to_json_and_toml(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_from_json(self):
+        doc = Base.from_yaml(self.fix_get_path('repo/settings.json'))
+        self.assertDictEqual({
+            'base': {
+                '$ref': '/defaults',
+                'str_field': 'from json',
+                'int_field': 12,
+                'more': {'enabled': True, 'list': [1, 'two']}
+            }
+        }, deep_sort(doc.to_dict()))
+
+    def test_from_toml(self):
+        doc = Base.from_yaml(self.fix_get_path('repo/defaults.toml'))
+        self.assertDictEqual({
+            'base': {
+                'int_field': 1,
+                'more': {'ratio': 0.5, 'name': 'legacy'}
+            }
+        }, deep_sort(doc.to_dict()))
+
+    def test_load_multiple(self):
+        doc = load_multiple_yml(
+            Base, self.fix_get_path('repo/defaults.toml'), self.fix_get_path('repo/settings.json')
+        )
+        self.assertEqual(12, doc.internal_get('int_field'))
+        self.assertEqual('legacy', doc.internal_get('more')['name'])
+        self.assertEqual(True, doc.internal_get('more')['enabled'])
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'Unable to read JSON file'):
+            Base.from_yaml(self.fix_get_path('invalid.json'))
+
+    def test_write_back_rejected(self):
+        path = self.fix_get_path('repo/settings.json')
+        doc = Base.from_yaml(path)
+        with open(path) as f:
+            content = f.read()
+        with self.assertRaisesRegex(ValueError, 'Only YAML files can be written, this is a JSON file'):
+            doc.write_back('str_field', 'changed')
+        with open(path) as f:
+            self.assertEqual(content, f.read())
@@ -0,0 +1,57 @@
+import json
+
+from configcrunch import load_multiple_yml
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class KeyOrderTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'key_order'
+
+    def test_file_order(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        self.assertEqual(['$ref', 'str_field', 'more'], list(doc.to_dict()['base']))
+        self.assertEqual(['zulu', 'alpha'], list(doc.to_dict()['base']['more']))
+
+    def test_merged_keys_after_existing(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertEqual(['more', 'int_field', 'str_field'], list(doc.to_dict()['base']))
+        self.assertEqual(['mike', 'zulu', 'alpha'], list(doc.to_dict()['base']['more']))
+        self.assertEqual({'mike': 0, 'zulu': 1, 'alpha': 2}, doc.to_dict()['base']['more'])
+
+    def test_templates(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.process_vars()
+        self.assertEqual('mike;zulu;alpha;', doc['str_field'])
+
+    def test_exports(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertEqual(['mike', 'zulu', 'alpha'], list(json.loads(doc.to_json())['base']['more']))
+        doc.freeze()
+        self.assertEqual(['more', 'int_field', 'str_field'], list(doc))
+
+    def test_multiple_files(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('repo/reference.yml'), self.fix_get_path('base.yml'))
+        self.assertEqual(['more', 'int_field', '$ref', 'str_field'], list(doc.to_dict()['base']))
+
+    def test_reorder(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertIs(doc, doc.reorder('', ['str_field', 'unknown', 'int_field']))
+        self.assertEqual(['str_field', 'int_field', 'more'], list(doc.to_dict()['base']))
+        doc.reorder('more', ['alpha'])
+        self.assertEqual(['alpha', 'mike', 'zulu'], list(doc.to_dict()['base']['more']))
+
+    def test_reorder_no_dict(self):
+        doc = self.load_base('base.yml', ['repo'])
+        with self.assertRaises(KeyError):
+            doc.reorder('int_field', [])
+        with self.assertRaises(KeyError):
+            doc.reorder('more.unknown', [])
+
+    def test_reorder_frozen(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.freeze()
+        with self.assertRaises(RuntimeError):
+            doc.reorder('', [])
@@ -0,0 +1,45 @@
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class LintOverrideTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'lint_override'
+
+    def test_lint(self):
+        doc = self.load_base('base.yml', ['repo'])
+        findings = doc.lint_override(self.fix_get_yml('override.yml')['base'])
+        self.assertEqual([
+            ('dead_override', 'int_field'),
+            ('nonexistent_remove', 'level_direct'),
+            ('dead_override', 'more.b'),
+            ('nonexistent_remove', 'more.list'),
+            ('dead_override', 'more.nested'),
+            ('unknown_key', 'typo_field'),
+        ], [(kind, path) for kind, path, _ in findings])
+        self.assertIn('x', findings[3][2])
+
+    def test_later_layers(self):
+        doc = self.load_base('base.yml', ['repo'])
+        findings = doc.lint_override(
+            self.fix_get_yml('override.yml')['base'],
+            [self.fix_get_yml('later.yml')['base']]
+        )
+        self.assertIn(('shadowed_key', 'str_field'), [(kind, path) for kind, path, _ in findings])
+        self.assertIn(('shadowed_key', 'more.a'), [(kind, path) for kind, path, _ in findings])
+
+    def test_clean(self):
+        doc = self.load_base('base.yml', ['repo'])
+        self.assertEqual([], doc.lint_override({'str_field': 'changed', 'more': {'c': 1}}))
+        self.assertEqual([], doc.lint_override({'more': {'list': ['c']}}, [{'more': {'d': 2}}]))
+
+    def test_does_not_change(self):
+        doc = self.load_base('base.yml', ['repo'])
+        before = doc.to_dict()
+        doc.lint_override(self.fix_get_yml('override.yml')['base'])
+        self.assertEqual(before, doc.to_dict())
+
+    def test_invalid_layer(self):
+        doc = self.load_base('base.yml', ['repo'])
+        with self.assertRaises(TypeError):
+            doc.lint_override('not a layer')
@@ -0,0 +1,33 @@
+from configcrunch import LockViolationError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class LockTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'lock'
+
+    def test_error(self):
+        with self.assertRaises(LockViolationError):
+            self.load_base('base.yml', ['repo'])
+
+    def test_ignore(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], lock_violation='ignore')
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected_ignore.yml')), deep_sort(doc.to_dict()))
+
+    def test_unchanged_value(self):
+        doc = Base.from_yaml(self.fix_get_path('unchanged.yml'))
+        with self.assertRaises(LockViolationError):
+            # more is entirely locked, so adding b is a change
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+
+    def test_unlock_not_allowed(self):
+        with self.assertRaises(LockViolationError):
+            self.load_base('unlock.yml', ['repo'])
+
+    def test_invalid_option(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        with self.assertRaises(ValueError):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], lock_violation='warn')
@@ -0,0 +1,36 @@
+from configcrunch import load_from_manifest, InvalidDocumentError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class ManifestTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'manifest'
+
+    def test_layers(self):
+        doc = load_from_manifest(Base, self.fix_get_path('layers.yml'), variables={'environment': 'prod'})
+        self.assertEqual('from env', doc.internal_get('str_field'))
+        self.assertEqual({'layers': ['base', 'env'], 'profile': 'prod'}, doc.internal_get('more'))
+        # Resolved with the lookup paths of the manifest.
+        self.assertEqual(5, doc.internal_get('int_field'))
+        self.assertEqual([self.fix_get_path('base.yml'), self.fix_get_path('env.yml')],
+                         [path for path, role in doc.merge_sources if role != 'referenced'])
+
+    def test_condition(self):
+        doc = load_from_manifest(Base, self.fix_get_path('layers.yml'), variables={'environment': 'dev'})
+        self.assertIs(True, doc.internal_get('more')['debug'])
+
+    def test_required(self):
+        with self.assertRaises(InvalidDocumentError):
+            load_from_manifest(Base, self.fix_get_path('required.yml'))
+
+    def test_invalid(self):
+        with self.assertRaisesRegex(InvalidDocumentError, 'requierd'):
+            load_from_manifest(Base, self.fix_get_path('invalid.yml'))
+
+    def test_loading_options(self):
+        path = self.fix_get_path('virtual.yml')
+        doc = load_from_manifest(Base, path, virtual_files={path: 'layers: [base.yml, env.yml]\n'}, profile='prod')
+        self.assertEqual('prod', doc.internal_get('more')['profile'])
+        self.assertNotIn('int_field', doc.doc)
@@ -0,0 +1,42 @@
+from configcrunch import load_multiple_yml
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class MergeSourcesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merge_sources'
+
+    def test_from_yaml(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        self.assertEqual([(self.fix_get_path('base.yml'), 'base')], doc.merge_sources)
+
+    def test_load_multiple_yml(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'))
+        self.assertEqual([
+            (self.fix_get_path('base.yml'), 'base'),
+            (self.fix_get_path('override.yml'), 'override'),
+        ], doc.merge_sources)
+
+    def test_resolved(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual([
+            (self.fix_get_path('repo/lowest.yml'), 'referenced'),
+            (self.fix_get_path('repo/lower.yml'), 'referenced'),
+            (self.fix_get_path('base.yml'), 'base'),
+            (self.fix_get_path('override.yml'), 'override'),
+        ], doc.merge_sources)
+
+    def test_overlay(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.overlay(Base.from_yaml(self.fix_get_path('override.yml')))
+        doc.overlay({'str_field': 'from dict'})
+        self.assertEqual([
+            (self.fix_get_path('base.yml'), 'base'),
+            (self.fix_get_path('override.yml'), 'override'),
+        ], doc.merge_sources)
+
+    def test_from_dict(self):
+        self.assertEqual([], Base.from_dict({}).merge_sources)
@@ -0,0 +1,69 @@
+from configcrunch import MergeStats, load_multiple_yml
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class MergeStatsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merge_stats'
+
+    def load(self, **kwargs):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], **kwargs)
+        return doc
+
+    def test_resolution(self):
+        stats = self.load().merge_stats()
+        self.assertIsInstance(stats, MergeStats)
+        self.assertEqual(4, stats.documents_loaded)
+        self.assertEqual(3, stats.refs_resolved)
+        self.assertEqual(1, stats.keys_overridden)
+        self.assertEqual(1, stats.keys_removed)
+        self.assertEqual(1, stats.list_entries_merged)
+        self.assertEqual(0, stats.templates_rendered)
+        self.assertEqual(0, stats.cache_hits)
+
+    def test_identity_map(self):
+        stats = self.load(identity_map=True).merge_stats()
+        self.assertEqual(3, stats.documents_loaded)
+        self.assertEqual(2, stats.refs_resolved)
+        self.assertEqual(1, stats.cache_hits)
+
+    def test_process_vars(self):
+        doc = self.load()
+        doc.process_vars()
+        stats = doc.merge_stats()
+        self.assertEqual(1, stats.templates_rendered)
+        # The counts are totals.
+        self.assertEqual(4, stats.documents_loaded)
+        doc.freeze()
+        self.assertEqual('overridden!', doc['more']['greeting'])
+        self.assertEqual(['a', 'b', 'c'], doc['more']['list'])
+
+    def test_to_dict(self):
+        self.assertEqual({
+            'documents_loaded': 4,
+            'refs_resolved': 3,
+            'keys_overridden': 1,
+            'keys_removed': 1,
+            'list_entries_merged': 1,
+            'templates_rendered': 0,
+            'cache_hits': 0,
+        }, self.load().merge_stats().to_dict())
+
+    def test_snapshot(self):
+        doc = Base.from_dict({'more': {'a': '{{ 1 + 1 }}'}})
+        doc.resolve_and_merge_references([])
+        stats = doc.merge_stats()
+        doc.process_vars()
+        self.assertEqual(0, stats.templates_rendered)
+        self.assertEqual(1, doc.merge_stats().templates_rendered)
+        self.assertEqual(0, doc.merge_stats().documents_loaded)
+
+    def test_load_multiple_yml(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('repo/base/parent.yml'), self.fix_get_path('base.yml'))
+        stats = doc.merge_stats()
+        self.assertEqual(2, stats.documents_loaded)
+        self.assertEqual(1, stats.keys_overridden)
+        self.assertEqual(1, stats.keys_removed)
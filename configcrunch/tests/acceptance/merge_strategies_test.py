@@ -0,0 +1,115 @@
+from schema import Schema, SchemaError, Optional
+
+from configcrunch import YamlConfigDocument, UniqueList, KeyedList, load_multiple_yml
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class Deployment(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "deployment"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            Optional('$ref'): str,
+            Optional('hosts'): UniqueList(str),
+            Optional('services'): KeyedList('name', {
+                'name': str,
+                Optional('port'): int,
+                Optional('env'): UniqueList(str),
+            }),
+            Optional('labels'): [str],
+            Optional('volumes'): {str: {'size': int}},
+        })
+
+
+class ReplacingDeployment(Deployment):
+    @classmethod
+    def merge_strategies(cls):
+        return {**super().merge_strategies(), 'labels': 'replace', 'volumes': 'replace'}
+
+
+class MergeStrategiesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merge_strategies'
+
+    def test_from_schema(self):
+        self.assertEqual({
+            'hosts': 'set',
+            'services': 'keyed:name',
+            'services.*.env': 'set',
+        }, Deployment.merge_strategies())
+
+    def test_resolve(self):
+        doc = Deployment.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual(['a', 'b', 'c'], doc['hosts'])
+        self.assertEqual([
+            {'name': 'web', 'port': 8080, 'env': ['A', 'B']},
+            {'name': 'db', 'port': 5432},
+            {'name': 'cache', 'port': 6379},
+        ], doc['services'])
+        # Lists without a strategy are appended, dicts merged.
+        self.assertEqual(['x', 'y'], doc['labels'])
+        self.assertEqual({'data': {'size': 1}, 'logs': {'size': 2}}, doc['volumes'])
+        self.assertTrue(doc.validate())
+
+    def test_load_multiple_yml(self):
+        doc = load_multiple_yml(Deployment, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual(['b', 'c', 'd'], doc['hosts'])
+        self.assertEqual([
+            {'name': 'web', 'port': 8080, 'env': ['A', 'B']},
+            {'name': 'db', 'port': 5433},
+            {'name': 'cache', 'port': 6379},
+        ], doc['services'])
+
+    def test_overridden_strategies(self):
+        doc = ReplacingDeployment.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual(['y'], doc['labels'])
+        self.assertEqual({'logs': {'size': 2}}, doc['volumes'])
+        self.assertEqual(['a', 'b', 'c'], doc['hosts'])
+
+    def test_invalid_strategy(self):
+        class Invalid(Deployment):
+            @classmethod
+            def merge_strategies(cls):
+                return {'hosts': 'merge'}
+
+        with self.assertRaises(ValueError):
+            load_multiple_yml(Invalid, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'))
+
+    def test_validate(self):
+        with self.assertRaisesRegex(SchemaError, 'more than once'):
+            Deployment.from_dict({'hosts': ['a', 'a']}).validate()
+        with self.assertRaisesRegex(SchemaError, 'more than one entry'):
+            Deployment.from_dict({'services': [{'name': 'a'}, {'name': 'a', 'port': 1}]}).validate()
+        with self.assertRaisesRegex(SchemaError, 'has no key'):
+            Deployment.from_dict({'services': [{'port': 1}]}).validate()
+
+    def test_without_schema(self):
+        class Schemaless(YamlConfigDocument):
+            @classmethod
+            def header(cls) -> str:
+                return "schemaless"
+
+        self.assertEqual({}, Schemaless.merge_strategies())
+        doc = Schemaless.from_dict({'hosts': ['a']})
+        doc.overlay({'hosts': ['a', 'b']})
+        self.assertEqual(['a', 'a', 'b'], doc.internal_get('hosts'))
+
+    def test_derived_once(self):
+        calls = []
+
+        class Counting(Deployment):
+            @classmethod
+            def schema(cls) -> Schema:
+                calls.append(cls)
+                return super().schema()
+
+        Counting.merge_strategies()['hosts'] = 'replace'
+        self.assertEqual('set', Counting.merge_strategies()['hosts'])
+        self.assertEqual(1, len(calls))
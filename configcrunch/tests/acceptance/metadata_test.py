@@ -0,0 +1,34 @@
+from configcrunch import InvalidDocumentError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class BaseWithOwner(Base):
+    @classmethod
+    def metadata_keys(cls):
+        return ['$comment', '$owner']
+
+
+class MetadataTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'metadata'
+
+    def test_comments_removed(self):
+        self.assertDocEqualMerging(
+            'expected.yml',
+            'base.yml',
+            ['repo']
+        )
+
+    def test_custom_metadata_keys(self):
+        doc = BaseWithOwner.from_yaml(self.fix_get_path('base.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertDictEqual(
+            deep_sort(self.fix_get_yml('expected_owner.yml')),
+            deep_sort({'base': doc.to_dict()['base']})
+        )
+
+    def test_invalid(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('invalid.yml', [])
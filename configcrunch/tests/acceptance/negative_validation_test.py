@@ -1,8 +1,34 @@
-from schema import SchemaError
+from schema import Schema, Optional, SchemaError
 
+from configcrunch import DocReference, RuleViolationError
+from configcrunch.tests.fixtures.documents import Base, Level
 from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
 
 
+class ContainerBase(Base):
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema(
+            {
+                Optional('level_array'): DocReference.list_of(Level),
+                Optional('level_dict'): DocReference.map_of(Level),
+            }
+        )
+
+    @classmethod
+    def subdocuments(cls):
+        return [
+            ("level_array[list]", Level),
+            ("level_dict[map]", Level),
+        ]
+
+
+class RuleLevel(Level):
+    @classmethod
+    def validation_rules(cls):
+        return {'named': "name != 'invalid'"}
+
+
 class NegativeValidation(ConfigcrunchTestCase):
     @classmethod
     def fixture_name(cls):
@@ -15,3 +41,25 @@ class NegativeValidation(ConfigcrunchTestCase):
     def test_invalid_subdoc(self):
         doc = self.load_base('negative_subdocument.yml', [])
         self.assertRaises(SchemaError, doc.validate)
+
+    def test_invalid_subdoc_in_list(self):
+        doc = ContainerBase.from_yaml(self.fix_get_path('negative_subdocument_list.yml'))
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(SchemaError) as ctx:
+            doc.validate()
+        self.assertTrue(str(ctx.exception).startswith('[1]: '))
+
+    def test_invalid_subdoc_in_map(self):
+        doc = ContainerBase.from_yaml(self.fix_get_path('negative_subdocument_map.yml'))
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(SchemaError) as ctx:
+            doc.validate()
+        self.assertIn('["b"]: ', str(ctx.exception))
+
+    def test_invalid_subdoc_error_kept(self):
+        docs = [RuleLevel.from_dict({'name': 'valid'}), RuleLevel.from_dict({'name': 'invalid'})]
+        with self.assertRaises(RuleViolationError) as ctx:
+            DocReference.list_of(RuleLevel).validate(docs)
+        self.assertTrue(str(ctx.exception).startswith('[1]: '))
+        self.assertEqual(['named'], ctx.exception.rules)
+        self.assertIsInstance(ctx.exception.__cause__, RuleViolationError)
@@ -0,0 +1,69 @@
+import unittest
+
+import yaml
+
+from configcrunch.tests.fixtures.documents import Base
+
+
+class OrderedBase(Base):
+    @classmethod
+    def key_order(cls):
+        return ['str_field', 'more']
+
+
+BODY = {
+    'str_field': 'hello "world"',
+    'int_field': 12,
+    'more': {
+        'b': 1.5,
+        'a': [1, 'two', {'y': True, 'x': 1e20}],
+        'empty': {},
+        'list': [],
+        'true': 'key',
+        'forced': '{{ int_field|str }}',
+    },
+    'level_direct': {'name': 'lvl'},
+}
+
+EXPECTED = '''base:
+  int_field: 12
+  level_direct:
+    name: "lvl"
+  more:
+    a:
+      - 1
+      - "two"
+      - x: 1.0e+20
+        y: true
+    b: 1.5
+    empty: {}
+    forced: "12"
+    list: []
+    "true": "key"
+  str_field: "hello \\"world\\""
+'''
+
+
+class NormalizeTest(unittest.TestCase):
+    def load(self, doc_type):
+        doc = doc_type.from_dict(BODY)
+        doc.resolve_and_merge_references([])
+        doc.process_vars()
+        return doc
+
+    def test_normalize(self):
+        self.assertEqual(EXPECTED, self.load(Base).normalize())
+
+    def test_frozen(self):
+        doc = self.load(Base)
+        doc.freeze()
+        self.assertEqual(EXPECTED, doc.normalize())
+
+    def test_round_trip(self):
+        doc = self.load(Base)
+        self.assertEqual(doc.to_dict(), yaml.safe_load(doc.normalize()))
+
+    def test_key_order(self):
+        lines = self.load(OrderedBase).normalize().splitlines()
+        top_level = [line.split(':')[0] for line in lines if line.startswith('  ') and not line.startswith('   ')]
+        self.assertEqual(['str_field', 'more', 'int_field', 'level_direct'], top_level)
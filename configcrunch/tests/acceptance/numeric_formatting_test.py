@@ -0,0 +1,87 @@
+import locale
+
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+# Locales with "," as decimal point, the first one available is used.
+LOCALES = ['de_DE.UTF-8', 'de_DE.utf8', 'fr_FR.UTF-8', 'fr_FR.utf8']
+
+BODY = {
+    'int_field': 12345678901234567,
+    'more': {
+        'whole': 2.0,
+        'ratio': 0.1 + 0.2,
+        'big': 1e20,
+        'small': 1.5e-7,
+        'negative': -0.5,
+        'sum': '{{ 0.25 + 0.5 }}',
+        'grouped': '{{ 1234567.5|format_number }}',
+    },
+}
+
+
+class NumericFormattingTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'numeric_formatting'
+
+    def setUp(self):
+        super().setUp()
+        self.previous_locale = locale.setlocale(locale.LC_ALL)
+        for name in LOCALES:
+            try:
+                locale.setlocale(locale.LC_ALL, name)
+                break
+            except locale.Error:
+                pass
+
+    def tearDown(self):
+        locale.setlocale(locale.LC_ALL, self.previous_locale)
+        super().tearDown()
+
+    def load(self):
+        doc = Base.from_dict(BODY)
+        doc.resolve_and_merge_references([])
+        doc.process_vars()
+        return doc
+
+    def test_to_yaml(self):
+        text = self.load().to_yaml()
+        self.assertTrue(text.isascii())
+        for line in [
+            'int_field: 12345678901234567\n',
+            'whole: 2.0\n',
+            'ratio: 0.30000000000000004\n',
+            'big: 1.0e+20\n',
+            'small: 1.5e-7\n',
+            'negative: -0.5\n',
+        ]:
+            self.assertIn(line, text)
+
+    def test_str(self):
+        doc = self.load()
+        doc.freeze()
+        text = str(doc)
+        self.assertTrue(text.isascii())
+        self.assertIn("'whole': 2.0", text)
+        self.assertIn("'ratio': 0.30000000000000004", text)
+        self.assertIn("'big': 1e+20", text)
+
+    def test_to_env(self):
+        env = self.load().to_env(prefix='app')
+        self.assertEqual('12345678901234567', env['APP__INT_FIELD'])
+        self.assertEqual('2.0', env['APP__MORE__WHOLE'])
+        self.assertEqual('0.30000000000000004', env['APP__MORE__RATIO'])
+        self.assertEqual('1.0e+20', env['APP__MORE__BIG'])
+        self.assertEqual('1.5e-7', env['APP__MORE__SMALL'])
+
+    def test_templates(self):
+        more = self.load().internal_get('more')
+        self.assertEqual('0.75', more['sum'])
+        self.assertEqual('1,234,567.5', more['grouped'])
+
+    def test_read_back(self):
+        doc = self.load()
+        for key, value in BODY['more'].items():
+            if isinstance(value, float):
+                self.assertEqual(value, float(doc.to_env(prefix='app')['APP__MORE__' + key.upper()]))
@@ -0,0 +1,54 @@
+import os
+
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class OriginsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'origins'
+
+    def assertOrigin(self, expected_file, actual):
+        self.assertEqual(os.path.abspath(self.fix_get_path(expected_file)), os.path.abspath(actual))
+
+    def test_origins(self):
+        origins = self.load_base('base.yml', ['repo']).origins()
+        self.assertEqual(sorted(origins.keys()), list(origins.keys()))
+        self.assertOrigin('base.yml', origins['str_field'])
+        self.assertOrigin('base.yml', origins['more.debug'])
+        self.assertOrigin('repo/defaults.yml', origins['int_field'])
+        self.assertOrigin('repo/defaults.yml', origins['more.list'])
+        self.assertOrigin('repo/defaults.yml', origins['more.from_default'])
+        self.assertOrigin('base.yml', origins['level_direct.name'])
+        self.assertOrigin('repo/level.yml', origins['level_direct.more.x'])
+
+    def test_lines(self):
+        origins = self.load_base('base.yml', ['repo']).origins(lines=True)
+        for path, file, line in [
+            ('str_field', 'base.yml', 3),
+            ('more.debug', 'base.yml', 5),
+            ('int_field', 'repo/defaults.yml', 2),
+            ('more.list', 'repo/defaults.yml', 6),
+            ('level_direct.name', 'base.yml', 8),
+            ('level_direct.more.x', 'repo/level.yml', 4),
+        ]:
+            self.assertOrigin(file, origins[path][0])
+            self.assertEqual(line, origins[path][1], path)
+
+    def test_line_of_default(self):
+        origins = self.load_base('base.yml', ['repo']).origins(lines=True)
+        self.assertOrigin('repo/defaults.yml', origins['more.from_default'][0])
+        self.assertIsNone(origins['more.from_default'][1])
+
+    def test_not_from_file(self):
+        doc = Base.from_dict({'str_field': 'value', 'more': {'x': 1}})
+        doc.resolve_and_merge_references([])
+        self.assertEqual({'str_field': None, 'more.x': None}, doc.origins(lines=True))
+
+    def test_lines_of_virtual_files(self):
+        path = os.path.abspath(self.fix_get_path('virtual.yml'))
+        virtual_files = {path: 'base:\n  # a comment\n  str_field: virtual\n'}
+        doc = Base.from_yaml(path, virtual_files=virtual_files)
+        doc.resolve_and_merge_references([], virtual_files=virtual_files)
+        self.assertEqual({'str_field': (path, 3)}, doc.origins(lines=True, virtual_files=virtual_files))
@@ -0,0 +1,43 @@
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+OVERLAY = {
+    'str_field': 'from overlay',
+    'int_field': 2,
+    'more': {
+        'a': '$remove',
+        'd': 'from overlay'
+    }
+}
+
+
+class OverlayTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'overlay'
+
+    def test_overlay_dict(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.overlay(OVERLAY)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
+
+    def test_overlay_resolved(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.overlay(OVERLAY)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+
+    def test_compose(self):
+        layer = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc = Base.compose([layer, Base.from_dict(OVERLAY)])
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+        self.assertIsNot(layer, doc)
+        self.assertEqual('from base', layer.internal_get('str_field'))
+
+    def test_compose_empty(self):
+        with self.assertRaises(ValueError):
+            Base.compose([])
@@ -0,0 +1,47 @@
+import os
+import sys
+import tempfile
+import zipfile
+
+from configcrunch import _lookup, ReferencedDocumentNotFound
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+LOOKUP_PATH = 'pkg://configcrunch.tests.fixtures/package_lookup/repo'
+
+
+class PackageLookupTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'package_lookup'
+
+    def setUp(self):
+        _lookup._package_dirs.clear()
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        self.tmp = tmp.name
+
+    def load(self, lookup_path):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([lookup_path], cache_dir=os.path.join(self.tmp, 'cache'))
+        return doc
+
+    def test_package(self):
+        doc = self.load(LOOKUP_PATH)
+        self.assertEqual(1, doc.internal_get('int_field'))
+        self.assertEqual('local', doc.internal_get('str_field'))
+        self.assertTrue(os.path.samefile(self.fix_get_path('repo'), _lookup.package_dir(LOOKUP_PATH, None)))
+
+    def test_zipped_package(self):
+        archive = os.path.join(self.tmp, 'zipped.zip')
+        with zipfile.ZipFile(archive, 'w') as z:
+            z.writestr('zipped_configs/__init__.py', '')
+            z.writestr('zipped_configs/defaults/shared.yml', 'base:\n  int_field: 2\n')
+        sys.path.insert(0, archive)
+        self.addCleanup(sys.path.remove, archive)
+        self.addCleanup(sys.modules.pop, 'zipped_configs', None)
+        self.assertEqual(2, self.load('pkg://zipped_configs/defaults').internal_get('int_field'))
+
+    def test_unknown_package(self):
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.load('pkg://configcrunch_no_such_package/configs')
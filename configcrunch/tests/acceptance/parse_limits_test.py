@@ -0,0 +1,40 @@
+from configcrunch import DocumentLimitError, InvalidDocumentError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class ParseLimitsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'parse_limits'
+
+    def test_within_limits(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), max_file_size=1000, max_depth=4, max_nodes=7)
+        self.assertEqual('string', doc.internal_get('str_field'))
+        self.assertEqual(['a', 'b'], doc.internal_get('more')['list'])
+
+    def test_max_file_size(self):
+        with self.assertRaises(DocumentLimitError):
+            Base.from_yaml(self.fix_get_path('base.yml'), max_file_size=10)
+
+    def test_max_depth(self):
+        Base.from_yaml(self.fix_get_path('deep.yml'), max_depth=6)
+        with self.assertRaises(DocumentLimitError):
+            Base.from_yaml(self.fix_get_path('deep.yml'), max_depth=5)
+
+    def test_max_nodes(self):
+        with self.assertRaises(DocumentLimitError):
+            Base.from_yaml(self.fix_get_path('base.yml'), max_nodes=6)
+
+    def test_max_nodes_aliases(self):
+        with self.assertRaises(DocumentLimitError):
+            Base.from_yaml(self.fix_get_path('aliases.yml'), max_nodes=10000)
+
+    def test_limit_error_is_invalid_document_error(self):
+        with self.assertRaises(InvalidDocumentError):
+            Base.from_yaml(self.fix_get_path('deep.yml'), max_depth=2)
+
+    def test_referenced_files(self):
+        doc = Base.from_dict({'$ref': '/deep'})
+        with self.assertRaises(DocumentLimitError):
+            doc.resolve_and_merge_references([self.fix_get_path('')], max_depth=3)
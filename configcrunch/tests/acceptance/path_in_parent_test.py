@@ -0,0 +1,34 @@
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class PathInParentTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'path_in_parent'
+
+    def test_path_in_parent(self):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertIsNone(doc.path_in_parent())
+        self.assertEqual('level_dict/xyz', doc['level_dict']['xyz'].path_in_parent())
+        self.assertEqual('level_array/0', doc['level_array'][0].path_in_parent())
+        self.assertEqual('level_direct', doc['level_direct'].path_in_parent())
+        self.assertEqual('base_ref', doc['level_direct']['base_ref'].path_in_parent())
+
+    def test_parent(self):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertIs(doc, doc['level_direct'].parent())
+        self.assertIs(doc['level_direct'], doc['level_direct']['base_ref'].parent())
+
+    def test_template(self):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([])
+        doc.process_vars()
+        self.assertEqual('level_dict/xyz', doc['level_dict']['xyz']['more'])
+
+    def test_error_message(self):
+        doc = Base.from_yaml(self.fix_get_path('broken.yml'))
+        with self.assertRaisesRegex(ValueError, '^In the sub-document at level_direct: '):
+            doc.resolve_and_merge_references([])
@@ -0,0 +1,57 @@
+import unittest
+
+from configcrunch import register_policy, unregister_policy, PolicyViolationError, ConfigcrunchError
+from configcrunch.tests.fixtures.documents import Base, Level
+
+
+def make_doc(body):
+    doc = Base.from_dict(body)
+    doc.resolve_and_merge_references([])
+    return doc
+
+
+class PoliciesTest(unittest.TestCase):
+    def tearDown(self):
+        for name in ['expression', 'callable', 'messages', 'level']:
+            unregister_policy(name)
+
+    def test_expression(self):
+        register_policy('expression', "not (more.privileged and str_field == 'prod')")
+        make_doc({'str_field': 'dev', 'more': {'privileged': True}})
+        with self.assertRaises(PolicyViolationError) as ctx:
+            make_doc({'str_field': 'prod', 'more': {'privileged': True}})
+        self.assertEqual('expression', ctx.exception.violations[0][0])
+        self.assertIn('expression', str(ctx.exception))
+
+    def test_callable(self):
+        register_policy('callable', lambda doc: doc['int_field'] < 10)
+        register_policy('messages', lambda doc: None if doc['int_field'] < 20 else ['too large', 'really'])
+        make_doc({'int_field': 5})
+        with self.assertRaises(PolicyViolationError) as ctx:
+            make_doc({'int_field': 25})
+        self.assertEqual([
+            ('callable', '', 'The policy was violated'),
+            ('messages', '', 'too large'),
+            ('messages', '', 'really'),
+        ], ctx.exception.violations)
+
+    def test_doc_type(self):
+        register_policy('level', lambda doc: None if doc['name'] != 'forbidden' else 'forbidden name', Level)
+        doc = Base.from_dict({'level_direct': {'name': 'forbidden'}, 'level_array': [{'name': 'ok'}]})
+        with self.assertRaises(PolicyViolationError) as ctx:
+            doc.resolve_and_merge_references([])
+        self.assertEqual([('level', 'level_direct', 'forbidden name')], ctx.exception.violations)
+
+    def test_check_policies(self):
+        register_policy('callable', lambda doc: False)
+        doc = Base.from_dict({})
+        self.assertEqual([('callable', '', 'The policy was violated')], doc.check_policies())
+
+    def test_unregister(self):
+        register_policy('callable', lambda doc: False)
+        self.assertTrue(unregister_policy('callable'))
+        self.assertFalse(unregister_policy('callable'))
+        make_doc({})
+
+    def test_is_configcrunch_error(self):
+        self.assertTrue(issubclass(PolicyViolationError, ConfigcrunchError))
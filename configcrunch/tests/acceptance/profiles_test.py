@@ -0,0 +1,30 @@
+from configcrunch import load_multiple_yml
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class ProfilesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'profiles'
+
+    def load(self, profile):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), profile=profile)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], profile=profile)
+        return doc
+
+    def test_no_profile(self):
+        # Without a profile, profiles is not reserved and merged like any other key.
+        doc = self.load(None)
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected_none.yml')), deep_sort(doc.to_dict()))
+
+    def test_profile(self):
+        doc = self.load('dev')
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected_dev.yml')), deep_sort(doc.to_dict()))
+
+    def test_load_multiple_yml(self):
+        doc = load_multiple_yml(
+            Base, self.fix_get_path('base.yml'), self.fix_get_path('override.yml'), profile='prod'
+        )
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], profile='prod')
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected_multiple_prod.yml')), deep_sort(doc.to_dict()))
@@ -0,0 +1,41 @@
+from configcrunch import load_multiple_yml
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class ProgressTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'progress'
+
+    def test_loading_and_merging(self):
+        events = []
+        doc = load_multiple_yml(Base, self.fix_get_path('base.yml'), progress=lambda *args: events.append(args))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')],
+                                         progress=lambda *args: events.append(args))
+        self.assertEqual(12, doc['int_field'])
+        self.assertEqual(('file_loaded', 1), events[0][:2])
+        self.assertTrue(events[0][2].endswith('base.yml'))
+        loaded = [e for e in events if e[0] == 'file_loaded']
+        self.assertEqual([1, 1], [e[1] for e in loaded])
+        self.assertTrue(loaded[1][2].endswith('reference.yml'))
+        merged = [e for e in events if e[0] == 'document_merged']
+        self.assertEqual(list(range(1, len(merged) + 1)), [e[1] for e in merged])
+        self.assertNotEqual([], merged)
+
+    def test_template_rendered(self):
+        events = []
+        doc = Base.from_dict({'str_field': 'plain', 'more': {'a': '{{ 1 + 1 }}', 'b': '{{ 2 + 2 }}'}})
+        doc.resolve_and_merge_references([])
+        doc.process_vars(progress=lambda *args: events.append(args))
+        self.assertEqual('2', str(doc['more']['a']))
+        self.assertEqual({'template_rendered'}, {e[0] for e in events})
+        self.assertEqual([1, 2], sorted(e[1] for e in events))
+        self.assertEqual(['more.a', 'more.b'], sorted(e[2] for e in events))
+
+    def test_callback_error_aborts(self):
+        def fail(*args):
+            raise RuntimeError('abort')
+
+        with self.assertRaises(RuntimeError):
+            Base.from_yaml(self.fix_get_path('base.yml'), progress=fail)
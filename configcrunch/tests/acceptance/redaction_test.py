@@ -0,0 +1,156 @@
+import json
+import unittest
+from typing import List, Tuple, Type
+
+import yaml
+from schema import Schema, SchemaError, Optional
+
+from configcrunch import Coerce, DocReference, Secret, YamlConfigDocument
+
+
+class Database(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "database"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            'host': str,
+            'password': Secret(str),
+        })
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+
+class Service(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "service"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({
+            'name': str,
+            Optional('api'): {str: str},
+            Optional('tokens'): [str],
+            Optional('databases'): {str: DocReference(Database)},
+        })
+
+    @classmethod
+    def subdocuments(cls) -> List[Tuple[str, Type[YamlConfigDocument]]]:
+        return [("databases[]", Database)]
+
+    @classmethod
+    def secret_paths(cls):
+        return ['api.key', 'tokens']
+
+
+class Device(YamlConfigDocument):
+    @classmethod
+    def header(cls) -> str:
+        return "device"
+
+    @classmethod
+    def schema(cls) -> Schema:
+        return Schema({'pin': Coerce('int')})
+
+    @classmethod
+    def subdocuments(cls):
+        return []
+
+    @classmethod
+    def secret_paths(cls):
+        return ['pin']
+
+
+BODY = {
+    'name': 'web',
+    'api': {'key': 'api-secret', 'url': 'https://example.org'},
+    'tokens': ['token-secret'],
+    'databases': {'main': {'host': 'db', 'password': 'db-secret'}},
+}
+
+
+def make_doc():
+    doc = Service.from_dict(BODY)
+    doc.resolve_and_merge_references([])
+    return doc
+
+
+class RedactionTest(unittest.TestCase):
+    def assertRedacted(self, text):
+        self.assertNotIn('secret', text)
+        self.assertIn('****', text)
+
+    def test_to_yaml(self):
+        text = make_doc().to_yaml()
+        self.assertRedacted(text)
+        self.assertEqual({'service': {
+            'name': 'web',
+            'api': {'key': '****', 'url': 'https://example.org'},
+            'tokens': '****',
+            'databases': {'main': {'host': 'db', 'password': '****'}},
+        }}, yaml.safe_load(text))
+
+    def test_to_json(self):
+        self.assertRedacted(make_doc().to_json())
+        self.assertEqual('****', json.loads(make_doc().to_json())['service']['databases']['main']['password'])
+
+    def test_to_env(self):
+        env = make_doc().to_env()
+        self.assertEqual('****', env['API__KEY'])
+        self.assertEqual('****', env['TOKENS'])
+        self.assertEqual('****', env['DATABASES__MAIN__PASSWORD'])
+        self.assertEqual('db', env['DATABASES__MAIN__HOST'])
+        self.assertEqual('db-secret', make_doc().to_env(redact=False)['DATABASES__MAIN__PASSWORD'])
+
+    def test_unredacted(self):
+        doc = make_doc()
+        self.assertEqual({'service': BODY}, yaml.safe_load(doc.to_yaml(redact=False)))
+        self.assertEqual({'service': BODY}, json.loads(doc.to_json(redact=False)))
+        self.assertEqual({'service': BODY}, doc.to_dict())
+        self.assertIn('db-secret', doc.normalize())
+
+    def test_str(self):
+        doc = make_doc()
+        doc.freeze()
+        self.assertRedacted(str(doc))
+        self.assertRedacted(repr(doc['databases']['main']))
+        self.assertEqual('api-secret', doc['api']['key'])
+
+    def test_annotated_yaml(self):
+        self.assertRedacted(make_doc().render_annotated_yaml())
+
+    def test_lint(self):
+        findings = make_doc().lint_override({'tokens': ['$remove::token-secret2']})
+        self.assertEqual(1, len(findings))
+        self.assertRedacted(findings[0][2])
+
+    def test_validation_error(self):
+        doc = Database.from_dict({'host': 'db', 'password': 12345678})
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(SchemaError) as ctx:
+            doc.validate()
+        self.assertNotIn('12345678', str(ctx.exception))
+
+    def test_validate(self):
+        self.assertTrue(make_doc().validate())
+
+    def test_schema_error_at_secret_path(self):
+        doc = Service.from_dict({'name': 'web', 'api': {'key': 987654321}})
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(SchemaError) as ctx:
+            doc.validate()
+        self.assertNotIn('987654321', str(ctx.exception))
+        self.assertNotIn('987654321', ctx.exception.code)
+        self.assertIn('****', str(ctx.exception))
+
+    def test_coercion_error(self):
+        doc = Device.from_dict({'pin': 'pin-secret'})
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(SchemaError) as ctx:
+            doc.validate()
+        self.assertRedacted(str(ctx.exception))
@@ -0,0 +1,28 @@
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class RefFirstTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'ref_first'
+
+    def test_first_existing(self):
+        self.assertDocEqualMerging(
+            'expected_linux.yml',
+            'linux.yml',
+            ['repo']
+        )
+
+    def test_condition(self):
+        self.assertDocEqualMerging(
+            'expected_windows.yml',
+            'windows.yml',
+            ['repo']
+        )
+
+    def test_none_existing(self):
+        self.assertDocEqualMerging(
+            'expected_none.yml',
+            'none.yml',
+            ['repo']
+        )
@@ -0,0 +1,24 @@
+from configcrunch import InvalidDocumentError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class RefParametersTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'ref_parameters'
+
+    def test_parameters(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.process_vars()
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
+
+    def test_parameter_defaults(self):
+        doc = self.load_base('defaults.yml', ['repo'])
+        doc.process_vars()
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected_defaults.yml')), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
+
+    def test_missing_path(self):
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('invalid.yml', ['repo'])
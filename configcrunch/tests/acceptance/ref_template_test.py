@@ -0,0 +1,31 @@
+from configcrunch import ResolutionContext, ReferencedDocumentNotFound
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class RefTemplateTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'ref_template'
+
+    def test_vars(self):
+        doc = self.load_base('vars.yml', ['repo'])
+        self.assertEqual(1, doc.internal_get('int_field'))
+        self.assertFalse(doc.internal_contains('$ref'))
+
+    def test_document_keys(self):
+        doc = self.load_base('key.yml', ['repo'])
+        self.assertEqual(2, doc.internal_get('int_field'))
+
+    def test_context_globals(self):
+        context = ResolutionContext([self.fix_get_path('repo')], globals={'region': 'eu'})
+        doc = context.load(Base, self.fix_get_path('globals.yml'))
+        self.assertEqual(3, doc.internal_get('int_field'))
+
+    def test_parent(self):
+        doc = self.load_base('parent.yml', ['repo'])
+        self.assertEqual(1, doc.internal_get('level_direct').internal_get('base_ref').internal_get('int_field'))
+
+    def test_undefined_variable(self):
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.load_base('globals.yml', ['repo'])
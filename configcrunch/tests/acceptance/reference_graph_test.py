@@ -0,0 +1,60 @@
+import os
+
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class ReferenceGraphTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_one_ref'
+
+    def test_dot(self):
+        doc = self.load_base('base.yml', ['repo1'])
+        base = self.fix_get_path('base.yml')
+        reference = os.path.join(self.fix_get_path('repo1'), 'folder', 'reference.yml')
+        self.assertEqual(
+            'digraph references {\n'
+            f'    n0 [label="Base\\n{base}", shape=box];\n'
+            f'    n1 [label="{reference}", shape=note];\n'
+            f'    n0 -> n1 [label="$ref /folder/reference (lookup path: {self.fix_get_path("repo1")})"];\n'
+            '}\n',
+            doc.render_reference_graph()
+        )
+
+    def test_mermaid(self):
+        doc = self.load_base('base.yml', ['repo1'])
+        graph = doc.render_reference_graph("mermaid")
+        self.assertTrue(graph.startswith('graph TD\n'))
+        self.assertIn('n0 -->|"$ref /folder/reference', graph)
+
+    def test_invalid_format(self):
+        doc = self.load_base('base.yml', ['repo1'])
+        with self.assertRaises(ValueError):
+            doc.render_reference_graph("svg")
+
+    def test_reference_graph(self):
+        doc = self.load_base('base.yml', ['repo1'])
+        reference = os.path.join(self.fix_get_path('repo1'), 'folder', 'reference.yml')
+        graph = doc.reference_graph()
+        self.assertEqual([
+            {'id': 'n0', 'kind': 'document', 'type': 'Base', 'file': self.fix_get_path('base.yml'), 'document': doc},
+            {'id': 'n1', 'kind': 'file', 'file': reference},
+        ], graph['nodes'])
+        self.assertEqual([
+            {'from': 'n0', 'to': 'n1', 'kind': 'ref', 'reference': '/folder/reference',
+             'lookup_path': self.fix_get_path('repo1')},
+        ], graph['edges'])
+
+    def test_reference_graph_subdocuments(self):
+        doc = Base.from_dict({'level_direct': {'name': 'direct'}, 'level_array': [{'name': 'first'}]})
+        doc.resolve_and_merge_references([])
+        graph = doc.reference_graph()
+        self.assertEqual(['document', 'document', 'document'], [n['kind'] for n in graph['nodes']])
+        self.assertIsNone(graph['nodes'][0]['file'])
+        self.assertEqual('Level', graph['nodes'][1]['type'])
+        self.assertEqual([
+            {'from': 'n0', 'to': 'n1', 'kind': 'subdocument', 'path': 'level_array/0'},
+            {'from': 'n0', 'to': 'n2', 'kind': 'subdocument', 'path': 'level_direct'},
+        ], graph['edges'])
+        self.assertEqual('direct', graph['nodes'][2]['document']['name'])
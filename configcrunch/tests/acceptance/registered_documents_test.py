@@ -0,0 +1,80 @@
+from configcrunch import register_document, unregister_document, ReferencedDocumentNotFound, \
+    InvalidDocumentError, _main
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+DEFAULTS_YAML = """
+base:
+    int_field: 1
+    more:
+      a: from registered
+      b: from registered
+"""
+
+DEFAULTS_DICT = {
+    'base': {
+        'int_field': 1,
+        'more': {
+            'a': 'from registered',
+            'b': 'from registered'
+        }
+    }
+}
+
+
+class RegisteredDocumentsTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'registered_documents'
+
+    def tearDown(self):
+        unregister_document('defaults/base')
+
+    def assertExpected(self, expected, input='base.yml', lookup_paths=None):
+        doc = self.load_base(input, lookup_paths or [])
+        self.assertDictEqual(deep_sort(self.fix_get_yml(expected)), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
+
+    def test_yaml(self):
+        register_document('defaults/base', DEFAULTS_YAML)
+        self.assertExpected('expected.yml')
+
+    def test_dict(self):
+        register_document('/defaults/base', DEFAULTS_DICT)
+        self.assertExpected('expected.yml')
+
+    def test_document(self):
+        register_document('defaults/base', Base.from_dict(DEFAULTS_DICT['base']))
+        self.assertExpected('expected.yml')
+
+    def test_files_take_priority(self):
+        register_document('defaults/base', DEFAULTS_YAML)
+        self.assertExpected('expected_with_file.yml', lookup_paths=['repo'])
+
+    def test_ref_first(self):
+        register_document('defaults/base', DEFAULTS_YAML)
+        self.assertExpected('expected.yml', input='ref_first.yml')
+
+    def test_unregister(self):
+        register_document('defaults/base', DEFAULTS_YAML)
+        self.assertTrue(unregister_document('defaults/base'))
+        self.assertFalse(unregister_document('defaults/base'))
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.load_base('base.yml', [])
+
+    def test_invalid_yaml(self):
+        with self.assertRaises(InvalidDocumentError):
+            register_document('defaults/base', 'base: [')
+
+    def test_per_interpreter(self):
+        register_document('defaults/base', DEFAULTS_YAML)
+        # Registered documents are stored in the registry of the interpreter, errors accessing it are raised.
+        registry = _main._interpreter_registry
+        del _main._interpreter_registry
+        try:
+            with self.assertRaises(AttributeError):
+                self.load_base('base.yml', [])
+        finally:
+            _main._interpreter_registry = registry
+        self.assertExpected('expected.yml')
@@ -0,0 +1,110 @@
+import io
+import tempfile
+from unittest import mock
+from urllib.error import HTTPError, URLError
+
+from configcrunch import _lookup, ReferencedDocumentNotFound
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+BASE_URL = 'https://configs.example.org/published'
+REMOTE_FILE = BASE_URL + '/shared/base.yml'
+
+
+class FakeResponse(io.BytesIO):
+    def __init__(self, content, headers):
+        super().__init__(content)
+        self.headers = headers
+
+
+class FakeServer:
+    """Serves files by URL, with ETags. If offline, all requests fail."""
+    def __init__(self):
+        self.files = {}
+        self.offline = False
+        self.requests = []
+
+    def urlopen(self, request, timeout=None):
+        self.requests.append((request.full_url, request.get_header('If-none-match')))
+        if self.offline:
+            raise URLError('offline')
+        if request.full_url not in self.files:
+            raise HTTPError(request.full_url, 404, 'Not Found', {}, None)
+        content, etag = self.files[request.full_url]
+        if request.get_header('If-none-match') == etag:
+            raise HTTPError(request.full_url, 304, 'Not Modified', {}, None)
+        return FakeResponse(content.encode('utf-8'), {'ETag': etag})
+
+
+class RemoteLookupTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'remote_lookup'
+
+    def setUp(self):
+        self.server = FakeServer()
+        self.server.files[REMOTE_FILE] = ('base:\n  str_field: remote\n  int_field: 1\n', '"v1"')
+        patcher = mock.patch.object(_lookup, 'urlopen', self.server.urlopen)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+        cache = tempfile.TemporaryDirectory()
+        self.addCleanup(cache.cleanup)
+        self.cache_dir = cache.name
+        self.new_process()
+
+    def new_process(self):
+        # Files are only revalidated once per process.
+        _lookup._checked_urls.clear()
+
+    def load(self):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'), cache_dir=self.cache_dir)
+        doc.resolve_and_merge_references([BASE_URL, self.fix_get_path('repo')], cache_dir=self.cache_dir)
+        return doc
+
+    def test_fetch(self):
+        doc = self.load()
+        self.assertEqual('local', doc.internal_get('str_field'))
+        self.assertEqual(1, doc.internal_get('int_field'))
+        edges = [e for e in doc.reference_graph()['edges'] if e['kind'] == 'ref']
+        self.assertEqual([BASE_URL], [e['lookup_path'] for e in edges])
+        self.assertTrue(any(path.startswith(self.cache_dir) for path, _ in doc.merge_sources))
+
+    def test_revalidate(self):
+        self.load()
+        self.new_process()
+        self.assertEqual(1, self.load().internal_get('int_field'))
+        self.assertIn((REMOTE_FILE, '"v1"'), self.server.requests)
+        self.server.files[REMOTE_FILE] = ('base:\n  int_field: 2\n', '"v2"')
+        self.new_process()
+        self.assertEqual(2, self.load().internal_get('int_field'))
+
+    def test_once_per_process(self):
+        self.load()
+        count = len(self.server.requests)
+        self.load()
+        self.assertEqual(count, len(self.server.requests))
+
+    def test_offline_fallback(self):
+        self.load()
+        self.new_process()
+        self.server.offline = True
+        self.assertEqual(1, self.load().internal_get('int_field'))
+
+    def test_offline_without_cache(self):
+        self.server.offline = True
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.load()
+
+    def test_removed(self):
+        self.load()
+        self.new_process()
+        del self.server.files[REMOTE_FILE]
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.load()
+
+    def test_other_cache_dir(self):
+        self.load()
+        other = tempfile.TemporaryDirectory()
+        self.addCleanup(other.cleanup)
+        self.cache_dir = other.name
+        self.assertEqual(1, self.load().internal_get('int_field'))
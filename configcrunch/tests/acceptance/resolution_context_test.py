@@ -0,0 +1,60 @@
+from configcrunch import ResolutionContext, register_document, unregister_document, ReferencedDocumentNotFound
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class ResolutionContextTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'resolution_context'
+
+    def setUp(self):
+        super().setUp()
+        self.tenant_a = ResolutionContext([self.fix_get_path('tenant_a')], globals={'tenant': 'a'})
+        self.tenant_b = ResolutionContext([self.fix_get_path('tenant_b')], globals={'tenant': 'b'})
+
+    def test_lookup_paths_and_globals(self):
+        for context, tenant, int_field in [(self.tenant_a, 'a', 1), (self.tenant_b, 'b', 2)]:
+            with self.subTest(tenant=tenant):
+                doc = context.load(Base, self.fix_get_path('service.yml'))
+                context.process_vars(doc)
+                self.assertEqual({'str_field': tenant, 'int_field': int_field}, doc.to_dict())
+                self.assertValidDoc(doc)
+
+    def test_globals_kwarg_takes_priority(self):
+        doc = self.tenant_a.load(Base, self.fix_get_path('service.yml'))
+        self.tenant_a.process_vars(doc, globals={'tenant': 'override'})
+        self.assertEqual('override', doc.internal_get('str_field'))
+
+    def test_resolve(self):
+        doc = Base.from_yaml(self.fix_get_path('service.yml'))
+        self.assertIs(doc, self.tenant_b.resolve(doc))
+        self.assertEqual(2, doc.internal_get('int_field'))
+
+    def test_registered_documents_are_isolated(self):
+        self.tenant_a.register_document('extra/base', 'base: {int_field: 10}')
+        doc = self.tenant_a.load(Base, self.fix_get_path('extra.yml'))
+        self.assertEqual({'str_field': 'from service', 'int_field': 10}, doc.to_dict())
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.tenant_b.load(Base, self.fix_get_path('extra.yml'))
+        self.assertTrue(self.tenant_a.unregister_document('extra/base'))
+        self.assertFalse(self.tenant_a.unregister_document('extra/base'))
+
+    def test_context_documents_take_priority(self):
+        register_document('extra/base', 'base: {int_field: 10}')
+        try:
+            self.tenant_a.register_document('extra/base', 'base: {int_field: 20}')
+            doc_a = self.tenant_a.load(Base, self.fix_get_path('extra.yml'))
+            doc_b = self.tenant_b.load(Base, self.fix_get_path('extra.yml'))
+            self.assertEqual(20, doc_a.internal_get('int_field'))
+            self.assertEqual(10, doc_b.internal_get('int_field'))
+        finally:
+            unregister_document('extra/base')
+
+    def test_registered_templates_are_isolated(self):
+        self.tenant_a.register_template('tenant_template', {'int_field': 30})
+        doc = self.tenant_a.load(Base, self.fix_get_path('extends.yml'))
+        self.assertEqual({'str_field': 'from service', 'int_field': 30}, doc.to_dict())
+        with self.assertRaises(ReferencedDocumentNotFound):
+            self.tenant_b.load(Base, self.fix_get_path('extends.yml'))
+        self.assertTrue(self.tenant_a.unregister_template('tenant_template'))
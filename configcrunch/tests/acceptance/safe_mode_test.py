@@ -0,0 +1,60 @@
+from configcrunch import VariableProcessingError, InvalidDocumentError, variable_helper
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class SafeModeTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'safe_mode'
+
+    def load(self, more):
+        doc = Base.from_dict({
+            'str_field': 'value',
+            'more': more,
+            'level_direct': {'name': '{{ parent().str_field }} at {{ path_in_parent() }}'},
+        })
+        doc.resolve_and_merge_references([])
+        return doc
+
+    def test_builtins(self):
+        doc = self.load({'upper': '{{ str_field|upper }}', 'count': '{{ [1, 2]|length }}'})
+        doc.process_vars(safe=True)
+        doc.freeze()
+        self.assertEqual('VALUE', doc['more']['upper'])
+        self.assertEqual(2, doc['more']['count'])
+        self.assertEqual('value at level_direct', doc['level_direct']['name'])
+
+    def test_helper_forbidden(self):
+        doc = self.load({'helper': '{{ simple_helper() }}'})
+        with self.assertRaises(VariableProcessingError) as ctx:
+            doc.process_vars(safe=True)
+        self.assertIn('safe mode', str(ctx.exception.__cause__))
+
+    def test_helper_of_parent_forbidden(self):
+        doc = Base.from_dict({'level_direct': {'name': '{{ parent().simple_helper() }}'}})
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(VariableProcessingError):
+            doc.process_vars(safe=True)
+
+    def test_not_safe_by_default(self):
+        doc = self.load({'helper': '{{ simple_helper() }}'})
+        doc.process_vars()
+        doc.freeze()
+        self.assertEqual('simple', doc['more']['helper'])
+
+    def test_stays_safe(self):
+        @variable_helper
+        def extra():
+            return 'extra'
+
+        doc = self.load({})
+        doc.process_vars(safe=True)
+        self.assertEqual('VALUE', doc.process_vars_for('{{ str_field|upper }}', []))
+        with self.assertRaises(ValueError):
+            doc.process_vars_for('{{ extra() }}', [extra])
+
+    def test_ref_target(self):
+        doc = Base.from_dict({'$ref': '/{{ simple_helper() }}'})
+        with self.assertRaisesRegex(InvalidDocumentError, 'safe mode'):
+            doc.resolve_and_merge_references([], safe=True)
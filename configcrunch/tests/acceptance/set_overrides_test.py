@@ -0,0 +1,75 @@
+from configcrunch import InvalidDocumentError, parse_set_overrides
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
+
+
+class SetOverridesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'set_overrides'
+
+    def test_types(self):
+        self.assertDictEqual({
+            'int': 12,
+            'float': 1.5,
+            'bool': False,
+            'str': 'hello world',
+            'quoted': '12',
+            'empty': '',
+            'nan': 'nan',
+            'version': '1.2.3',
+            'with': {'equals': 'a=b'}
+        }, parse_set_overrides([
+            'int=12',
+            'float=1.5',
+            'bool=false',
+            'str=hello world',
+            'quoted="12"',
+            'empty=',
+            'nan=nan',
+            'version=1.2.3',
+            'with.equals=a=b'
+        ]))
+
+    def test_paths(self):
+        self.assertDictEqual({
+            'a': {'b': {'c': 1}, 'x.y': 2},
+            'list': [
+                {'$index::0': {'name': 'first', 'nested': [{'$index::0': [{'$index::0': 3}]}]}},
+                {'$index::1': 'second'}
+            ],
+        }, parse_set_overrides([
+            'a.b.c=1',
+            'a.x\\.y=2',
+            'list[1]=second',
+            'list[0].name=first',
+            'list[0].nested[0][0]=3',
+        ]))
+
+    def test_last_assignment_wins(self):
+        self.assertDictEqual({'a': {'b': 2}}, parse_set_overrides(['a=1', 'a.b=1', 'a.b=2']))
+
+    def test_invalid(self):
+        for assignment in ['novalue', '=1', 'a..b=1', 'a[x]=1', 'a[0=1', '[0]=1', 'a[0]b=1']:
+            with self.subTest(assignment=assignment):
+                with self.assertRaises(ValueError):
+                    parse_set_overrides([assignment])
+
+    def test_index_out_of_range(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        with self.assertRaisesRegex(InvalidDocumentError, 'has 1 entries'):
+            doc.overlay(parse_set_overrides(['more.hosts[1]=two.example.com']))
+
+    def test_overlay(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        doc.overlay(parse_set_overrides([
+            'str_field="42"',
+            'int_field=2',
+            'more.a=$remove',
+            'more.c\\.d=true',
+            'more.hosts[0]=one.example.com',
+            'more.servers[1].port=8081',
+        ]))
+        doc.resolve_and_merge_references([])
+        self.assertDictEqual(deep_sort(self.fix_get_yml('expected.yml')), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
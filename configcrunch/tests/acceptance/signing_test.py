@@ -0,0 +1,110 @@
+import tempfile
+from unittest import mock
+
+from configcrunch import SignatureError, InvalidDocumentError, _lookup
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.remote_lookup_test import FakeServer
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+BASE_URL = 'https://configs.example.org/signed'
+PACKAGE_LOOKUP_PATH = 'pkg://configcrunch.tests.fixtures/signing/repo'
+
+
+class SigningTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'signing'
+
+    def key(self, name='key.pub'):
+        with open(self.fix_get_path(name), 'r') as f:
+            return f.read()
+
+    def load_ref(self, name, trusted_keys, lookup_path=None, **kwargs):
+        doc = Base.from_dict({'$ref': '/' + name})
+        doc.resolve_and_merge_references([lookup_path or self.fix_get_path('repo')], trusted_keys=trusted_keys,
+                                         **kwargs)
+        return doc
+
+    def serve(self, *names):
+        """Serves the files of the repo fixture below BASE_URL, in a new cache directory."""
+        server = FakeServer()
+        for name in names:
+            with open(self.fix_get_path('repo/' + name), 'r') as f:
+                server.files[f'{BASE_URL}/{name}'] = (f.read(), '"v1"')
+        patcher = mock.patch.object(_lookup, 'urlopen', server.urlopen)
+        patcher.start()
+        self.addCleanup(patcher.stop)
+        _lookup._checked_urls.clear()
+        cache = tempfile.TemporaryDirectory()
+        self.addCleanup(cache.cleanup)
+        return cache.name
+
+    def test_signed(self):
+        keys = {self.fix_get_path(''): self.key()}
+        doc = Base.from_yaml(self.fix_get_path('base.yml'), trusted_keys=keys)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], trusted_keys=keys)
+        self.assertEqual({'base': {'str_field': 'from base', 'int_field': 12}}, doc.to_dict())
+
+    def test_key_only(self):
+        key = self.key().splitlines()[1]
+        self.assertEqual(12, self.load_ref('signed', {self.fix_get_path('repo'): key})['int_field'])
+
+    def test_legacy_signature(self):
+        self.assertEqual(13, self.load_ref('legacy', {self.fix_get_path('repo'): self.key()})['int_field'])
+
+    def test_tampered(self):
+        with self.assertRaises(SignatureError):
+            self.load_ref('tampered', {self.fix_get_path('repo'): self.key()})
+
+    def test_unsigned(self):
+        with self.assertRaises(SignatureError):
+            self.load_ref('unsigned', {self.fix_get_path('repo'): self.key()})
+
+    def test_other_key(self):
+        with self.assertRaises(SignatureError):
+            self.load_ref('other_key', {self.fix_get_path('repo'): self.key()})
+        self.assertEqual(14, self.load_ref('other_key', {self.fix_get_path('repo'): self.key('other_key.pub')})['int_field'])
+
+    def test_innermost_directory(self):
+        keys = {self.fix_get_path(''): self.key(), self.fix_get_path('repo'): self.key('other_key.pub')}
+        self.assertEqual(14, self.load_ref('other_key', keys)['int_field'])
+        with self.assertRaises(SignatureError):
+            self.load_ref('signed', keys)
+
+    def test_outside_trusted_directories(self):
+        self.assertEqual(15, self.load_ref('unsigned', {self.fix_get_path('other'): self.key()})['int_field'])
+        self.assertEqual(15, self.load_ref('unsigned', None)['int_field'])
+
+    def test_virtual_file(self):
+        path = self.fix_get_path('repo/signed.yml')
+        with self.assertRaises(SignatureError):
+            Base.from_yaml(path, trusted_keys={self.fix_get_path('repo'): self.key()},
+                           virtual_files={path: 'base:\n    int_field: 666\n'})
+
+    def test_remote_lookup_path(self):
+        cache_dir = self.serve('signed.yml', 'signed.yml.minisig', 'tampered.yml', 'tampered.yml.minisig',
+                               'unsigned.yml')
+        keys = {BASE_URL: self.key()}
+        self.assertEqual(12, self.load_ref('signed', keys, BASE_URL, cache_dir=cache_dir)['int_field'])
+        with self.assertRaises(SignatureError):
+            self.load_ref('tampered', keys, BASE_URL, cache_dir=cache_dir)
+        with self.assertRaises(SignatureError):
+            self.load_ref('unsigned', keys, BASE_URL, cache_dir=cache_dir)
+
+    def test_remote_lookup_path_without_key(self):
+        cache_dir = self.serve('unsigned.yml')
+        self.assertEqual(15, self.load_ref('unsigned', None, BASE_URL, cache_dir=cache_dir)['int_field'])
+
+    def test_package_lookup_path(self):
+        _lookup._package_dirs.clear()
+        keys = {PACKAGE_LOOKUP_PATH: self.key()}
+        self.assertEqual(12, self.load_ref('signed', keys, PACKAGE_LOOKUP_PATH)['int_field'])
+        with self.assertRaises(SignatureError):
+            self.load_ref('unsigned', keys, PACKAGE_LOOKUP_PATH)
+
+    def test_invalid_key(self):
+        with self.assertRaises(ValueError):
+            Base.from_yaml(self.fix_get_path('base.yml'), trusted_keys={self.fix_get_path(''): 'not a key'})
+
+    def test_is_invalid_document_error(self):
+        self.assertTrue(issubclass(SignatureError, InvalidDocumentError))
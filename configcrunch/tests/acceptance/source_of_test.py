@@ -0,0 +1,44 @@
+import os
+
+from configcrunch import VariableProcessingError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class SourceOfTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'source_of'
+
+    def load(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.process_vars()
+        return doc.internal_get('more')
+
+    def test_source_of(self):
+        more = self.load()
+        self.assertTrue(os.path.samefile(self.fix_get_path('base.yml'), more['debug_source']))
+        self.assertTrue(os.path.samefile(self.fix_get_path('repo/defaults.yml'), more['cache_source']))
+
+    def test_conditions(self):
+        more = self.load()
+        self.assertEqual('yes', more['verbose'])
+        self.assertEqual('no', more['cached_locally'])
+
+    def test_not_from_file(self):
+        doc = Base.from_dict({'more': {'source': "{% if source_of('more') %}file{% else %}none{% endif %}"}})
+        doc.resolve_and_merge_references([])
+        doc.process_vars()
+        self.assertEqual('none', doc.internal_get('more')['source'])
+
+    def test_missing_path(self):
+        doc = self.load_base('base.yml', ['repo'])
+        doc.internal_set('str_field', "{% if source_of('more.typo') %}file{% else %}none{% endif %}")
+        doc.process_vars()
+        self.assertEqual('none', doc.internal_get('str_field'))
+
+    def test_invalid_arguments(self):
+        doc = Base.from_dict({'str_field': "{{ source_of() }}"})
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(VariableProcessingError):
+            doc.process_vars()
@@ -162,3 +162,24 @@ class SubdocSpecsTest(unittest.TestCase):
             }, type),
             _test__subdoc_specs("level1/level2/dict2[]", type, FIXTURE, "REPLACED")
         )
+
+    def test_subdoc_specs_containers(self):
+        result, _ = _test__subdoc_specs("level1/list1[list]", type, FIXTURE, "REPLACED")
+        self.assertEqual(["REPLACED", "REPLACED"], result["level1"]["list1"])
+        result, _ = _test__subdoc_specs("level1/dict1[map]", type, FIXTURE, "REPLACED")
+        self.assertEqual(
+            {"lev1_1": "REPLACED", "lev1_2": "REPLACED", "lev1_3": "REPLACED"},
+            result["level1"]["dict1"]
+        )
+        with self.assertRaises(ValueError):
+            _test__subdoc_specs("level1/dict1[list]", type, FIXTURE, "REPLACED")
+        with self.assertRaises(ValueError):
+            _test__subdoc_specs("level1/list1[map]", type, FIXTURE, "REPLACED")
+
+    def test_subdoc_specs_missing_dict(self):
+        with self.assertRaisesRegex(ValueError, 'Not found'):
+            _test__subdoc_specs("missing/dict", type, FIXTURE, "REPLACED")
+        result, _ = _test__subdoc_specs("missing?/dict", type, FIXTURE, "REPLACED")
+        self.assertEqual(FIXTURE, result)
+        result, _ = _test__subdoc_specs("level1?/level2/dict2", type, FIXTURE, "REPLACED")
+        self.assertEqual("REPLACED", result["level1"]["level2"]["dict2"])
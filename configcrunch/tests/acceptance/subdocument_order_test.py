@@ -0,0 +1,44 @@
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class SubdocumentOrderTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'subdocument_order'
+
+    def test_subdocument_items(self):
+        doc = self.load_base('base.yml', [])
+        items = doc.subdocument_items()
+        self.assertEqual([
+            'level_direct',
+            'level_dict/zeta',
+            'level_dict/alpha',
+            'level_dict/mid',
+            'level_array/0',
+            'level_array/1',
+            'level_array/2'
+        ], [path for path, _ in items])
+        self.assertEqual(
+            ['direct', 'zeta', 'alpha', 'mid', 'first', 'second', 'third'],
+            [subdoc.internal_get('name') for _, subdoc in items]
+        )
+        self.assertIs(doc.internal_get('level_direct'), dict(items)['level_direct'])
+
+    def test_stable_order(self):
+        doc = self.load_base('base.yml', [])
+        doc.freeze()
+        self.assertEqual(['str_field', 'level_direct', 'level_dict', 'level_array'], list(doc))
+        self.assertEqual(['zeta', 'alpha', 'mid'], list(doc['level_dict']))
+        self.assertEqual(['base'], list(doc.to_dict()))
+        self.assertEqual(['str_field', 'level_direct', 'level_dict', 'level_array'], list(doc.to_dict()['base']))
+        self.assertEqual(str(doc), str(self.load_frozen()))
+        self.assertEqual(doc.to_msgpack(), self.load_frozen().to_msgpack())
+
+    def test_unfrozen_to_dict(self):
+        doc = self.load_base('base.yml', [])
+        self.assertEqual(['zeta', 'alpha', 'mid'], list(doc.to_dict()['base']['level_dict']))
+
+    def load_frozen(self):
+        doc = self.load_base('base.yml', [])
+        doc.freeze()
+        return doc
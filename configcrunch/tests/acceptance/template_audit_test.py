@@ -0,0 +1,88 @@
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class SecretBase(Base):
+    @classmethod
+    def secret_paths(cls):
+        return ['more.token']
+
+
+class TemplateAuditTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'template_audit'
+
+    def process(self, **kwargs):
+        doc = Base.from_dict({
+            '$vars': {'greeting': 'Hello {{ str_field }}'},
+            'str_field': 'world',
+            'more': {
+                'message': '{{ greeting }}!',
+                'helper': '{{ simple_helper() }}',
+                'count': '{{ 1 + 1 }}',
+                'chained': "{{ '{{ str_field }}' }}",
+                'plain': 'no template',
+                '{{ str_field }}_key': 'x',
+            },
+            'level_direct': {'name': '{{ parent().str_field }}-level'},
+        })
+        doc.resolve_and_merge_references([])
+        doc.process_vars(**kwargs)
+        return doc
+
+    def entries(self, log, path):
+        return [(e.template, e.result) for e in log if e.path == path]
+
+    def test_log(self):
+        log = self.process(audit=True).template_audit_log()
+        by_path = {e.path: e for e in log}
+
+        greeting = by_path['$vars.greeting']
+        self.assertEqual('Hello {{ str_field }}', greeting.template)
+        self.assertEqual('Hello world', greeting.result)
+        self.assertEqual({'str_field': 'world'}, greeting.variables)
+        self.assertEqual([], greeting.helpers)
+
+        message = by_path['more.message']
+        self.assertEqual('Hello world!', message.result)
+        self.assertEqual({'greeting': 'Hello world'}, message.variables)
+
+        helper = by_path['more.helper']
+        self.assertEqual('simple', helper.result)
+        self.assertEqual({}, helper.variables)
+        self.assertEqual(['simple_helper'], helper.helpers)
+
+        self.assertEqual(2, by_path['more.count'].result)
+        self.assertNotIn('more.plain', by_path)
+        self.assertEqual('world_key', by_path['more.{{ str_field }}_key'].result)
+
+    def test_chained_templates(self):
+        log = self.process(audit=True).template_audit_log()
+        self.assertEqual([
+            ("{{ '{{ str_field }}' }}", '{{ str_field }}'),
+            ('{{ str_field }}', 'world'),
+        ], self.entries(log, 'more.chained'))
+
+    def test_subdocuments(self):
+        log = self.process(audit=True).template_audit_log()
+        # Sub-documents are processed first.
+        self.assertEqual('level_direct.name', log[0].path)
+        self.assertEqual('world-level', log[0].result)
+        self.assertEqual(['parent'], log[0].helpers)
+        self.assertEqual(1, len(self.entries(log, 'level_direct.name')))
+
+    def test_disabled(self):
+        self.assertEqual([], self.process().template_audit_log())
+
+    def test_secrets_redacted(self):
+        doc = SecretBase.from_dict({
+            'str_field': 'world',
+            'more': {'token': '{{ str_field }}-secret', 'uses': "{{ more.token|length }}"},
+        })
+        doc.resolve_and_merge_references([])
+        doc.process_vars(audit=True)
+        by_path = {e.path: e for e in doc.template_audit_log()}
+        self.assertEqual('****', by_path['more.token'].result)
+        self.assertEqual('****', by_path['more.uses'].variables['more']['token'])
+        self.assertEqual('world-secret', doc.internal_get('more')['token'])
@@ -0,0 +1,69 @@
+import os
+import shutil
+import tempfile
+import unittest
+
+from configcrunch.testing import resolve_fixture, structural_diff, assert_matches_snapshot, \
+    get_path, assert_path_equals, assert_path_missing
+from configcrunch.tests.fixtures.documents import Base
+
+FIXTURES = os.path.abspath(os.path.join(os.path.dirname(__file__), os.pardir, 'fixtures', 'testing_helpers'))
+
+
+class TestingHelpersTest(unittest.TestCase):
+    def load(self):
+        return resolve_fixture(Base, os.path.join(FIXTURES, 'base.yml'), [os.path.join(FIXTURES, 'repo')])
+
+    def test_resolve_fixture(self):
+        doc = self.load()
+        self.assertEqual(5678, doc['int_field'])
+        self.assertEqual('hello', doc['more']['name'])
+        self.assertEqual(-1, doc['more']['c'])
+
+    def test_snapshot_matches(self):
+        assert_matches_snapshot(self.load(), os.path.join(FIXTURES, 'snapshots', 'base.yml'), update=False)
+
+    def test_snapshot_mismatch(self):
+        doc = self.load()
+        more = doc['more']
+        more['c'] = 5
+        doc['more'] = more
+        with self.assertRaises(AssertionError) as cm:
+            assert_matches_snapshot(doc, os.path.join(FIXTURES, 'snapshots', 'base.yml'), update=False)
+        self.assertIn('more.c: expected -1, got 5', str(cm.exception))
+
+    def test_snapshot_missing_and_update(self):
+        tmp = tempfile.mkdtemp()
+        try:
+            snapshot = os.path.join(tmp, 'sub', 'base.yml')
+            with self.assertRaises(AssertionError):
+                assert_matches_snapshot(self.load(), snapshot, update=False)
+            assert_matches_snapshot(self.load(), snapshot, update=True)
+            with open(snapshot) as f, open(os.path.join(FIXTURES, 'snapshots', 'base.yml')) as g:
+                self.assertEqual(g.read(), f.read())
+            assert_matches_snapshot(self.load(), snapshot, update=False)
+        finally:
+            shutil.rmtree(tmp)
+
+    def test_structural_diff(self):
+        self.assertEqual([], structural_diff({'a': [1, {'b': 2}]}, {'a': [1, {'b': 2}]}))
+        self.assertEqual([
+            'a.0: expected 1, got True',
+            'a.1.b: missing (expected 2)',
+            'a.2: unexpected (got 3)',
+            'c: unexpected (got None)',
+        ], structural_diff({'a': [1, {'b': 2}]}, {'a': [True, {}, 3], 'c': None}))
+
+    def test_path_assertions(self):
+        doc = self.load()
+        self.assertEqual({'hello': 'world'}, get_path(doc, 'more.list.0'))
+        assert_path_equals(doc, 'more.list', [{'hello': 'world'}])
+        assert_path_missing(doc, 'more.list.1')
+        assert_path_missing(doc, 'level_direct')
+        with self.assertRaises(AssertionError) as cm:
+            assert_path_equals(doc, 'more.list', [{'hello': 'there'}])
+        self.assertEqual("more.list.0.hello: expected 'there', got 'world'", str(cm.exception))
+        with self.assertRaises(AssertionError):
+            assert_path_missing(doc, 'more.b')
+        with self.assertRaises(KeyError):
+            get_path(doc, 'more.unknown')
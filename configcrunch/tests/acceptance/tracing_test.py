@@ -0,0 +1,79 @@
+import unittest
+
+from configcrunch import enable_tracing, disable_tracing, InvalidDocumentError
+from configcrunch._tracing import _set_tracer
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+try:
+    from opentelemetry.sdk.trace import TracerProvider
+    from opentelemetry.sdk.trace.export import SimpleSpanProcessor
+    from opentelemetry.sdk.trace.export.in_memory_span_exporter import InMemorySpanExporter
+    HAS_OPENTELEMETRY = True
+except ImportError:
+    HAS_OPENTELEMETRY = False
+
+
+@unittest.skipUnless(HAS_OPENTELEMETRY, "opentelemetry-sdk is not installed")
+class TracingTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_one_ref'
+
+    def setUp(self):
+        super().setUp()
+        self.exporter = InMemorySpanExporter()
+        provider = TracerProvider()
+        provider.add_span_processor(SimpleSpanProcessor(self.exporter))
+        enable_tracing(provider)
+
+    def tearDown(self):
+        disable_tracing()
+
+    def test_spans(self):
+        doc = self.load_base('base.yml', ['repo1'])
+        doc.process_vars()
+        spans = self.exporter.get_finished_spans()
+        names = [span.name for span in spans]
+        self.assertIn('configcrunch.load', names)
+        self.assertIn('configcrunch.resolve', names)
+        self.assertIn('configcrunch.process_vars', names)
+        loaded_files = [span.attributes['configcrunch.file'] for span in spans if span.name == 'configcrunch.load']
+        self.assertIn(self.fix_get_path('base.yml'), loaded_files)
+
+    def test_disabled(self):
+        disable_tracing()
+        self.load_base('base.yml', ['repo1'])
+        self.assertEqual(0, len(self.exporter.get_finished_spans()))
+
+
+class FailingSpan:
+    def __enter__(self):
+        return self
+
+    def __exit__(self, *args):
+        raise RuntimeError("Unable to end the span")
+
+
+class FailingTracer:
+    def start_as_current_span(self, name, attributes):
+        return FailingSpan()
+
+
+class FailingTracingTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'merging_one_ref'
+
+    def setUp(self):
+        super().setUp()
+        _set_tracer(FailingTracer())
+
+    def tearDown(self):
+        _set_tracer(None)
+
+    def test_keeps_original_error(self):
+        # The error of loading is raised, not the one of ending the span.
+        with self.assertRaises(InvalidDocumentError):
+            self.load_base('does_not_exist.yml', ['repo1'])
+        with self.assertRaisesRegex(RuntimeError, 'Unable to end the span'):
+            self.load_base('base.yml', ['repo1'])
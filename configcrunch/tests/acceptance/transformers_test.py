@@ -0,0 +1,82 @@
+import os
+
+from configcrunch import register_transformer, unregister_transformer, set_transformer_enabled, \
+    InvalidDocumentError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+def rename_legacy_keys(content, path):
+    body = content['base']
+    if 'old_str_field' in body:
+        body['str_field'] = body.pop('old_str_field')
+    return content
+
+
+def strip_vendor_sections(content, path):
+    content['base'].pop('vendor', None)
+
+
+class TransformersTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'transformers'
+
+    def setUp(self):
+        self.registered = []
+
+    def tearDown(self):
+        for name in self.registered:
+            unregister_transformer(name)
+
+    def register(self, name, transformer, **kwargs):
+        register_transformer(name, transformer, **kwargs)
+        self.registered.append(name)
+
+    def load(self):
+        return self.load_base('base.yml', ['repo']).to_dict()['base']
+
+    def test_transformers(self):
+        self.register('rename', rename_legacy_keys)
+        self.register('strip', strip_vendor_sections)
+        body = self.load()
+        self.assertEqual('local', body['str_field'])
+        self.assertEqual(1, body['int_field'])
+        self.assertNotIn('old_str_field', body)
+        self.assertNotIn('vendor', body)
+
+    def test_all_files(self):
+        paths = []
+        self.register('record', lambda content, path: paths.append(path))
+        self.load()
+        self.assertEqual(
+            [os.path.abspath(self.fix_get_path('base.yml')), os.path.abspath(self.fix_get_path('repo/parent.yml'))],
+            [os.path.abspath(path) for path in paths]
+        )
+
+    def test_order(self):
+        calls = []
+        for name, order in [('b', 1), ('a', 2), ('c', 1), ('d', -1)]:
+            self.register(name, lambda content, path, name=name: calls.append(name), order=order)
+        self.load()
+        # For both files.
+        self.assertEqual(['d', 'b', 'c', 'a'] * 2, calls)
+
+    def test_enabled(self):
+        self.register('strip', strip_vendor_sections, enabled=False)
+        self.assertIn('vendor', self.load())
+        self.assertTrue(set_transformer_enabled('strip', True))
+        self.assertNotIn('vendor', self.load())
+        self.assertTrue(set_transformer_enabled('strip', False))
+        self.assertIn('vendor', self.load())
+        self.assertFalse(set_transformer_enabled('unknown', True))
+
+    def test_invalid_result(self):
+        self.register('invalid', lambda content, path: 'invalid')
+        with self.assertRaises(InvalidDocumentError):
+            self.load()
+
+    def test_unregister(self):
+        register_transformer('strip', strip_vendor_sections)
+        self.assertTrue(unregister_transformer('strip'))
+        self.assertFalse(unregister_transformer('strip'))
+        self.assertIn('vendor', self.load())
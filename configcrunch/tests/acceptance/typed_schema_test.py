@@ -0,0 +1,167 @@
+from configcrunch import YamlConfigDocument, TypedSchema, Field, DocReference, InvalidDocumentError
+from configcrunch.tests.fixtures.documents import Base, Level
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class TypedLevel(Level):
+    @classmethod
+    def typed_schema(cls):
+        return TypedSchema({
+            'name': str,
+            'base_ref': Field(Base, required=False),
+            'more': Field(object, required=False),
+        })
+
+
+class TypedBase(Base):
+    @classmethod
+    def subdocuments(cls):
+        return [
+            ("level_dict[]", TypedLevel),
+            ("level_array[]", TypedLevel),
+            ("level_direct", TypedLevel),
+        ]
+
+    @classmethod
+    def typed_schema(cls):
+        return TypedSchema({
+            'str_field': str,
+            'int_field': Field(int, required=False),
+            'level_dict': Field(DocReference.map_of(TypedLevel), required=False),
+            'level_array': Field([TypedLevel], required=False),
+            'level_direct': Field(TypedLevel, required=False),
+            'more': Field({
+                'ratio': float,
+                'ports': Field([int], required=False),
+                'env': Field({str: str}, required=False),
+            }, required=False),
+        })
+
+
+class LenientBase(TypedBase):
+    @classmethod
+    def typed_schema(cls):
+        return TypedSchema({'str_field': str}, allow_unknown=True)
+
+
+class OnlyTypedLevel(YamlConfigDocument):
+    @classmethod
+    def header(cls):
+        return 'level'
+
+    @classmethod
+    def typed_schema(cls):
+        return TypedSchema({'name': str})
+
+
+class OnlyTypedBase(YamlConfigDocument):
+    @classmethod
+    def header(cls):
+        return 'base'
+
+    @classmethod
+    def subdocuments(cls):
+        return [("level_direct", OnlyTypedLevel)]
+
+    @classmethod
+    def typed_schema(cls):
+        return TypedSchema({'str_field': str, 'level_direct': Field(OnlyTypedLevel, required=False)})
+
+
+VALID = {
+    'str_field': 'hello',
+    'int_field': 12,
+    'level_dict': {'a': {'name': 'a'}},
+    'level_array': [{'name': 'first'}, {'name': 'second'}],
+    'level_direct': {'name': 'direct', 'more': [1, 2]},
+    'more': {'ratio': 1, 'ports': [80, 443], 'env': {'A': 'b'}},
+}
+
+
+class TypedSchemaTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'typed_schema'
+
+    def load(self, body, doc_type=TypedBase):
+        doc = doc_type.from_dict(body)
+        doc.resolve_and_merge_references([])
+        return doc
+
+    def assertViolations(self, expected_paths, body, doc_type=TypedBase):
+        with self.assertRaises(InvalidDocumentError) as cm:
+            self.load(body, doc_type)
+        self.assertEqual(expected_paths, [path for path, _ in cm.exception.violations])
+        for path in expected_paths:
+            self.assertIn(f'- {path}: ', str(cm.exception))
+        return cm.exception
+
+    def test_valid(self):
+        doc = self.load(VALID)
+        self.assertTrue(doc.validate())
+
+    def test_no_typed_schema(self):
+        self.assertIsNone(Base.typed_schema())
+        self.load({'int_field': 'not checked'}, Base)
+
+    def test_types(self):
+        err = self.assertViolations(['base.int_field', 'base.level_array.1.name', 'base.more.ratio', 'base.more.ports.1',
+                                    'base.more.env.B'], {
+            **VALID,
+            'int_field': 'twelve',
+            'level_array': [{'name': 'first'}, {'name': 2}],
+            'more': {'ratio': 'high', 'ports': [80, '443'], 'env': {'A': 'b', 'B': True}},
+        })
+        self.assertIn('- base.int_field: Expected int, got: "twelve"', str(err))
+        self.assertIn('- base.more.ratio: Expected float, got: "high"', str(err))
+
+    def test_missing_keys(self):
+        self.assertViolations(['base.str_field', 'base.level_dict.a.name', 'base.more.ratio'], {
+            'level_dict': {'a': {'more': 1}},
+            'more': {},
+        })
+
+    def test_unknown_keys(self):
+        body = {**VALID, 'more': {'ratio': 0.5, 'unknown': 1}, 'level_direct': {'name': 'x', 'other': 2}}
+        self.assertViolations(['base.level_direct.other', 'base.more.unknown'], body)
+        self.load(body, LenientBase)
+
+    def test_merged(self):
+        # The referenced values are checked after merging
+        doc = TypedBase.from_dict({'$ref': '/other', 'str_field': 'hello'})
+        with self.assertRaises(InvalidDocumentError) as cm:
+            doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual(['base.int_field'], [path for path, _ in cm.exception.violations])
+
+    def test_templates(self):
+        doc = self.load({**VALID, 'int_field': '{{ 1 + 1 }}'})
+        doc.process_vars()
+        self.assertTrue(doc.validate())
+
+    def test_validate(self):
+        doc = self.load(VALID)
+        doc.internal_set('int_field', 'twelve')
+        with self.assertRaises(InvalidDocumentError) as cm:
+            doc.validate()
+        self.assertEqual([('base.int_field', 'Expected int, got: "twelve"')], cm.exception.violations)
+
+    def test_validate_subdocument(self):
+        doc = self.load(VALID)
+        doc['level_array'][1].internal_set('name', 2)
+        with self.assertRaises(InvalidDocumentError) as cm:
+            doc['level_array'][1].validate()
+        self.assertEqual([('base.level_array.1.name', 'Expected str, got: 2')], cm.exception.violations)
+
+    def test_without_schema(self):
+        # Types with a typed schema don't need to implement schema
+        doc = self.load({'str_field': 'hello', 'level_direct': {'name': 'direct'}}, OnlyTypedBase)
+        self.assertTrue(doc.validate())
+        doc.internal_set('str_field', 1)
+        with self.assertRaises(InvalidDocumentError):
+            doc.validate()
+
+    def test_invalid_spec(self):
+        with self.assertRaises(ValueError):
+            TypedSchema({'name': 'str'})
+        with self.assertRaises(ValueError):
+            Field([str, int])
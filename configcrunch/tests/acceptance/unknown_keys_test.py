@@ -0,0 +1,71 @@
+import warnings
+
+from schema import SchemaError
+
+from configcrunch import UnknownKeyError, UnknownKeyWarning
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class WarningBase(Base):
+    @classmethod
+    def unknown_keys(cls):
+        return 'warn'
+
+
+class UnknownKeysTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'unknown_keys'
+
+    def test_default(self):
+        doc = self.load_base('typo.yml', [])
+        with self.assertRaises(SchemaError) as ctx:
+            doc.validate()
+        self.assertNotIsInstance(ctx.exception, UnknownKeyError)
+
+    def test_error(self):
+        doc = self.load_base('typo.yml', [])
+        with self.assertRaises(UnknownKeyError) as ctx:
+            doc.validate(unknown_keys='error')
+        self.assertIn('str_feld', str(ctx.exception))
+        self.assertIn('typo.yml', str(ctx.exception))
+        self.assertIn('str_feld (did you mean str_field?)', str(ctx.exception))
+        self.assertEqual({'str_feld': ['str_field']}, ctx.exception.suggestions)
+
+    def test_file_of_key(self):
+        # The file that set the key is reported, not all files of the document.
+        doc = self.load_base('ref.yml', ['repo'])
+        with self.assertRaises(UnknownKeyError) as ctx:
+            doc.validate(unknown_keys='error')
+        self.assertIn('int_feld (did you mean int_field?) (in ', str(ctx.exception))
+        self.assertIn('lower.yml', str(ctx.exception))
+        self.assertNotIn('ref.yml', str(ctx.exception))
+
+    def test_warn(self):
+        doc = WarningBase.from_yaml(self.fix_get_path('typo.yml'))
+        doc.resolve_and_merge_references([])
+        # The sub-document (Level) uses the default policy, so the schema rejects it's unknown key.
+        doc.internal_delete('level_direct')
+        with self.assertWarns(UnknownKeyWarning) as ctx:
+            self.assertTrue(doc.validate())
+        self.assertIn('str_feld', str(ctx.warning))
+
+    def test_keep(self):
+        doc = self.load_base('typo.yml', [])
+        doc.internal_delete('level_direct')
+        with warnings.catch_warnings():
+            warnings.simplefilter('error')
+            self.assertTrue(doc.validate(unknown_keys='keep'))
+        self.assertEqual('typo', doc.internal_get('str_feld'))
+
+    def test_subdocument_policy(self):
+        doc = self.load_base('typo.yml', [])
+        # The policy of the call only applies to the document itself, sub-documents use their class policy.
+        with self.assertRaises(SchemaError):
+            doc.validate(unknown_keys='keep')
+
+    def test_invalid_policy(self):
+        doc = self.load_base('typo.yml', [])
+        with self.assertRaises(ValueError):
+            doc.validate(unknown_keys='ignore')
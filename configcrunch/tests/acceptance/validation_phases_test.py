@@ -0,0 +1,50 @@
+from schema import SchemaError
+
+from configcrunch import load_multiple_yml, LayerValidationError, MergedValidationError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class ValidationPhasesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'validation_phases'
+
+    def test_layers(self):
+        with self.assertRaisesRegex(LayerValidationError, 'invalid_override.yml') as cm:
+            load_multiple_yml(Base, self.fix_get_path('base.yml'), self.fix_get_path('invalid_override.yml'),
+                              validation='layers')
+        self.assertIsInstance(cm.exception, SchemaError)
+        self.assertIsInstance(cm.exception.__cause__, SchemaError)
+
+    def test_layers_partial(self):
+        # Required keys (name of the sub-document), directives, removals and templates are not checked.
+        doc = load_multiple_yml(Base, self.fix_get_path('base.yml'), self.fix_get_path('skipped_override.yml'),
+                                validation='layers')
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], validation='layers')
+        self.assertEqual('{{ 1 + 1 }}', doc['int_field'])
+        self.assertNotIn('str_field', doc.doc)
+
+    def test_layers_referenced(self):
+        doc = Base.from_yaml(self.fix_get_path('broken_ref.yml'), validation='layers')
+        with self.assertRaisesRegex(LayerValidationError, 'broken.yml'):
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], validation='layers')
+
+    def test_merged(self):
+        doc = Base.from_yaml(self.fix_get_path('base.yml'))
+        with self.assertRaises(MergedValidationError) as cm:
+            doc.resolve_and_merge_references([self.fix_get_path('repo')], validation='merged')
+        self.assertIsInstance(cm.exception.__cause__, SchemaError)
+
+        doc = Base.from_yaml(self.fix_get_path('complete.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], validation='both')
+        self.assertEqual('complete', doc['level_direct']['name'])
+
+    def test_default(self):
+        doc = load_multiple_yml(Base, self.fix_get_path('base.yml'), self.fix_get_path('invalid_override.yml'))
+        doc.resolve_and_merge_references([self.fix_get_path('repo')])
+        self.assertEqual('not a number', doc['int_field'])
+
+    def test_invalid_option(self):
+        with self.assertRaises(ValueError):
+            Base.from_yaml(self.fix_get_path('base.yml'), validation='always')
@@ -0,0 +1,45 @@
+from schema import SchemaError
+
+from configcrunch import RuleViolationError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+def check_port(doc):
+    if doc['more']['tls']['enabled'] and doc['int_field'] != 443:
+        raise SchemaError("TLS must use port 443")
+    return True
+
+
+class RuleBase(Base):
+    @classmethod
+    def validation_rules(cls):
+        return {
+            'tls_cert': 'not more.tls.enabled or more.tls.cert',
+            'tls_port': check_port,
+            'name': lambda doc: doc['str_field'] != '',
+        }
+
+
+class ValidationRulesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'validation_rules'
+
+    def test_valid(self):
+        doc = RuleBase.from_yaml(self.fix_get_path('valid.yml'))
+        doc.resolve_and_merge_references([])
+        self.assertTrue(doc.validate())
+
+    def test_invalid(self):
+        doc = RuleBase.from_yaml(self.fix_get_path('invalid.yml'))
+        doc.resolve_and_merge_references([])
+        with self.assertRaises(RuleViolationError) as ctx:
+            doc.validate()
+        self.assertIsInstance(ctx.exception, SchemaError)
+        self.assertEqual(['tls_cert', 'tls_port'], ctx.exception.rules)
+        self.assertIn('TLS must use port 443', str(ctx.exception))
+
+    def test_no_rules_by_default(self):
+        doc = self.load_base('invalid.yml', [])
+        self.assertTrue(doc.validate())
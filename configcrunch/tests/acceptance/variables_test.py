@@ -1,4 +1,9 @@
-from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+import os
+from concurrent.futures import ThreadPoolExecutor
+from unittest import mock
+
+from configcrunch import VariableProcessingError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase, deep_sort
 
 
 class Variables(ConfigcrunchTestCase):
@@ -36,6 +41,153 @@ class Variables(ConfigcrunchTestCase):
             'complex.yml'
         )
 
+    def test_local_vars(self):
+        self.assertDocEqualVariables(
+            'expected/local_vars.yml',
+            'local_vars.yml'
+        )
+
+    def test_remove_if(self):
+        self.assertDocEqualVariables(
+            'expected/remove_if.yml',
+            'remove_if.yml'
+        )
+
+    def test_values(self):
+        doc = self.load_base('values.yml', [])
+        doc.process_vars(values=[
+            self.fix_get_path('values/defaults.yml'),
+            self.fix_get_path('values/prod.yml'),
+            {'name': 'from dict'}
+        ])
+        expected_result = self.fix_get_yml('expected/values.yml')
+
+        self.assertDictEqual(deep_sort(expected_result), deep_sort(doc.to_dict()))
+        self.assertValidDoc(doc)
+
+    def test_inherit_vars(self):
+        doc = self.load_base('inherit_vars.yml', [])
+        doc.process_vars(inherit_vars=True)
+        expected_result = self.fix_get_yml('expected/inherit_vars.yml')
+
+        self.assertDictEqual(deep_sort(expected_result), deep_sort(doc.to_dict()))
+
+    def test_vars_not_inherited_by_default(self):
+        doc = self.load_base('inherit_vars.yml', [])
+        doc.process_vars()
+        self.assertEqual(" from level", doc['level_direct']['name'])
+
+    def test_item_context(self):
+        self.assertDocEqualVariables(
+            'expected/item_context.yml',
+            'item_context.yml'
+        )
+
+    def test_templated_keys(self):
+        self.assertDocEqualVariables(
+            'expected/templated_keys.yml',
+            'templated_keys.yml'
+        )
+
+    def test_templated_keys_collision(self):
+        doc = self.load_base('templated_keys_collision.yml', [])
+        with self.assertRaises(VariableProcessingError):
+            doc.process_vars()
+
+    def test_subdoc_errors(self):
+        doc = self.load_base('subdoc_errors.yml', [])
+        with self.assertRaises(VariableProcessingError):
+            doc.process_vars()
+
+    def test_subdoc_errors_collected(self):
+        doc = self.load_base('subdoc_errors.yml', [])
+        with self.assertRaises(VariableProcessingError) as cm:
+            doc.process_vars(collect_errors=True)
+        self.assertEqual(
+            ['level_array[0]', 'level_array[2].base_ref'],
+            sorted(location for location, _ in cm.exception.errors)
+        )
+        for _, e in cm.exception.errors:
+            self.assertIsInstance(e, VariableProcessingError)
+        # The other documents were still processed
+        self.assertEqual(1, doc.internal_get('str_field'))
+        self.assertEqual(1, doc.internal_get('level_array')[1].internal_get('name'))
+
+    def test_inherit_helpers(self):
+        self.assertDocEqualVariables(
+            'expected/inherit_helpers.yml',
+            'inherit_helpers.yml'
+        )
+
+    def test_inherit_helpers_disabled(self):
+        doc = self.load_base('inherit_helpers.yml', [])
+        with self.assertRaises(VariableProcessingError):
+            doc.process_vars(inherit_helpers=False)
+
+    def test_process_vars_at(self):
+        doc = self.load_base('complex.yml', [])
+        doc.process_vars()
+        more = doc.internal_get('more')
+        more['x'] = "{{ more.source }}-x"
+        more['y'] = "{{ more.source }}-y"
+        more['e_in_list'] = ["{{ more.source }}-z"]
+        doc.internal_set('more', more)
+
+        self.assertEqual('source-x', doc.process_vars_at('more.x'))
+        self.assertEqual('source-z', doc.process_vars_at('more.e_in_list.0'))
+        self.assertEqual('source-x', doc.internal_get('more')['x'])
+        self.assertEqual("{{ more.source }}-y", doc.internal_get('more')['y'])
+
+    def test_process_vars_at_subdoc(self):
+        doc = self.load_base('complex.yml', [])
+        doc.process_vars()
+        subdoc = doc.internal_get('level_dict')['a']
+        subdoc.internal_set('name', "{{ parent().more.source|upper }}")
+
+        self.assertEqual('SOURCE', doc.process_vars_at('level_dict.a.name'))
+        self.assertEqual('SOURCE', subdoc.internal_get('name'))
+
+    def test_process_vars_at_not_found(self):
+        doc = self.load_base('complex.yml', [])
+        doc.process_vars()
+        with self.assertRaises(KeyError):
+            doc.process_vars_at('more.not_there')
+        with self.assertRaises(KeyError):
+            doc.process_vars_at('more.e_in_list.5')
+
+    def test_render_frozen_from_threads(self):
+        doc = self.load_base('complex.yml', [])
+        doc.process_vars()
+        doc.freeze()
+        subdoc = doc.internal_get('level_dict')['a']
+
+        def render(i):
+            # Alternate between the documents, so that helpers are looked up on both concurrently.
+            target = doc if i % 2 == 0 else subdoc
+            return target.process_vars_for("{{ parent().simple_helper() if parent() else simple_helper() }}")
+
+        with ThreadPoolExecutor(max_workers=8) as executor:
+            results = list(executor.map(render, range(400)))
+        self.assertEqual(['simple'] * 400, results)
+
+    @mock.patch.dict(os.environ, {"CONFIGCRUNCH_TEST_VAR": "value"})
+    def test_compose_interpolation(self):
+        doc = self.load_base('compose.yml', [])
+        doc.process_vars(compose_interpolation=True)
+        expected_result = self.fix_get_yml('expected/compose.yml')
+
+        self.assertDictEqual(deep_sort(expected_result), deep_sort(doc.to_dict()))
+
+    def test_compose_interpolation_disabled(self):
+        doc = self.load_base('compose.yml', [])
+        doc.process_vars()
+        self.assertEqual("${CONFIGCRUNCH_TEST_VAR}", doc.internal_get('str_field'))
+
+    def test_compose_interpolation_required(self):
+        doc = self.load_base('compose_required.yml', [])
+        with self.assertRaises(VariableProcessingError):
+            doc.process_vars(compose_interpolation=True)
+
     def test_not_working(self):
         """
         When calling parent() and accessing a field on the parent with variables in it,
@@ -0,0 +1,50 @@
+from configcrunch import load_multiple_yml, DocumentLimitError
+from configcrunch.tests.fixtures.documents import Base
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class VirtualFilesTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'virtual_files'
+
+    def load(self, virtual_files, *paths):
+        doc = load_multiple_yml(Base, *[self.fix_get_path(p) for p in paths], virtual_files=virtual_files)
+        doc.resolve_and_merge_references([self.fix_get_path('repo')], virtual_files=virtual_files)
+        return doc
+
+    def test_without_virtual_files(self):
+        self.assertEqual({'base': {
+            'str_field': 'hello', 'int_field': 1234, 'more': {'from_disk': True}
+        }}, self.load(None, 'base.yml').to_dict())
+
+    def test_shadow_file(self):
+        doc = self.load({
+            self.fix_get_path('base.yml'): 'base: {$ref: /folder/reference, str_field: virtual}',
+            self.fix_get_path('repo/folder/reference.yml'): 'base: {int_field: 5}',
+        }, 'base.yml')
+        self.assertEqual({'base': {'str_field': 'virtual', 'int_field': 5}}, doc.to_dict())
+
+    def test_supplement_files(self):
+        doc = self.load({
+            self.fix_get_path('override.yml'): 'base: {$ref: /folder/new}',
+            self.fix_get_path('repo/folder/new.yml'): 'base: {more: {new: true}}',
+            self.fix_get_path('repo/folder/new.ini'): '[base.more]\nini = 1',
+        }, 'base.yml', 'override.yml')
+        self.assertEqual({'base': {
+            'str_field': 'hello', 'int_field': 1234, 'more': {'from_disk': True, 'new': True, 'ini': 1}
+        }}, doc.to_dict())
+
+    def test_import_all(self):
+        doc = self.load({
+            self.fix_get_path('base.yml'): 'base: {$import_all: /conf.d/*}',
+            self.fix_get_path('repo/conf.d/b.yml'): 'base: {more: {b: virtual}}',
+            self.fix_get_path('repo/conf.d/not_a_document.txt'): 'base: {more: {c: virtual}}',
+        }, 'base.yml')
+        self.assertEqual({'base': {'more': {'a': 'disk', 'b': 'virtual'}}}, doc.to_dict())
+
+    def test_limits(self):
+        with self.assertRaises(DocumentLimitError):
+            Base.from_yaml(self.fix_get_path('base.yml'), max_file_size=10, virtual_files={
+                self.fix_get_path('base.yml'): 'base: {str_field: more than ten bytes}'
+            })
@@ -0,0 +1,66 @@
+import os
+
+from configcrunch import WalkNode
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Level
+
+
+class WalkTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'walk'
+
+    def setUp(self):
+        super().setUp()
+        self.doc = self.load_base('base.yml', ['repo'])
+
+    def test_events(self):
+        nodes = []
+        self.doc.walk(lambda node: nodes.append((node.event, node.path, node.type)))
+        self.assertEqual([
+            ('enter', '', 'document'),
+            ('value', 'int_field', 'int'),
+            ('value', 'str_field', 'str'),
+            ('enter', 'level_direct', 'document'),
+            ('value', 'level_direct.name', 'str'),
+            ('exit', 'level_direct', 'document'),
+            ('enter', 'more', 'dict'),
+            ('enter', 'more.list', 'list'),
+            ('value', 'more.list.0', 'str'),
+            ('value', 'more.list.1', 'int'),
+            ('exit', 'more.list', 'list'),
+            ('exit', 'more', 'dict'),
+            ('exit', '', 'document'),
+        ], nodes)
+
+    def test_values_and_files(self):
+        nodes = {}
+
+        def callback(node):
+            self.assertIsInstance(node, WalkNode)
+            if node.event != 'exit':
+                nodes[node.path] = node
+
+        self.doc.walk(callback)
+        self.assertEqual(12, nodes['int_field'].value)
+        self.assertEqual([self.fix_get_path('repo/parent.yml')], [os.path.abspath(f) for f in nodes['int_field'].files])
+        self.assertEqual([self.fix_get_path('base.yml')], [os.path.abspath(f) for f in nodes['more.list.1'].files])
+        self.assertIsInstance(nodes['level_direct'].value, Level)
+        self.assertIsNone(nodes['more'].value)
+
+    def test_skip(self):
+        paths = []
+
+        def callback(node):
+            paths.append(node.path)
+            return node.type != 'dict'
+
+        self.doc.walk(callback)
+        self.assertIn('more', paths)
+        self.assertNotIn('more.list', paths)
+        self.assertEqual(1, paths.count('more'))
+
+    def test_frozen(self):
+        self.doc.freeze()
+        with self.assertRaises(RuntimeError):
+            self.doc.walk(lambda node: None)
@@ -0,0 +1,68 @@
+import os
+
+from schema import SchemaError
+
+from configcrunch import Workspace, WorkspaceValidationError
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base, Level
+
+
+class WorkspaceTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'workspace'
+
+    def load(self, level_file='level.yml', **kwargs):
+        return Workspace.load({
+            'project': (Base, self.fix_get_path('base.yml')),
+            'level': (Level, [self.fix_get_path(level_file)]),
+        }, lookup_paths=[self.fix_get_path('repo')], **kwargs)
+
+    def test_load(self):
+        workspace = self.load()
+        self.assertEqual(2, len(workspace))
+        self.assertIn('project', workspace)
+        self.assertNotIn('other', workspace)
+        self.assertEqual(['project', 'level'], list(workspace.documents))
+        self.assertEqual({'base': {'str_field': 'project', 'int_field': 1}}, workspace['project'].to_dict())
+        with self.assertRaises(KeyError):
+            workspace['other']
+
+    def test_cross_references(self):
+        workspace = self.load()
+        self.assertEqual('project-level', workspace['level'].to_dict()['level']['name'])
+
+    def test_without_processing_vars(self):
+        workspace = self.load(process_vars=False)
+        self.assertEqual('{{ project.str_field }}-level', workspace['level'].to_dict()['level']['name'])
+        workspace.process_vars(globals={'unused': 1})
+        self.assertEqual('project-level', workspace['level'].to_dict()['level']['name'])
+
+    def test_validation(self):
+        with self.assertRaises(WorkspaceValidationError) as ctx:
+            self.load('invalid_level.yml')
+        self.assertIsInstance(ctx.exception, SchemaError)
+        self.assertEqual(['level'], [name for name, _ in ctx.exception.errors])
+        self.assertIsInstance(ctx.exception.errors[0][1], SchemaError)
+        self.assertIn('level', str(ctx.exception))
+
+        workspace = self.load('invalid_level.yml', validate=False)
+        with self.assertRaises(WorkspaceValidationError):
+            workspace.validate()
+
+    def test_shared_provenance(self):
+        workspace = self.load()
+        shared = os.path.abspath(self.fix_get_path(os.path.join('repo', 'shared.yml')))
+        self.assertIn(shared, [os.path.abspath(f) for f in workspace.files()])
+        self.assertEqual({'project': ['int_field']}, workspace.affected_paths(shared))
+        self.assertEqual({'level': ['name']}, workspace.affected_paths(self.fix_get_path('level.yml')))
+
+    def test_reference_graph(self):
+        graph = self.load().reference_graph()
+        self.assertEqual(
+            ['Base', 'Level'],
+            [n['type'] for n in graph['nodes'] if n['kind'] == 'document']
+        )
+        self.assertEqual(1, len([n for n in graph['nodes'] if n['kind'] == 'file']))
+        self.assertEqual(['ref'], [e['kind'] for e in graph['edges']])
+        self.assertIn('digraph', self.load().render_reference_graph())
@@ -0,0 +1,127 @@
+import os
+import shutil
+import tempfile
+
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+from configcrunch.tests.fixtures.documents import Base
+
+
+class WriteBackTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'write_back'
+
+    def setUp(self):
+        super().setUp()
+        tmp = tempfile.TemporaryDirectory()
+        self.addCleanup(tmp.cleanup)
+        # The files are changed by the tests.
+        self.tmp = os.path.join(tmp.name, 'write_back')
+        shutil.copytree(self.fix_get_path(''), self.tmp)
+        self.base = os.path.join(self.tmp, 'base.yml')
+        self.parent = os.path.join(self.tmp, 'repo', 'parent.yml')
+        self.base_content = self.read(self.base)
+        self.parent_content = self.read(self.parent)
+
+    def load(self):
+        doc = Base.from_yaml(self.base)
+        doc.resolve_and_merge_references([os.path.join(self.tmp, 'repo')])
+        return doc
+
+    def read(self, path):
+        with open(path) as f:
+            return f.read()
+
+    def assertSameFile(self, expected, actual):
+        self.assertEqual(os.path.abspath(expected), os.path.abspath(actual))
+
+    def test_source_file(self):
+        doc = self.load()
+        self.assertSameFile(self.base, doc.source_file('str_field'))
+        self.assertSameFile(self.parent, doc.source_file('int_field'))
+        self.assertSameFile(self.parent, doc.source_file('more.flow.y'))
+        self.assertSameFile(self.base, doc.source_file('more.new'))
+        self.assertSameFile(self.base, doc.source_file('level_direct.name'))
+        self.assertIsNone(Base.from_dict({'str_field': 'x'}).source_file('str_field'))
+
+    def test_replace_keeps_formatting(self):
+        file = self.load().write_back('str_field', 'new')
+        self.assertSameFile(self.base, file)
+        self.assertEqual(
+            self.base_content.replace('str_field: old', 'str_field: "new"'),
+            self.read(self.base)
+        )
+        self.assertEqual(self.parent_content, self.read(self.parent))
+        self.assertEqual('new', self.load()['str_field'])
+
+    def test_replace_in_other_layer(self):
+        file = self.load().write_back('int_field', 13)
+        self.assertSameFile(self.parent, file)
+        self.assertEqual(
+            self.parent_content.replace('int_field: 12', 'int_field: 13'),
+            self.read(self.parent)
+        )
+        self.assertEqual(self.base_content, self.read(self.base))
+
+    def test_read_only_files(self):
+        # Virtual files and files of package (or remote and git) lookup paths are never written to.
+        virtual_files = {self.base: self.base_content.replace('str_field: old', 'str_field: virtual')}
+        doc = Base.from_yaml(self.base, virtual_files=virtual_files)
+        doc.resolve_and_merge_references([os.path.join(self.tmp, 'repo')])
+        with self.assertRaises(ValueError):
+            doc.write_back('str_field', 'new')
+        self.assertEqual(self.base_content, self.read(self.base))
+
+        doc = Base.from_yaml(self.base)
+        doc.resolve_and_merge_references(['pkg://configcrunch.tests.fixtures/write_back/repo'])
+        with self.assertRaises(ValueError):
+            doc.write_back('int_field', 13)
+        self.assertEqual(self.parent_content, self.read(self.fix_get_path('repo/parent.yml')))
+        self.assertEqual(13, self.load()['int_field'])
+
+    def test_replace_block(self):
+        self.load().write_back('more.list', ['x'])
+        self.assertEqual(
+            self.base_content.replace('      - a\n      - b\n', '      - "x"\n'),
+            self.read(self.base)
+        )
+        self.assertEqual(['x'], self.load()['more']['list'])
+
+    def test_insert(self):
+        self.load().write_back('more.new.key', True)
+        self.assertEqual(
+            self.base_content.replace('      - b\n', '      - b\n    new:\n      key: true\n'),
+            self.read(self.base)
+        )
+        self.assertEqual({'key': True}, self.load()['more']['new'])
+
+    def test_insert_into_flow_mapping(self):
+        self.load().write_back('more.flow.y', 2)
+        self.assertEqual(
+            self.parent_content.replace('{x: 1}', '{x: 1, y: 2}'),
+            self.read(self.parent)
+        )
+        self.assertEqual({'x': 1, 'y': 2}, self.load()['more']['flow'])
+
+    def test_subdocument(self):
+        self.load().write_back('level_direct.name', 'changed')
+        self.assertEqual(
+            self.base_content.replace("name: 'direct'", 'name: "changed"'),
+            self.read(self.base)
+        )
+        self.assertEqual('changed', self.load()['level_direct']['name'])
+
+    def test_replaces_file(self):
+        os.chmod(self.base, 0o640)
+        files = sorted(os.listdir(self.tmp))
+        self.load().write_back('str_field', 'new')
+        # Written to a temporary file, that replaced the file.
+        self.assertEqual(files, sorted(os.listdir(self.tmp)))
+        self.assertEqual(0o640, os.stat(self.base).st_mode & 0o777)
+
+    def test_errors(self):
+        with self.assertRaises(ValueError):
+            self.load().write_back('more.list.5', 'x')
+        with self.assertRaises(ValueError):
+            Base.from_dict({'str_field': 'x'}).write_back('str_field', 'y')
+        self.assertEqual(self.base_content, self.read(self.base))
@@ -0,0 +1,54 @@
+from collections.abc import Sequence
+
+from configcrunch import YcdList
+from configcrunch.tests.fixtures.documents import Base, Level
+from configcrunch.tests.acceptance.testcases import ConfigcrunchTestCase
+
+
+class YcdListTest(ConfigcrunchTestCase):
+    @classmethod
+    def fixture_name(cls):
+        return 'ycd_list'
+
+    def load(self):
+        doc = Base.from_yaml(self.fix_get_path('doc.yml'))
+        doc.resolve_and_merge_references([])
+        return doc
+
+    def test_sequence(self):
+        more = self.load().internal_get_lazy('more')
+        self.assertIsInstance(more, YcdList)
+        self.assertIsInstance(more, Sequence)
+        self.assertEqual(4, len(more))
+        self.assertEqual('a', more[0])
+        self.assertEqual(1, more[-1])
+        self.assertEqual({'d': 'e'}, more[2])
+        self.assertIsInstance(more[1], YcdList)
+        self.assertEqual(['b', 'c'], more[1])
+        self.assertEqual(['a', ['b', 'c']], more[:2])
+        self.assertEqual([1, {'d': 'e'}], more[::-2])
+        self.assertEqual(['a', ['b', 'c'], {'d': 'e'}, 1], list(more))
+        self.assertIn('a', more)
+        self.assertNotIn('b', more)
+        self.assertEqual(1, more.index(['b', 'c']))
+        self.assertEqual(1, more.count(1))
+        self.assertEqual("['a', ['b', 'c'], {'d': 'e'}, 1]", repr(more))
+        with self.assertRaises(IndexError):
+            more[4]
+
+    def test_documents(self):
+        levels = self.load().internal_get_lazy('level_array')
+        self.assertIsInstance(levels[0], Level)
+        self.assertEqual(['one', 'two'], [level.internal_get('name') for level in levels])
+
+    def test_set(self):
+        doc = self.load()
+        doc.internal_set('more', doc.internal_get_lazy('more')[1])
+        doc.freeze()
+        self.assertEqual(['b', 'c'], doc['more'])
+        self.assertIsInstance(doc['more'], list)
+
+    def test_internal_get(self):
+        more = self.load().internal_get('more')
+        self.assertIsInstance(more, list)
+        self.assertEqual(['a', ['b', 'c'], {'d': 'e'}, 1], more)
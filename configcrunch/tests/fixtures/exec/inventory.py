@@ -0,0 +1,4 @@
+print("hosts:")
+print("  - web1")
+print("  - web2")
+print("region: eu")
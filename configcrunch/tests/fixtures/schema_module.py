@@ -0,0 +1,4 @@
+"""A module that files must not be able to import by naming it as $schema."""
+from schema import Schema
+
+SCHEMA = Schema({'str_field': str})
@@ -0,0 +1,136 @@
+//! Checks invariants of merging three arbitrary documents.
+//! Run with ``cargo fuzz run merge`` (in this directory's parent).
+#![no_main]
+use arbitrary::Arbitrary;
+use configcrunch::fuzzing::{merge, merge_all, Dict, Value};
+use libfuzzer_sys::fuzz_target;
+
+/// Keys and strings are taken from small sets, so that the documents overlap.
+#[derive(Arbitrary, Debug)]
+enum FuzzKey {
+    A,
+    B,
+    C,
+    Lock,
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzString {
+    A,
+    B,
+    Remove,
+    RemoveFromListA,
+}
+
+#[derive(Arbitrary, Debug)]
+enum FuzzValue {
+    Dict(Vec<(FuzzKey, FuzzValue)>),
+    List(Vec<FuzzValue>),
+    String(FuzzString),
+    Bool(bool),
+    Int(i8),
+    Float(i8),
+}
+
+impl FuzzKey {
+    fn build(&self) -> String {
+        match self {
+            FuzzKey::A => "a",
+            FuzzKey::B => "b",
+            FuzzKey::C => "c",
+            FuzzKey::Lock => "$lock",
+        }
+        .to_string()
+    }
+}
+
+impl FuzzValue {
+    fn build(&self) -> Value {
+        match self {
+            FuzzValue::Dict(v) => Value::Dict(build_dict(v)),
+            FuzzValue::List(v) => Value::List(v.iter().map(FuzzValue::build).collect()),
+            FuzzValue::String(FuzzString::A) => Value::String("a".to_string()),
+            FuzzValue::String(FuzzString::B) => Value::String("b".to_string()),
+            FuzzValue::String(FuzzString::Remove) => Value::String("$remove".to_string()),
+            FuzzValue::String(FuzzString::RemoveFromListA) => {
+                Value::String("$remove::a".to_string())
+            }
+            FuzzValue::Bool(v) => Value::Bool(*v),
+            FuzzValue::Int(v) => Value::Int(*v as i64),
+            FuzzValue::Float(v) => Value::Float(*v as f64 / 4.0),
+        }
+    }
+}
+
+fn build_dict(entries: &[(FuzzKey, FuzzValue)]) -> Dict {
+    entries
+        .iter()
+        .map(|(k, v)| (k.build(), v.build()))
+        .collect()
+}
+
+/// Whether the dict contains no directives (neither as keys nor as strings).
+fn is_plain(dict: &Dict) -> bool {
+    fn plain_value(value: &Value) -> bool {
+        match value {
+            Value::Dict(v) => is_plain(v),
+            Value::List(v) => v.iter().all(plain_value),
+            Value::String(v) => !v.starts_with('$'),
+            _ => true,
+        }
+    }
+    dict.iter()
+        .all(|(k, v)| !k.starts_with('$') && plain_value(v))
+}
+
+/// Whether the values at all paths, that exist in both dicts, are of the same kind
+/// (dict, list or scalar).
+fn same_shape(a: &Dict, b: &Dict) -> bool {
+    a.iter().all(|(k, av)| match (av, b.get(k)) {
+        (_, None) => true,
+        (Value::Dict(av), Some(Value::Dict(bv))) => same_shape(av, bv),
+        (Value::List(_), Some(Value::List(_))) => true,
+        (Value::Dict(_) | Value::List(_), _) | (_, Some(Value::Dict(_) | Value::List(_))) => false,
+        _ => true,
+    })
+}
+
+fuzz_target!(|input: [Vec<(FuzzKey, FuzzValue)>; 3]| {
+    let [a, b, c] = [
+        build_dict(&input[0]),
+        build_dict(&input[1]),
+        build_dict(&input[2]),
+    ];
+
+    // Merging is deterministic.
+    let ab = merge(&a, &b);
+    assert_eq!(ab, merge(&a, &b));
+
+    // Merging with an empty document changes nothing.
+    if let Ok(result) = merge(&a, &Dict::new()) {
+        assert_eq!(a, result);
+    }
+    assert_eq!(Ok(a.clone()), merge(&Dict::new(), &a));
+
+    // Merging is associative for documents without directives and without type changes.
+    if is_plain(&a)
+        && is_plain(&b)
+        && is_plain(&c)
+        && same_shape(&a, &b)
+        && same_shape(&a, &c)
+        && same_shape(&b, &c)
+    {
+        let left = merge(&ab.unwrap(), &c).unwrap();
+        let right = merge(&a, &merge(&b, &c).unwrap()).unwrap();
+        assert_eq!(left, right);
+    }
+
+    // Keys set to $remove are removed from the result.
+    if let Ok(result) = merge_all(&[a, b.clone()]) {
+        for (k, v) in &b {
+            if v == &Value::String("$remove".to_string()) {
+                assert!(!result.contains_key(k), "{} was not removed", k);
+            }
+        }
+    }
+});
@@ -21,6 +21,13 @@ setup(
     install_requires=[
         'schema >= 0.7'
     ],
+    extras_require={
+        # For comparing snapshots with configcrunch.testing.
+        'testing': ['pyyaml'],
+    },
+    entry_points={
+        'console_scripts': ['configcrunch = configcrunch.__main__:main'],
+    },
     classifiers=[
         'Development Status :: 4 - Beta',
         'Programming Language :: Python',
@@ -0,0 +1,163 @@
+use crate::conv::YcdValueType::{Dict, List, Ycd};
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::{construct_new_ycd, InvalidDocumentError, ALIAS, ANCHOR};
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use std::collections::HashMap;
+use std::mem::take;
+
+/// Resolves all $anchor and $alias entries in a document and all of its sub-documents.
+///
+/// A dict containing ``$anchor: <name>`` defines a block with that name (the dict without
+/// the $anchor entry). A dict that only contains ``$alias: <name>`` is replaced by a copy of that block.
+/// Anchors can be defined in any document that is part of the hierarchy, independent of the
+/// file they came from.
+pub(crate) fn resolve_anchors(py: Python, document: &PyYamlConfigDocument) -> PyResult<()> {
+    let mut anchors = HashMap::new();
+    let mut root = Ycd(document.clone_ref(py));
+    collect_anchors(py, &mut root, &mut anchors)?;
+    if anchors.is_empty() {
+        return Ok(());
+    }
+    replace_aliases(py, &mut root, &anchors, &mut vec![])
+}
+
+/// Collects (and removes) all anchors in value.
+fn collect_anchors(
+    py: Python,
+    value: &mut YcdValueType,
+    anchors: &mut HashMap<String, YcdDict>,
+) -> PyResult<()> {
+    match value {
+        Ycd(v) => {
+            let mut doc = Dict(take(&mut v.borrow_mut(py).doc));
+            let result = collect_anchors(py, &mut doc, anchors);
+            v.borrow_mut(py).doc = doc.unwrap_dict();
+            result
+        }
+        Dict(v) => {
+            for vv in v.values_mut() {
+                collect_anchors(py, vv, anchors)?;
+            }
+            if let Some(name) = v.shift_remove(ANCHOR) {
+                let name = name.to_string();
+                if anchors.insert(name.clone(), v.clone()).is_some() {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "The {} {} is defined multiple times.",
+                        ANCHOR, name
+                    )));
+                }
+            }
+            Ok(())
+        }
+        List(v) => {
+            for vv in v.iter_mut() {
+                collect_anchors(py, vv, anchors)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replaces all aliases in value. stack contains the names of the anchors currently being inserted.
+fn replace_aliases(
+    py: Python,
+    value: &mut YcdValueType,
+    anchors: &HashMap<String, YcdDict>,
+    stack: &mut Vec<String>,
+) -> PyResult<()> {
+    match value {
+        Ycd(v) => {
+            let mut doc = Dict(take(&mut v.borrow_mut(py).doc));
+            let result = replace_aliases(py, &mut doc, anchors, stack);
+            v.borrow_mut(py).doc = doc.unwrap_dict();
+            result
+        }
+        Dict(v) => {
+            if let Some(name) = v.get(ALIAS) {
+                let name = name.to_string();
+                if v.len() > 1 {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "A dict containing {} {} must not contain any other keys.",
+                        ALIAS, name
+                    )));
+                }
+                if stack.contains(&name) {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "The {} {} contains itself.",
+                        ANCHOR, name
+                    )));
+                }
+                let block = match anchors.get(&name) {
+                    Some(block) => block,
+                    None => {
+                        return Err(InvalidDocumentError::new_err(format!(
+                            "The {} {} is not defined.",
+                            ANCHOR, name
+                        )))
+                    }
+                };
+                *v = copy_dict(py, block)?;
+                stack.push(name);
+                let result = v
+                    .values_mut()
+                    .try_for_each(|vv| replace_aliases(py, vv, anchors, stack));
+                stack.pop();
+                return result;
+            }
+            v.values_mut()
+                .try_for_each(|vv| replace_aliases(py, vv, anchors, stack))
+        }
+        List(v) => v
+            .iter_mut()
+            .try_for_each(|vv| replace_aliases(py, vv, anchors, stack)),
+        _ => Ok(()),
+    }
+}
+
+fn copy_dict(py: Python, dict: &YcdDict) -> PyResult<YcdDict> {
+    dict.iter()
+        .map(|(k, v)| Ok((k.clone(), copy_value(py, v)?)))
+        .collect()
+}
+
+/// Deep copy of value. Sub-documents are copied as new documents of the same type, with the same
+/// files, references and provenance.
+pub(crate) fn copy_value(py: Python, value: &YcdValueType) -> PyResult<YcdValueType> {
+    Ok(match value {
+        Ycd(v) => {
+            let doc_cls: Py<PyType> = v.getattr(py, "__class__")?.extract(py)?;
+            let doc_ref = v.borrow(py);
+            let copy = construct_new_ycd(
+                py,
+                doc_cls.as_ref(py),
+                [
+                    doc_cls.to_object(py),
+                    copy_dict(py, &doc_ref.doc)?.to_object(py),
+                    doc_ref.path.to_object(py),
+                    doc_ref.parent_doc.to_object(py),
+                    py.None(),
+                    doc_ref.absolute_paths.to_object(py),
+                ],
+            )?;
+            let mut copy_ref = copy.borrow_mut(py);
+            copy_ref.path_in_parent = doc_ref.path_in_parent.clone();
+            copy_ref.already_loaded_docs = doc_ref.already_loaded_docs.clone();
+            copy_ref.references = doc_ref.references.clone();
+            copy_ref.features = doc_ref.features.clone();
+            copy_ref.sources = doc_ref.sources.clone();
+            copy_ref.provenance = doc_ref.provenance.clone();
+            copy_ref.stats = doc_ref.stats.clone();
+            drop(copy_ref);
+            Ycd(copy)
+        }
+        Dict(v) => Dict(copy_dict(py, v)?),
+        List(v) => List(
+            v.iter()
+                .map(|vv| copy_value(py, vv))
+                .collect::<PyResult<Vec<YcdValueType>>>()?,
+        ),
+        v => v.clone(),
+    })
+}
@@ -0,0 +1,173 @@
+//! The audit log of rendered templates (``audit`` option of ``process_vars``), to find out why a
+//! value was rendered the way it was.
+use crate::conv::YcdValueType::{Dict, List, Ycd};
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::minijinja::{ItemPathPart, UsedName};
+use crate::provenance::join;
+use crate::redaction::redact;
+use pyo3::class::gc::{PyTraverseError, PyVisit};
+use pyo3::prelude::*;
+
+/// A rendered template of a document.
+#[derive(Clone, Debug)]
+pub(crate) struct AuditEntry {
+    /// Path of the value (or templated key) in the document. Paths in $vars start with the key $vars.
+    pub(crate) path: Vec<ItemPathPart>,
+    pub(crate) template: String,
+    pub(crate) result: YcdValueType,
+    /// The variables and helpers the template looked up.
+    pub(crate) used_names: Vec<UsedName>,
+}
+
+/// A template rendered by ``process_vars`` (see ``template_audit_log``).
+///
+/// - ``path``: Path of the value, the parts are separated by "." and list entries are addressed by
+///   their index (``ports.0``). Templates in $vars have paths starting with ``$vars``, templated keys
+///   the path of the (unrendered) key.
+/// - ``template``: The template string.
+/// - ``result``: The rendered value.
+/// - ``variables``: The variables the template used, with their values at the time of rendering
+///   (keys of the document, $vars, ``values`` and globals).
+/// - ``helpers``: The names of the variable helpers the template used.
+///
+/// Secret values (see ``YamlConfigDocument.secret_paths``) are redacted in ``result`` and ``variables``.
+#[pyclass(module = "_main")]
+pub(crate) struct TemplateAuditEntry {
+    #[pyo3(get)]
+    path: String,
+    #[pyo3(get)]
+    template: String,
+    #[pyo3(get)]
+    result: YcdValueType,
+    #[pyo3(get)]
+    variables: YcdDict,
+    #[pyo3(get)]
+    helpers: Vec<String>,
+}
+
+#[pymethods]
+impl TemplateAuditEntry {
+    fn __repr__(&self) -> String {
+        format!(
+            "TemplateAuditEntry(path={:?}, template={:?}, result={})",
+            self.path, self.template, self.result
+        )
+    }
+}
+
+impl TemplateAuditEntry {
+    fn new(prefix: &str, entry: &AuditEntry) -> Self {
+        let path = entry.path.iter().fold(prefix.to_string(), |path, part| {
+            join(&path, &format_part(part))
+        });
+        let mut variables = YcdDict::new();
+        let mut helpers = vec![];
+        for used in &entry.used_names {
+            match &used.value {
+                Some(value) => {
+                    variables.insert(used.name.clone(), value.clone());
+                }
+                None => helpers.push(used.name.clone()),
+            }
+        }
+        Self {
+            path,
+            template: entry.template.clone(),
+            result: entry.result.clone(),
+            variables,
+            helpers,
+        }
+    }
+}
+
+impl AuditEntry {
+    /// The entry with secret values (see secret_patterns) redacted: The result if the path is
+    /// secret and the secret values in the used variables.
+    pub(crate) fn redacted(
+        path: &[ItemPathPart],
+        template: &str,
+        result: &YcdValueType,
+        used_names: Vec<UsedName>,
+        patterns: &[String],
+    ) -> Self {
+        let path_str = path
+            .iter()
+            .fold(String::new(), |path, part| join(&path, &format_part(part)));
+        Self {
+            path: path.to_vec(),
+            template: template.to_string(),
+            result: redact(result.clone(), &path_str, patterns),
+            used_names: used_names
+                .into_iter()
+                .map(|used| UsedName {
+                    value: used.value.map(|value| redact(value, &used.name, patterns)),
+                    name: used.name,
+                })
+                .collect(),
+        }
+    }
+
+    /// Visits the documents in the result and the used variables (see traverse_dict).
+    pub(crate) fn traverse(&self, visit: &PyVisit) -> Result<(), PyTraverseError> {
+        self.result.traverse(visit)?;
+        self.used_names
+            .iter()
+            .filter_map(|used| used.value.as_ref())
+            .try_for_each(|value| value.traverse(visit))
+    }
+}
+
+fn format_part(part: &ItemPathPart) -> String {
+    match part {
+        ItemPathPart::Key(k) => k.clone(),
+        ItemPathPart::Index(i) => i.to_string(),
+    }
+}
+
+/// The audit log of the document and its sub-documents, in the order the templates were rendered:
+/// The sub-documents (in document order) before the document itself, since they are processed first.
+/// The paths of the entries of sub-documents are prefixed with their path in the document.
+pub(crate) fn audit_log(py: Python, doc: &PyYamlConfigDocument) -> Vec<TemplateAuditEntry> {
+    let mut log = vec![];
+    collect(py, doc, "", &mut log);
+    log
+}
+
+fn collect(
+    py: Python,
+    doc: &PyYamlConfigDocument,
+    prefix: &str,
+    log: &mut Vec<TemplateAuditEntry>,
+) {
+    let bow = doc.borrow(py);
+    for (key, value) in &bow.doc {
+        collect_subdocuments(py, value, &join(prefix, key), log);
+    }
+    log.extend(
+        bow.template_audit
+            .iter()
+            .map(|entry| TemplateAuditEntry::new(prefix, entry)),
+    );
+}
+
+fn collect_subdocuments(
+    py: Python,
+    value: &YcdValueType,
+    path: &str,
+    log: &mut Vec<TemplateAuditEntry>,
+) {
+    match value {
+        Ycd(doc) => collect(py, doc, path, log),
+        Dict(d) => {
+            for (key, v) in d {
+                collect_subdocuments(py, v, &join(path, key), log);
+            }
+        }
+        List(l) => {
+            for (i, v) in l.iter().enumerate() {
+                collect_subdocuments(py, v, &join(path, &i.to_string()), log);
+            }
+        }
+        _ => {}
+    }
+}
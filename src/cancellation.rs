@@ -0,0 +1,85 @@
+//! Timeouts and cancellation of loading, resolving and processing variables
+//! (the ``timeout`` and ``cancel_token`` options).
+use crate::ResolutionCancelledError;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A token to cancel loading, resolving or processing the variables of documents from another
+/// thread: Pass it as ``cancel_token`` and call ``cancel``. The operation then raises a
+/// ResolutionCancelledError as soon as possible. A token stays cancelled.
+#[pyclass(module = "_main")]
+#[derive(Default)]
+pub(crate) struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+#[pymethods]
+impl CancellationToken {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// When an operation has to be aborted: After a timeout or when a token is cancelled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Deadline {
+    until: Option<(Instant, Duration)>,
+    token: Option<Arc<AtomicBool>>,
+}
+
+impl Deadline {
+    /// Sets a timeout (in seconds) from now.
+    pub(crate) fn set_timeout(&mut self, timeout: Option<f64>) -> PyResult<()> {
+        self.until = match timeout {
+            None => None,
+            Some(t) if t.is_finite() && t >= 0.0 => {
+                let duration = Duration::from_secs_f64(t);
+                Some((Instant::now() + duration, duration))
+            }
+            Some(t) => {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "Invalid timeout: {}",
+                    t
+                )))
+            }
+        };
+        Ok(())
+    }
+
+    pub(crate) fn set_token(&mut self, token: Option<PyRef<CancellationToken>>) {
+        self.token = token.map(|t| t.cancelled.clone());
+    }
+
+    /// Raises a ResolutionCancelledError, if the timeout has passed or the token was cancelled.
+    pub(crate) fn check(&self) -> PyResult<()> {
+        if let Some(token) = &self.token {
+            if token.load(Ordering::SeqCst) {
+                return Err(ResolutionCancelledError::new_err(
+                    "The operation was cancelled.",
+                ));
+            }
+        }
+        if let Some((until, duration)) = self.until {
+            if Instant::now() >= until {
+                return Err(ResolutionCancelledError::new_err(format!(
+                    "The operation timed out after {:.3} seconds.",
+                    duration.as_secs_f64()
+                )));
+            }
+        }
+        Ok(())
+    }
+}
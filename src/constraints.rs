@@ -0,0 +1,500 @@
+//! Value constraints that can be used in schemas (like DocReference).
+//! Validation returns the validated value, so constraints can be combined using ``schema.And``.
+use crate::conv::decimal_to_integer;
+use crate::conv::YcdValueType;
+use crate::conv::YcdValueType::{Bool, Decimal, Float, Int, List, YString};
+use crate::SchemaError;
+use pyo3::basic::CompareOp;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use std::cmp::Ordering;
+
+/// The value must be equal to one of the choices.
+///
+///     Schema({'mode': OneOf('dev', 'prod')})
+#[pyclass(module = "_main")]
+pub(crate) struct OneOf {
+    choices: Vec<PyObject>,
+}
+
+#[pymethods]
+impl OneOf {
+    #[new]
+    #[args(choices = "*")]
+    fn new(choices: &PyTuple) -> Self {
+        Self {
+            choices: choices.iter().map(|c| c.into()).collect(),
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!("OneOf({})", self.choices_repr(py)?))
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        for choice in &self.choices {
+            if choice
+                .as_ref(py)
+                .rich_compare(&data, CompareOp::Eq)?
+                .is_true()?
+            {
+                return Ok(data);
+            }
+        }
+        Err(SchemaError::new_err(format!(
+            "{} is not one of: {}",
+            data.as_ref(py).repr()?,
+            self.choices_repr(py)?
+        )))
+    }
+}
+
+impl OneOf {
+    fn choices_repr(&self, py: Python) -> PyResult<String> {
+        Ok(self
+            .choices
+            .iter()
+            .map(|c| Ok(c.as_ref(py).repr()?.to_string()))
+            .collect::<PyResult<Vec<String>>>()?
+            .join(", "))
+    }
+}
+
+/// The value must be a number (int or float) between min and max (inclusive).
+/// Both bounds are optional.
+///
+///     Schema({'port': Range(min=1, max=65535)})
+#[pyclass(module = "_main")]
+pub(crate) struct Range {
+    #[pyo3(get)]
+    min: Option<PyObject>,
+    #[pyo3(get)]
+    max: Option<PyObject>,
+}
+
+#[pymethods]
+impl Range {
+    #[new]
+    #[args(min = "None", max = "None")]
+    fn new(min: Option<PyObject>, max: Option<PyObject>) -> Self {
+        Self { min, max }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(format!(
+            "Range(min={}, max={})",
+            self.min.to_object(py).as_ref(py).repr()?,
+            self.max.to_object(py).as_ref(py).repr()?
+        ))
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let value = data.as_ref(py);
+        if value.is_instance::<PyBool>()?
+            || !(value.is_instance::<PyLong>()?
+                || value.is_instance::<PyFloat>()?
+                || is_decimal(value)?)
+        {
+            return Err(SchemaError::new_err(format!(
+                "{} is not a number",
+                value.repr()?
+            )));
+        }
+        if let Some(min) = &self.min {
+            if value.compare(min)? == Ordering::Less {
+                return Err(SchemaError::new_err(format!(
+                    "{} is smaller than the minimum {}",
+                    value.repr()?,
+                    min.as_ref(py).repr()?
+                )));
+            }
+        }
+        if let Some(max) = &self.max {
+            if value.compare(max)? == Ordering::Greater {
+                return Err(SchemaError::new_err(format!(
+                    "{} is greater than the maximum {}",
+                    value.repr()?,
+                    max.as_ref(py).repr()?
+                )));
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// The value must be a string that entirely matches the regular expression (Python ``re`` syntax).
+///
+///     Schema({'name': Pattern(r'[a-z][a-z0-9-]*')})
+#[pyclass(module = "_main")]
+pub(crate) struct Pattern {
+    #[pyo3(get)]
+    pattern: String,
+    compiled: PyObject,
+}
+
+#[pymethods]
+impl Pattern {
+    #[new]
+    fn new(py: Python, pattern: String) -> PyResult<Self> {
+        let compiled = py
+            .import("re")?
+            .getattr("compile")?
+            .call1((pattern.as_str(),))?
+            .into();
+        Ok(Self { pattern, compiled })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Pattern({:?})", self.pattern)
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let value = data.as_ref(py);
+        if !value.is_instance::<PyString>()? {
+            return Err(SchemaError::new_err(format!(
+                "{} is not a string",
+                value.repr()?
+            )));
+        }
+        if self
+            .compiled
+            .call_method1(py, "fullmatch", (value,))?
+            .is_none(py)
+        {
+            return Err(SchemaError::new_err(format!(
+                "{} does not match the pattern {:?}",
+                value.repr()?,
+                self.pattern
+            )));
+        }
+        Ok(data)
+    }
+}
+
+/// The length of the value (a string, list or dict) must be between min and max (inclusive).
+/// Both bounds are optional.
+///
+///     Schema({'name': Length(min=1, max=63)})
+#[pyclass(module = "_main")]
+pub(crate) struct Length {
+    #[pyo3(get)]
+    min: Option<usize>,
+    #[pyo3(get)]
+    max: Option<usize>,
+}
+
+#[pymethods]
+impl Length {
+    #[new]
+    #[args(min = "None", max = "None")]
+    fn new(min: Option<usize>, max: Option<usize>) -> Self {
+        Self { min, max }
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Length(min={:?}, max={:?})", self.min, self.max)
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let value = data.as_ref(py);
+        let len = match value.len() {
+            Ok(len) => len,
+            Err(_) => {
+                return Err(SchemaError::new_err(format!(
+                    "{} has no length",
+                    value.repr()?
+                )))
+            }
+        };
+        if self.min.map_or(false, |min| len < min) || self.max.map_or(false, |max| len > max) {
+            return Err(SchemaError::new_err(format!(
+                "The length of {} is {}, expected: {}",
+                value.repr()?,
+                len,
+                self.__repr__()
+            )));
+        }
+        Ok(data)
+    }
+}
+
+/// Coerces loosely typed values into the type ``to`` before the document is validated
+/// (see ``YamlConfigDocument.validate``). While validating, the value must have that type.
+///
+/// - ``"int"``: Strings containing integers and floats without fractional part.
+/// - ``"float"``: Integers and strings containing numbers.
+/// - ``"bool"``: The strings yes/no, true/false, on/off, 1/0 (case-insensitive) and the integers 1 and 0.
+/// - ``"str"``: Integers, floats and booleans.
+/// - ``"list"``: Everything that is not a list is wrapped in a single-element list.
+///
+/// Values that can not be coerced are not changed. Integers out of the 64 bit range raise a SchemaError.
+///
+///     Schema({'port': And(Coerce('int'), Range(min=1))})
+#[pyclass(module = "_main")]
+pub(crate) struct Coerce {
+    #[pyo3(get)]
+    to: String,
+}
+
+#[pymethods]
+impl Coerce {
+    #[new]
+    fn new(to: String) -> PyResult<Self> {
+        if !["int", "float", "bool", "str", "list"].contains(&to.as_str()) {
+            return Err(exceptions::PyValueError::new_err(format!(
+                "Invalid coercion target: {}. Expected one of 'int', 'float', 'bool', 'str' or 'list'.",
+                to
+            )));
+        }
+        Ok(Self { to })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Coerce({:?})", self.to)
+    }
+
+    /// Returns the coerced value, or the value itself if it can not be coerced.
+    fn coerce(&self, data: YcdValueType) -> PyResult<YcdValueType> {
+        Ok(match (self.to.as_str(), data) {
+            ("int", YString(s)) => match s.trim().parse::<i128>() {
+                Ok(i) => Int(int_in_range(i, s.trim())?),
+                // Floats beyond i128 saturate, they are out of range either way.
+                Err(_) => match s.trim().parse::<f64>() {
+                    Ok(f) if f.fract() == 0.0 => Int(int_in_range(f as i128, s.trim())?),
+                    _ => YString(s),
+                },
+            },
+            ("int", Float(f)) if f.fract() == 0.0 => Int(int_in_range(f as i128, &f.to_string())?),
+            ("int", Decimal(d)) => match decimal_to_integer(&d) {
+                Some(i) => Int(int_in_range(i, &d)?),
+                None => Decimal(d),
+            },
+            ("float", YString(s)) => match s.trim().parse::<f64>() {
+                Ok(f) => Float(f),
+                Err(_) => YString(s),
+            },
+            ("float", Int(i)) => Float(i as f64),
+            ("bool", YString(s)) => match s.trim().to_lowercase().as_str() {
+                "yes" | "true" | "on" | "1" => Bool(true),
+                "no" | "false" | "off" | "0" => Bool(false),
+                _ => YString(s),
+            },
+            ("bool", Int(i)) if i == 0 || i == 1 => Bool(i == 1),
+            ("str", v @ (Int(_) | Float(_) | Decimal(_) | Bool(_))) => YString(v.to_string()),
+            ("list", List(l)) => List(l),
+            ("list", v) => List(vec![v]),
+            (_, v) => v,
+        })
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let value = data.as_ref(py);
+        let valid = match self.to.as_str() {
+            "int" => value.is_instance::<PyLong>()? && !value.is_instance::<PyBool>()?,
+            "float" => value.is_instance::<PyFloat>()? || is_decimal(value)?,
+            "bool" => value.is_instance::<PyBool>()?,
+            "str" => value.is_instance::<PyString>()?,
+            _ => value.is_instance::<PyList>()?,
+        };
+        if !valid {
+            return Err(SchemaError::new_err(format!(
+                "{} can not be coerced to {}",
+                value.repr()?,
+                self.to
+            )));
+        }
+        Ok(data)
+    }
+}
+
+/// The integer i (written as text), if it is in the range of int (64 bit).
+fn int_in_range(i: i128, text: &str) -> PyResult<i64> {
+    i64::try_from(i).map_err(|_| {
+        SchemaError::new_err(format!(
+            "{} can not be coerced to int, it is out of range (64 bit)",
+            text
+        ))
+    })
+}
+
+/// Marks the value as secret: It is redacted (replaced by ``****``) in output meant for humans,
+/// see ``YamlConfigDocument.secret_paths``. If ``schema`` is given, the value must match it.
+/// Errors while validating do not contain the value.
+///
+///     Schema({'password': Secret(str)})
+#[pyclass(module = "_main")]
+pub(crate) struct Secret {
+    #[pyo3(get)]
+    schema: Option<PyObject>,
+}
+
+#[pymethods]
+impl Secret {
+    #[new]
+    #[args(schema = "None")]
+    fn new(schema: Option<PyObject>) -> Self {
+        Self { schema }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(match &self.schema {
+            None => "Secret()".to_string(),
+            Some(schema) => format!("Secret({})", schema.as_ref(py).repr()?),
+        })
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let schema = match &self.schema {
+            None => return Ok(data),
+            Some(schema) => schema,
+        };
+        py.import("schema")?
+            .getattr("Schema")?
+            .call1((schema,))?
+            .call_method1("validate", (data,))
+            .map(|v| v.into())
+            .map_err(|_| SchemaError::new_err("The secret value does not match its schema."))
+    }
+}
+
+/// The value must be a list without duplicate entries. If ``schema`` is given, every entry must
+/// match it. When documents are merged, the entries of the overriding list are only appended if
+/// the list doesn't contain them yet (see ``YamlConfigDocument.merge_strategies``).
+///
+///     Schema({'hosts': UniqueList(str)})
+#[pyclass(module = "_main")]
+pub(crate) struct UniqueList {
+    #[pyo3(get)]
+    schema: Option<PyObject>,
+}
+
+#[pymethods]
+impl UniqueList {
+    #[new]
+    #[args(schema = "None")]
+    fn new(schema: Option<PyObject>) -> Self {
+        Self { schema }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(match &self.schema {
+            None => "UniqueList()".to_string(),
+            Some(schema) => format!("UniqueList({})", schema.as_ref(py).repr()?),
+        })
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let entries = validate_entries(py, &data, &self.schema)?;
+        for (i, entry) in entries.iter().enumerate() {
+            for other in &entries[..i] {
+                if other.rich_compare(*entry, CompareOp::Eq)?.is_true()? {
+                    return Err(SchemaError::new_err(format!(
+                        "The list contains {} more than once",
+                        entry.repr()?
+                    )));
+                }
+            }
+        }
+        Ok(data)
+    }
+}
+
+/// The value must be a list of dicts, that are identified by the value of their key ``key``
+/// (e.g. ``name``). Every entry must contain the key and the values must be unique. If ``schema``
+/// is given, every entry must match it. When documents are merged, entries of the overriding list
+/// are merged over the entries of the base with the same key, other entries are appended (see
+/// ``YamlConfigDocument.merge_strategies``).
+///
+///     Schema({'services': KeyedList('name', {'name': str, Optional('port'): int})})
+#[pyclass(module = "_main")]
+pub(crate) struct KeyedList {
+    #[pyo3(get)]
+    key: String,
+    #[pyo3(get)]
+    schema: Option<PyObject>,
+}
+
+#[pymethods]
+impl KeyedList {
+    #[new]
+    #[args(schema = "None")]
+    fn new(key: String, schema: Option<PyObject>) -> Self {
+        Self { key, schema }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(match &self.schema {
+            None => format!("KeyedList({:?})", self.key),
+            Some(schema) => format!("KeyedList({:?}, {})", self.key, schema.as_ref(py).repr()?),
+        })
+    }
+
+    fn validate(&self, py: Python, data: PyObject) -> PyResult<PyObject> {
+        let entries = validate_entries(py, &data, &self.schema)?;
+        let mut keys: Vec<&PyAny> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let key = match entry.downcast::<PyDict>().map(|e| e.get_item(&self.key)) {
+                Ok(Some(key)) => key,
+                Ok(None) => {
+                    return Err(SchemaError::new_err(format!(
+                        "The list entry {} has no key {:?}",
+                        entry.repr()?,
+                        self.key
+                    )))
+                }
+                Err(_) => {
+                    return Err(SchemaError::new_err(format!(
+                        "The list entry {} is not a dict",
+                        entry.repr()?
+                    )))
+                }
+            };
+            for other in &keys {
+                if other.rich_compare(key, CompareOp::Eq)?.is_true()? {
+                    return Err(SchemaError::new_err(format!(
+                        "The list contains more than one entry with {} {}",
+                        self.key,
+                        key.repr()?
+                    )));
+                }
+            }
+            keys.push(key);
+        }
+        Ok(data)
+    }
+}
+
+/// Checks that data is a list and that its entries match the schema (if given).
+fn validate_entries<'py>(
+    py: Python<'py>,
+    data: &PyObject,
+    schema: &Option<PyObject>,
+) -> PyResult<Vec<&'py PyAny>> {
+    let list = match data.as_ref(py).downcast::<PyList>() {
+        Ok(list) => list,
+        Err(_) => {
+            return Err(SchemaError::new_err(format!(
+                "{} is not a list",
+                data.as_ref(py).repr()?
+            )))
+        }
+    };
+    let schema = match schema {
+        None => None,
+        Some(schema) => Some(py.import("schema")?.getattr("Schema")?.call1((schema,))?),
+    };
+    let mut entries = Vec::with_capacity(list.len());
+    for entry in list.iter() {
+        if let Some(schema) = schema {
+            schema.call_method1("validate", (entry,))?;
+        }
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Whether value is a ``decimal.Decimal`` (see the ``decimal_floats`` loading option).
+fn is_decimal(value: &PyAny) -> PyResult<bool> {
+    Ok(value.get_type().name()? == "Decimal")
+}
@@ -0,0 +1,186 @@
+//! Resolution contexts, to resolve documents multiple times in one process with isolated settings
+//! (e.g. one context per tenant of a server).
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::loader::load_multiple_yml;
+use crate::lookup::LookupPathEntry;
+use crate::registry::{document_content, document_name, template_body, ContextRegistry};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple, PyType};
+use std::sync::Arc;
+use std::time::Instant;
+
+/// A set of lookup paths, template globals and registered templates and documents, that documents
+/// are resolved with. Templates and documents registered in a context are only visible to documents
+/// resolved with it and take priority over those registered with ``register_template`` and
+/// ``register_document``. Contexts do not share state with each other, so the same documents can be
+/// resolved with different contexts without affecting each other.
+///
+///     tenant = ResolutionContext([tenant_repo, shared_repo], globals={'tenant': 'acme'})
+///     tenant.register_document('defaults/service', defaults_yaml)
+///     doc = tenant.load(Service, 'service.yml')
+///     tenant.process_vars(doc)
+///
+/// :param lookup_paths: Paths to the repositories, where references are looked up (entries can be
+///                      conditional, see ``resolve_and_merge_references``).
+/// :param globals:      Variables available in all templates of documents processed with ``process_vars``.
+#[pyclass(module = "_main")]
+pub(crate) struct ResolutionContext {
+    #[pyo3(get)]
+    lookup_paths: Vec<LookupPathEntry>,
+    pub(crate) globals: YcdDict,
+    pub(crate) registry: Arc<ContextRegistry>,
+}
+
+#[pymethods]
+impl ResolutionContext {
+    #[new]
+    #[args(globals = "None")]
+    fn new(lookup_paths: Vec<LookupPathEntry>, globals: Option<YcdDict>) -> Self {
+        Self {
+            lookup_paths,
+            globals: globals.unwrap_or_default(),
+            registry: Arc::new(ContextRegistry::default()),
+        }
+    }
+
+    #[getter]
+    fn globals(&self, py: Python) -> PyObject {
+        self.globals.to_object(py)
+    }
+
+    /// Registers a template in this context. See ``register_template``.
+    fn register_template(&self, py: Python, name: String, template: YcdValueType) -> PyResult<()> {
+        let body = template_body(py, template)?;
+        self.registry.templates.lock().unwrap().insert(name, body);
+        Ok(())
+    }
+
+    /// Removes a template registered in this context. Returns whether it existed.
+    fn unregister_template(&self, name: &str) -> bool {
+        self.registry
+            .templates
+            .lock()
+            .unwrap()
+            .remove(name)
+            .is_some()
+    }
+
+    /// Registers a document in this context. See ``register_document``.
+    fn register_document(&self, py: Python, name: &str, document: YcdValueType) -> PyResult<()> {
+        let content = document_content(py, name, document)?;
+        self.registry
+            .documents
+            .lock()
+            .unwrap()
+            .insert(document_name(name), content);
+        Ok(())
+    }
+
+    /// Removes a document registered in this context. Returns whether it existed.
+    fn unregister_document(&self, name: &str) -> bool {
+        self.registry
+            .documents
+            .lock()
+            .unwrap()
+            .remove(&document_name(name))
+            .is_some()
+    }
+
+    /// Loads the files like ``load_multiple_yml`` and resolves the references of the result with
+    /// this context (see ``resolve``).
+    ///
+    /// Keyword arguments are loading options, see ``from_yaml``.
+    #[args(args = "*", kwargs = "**")]
+    fn load(
+        slf: Py<Self>,
+        py: Python,
+        doc_type: &PyType,
+        args: &PyTuple,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        let kwargs = Self::with_context(&slf, py, kwargs)?;
+        let start = Instant::now();
+        let doc = load_multiple_yml(py, doc_type, args, Some(kwargs))?;
+        // The timeout applies to loading and resolving together.
+        if let Some(timeout) = kwargs.get_item("timeout") {
+            if let Some(timeout) = timeout.extract::<Option<f64>>()? {
+                let remaining = timeout - start.elapsed().as_secs_f64();
+                kwargs.set_item("timeout", remaining.max(0.0))?;
+            }
+        }
+        Self::resolve_impl(slf, py, doc, kwargs)
+    }
+
+    /// Resolves and merges the references of document (see ``resolve_and_merge_references``), using
+    /// the lookup paths, templates and documents of this context.
+    ///
+    /// Keyword arguments are loading options, see ``from_yaml``.
+    ///
+    ///  :returns: document
+    #[args(kwargs = "**")]
+    fn resolve(
+        slf: Py<Self>,
+        py: Python,
+        document: PyYamlConfigDocument,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        let kwargs = Self::with_context(&slf, py, kwargs)?;
+        Self::resolve_impl(slf, py, document, kwargs)
+    }
+
+    /// Processes the variables of document (see ``process_vars``), with the globals of this context
+    /// available in all templates. Globals passed as keyword argument take priority.
+    ///
+    ///  :returns: document
+    #[args(kwargs = "**")]
+    fn process_vars(
+        &self,
+        py: Python,
+        document: PyYamlConfigDocument,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        let kwargs = match kwargs {
+            None => PyDict::new(py),
+            Some(kwargs) => kwargs.copy()?,
+        };
+        let mut globals = self.globals.clone();
+        if let Some(passed) = kwargs.get_item("globals") {
+            globals.extend(passed.extract::<YcdDict>()?);
+        }
+        kwargs.set_item("globals", globals.to_object(py))?;
+        document.0.call_method(py, "process_vars", (), Some(kwargs))
+    }
+
+    fn __repr__(&self) -> String {
+        let paths: Vec<&String> = self.lookup_paths.iter().map(|e| &e.path).collect();
+        format!("ResolutionContext(lookup_paths={:?})", paths)
+    }
+}
+
+impl ResolutionContext {
+    /// Copy of kwargs, with this context as the ``context`` option.
+    fn with_context<'py>(
+        slf: &Py<Self>,
+        py: Python<'py>,
+        kwargs: Option<&'py PyDict>,
+    ) -> PyResult<&'py PyDict> {
+        let kwargs = match kwargs {
+            None => PyDict::new(py),
+            Some(kwargs) => kwargs.copy()?,
+        };
+        kwargs.set_item("context", slf.clone_ref(py))?;
+        Ok(kwargs)
+    }
+
+    fn resolve_impl(
+        slf: Py<Self>,
+        py: Python,
+        document: PyYamlConfigDocument,
+        kwargs: &PyDict,
+    ) -> PyResult<PyObject> {
+        let args = (slf.borrow(py).lookup_paths.clone(),);
+        document
+            .0
+            .call_method(py, "resolve_and_merge_references", args, Some(kwargs))
+    }
+}
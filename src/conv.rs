@@ -1,15 +1,24 @@
+use crate::encryption::{encrypted_ciphertext, wrapper, EncryptedValue};
+use crate::minijinja::ItemPathPart;
+use crate::nested;
+use crate::nested::{clone_value, Expanded};
+use crate::ycd_list::PyYcdList;
 use crate::YamlConfigDocument;
+use indexmap::IndexMap;
+use pyo3::class::gc::{PyTraverseError, PyVisit};
 use pyo3::exceptions;
 use pyo3::prelude::*;
 use pyo3::types::{PyBool, PyDict, PyFloat, PyInt, PyList, PyString};
 use serde::{Deserialize, Serialize, Serializer};
-use std::collections::HashMap;
 use std::fmt::{Debug, Display, Formatter};
+use std::mem::take;
 
-pub(crate) type YcdDict = HashMap<String, YcdValueType>;
+/// Dicts keep the order of their keys: The order in the file, with keys added by merging after
+/// the existing ones. This order is used everywhere (templates, exports to Python, serialization).
+pub(crate) type YcdDict = IndexMap<String, YcdValueType>;
 pub(crate) type YcdList = Vec<YcdValueType>;
 #[derive(Debug)]
-pub(crate) struct YHashMap<K, V>(pub(crate) HashMap<K, V>);
+pub(crate) struct YHashMap<K, V>(pub(crate) IndexMap<K, V>);
 
 impl<K: Debug, V: Debug> Display for YHashMap<K, V> {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -44,19 +53,171 @@ impl IntoPy<PyObject> for PyYamlConfigDocument {
     }
 }
 
-#[derive(Serialize, Clone, Debug)]
+#[derive(Serialize, Debug)]
 #[serde(untagged)]
 pub(crate) enum YcdValueType {
     Ycd(PyYamlConfigDocument),
     Dict(YcdDict),
     List(YcdList),
     YString(String),
+    /// A string that is not converted into an integer after rendering templates (``str`` filter).
+    /// Exported like any other string.
+    ForcedString(String),
     Bool(bool),
     Int(i64),
     Float(f64),
+    /// A float kept in decimal notation (``decimal_floats`` loading option), a ``decimal.Decimal``
+    /// in Python. Serialized as float.
+    #[serde(serialize_with = "serialize_decimal")]
+    Decimal(String),
+}
+
+#[allow(clippy::ptr_arg)]
+fn serialize_decimal<S>(value: &String, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_f64(decimal_to_f64(value))
+}
+
+/// Visits the documents contained in the values of dict, for the GC protocol of the classes holding
+/// it. Documents reference each other (e.g. sub-documents their parent), so the garbage collector
+/// must know about these references to collect documents that are no longer used.
+pub(crate) fn traverse_dict(dict: &YcdDict, visit: &PyVisit) -> Result<(), PyTraverseError> {
+    dict.values().try_for_each(|v| v.traverse(visit))
+}
+
+/// Copies are made with an explicit stack (see nested::clone_value), so copying deeply nested
+/// values can't overflow the stack.
+impl Clone for YcdValueType {
+    fn clone(&self) -> Self {
+        clone_value(self)
+    }
+}
+
+/// The decimal (in the notation of Python's ``decimal.Decimal``) as float.
+pub(crate) fn decimal_to_f64(value: &str) -> f64 {
+    value.parse().unwrap_or(f64::NAN)
+}
+
+/// Formats the float the same on all platforms and in all locales, like Python's ``repr``: With as
+/// few digits as needed to read it back as the same float and always with a ``.``. Very large and
+/// very small values (``>= 1e16``, ``< 1e-4``) are written with a signed exponent (``1.0e+20``,
+/// ``1.5e-7``), non-finite values as ``inf``, ``-inf`` and ``nan``.
+pub(crate) fn format_float(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    // Debug (unlike Display) switches to the exponent notation for very large and small values.
+    let repr = format!("{:?}", value);
+    let (mantissa, exponent) = match repr.split_once('e') {
+        None => return repr,
+        Some(parts) => parts,
+    };
+    let mantissa = if mantissa.contains('.') {
+        mantissa.to_string()
+    } else {
+        format!("{}.0", mantissa)
+    };
+    if exponent.starts_with('-') {
+        format!("{}e{}", mantissa, exponent)
+    } else {
+        format!("{}e+{}", mantissa, exponent)
+    }
+}
+
+/// The integer value of the decimal (in the notation of Python's ``decimal.Decimal``), without
+/// rounding. None if it has a fractional part (or is not a decimal). Values beyond the range of
+/// i128 are clamped to it.
+pub(crate) fn decimal_to_integer(value: &str) -> Option<i128> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let (mantissa, exponent) = match value.split_once(|c| c == 'e' || c == 'E') {
+        Some((m, e)) => (m, e.parse::<i64>().ok()?),
+        None => (value, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let digits = format!("{}{}", integer, fraction);
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let exponent = exponent.saturating_sub(fraction.len() as i64);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Some(0);
+    }
+    let integer = if exponent < 0 {
+        // All digits after the decimal point must be zero (the first digit isn't).
+        let point = (digits.len() as i64).saturating_add(exponent);
+        if point <= 0 || digits[point as usize..].chars().any(|c| c != '0') {
+            return None;
+        }
+        digits[..point as usize].to_string()
+    } else if (digits.len() as i64).saturating_add(exponent) <= 38 {
+        format!("{}{}", digits, "0".repeat(exponent as usize))
+    } else {
+        return Some(if negative { i128::MIN } else { i128::MAX });
+    };
+    let integer: i128 = integer.parse().ok()?;
+    Some(if negative { -integer } else { integer })
+}
+
+/// Replaces the floats at the paths in dict (the content of a YAML file) with decimals written
+/// like the scalars in the file (texts, see yaml::find_float_texts), so no digits are lost.
+pub(crate) fn floats_to_source_decimals(
+    dict: &mut YcdDict,
+    texts: Vec<(Vec<ItemPathPart>, String)>,
+) {
+    for (path, text) in texts {
+        let mut value = match path.split_first() {
+            Some((ItemPathPart::Key(first), _)) => dict.get_mut(first),
+            _ => None,
+        };
+        for part in path.iter().skip(1) {
+            value = match (value, part) {
+                (Some(YcdValueType::Dict(d)), ItemPathPart::Key(k)) => d.get_mut(k),
+                (Some(YcdValueType::List(l)), ItemPathPart::Index(i)) => l.get_mut(*i),
+                _ => None,
+            };
+        }
+        if let Some(value) = value {
+            if matches!(value, YcdValueType::Float(f) if f.is_finite()) {
+                *value = YcdValueType::Decimal(text);
+            }
+        }
+    }
+}
+
+/// Replaces all (finite) floats in value with decimals, written with as few digits as possible
+/// (e.g. ``0.1`` instead of ``0.1000000000000000055511151231257827``). Used for the floats of
+/// files, that are not YAML files (see floats_to_source_decimals).
+pub(crate) fn floats_to_decimals(value: &mut YcdValueType) {
+    match value {
+        YcdValueType::Dict(d) => d.values_mut().for_each(floats_to_decimals),
+        YcdValueType::List(l) => l.iter_mut().for_each(floats_to_decimals),
+        YcdValueType::Float(f) if f.is_finite() => {
+            let decimal = format!("{:?}", f);
+            *value = YcdValueType::Decimal(decimal);
+        }
+        _ => {}
+    }
 }
 
 impl YcdValueType {
+    /// Visits the documents contained in the value (see traverse_dict).
+    pub(crate) fn traverse(&self, visit: &PyVisit) -> Result<(), PyTraverseError> {
+        nested::visit(self, |value| match value {
+            YcdValueType::Ycd(doc) => visit.call(&doc.0),
+            _ => Ok(()),
+        })
+    }
+
     pub(crate) fn unwrap_dict(self) -> YcdDict {
         if let YcdValueType::Dict(d) = self {
             d
@@ -66,11 +227,47 @@ impl YcdValueType {
     }
 }
 
+/// Converts dict into a Python dict, with the keys inserted in the order of dict.
+pub(crate) fn dict_to_object(py: Python, dict: &YcdDict) -> PyObject {
+    let out = PyDict::new(py);
+    for (k, v) in dict {
+        out.set_item(k, v.to_object(py))
+            .expect("Failed to set_item on dict");
+    }
+    out.into()
+}
+
+/// Moves the keys in key_order to the front of dict, in that order. Other keys keep their order.
+pub(crate) fn reorder_keys(dict: &mut YcdDict, key_order: &[String]) {
+    let mut reordered = YcdDict::with_capacity(dict.len());
+    for key in key_order {
+        if let Some(value) = dict.shift_remove(key) {
+            reordered.insert(key.clone(), value);
+        }
+    }
+    reordered.extend(take(dict));
+    *dict = reordered;
+}
+
+/// Renames key in dict to new_key. The entry keeps its position.
+pub(crate) fn rename_key(dict: &mut YcdDict, key: &str, new_key: &str) {
+    *dict = take(dict)
+        .into_iter()
+        .map(|(k, v)| {
+            if k == key {
+                (new_key.to_string(), v)
+            } else {
+                (k, v)
+            }
+        })
+        .collect();
+}
+
 /// Same as YcdValueType but without any containing Ycd; for deserialization
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(untagged)]
 pub(crate) enum SimpleYcdValueType {
-    Dict(HashMap<String, SimpleYcdValueType>),
+    Dict(IndexMap<String, SimpleYcdValueType>),
     List(Vec<SimpleYcdValueType>),
     YString(String),
     Bool(bool),
@@ -85,10 +282,11 @@ impl Display for YcdValueType {
             YcdValueType::Ycd(_) => write!(f, "<a document>"),
             YcdValueType::Dict(_) => write!(f, "<a dictionary>"),
             YcdValueType::List(_) => write!(f, "<a list>"),
-            YcdValueType::YString(v) => write!(f, "{}", v),
+            YcdValueType::YString(v) | YcdValueType::ForcedString(v) => write!(f, "{}", v),
             YcdValueType::Bool(v) => write!(f, "{}", v),
             YcdValueType::Int(v) => write!(f, "{}", v),
-            YcdValueType::Float(v) => write!(f, "{}", v),
+            YcdValueType::Float(v) => write!(f, "{}", format_float(*v)),
+            YcdValueType::Decimal(v) => write!(f, "{}", v),
         }
     }
 }
@@ -101,97 +299,120 @@ impl Display for SimpleYcdValueType {
             SimpleYcdValueType::YString(v) => write!(f, "{}", v),
             SimpleYcdValueType::Bool(v) => write!(f, "{}", v),
             SimpleYcdValueType::Int(v) => write!(f, "{}", v),
-            SimpleYcdValueType::Float(v) => write!(f, "{}", v),
+            SimpleYcdValueType::Float(v) => write!(f, "{}", format_float(*v)),
         }
     }
 }
 
+/// Nested dicts and lists are extracted with an explicit stack (see nested::build), so extracting
+/// deeply nested values can't overflow the stack.
 impl<'source> FromPyObject<'source> for YcdValueType {
     fn extract(v: &'source PyAny) -> PyResult<Self> {
-        match v.get_type().name()? {
-            "dict" => {
-                if let Ok(v) = <HashMap<String, YcdValueType>>::extract(v) {
-                    return Ok(YcdValueType::Dict(v));
-                }
+        nested::build(v, extract_shallow)
+    }
+}
+
+/// Extracts v, except for the entries of dicts and lists (see FromPyObject for YcdValueType).
+fn extract_shallow(v: &PyAny) -> PyResult<Expanded<&PyAny>> {
+    match v.get_type().name()? {
+        "dict" => {
+            if let Ok(v) = <IndexMap<String, &PyAny>>::extract(v) {
+                return Ok(Expanded::Dict(v.into_iter().collect()));
             }
-            "list" => {
-                if let Ok(v) = <Vec<YcdValueType>>::extract(v) {
-                    return Ok(YcdValueType::List(v));
-                }
+        }
+        "list" => {
+            if let Ok(v) = v.downcast::<PyList>() {
+                return Ok(Expanded::List(v.iter().collect()));
             }
-            "str" => {
-                if let Ok(v) = <String>::extract(v) {
-                    return Ok(YcdValueType::YString(v));
-                }
+        }
+        "str" => {
+            if let Ok(v) = <String>::extract(v) {
+                return Ok(Expanded::Value(YcdValueType::YString(v)));
             }
-            "int" => {
-                if let Ok(v) = <i64>::extract(v) {
-                    return Ok(YcdValueType::Int(v));
-                }
+        }
+        "int" => {
+            if let Ok(v) = <i64>::extract(v) {
+                return Ok(Expanded::Value(YcdValueType::Int(v)));
             }
-            "bool" => {
-                if let Ok(v) = <bool>::extract(v) {
-                    return Ok(YcdValueType::Bool(v));
-                }
+        }
+        "bool" => {
+            if let Ok(v) = <bool>::extract(v) {
+                return Ok(Expanded::Value(YcdValueType::Bool(v)));
             }
-            "float" => {
-                if let Ok(v) = <f64>::extract(v) {
-                    return Ok(YcdValueType::Float(v));
-                }
+        }
+        "float" => {
+            if let Ok(v) = <f64>::extract(v) {
+                return Ok(Expanded::Value(YcdValueType::Float(v)));
             }
-            &_ => { /* Go to fallback*/ }
         }
-        // Fallback
-        if let Ok(v) = v.extract::<Py<YamlConfigDocument>>() {
-            Ok(YcdValueType::Ycd(v.into()))
-        } else if let Ok(v) = <String>::extract(v) {
-            Ok(YcdValueType::YString(v))
-        } else if let Ok(v) = <i64>::extract(v) {
-            Ok(YcdValueType::Int(v))
-        } else if let Ok(v) = <f64>::extract(v) {
-            Ok(YcdValueType::Float(v))
-        } else if let Ok(v) = <bool>::extract(v) {
-            Ok(YcdValueType::Bool(v))
-        } else if let Ok(v) = <Vec<YcdValueType>>::extract(v) {
-            Ok(YcdValueType::List(v))
-        } else if let Ok(v) = <HashMap<String, YcdValueType>>::extract(v) {
-            Ok(YcdValueType::Dict(v))
-        } else {
-            Err(exceptions::PyTypeError::new_err(format!(
-                "Could not map type for {:?}",
-                v
-            )))
+        "Decimal" => return Ok(Expanded::Value(YcdValueType::Decimal(v.str()?.to_string()))),
+        "EncryptedValue" => {
+            let py = v.py();
+            let mut v: PyRefMut<EncryptedValue> = v.extract()?;
+            return Ok(Expanded::Value(wrapper(v.ciphertext(py)?)));
         }
+        &_ => { /* Go to fallback*/ }
+    }
+    // Fallback
+    if let Ok(v) = v.extract::<Py<YamlConfigDocument>>() {
+        Ok(Expanded::Value(YcdValueType::Ycd(v.into())))
+    } else if let Ok(v) = v.extract::<PyRef<PyYcdList>>() {
+        Ok(Expanded::Value(YcdValueType::List(v.list().clone())))
+    } else if let Ok(v) = <String>::extract(v) {
+        Ok(Expanded::Value(YcdValueType::YString(v)))
+    } else if let Ok(v) = <i64>::extract(v) {
+        Ok(Expanded::Value(YcdValueType::Int(v)))
+    } else if let Ok(v) = <f64>::extract(v) {
+        Ok(Expanded::Value(YcdValueType::Float(v)))
+    } else if let Ok(v) = <bool>::extract(v) {
+        Ok(Expanded::Value(YcdValueType::Bool(v)))
+    } else if let Ok(v) = <Vec<&PyAny>>::extract(v) {
+        Ok(Expanded::List(v))
+    } else if let Ok(v) = <IndexMap<String, &PyAny>>::extract(v) {
+        Ok(Expanded::Dict(v.into_iter().collect()))
+    } else {
+        Err(exceptions::PyTypeError::new_err(format!(
+            "Could not map type for {:?}",
+            v
+        )))
     }
 }
 
 impl IntoPy<PyObject> for YcdValueType {
     fn into_py(self, py: Python) -> PyObject {
-        match self {
-            YcdValueType::Ycd(v) => v.0.into_py(py),
-            YcdValueType::Dict(v) => v.into_py(py),
-            YcdValueType::List(v) => v.into_py(py),
-            YcdValueType::YString(v) => v.into_py(py),
-            YcdValueType::Bool(v) => v.into_py(py),
-            YcdValueType::Int(v) => v.into_py(py),
-            YcdValueType::Float(v) => v.into_py(py),
-            //YcdValueType::CatchAll(v) => v.into_py(py)
-        }
+        self.to_object(py)
     }
 }
 
+/// Nested dicts and lists are converted with an explicit stack (see nested::to_object), so
+/// converting deeply nested values can't overflow the stack.
 impl ToPyObject for YcdValueType {
     fn to_object(&self, py: Python) -> PyObject {
-        match self {
-            YcdValueType::Ycd(v) => v.0.to_object(py), // TODO: Probably not the fastest choice...
-            YcdValueType::Dict(v) => v.to_object(py),
-            YcdValueType::List(v) => v.to_object(py),
-            YcdValueType::YString(v) => v.to_object(py),
-            YcdValueType::Bool(v) => v.to_object(py),
-            YcdValueType::Int(v) => v.to_object(py),
-            YcdValueType::Float(v) => v.to_object(py),
-            //YcdValueType::CatchAll(v) => v.to_object(py)
-        }
+        nested::to_object(py, self, |value| match value {
+            YcdValueType::Ycd(v) => Some(v.0.to_object(py)), // TODO: Probably not the fastest choice...
+            YcdValueType::Dict(v) => encrypted_ciphertext(v).map(|ciphertext| {
+                Py::new(py, EncryptedValue::encrypted(ciphertext.to_string()))
+                    .expect("Failed to create EncryptedValue")
+                    .into_py(py)
+            }),
+            YcdValueType::List(_) => None,
+            YcdValueType::YString(v) | YcdValueType::ForcedString(v) => Some(v.to_object(py)),
+            YcdValueType::Bool(v) => Some(v.to_object(py)),
+            YcdValueType::Int(v) => Some(v.to_object(py)),
+            YcdValueType::Float(v) => Some(v.to_object(py)),
+            YcdValueType::Decimal(v) => Some(decimal_to_object(py, v)),
+        })
+    }
+}
+
+/// A ``decimal.Decimal``, or a float if it can't be created.
+fn decimal_to_object(py: Python, value: &str) -> PyObject {
+    match py
+        .import("decimal")
+        .and_then(|m| m.getattr("Decimal")?.call1((value,)))
+    {
+        Ok(v) => v.into(),
+        Err(_) => decimal_to_f64(value).to_object(py),
     }
 }
 
@@ -201,7 +422,7 @@ impl Serialize for PyYamlConfigDocument {
         S: Serializer,
     {
         Python::with_gil(|py| match self.0.extract::<YamlConfigDocument>(py) {
-            Ok(ycd) => serializer.collect_map(ycd.doc),
+            Ok(ycd) => serializer.collect_map(&ycd.doc),
             Err(_) => panic!("Internal serialization failed."),
         })
     }
@@ -247,10 +468,13 @@ impl From<YcdValueType> for SimpleYcdValueType {
             YcdValueType::List(v) => {
                 SimpleYcdValueType::List(v.into_iter().map(|x| x.into()).collect())
             }
-            YcdValueType::YString(v) => SimpleYcdValueType::YString(v),
+            YcdValueType::YString(v) | YcdValueType::ForcedString(v) => {
+                SimpleYcdValueType::YString(v)
+            }
             YcdValueType::Bool(v) => SimpleYcdValueType::Bool(v),
             YcdValueType::Int(v) => SimpleYcdValueType::Int(v),
             YcdValueType::Float(v) => SimpleYcdValueType::Float(v),
+            YcdValueType::Decimal(v) => SimpleYcdValueType::Float(decimal_to_f64(&v)),
             _ => {
                 panic!("Invalid unexpected internal conversion.")
             } // This should never happen.
@@ -258,7 +482,7 @@ impl From<YcdValueType> for SimpleYcdValueType {
     }
 }
 
-impl From<YHashMap<String, SimpleYcdValueType>> for HashMap<String, YcdValueType> {
+impl From<YHashMap<String, SimpleYcdValueType>> for YcdDict {
     fn from(h: YHashMap<String, SimpleYcdValueType>) -> Self {
         h.0.into_iter().map(|(k, v)| (k, v.into())).collect()
     }
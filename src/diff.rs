@@ -0,0 +1,97 @@
+use crate::conv::YcdValueType::{Dict, List, YString};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::merger::same_value;
+use crate::{REMOVE, REMOVE_FROM_LIST_PREFIX};
+use pyo3::exceptions;
+use pyo3::PyResult;
+
+/// Computes the smallest override that produces target when it is merged over base.
+/// Both values must not contain sub-documents (see recursive_docs_to_dicts).
+///
+/// Keys missing in target are removed with $remove. Lists can only be changed by removing
+/// strings ($remove::) and appending entries, other changes of lists raise a ValueError.
+/// path is the path of the values (parts separated by "/"), for error messages.
+/// Returns None if base and target are equal.
+pub(crate) fn extract_override(
+    base: &YcdValueType,
+    target: &YcdValueType,
+    path: &str,
+) -> PyResult<Option<YcdValueType>> {
+    match (base, target) {
+        (Dict(base), Dict(target)) => {
+            let mut out = YcdDict::new();
+            for (k, tv) in target.iter() {
+                match base.get(k) {
+                    None => {
+                        out.insert(k.clone(), tv.clone());
+                    }
+                    Some(bv) => {
+                        if let Some(ov) = extract_override(bv, tv, &join(path, k))? {
+                            out.insert(k.clone(), ov);
+                        }
+                    }
+                }
+            }
+            for k in base.keys().filter(|k| !target.contains_key(*k)) {
+                out.insert(k.clone(), YString(REMOVE.to_string()));
+            }
+            Ok(if out.is_empty() {
+                None
+            } else {
+                Some(Dict(out))
+            })
+        }
+        (List(base), List(target)) => extract_list_override(base, target, path),
+        (base, target) => Ok(if same_value(base, target) {
+            None
+        } else {
+            Some(target.clone())
+        }),
+    }
+}
+
+/// Lists are merged by appending the entries of the override, after that all strings marked
+/// with $remove:: are removed.
+fn extract_list_override(
+    base: &[YcdValueType],
+    target: &[YcdValueType],
+    path: &str,
+) -> PyResult<Option<YcdValueType>> {
+    let mut removed: Vec<&String> = vec![];
+    for v in base {
+        if let YString(s) = v {
+            if !removed.contains(&s) && !target.iter().any(|tv| same_value(v, tv)) {
+                removed.push(s);
+            }
+        }
+    }
+    let kept: Vec<&YcdValueType> = base
+        .iter()
+        .filter(|v| !matches!(v, YString(s) if removed.contains(&s)))
+        .collect();
+    if kept.len() > target.len() || !kept.iter().zip(target).all(|(a, b)| same_value(a, b)) {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "The list at {} can not be changed into the target list by an override. \
+            Entries can only be appended and strings can only be removed.",
+            path
+        )));
+    }
+    let out: Vec<YcdValueType> = removed
+        .into_iter()
+        .map(|s| YString(format!("{}{}", REMOVE_FROM_LIST_PREFIX, s)))
+        .chain(target[kept.len()..].iter().cloned())
+        .collect();
+    Ok(if out.is_empty() {
+        None
+    } else {
+        Some(List(out))
+    })
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}/{}", path, key)
+    }
+}
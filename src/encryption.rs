@@ -0,0 +1,170 @@
+//! Encrypted values of individual fields: ``password: !encrypted AGE-...`` or
+//! ``password: {$encrypted: AGE-...}``. Documents only contain the ciphertext. In Python, the
+//! values are EncryptedValue objects, that are decrypted on access with the key provider
+//! registered with ``register_key_provider``.
+use crate::conv::YcdValueType::{Dict, List, YString};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::minijinja::ItemPathPart;
+use crate::registry::get_key_provider;
+use crate::{EncryptionError, ENCRYPTED};
+use pyo3::prelude::*;
+use std::mem::take;
+
+/// A value encrypted with the registered key provider. ``EncryptedValue(plaintext)`` creates a new
+/// value, that is encrypted when it's stored in a document (e.g. by assigning it to a key of a
+/// frozen document and unfreezing it).
+///
+/// The plaintext is decrypted when ``value`` is accessed for the first time. Setting ``value``
+/// changes the plaintext, it is encrypted again when it's stored in a document.
+///
+/// Exports (``to_dict``, ``to_yaml``, ...) only contain the ciphertext, as ``{$encrypted: <ciphertext>}``.
+#[pyclass(module = "_main")]
+#[derive(Clone, Debug)]
+pub(crate) struct EncryptedValue {
+    /// None if the plaintext was set and not encrypted yet.
+    ciphertext: Option<String>,
+    /// None until decrypted.
+    plaintext: Option<String>,
+}
+
+#[pymethods]
+impl EncryptedValue {
+    #[new]
+    fn new(plaintext: String) -> Self {
+        Self {
+            ciphertext: None,
+            plaintext: Some(plaintext),
+        }
+    }
+
+    #[staticmethod]
+    /// An EncryptedValue for the (already encrypted) ciphertext.
+    fn from_ciphertext(ciphertext: String) -> Self {
+        Self::encrypted(ciphertext)
+    }
+
+    #[getter]
+    /// The plaintext. Decrypted with the registered key provider.
+    fn value(&mut self, py: Python) -> PyResult<String> {
+        if let Some(plaintext) = &self.plaintext {
+            return Ok(plaintext.clone());
+        }
+        let ciphertext = self.ciphertext.clone().unwrap_or_default();
+        let plaintext = call_key_provider(py, "decrypt", &ciphertext)?;
+        self.plaintext = Some(plaintext.clone());
+        Ok(plaintext)
+    }
+
+    #[setter]
+    fn set_value(&mut self, value: String) {
+        self.plaintext = Some(value);
+        self.ciphertext = None;
+    }
+
+    #[getter]
+    /// The ciphertext. Encrypted with the registered key provider, if the plaintext was changed.
+    fn ciphertext(&mut self, py: Python) -> PyResult<String> {
+        if let Some(ciphertext) = &self.ciphertext {
+            return Ok(ciphertext.clone());
+        }
+        let plaintext = self.plaintext.clone().unwrap_or_default();
+        let ciphertext = call_key_provider(py, "encrypt", &plaintext)?;
+        self.ciphertext = Some(ciphertext.clone());
+        Ok(ciphertext)
+    }
+
+    #[getter]
+    /// Whether the plaintext is known (decrypted or set).
+    fn decrypted(&self) -> bool {
+        self.plaintext.is_some()
+    }
+
+    fn __repr__(&self) -> String {
+        match &self.ciphertext {
+            Some(ciphertext) => format!("EncryptedValue.from_ciphertext({:?})", ciphertext),
+            None => "EncryptedValue(<not encrypted yet>)".to_string(),
+        }
+    }
+
+    fn __str__(&self) -> &'static str {
+        "<encrypted>"
+    }
+}
+
+impl EncryptedValue {
+    pub(crate) fn encrypted(ciphertext: String) -> Self {
+        Self {
+            ciphertext: Some(ciphertext),
+            plaintext: None,
+        }
+    }
+}
+
+/// ``{$encrypted: <ciphertext>}``.
+pub(crate) fn wrapper(ciphertext: String) -> YcdValueType {
+    Dict(YcdDict::from_iter([(
+        ENCRYPTED.to_string(),
+        YString(ciphertext),
+    )]))
+}
+
+/// The ciphertext, if dict is an encrypted value (``{$encrypted: <ciphertext>}``).
+pub(crate) fn encrypted_ciphertext(dict: &YcdDict) -> Option<&str> {
+    match dict.get(ENCRYPTED) {
+        Some(YString(ciphertext)) if dict.len() == 1 => Some(ciphertext),
+        _ => None,
+    }
+}
+
+/// Calls the method (``encrypt`` or ``decrypt``) of the registered key provider.
+fn call_key_provider(py: Python, method: &str, text: &str) -> PyResult<String> {
+    let provider = get_key_provider(py)?.ok_or_else(|| {
+        EncryptionError::new_err(format!(
+            "Unable to {} a value: No key provider is registered (see register_key_provider).",
+            method
+        ))
+    })?;
+    provider
+        .call_method1(py, method, (text,))
+        .and_then(|v| v.extract(py))
+        .map_err(|e| {
+            let new = EncryptionError::new_err(format!("Unable to {} a value: {}", method, e));
+            new.pvalue(py).setattr("__cause__", e.pvalue(py)).ok();
+            new
+        })
+}
+
+/// Replaces the strings at the paths in dict (the content of a file) with encrypted values
+/// (``{<key>: <string>}``, key is ``$encrypted`` with the directive prefix of the file).
+/// Used for the scalars tagged with ``!encrypted``.
+pub(crate) fn wrap_tagged_values(dict: &mut YcdDict, paths: Vec<Vec<ItemPathPart>>, key: &str) {
+    for path in paths {
+        if let Some((ItemPathPart::Key(first), rest)) = path.split_first() {
+            if let Some(value) = dict.get_mut(first) {
+                wrap_tagged_value(value, rest, key);
+            }
+        }
+    }
+}
+
+fn wrap_tagged_value(value: &mut YcdValueType, path: &[ItemPathPart], key: &str) {
+    match (path.split_first(), value) {
+        (None, value) => {
+            if let YString(s) = value {
+                let ciphertext = YString(take(s));
+                *value = Dict(YcdDict::from_iter([(key.to_string(), ciphertext)]));
+            }
+        }
+        (Some((ItemPathPart::Key(k), rest)), Dict(d)) => {
+            if let Some(v) = d.get_mut(k) {
+                wrap_tagged_value(v, rest, key);
+            }
+        }
+        (Some((ItemPathPart::Index(i), rest)), List(l)) => {
+            if let Some(v) = l.get_mut(*i) {
+                wrap_tagged_value(v, rest, key);
+            }
+        }
+        _ => {}
+    }
+}
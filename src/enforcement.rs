@@ -0,0 +1,113 @@
+//! Checks resolved documents against the policies registered with ``register_policy``.
+use crate::conv::PyYamlConfigDocument;
+use crate::conv::YcdValueType::Dict;
+use crate::graph::collect_subdocuments;
+use crate::merger::evaluate_condition;
+use crate::registry::{get_policies, Policy};
+use crate::PolicyViolationError;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyType};
+
+/// A violation: name of the policy, path of the (sub-)document (parts separated by "/", empty
+/// for the document itself) and message.
+pub(crate) type Violation = (String, String, String);
+
+/// Returns all violations of the registered policies by the document and its sub-documents.
+pub(crate) fn check_policies(
+    py: Python,
+    document: &PyYamlConfigDocument,
+) -> PyResult<Vec<Violation>> {
+    let policies = get_policies(py)?;
+    let mut out = vec![];
+    if !policies.is_empty() {
+        check_document(py, document, "", &policies, &mut out)?;
+    }
+    Ok(out)
+}
+
+/// Raises a PolicyViolationError, if the document or its sub-documents violate registered policies.
+/// The violations are available via its ``violations`` attribute.
+pub(crate) fn enforce_policies(py: Python, document: &PyYamlConfigDocument) -> PyResult<()> {
+    let violations = check_policies(py, document)?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let class_name: String = document
+        .getattr(py, "__class__")?
+        .getattr(py, "__name__")?
+        .extract(py)?;
+    let err = PolicyViolationError::new_err(format!(
+        "The {} document violates policies:\n{}",
+        class_name,
+        violations
+            .iter()
+            .map(|(name, path, message)| {
+                if path.is_empty() {
+                    format!("- {}: {}", name, message)
+                } else {
+                    format!("- {} (in {}): {}", name, path, message)
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    ));
+    err.pvalue(py).setattr("violations", violations)?;
+    Err(err)
+}
+
+fn check_document(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    path: &str,
+    policies: &[(String, Policy)],
+    out: &mut Vec<Violation>,
+) -> PyResult<()> {
+    for (name, policy) in policies {
+        let applies = match &policy.doc_type {
+            None => path.is_empty(),
+            Some(doc_type) => doc_type
+                .extract::<&PyType>(py)?
+                .is_instance(document.0.as_ref(py))?,
+        };
+        if applies {
+            for message in check_rule(py, document, &policy.rule)? {
+                out.push((name.clone(), path.to_string(), message));
+            }
+        }
+    }
+    let mut subdocuments = vec![];
+    let body = Dict(document.borrow(py).doc.clone());
+    collect_subdocuments(py, &body, path, &mut subdocuments);
+    for (sub_path, subdocument) in subdocuments {
+        check_document(py, &subdocument, &sub_path, policies, out)?;
+    }
+    Ok(())
+}
+
+/// Returns the messages of the violations of the rule by the document.
+fn check_rule(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    rule: &PyObject,
+) -> PyResult<Vec<String>> {
+    if let Ok(expression) = rule.extract::<&str>(py) {
+        return Ok(if evaluate_condition(py, document, "policy", expression)? {
+            vec![]
+        } else {
+            vec![format!("{} is not true", expression)]
+        });
+    }
+    let result = rule.call1(py, (document.to_object(py),))?;
+    let result = result.as_ref(py);
+    if result.is_none() {
+        Ok(vec![])
+    } else if let Ok(message) = result.extract::<String>() {
+        Ok(vec![message])
+    } else if result.is_instance::<PyList>()? {
+        result.extract()
+    } else if result.is_true()? {
+        Ok(vec![])
+    } else {
+        Ok(vec!["The policy was violated".to_string()])
+    }
+}
@@ -6,5 +6,21 @@ create_exception!(_main, VariableProcessingError, ConfigcrunchError);
 create_exception!(_main, InvalidDocumentError, ConfigcrunchError);
 create_exception!(_main, InvalidHeaderError, InvalidDocumentError);
 create_exception!(_main, InvalidRemoveError, InvalidDocumentError);
+create_exception!(_main, LockViolationError, InvalidDocumentError);
+create_exception!(_main, DocumentLimitError, InvalidDocumentError);
+create_exception!(_main, DuplicateKeyError, InvalidDocumentError);
+create_exception!(_main, SignatureError, InvalidDocumentError);
+create_exception!(_main, PolicyViolationError, ConfigcrunchError);
+create_exception!(_main, ResolutionCancelledError, ConfigcrunchError);
+create_exception!(_main, EncryptionError, ConfigcrunchError);
+create_exception!(_main, DeprecatedKeyWarning, pyo3::exceptions::PyUserWarning);
+create_exception!(_main, UnknownKeyWarning, pyo3::exceptions::PyUserWarning);
+create_exception!(_main, DuplicateKeyWarning, pyo3::exceptions::PyUserWarning);
+create_exception!(_main, CoercionWarning, pyo3::exceptions::PyUserWarning);
 
 pyo3::import_exception!(schema, SchemaError);
+create_exception!(_main, RuleViolationError, SchemaError);
+create_exception!(_main, UnknownKeyError, SchemaError);
+create_exception!(_main, LayerValidationError, SchemaError);
+create_exception!(_main, MergedValidationError, SchemaError);
+create_exception!(_main, WorkspaceValidationError, SchemaError);
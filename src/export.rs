@@ -0,0 +1,405 @@
+use crate::conv::YcdValueType::{
+    Bool, Decimal, Dict, Float, ForcedString, Int, List, YString, Ycd,
+};
+use crate::conv::{decimal_to_f64, format_float, PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::policy::unescaped;
+use crate::redaction::{is_secret, REDACTED};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+
+/// Flattens a document body into environment variables.
+///
+/// The variable names are built from the (upper-cased) keys, joined by separator and
+/// prefixed with prefix (if given). List entries use their index as key.
+/// Characters that are not valid in environment variable names are replaced by "_".
+/// Floats are rounded to float_precision digits after the decimal point (if given).
+pub(crate) fn to_env_vars(
+    py: Python,
+    value: &YcdValueType,
+    prefix: Option<&str>,
+    separator: &str,
+    float_precision: Option<u32>,
+) -> HashMap<String, String> {
+    let mut out = HashMap::new();
+    let env = EnvRendering {
+        separator,
+        float_precision,
+    };
+    to_env_vars_impl(py, value, prefix.map(env_name_part), env, &mut out);
+    out
+}
+
+#[derive(Clone, Copy)]
+struct EnvRendering<'a> {
+    separator: &'a str,
+    float_precision: Option<u32>,
+}
+
+fn to_env_vars_impl(
+    py: Python,
+    value: &YcdValueType,
+    name: Option<String>,
+    env: EnvRendering,
+    out: &mut HashMap<String, String>,
+) {
+    let separator = env.separator;
+    let join = |key: &str| match &name {
+        None => env_name_part(key),
+        Some(n) => format!("{}{}{}", n, separator, env_name_part(key)),
+    };
+    match value {
+        Ycd(v) => {
+            for (k, v) in v.borrow(py).doc.iter() {
+                to_env_vars_impl(py, v, Some(join(k)), env, out)
+            }
+        }
+        Dict(v) => {
+            for (k, v) in v.iter() {
+                to_env_vars_impl(py, v, Some(join(k)), env, out)
+            }
+        }
+        List(v) => {
+            for (i, v) in v.iter().enumerate() {
+                to_env_vars_impl(py, v, Some(join(&i.to_string())), env, out)
+            }
+        }
+        YString(_) | ForcedString(_) | Bool(_) | Int(_) | Float(_) | Decimal(_) => {
+            if let Some(name) = &name {
+                let value = round_floats(value.clone(), env.float_precision);
+                out.insert(name.clone(), value.to_string());
+            }
+        }
+    }
+}
+
+fn env_name_part(key: &str) -> String {
+    key.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// Renders environment variables as the content of a .env file, sorted by name.
+/// Values are double-quoted if needed.
+pub(crate) fn to_env_file(vars: &HashMap<String, String>) -> String {
+    let mut names: Vec<&String> = vars.keys().collect();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| format!("{}={}\n", name, quote_env_value(&vars[name])))
+        .collect()
+}
+
+fn quote_env_value(value: &str) -> String {
+    if !value.is_empty()
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.,:/@+".contains(c))
+    {
+        return value.to_string();
+    }
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '$' => out.push_str("\\$"),
+            _ => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A value in canonical YAML: Either written in the same line as its key or list dash, or as
+/// lines of a block below it.
+pub(crate) enum Canonical {
+    Inline(String),
+    Block(Vec<String>),
+}
+
+/// The source files of values by their path (parts separated by "."), see provenance::value_files.
+pub(crate) type Annotations = HashMap<String, Vec<String>>;
+
+/// What is added to the canonical YAML.
+#[derive(Clone, Copy, Default)]
+pub(crate) struct Rendering<'a> {
+    /// If given, the lines containing values (or the keys of lists) end with a comment naming the
+    /// files of the value.
+    pub(crate) annotations: Option<&'a Annotations>,
+    /// Patterns of the paths of values that are redacted (see redaction::secret_patterns).
+    pub(crate) secrets: &'a [String],
+    /// If given, floats are rounded to this number of digits after the decimal point.
+    pub(crate) float_precision: Option<u32>,
+}
+
+/// Serializes a document (with header) into canonical YAML (see ``YamlConfigDocument.normalize``).
+pub(crate) fn to_canonical_yaml(py: Python, document: &PyYamlConfigDocument) -> PyResult<String> {
+    render_yaml(py, document, Rendering::default())
+}
+
+/// Serializes a document (with header) into canonical YAML, annotated and redacted as given.
+pub(crate) fn render_yaml(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    rendering: Rendering,
+) -> PyResult<String> {
+    let header: String = document.getattr(py, "header")?.call0(py)?.extract(py)?;
+    let lines = match canonical(py, &Ycd(document.clone_ref(py)), rendering, "")? {
+        Canonical::Inline(s) => vec![format!("{}: {}", canonical_key(&header), s)],
+        Canonical::Block(block) => block_lines(&canonical_key(&header), block, None),
+    };
+    Ok(lines.join("\n") + "\n")
+}
+
+/// A value in canonical YAML, without annotations.
+pub(crate) fn canonical_value(py: Python, value: &YcdValueType) -> PyResult<Canonical> {
+    canonical(py, value, Rendering::default(), "")
+}
+
+/// The lines of a dict entry (``key: value``) in canonical YAML, without annotations.
+pub(crate) fn canonical_entry(
+    py: Python,
+    key: &str,
+    value: &YcdValueType,
+) -> PyResult<Vec<String>> {
+    Ok(match canonical_value(py, value)? {
+        Canonical::Inline(s) => vec![format!("{}: {}", canonical_key(key), s)],
+        Canonical::Block(block) => block_lines(&canonical_key(key), block, None),
+    })
+}
+
+/// A value in YAML flow style (in a single line, dicts as ``{key: value}`` and lists as ``[value]``).
+/// Scalars are written like in canonical YAML.
+pub(crate) fn flow_value(py: Python, value: &YcdValueType) -> PyResult<String> {
+    Ok(match value {
+        Ycd(doc) => flow_value(py, &Dict(document_body(py, doc)?))?,
+        Dict(dict) => {
+            let entries = dict
+                .iter()
+                .map(|(k, v)| Ok(format!("{}: {}", canonical_key(k), flow_value(py, v)?)))
+                .collect::<PyResult<Vec<String>>>()?;
+            format!("{{{}}}", entries.join(", "))
+        }
+        List(list) => {
+            let entries = list
+                .iter()
+                .map(|v| flow_value(py, v))
+                .collect::<PyResult<Vec<String>>>()?;
+            format!("[{}]", entries.join(", "))
+        }
+        scalar => match canonical_value(py, scalar)? {
+            Canonical::Inline(s) => s,
+            Canonical::Block(_) => unreachable!("only dicts and lists are written as blocks"),
+        },
+    })
+}
+
+fn canonical(
+    py: Python,
+    value: &YcdValueType,
+    rendering: Rendering,
+    path: &str,
+) -> PyResult<Canonical> {
+    if !path.is_empty() && is_secret(path, rendering.secrets) {
+        return Ok(Canonical::Inline(quote_yaml_string(REDACTED)));
+    }
+    Ok(match value {
+        Ycd(doc) => {
+            let order: Vec<String> = doc.getattr(py, "key_order")?.call0(py)?.extract(py)?;
+            canonical_dict(py, &document_body(py, doc)?, &order, rendering, path)?
+        }
+        Dict(dict) => canonical_dict(py, dict, &[], rendering, path)?,
+        List(list) if list.is_empty() => Canonical::Inline("[]".to_string()),
+        List(list) => {
+            // Lists are annotated as a whole.
+            let entries = Rendering {
+                annotations: None,
+                ..rendering
+            };
+            let mut lines = vec![];
+            for (i, value) in list.iter().enumerate() {
+                match canonical(py, value, entries, &join_path(path, &i.to_string()))? {
+                    Canonical::Inline(s) => lines.push(format!("- {}", s)),
+                    Canonical::Block(block) => {
+                        for (i, line) in block.into_iter().enumerate() {
+                            let prefix = if i == 0 { "- " } else { "  " };
+                            lines.push(format!("{}{}", prefix, line));
+                        }
+                    }
+                }
+            }
+            Canonical::Block(lines)
+        }
+        YString(s) | ForcedString(s) => Canonical::Inline(quote_yaml_string(unescaped(s))),
+        Bool(b) => Canonical::Inline(b.to_string()),
+        Int(i) => Canonical::Inline(i.to_string()),
+        Float(f) => Canonical::Inline(canonical_float(round_float(*f, rendering.float_precision))),
+        Decimal(d) => Canonical::Inline(match rendering.float_precision {
+            None if is_plain_decimal(d) => d.clone(),
+            precision => canonical_float(round_float(decimal_to_f64(d), precision)),
+        }),
+    })
+}
+
+/// Keys in order come first (in that order), all other keys are sorted.
+fn canonical_dict(
+    py: Python,
+    dict: &YcdDict,
+    order: &[String],
+    rendering: Rendering,
+    path: &str,
+) -> PyResult<Canonical> {
+    if dict.is_empty() {
+        return Ok(Canonical::Inline("{}".to_string()));
+    }
+    let mut keys: Vec<&String> = dict.keys().collect();
+    keys.sort_by_key(|k| {
+        let position = order.iter().position(|o| o == *k);
+        (position.unwrap_or(order.len()), k.as_str())
+    });
+    let mut lines = vec![];
+    for key in keys {
+        let key_str = canonical_key(key);
+        let key_path = join_path(path, key);
+        let comment = rendering.annotations.and_then(|a| annotation(a, &key_path));
+        match canonical(py, &dict[key], rendering, &key_path)? {
+            Canonical::Inline(s) => lines.push(match comment {
+                None => format!("{}: {}", key_str, s),
+                Some(c) => format!("{}: {}  {}", key_str, s, c),
+            }),
+            Canonical::Block(block) => lines.extend(block_lines(&key_str, block, comment)),
+        }
+    }
+    Ok(Canonical::Block(lines))
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// The lines of a block below its key.
+fn block_lines(key: &str, block: Vec<String>, comment: Option<String>) -> Vec<String> {
+    let first = match comment {
+        None => format!("{}:", key),
+        Some(c) => format!("{}:  {}", key, c),
+    };
+    std::iter::once(first)
+        .chain(block.into_iter().map(|line| format!("  {}", line)))
+        .collect()
+}
+
+/// The comment for the value at path. Only values (and lists) have annotations, dicts don't.
+fn annotation(annotations: &Annotations, path: &str) -> Option<String> {
+    match annotations.get(path) {
+        Some(files) if !files.is_empty() => Some(format!("# {}", files.join(", "))),
+        _ => None,
+    }
+}
+
+/// The body of the document. For frozen documents, this is the frozen data.
+pub(crate) fn document_body(py: Python, document: &PyYamlConfigDocument) -> PyResult<YcdDict> {
+    let frozen = match &document.borrow(py).frozen {
+        None => return Ok(document.borrow(py).doc.clone()),
+        Some(frozen) => frozen.clone_ref(py),
+    };
+    frozen.extract(py)
+}
+
+/// Keys are only quoted if they could be read as something else than a string.
+pub(crate) fn canonical_key(key: &str) -> String {
+    let key = unescaped(key);
+    let plain = key
+        .chars()
+        .next()
+        .map_or(false, |c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_$./-".contains(c))
+        && !["y", "n", "yes", "no", "on", "off", "true", "false", "null"]
+            .contains(&key.to_lowercase().as_str());
+    if plain {
+        key.to_string()
+    } else {
+        quote_yaml_string(key)
+    }
+}
+
+fn quote_yaml_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Rounds value to precision digits after the decimal point (if given). The result is written
+/// without trailing digits by canonical_float and Python (e.g. ``0.1``).
+pub(crate) fn round_float(value: f64, precision: Option<u32>) -> f64 {
+    let factor = match precision {
+        Some(p) if p < 16 => 10f64.powi(p as i32),
+        _ => return value,
+    };
+    let rounded = (value * factor).round() / factor;
+    if rounded.is_finite() {
+        rounded
+    } else {
+        value
+    }
+}
+
+/// Replaces decimals with floats (rounded like round_float, if a precision is given) and rounds
+/// all other floats.
+pub(crate) fn round_floats(value: YcdValueType, precision: Option<u32>) -> YcdValueType {
+    match value {
+        Dict(d) => Dict(
+            d.into_iter()
+                .map(|(k, v)| (k, round_floats(v, precision)))
+                .collect(),
+        ),
+        List(l) => List(l.into_iter().map(|v| round_floats(v, precision)).collect()),
+        Float(f) => Float(round_float(f, precision)),
+        Decimal(d) => Float(round_float(decimal_to_f64(&d), precision)),
+        v => v,
+    }
+}
+
+/// Whether the decimal contains a "." and no exponent, so that it is read as the same float by
+/// YAML parsers.
+fn is_plain_decimal(value: &str) -> bool {
+    let unsigned = value.strip_prefix('-').unwrap_or(value);
+    unsigned.contains('.') && unsigned.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// Floats always contain a "." and a signed exponent (if any), so that they are read as floats
+/// by YAML 1.1 and 1.2 parsers (see format_float).
+fn canonical_float(value: f64) -> String {
+    if value.is_nan() {
+        return ".nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { ".inf" } else { "-.inf" }.to_string();
+    }
+    format_float(value)
+}
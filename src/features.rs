@@ -0,0 +1,200 @@
+//! Per-file feature restrictions. A file can declare ``$features`` next to the header, to disable
+//! engine features for the documents in that file. The caller can impose features on all loaded
+//! files with the ``features`` loading option, the ``$features`` of a file only add to them.
+use crate::conv::YcdValueType::{
+    Bool, Decimal, Dict, Float, ForcedString, Int, List, YString, Ycd,
+};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::{
+    InvalidDocumentError, EXTENDS, FEATURES, IMPORT_ALL, REF, REF_FIRST, REMOVE,
+    REMOVE_FROM_LIST_PREFIX, REMOVE_IF,
+};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+
+/// Strings must not contain templates (``{{ ... }}``, ``{% ... %}`` or ``{# ... #}``).
+pub(crate) const NO_TEMPLATES: &str = "no-templates";
+/// $ref, $ref_first, $extends and $import_all must not be used.
+pub(crate) const NO_REFERENCES: &str = "no-references";
+/// $remove, ``$remove::`` list entries and $remove_if must not be used.
+pub(crate) const NO_REMOVE: &str = "no-remove";
+/// Values must have the same type as the values they override while merging.
+pub(crate) const STRICT_TYPES: &str = "strict-types";
+const ALL_FEATURES: [&str; 4] = [NO_TEMPLATES, NO_REFERENCES, NO_REMOVE, STRICT_TYPES];
+
+/// Reads the ``$features`` of a loaded file (with header).
+pub(crate) fn file_features(path: &str, dict: &YcdDict) -> PyResult<Vec<String>> {
+    let features = match dict.get(FEATURES) {
+        None => return Ok(vec![]),
+        Some(List(features)) => features,
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Invalid {} in {}: {}. Expected a list of features.",
+                FEATURES, path, v
+            )))
+        }
+    };
+    features
+        .iter()
+        .map(|f| match f {
+            YString(f) if ALL_FEATURES.contains(&f.as_str()) => Ok(f.clone()),
+            v => Err(InvalidDocumentError::new_err(format!(
+                "Invalid feature in {}: {}. Supported are: {}",
+                path,
+                v,
+                ALL_FEATURES.join(", ")
+            ))),
+        })
+        .collect()
+}
+
+/// Validates the features passed with the ``features`` loading option.
+pub(crate) fn imposed_features(features: Vec<String>) -> PyResult<Vec<String>> {
+    match features
+        .iter()
+        .find(|f| !ALL_FEATURES.contains(&f.as_str()))
+    {
+        Some(f) => Err(exceptions::PyValueError::new_err(format!(
+            "Invalid value for features: {}. Supported are: {}",
+            f,
+            ALL_FEATURES.join(", ")
+        ))),
+        None => Ok(features),
+    }
+}
+
+/// Adds the imposed features (see ``imposed_features``) to the ``$features`` of a loaded file (with
+/// header), so the documents loaded from it have them as well. Returns all features of the file.
+pub(crate) fn impose_features(
+    path: &str,
+    dict: &mut YcdDict,
+    imposed: &[String],
+) -> PyResult<Vec<String>> {
+    let mut features = file_features(path, dict)?;
+    if imposed.is_empty() {
+        return Ok(features);
+    }
+    for feature in imposed {
+        if !has(&features, feature) {
+            features.push(feature.clone());
+        }
+    }
+    dict.insert(
+        FEATURES.to_string(),
+        List(features.iter().map(|f| YString(f.clone())).collect()),
+    );
+    Ok(features)
+}
+
+/// Checks that the document bodies of a loaded file don't use any of the disabled features,
+/// that can be detected without merging.
+pub(crate) fn check_file_features(path: &str, dict: &YcdDict, features: &[String]) -> PyResult<()> {
+    if features.is_empty() {
+        return Ok(());
+    }
+    for (key, body) in dict {
+        if key != FEATURES {
+            check_value(path, body, features)?;
+        }
+    }
+    Ok(())
+}
+
+fn check_value(path: &str, value: &YcdValueType, features: &[String]) -> PyResult<()> {
+    match value {
+        Dict(d) => {
+            for (k, v) in d {
+                check_string(path, k, features)?;
+                if has(features, NO_REFERENCES)
+                    && [REF, REF_FIRST, EXTENDS, IMPORT_ALL].contains(&k.as_str())
+                {
+                    return Err(violation(path, NO_REFERENCES, k));
+                }
+                if has(features, NO_REMOVE) && k == REMOVE_IF {
+                    return Err(violation(path, NO_REMOVE, k));
+                }
+                check_value(path, v, features)?;
+            }
+        }
+        List(l) => {
+            for v in l {
+                check_value(path, v, features)?;
+            }
+        }
+        YString(s) => check_string(path, s, features)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn check_string(path: &str, s: &str, features: &[String]) -> PyResult<()> {
+    if has(features, NO_TEMPLATES) && ["{{", "{%", "{#"].iter().any(|t| s.contains(t)) {
+        return Err(violation(path, NO_TEMPLATES, s));
+    }
+    if has(features, NO_REMOVE) && (s == REMOVE || s.starts_with(REMOVE_FROM_LIST_PREFIX)) {
+        return Err(violation(path, NO_REMOVE, s));
+    }
+    Ok(())
+}
+
+/// Checks that the values of overriding have the same types as the values in base they override
+/// (for documents loaded from files with the ``strict-types`` feature).
+/// Dicts and sub-documents are considered the same type, $remove may replace any value.
+pub(crate) fn check_strict_types(
+    path: &str,
+    base: &YcdDict,
+    overriding: &YcdDict,
+    key_path: &str,
+) -> PyResult<()> {
+    for (key, value) in overriding {
+        let base_value = match base.get(key) {
+            None => continue,
+            Some(v) => v,
+        };
+        let is_remove = |v: &YcdValueType| matches!(v, YString(s) if s == REMOVE);
+        if is_remove(value) || is_remove(base_value) || key.starts_with('$') {
+            continue;
+        }
+        let full_key = if key_path.is_empty() {
+            key.clone()
+        } else {
+            format!("{}.{}", key_path, key)
+        };
+        if type_name(base_value) != type_name(value) {
+            return Err(InvalidDocumentError::new_err(format!(
+                "{} declares the feature {}, but changes the type of {} from {} to {}.",
+                path,
+                STRICT_TYPES,
+                full_key,
+                type_name(base_value),
+                type_name(value)
+            )));
+        }
+        if let (Dict(b), Dict(o)) = (base_value, value) {
+            check_strict_types(path, b, o, &full_key)?;
+        }
+    }
+    Ok(())
+}
+
+fn type_name(value: &YcdValueType) -> &'static str {
+    match value {
+        Ycd(_) | Dict(_) => "mapping",
+        List(_) => "list",
+        YString(_) | ForcedString(_) => "string",
+        Bool(_) => "bool",
+        Int(_) => "int",
+        Float(_) | Decimal(_) => "float",
+    }
+}
+
+fn has(features: &[String], feature: &str) -> bool {
+    features.iter().any(|f| f == feature)
+}
+
+fn violation(path: &str, feature: &str, found: &str) -> PyErr {
+    InvalidDocumentError::new_err(format!(
+        "{} declares the feature {}, but contains: {}",
+        path, feature, found
+    ))
+}
@@ -0,0 +1,172 @@
+use crate::conv::YcdValueType::{Bool, Dict, Float, Int, YString};
+use crate::conv::{SimpleYcdValueType, YHashMap, YcdDict, YcdValueType};
+use crate::options::LoadOptions;
+use crate::InvalidDocumentError;
+use indexmap::IndexMap;
+use ini::Ini;
+use pyo3::prelude::*;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// File extensions of INI / ConfigParser style files.
+const INI_EXTENSIONS: [&str; 2] = ["ini", "cfg"];
+
+/// File extensions of documents, in the order the files are merged if a document exists with
+/// multiple of them.
+pub(crate) const DOCUMENT_EXTENSIONS: [&str; 6] = ["yml", "yaml", "ini", "cfg", "json", "toml"];
+
+/// The format of a document file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum FileFormat {
+    Yaml,
+    Ini,
+    Json,
+    Toml,
+}
+
+impl FileFormat {
+    /// The format of the file at path, based on its extension. Unknown extensions are read as YAML.
+    pub(crate) fn of(path: &str) -> Self {
+        let ext = match Path::new(path).extension().and_then(|e| e.to_str()) {
+            None => return FileFormat::Yaml,
+            Some(ext) => ext.to_lowercase(),
+        };
+        match ext.as_str() {
+            "json" => FileFormat::Json,
+            "toml" => FileFormat::Toml,
+            ext if INI_EXTENSIONS.contains(&ext) => FileFormat::Ini,
+            _ => FileFormat::Yaml,
+        }
+    }
+
+    /// Name of the format in error messages.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            FileFormat::Yaml => "YAML",
+            FileFormat::Ini => "INI",
+            FileFormat::Json => "JSON",
+            FileFormat::Toml => "TOML",
+        }
+    }
+}
+
+/// Loads an INI / ConfigParser style file into a nested dict.
+///
+/// Each section becomes a dict, keys without a section are stored at the top level.
+/// Section names containing dots are treated as nested dicts, so ``[base.more]``
+/// ends up at ``{"base": {"more": {...}}}``. This way INI files can contain a document header
+/// just like YAML files. content is the content of the file.
+pub(crate) fn load_ini_file(
+    path_to_ini: &str,
+    content: &str,
+    options: &LoadOptions,
+) -> PyResult<YcdDict> {
+    let ini = match Ini::load_from_str(content) {
+        Ok(v) => v,
+        Err(e) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Unable to read INI file {}: {:?}",
+                path_to_ini, e
+            )))
+        }
+    };
+    let mut out = YcdDict::new();
+    for (section, properties) in ini.iter() {
+        let target = match section {
+            None => &mut out,
+            Some(section) => ini_section(&mut out, section, path_to_ini)?,
+        };
+        for (key, value) in properties.iter() {
+            let value = if options.ini_infer_types {
+                infer_ini_value(value, options.core_schema)
+            } else {
+                YString(value.to_string())
+            };
+            target.insert(key.to_string(), value);
+        }
+    }
+    Ok(out)
+}
+
+/// Returns the (possibly nested) dict for a section name, creating it if needed.
+fn ini_section<'a>(
+    mut dict: &'a mut YcdDict,
+    section: &str,
+    path_to_ini: &str,
+) -> PyResult<&'a mut YcdDict> {
+    for part in section.split('.') {
+        let current = dict;
+        let entry = current
+            .entry(part.to_string())
+            .or_insert_with(|| Dict(YcdDict::new()));
+        match entry {
+            Dict(d) => dict = d,
+            _ => {
+                return Err(InvalidDocumentError::new_err(format!(
+                    "Unable to read INI file {}: Section {} conflicts with an existing key.",
+                    path_to_ini, section
+                )))
+            }
+        }
+    }
+    Ok(dict)
+}
+
+/// Converts an INI value into a bool, int or float, if it looks like one.
+/// With core_schema, only ``true`` and ``false`` are booleans (not ``yes``, ``no``, ``on`` and ``off``).
+fn infer_ini_value(value: &str, core_schema: bool) -> YcdValueType {
+    match (value.to_lowercase().as_str(), core_schema) {
+        ("true", _) | ("yes", false) | ("on", false) => return Bool(true),
+        ("false", _) | ("no", false) | ("off", false) => return Bool(false),
+        _ => {}
+    }
+    if let Ok(v) = value.parse::<i64>() {
+        Int(v)
+    } else if !value.chars().any(|c| c.is_ascii_digit()) {
+        // Don't convert things like "inf" or "nan"
+        YString(value.to_string())
+    } else if let Ok(v) = value.parse::<f64>() {
+        Float(v)
+    } else {
+        YString(value.to_string())
+    }
+}
+
+/// Loads a JSON file into a dict. The top level must be an object, like the top level of YAML files
+/// must be a dict (containing the document header).
+pub(crate) fn load_json_file(path_to_json: &str, content: &str) -> PyResult<YcdDict> {
+    match serde_json::from_str::<IndexMap<String, SimpleYcdValueType>>(content) {
+        Ok(v) => Ok(YHashMap(v).into()),
+        Err(e) => Err(InvalidDocumentError::new_err(format!(
+            "Unable to read JSON file {}: {}",
+            path_to_json, e
+        ))),
+    }
+}
+
+/// Loads a TOML file into a dict. Tables become dicts, so the header is the name of the top
+/// level table (``[base]``). Dates and times are not supported.
+pub(crate) fn load_toml_file(path_to_toml: &str, content: &str) -> PyResult<YcdDict> {
+    match toml::from_str::<IndexMap<String, SimpleYcdValueType>>(content) {
+        Ok(v) => Ok(YHashMap(v).into()),
+        Err(e) => Err(InvalidDocumentError::new_err(format!(
+            "Unable to read TOML file {}: {}",
+            path_to_toml, e
+        ))),
+    }
+}
+
+/// The content of the (virtual) file at path.
+pub(crate) fn file_content(path: &str, options: &LoadOptions) -> PyResult<String> {
+    match options.virtual_files.get(path) {
+        Some(content) => Ok(content.to_string()),
+        None => read_to_string(path).map_err(|e| {
+            InvalidDocumentError::new_err(format!(
+                "Unable to open {} file {}: {:?}",
+                FileFormat::of(path).name(),
+                path,
+                e
+            ))
+        }),
+    }
+}
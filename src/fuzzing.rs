@@ -0,0 +1,99 @@
+//! Deterministic entry points into the merger, for fuzzing and property-based tests
+//! (see ``fuzz/``). They work on plain values without documents, do not read any files and
+//! do not emit warnings. This is not a stable API.
+//!
+//! The entry points need an initialized Python interpreter (errors are Python exceptions
+//! internally). It is initialized on first use, if it isn't already.
+use crate::conv::{decimal_to_f64, YcdDict, YcdValueType};
+use crate::merger::{delete_remove_markers, merge_dicts};
+use crate::options::LoadOptions;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+
+/// A plain value, like in the body of a document.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Dict(BTreeMap<String, Value>),
+    List(Vec<Value>),
+    String(String),
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+}
+
+pub type Dict = BTreeMap<String, Value>;
+
+/// Merges overriding over base, like the bodies of two documents. $remove markers are kept,
+/// as when merging several documents in a row.
+///
+/// Returns the message of the raised exception, if merging fails (e.g. because of $lock).
+pub fn merge(base: &Dict, overriding: &Dict) -> Result<Dict, String> {
+    with_gil(|py| {
+        merge_dicts(
+            py,
+            to_ycd_dict(base),
+            to_ycd_dict(overriding),
+            &LoadOptions::default(),
+        )
+    })
+    .map(|d| from_ycd_dict(&d))
+}
+
+/// Removes all $remove markers (and $lock and $deprecated), like after the last merge.
+pub fn remove_markers(doc: &Dict) -> Result<Dict, String> {
+    with_gil(|py| delete_remove_markers(py, YcdValueType::Dict(to_ycd_dict(doc))))
+        .map(|v| from_ycd_dict(&v.unwrap_dict()))
+}
+
+/// Merges all layers in order (later ones take priority) and removes the markers afterwards.
+pub fn merge_all(layers: &[Dict]) -> Result<Dict, String> {
+    let mut result = Dict::new();
+    for layer in layers {
+        result = merge(&result, layer)?;
+    }
+    remove_markers(&result)
+}
+
+fn with_gil<T, F>(f: F) -> Result<T, String>
+where
+    F: FnOnce(Python) -> PyResult<T>,
+{
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| f(py).map_err(|e| e.pvalue(py).to_string()))
+}
+
+fn to_ycd_dict(dict: &Dict) -> YcdDict {
+    dict.iter()
+        .map(|(k, v)| (k.clone(), to_ycd_value(v)))
+        .collect()
+}
+
+fn to_ycd_value(value: &Value) -> YcdValueType {
+    match value {
+        Value::Dict(v) => YcdValueType::Dict(to_ycd_dict(v)),
+        Value::List(v) => YcdValueType::List(v.iter().map(to_ycd_value).collect()),
+        Value::String(v) => YcdValueType::YString(v.clone()),
+        Value::Bool(v) => YcdValueType::Bool(*v),
+        Value::Int(v) => YcdValueType::Int(*v),
+        Value::Float(v) => YcdValueType::Float(*v),
+    }
+}
+
+fn from_ycd_dict(dict: &YcdDict) -> Dict {
+    dict.iter()
+        .map(|(k, v)| (k.clone(), from_ycd_value(v)))
+        .collect()
+}
+
+fn from_ycd_value(value: &YcdValueType) -> Value {
+    match value {
+        YcdValueType::Dict(v) => Value::Dict(from_ycd_dict(v)),
+        YcdValueType::List(v) => Value::List(v.iter().map(from_ycd_value).collect()),
+        YcdValueType::YString(v) | YcdValueType::ForcedString(v) => Value::String(v.clone()),
+        YcdValueType::Bool(v) => Value::Bool(*v),
+        YcdValueType::Int(v) => Value::Int(*v),
+        YcdValueType::Float(v) => Value::Float(*v),
+        YcdValueType::Decimal(v) => Value::Float(decimal_to_f64(v)),
+        YcdValueType::Ycd(_) => unreachable!("Plain values never contain documents."),
+    }
+}
@@ -0,0 +1,355 @@
+use crate::conv::YcdValueType::{Dict, List, Ycd};
+use crate::conv::{PyYamlConfigDocument, YcdValueType};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use pyo3::AsPyPointer;
+use std::collections::HashMap;
+
+/// A $ref that was resolved while merging a document.
+#[derive(Clone, Debug)]
+pub(crate) struct ResolvedReference {
+    /// Absolute path of the document that contained the $ref, if it was loaded from a file.
+    pub(crate) from: Option<String>,
+    /// The referenced path, relative to the lookup paths.
+    pub(crate) reference: String,
+    /// Absolute path of the file that was loaded for the reference.
+    pub(crate) file: String,
+    /// The lookup path the file was found in.
+    pub(crate) lookup_path: String,
+}
+
+/// A file that was merged into a document.
+#[derive(Clone, Debug)]
+pub(crate) struct MergeSource {
+    pub(crate) path: String,
+    pub(crate) role: MergeRole,
+    /// Whether the file must not be written to: A virtual file, a registered document or a file
+    /// of a remote, git or package lookup path (which are only cached copies).
+    pub(crate) read_only: bool,
+}
+
+/// How a file was merged into a document.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum MergeRole {
+    /// The file the document was loaded from (the first file passed to ``load_multiple_yml``).
+    Base,
+    /// A file merged over the base file (by ``load_multiple_yml`` or ``overlay``).
+    Override,
+    /// A file loaded for a $ref, $ref_first, $extends or $import_all.
+    Referenced,
+}
+
+impl MergeRole {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            MergeRole::Base => "base",
+            MergeRole::Override => "override",
+            MergeRole::Referenced => "referenced",
+        }
+    }
+}
+
+/// Changes the role of the base files of a document that is merged over another document to override.
+pub(crate) fn mark_as_override(sources: &mut [MergeSource]) {
+    for source in sources.iter_mut() {
+        if source.role == MergeRole::Base {
+            source.role = MergeRole::Override;
+        }
+    }
+}
+
+enum GraphNodeKind {
+    Document {
+        type_name: String,
+        file: Option<String>,
+        document: PyYamlConfigDocument,
+    },
+    File {
+        file: String,
+    },
+}
+
+struct GraphNode {
+    id: String,
+    label: String,
+    kind: GraphNodeKind,
+}
+
+enum GraphEdgeKind {
+    Ref {
+        reference: String,
+        lookup_path: String,
+    },
+    Subdocument {
+        path: String,
+    },
+}
+
+struct GraphEdge {
+    from: String,
+    to: String,
+    label: String,
+    kind: GraphEdgeKind,
+}
+
+/// The graph of $ref references and sub-documents of a document hierarchy.
+/// Nodes are documents and the files that were loaded for $refs.
+#[derive(Default)]
+pub(crate) struct ReferenceGraph {
+    nodes: Vec<GraphNode>,
+    edges: Vec<GraphEdge>,
+    ids: HashMap<String, String>,
+}
+
+impl ReferenceGraph {
+    pub(crate) fn build(py: Python, root: &PyYamlConfigDocument) -> PyResult<Self> {
+        let mut slf = Self::default();
+        slf.add_document(py, root)?;
+        Ok(slf)
+    }
+
+    /// The graph of several root documents. Files and documents reachable from more than one root
+    /// are single nodes.
+    pub(crate) fn build_all(py: Python, roots: &[PyYamlConfigDocument]) -> PyResult<Self> {
+        let mut slf = Self::default();
+        for root in roots {
+            slf.add_document(py, root)?;
+        }
+        Ok(slf)
+    }
+
+    /// Renders the graph in the given format ("dot" or "mermaid").
+    pub(crate) fn render(&self, format: &str) -> PyResult<String> {
+        match format {
+            "dot" => Ok(self.to_dot()),
+            "mermaid" => Ok(self.to_mermaid()),
+            _ => Err(exceptions::PyValueError::new_err(format!(
+                "Unknown graph format: {}. Supported are 'dot' and 'mermaid'.",
+                format
+            ))),
+        }
+    }
+
+    /// Converts the graph into Python data: A dict with the lists ``nodes`` and ``edges`` (see
+    /// ``YamlConfigDocument.reference_graph``).
+    pub(crate) fn to_python(&self, py: Python) -> PyResult<PyObject> {
+        let nodes = PyList::empty(py);
+        for node in &self.nodes {
+            let dict = PyDict::new(py);
+            dict.set_item("id", &node.id)?;
+            match &node.kind {
+                GraphNodeKind::Document {
+                    type_name,
+                    file,
+                    document,
+                } => {
+                    dict.set_item("kind", "document")?;
+                    dict.set_item("type", type_name)?;
+                    dict.set_item("file", file)?;
+                    dict.set_item("document", document.to_object(py))?;
+                }
+                GraphNodeKind::File { file } => {
+                    dict.set_item("kind", "file")?;
+                    dict.set_item("file", file)?;
+                }
+            }
+            nodes.append(dict)?;
+        }
+        let edges = PyList::empty(py);
+        for edge in &self.edges {
+            let dict = PyDict::new(py);
+            dict.set_item("from", &edge.from)?;
+            dict.set_item("to", &edge.to)?;
+            match &edge.kind {
+                GraphEdgeKind::Ref {
+                    reference,
+                    lookup_path,
+                } => {
+                    dict.set_item("kind", "ref")?;
+                    dict.set_item("reference", reference)?;
+                    dict.set_item("lookup_path", lookup_path)?;
+                }
+                GraphEdgeKind::Subdocument { path } => {
+                    dict.set_item("kind", "subdocument")?;
+                    dict.set_item("path", path)?;
+                }
+            }
+            edges.append(dict)?;
+        }
+        let graph = PyDict::new(py);
+        graph.set_item("nodes", nodes)?;
+        graph.set_item("edges", edges)?;
+        Ok(graph.into())
+    }
+
+    fn add_document(&mut self, py: Python, document: &PyYamlConfigDocument) -> PyResult<String> {
+        let key = format!("doc:{:?}", document.0.as_ptr());
+        if let Some(id) = self.ids.get(&key) {
+            return Ok(id.clone());
+        }
+        let class_name: String = document
+            .getattr(py, "__class__")?
+            .getattr(py, "__name__")?
+            .extract(py)?;
+        let doc = document.borrow(py);
+        let own_file = doc.absolute_paths.first().cloned();
+        let label = match &own_file {
+            None => class_name.clone(),
+            Some(f) => format!("{}\n{}", class_name, f),
+        };
+        let id = self.add_node(
+            key,
+            label,
+            GraphNodeKind::Document {
+                type_name: class_name,
+                file: own_file.clone(),
+                document: document.clone_ref(py),
+            },
+        );
+        for reference in &doc.references {
+            let from = match &reference.from {
+                Some(f) if Some(f) != own_file.as_ref() => self.add_file(f),
+                _ => id.clone(),
+            };
+            let to = self.add_file(&reference.file);
+            self.edges.push(GraphEdge {
+                from,
+                to,
+                label: format!(
+                    "$ref {} (lookup path: {})",
+                    reference.reference, reference.lookup_path
+                ),
+                kind: GraphEdgeKind::Ref {
+                    reference: reference.reference.clone(),
+                    lookup_path: reference.lookup_path.clone(),
+                },
+            });
+        }
+        let mut subdocs = vec![];
+        for (key, value) in &doc.doc {
+            collect_subdocuments(py, value, key, &mut subdocs);
+        }
+        drop(doc);
+        for (path, subdoc) in subdocs {
+            let to = self.add_document(py, &subdoc)?;
+            self.edges.push(GraphEdge {
+                from: id.clone(),
+                to,
+                label: path.clone(),
+                kind: GraphEdgeKind::Subdocument { path },
+            });
+        }
+        Ok(id)
+    }
+
+    fn add_file(&mut self, file: &str) -> String {
+        self.add_node(
+            format!("file:{}", file),
+            file.to_string(),
+            GraphNodeKind::File {
+                file: file.to_string(),
+            },
+        )
+    }
+
+    fn add_node(&mut self, key: String, label: String, kind: GraphNodeKind) -> String {
+        if let Some(id) = self.ids.get(&key) {
+            return id.clone();
+        }
+        let id = format!("n{}", self.nodes.len());
+        self.ids.insert(key, id.clone());
+        self.nodes.push(GraphNode {
+            id: id.clone(),
+            label,
+            kind,
+        });
+        id
+    }
+
+    fn to_dot(&self) -> String {
+        let escape = |s: &str| {
+            s.replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n")
+        };
+        let mut out = String::from("digraph references {\n");
+        for node in &self.nodes {
+            let shape = match node.kind {
+                GraphNodeKind::Document { .. } => "box",
+                GraphNodeKind::File { .. } => "note",
+            };
+            out.push_str(&format!(
+                "    {} [label=\"{}\", shape={}];\n",
+                node.id,
+                escape(&node.label),
+                shape
+            ));
+        }
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    {} -> {} [label=\"{}\"];\n",
+                edge.from,
+                edge.to,
+                escape(&edge.label)
+            ));
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn to_mermaid(&self) -> String {
+        let escape = |s: &str| s.replace('"', "#quot;").replace('\n', "<br/>");
+        let mut out = String::from("graph TD\n");
+        for node in &self.nodes {
+            match node.kind {
+                GraphNodeKind::Document { .. } => {
+                    out.push_str(&format!("    {}[\"{}\"]\n", node.id, escape(&node.label)))
+                }
+                GraphNodeKind::File { .. } => {
+                    out.push_str(&format!("    {}[(\"{}\")]\n", node.id, escape(&node.label)))
+                }
+            }
+        }
+        for edge in &self.edges {
+            out.push_str(&format!(
+                "    {} -->|\"{}\"| {}\n",
+                edge.from,
+                escape(&edge.label),
+                edge.to
+            ));
+        }
+        out
+    }
+}
+
+/// Collects all direct sub-documents in value, with their paths (parts separated by "/").
+/// Keys are visited in document order.
+pub(crate) fn collect_subdocuments(
+    py: Python,
+    value: &YcdValueType,
+    path: &str,
+    out: &mut Vec<(String, PyYamlConfigDocument)>,
+) {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", path, key)
+        }
+    };
+    match value {
+        Ycd(v) => out.push((path.to_string(), v.clone_ref(py))),
+        Dict(v) => {
+            for (k, vv) in v {
+                collect_subdocuments(py, vv, &join(k), out);
+            }
+        }
+        List(v) => {
+            for (i, vv) in v.iter().enumerate() {
+                collect_subdocuments(py, vv, &join(&i.to_string()), out);
+            }
+        }
+        _ => {}
+    }
+}
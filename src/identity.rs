@@ -0,0 +1,60 @@
+//! The identity map of sub-documents (the ``identity_map`` loading option): Sub-documents, that
+//! only reference a file, are loaded once per resolution and the document is shared.
+use crate::conv::PyYamlConfigDocument;
+use crate::conv::YcdDict;
+use crate::conv::YcdValueType::YString;
+use crate::loader::path_in_repo;
+use crate::minijinja::is_template;
+use crate::REF;
+use pyo3::prelude::*;
+use pyo3::types::PyType;
+use pyo3::AsPyPointer;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// The document type (address of the class), the referenced path in the repositories and the key
+/// of documents in dicts (which is stored as $name in the document).
+type IdentityKey = (usize, String, Option<String>);
+
+/// The sub-documents loaded so far, if enabled. Clones share the map.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct IdentityMap(Option<Arc<Mutex<HashMap<IdentityKey, PyYamlConfigDocument>>>>);
+
+impl IdentityMap {
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self(enabled.then(Default::default))
+    }
+
+    /// The key of a sub-document of type doc_clss with the body doc, in a document at base_path
+    /// (and at the key name, if in a dict). None if disabled or if the body contains more than a
+    /// $ref to a single path, that isn't a template (and could therefore depend on the document
+    /// it's in).
+    pub(crate) fn key(
+        &self,
+        doc_clss: &PyType,
+        base_path: &Option<String>,
+        name: Option<&str>,
+        doc: &YcdDict,
+    ) -> Option<IdentityKey> {
+        self.0.as_ref()?;
+        match doc.get(REF) {
+            Some(YString(path)) if doc.len() == 1 && !is_template(path) => Some((
+                doc_clss.as_ptr() as usize,
+                path_in_repo(base_path, path),
+                name.map(str::to_string),
+            )),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn get(&self, py: Python, key: &IdentityKey) -> Option<PyYamlConfigDocument> {
+        let map = self.0.as_ref()?.lock().unwrap();
+        map.get(key).map(|doc| doc.clone_ref(py))
+    }
+
+    pub(crate) fn insert(&self, key: IdentityKey, doc: PyYamlConfigDocument) {
+        if let Some(map) = &self.0 {
+            map.lock().unwrap().insert(key, doc);
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use std::env;
+
+/// Interpolates all Docker Compose style variables in input, using the environment variables of the process.
+/// See https://docs.docker.com/compose/environment-variables/#substitute-environment-variables-in-compose-files
+///
+/// Supported are:
+///
+/// - ``$VAR`` and ``${VAR}``: Value of VAR, empty if not set.
+/// - ``${VAR:-default}``: Value of VAR, default if VAR is not set or empty.
+/// - ``${VAR-default}``: Value of VAR, default if VAR is not set.
+/// - ``${VAR:?error}``: Value of VAR, error if VAR is not set or empty.
+/// - ``${VAR?error}``: Value of VAR, error if VAR is not set.
+/// - ``${VAR:+replacement}``: replacement if VAR is set and not empty, empty otherwise.
+/// - ``${VAR+replacement}``: replacement if VAR is set, empty otherwise.
+/// - ``$$``: A literal ``$``.
+///
+/// Returns an error message if a required variable is missing or the syntax is invalid.
+pub(crate) fn interpolate_compose(input: &str) -> Result<String, String> {
+    interpolate_compose_with(input, |name| env::var(name).ok())
+}
+
+/// Same as interpolate_compose, but the variables are looked up using the lookup function.
+pub(crate) fn interpolate_compose_with<F>(input: &str, lookup: F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    interpolate(input, lookup, false)
+}
+
+/// Same as interpolate_compose, but only ``${...}`` expressions are interpolated. Other ``$`` are kept
+/// as-is (so directives like ``$remove`` are not affected), ``$${`` is a literal ``${``.
+pub(crate) fn interpolate_braced(input: &str) -> Result<String, String> {
+    interpolate(input, |name| env::var(name).ok(), true)
+}
+
+fn interpolate<F>(input: &str, lookup: F, braced_only: bool) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    if !input.contains('$') {
+        return Ok(input.to_string());
+    }
+    let mut out = String::with_capacity(input.len());
+    let mut rest = input;
+    while let Some(pos) = rest.find('$') {
+        out.push_str(&rest[..pos]);
+        rest = &rest[pos + 1..];
+        if braced_only && !rest.starts_with('{') && !rest.starts_with("${") {
+            out.push('$');
+        } else if let Some(stripped) = rest.strip_prefix('$') {
+            // Escaped $
+            out.push('$');
+            rest = stripped;
+        } else if let Some(braced) = rest.strip_prefix('{') {
+            let end = match braced.find('}') {
+                Some(end) => end,
+                None => return Err(format!("Unterminated variable in {:?}", input)),
+            };
+            out.push_str(&substitute(&braced[..end], &lookup)?);
+            rest = &braced[end + 1..];
+        } else {
+            let len = identifier_len(rest);
+            if len == 0 {
+                // Not a variable, keep the $ as-is.
+                out.push('$');
+            } else {
+                out.push_str(&lookup(&rest[..len]).unwrap_or_default());
+                rest = &rest[len..];
+            }
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Length of the variable name at the start of s (0 if there is none).
+fn identifier_len(s: &str) -> usize {
+    let mut len = 0;
+    for (i, c) in s.char_indices() {
+        if c == '_' || c.is_ascii_alphabetic() || (i > 0 && c.is_ascii_digit()) {
+            len = i + c.len_utf8();
+        } else {
+            break;
+        }
+    }
+    len
+}
+
+/// Substitutes the content of a single ``${...}`` expression.
+fn substitute<F>(expr: &str, lookup: &F) -> Result<String, String>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let len = identifier_len(expr);
+    if len == 0 {
+        return Err(format!("Invalid variable name in ${{{}}}", expr));
+    }
+    let (name, modifier) = expr.split_at(len);
+    let value = lookup(name);
+    let set_and_not_empty = matches!(&value, Some(v) if !v.is_empty());
+    if modifier.is_empty() {
+        return Ok(value.unwrap_or_default());
+    }
+    if let Some(default) = modifier.strip_prefix(":-") {
+        Ok(if set_and_not_empty {
+            value.unwrap()
+        } else {
+            default.to_string()
+        })
+    } else if let Some(default) = modifier.strip_prefix('-') {
+        Ok(value.unwrap_or_else(|| default.to_string()))
+    } else if let Some(error) = modifier.strip_prefix(":?") {
+        if set_and_not_empty {
+            Ok(value.unwrap())
+        } else {
+            Err(format!(
+                "Required variable {} is missing a value: {}",
+                name, error
+            ))
+        }
+    } else if let Some(error) = modifier.strip_prefix('?') {
+        match value {
+            Some(v) => Ok(v),
+            None => Err(format!(
+                "Required variable {} is missing a value: {}",
+                name, error
+            )),
+        }
+    } else if let Some(replacement) = modifier.strip_prefix(":+") {
+        Ok(if set_and_not_empty {
+            replacement.to_string()
+        } else {
+            String::new()
+        })
+    } else if let Some(replacement) = modifier.strip_prefix('+') {
+        Ok(match value {
+            Some(_) => replacement.to_string(),
+            None => String::new(),
+        })
+    } else {
+        Err(format!("Invalid variable expression ${{{}}}", expr))
+    }
+}
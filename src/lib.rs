@@ -2,22 +2,113 @@ use pyo3::prelude::*;
 use pyo3::{wrap_pyfunction, PyResult, Python};
 
 pub(crate) const REF: &str = "$ref";
+pub(crate) const EXTENDS: &str = "$extends";
+pub(crate) const VARS: &str = "$vars";
+pub(crate) const IMPORT_ALL: &str = "$import_all";
+pub(crate) const SCHEMA: &str = "$schema";
+pub(crate) const DEPRECATED: &str = "$deprecated";
+pub(crate) const ANCHOR: &str = "$anchor";
+pub(crate) const ALIAS: &str = "$alias";
+pub(crate) const REF_FIRST: &str = "$ref_first";
+pub(crate) const COMMENT: &str = "$comment";
+pub(crate) const DEFAULT: &str = "$default";
+pub(crate) const LOCK: &str = "$lock";
+pub(crate) const REMOVE_IF: &str = "$remove_if";
 pub(crate) const REMOVE: &str = "$remove";
 pub(crate) const REMOVE_FROM_LIST_PREFIX: &str = "$remove::";
-pub(crate) const FORCE_STRING: &str = "__forcestring__";
+/// Prefix of the key of a list entry, whose values are inserted before an existing entry.
+pub(crate) const INSERT_BEFORE_PREFIX: &str = "$insert_before::";
+/// Prefix of the key of a list entry, whose values are inserted after an existing entry.
+pub(crate) const INSERT_AFTER_PREFIX: &str = "$insert_after::";
+/// Runs a command and merges its output (a YAML mapping) at its location. Must be enabled with
+/// ``DirectivePolicy(allow_exec=True)``.
+pub(crate) const EXEC: &str = "$exec";
+/// An encrypted value (``{$encrypted: <ciphertext>}``, or a string tagged with ``!encrypted``),
+/// decrypted with the key provider registered with ``register_key_provider``.
+pub(crate) const ENCRYPTED: &str = "$encrypted";
+/// Features disabled for a file, declared next to the header (not in the body).
+pub(crate) const FEATURES: &str = "$features";
+/// Prefix of the key of a list entry, whose value is merged over the existing entry with that index.
+pub(crate) const INDEX_PREFIX: &str = "$index::";
+/// Name of the merged values files in templates.
+pub(crate) const VALUES: &str = "values";
+/// Key of the per-profile overrides in document bodies (not a directive). Only reserved if a profile is
+/// selected (``profile`` loading option).
+pub(crate) const PROFILES: &str = "profiles";
+/// All directives that can be used as keys (and $remove).
+pub(crate) const DIRECTIVES: &[&str] = &[
+    REF, REF_FIRST, EXTENDS, IMPORT_ALL, VARS, SCHEMA, DEPRECATED, ANCHOR, ALIAS, COMMENT, DEFAULT,
+    LOCK, REMOVE_IF, REMOVE, FEATURES, EXEC, ENCRYPTED,
+];
 
+mod anchors;
+mod audit;
+mod cancellation;
+mod constraints;
+mod context;
 mod conv;
+mod diff;
+mod encryption;
+mod enforcement;
 pub(crate) mod errors;
+mod export;
+mod features;
+mod formats;
+pub mod fuzzing;
+mod graph;
+mod identity;
+mod interpolation;
+mod limits;
+mod lint;
 pub(crate) mod loader;
+mod lookup;
+mod manifest;
 pub(crate) mod merger;
 mod minijinja;
+mod nested;
+pub(crate) mod options;
+mod overrides;
+mod policy;
+mod progress;
+mod provenance;
+mod redaction;
+mod registry;
+mod schema;
+mod signing;
+mod stats;
+mod suggestions;
+mod tracing;
+mod validation;
 pub(crate) mod variables;
+mod view;
+mod virtual_files;
+pub mod walk;
+mod workspace;
+mod write_back;
+mod yaml;
 pub(crate) mod ycd;
+mod ycd_list;
 
+use crate::audit::TemplateAuditEntry;
+use crate::cancellation::CancellationToken;
+use crate::constraints::{Coerce, KeyedList, Length, OneOf, Pattern, Range, Secret, UniqueList};
+use crate::context::ResolutionContext;
+use crate::encryption::EncryptedValue;
 use crate::errors::*;
 use crate::loader::*;
+use crate::manifest::load_from_manifest;
 use crate::merger::*;
+use crate::overrides::parse_set_overrides;
+use crate::policy::DirectivePolicy;
+use crate::registry::*;
+use crate::schema::{Field, TypedSchema};
+use crate::stats::MergeStats;
+use crate::tracing::set_tracing_enabled;
+use crate::view::DocumentView;
+use crate::walk::WalkNode;
+use crate::workspace::Workspace;
 use crate::ycd::*;
+use crate::ycd_list::PyYcdList;
 
 #[pymodule]
 fn _main(py: Python<'_>, m: &PyModule) -> PyResult<()> {
@@ -40,12 +131,88 @@ fn _main(py: Python<'_>, m: &PyModule) -> PyResult<()> {
     )?;
     m.add("InvalidHeaderError", py.get_type::<InvalidHeaderError>())?;
     m.add("InvalidRemoveError", py.get_type::<InvalidRemoveError>())?;
+    m.add("LockViolationError", py.get_type::<LockViolationError>())?;
+    m.add("DocumentLimitError", py.get_type::<DocumentLimitError>())?;
+    m.add("DuplicateKeyError", py.get_type::<DuplicateKeyError>())?;
+    m.add("SignatureError", py.get_type::<SignatureError>())?;
+    m.add(
+        "PolicyViolationError",
+        py.get_type::<PolicyViolationError>(),
+    )?;
+    m.add(
+        "ResolutionCancelledError",
+        py.get_type::<ResolutionCancelledError>(),
+    )?;
+    m.add("EncryptionError", py.get_type::<EncryptionError>())?;
+    m.add("RuleViolationError", py.get_type::<RuleViolationError>())?;
+    m.add("UnknownKeyError", py.get_type::<UnknownKeyError>())?;
+    m.add(
+        "LayerValidationError",
+        py.get_type::<LayerValidationError>(),
+    )?;
+    m.add(
+        "MergedValidationError",
+        py.get_type::<MergedValidationError>(),
+    )?;
+    m.add(
+        "WorkspaceValidationError",
+        py.get_type::<WorkspaceValidationError>(),
+    )?;
+    m.add(
+        "DeprecatedKeyWarning",
+        py.get_type::<DeprecatedKeyWarning>(),
+    )?;
+    m.add("UnknownKeyWarning", py.get_type::<UnknownKeyWarning>())?;
+    m.add("DuplicateKeyWarning", py.get_type::<DuplicateKeyWarning>())?;
+    m.add("CoercionWarning", py.get_type::<CoercionWarning>())?;
 
     m.add_function(wrap_pyfunction!(load_multiple_yml, m)?)?;
+    m.add_function(wrap_pyfunction!(load_from_manifest, m)?)?;
     m.add_function(wrap_pyfunction!(test_subdoc_specs, m)?)?;
+    m.add_function(wrap_pyfunction!(parse_set_overrides, m)?)?;
+    m.add_function(wrap_pyfunction!(register_template, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_template, m)?)?;
+    m.add_function(wrap_pyfunction!(register_document, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_document, m)?)?;
+    m.add_function(wrap_pyfunction!(register_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_schema, m)?)?;
+    m.add_function(wrap_pyfunction!(register_directive, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_directive, m)?)?;
+    m.add_function(wrap_pyfunction!(register_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(register_transformer, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_transformer, m)?)?;
+    m.add_function(wrap_pyfunction!(set_transformer_enabled, m)?)?;
+    m.add_function(wrap_pyfunction!(set_tracing_enabled, m)?)?;
+    m.add_function(wrap_pyfunction!(register_key_provider, m)?)?;
+    m.add_function(wrap_pyfunction!(unregister_key_provider, m)?)?;
 
     m.add_class::<YamlConfigDocument>()?;
     m.add_class::<DocReference>()?;
+    m.add_class::<PyYcdList>()?;
+    m.add_class::<DirectivePolicy>()?;
+    m.add_class::<ResolutionContext>()?;
+    m.add_class::<CancellationToken>()?;
+    m.add_class::<OneOf>()?;
+    m.add_class::<Range>()?;
+    m.add_class::<Pattern>()?;
+    m.add_class::<Length>()?;
+    m.add_class::<Coerce>()?;
+    m.add_class::<Secret>()?;
+    m.add_class::<UniqueList>()?;
+    m.add_class::<KeyedList>()?;
+    m.add_class::<TypedSchema>()?;
+    m.add_class::<Field>()?;
+    m.add_class::<EncryptedValue>()?;
+    m.add_class::<WalkNode>()?;
+    m.add_class::<TemplateAuditEntry>()?;
+    m.add_class::<MergeStats>()?;
+    m.add_class::<DocumentView>()?;
+    m.add_class::<Workspace>()?;
+    m.add(
+        INTERPRETER_REGISTRY,
+        Py::new(py, InterpreterRegistry::default())?,
+    )?;
 
     Ok(())
 }
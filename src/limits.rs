@@ -0,0 +1,59 @@
+//! Limits for loaded files, so that malicious or corrupted files can't exhaust the memory of
+//! services that load user-supplied files.
+use crate::DocumentLimitError;
+use pyo3::prelude::*;
+use std::fs::metadata;
+
+/// Limits enforced while loading files. Set with the ``max_file_size``, ``max_depth`` and
+/// ``max_nodes`` loading options.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct ParseLimits {
+    /// Maximum size of a file in bytes.
+    pub(crate) max_file_size: Option<u64>,
+    /// Maximum nesting depth of dicts and lists. The top level of a file (containing the header)
+    /// has depth 1.
+    pub(crate) max_depth: Option<usize>,
+    /// Maximum number of values (including dicts and lists) in a file. Values of YAML aliases
+    /// are counted every time the alias is used.
+    pub(crate) max_nodes: Option<usize>,
+}
+
+impl ParseLimits {
+    /// Checks the size of the file at path, before it is read.
+    pub(crate) fn check_file_size(&self, path: &str) -> PyResult<()> {
+        match (self.max_file_size, metadata(path)) {
+            (Some(_), Ok(meta)) => self.check_size(path, meta.len()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Checks the size (in bytes) of the content of the file at path.
+    pub(crate) fn check_size(&self, path: &str, size: u64) -> PyResult<()> {
+        match self.max_file_size {
+            Some(max) if size > max => Err(DocumentLimitError::new_err(format!(
+                "{} is {} bytes large, the maximum is {} bytes.",
+                path, size, max
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// The error message, if a file has more than the maximum number of values.
+    pub(crate) fn nodes_exceeded(&self, nodes: usize) -> Option<String> {
+        match self.max_nodes {
+            Some(max) if nodes > max => Some(format!("More than {} values (the maximum).", max)),
+            _ => None,
+        }
+    }
+
+    /// The error message, if a dict or list is nested deeper than the maximum depth.
+    pub(crate) fn depth_exceeded(&self, depth: usize) -> Option<String> {
+        match self.max_depth {
+            Some(max) if depth > max => Some(format!(
+                "Values are nested deeper than {} levels (the maximum).",
+                max
+            )),
+            _ => None,
+        }
+    }
+}
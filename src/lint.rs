@@ -0,0 +1,144 @@
+//! Lints override documents against the documents they are merged over.
+use crate::conv::YcdValueType::{Dict, List, YString};
+use crate::conv::{YcdDict, YcdList, YcdValueType};
+use crate::merger::same_value;
+use crate::redaction::{is_secret, REDACTED};
+use crate::{DIRECTIVES, REMOVE, REMOVE_FROM_LIST_PREFIX};
+
+/// The value of the override is the same as in the base, so it has no effect.
+pub(crate) const DEAD_OVERRIDE: &str = "dead_override";
+/// The override removes a key or list entry that doesn't exist in the base.
+pub(crate) const NONEXISTENT_REMOVE: &str = "nonexistent_remove";
+/// The key is not part of the schema.
+pub(crate) const UNKNOWN_KEY: &str = "unknown_key";
+/// The value of the override is replaced or removed by a layer merged over it later.
+pub(crate) const SHADOWED_KEY: &str = "shadowed_key";
+
+/// A finding: kind, path of the value (parts separated by ".") and message.
+pub(crate) type LintFinding = (&'static str, String, String);
+
+/// Lints the override body ov, that is merged over base (None if base doesn't contain the dict).
+/// later are the dicts at the same path in layers merged over the override afterwards.
+/// Neither may contain sub-documents (see recursive_docs_to_dicts). Messages don't contain the
+/// values at paths matching secrets (see redaction::secret_patterns).
+pub(crate) fn lint_dict(
+    base: Option<&YcdDict>,
+    ov: &YcdDict,
+    later: &[&YcdDict],
+    path: &str,
+    secrets: &[String],
+    out: &mut Vec<LintFinding>,
+) {
+    let mut keys: Vec<&String> = ov
+        .keys()
+        .filter(|k| !DIRECTIVES.contains(&k.as_str()))
+        .collect();
+    keys.sort();
+    for key in keys {
+        let value = &ov[key];
+        let key_path = join(path, key);
+        let base_value = base.and_then(|b| b.get(key));
+        let replaced_later = later.iter().any(|l| match (value, l.get(key)) {
+            (_, None) | (Dict(_), Some(Dict(_))) | (List(_), Some(List(_))) => false,
+            _ => true,
+        });
+        if replaced_later {
+            out.push((
+                SHADOWED_KEY,
+                key_path,
+                "The value is replaced or removed by a later layer.".to_string(),
+            ));
+            continue;
+        }
+        match value {
+            YString(s) if s == REMOVE => {
+                if base_value.is_none() {
+                    out.push((
+                        NONEXISTENT_REMOVE,
+                        key_path,
+                        "The removed key doesn't exist.".to_string(),
+                    ));
+                }
+            }
+            Dict(d) => {
+                let later: Vec<&YcdDict> = later
+                    .iter()
+                    .filter_map(|l| match l.get(key) {
+                        Some(Dict(ld)) => Some(ld),
+                        _ => None,
+                    })
+                    .collect();
+                match base_value {
+                    Some(Dict(_)) if d.is_empty() => out.push((
+                        DEAD_OVERRIDE,
+                        key_path,
+                        "The empty dict doesn't change anything.".to_string(),
+                    )),
+                    Some(Dict(bd)) => lint_dict(Some(bd), d, &later, &key_path, secrets, out),
+                    _ => lint_dict(None, d, &later, &key_path, secrets, out),
+                }
+            }
+            List(l) => {
+                let base_list = match base_value {
+                    Some(List(bl)) => Some(bl),
+                    _ => None,
+                };
+                lint_list(base_list, l, &key_path, secrets, out);
+            }
+            _ => {
+                if base_value.map_or(false, |bv| same_value(bv, value)) {
+                    out.push((
+                        DEAD_OVERRIDE,
+                        key_path,
+                        "The value is the same as in the base.".to_string(),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn lint_list(
+    base: Option<&YcdList>,
+    ov: &[YcdValueType],
+    path: &str,
+    secrets: &[String],
+    out: &mut Vec<LintFinding>,
+) {
+    if ov.is_empty() && base.is_some() {
+        out.push((
+            DEAD_OVERRIDE,
+            path.to_string(),
+            "The empty list doesn't change anything.".to_string(),
+        ));
+    }
+    for entry in ov {
+        if let YString(s) = entry {
+            if let Some(removed) = s.strip_prefix(REMOVE_FROM_LIST_PREFIX) {
+                let exists = base.map_or(false, |bl| {
+                    bl.iter().any(|e| matches!(e, YString(be) if be == removed))
+                });
+                if !exists {
+                    let removed = if is_secret(path, secrets) {
+                        REDACTED
+                    } else {
+                        removed
+                    };
+                    out.push((
+                        NONEXISTENT_REMOVE,
+                        path.to_string(),
+                        format!("The removed list entry {} doesn't exist.", removed),
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
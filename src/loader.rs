@@ -1,51 +1,91 @@
-use crate::conv::YcdValueType::YString;
-use crate::conv::{PyYamlConfigDocument, SimpleYcdValueType, YHashMap, YcdDict};
-use crate::{merge_documents, InvalidDocumentError, InvalidHeaderError, YamlConfigDocument, REF};
+use crate::cancellation::Deadline;
+use crate::conv::YcdValueType::{Dict, List, YString};
+use crate::conv::{
+    floats_to_decimals, floats_to_source_decimals, PyYamlConfigDocument, YcdDict, YcdValueType,
+};
+use crate::encryption::wrap_tagged_values;
+use crate::features::{check_file_features, file_features, impose_features};
+use crate::formats::{
+    file_content, load_ini_file, load_json_file, load_toml_file, FileFormat, DOCUMENT_EXTENSIONS,
+};
+use crate::graph::{mark_as_override, MergeRole, MergeSource, ResolvedReference};
+use crate::interpolation::interpolate_braced;
+use crate::limits::ParseLimits;
+use crate::lookup::{is_remote, lookup_dir, needs_lookup_dir};
+use crate::options::{DuplicateKeys, LoadOptions};
+use crate::progress::ProgressEvent;
+use crate::provenance::Provenance;
+use crate::registry::{
+    document_names, get_document, get_schema, get_transformers, has_document,
+    registered_document_path, REGISTERED_DOCUMENTS,
+};
+use crate::stats::Stat;
+use crate::suggestions::invalid_header_error;
+use crate::tracing::with_span;
+use crate::validation::validate_layer;
+use crate::yaml::{
+    describe_scalar, find_coercions, find_duplicate_keys, find_encrypted_tags, find_float_texts,
+    parse_yaml,
+};
+use crate::{
+    merge_dicts, merge_documents, CoercionWarning, DuplicateKeyError, DuplicateKeyWarning,
+    InvalidDocumentError, YamlConfigDocument, ENCRYPTED, EXEC, PROFILES, REF, SCHEMA,
+};
 use path_absolutize::Absolutize;
 use pyo3::exceptions;
 pub(crate) use pyo3::prelude::*;
-use pyo3::types::{PyTuple, PyType};
-use std::collections::HashMap;
+use pyo3::types::{PyDict, PyTuple, PyType};
 use std::env::current_dir;
-use std::fs::File;
-use std::path::PathBuf;
+use std::fs::read_dir;
+use std::io;
+use std::io::Read;
+use std::mem::take;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::thread::{sleep, spawn, JoinHandle};
+use std::time::Duration;
 
-#[pyfunction(args = "*")]
+#[pyfunction(args = "*", kwargs = "**")]
 /// Loads (one or) multiple YAML files (paths specified by *args) into the
 /// given YamlConfigDocument model.
 /// The documents are merged as if the rightmost document "$ref"'ed the document left to it, etc.
 /// until all documents are merged.  However ``resolve_and_merge_references`` is not called on the base model;
 /// an optimized internal merging is done instead.
+///
+/// Keyword arguments are loading options, see ``from_yaml``.
 pub(crate) fn load_multiple_yml(
     py: Python,
     doc_type: &PyType,
     args: &PyTuple,
+    kwargs: Option<&PyDict>,
 ) -> PyResult<PyYamlConfigDocument> {
     if args.is_empty() {
         return Err(exceptions::PyTypeError::new_err(
             "At least one document path must be passed.",
         ));
     }
+    let options = LoadOptions::from_kwargs(kwargs)?;
     let args = args.iter().map(|x| x.extract::<String>());
     let mut doc: Option<PyYamlConfigDocument> = None;
     for rarg in args {
         match rarg {
             Ok(arg) => {
-                let new_doc = YamlConfigDocument::from_yaml(doc_type, py, arg.clone())?;
+                let new_doc =
+                    YamlConfigDocument::from_yaml_internal(doc_type, py, arg.clone(), &options)?;
                 doc = Some(match doc {
                     None => new_doc,
-                    Some(d) => merge_documents(py, new_doc, d)?,
+                    Some(d) => {
+                        mark_as_override(&mut new_doc.borrow_mut(py).sources);
+                        merge_documents(py, new_doc, d, &options)?
+                    }
                 });
             }
             Err(e) => return Err(e),
         }
     }
-    Ok(doc.unwrap())
-}
-
-/// Load the full absolute paths to the repositories (lookup paths) stored on disk.
-pub(crate) fn load_repos(lookup_paths: &[String]) -> Vec<String> {
-    lookup_paths.iter().map(|p| to_abs_path(p)).collect()
+    let doc = doc.unwrap();
+    doc.borrow_mut(py).stats = options.stats;
+    Ok(doc)
 }
 
 fn to_abs_path(str: &str) -> String {
@@ -90,114 +130,661 @@ pub(crate) fn path_in_repo(base_path: &Option<String>, reference_path: &str) ->
 
 /// Appends the paths inside repositories to the lookup_paths/repository paths, building a unique
 /// absolute path on the disc that is only missing the file extension.
+/// Remote and git lookup paths are replaced by their cache directories, after fetching the documents.
 ///
 /// :param ref_path_in_repo: Path of resource absolute to repository root
 /// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
 pub(crate) fn absolute_paths(
+    py: Python,
     ref_path_in_repo: &str,
     lookup_paths: &[String],
+    options: &LoadOptions,
 ) -> PyResult<Vec<String>> {
     let ref_path_in_repo_cln: &str;
     match ref_path_in_repo.strip_prefix('/') {
         None => ref_path_in_repo_cln = ref_path_in_repo,
         Some(p) => ref_path_in_repo_cln = p,
     }
-    load_repos(lookup_paths)
+    lookup_paths
         .iter()
-        .map(|absolute_repo_path| {
+        .map(|lookup_path| {
+            let absolute_repo_path = if needs_lookup_dir(lookup_path) {
+                lookup_dir(py, lookup_path, ref_path_in_repo_cln, options)?
+            } else {
+                to_abs_path(lookup_path)
+            };
             // TODO: Is this safe?
             Ok(format!("{}/{}", absolute_repo_path, ref_path_in_repo_cln))
         })
         .collect::<PyResult<Vec<String>>>()
 }
 
-/// Load the actual dictionaries at path by checking if files ending in .yml/.yaml exist.
-pub(crate) fn load_dicts(path: &str) -> PyResult<Vec<YcdDict>> {
-    let mut doc_dicts: Vec<YcdDict> = Vec::with_capacity(2);
-    if let Some(f) = load_dicts_try_single_path(PathBuf::from(format!("{}.yml", path)))? {
-        doc_dicts.push(f);
+/// Whether a document exists at the path inside any of the repositories (or is registered
+/// with ``register_document`` or a virtual file).
+///
+/// :param ref_path_in_repo: Path of the document absolute to the repositories, without file extension
+/// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
+/// :param options: Loading options, for the documents registered in the resolution context
+pub(crate) fn document_exists_in_repos(
+    py: Python,
+    ref_path_in_repo: &str,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<bool> {
+    if has_document(py, ref_path_in_repo, options.context.as_deref())? {
+        return Ok(true);
     }
-    if let Some(f) = load_dicts_try_single_path(PathBuf::from(format!("{}.yaml", path)))? {
-        doc_dicts.push(f);
+    Ok(absolute_paths(py, ref_path_in_repo, lookup_paths, options)?
+        .iter()
+        .any(|path| {
+            DOCUMENT_EXTENSIONS
+                .iter()
+                .any(|ext| options.virtual_files.is_file(format!("{}.{}", path, ext)))
+        }))
+}
+
+/// Finds the paths (absolute to the repositories, without file extension) of all documents in
+/// the repositories (and of all registered documents and virtual files) that match pattern. Only the last part of
+/// the pattern may contain the wildcards ``*`` (any number of characters) and ``?`` (a single character).
+///
+/// The result is sorted and contains each path only once, even if it exists in multiple repositories.
+/// The documents of remote lookup paths can't be listed, they are not searched.
+///
+/// :param pattern_in_repo: Pattern absolute to the repository root, without file extension
+/// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
+/// :param options: Loading options, for the documents registered in the resolution context
+pub(crate) fn matching_paths_in_repos(
+    py: Python,
+    pattern_in_repo: &str,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<Vec<String>> {
+    let (dir_in_repo, file_pattern) = match pattern_in_repo.rsplit_once('/') {
+        None => ("", pattern_in_repo),
+        Some(x) => x,
+    };
+    let mut out: Vec<String> = vec![];
+    for name in document_names(py, options.context.as_deref())? {
+        if let Some((dir, stem)) = format!("/{}", name).rsplit_once('/') {
+            if dir.trim_start_matches('/') == dir_in_repo.trim_start_matches('/')
+                && wildcard_match(file_pattern, stem)
+            {
+                out.push(format!("{}/{}", dir_in_repo, stem));
+            }
+        }
+    }
+    let local_lookup_paths: Vec<String> = lookup_paths
+        .iter()
+        .filter(|p| !is_remote(p))
+        .cloned()
+        .collect();
+    for dir in absolute_paths(py, dir_in_repo, &local_lookup_paths, options)? {
+        let mut paths = options.virtual_files.files_in_dir(&dir);
+        if let Ok(entries) = read_dir(&dir) {
+            paths.extend(
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file()),
+            );
+        }
+        for path in paths {
+            let is_document = path
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .map(|ext| DOCUMENT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+                .unwrap_or(false);
+            if !is_document {
+                continue;
+            }
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                if wildcard_match(file_pattern, stem) {
+                    out.push(format!("{}/{}", dir_in_repo, stem));
+                }
+            }
+        }
+    }
+    out.sort();
+    out.dedup();
+    Ok(out)
+}
+
+/// Matches name against a pattern that may contain the wildcards ``*`` and ``?``.
+fn wildcard_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, n));
+            p += 1;
+        } else if let Some((bp, bn)) = backtrack {
+            p = bp + 1;
+            n = bn + 1;
+            backtrack = Some((bp, bn + 1));
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+/// Load the actual dictionaries at path by checking if files ending in .yml/.yaml
+/// (or .ini/.cfg, .json, .toml) exist. Returns the absolute paths of the files with their content.
+/// The files are loaded as documents of type doc_cls.
+pub(crate) fn load_dicts(
+    py: Python,
+    path: &str,
+    doc_cls: &PyType,
+    options: &LoadOptions,
+) -> PyResult<Vec<(String, YcdDict)>> {
+    let mut doc_dicts: Vec<(String, YcdDict)> = Vec::with_capacity(2);
+    for ext in DOCUMENT_EXTENSIONS.iter() {
+        if let Some(f) = load_dicts_try_single_path(
+            py,
+            PathBuf::from(format!("{}.{}", path, ext)),
+            doc_cls,
+            options,
+        )? {
+            doc_dicts.push(f);
+        }
     }
     Ok(doc_dicts)
 }
 
-fn load_dicts_try_single_path(path: PathBuf) -> PyResult<Option<YcdDict>> {
+fn load_dicts_try_single_path(
+    py: Python,
+    path: PathBuf,
+    doc_cls: &PyType,
+    options: &LoadOptions,
+) -> PyResult<Option<(String, YcdDict)>> {
     if let Ok(c) = path.absolutize_virtually("/") {
-        if c.exists() {
-            return Ok(Some(load_yaml_file(c.to_str().unwrap())?));
+        if options.virtual_files.get(&c).is_some() || c.exists() {
+            let file = c.to_str().unwrap().to_string();
+            let dict = load_document_file(py, &file, doc_cls, options)?;
+            return Ok(Some((file, dict)));
         }
     }
     Ok(None)
 }
 
-pub(crate) fn load_yaml_file(path_to_yaml: &str) -> PyResult<YcdDict> {
-    let file;
-    match File::open(path_to_yaml) {
-        Ok(v) => file = v,
-        Err(e) => {
-            return Err(InvalidDocumentError::new_err(format!(
-                "Unable to open YAML file {}: {:?}",
-                path_to_yaml, e
-            )))
+/// Loads a document file, as a document of type doc_cls (see
+/// YamlConfigDocument::canonicalize_header). INI, JSON and TOML files are detected by their file
+/// extension, everything else is read as YAML.
+pub(crate) fn load_document_file(
+    py: Python,
+    path: &str,
+    doc_cls: &PyType,
+    options: &LoadOptions,
+) -> PyResult<YcdDict> {
+    with_span(
+        py,
+        "configcrunch.load",
+        || Ok(vec![("configcrunch.file", path.to_string())]),
+        || {
+            options.deadline.check()?;
+            match options.virtual_files.get(path) {
+                Some(content) => options.limits.check_size(path, content.len() as u64)?,
+                None => options.limits.check_file_size(path)?,
+            }
+            // Reading and parsing doesn't need the GIL, other threads can run meanwhile.
+            // The file is read once, so the parsed content is the verified content.
+            let content = py.allow_threads(|| file_content(path, options))?;
+            options
+                .trusted_keys
+                .verify(path, content.as_bytes(), &options.virtual_files)?;
+            let content = content.as_str();
+            let dict = match FileFormat::of(path) {
+                FileFormat::Ini => py.allow_threads(|| load_ini_file(path, content, options))?,
+                FileFormat::Json => py.allow_threads(|| load_json_file(path, content))?,
+                FileFormat::Toml => py.allow_threads(|| load_toml_file(path, content))?,
+                FileFormat::Yaml => {
+                    check_duplicate_keys(py, path, content, options)?;
+                    report_coercions(py, path, content, options)?;
+                    let mut dict =
+                        py.allow_threads(|| parse_yaml_content(path, content, options))?;
+                    apply_encrypted_tags(path, &mut dict, content, options)?;
+                    if options.decimal_floats {
+                        floats_to_source_decimals(&mut dict, find_float_texts(path, content)?);
+                    }
+                    dict
+                }
+            };
+            let mut dict = apply_transformers(py, path, dict)?;
+            YamlConfigDocument::canonicalize_header(doc_cls, &mut dict, path)?;
+            let dict = prepare_document_dict(py, path, dict, options)?;
+            options.stats.add(Stat::DocumentsLoaded, 1);
+            options
+                .progress
+                .report(py, ProgressEvent::FileLoaded, Some(path))?;
+            Ok(dict)
+        },
+    )
+}
+
+/// Passes the content of a loaded file through all enabled transformers (see
+/// ``register_transformer``).
+fn apply_transformers(py: Python, path: &str, mut dict: YcdDict) -> PyResult<YcdDict> {
+    for (name, transformer) in get_transformers(py)? {
+        let content = dict.to_object(py);
+        let result = transformer.call1(py, (content.clone_ref(py), path))?;
+        let result = if result.is_none(py) { content } else { result };
+        dict = result.extract(py).map_err(|_| {
+            InvalidDocumentError::new_err(format!(
+                "Transformer {} returned invalid content for {}: {}. Expected a dict or None.",
+                name,
+                path,
+                result.as_ref(py)
+            ))
+        })?;
+    }
+    Ok(dict)
+}
+
+/// Applies the directive policy, $exec, the $features, the selected profile and the inline schemas
+/// to the content of a loaded file or registered document.
+pub(crate) fn prepare_document_dict(
+    py: Python,
+    path: &str,
+    mut dict: YcdDict,
+    options: &LoadOptions,
+) -> PyResult<YcdDict> {
+    if options.env_interpolation {
+        for value in dict.values_mut() {
+            interpolate_env(path, value)?;
+        }
+    }
+    if options.decimal_floats {
+        dict.values_mut().for_each(floats_to_decimals);
+    }
+    if let Some(policy) = &options.directive_policy {
+        dict = policy.apply(py, path, dict)?;
+    }
+    for value in dict.values_mut() {
+        apply_exec(py, path, value, options)?;
+    }
+    let features = impose_features(path, &mut dict, &options.features)?;
+    check_file_features(path, &dict, &features)?;
+    dict = apply_profiles(py, path, dict, options)?;
+    validate_inline_schemas(py, path, dict)
+}
+
+/// Replaces ``${VAR}`` expressions in all strings of value with environment variables.
+fn interpolate_env(path: &str, value: &mut YcdValueType) -> PyResult<()> {
+    match value {
+        Dict(d) => {
+            for v in d.values_mut() {
+                interpolate_env(path, v)?;
+            }
+        }
+        List(l) => {
+            for v in l.iter_mut() {
+                interpolate_env(path, v)?;
+            }
+        }
+        YString(s) => {
+            *s = interpolate_braced(s).map_err(|e| {
+                InvalidDocumentError::new_err(format!(
+                    "Error interpolating an environment variable in {}: {}",
+                    path, e
+                ))
+            })?
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Replaces the $exec entries of all dicts in value with the output of their commands. The other
+/// entries of the dicts are merged over the output.
+fn apply_exec(
+    py: Python,
+    path: &str,
+    value: &mut YcdValueType,
+    options: &LoadOptions,
+) -> PyResult<()> {
+    match value {
+        Dict(d) => {
+            for v in d.values_mut() {
+                apply_exec(py, path, v, options)?;
+            }
+            if let Some(command) = d.shift_remove(EXEC) {
+                let output = run_exec_command(py, path, command, options)?;
+                *d = merge_dicts(py, output, take(d), options)?;
+            }
+        }
+        List(l) => {
+            for v in l.iter_mut() {
+                apply_exec(py, path, v, options)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Runs the command of an $exec (in the directory of the file at path, if it exists) and parses
+/// its output as YAML mapping. The command is killed if the timeout of the options passes (or
+/// the operation is cancelled) before it finishes.
+fn run_exec_command(
+    py: Python,
+    path: &str,
+    command: YcdValueType,
+    options: &LoadOptions,
+) -> PyResult<YcdDict> {
+    if !options
+        .directive_policy
+        .as_ref()
+        .map_or(false, |p| p.allow_exec)
+    {
+        return Err(InvalidDocumentError::new_err(format!(
+            "{} is not allowed in {}. It must be enabled with DirectivePolicy(allow_exec=True).",
+            EXEC, path
+        )));
+    }
+    let args: Vec<String> = match command {
+        List(l) if !l.is_empty() => l
+            .into_iter()
+            .map(|arg| match arg {
+                YString(s) => Some(s),
+                _ => None,
+            })
+            .collect::<Option<Vec<String>>>(),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        InvalidDocumentError::new_err(format!(
+            "Invalid {} in {}. Expected a list of the command and its arguments (as strings).",
+            EXEC, path
+        ))
+    })?;
+    let mut cmd = Command::new(&args[0]);
+    cmd.args(&args[1..]);
+    if let Some(dir) = Path::new(path).parent().filter(|d| d.is_dir()) {
+        cmd.current_dir(dir);
+    }
+    let io_error = |e: io::Error| {
+        InvalidDocumentError::new_err(format!(
+            "Unable to run the {} command {} in {}: {}",
+            EXEC, args[0], path, e
+        ))
+    };
+    let result = py.allow_threads(|| run_until_deadline(&mut cmd, &options.deadline, io_error))?;
+    if !result.status.success() {
+        return Err(InvalidDocumentError::new_err(format!(
+            "The {} command {} in {} failed ({}): {}",
+            EXEC,
+            args[0],
+            path,
+            result.status,
+            String::from_utf8_lossy(&result.stderr).trim()
+        )));
+    }
+    let name = format!("{} output of {} in {}", EXEC, args[0], path);
+    let mut output = parse_yaml_str(&name, &String::from_utf8_lossy(&result.stdout))?;
+    if options.decimal_floats {
+        output.values_mut().for_each(floats_to_decimals);
+    }
+    Ok(output)
+}
+
+/// Runs cmd and collects its output, like Command::output. If the deadline passes before the
+/// command finishes, the command is killed and the error of the deadline is returned.
+fn run_until_deadline(
+    cmd: &mut Command,
+    deadline: &Deadline,
+    io_error: impl Fn(io::Error) -> PyErr,
+) -> PyResult<Output> {
+    let mut child = cmd
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(&io_error)?;
+    // The pipes are read while waiting, so the command doesn't block when they are full.
+    let stdout = read_in_thread(child.stdout.take());
+    let stderr = read_in_thread(child.stderr.take());
+    let status = loop {
+        if let Some(status) = child.try_wait().map_err(&io_error)? {
+            break status;
         }
+        if let Err(e) = deadline.check() {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(e);
+        }
+        sleep(Duration::from_millis(10));
     };
+    Ok(Output {
+        status,
+        stdout: stdout.join().unwrap_or_default(),
+        stderr: stderr.join().unwrap_or_default(),
+    })
+}
+
+/// Reads pipe to its end in a new thread.
+fn read_in_thread(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    spawn(move || {
+        let mut content = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut content);
+        }
+        content
+    })
+}
 
-    match serde_yaml::from_reader::<File, HashMap<String, SimpleYcdValueType>>(file) {
-        Ok(v) => Ok(YHashMap(v).into()),
-        Err(e) => Err(InvalidDocumentError::new_err(format!(
-            "Unable to read YAML file {}: {:?}",
-            path_to_yaml, e
-        ))),
+/// If a profile is selected in the options, removes the ``profiles`` sections from the document bodies
+/// of a loaded file and merges the section of the profile over the body. Otherwise ``profiles`` is
+/// not reserved and kept like any other key.
+fn apply_profiles(
+    py: Python,
+    path: &str,
+    mut dict: YcdDict,
+    options: &LoadOptions,
+) -> PyResult<YcdDict> {
+    let name = match &options.profile {
+        None => return Ok(dict),
+        Some(name) => name,
+    };
+    for body in dict.values_mut() {
+        if let Dict(body) = body {
+            let mut profiles = match body.shift_remove(PROFILES) {
+                None => continue,
+                Some(Dict(profiles)) => profiles,
+                Some(v) => {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "Invalid {} in {}: {}. Expected a mapping of profile names to overrides.",
+                        PROFILES, path, v
+                    )))
+                }
+            };
+            let profile = match profiles.shift_remove(name) {
+                None => continue,
+                Some(Dict(profile)) => profile,
+                Some(v) => {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "Invalid profile {} in {}: {}. Expected a mapping.",
+                        name, path, v
+                    )))
+                }
+            };
+            *body = merge_dicts(py, take(body), profile, options)?;
+        }
     }
+    Ok(dict)
+}
+
+/// Validates the document bodies of a loaded file that declare a $schema against that schema.
+/// The $schema entries are removed.
+fn validate_inline_schemas(py: Python, path: &str, mut dict: YcdDict) -> PyResult<YcdDict> {
+    for body in dict.values_mut() {
+        if let Dict(body) = body {
+            let name = match body.shift_remove(SCHEMA) {
+                None => continue,
+                Some(YString(name)) => name,
+                Some(v) => {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "Invalid {} in {}: {}. Expected a schema name.",
+                        SCHEMA, path, v
+                    )))
+                }
+            };
+            let args = PyTuple::new(py, [body.to_object(py)]);
+            if let Err(orig_err) = get_schema(py, &name)?.call_method1("validate", args) {
+                let err = InvalidDocumentError::new_err(format!(
+                    "{} does not match the schema {}: {}",
+                    path,
+                    name,
+                    orig_err.pvalue(py)
+                ));
+                err.pvalue(py).setattr("__cause__", orig_err.pvalue(py))?;
+                return Err(err);
+            }
+        }
+    }
+    Ok(dict)
+}
+
+/// Raises a DuplicateKeyError or emits a DuplicateKeyWarning (depending on the options), if a
+/// mapping in the YAML file contains the same key more than once.
+fn check_duplicate_keys(
+    py: Python,
+    path: &str,
+    content: &str,
+    options: &LoadOptions,
+) -> PyResult<()> {
+    if options.duplicate_keys == DuplicateKeys::Ignore {
+        return Ok(());
+    }
+    for duplicate in find_duplicate_keys(path, content)? {
+        let message = format!(
+            "Duplicate key {} in {}: Defined in line {} and again in line {}.",
+            duplicate.path, path, duplicate.first_line, duplicate.line
+        );
+        if options.duplicate_keys == DuplicateKeys::Error {
+            return Err(DuplicateKeyError::new_err(message));
+        }
+        PyErr::warn(py, py.get_type::<DuplicateKeyWarning>(), &message, 1)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn load_yaml_file(path_to_yaml: &str, options: &LoadOptions) -> PyResult<YcdDict> {
+    parse_yaml_content(path_to_yaml, &file_content(path_to_yaml, options)?, options)
+}
+
+/// Emits a CoercionWarning for every scalar in the YAML file, whose type or value depends on
+/// whether it's typed with YAML 1.1 (the default) or the YAML 1.2 core schema.
+fn report_coercions(py: Python, path: &str, content: &str, options: &LoadOptions) -> PyResult<()> {
+    if !options.report_coercions || options.core_schema {
+        return Ok(());
+    }
+    for coercion in find_coercions(path, content)? {
+        let message = format!(
+            "{} in {} (line {}): {} is read as {}, but as {} with core_schema.",
+            coercion.path,
+            path,
+            coercion.line,
+            coercion.text,
+            describe_scalar(&coercion.value),
+            describe_scalar(&coercion.core_value)
+        );
+        PyErr::warn(py, py.get_type::<CoercionWarning>(), &message, 1)?;
+    }
+    Ok(())
+}
+
+/// Replaces the scalars tagged with ``!encrypted`` in the YAML file at path with encrypted values
+/// (``{$encrypted: <ciphertext>}``, see ``encryption``).
+fn apply_encrypted_tags(
+    path: &str,
+    dict: &mut YcdDict,
+    content: &str,
+    options: &LoadOptions,
+) -> PyResult<()> {
+    if !content.contains("!encrypted") {
+        return Ok(());
+    }
+    let key = match &options.directive_policy {
+        Some(policy) => format!("{}{}", policy.prefix, &ENCRYPTED[1..]),
+        None => ENCRYPTED.to_string(),
+    };
+    wrap_tagged_values(dict, find_encrypted_tags(path, content)?, &key);
+    Ok(())
+}
+
+/// Parses the content of the YAML file at path, with the typing and limits selected by the options.
+fn parse_yaml_content(path: &str, content: &str, options: &LoadOptions) -> PyResult<YcdDict> {
+    parse_yaml(path, content, &options.limits, options.core_schema)
+}
+
+/// Parses YAML content that was not read from a file. name is used in error messages.
+pub(crate) fn parse_yaml_str(name: &str, content: &str) -> PyResult<YcdDict> {
+    parse_yaml(name, content, &ParseLimits::default(), false)
 }
 
 /// Converts a loaded dict-object into a specified type of YamlConfigDocument if it's header matches.
 ///
 /// :param doc_dict: source dictionary to be converted
 /// :param doc_cls: instance of YamlConfigDocument to be created
+/// :param absolute_path: Absolute path of the file (or path of the registered document) doc_dict was loaded from
 /// :param ref_path_in_repo: Path of this document that should be created inside of the repositories
 /// :param parent: parent document
+/// :param read_only: Whether the file must not be written back to (see ``MergeSource``)
 /// :return: instance of YamlConfigDocument containing doc_dict without the header
 pub(crate) fn dict_to_doc_cls(
     py: Python,
-    doc_dict: YcdDict,
+    mut doc_dict: YcdDict,
     doc_cls: &PyType,
     absolute_path: &str,
     ref_path_in_repo: &str,
     parent: PyYamlConfigDocument,
+    read_only: bool,
 ) -> PyResult<PyYamlConfigDocument> {
-    let buf = PathBuf::from(absolute_path);
-    let vrt = buf.absolutize_virtually("/")?;
-    let absolute_path: String = vrt.to_str().as_ref().unwrap().to_string();
     let parent_ref = parent.borrow(py);
-    let header = doc_cls.getattr("header")?.call0()?;
-    let header: &str = header.extract()?;
-    if doc_dict.contains_key(header) {
-        let new_abs_paths: Vec<String> = [absolute_path]
+    let header = YamlConfigDocument::canonicalize_header(doc_cls, &mut doc_dict, absolute_path)?;
+    if doc_dict.contains_key(&header) {
+        let new_abs_paths: Vec<String> = [absolute_path.to_string()]
             .into_iter()
             .chain(parent_ref.absolute_paths.clone().into_iter())
             .collect();
-        return construct_new_ycd(
+        let doc = construct_new_ycd(
             py,
             doc_cls,
             [
                 doc_cls.to_object(py),
-                doc_dict.get(header).unwrap().to_object(py),
+                doc_dict.get(&header).unwrap().to_object(py),
                 ref_path_in_repo.into_py(py),
                 parent.to_object(py),
                 parent_ref.already_loaded_docs.to_object(py),
                 new_abs_paths.into_py(py),
             ],
-        );
+        )?;
+        let mut doc_mut = doc.borrow_mut(py);
+        doc_mut.features = file_features(absolute_path, &doc_dict)?;
+        doc_mut.provenance = Provenance::from_body(&doc_mut.doc, Some(absolute_path));
+        doc_mut.sources = vec![MergeSource {
+            path: absolute_path.to_string(),
+            role: MergeRole::Referenced,
+            read_only,
+        }];
+        drop(doc_mut);
+        return Ok(doc);
     }
 
-    Err(InvalidHeaderError::new_err(format!(
-        "Subdocument of type {} (path: {}) has invalid header.",
-        doc_cls.getattr("__name__")?,
-        ref_path_in_repo
-    )))
+    Err(invalid_header_error(
+        py,
+        doc_cls,
+        &doc_dict,
+        format!(
+            "Subdocument of type {} (path: {}) has invalid header.",
+            doc_cls.getattr("__name__")?,
+            ref_path_in_repo
+        ),
+    )?)
 }
 
 /// Loads a document referenced ($ref) in a YamlConfigDocument
@@ -208,6 +795,7 @@ pub(crate) fn load_referenced_document(
     py: Python,
     document: PyYamlConfigDocument,
     lookup_paths: &[String],
+    options: &LoadOptions,
 ) -> PyResult<Vec<PyYamlConfigDocument>> {
     let doc_ref: PyRef<YamlConfigDocument> = document.borrow(py);
     let ref_path_in_repo;
@@ -221,29 +809,78 @@ pub(crate) fn load_referenced_document(
         // Invalid path
         return Ok(vec![]);
     }
+    drop(doc_ref);
+    load_documents_in_repos(py, document, &ref_path_in_repo, lookup_paths, options)
+}
+
+/// Loads all documents at a path inside the repositories (lookup paths) with the type of document.
+/// A document registered at the path comes first, so the files take priority over it.
+/// The loaded files are recorded as references of document.
+///
+/// :param document: The document that references the documents
+/// :param ref_path_in_repo: Path of the documents absolute to the repositories, without file extension
+/// :param lookup_paths: Paths to the repositories, as stored in the configuration documents
+pub(crate) fn load_documents_in_repos(
+    py: Python,
+    document: PyYamlConfigDocument,
+    ref_path_in_repo: &str,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<Vec<PyYamlConfigDocument>> {
     let doc_cls: Py<PyType> = document.getattr(py, "__class__")?.extract(py)?;
-    // error handling with nested iterators/vectors involved sure is readable.
+    let from = document.borrow(py).absolute_paths.first().cloned();
     let mut out: Vec<PyYamlConfigDocument> = Vec::with_capacity(100);
-    for absolute_path in absolute_paths(&ref_path_in_repo, lookup_paths)? {
-        let dicts = load_dicts(&absolute_path)?;
-        match dicts
-            .into_iter()
-            .map(|doc_dict| {
-                dict_to_doc_cls(
-                    py,
-                    doc_dict,
-                    doc_cls.as_ref(py),
-                    &absolute_path,
-                    &ref_path_in_repo,
-                    document.clone_ref(py),
-                )
-            })
-            .collect::<PyResult<Vec<PyYamlConfigDocument>>>()
-        {
-            Ok(mut inner_vec) => out.append(&mut inner_vec),
-            Err(e) => return Err(e),
-        };
+    let mut references: Vec<ResolvedReference> = Vec::with_capacity(2);
+    if let Some(doc_dict) = get_document(py, ref_path_in_repo, options.context.as_deref())? {
+        let file = registered_document_path(ref_path_in_repo);
+        let mut doc_dict = doc_dict;
+        YamlConfigDocument::canonicalize_header(doc_cls.as_ref(py), &mut doc_dict, &file)?;
+        let doc_dict = prepare_document_dict(py, &file, doc_dict, options)?;
+        let doc = dict_to_doc_cls(
+            py,
+            doc_dict,
+            doc_cls.as_ref(py),
+            &file,
+            ref_path_in_repo,
+            document.clone_ref(py),
+            true,
+        )?;
+        validate_layer(py, &doc.0, &file, options)?;
+        out.push(doc);
+        references.push(ResolvedReference {
+            from: from.clone(),
+            reference: ref_path_in_repo.to_string(),
+            file,
+            lookup_path: REGISTERED_DOCUMENTS.to_string(),
+        });
+    }
+    for (absolute_path, lookup_path) in absolute_paths(py, ref_path_in_repo, lookup_paths, options)?
+        .into_iter()
+        .zip(lookup_paths.iter())
+    {
+        for (file, doc_dict) in load_dicts(py, &absolute_path, doc_cls.as_ref(py), options)? {
+            let read_only =
+                needs_lookup_dir(lookup_path) || options.virtual_files.get(&file).is_some();
+            let doc = dict_to_doc_cls(
+                py,
+                doc_dict,
+                doc_cls.as_ref(py),
+                &file,
+                ref_path_in_repo,
+                document.clone_ref(py),
+                read_only,
+            )?;
+            validate_layer(py, &doc.0, &file, options)?;
+            out.push(doc);
+            references.push(ResolvedReference {
+                from: from.clone(),
+                reference: ref_path_in_repo.to_string(),
+                file,
+                lookup_path: lookup_path.clone(),
+            });
+        }
     }
+    document.borrow_mut(py).references.extend(references);
     Ok(out)
 }
 
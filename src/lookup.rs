@@ -0,0 +1,179 @@
+//! Lookup paths that are not directories on disk. They are resolved to (cache) directories by
+//! ``configcrunch._lookup``, that are then searched like other lookup paths:
+//!
+//! - HTTPS base URLs (``https://example.org/configs``): The documents are fetched on demand into
+//!   the cache directory and revalidated with ETag / Last-Modified.
+//! - Git repositories (``git+https://example.org/repo.git@v1.2//subdir``): The repository is
+//!   fetched into the cache directory and the ref is checked out.
+//! - Package data (``pkg://mypackage/configs``): The directory of the resources of a Python
+//!   package, found with importlib.resources.
+//!
+//! Lookup paths can also be conditional (``{path: ./linux, when: "platform == 'linux'"}``), they
+//! are only searched if their condition is true (see select_lookup_paths).
+use crate::conv::YcdDict;
+use crate::formats::DOCUMENT_EXTENSIONS;
+use crate::merger::evaluate_condition;
+use crate::options::LoadOptions;
+use crate::YamlConfigDocument;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+
+/// An entry of lookup paths: A path, or a dict with the ``path`` and a ``when`` condition.
+#[derive(Clone, Debug)]
+pub(crate) struct LookupPathEntry {
+    pub(crate) path: String,
+    /// Template expression, the path is only searched if it's true.
+    pub(crate) when: Option<String>,
+}
+
+impl<'source> FromPyObject<'source> for LookupPathEntry {
+    fn extract(ob: &'source PyAny) -> PyResult<Self> {
+        if let Ok(path) = ob.extract::<String>() {
+            return Ok(Self { path, when: None });
+        }
+        let dict: &PyDict = ob.downcast().map_err(|_| {
+            exceptions::PyTypeError::new_err(format!(
+                "Invalid lookup path: {}. Expected a path or a dict with path and when.",
+                ob
+            ))
+        })?;
+        let mut path = None;
+        let mut when = None;
+        for (key, value) in dict {
+            match key.extract::<&str>()? {
+                "path" => path = Some(value.extract()?),
+                "when" => when = value.extract()?,
+                other => {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "Unknown key {} of lookup path {}.",
+                        other, dict
+                    )))
+                }
+            }
+        }
+        let path = path.ok_or_else(|| {
+            exceptions::PyValueError::new_err(format!("The lookup path {} has no path.", dict))
+        })?;
+        Ok(Self { path, when })
+    }
+}
+
+impl ToPyObject for LookupPathEntry {
+    fn to_object(&self, py: Python) -> PyObject {
+        match &self.when {
+            None => self.path.to_object(py),
+            Some(when) => {
+                let dict = PyDict::new(py);
+                dict.set_item("path", &self.path).ok();
+                dict.set_item("when", when).ok();
+                dict.into()
+            }
+        }
+    }
+}
+
+impl IntoPy<PyObject> for LookupPathEntry {
+    fn into_py(self, py: Python) -> PyObject {
+        self.to_object(py)
+    }
+}
+
+/// The paths of the entries, whose condition is true (or that have none). The conditions are
+/// template expressions, that can use ``platform`` (``sys.platform``), ``hostname`` and the globals
+/// of the loading options (which take priority). They are evaluated in an empty document of
+/// doc_type, so that its variable helpers are available.
+pub(crate) fn select_lookup_paths(
+    py: Python,
+    doc_type: &PyType,
+    entries: Vec<LookupPathEntry>,
+    options: &LoadOptions,
+) -> PyResult<Vec<String>> {
+    if entries.iter().all(|e| e.when.is_none()) {
+        return Ok(entries.into_iter().map(|e| e.path).collect());
+    }
+    let mut globals: YcdDict = py
+        .import("configcrunch._util")?
+        .getattr("lookup_path_globals")?
+        .call0()?
+        .extract()?;
+    if let Some(passed) = &options.globals {
+        globals.extend(passed.clone());
+    }
+    let doc = YamlConfigDocument::from_dict(doc_type, py, PyDict::new(py).into())?;
+    doc.borrow_mut(py).globals = Some(globals);
+    let mut paths = vec![];
+    for entry in entries {
+        let selected = match &entry.when {
+            None => true,
+            Some(condition) => evaluate_condition(
+                py,
+                &doc,
+                &format!("the lookup path {}", entry.path),
+                condition,
+            )?,
+        };
+        if selected {
+            paths.push(entry.path);
+        }
+    }
+    Ok(paths)
+}
+
+/// Whether the lookup path is an HTTPS base URL.
+pub(crate) fn is_remote(lookup_path: &str) -> bool {
+    lookup_path.starts_with("https://")
+}
+
+/// Whether the lookup path is a git repository.
+pub(crate) fn is_git(lookup_path: &str) -> bool {
+    lookup_path.starts_with("git+")
+}
+
+/// Whether the lookup path names the resources of a Python package.
+pub(crate) fn is_package(lookup_path: &str) -> bool {
+    lookup_path.starts_with("pkg://")
+}
+
+/// Whether the lookup path must be resolved with lookup_dir.
+pub(crate) fn needs_lookup_dir(lookup_path: &str) -> bool {
+    is_remote(lookup_path) || is_git(lookup_path) || is_package(lookup_path)
+}
+
+/// The directory containing the documents of a lookup path, that is not a directory itself (see
+/// needs_lookup_dir). For remote lookup paths, the documents at path_in_repo (without file
+/// extension) are fetched first, if they are not available locally yet (with their signatures, if
+/// the lookup path has a trusted key). Git repositories are fetched once per process.
+pub(crate) fn lookup_dir(
+    py: Python,
+    lookup_path: &str,
+    path_in_repo: &str,
+    options: &LoadOptions,
+) -> PyResult<String> {
+    let module = py.import("configcrunch._lookup")?;
+    let dir: String = if is_git(lookup_path) {
+        module
+            .getattr("checkout_git")?
+            .call1((lookup_path, options.cache_dir.as_deref()))?
+            .extract()?
+    } else if is_package(lookup_path) {
+        module
+            .getattr("package_dir")?
+            .call1((lookup_path, options.cache_dir.as_deref()))?
+            .extract()?
+    } else {
+        let extensions: Vec<&str> = DOCUMENT_EXTENSIONS.to_vec();
+        module
+            .getattr("fetch_remote")?
+            .call1((
+                lookup_path,
+                path_in_repo,
+                extensions,
+                options.cache_dir.as_deref(),
+                options.trusted_keys.has_lookup_path(lookup_path),
+            ))?
+            .extract()?
+    };
+    options.trusted_keys.add_lookup_dir(lookup_path, &dir);
+    Ok(dir)
+}
@@ -0,0 +1,252 @@
+//! Layer composition manifests: Files listing the documents to compose, in which order and with
+//! which per-layer options (see ``load_from_manifest``).
+use crate::conv::YcdValueType::{Bool, Dict, List, YString};
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::graph::mark_as_override;
+use crate::loader::load_yaml_file;
+use crate::lookup::LookupPathEntry;
+use crate::merger::{evaluate_condition, merge_documents};
+use crate::minijinja::SafeMode;
+use crate::options::LoadOptions;
+use crate::{InvalidDocumentError, YamlConfigDocument};
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use std::mem::replace;
+use std::path::Path;
+
+/// Key of the list of layers in a manifest.
+const LAYERS: &str = "layers";
+/// Key of the lookup paths in a manifest.
+const LOOKUP_PATHS: &str = "lookup_paths";
+
+/// A layer of a manifest.
+struct Layer {
+    /// Path of the file, relative to the directory of the manifest.
+    path: String,
+    /// Profile the file is loaded with, instead of the profile of the loading options.
+    profile: Option<String>,
+    /// If false, the layer is skipped if the file doesn't exist.
+    required: bool,
+    /// Template expression (see evaluate_condition). The layer is skipped if it's false.
+    condition: Option<String>,
+}
+
+#[pyfunction(variables = "None", kwargs = "**")]
+/// Loads the layers listed in a manifest file (usually ``layers.yml``) as a document of type
+/// ``doc_type`` and merges them in order, like ``load_multiple_yml`` (later layers take priority).
+/// The manifest looks like this:
+///
+/// .. code-block:: yaml
+///
+///     layers:
+///       - base.yml
+///       - path: local.yml
+///         required: false
+///       - path: debug.yml
+///         profile: debug
+///         if: "environment == 'dev' and debug"
+///     lookup_paths:
+///       - repo
+///       - path: repo_linux
+///         when: "platform == 'linux'"
+///
+/// Layers are paths or dicts with the ``path`` and optionally:
+///
+/// - ``profile``: The profile the layer is loaded with (see the ``profile`` loading option).
+/// - ``required`` (default true): If false, the layer is skipped if the file doesn't exist.
+/// - ``if``: A template expression. The layer is skipped if it's false. It can use the ``variables``
+///   and the keys of the document composed of the previous layers.
+///
+/// Paths are relative to the directory of the manifest. If the manifest contains ``lookup_paths``,
+/// the references of the composed document are resolved (``resolve_and_merge_references``). Like
+/// there, lookup paths can be dicts with the ``path`` and a ``when`` condition.
+///
+/// :param doc_type: Type of the document.
+/// :param manifest_path: Path to the manifest file.
+/// :param variables: Variables available in the ``if`` expressions.
+///
+/// Keyword arguments are loading options, see ``from_yaml``.
+pub(crate) fn load_from_manifest(
+    py: Python,
+    doc_type: &PyType,
+    manifest_path: &str,
+    variables: Option<YcdDict>,
+    kwargs: Option<&PyDict>,
+) -> PyResult<PyYamlConfigDocument> {
+    let options = LoadOptions::from_kwargs(kwargs)?;
+    let _safe_mode = SafeMode::enter(options.safe);
+    let mut manifest = load_yaml_file(manifest_path, &options)?;
+    let base_dir = Path::new(manifest_path)
+        .parent()
+        .unwrap_or_else(|| Path::new(""));
+    let relative = |path: &str| base_dir.join(path).to_string_lossy().into_owned();
+    let layers = match manifest.shift_remove(LAYERS) {
+        Some(List(layers)) => layers
+            .into_iter()
+            .map(|layer| parse_layer(manifest_path, layer))
+            .collect::<PyResult<Vec<Layer>>>()?,
+        _ => return Err(invalid(manifest_path, "Expected a list of layers.")),
+    };
+    let lookup_paths = match manifest.shift_remove(LOOKUP_PATHS) {
+        None => None,
+        Some(List(paths)) => Some(
+            paths
+                .into_iter()
+                .map(|p| parse_lookup_path(manifest_path, p, &relative))
+                .collect::<PyResult<Vec<LookupPathEntry>>>()?,
+        ),
+        Some(_) => return Err(invalid(manifest_path, "Expected a list of lookup paths.")),
+    };
+    if let Some(key) = manifest.keys().next() {
+        return Err(invalid(manifest_path, &format!("Unknown key {}.", key)));
+    }
+
+    let mut doc: Option<PyYamlConfigDocument> = None;
+    for layer in layers {
+        if let Some(condition) = &layer.condition {
+            if !layer_condition(py, doc_type, &doc, manifest_path, condition, &variables)? {
+                continue;
+            }
+        }
+        let path = relative(&layer.path);
+        if !layer.required && !options.virtual_files.is_file(&path) {
+            continue;
+        }
+        let mut layer_options = options.clone();
+        if layer.profile.is_some() {
+            layer_options.profile = layer.profile;
+        }
+        let new_doc = YamlConfigDocument::from_yaml_internal(doc_type, py, path, &layer_options)?;
+        doc = Some(match doc {
+            None => new_doc,
+            Some(d) => {
+                mark_as_override(&mut new_doc.borrow_mut(py).sources);
+                merge_documents(py, new_doc, d, &options)?
+            }
+        });
+    }
+    let doc = doc.ok_or_else(|| invalid(manifest_path, "No layer was loaded."))?;
+    if let Some(lookup_paths) = lookup_paths {
+        doc.0
+            .call_method(py, "resolve_and_merge_references", (lookup_paths,), kwargs)?;
+    }
+    Ok(doc)
+}
+
+fn parse_lookup_path(
+    manifest_path: &str,
+    lookup_path: YcdValueType,
+    relative: impl Fn(&str) -> String,
+) -> PyResult<LookupPathEntry> {
+    match lookup_path {
+        YString(path) => Ok(LookupPathEntry {
+            path: relative(&path),
+            when: None,
+        }),
+        Dict(mut entry) => {
+            let path = match entry.shift_remove("path") {
+                Some(YString(path)) => relative(&path),
+                _ => return Err(invalid(manifest_path, "A lookup path has no path.")),
+            };
+            let when = match entry.shift_remove("when") {
+                None => None,
+                Some(YString(when)) => Some(when),
+                Some(v) => {
+                    return Err(invalid(
+                        manifest_path,
+                        &format!(
+                            "Invalid when of lookup path {}: {}. Expected a string.",
+                            path, v
+                        ),
+                    ))
+                }
+            };
+            if let Some(key) = entry.keys().next() {
+                return Err(invalid(
+                    manifest_path,
+                    &format!("Unknown key {} of lookup path {}.", key, path),
+                ));
+            }
+            Ok(LookupPathEntry { path, when })
+        }
+        _ => Err(invalid(
+            manifest_path,
+            "Lookup paths must be strings or dicts with path and when.",
+        )),
+    }
+}
+
+fn parse_layer(manifest_path: &str, layer: YcdValueType) -> PyResult<Layer> {
+    let mut layer = match layer {
+        YString(path) => {
+            return Ok(Layer {
+                path,
+                profile: None,
+                required: true,
+                condition: None,
+            })
+        }
+        Dict(layer) => layer,
+        _ => return Err(invalid(manifest_path, "Layers must be paths or dicts.")),
+    };
+    let mut string = |key: &str| match layer.shift_remove(key) {
+        None => Ok(None),
+        Some(YString(v)) => Ok(Some(v)),
+        Some(v) => Err(invalid(
+            manifest_path,
+            &format!("Invalid {} of a layer: {}. Expected a string.", key, v),
+        )),
+    };
+    let path = string("path")?.ok_or_else(|| invalid(manifest_path, "A layer has no path."))?;
+    let profile = string("profile")?;
+    let condition = string("if")?;
+    let required = match layer.shift_remove("required") {
+        None => true,
+        Some(Bool(v)) => v,
+        Some(v) => {
+            return Err(invalid(
+                manifest_path,
+                &format!(
+                    "Invalid required of layer {}: {}. Expected a bool.",
+                    path, v
+                ),
+            ))
+        }
+    };
+    if let Some(key) = layer.keys().next() {
+        return Err(invalid(
+            manifest_path,
+            &format!("Unknown key {} of layer {}.", key, path),
+        ));
+    }
+    Ok(Layer {
+        path,
+        profile,
+        required,
+        condition,
+    })
+}
+
+/// Evaluates the condition of a layer, with the variables as globals and the keys of the document
+/// composed so far (an empty document, if there is none yet).
+fn layer_condition(
+    py: Python,
+    doc_type: &PyType,
+    doc: &Option<PyYamlConfigDocument>,
+    manifest_path: &str,
+    condition: &str,
+    variables: &Option<YcdDict>,
+) -> PyResult<bool> {
+    let doc = match doc {
+        Some(d) => d.clone_ref(py),
+        None => YamlConfigDocument::from_dict(doc_type, py, PyDict::new(py).into())?,
+    };
+    let prev_globals = replace(&mut doc.borrow_mut(py).globals, variables.clone());
+    let result = evaluate_condition(py, &doc, manifest_path, condition);
+    doc.borrow_mut(py).globals = prev_globals;
+    result
+}
+
+fn invalid(manifest_path: &str, message: &str) -> PyErr {
+    InvalidDocumentError::new_err(format!("Invalid manifest {}: {}", manifest_path, message))
+}
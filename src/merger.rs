@@ -1,59 +1,99 @@
-use crate::conv::YcdValueType::{Dict, List, YString, Ycd};
+use crate::anchors::copy_value;
+use crate::conv::YcdValueType::{
+    Bool, Decimal, Dict, Float, ForcedString, Int, List, YString, Ycd,
+};
 use crate::conv::{PyYamlConfigDocument, YcdDict, YcdList, YcdValueType};
+use crate::features::{check_strict_types, STRICT_TYPES};
+use crate::graph::collect_subdocuments;
+use crate::minijinja::{is_template, TemplateRenderer};
+use crate::nested::{rewrite, visit_mut};
+use crate::options::{LoadOptions, LockViolation};
+use crate::policy::unescape;
+use crate::progress::ProgressEvent;
+use crate::redaction::matches_path;
+use crate::registry::{get_directive_handler, get_template, has_directive_handlers};
+use crate::stats::Stat;
 use crate::{
-    construct_new_ycd, load_referenced_document, InvalidRemoveError, ReferencedDocumentNotFound,
-    YamlConfigDocument, REF, REMOVE, REMOVE_FROM_LIST_PREFIX,
+    construct_new_ycd, document_exists_in_repos, load_documents_in_repos, load_referenced_document,
+    matching_paths_in_repos, path_in_repo, DeprecatedKeyWarning, InvalidDocumentError,
+    LockViolationError, ReferencedDocumentNotFound, YamlConfigDocument, DEFAULT, DEPRECATED,
+    EXTENDS, IMPORT_ALL, INDEX_PREFIX, INSERT_AFTER_PREFIX, INSERT_BEFORE_PREFIX, LOCK, REF,
+    REF_FIRST, REMOVE, REMOVE_FROM_LIST_PREFIX, VARS,
 };
+use indexmap::map::Entry;
 use pyo3::exceptions;
 pub(crate) use pyo3::prelude::*;
 use pyo3::types::PyType;
-use std::collections::hash_map::Entry;
+use pyo3::AsPyPointer;
 use std::iter::Peekable;
-use std::mem::take;
-use std::str::Split;
+use std::mem::{replace, take};
 
 #[derive(FromPyObject)]
 pub(crate) struct SubdocSpec(String, Py<PyType>); // path spec, type
 
+/// The container of multiple sub-documents at the end of a path spec.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SubdocContainer {
+    /// ``[]``: A list or a dict.
+    Any,
+    /// ``[list]``: Must be a list.
+    List,
+    /// ``[map]``: Must be a dict.
+    Map,
+}
+
 impl SubdocSpec {
+    /// Replaces all values matching the spec with the return value of cb, which is called with
+    /// the path of the value (parts separated by "/"), its key if the value is an entry of a dict
+    /// and the value.
     pub(crate) fn replace_at<C>(&self, from: &mut YcdDict, cb: C, py: Python) -> PyResult<()>
     where
-        C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
+        C: Fn(&str, Option<&str>, &mut YcdValueType) -> PyResult<YcdValueType>,
     {
-        let multiple = self.0.ends_with("[]");
-        let path: Split<char>;
-        let s;
-        if multiple {
-            s = self.0.chars().take(self.0.len() - 2).collect::<String>();
-            path = s.split('/');
+        let (path, container) = if let Some(p) = self.0.strip_suffix("[]") {
+            (p, Some(SubdocContainer::Any))
+        } else if let Some(p) = self.0.strip_suffix("[list]") {
+            (p, Some(SubdocContainer::List))
+        } else if let Some(p) = self.0.strip_suffix("[map]") {
+            (p, Some(SubdocContainer::Map))
         } else {
-            path = self.0.split('/');
-        }
-        Self::replace_at_impl(path.peekable(), from, cb, multiple, py)?;
+            (self.0.as_str(), None)
+        };
+        Self::replace_at_impl(path.split('/').peekable(), from, cb, container, py)?;
         Ok(())
     }
     fn replace_at_impl<'s, C, P>(
         mut path: Peekable<P>,
         mut from: &mut YcdDict,
         cb: C,
-        multiple: bool,
+        container: Option<SubdocContainer>,
         py: Python,
     ) -> PyResult<()>
     where
-        C: Fn(&mut YcdValueType) -> PyResult<YcdValueType>,
+        C: Fn(&str, Option<&str>, &mut YcdValueType) -> PyResult<YcdValueType>,
         P: Iterator<Item = &'s str>,
     {
         let mut run_at_least_once = false;
+        let mut walked: Vec<&str> = vec![];
         while let Some(k) = path.next() {
             run_at_least_once = true;
+            // "?" after a key: The dict may be missing.
+            let (k, optional) = match k.strip_suffix('?') {
+                Some(k) => (k, true),
+                None => (k, false),
+            };
+            walked.push(k);
+            let value_path = walked.join("/");
             match path.peek() {
                 None => match from.entry(k.to_string()) {
                     Entry::Occupied(mut oe) => {
-                        if multiple {
+                        if let Some(container) = container {
                             match oe.get_mut() {
+                                Dict(_) if container == SubdocContainer::List => return Err(exceptions::PyValueError::new_err(format!("Invalid path in subdocument patterns: Expected a list at {:?}, got a dict.", oe.key()))),
+                                List(_) if container == SubdocContainer::Map => return Err(exceptions::PyValueError::new_err(format!("Invalid path in subdocument patterns: Expected a dict at {:?}, got a list.", oe.key()))),
                                 Dict(dobj) => *dobj = dobj
                                         .iter_mut()
-                                        .map(|(k,v)| match cb(v) {
+                                        .map(|(k,v)| match cb(&format!("{}/{}", value_path, k), Some(k), v) {
                                             Ok(nv) => Ok((k.clone(), {
                                                 match nv {
                                                     Ycd(nvycd) => {
@@ -69,7 +109,8 @@ impl SubdocSpec {
                                         .collect::<PyResult<YcdDict>>()?,
                                 List(lobj) => *lobj = lobj
                                         .iter_mut()
-                                        .map(&cb)
+                                        .enumerate()
+                                        .map(|(i, v)| cb(&format!("{}/{}", value_path, i), None, v))
                                         .collect::<PyResult<YcdList>>()?,
                                 YString(s) => if s != REMOVE {
                                     return Err(exceptions::PyValueError::new_err(format!("Invalid path in subdocument patterns: Invalid reference: {:?}.", oe)))
@@ -78,12 +119,14 @@ impl SubdocSpec {
                             }
                         } else {
                             let w = oe.get_mut();
-                            *w = cb(w)?
+                            *w = cb(&value_path, None, w)?
                         }
                     }
                     Entry::Vacant(_ve) => return Ok(())
                 }
                 Some(_) => match from.get_mut(k) {
+                    // Like a missing sub-document.
+                    None if optional => return Ok(()),
                     None => return Err(exceptions::PyValueError::new_err(
                         format!("Invalid path in subdocument patterns: Not found (expected a dict at {:?}, got nothing).", k)
                     )),
@@ -115,45 +158,160 @@ pub(crate) fn test_subdoc_specs(
     replace_with: YcdValueType,
 ) -> PyResult<(YcdDict, Py<PyType>)> {
     let spec = SubdocSpec(path, typ);
-    spec.replace_at(&mut input, |_| Ok(replace_with.clone()), py)?;
+    spec.replace_at(&mut input, |_, _, _| Ok(replace_with.clone()), py)?;
     Ok((input, spec.1))
 }
 
-/// Removes the $remove:: marker from all lists in doc.
+/// Replaces all user-defined directives (see ``register_directive``) in the document with the
+/// return values of their handlers. Sub-documents are not processed, they handle their own directives.
+pub(crate) fn apply_custom_directives(py: Python, pydoc: &PyYamlConfigDocument) -> PyResult<()> {
+    if !has_directive_handlers(py)? {
+        return Ok(());
+    }
+    // Handlers see the document as it was before any directive was replaced.
+    let mut doc = Dict(pydoc.borrow(py).doc.clone());
+    apply_custom_directives_recursion(py, pydoc, &mut doc, "")?;
+    pydoc.borrow_mut(py).set_doc(doc.unwrap_dict());
+    Ok(())
+}
+
+fn apply_custom_directives_recursion(
+    py: Python,
+    pydoc: &PyYamlConfigDocument,
+    node: &mut YcdValueType,
+    path: &str,
+) -> PyResult<()> {
+    let join = |key: &str| {
+        if path.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}/{}", path, key)
+        }
+    };
+    match node {
+        Dict(v) => {
+            for (k, vv) in v.iter_mut() {
+                apply_custom_directives_recursion(py, pydoc, vv, &join(k))?;
+            }
+            if v.len() == 1 {
+                let (name, value) = v.iter().next().unwrap();
+                if let Some(handler) = get_directive_handler(py, name)? {
+                    let args = (value.to_object(py), path, pydoc.to_object(py));
+                    *node = handler.call1(py, args)?.extract(py)?;
+                }
+            }
+            Ok(())
+        }
+        List(v) => {
+            for (i, vv) in v.iter_mut().enumerate() {
+                apply_custom_directives_recursion(py, pydoc, vv, &join(&i.to_string()))?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Applies all $default entries in doc: The keys of a $default mapping are only set in the dict
+/// containing it if they are not set already (by any document). Nested dicts are filled recursively.
+/// Sub-documents are not processed, they apply their own defaults.
+pub(crate) fn apply_defaults(doc: &mut YcdValueType) -> PyResult<()> {
+    visit_mut(doc, &mut vec![], |value, _| match value {
+        Dict(v) => match v.shift_remove(DEFAULT) {
+            None => Ok(()),
+            Some(Dict(defaults)) => {
+                fill_absent(v, defaults);
+                Ok(())
+            }
+            Some(value) => Err(InvalidDocumentError::new_err(format!(
+                "{} must be a dict, got: {}",
+                DEFAULT, value
+            ))),
+        },
+        _ => Ok(()),
+    })
+}
+
+fn fill_absent(target: &mut YcdDict, defaults: YcdDict) {
+    for (k, default) in defaults {
+        match (target.get_mut(&k), default) {
+            (None, default) => {
+                target.insert(k, default);
+            }
+            (Some(Dict(existing)), Dict(default)) => fill_absent(existing, default),
+            _ => {}
+        }
+    }
+}
+
+/// Removes the metadata keys from all dicts in doc (but not in sub-documents, they have
+/// their own metadata keys). Fails if a metadata value is not a string.
+pub(crate) fn delete_metadata(doc: &mut YcdValueType, metadata_keys: &[String]) -> PyResult<()> {
+    visit_mut(doc, &mut vec![], |value, _| {
+        if let Dict(v) = value {
+            for key in metadata_keys {
+                match v.shift_remove(key) {
+                    None | Some(YString(_)) => {}
+                    Some(value) => {
+                        return Err(InvalidDocumentError::new_err(format!(
+                            "The value of {} must be a string, got: {}",
+                            key, value
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(())
+    })
+}
+
+/// Removes the $remove:: marker from all lists in doc, as well as $deprecated and $lock markers.
+/// Keys and values escaped by a DirectivePolicy are unescaped.
 pub(crate) fn delete_remove_markers(py: Python, doc: YcdValueType) -> PyResult<YcdValueType> {
-    match doc {
+    rewrite(doc, |value| remove_markers(py, value))
+}
+
+/// Removes the markers of value itself (see delete_remove_markers). Returns whether the values
+/// in it must be processed as well.
+fn remove_markers(py: Python, value: YcdValueType) -> PyResult<(YcdValueType, bool)> {
+    match value {
         Ycd(v) => {
-            let vrc = v.clone_ref(py);
-            let mut vmut = vrc.borrow_mut(py);
-            let doc = take(&mut vmut.doc);
+            let doc = take(&mut v.borrow_mut(py).doc);
             match delete_remove_markers(py, Dict(doc))? {
                 Dict(ndoc) => {
-                    vmut.doc = ndoc;
-                    Ok(Ycd(v))
+                    v.borrow_mut(py).doc = ndoc;
+                    Ok((Ycd(v), false))
                 }
                 _ => Err(exceptions::PyRuntimeError::new_err(
                     "Logic error while trying to remove delete markers.",
                 )),
             }
         }
-        Dict(v) => {
-            match v
-                .into_iter()
-                .filter(|(_k, v)| match v {
-                    YString(vs) => vs != REMOVE,
-                    _ => true,
-                })
-                .map(|(k, v)| match delete_remove_markers(py, v) {
-                    Ok(nv) => Ok((k, nv)),
-                    Err(e) => Err(e),
-                })
-                .collect::<PyResult<YcdDict>>()
-            {
-                Ok(vf) => Ok(Dict(vf)),
-                Err(e) => Err(e),
-            }
-        }
+        Dict(v) => Ok((
+            Dict(
+                v.into_iter()
+                    .filter(|(k, v)| match v {
+                        _ if k == DEPRECATED || k == LOCK => false,
+                        YString(vs) => vs != REMOVE,
+                        _ => true,
+                    })
+                    .map(|(k, v)| (unescape(k), v))
+                    .collect(),
+            ),
+            true,
+        )),
         List(v) => {
+            let directive = v.iter().find_map(|e| {
+                insert_directive(e)
+                    .map(|(key, _)| key)
+                    .or_else(|| index_directive(e))
+            });
+            if let Some(key) = directive {
+                return Err(InvalidDocumentError::new_err(format!(
+                    "{} can only be used in a list merged over a list containing the entry.",
+                    key
+                )));
+            }
             let mut removes: Vec<String> = Vec::with_capacity(v.len());
             for vy in v.iter() {
                 if let YString(vs) = vy {
@@ -162,114 +320,594 @@ pub(crate) fn delete_remove_markers(py: Python, doc: YcdValueType) -> PyResult<Y
                     }
                 }
             }
-            Ok(List(
-                v.into_iter()
-                    .filter(|v| match v {
-                        // Remove all $remove:: entries
-                        YString(vs) => {
-                            !vs.starts_with(REMOVE_FROM_LIST_PREFIX) && !removes.contains(vs)
-                        }
-                        _ => true,
-                    })
-                    .collect::<Vec<YcdValueType>>(),
+            // Strings in the list are unescaped like all other strings.
+            Ok((
+                List(
+                    v.into_iter()
+                        .filter(|v| match v {
+                            // Remove all $remove:: entries
+                            YString(vs) => {
+                                !vs.starts_with(REMOVE_FROM_LIST_PREFIX) && !removes.contains(vs)
+                            }
+                            _ => true,
+                        })
+                        .collect(),
+                ),
+                true,
             ))
         }
-        YString(v) => {
-            if v == REMOVE {
-                debug_assert!(false, "Tried to remove a node at an unexpected position");
-                Err(InvalidRemoveError::new_err(
-                    "Tried to remove a node at an unexpected position",
-                ))
-            } else {
-                Ok(YString(v))
+        // $remove values were removed with their dict entries.
+        YString(v) => Ok((YString(unescape(v)), false)),
+        v => Ok((v, false)),
+    }
+}
+
+/// Checks that overriding doesn't change any keys locked in base via $lock.
+/// ``$lock: true`` locks the entire dict, ``$lock: [keys...]`` locks the listed keys.
+/// Depending on the options, changes of locked keys are removed from overriding or raise a
+/// LockViolationError.
+fn check_locks(base: &YcdDict, overriding: &mut YcdDict, options: &LoadOptions) -> PyResult<()> {
+    let lock = base.get(LOCK);
+    let mut locked: Vec<String> = match lock {
+        None | Some(Bool(false)) => return Ok(()),
+        Some(Bool(true)) => overriding.keys().filter(|k| *k != LOCK).cloned().collect(),
+        Some(List(keys)) => keys
+            .iter()
+            .filter_map(|k| match k {
+                YString(k) if k != LOCK && overriding.contains_key(k) => Some(k.clone()),
+                _ => None,
+            })
+            .collect(),
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "{} must be true, false or a list of keys, got: {}",
+                LOCK, v
+            )))
+        }
+    };
+    if let Some(new_lock) = overriding.get(LOCK) {
+        // Locking more keys is allowed, unlocking is not.
+        let allowed = matches!((lock, new_lock), (Some(List(_)), List(_)))
+            || lock.map_or(false, |lock| same_value(lock, new_lock));
+        if !allowed {
+            locked.push(LOCK.to_string());
+        }
+    }
+    for key in locked {
+        let unchanged = match (base.get(&key), overriding.get(&key)) {
+            (Some(b), Some(o)) => key != LOCK && same_value(b, o),
+            _ => false,
+        };
+        if unchanged {
+            continue;
+        }
+        match options.lock_violation {
+            LockViolation::Error => {
+                return Err(LockViolationError::new_err(format!(
+                    "The key {} is locked and can not be changed.",
+                    key
+                )))
+            }
+            LockViolation::Ignore => {
+                overriding.shift_remove(&key);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether two values are equal. Sub-documents are only equal if they are the same object.
+pub(crate) fn same_value(a: &YcdValueType, b: &YcdValueType) -> bool {
+    match (a, b) {
+        (Ycd(a), Ycd(b)) => a.0.as_ptr() == b.0.as_ptr(),
+        (Dict(a), Dict(b)) => {
+            a.len() == b.len()
+                && a.iter()
+                    .all(|(k, v)| b.get(k).map_or(false, |bv| same_value(v, bv)))
+        }
+        (List(a), List(b)) => {
+            a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| same_value(a, b))
+        }
+        (YString(a) | ForcedString(a), YString(b) | ForcedString(b)) => a == b,
+        (Bool(a), Bool(b)) => a == b,
+        (Int(a), Int(b)) => a == b,
+        (Float(a), Float(b)) => a == b,
+        (Decimal(a), Decimal(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// How the values at a path are merged (see ``YamlConfigDocument.merge_strategies``).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum MergeStrategy {
+    /// Entries of overriding lists are appended (the default for lists).
+    Append,
+    /// Entries of overriding lists are appended, if the list doesn't contain them yet.
+    Set,
+    /// Dict entries of overriding lists are merged over the entries of the base with the same value
+    /// of the key. Other entries are appended.
+    Keyed(String),
+    /// Overriding values replace the value of the base, even if both are dicts or lists.
+    Replace,
+}
+
+/// Path patterns (see redaction::matches_path) and their merge strategies.
+pub(crate) type MergeStrategies = Vec<(String, MergeStrategy)>;
+
+impl MergeStrategy {
+    fn parse(strategy: &str) -> PyResult<Self> {
+        match strategy {
+            "append" => Ok(Self::Append),
+            "set" => Ok(Self::Set),
+            "replace" => Ok(Self::Replace),
+            s => match s.strip_prefix("keyed:") {
+                Some(key) if !key.is_empty() => Ok(Self::Keyed(key.to_string())),
+                _ => Err(exceptions::PyValueError::new_err(format!(
+                    "Invalid merge strategy: {}. Expected 'append', 'set', 'keyed:<key>' or 'replace'.",
+                    strategy
+                ))),
+            },
+        }
+    }
+
+    /// The strategy of the first pattern matching path, Append if there is none.
+    fn at<'a>(strategies: &'a [(String, MergeStrategy)], path: &str) -> &'a MergeStrategy {
+        strategies
+            .iter()
+            .find(|(pattern, _)| matches_path(path, pattern))
+            .map_or(&Self::Append, |(_, strategy)| strategy)
+    }
+}
+
+/// The merge strategies of the type of the document.
+fn merge_strategies(py: Python, doc: &PyYamlConfigDocument) -> PyResult<MergeStrategies> {
+    let strategies: Vec<(String, String)> = doc
+        .getattr(py, "merge_strategies")?
+        .call0(py)?
+        .call_method0(py, "items")?
+        .extract(py)?;
+    strategies
+        .into_iter()
+        .map(|(path, strategy)| Ok((path, MergeStrategy::parse(&strategy)?)))
+        .collect()
+}
+
+/// Merges the dict overriding over base (see merge_documents).
+pub(crate) fn merge_dicts(
+    py: Python,
+    base: YcdDict,
+    overriding: YcdDict,
+    options: &LoadOptions,
+) -> PyResult<YcdDict> {
+    Ok(merge_nodes(py, Dict(base), Dict(overriding), "", &[], options)?.unwrap_dict())
+}
+
+/// A dict or keyed list, whose entries are merged over the entries of the corresponding dict or
+/// list of the base. Nested values are merged with an explicit stack of frames instead of
+/// recursion (see merge_nodes), so deeply nested documents can't overflow the stack.
+enum MergeFrame {
+    Dict {
+        path: String,
+        base: YcdDict,
+        overriding: indexmap::map::IntoIter<String, YcdValueType>,
+        merged: YcdDict,
+        /// The key of the entry that is merged by the frame above, if any.
+        pending: Option<String>,
+    },
+    /// Also used for lists with $index entries (see index_directive), key is None if the list
+    /// doesn't have the keyed strategy.
+    KeyedList {
+        path: String,
+        key: Option<String>,
+        base: YcdList,
+        overriding: std::vec::IntoIter<YcdValueType>,
+        unmatched: YcdList,
+        removes: Vec<String>,
+        /// Whether the list has the set strategy.
+        unique: bool,
+        /// The index of the entry that is merged by the frame above, if any.
+        pending: Option<usize>,
+    },
+}
+
+/// The result of starting to merge two values.
+enum MergeStep {
+    /// The merged value.
+    Done(YcdValueType),
+    /// Their entries have to be merged.
+    Descend(MergeFrame),
+}
+
+impl MergeFrame {
+    /// Starts merging the next entry, that exists in the base. None if all entries are merged.
+    fn advance(
+        &mut self,
+        py: Python,
+        strategies: &[(String, MergeStrategy)],
+        options: &LoadOptions,
+    ) -> PyResult<Option<MergeStep>> {
+        match self {
+            MergeFrame::Dict {
+                path,
+                base,
+                overriding,
+                merged,
+                pending,
+            } => {
+                for (k, v) in overriding {
+                    match base.get(&k) {
+                        Some(b) => {
+                            let path = join_path(path, &k);
+                            let step = merge_step(py, b.clone(), v, &path, strategies, options)?;
+                            *pending = Some(k);
+                            return Ok(Some(step));
+                        }
+                        None => {
+                            merged.insert(k, v);
+                        }
+                    }
+                }
+                Ok(None)
+            }
+            MergeFrame::KeyedList {
+                path,
+                key,
+                base,
+                overriding,
+                unmatched,
+                pending,
+                ..
+            } => {
+                for entry in overriding {
+                    if let Some(directive) = index_directive(&entry) {
+                        let i = list_index(directive, path, base.len())?;
+                        let directive = directive.to_string();
+                        let value = entry.unwrap_dict().swap_remove(&directive).unwrap();
+                        let path = join_path(path, &i.to_string());
+                        let step =
+                            merge_step(py, base.remove(i), value, &path, strategies, options)?;
+                        *pending = Some(i);
+                        return Ok(Some(step));
+                    }
+                    let index = key.as_ref().and_then(|key| {
+                        entry_key(py, &entry, key).and_then(|k| {
+                            base.iter().position(|e| {
+                                entry_key(py, e, key).map_or(false, |ek| same_value(&ek, &k))
+                            })
+                        })
+                    });
+                    match index {
+                        Some(i) => {
+                            let path = join_path(path, &i.to_string());
+                            let step =
+                                merge_step(py, base.remove(i), entry, &path, strategies, options)?;
+                            *pending = Some(i);
+                            return Ok(Some(step));
+                        }
+                        None => unmatched.push(entry),
+                    }
+                }
+                Ok(None)
             }
         }
-        _ => Ok(doc),
+    }
+
+    /// Stores the merged value of the pending entry.
+    fn complete(&mut self, value: YcdValueType) {
+        match self {
+            MergeFrame::Dict {
+                merged, pending, ..
+            } => {
+                merged.insert(pending.take().unwrap(), value);
+            }
+            MergeFrame::KeyedList { base, pending, .. } => {
+                base.insert(pending.take().unwrap(), value)
+            }
+        }
+    }
+
+    /// The merged dict or list, after all entries were merged.
+    fn finish(self) -> PyResult<YcdValueType> {
+        match self {
+            MergeFrame::Dict {
+                mut base, merged, ..
+            } => {
+                base.extend(merged);
+                Ok(Dict(base))
+            }
+            // The entries of overriding, that don't match an entry of base, are appended like with
+            // insert_list_entries.
+            MergeFrame::KeyedList {
+                base,
+                unmatched,
+                removes,
+                unique,
+                ..
+            } => Ok(finish_list(
+                insert_list_entries(base, unmatched)?,
+                &removes,
+                unique,
+            )),
+        }
     }
 }
 
-/// Recursive merging step of merge_documents
+/// Merging step of merge_documents: Merges source_node over target_node.
 //
 //  :param target_node: Node to MERGE INTO
 //  :param source_node: Node to MERGE FROM
+//  :param path: Path of the nodes (parts separated by ".")
+//  :param strategies: Merge strategies of the document
 //  :return: Merge result
-fn merge_documents_recursion(
+fn merge_nodes(
     py: Python,
     target_node: YcdValueType,
     source_node: YcdValueType,
+    path: &str,
+    strategies: &[(String, MergeStrategy)],
+    options: &LoadOptions,
 ) -> PyResult<YcdValueType> {
-    match &source_node {
-        Ycd(_) => {
-            if let Ycd(t) = target_node {
-                if let Ycd(s) = source_node {
-                    // IS YCD IN SOURCE AND TARGET
-                    return Ok(Ycd(merge_documents(py, s, t.clone_ref(py))?));
-                }
-                panic!(); // This is impossible.
+    let mut stack: Vec<MergeFrame> = vec![];
+    let mut step = merge_step(py, target_node, source_node, path, strategies, options)?;
+    loop {
+        match step {
+            MergeStep::Descend(frame) => stack.push(frame),
+            MergeStep::Done(value) => match stack.last_mut() {
+                None => return Ok(value),
+                Some(frame) => frame.complete(value),
+            },
+        }
+        let frame = stack.last_mut().unwrap();
+        step = match frame.advance(py, strategies, options)? {
+            Some(step) => step,
+            None => MergeStep::Done(stack.pop().unwrap().finish()?),
+        };
+    }
+}
+
+/// Starts merging source_node over target_node at path: Scalars (and values with the replace
+/// strategy) are merged directly, documents by merge_documents and the entries of dicts and keyed
+/// lists by a MergeFrame.
+fn merge_step(
+    py: Python,
+    target_node: YcdValueType,
+    source_node: YcdValueType,
+    path: &str,
+    strategies: &[(String, MergeStrategy)],
+    options: &LoadOptions,
+) -> PyResult<MergeStep> {
+    let strategy = if path.is_empty() {
+        &MergeStrategy::Append
+    } else {
+        MergeStrategy::at(strategies, path)
+    };
+    if *strategy == MergeStrategy::Replace {
+        options.stats.add(Stat::KeysOverridden, 1);
+        return Ok(MergeStep::Done(source_node));
+    }
+    match (target_node, source_node) {
+        // IS YCD IN SOURCE AND TARGET
+        (Ycd(t), Ycd(s)) => Ok(MergeStep::Done(Ycd(merge_documents(py, s, t, options)?))),
+        // IS DICT IN SOURCE AND TARGET
+        (Dict(t), Dict(mut s)) => {
+            check_locks(&t, &mut s, options)?;
+            Ok(MergeStep::Descend(MergeFrame::Dict {
+                path: path.to_string(),
+                base: t,
+                merged: YcdDict::with_capacity(s.len()),
+                overriding: s.into_iter(),
+                pending: None,
+            }))
+        }
+        (List(t), List(s)) => {
+            options.stats.add(Stat::ListEntriesMerged, s.len());
+            let removes = list_removes(&t);
+            let key = match strategy {
+                MergeStrategy::Keyed(key) => Some(key.clone()),
+                _ => None,
+            };
+            let unique = *strategy == MergeStrategy::Set;
+            if key.is_none() && !s.iter().any(|e| index_directive(e).is_some()) {
+                return Ok(MergeStep::Done(finish_list(
+                    insert_list_entries(t, s)?,
+                    &removes,
+                    unique,
+                )));
             }
+            Ok(MergeStep::Descend(MergeFrame::KeyedList {
+                path: path.to_string(),
+                key,
+                base: t,
+                overriding: s.into_iter(),
+                unmatched: vec![],
+                removes,
+                unique,
+                pending: None,
+            }))
         }
-        Dict(_) => {
-            if let Dict(mut t) = target_node {
-                if let Dict(s) = source_node {
-                    // IS DICT IN SOURCE AND TARGET
-                    t.extend(
-                        s.into_iter()
-                            .map(|(k, v)| {
-                                if t.contains_key(&k) {
-                                    match merge_documents_recursion(
-                                        py,
-                                        t.get(&k).unwrap().clone(),
-                                        v,
-                                    ) {
-                                        Ok(ov) => Ok((k, ov)),
-                                        Err(e) => Err(e),
-                                    }
-                                } else {
-                                    Ok((k, v))
-                                }
-                            })
-                            .collect::<PyResult<YcdDict>>()?,
-                    );
-                    return Ok(Dict(t));
-                };
-                panic!(); // This is impossible.
+        // IS SCALAR IN BOTH (or just in SOURCE)
+        (_, source_node) => {
+            match &source_node {
+                YString(s) if s == REMOVE => options.stats.add(Stat::KeysRemoved, 1),
+                _ => options.stats.add(Stat::KeysOverridden, 1),
             }
+            Ok(MergeStep::Done(source_node))
         }
-        List(_) => {
-            if let List(t) = target_node {
-                if let List(s) = source_node {
-                    let removes: Vec<String> = t
-                        .iter()
-                        .filter(|&v| match v {
-                            YString(v) => v.starts_with(REMOVE_FROM_LIST_PREFIX),
-                            _ => false,
-                        })
-                        .map(|v| match v {
-                            YString(v) => v
-                                .splitn(2, REMOVE_FROM_LIST_PREFIX)
-                                .last()
-                                .unwrap()
-                                .to_string(),
-                            _ => panic!(""),
-                        })
-                        .collect();
-                    return Ok(List(
-                        t.into_iter()
-                            .chain(s.into_iter())
-                            .filter(|v| match v {
-                                YString(v) => !removes.contains(v),
-                                _ => true,
-                            })
-                            .collect::<YcdList>(),
-                    ));
-                }
-                panic!(); // This is impossible.
+    }
+}
+
+/// The entries removed by the $remove:: entries of list.
+fn list_removes(list: &[YcdValueType]) -> Vec<String> {
+    list.iter()
+        .filter_map(|v| match v {
+            YString(v) => v.strip_prefix(REMOVE_FROM_LIST_PREFIX).map(str::to_string),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Removes the entries in removes from the merged list and, if unique, all entries that are
+/// equal to an entry before them.
+fn finish_list(merged: YcdList, removes: &[String], unique: bool) -> YcdValueType {
+    let mut merged: YcdList = merged
+        .into_iter()
+        .filter(|v| match v {
+            YString(v) => !removes.contains(v),
+            _ => true,
+        })
+        .collect();
+    if unique {
+        let mut i = 1;
+        while i < merged.len() {
+            if merged[..i].iter().any(|e| same_value(e, &merged[i])) {
+                merged.remove(i);
+            } else {
+                i += 1;
             }
         }
-        _ => {}
     }
-    //     # IS SCALAR IN BOTH (or just in SOURCE)
-    Ok(source_node)
+    List(merged)
+}
+
+/// The value of key in the list entry, if it is a dict or document containing it.
+fn entry_key(py: Python, entry: &YcdValueType, key: &str) -> Option<YcdValueType> {
+    match entry {
+        Dict(d) => d.get(key).cloned(),
+        Ycd(doc) => doc.borrow(py).doc.get(key).cloned(),
+        _ => None,
+    }
+}
+
+fn join_path(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
+
+/// Appends the entries of overriding to base. Entries that are $insert_before or $insert_after
+/// directives (see insert_directive) are instead inserted directly before or after the first string
+/// entry equal to the entry named in the directive.
+fn insert_list_entries(mut base: YcdList, overriding: YcdList) -> PyResult<YcdList> {
+    for entry in overriding {
+        let (key, after) = match insert_directive(&entry) {
+            None => {
+                base.push(entry);
+                continue;
+            }
+            Some((key, after)) => (key.to_string(), after),
+        };
+        let existing = if after {
+            &key[INSERT_AFTER_PREFIX.len()..]
+        } else {
+            &key[INSERT_BEFORE_PREFIX.len()..]
+        };
+        let index = match base
+            .iter()
+            .position(|e| matches!(e, YString(s) if s == existing))
+        {
+            Some(index) if after => index + 1,
+            Some(index) => index,
+            None => {
+                return Err(InvalidDocumentError::new_err(format!(
+                    "The list entry {} of {} doesn't exist.",
+                    existing, key
+                )))
+            }
+        };
+        let values = match entry.unwrap_dict().swap_remove(&key) {
+            Some(List(values)) => values,
+            Some(value) => vec![value],
+            None => unreachable!(),
+        };
+        base.splice(index..index, values);
+    }
+    Ok(base)
+}
+
+/// If value is an $insert_before or $insert_after list entry (a dict with a single key
+/// ``$insert_before::<entry>`` or ``$insert_after::<entry>``), returns that key and whether it is
+/// an $insert_after.
+pub(crate) fn insert_directive(value: &YcdValueType) -> Option<(&str, bool)> {
+    match value {
+        Dict(d) if d.len() == 1 => {
+            let key = d.keys().next().unwrap().as_str();
+            if key.starts_with(INSERT_AFTER_PREFIX) {
+                Some((key, true))
+            } else if key.starts_with(INSERT_BEFORE_PREFIX) {
+                Some((key, false))
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// If value is an $index list entry (a dict with a single key ``$index::<index>``), returns that key.
+pub(crate) fn index_directive(value: &YcdValueType) -> Option<&str> {
+    match value {
+        Dict(d) if d.len() == 1 => d
+            .keys()
+            .next()
+            .map(String::as_str)
+            .filter(|key| key.starts_with(INDEX_PREFIX)),
+        _ => None,
+    }
+}
+
+/// The index of the $index directive key of the list at path with len entries. Fails if it is not an
+/// index of the list.
+fn list_index(key: &str, path: &str, len: usize) -> PyResult<usize> {
+    match key[INDEX_PREFIX.len()..].parse::<usize>() {
+        Ok(index) if index < len => Ok(index),
+        _ => Err(InvalidDocumentError::new_err(format!(
+            "{} of {} is not an index of the list, it has {} entries.",
+            key, path, len
+        ))),
+    }
+}
+
+/// Emits a DeprecatedKeyWarning for every key that is set in overriding and marked as deprecated
+/// in base. Keys are marked by a ``$deprecated`` mapping (key -> message) in the dict that contains them.
+fn warn_deprecated_keys(
+    py: Python,
+    base: &YcdDict,
+    overriding: &YcdDict,
+    path: &str,
+    file: &str,
+) -> PyResult<()> {
+    if let Some(Dict(deprecated)) = base.get(DEPRECATED) {
+        let mut keys: Vec<&String> = deprecated
+            .keys()
+            .filter(|k| overriding.contains_key(*k))
+            .collect();
+        keys.sort();
+        for key in keys {
+            PyErr::warn(
+                py,
+                py.get_type::<DeprecatedKeyWarning>(),
+                &format!(
+                    "{}: The key {}{} is deprecated: {}",
+                    file, path, key, deprecated[key]
+                ),
+                1,
+            )?;
+        }
+    }
+    for (key, value) in overriding {
+        if let (Dict(overriding_value), Some(Dict(base_value))) = (value, base.get(key)) {
+            warn_deprecated_keys(
+                py,
+                base_value,
+                overriding_value,
+                &format!("{}{}/", path, key),
+                file,
+            )?;
+        }
+    }
+    Ok(())
 }
 
 /// Merges two YamlConfigDocuments.
@@ -280,14 +918,56 @@ pub(crate) fn merge_documents(
     py: Python,
     target: PyYamlConfigDocument,
     source: PyYamlConfigDocument,
+    options: &LoadOptions,
 ) -> PyResult<PyYamlConfigDocument> {
+    options.deadline.check()?;
+    if target.0.as_ptr() == source.0.as_ptr() {
+        // Merging a document over itself doesn't change it.
+        return Ok(target);
+    }
+    let target = if target.borrow(py).shared {
+        // The other places sharing the document must not see the merged values.
+        match copy_value(py, &Ycd(target))? {
+            Ycd(copy) => copy,
+            _ => unreachable!(),
+        }
+    } else {
+        target
+    };
+    let strategies = merge_strategies(py, &target)?;
     let targetrc = target.clone_ref(py);
     let mut target_doc = target.borrow_mut(py);
     let source_doc = source.borrow(py);
-    match merge_documents_recursion(
+    if target_doc.features.iter().any(|f| f == STRICT_TYPES) {
+        check_strict_types(
+            target_doc
+                .absolute_paths
+                .first()
+                .map(String::as_str)
+                .unwrap_or("?"),
+            &source_doc.doc,
+            &target_doc.doc,
+            "",
+        )?;
+    }
+    warn_deprecated_keys(
+        py,
+        &source_doc.doc,
+        &target_doc.doc,
+        "",
+        target_doc
+            .absolute_paths
+            .first()
+            .map(String::as_str)
+            .unwrap_or("?"),
+    )?;
+    match merge_nodes(
         py,
         Dict(source_doc.doc.clone()),
         Dict(take(&mut target_doc.doc)),
+        "",
+        &strategies,
+        options,
     )? {
         Dict(newdoc) => target_doc.doc = newdoc,
         _ => {
@@ -304,6 +984,18 @@ pub(crate) fn merge_documents(
             .iter()
             .cloned(),
     );
+    target_doc
+        .references
+        .extend(source_doc.references.iter().cloned());
+    target_doc.provenance.merge_over(&source_doc.provenance);
+    // The sources of the base come first, since they have a lower priority.
+    let target_sources = take(&mut target_doc.sources);
+    target_doc.sources = source_doc
+        .sources
+        .iter()
+        .cloned()
+        .chain(target_sources)
+        .collect();
     let targets_before = target_doc.absolute_paths.clone();
     target_doc.absolute_paths.extend(
         source_doc
@@ -312,6 +1004,14 @@ pub(crate) fn merge_documents(
             .filter(|&v| !targets_before.contains(v))
             .map(|v| v.to_string()),
     );
+    // The callback may access the documents.
+    let detail = target_doc.absolute_paths.first().cloned();
+    drop(target_doc);
+    drop(source_doc);
+    reparent_subdocuments(py, &targetrc);
+    options
+        .progress
+        .report(py, ProgressEvent::DocumentMerged, detail.as_deref())?;
     Ok(targetrc)
 }
 
@@ -321,11 +1021,19 @@ pub(crate) fn merge_documents(
 ///
 /// :param doc: Document to work on
 /// :param lookup_paths: Paths to the repositories, where referenced should be looked up.
+/// :param options: Options for loading the referenced documents.
 pub(crate) fn resolve_and_merge(
     py: Python,
     pydoc: PyYamlConfigDocument,
     lookup_paths: &[String],
+    options: &LoadOptions,
 ) -> PyResult<PyYamlConfigDocument> {
+    options.deadline.check()?;
+    let pydoc = resolve_extends(py, pydoc, lookup_paths, options)?;
+    let pydoc = resolve_import_all(py, pydoc, lookup_paths, options)?;
+    select_first_ref(py, &pydoc, lookup_paths, options)?;
+    let parameters = take_ref_parameters(py, &pydoc)?;
+    render_ref_target(py, &pydoc, options)?;
     let mut pydocrc = pydoc.clone_ref(py);
     let doc: PyRef<YamlConfigDocument> = pydoc.borrow(py);
     match doc.doc.get(REF) {
@@ -340,10 +1048,12 @@ pub(crate) fn resolve_and_merge(
     drop(doc);
     // Resolve references
     let mut prev_referenced_doc: Option<PyYamlConfigDocument> = None;
-    for mut referenced_doc in load_referenced_document(py, pydocrc.clone_ref(py), lookup_paths)? {
+    for mut referenced_doc in
+        load_referenced_document(py, pydocrc.clone_ref(py), lookup_paths, options)?
+    {
         if let Some(pd) = prev_referenced_doc {
             // Merge referenced docs
-            referenced_doc = merge_documents(py, referenced_doc.clone_ref(py), pd)?;
+            referenced_doc = merge_documents(py, referenced_doc.clone_ref(py), pd, options)?;
         }
         prev_referenced_doc = Some(referenced_doc);
     }
@@ -362,25 +1072,377 @@ pub(crate) fn resolve_and_merge(
             )))
         };
     }
+    options.stats.add(Stat::RefsResolved, 1);
     // Resolve entire referenced docs
     let mut prev_referenced_doc = prev_referenced_doc.unwrap();
-    prev_referenced_doc = resolve_and_merge(py, prev_referenced_doc, lookup_paths)?;
+    prev_referenced_doc = resolve_and_merge(py, prev_referenced_doc, lookup_paths, options)?;
+    if let Some(parameters) = parameters.filter(|p| !p.is_empty()) {
+        inject_ref_parameters(py, &prev_referenced_doc, parameters)?;
+    }
     // Merge content of current doc into referenced doc (and execute $remove's on the way)
-    pydocrc = merge_documents(py, pydocrc, prev_referenced_doc)?;
+    pydocrc = merge_documents(py, pydocrc, prev_referenced_doc, options)?;
     // Remove $ref entry
-    pydocrc.borrow_mut(py).doc.remove(REF);
+    pydocrc.borrow_mut(py).doc.shift_remove(REF);
     Ok(pydocrc)
 }
 
+/// Replaces the $ref_first entry of the document with a $ref to the first candidate that exists
+/// in the repositories.
+///
+/// $ref_first is a list of candidates. A candidate is either a path (like a $ref) or a mapping with the keys
+/// ``path``, ``if`` (optional; a template expression, evaluated in the context of the document, that must
+/// be true for the candidate to be used) and ``with`` (optional; parameters like for $ref).
+/// If no candidate matches, nothing is referenced.
+fn select_first_ref(
+    py: Python,
+    pydoc: &PyYamlConfigDocument,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<()> {
+    let candidates = match pydoc.borrow_mut(py).doc.shift_remove(REF_FIRST) {
+        None => return Ok(()),
+        Some(List(candidates)) => candidates,
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Invalid value for {}: {}. Expected a list of candidates.",
+                REF_FIRST, v
+            )))
+        }
+    };
+    if pydoc.borrow(py).doc.contains_key(REF) {
+        return Err(InvalidDocumentError::new_err(format!(
+            "A document can not contain both {} and {}.",
+            REF, REF_FIRST
+        )));
+    }
+    for candidate in candidates {
+        let (path, condition) = match &candidate {
+            YString(path) => (path.clone(), None),
+            Dict(mapping) => match (mapping.get("path"), mapping.get("if")) {
+                (Some(YString(path)), None) => (path.clone(), None),
+                (Some(YString(path)), Some(YString(condition))) => {
+                    (path.clone(), Some(condition.clone()))
+                }
+                _ => {
+                    return Err(InvalidDocumentError::new_err(format!(
+                        "Invalid candidate in {}. Expected a path (string) and optionally a condition (if, string).",
+                        REF_FIRST
+                    )))
+                }
+            },
+            v => {
+                return Err(InvalidDocumentError::new_err(format!(
+                    "Invalid candidate in {}: {}",
+                    REF_FIRST, v
+                )))
+            }
+        };
+        if let Some(condition) = condition {
+            if !evaluate_condition(py, pydoc, REF_FIRST, &condition)? {
+                continue;
+            }
+        }
+        let ref_path_in_repo = path_in_repo(&pydoc.borrow(py).path, &path);
+        if document_exists_in_repos(py, &ref_path_in_repo, lookup_paths, options)? {
+            let new_ref = match candidate {
+                Dict(mut mapping) if mapping.contains_key("with") => {
+                    mapping.shift_remove("if");
+                    Dict(mapping)
+                }
+                _ => YString(path),
+            };
+            pydoc.borrow_mut(py).doc.insert(REF.to_string(), new_ref);
+            return Ok(());
+        }
+    }
+    Ok(())
+}
+
+/// Evaluates a template expression in the context of the document.
+/// directive is the directive the condition belongs to, for error messages.
+pub(crate) fn evaluate_condition(
+    py: Python,
+    pydoc: &PyYamlConfigDocument,
+    directive: &str,
+    condition: &str,
+) -> PyResult<bool> {
+    let template = format!("{{% if {} %}}true{{% endif %}}", condition);
+    match TemplateRenderer::new(pydoc.clone_ref(py))?.render(py, &template) {
+        Ok(result) => Ok(result.map_or(false, |r| r.output == "true")),
+        Err(e) => Err(InvalidDocumentError::new_err(format!(
+            "Error evaluating the condition {} in {}: {:?}",
+            condition, directive, e
+        ))),
+    }
+}
+
+/// If the $ref of the document contains a template, replaces it with the rendered template.
+/// Templates can use the keys of the document (as they are before merging), its $vars (not rendered),
+/// the globals of the resolution context and ``parent()``.
+fn render_ref_target(
+    py: Python,
+    pydoc: &PyYamlConfigDocument,
+    options: &LoadOptions,
+) -> PyResult<()> {
+    let target = match pydoc.borrow(py).doc.get(REF) {
+        Some(YString(target)) if is_template(target) => target.clone(),
+        _ => return Ok(()),
+    };
+    let (prev_local_vars, prev_globals) = {
+        let mut doc = pydoc.borrow_mut(py);
+        let local_vars = match doc.doc.get(VARS) {
+            Some(Dict(v)) => v.clone(),
+            _ => YcdDict::new(),
+        };
+        (
+            replace(&mut doc.local_vars, local_vars),
+            replace(&mut doc.globals, options.globals.clone()),
+        )
+    };
+    let rendered = TemplateRenderer::new(pydoc.clone_ref(py))?.render(py, &target);
+    let mut doc = pydoc.borrow_mut(py);
+    doc.local_vars = prev_local_vars;
+    doc.globals = prev_globals;
+    match rendered {
+        Ok(Some(rendered)) => {
+            doc.doc.insert(REF.to_string(), YString(rendered.output));
+            Ok(())
+        }
+        Ok(None) => Ok(()),
+        Err(e) => Err(InvalidDocumentError::new_err(format!(
+            "Error rendering the {} {}: {:?}",
+            REF, target, e
+        ))),
+    }
+}
+
+/// If the $ref of the document is written as a mapping (``$ref: {path: ..., with: {...}}``), replaces it
+/// with the path and returns the parameters.
+fn take_ref_parameters(py: Python, pydoc: &PyYamlConfigDocument) -> PyResult<Option<YcdDict>> {
+    let mut doc = pydoc.borrow_mut(py);
+    let mut ref_mapping = match doc.doc.get_mut(REF) {
+        Some(Dict(v)) => take(v),
+        _ => return Ok(None),
+    };
+    let path = match ref_mapping.shift_remove("path") {
+        Some(YString(path)) => path,
+        _ => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "A {} written as a mapping must contain a path (string).",
+                REF
+            )))
+        }
+    };
+    let parameters = match ref_mapping.shift_remove("with") {
+        None => YcdDict::new(),
+        Some(Dict(parameters)) => parameters,
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "The parameters ('with') of {} {} must be a mapping, got: {}",
+                REF, path, v
+            )))
+        }
+    };
+    if let Some(key) = ref_mapping.keys().next() {
+        return Err(InvalidDocumentError::new_err(format!(
+            "Unexpected key in {} {}: {}",
+            REF, path, key
+        )));
+    }
+    doc.doc.insert(REF.to_string(), YString(path));
+    Ok(Some(parameters))
+}
+
+/// Makes the parameters of a $ref available as $vars of the referenced document.
+/// Parameters take priority over $vars defined in the referenced document.
+fn inject_ref_parameters(
+    py: Python,
+    referenced_doc: &PyYamlConfigDocument,
+    parameters: YcdDict,
+) -> PyResult<()> {
+    let mut doc = referenced_doc.borrow_mut(py);
+    match doc
+        .doc
+        .entry(VARS.to_string())
+        .or_insert_with(|| Dict(YcdDict::new()))
+    {
+        Dict(vars) => {
+            vars.extend(parameters);
+            Ok(())
+        }
+        v => Err(InvalidDocumentError::new_err(format!(
+            "{} must be a dict, got: {}",
+            VARS, v
+        ))),
+    }
+}
+
+/// Resolve the $extends entry of a document and merge the document with the named templates
+/// (changes this document in place).
+///
+/// $extends is either the name of a template or a list of names. Later templates in the list are merged
+/// on top of earlier ones. Templates registered via register_template are used first, otherwise
+/// the name is looked up as a path in the repositories (like an absolute $ref).
+fn resolve_extends(
+    py: Python,
+    pydoc: PyYamlConfigDocument,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<PyYamlConfigDocument> {
+    let names: Vec<String> = match pydoc.borrow_mut(py).doc.shift_remove(EXTENDS) {
+        None => return Ok(pydoc),
+        Some(YString(name)) => vec![name],
+        Some(List(names)) => names
+            .into_iter()
+            .map(|name| match name {
+                YString(name) => Ok(name),
+                _ => Err(InvalidDocumentError::new_err(format!(
+                    "Invalid entry in {}: {}. Expected a template name.",
+                    EXTENDS, name
+                ))),
+            })
+            .collect::<PyResult<Vec<String>>>()?,
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Invalid value for {}: {}. Expected a template name or a list of names.",
+                EXTENDS, v
+            )))
+        }
+    };
+    let mut template: Option<PyYamlConfigDocument> = None;
+    for name in names {
+        let new_template = load_template(py, pydoc.clone_ref(py), &name, lookup_paths, options)?;
+        template = Some(match template {
+            None => new_template,
+            Some(t) => merge_documents(py, new_template, t, options)?,
+        });
+    }
+    match template {
+        None => Ok(pydoc),
+        Some(t) => merge_documents(py, pydoc, t, options),
+    }
+}
+
+/// Loads and resolves the template with the given name for document.
+fn load_template(
+    py: Python,
+    document: PyYamlConfigDocument,
+    name: &str,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<PyYamlConfigDocument> {
+    let template = match get_template(py, name, options.context.as_deref())? {
+        Some(body) => {
+            let doc_cls: Py<PyType> = document.getattr(py, "__class__")?.extract(py)?;
+            let doc_ref = document.borrow(py);
+            construct_new_ycd(
+                py,
+                doc_cls.as_ref(py),
+                [
+                    doc_cls.to_object(py),
+                    body.to_object(py),
+                    // Used for detecting circular $extends.
+                    format!("{}:{}", EXTENDS, name).into_py(py),
+                    document.to_object(py),
+                    doc_ref.already_loaded_docs.to_object(py),
+                    doc_ref.absolute_paths.to_object(py),
+                ],
+            )?
+        }
+        None => {
+            let ref_path_in_repo = format!("/{}", name.trim_start_matches('/'));
+            let mut template: Option<PyYamlConfigDocument> = None;
+            for t in load_documents_in_repos(
+                py,
+                document.clone_ref(py),
+                &ref_path_in_repo,
+                lookup_paths,
+                options,
+            )? {
+                template = Some(match template {
+                    None => t,
+                    Some(prev) => merge_documents(py, t, prev, options)?,
+                });
+            }
+            match template {
+                None => {
+                    return Err(ReferencedDocumentNotFound::new_err(format!(
+                        "Template {} not found. It is neither registered nor in the lookup paths.",
+                        name
+                    )))
+                }
+                Some(t) => t,
+            }
+        }
+    };
+    resolve_and_merge(py, template, lookup_paths, options)
+}
+
+/// Resolve the $import_all entry of a document and merge the document with all documents
+/// matching it (changes this document in place).
+///
+/// $import_all is a pattern (or a list of patterns) for paths of documents in the repositories, like
+/// ``conf.d/*``. Relative patterns are resolved like relative $refs. All matching documents in all
+/// repositories are merged, ordered by their path and then by the order of the lookup paths.
+/// Later documents take priority. The document itself takes priority over all imported documents.
+fn resolve_import_all(
+    py: Python,
+    pydoc: PyYamlConfigDocument,
+    lookup_paths: &[String],
+    options: &LoadOptions,
+) -> PyResult<PyYamlConfigDocument> {
+    let patterns: Vec<String> = match pydoc.borrow_mut(py).doc.shift_remove(IMPORT_ALL) {
+        None => return Ok(pydoc),
+        Some(YString(pattern)) => vec![pattern],
+        Some(List(patterns)) => patterns
+            .into_iter()
+            .map(|pattern| match pattern {
+                YString(pattern) => Ok(pattern),
+                _ => Err(InvalidDocumentError::new_err(format!(
+                    "Invalid entry in {}: {}. Expected a path pattern.",
+                    IMPORT_ALL, pattern
+                ))),
+            })
+            .collect::<PyResult<Vec<String>>>()?,
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Invalid value for {}: {}. Expected a path pattern or a list of patterns.",
+                IMPORT_ALL, v
+            )))
+        }
+    };
+    let mut imported: Option<PyYamlConfigDocument> = None;
+    for pattern in patterns {
+        let pattern_in_repo = path_in_repo(&pydoc.borrow(py).path, &pattern);
+        for path in matching_paths_in_repos(py, &pattern_in_repo, lookup_paths, options)? {
+            for doc in
+                load_documents_in_repos(py, pydoc.clone_ref(py), &path, lookup_paths, options)?
+            {
+                let doc = resolve_and_merge(py, doc, lookup_paths, options)?;
+                imported = Some(match imported {
+                    None => doc,
+                    Some(prev) => merge_documents(py, doc, prev, options)?,
+                });
+            }
+        }
+    }
+    match imported {
+        None => Ok(pydoc),
+        Some(imported) => merge_documents(py, pydoc, imported, options),
+    }
+}
+
 /// Load a subdocument of a specific type. This will convert the dict at this position
 /// into a YamlConfigDocument with the matching type and perform resolve_and_merge_references
 /// on it.
 pub(crate) fn load_subdocument(
     py: Python,
     doc: &mut YcdValueType,
+    path_in_parent: &str,
     args: [PyObject; 4],
     doc_clss: Py<PyType>,
     lookup_paths: &[String],
+    options: &LoadOptions,
 ) -> PyResult<YcdValueType> {
     let ycd: PyYamlConfigDocument;
     match doc {
@@ -396,12 +1458,53 @@ pub(crate) fn load_subdocument(
         },
         _ => return Err(exceptions::PyValueError::new_err(format!("Invalid path in subdocument: Invalid reference where a dict or document was expected: {:?}.", doc)))
     }
-    Ok(Ycd(YamlConfigDocument::resolve_and_merge_references(
+    let parent: Py<YamlConfigDocument> = args[1].extract(py)?;
+    set_parent(py, &ycd, &parent, path_in_parent);
+    let resolved: PyYamlConfigDocument = YamlConfigDocument::resolve_and_merge_references_internal(
         ycd.into(),
         py,
         lookup_paths.to_vec(),
-    )?
-    .into()))
+        options,
+    )
+    .map_err(|e| in_subdocument(py, e, path_in_parent))?
+    .into();
+    set_parent(py, &resolved, &parent, path_in_parent);
+    Ok(Ycd(resolved))
+}
+
+fn set_parent(
+    py: Python,
+    doc: &PyYamlConfigDocument,
+    parent: &Py<YamlConfigDocument>,
+    path_in_parent: &str,
+) {
+    let mut doc_mut = doc.borrow_mut(py);
+    doc_mut.parent_doc = Some(parent.clone_ref(py));
+    doc_mut.path_in_parent = Some(path_in_parent.to_string());
+}
+
+/// Sets the parent and the path in the parent of all direct sub-documents of doc
+/// (e.g. after sub-documents were moved into it by merging).
+pub(crate) fn reparent_subdocuments(py: Python, doc: &PyYamlConfigDocument) {
+    let mut subdocs = vec![];
+    for (key, value) in &doc.borrow(py).doc {
+        collect_subdocuments(py, value, key, &mut subdocs);
+    }
+    let parent: Py<YamlConfigDocument> = doc.clone_ref(py).into();
+    for (path, subdoc) in subdocs {
+        set_parent(py, &subdoc, &parent, &path);
+    }
+}
+
+/// Prefixes the message of an error raised while loading the sub-document at path. The type and
+/// attributes of the exception are kept.
+fn in_subdocument(py: Python, err: PyErr, path: &str) -> PyErr {
+    let value = err.pvalue(py);
+    if let Ok((msg,)) = value.getattr("args").and_then(|a| a.extract::<(String,)>()) {
+        let msg = format!("In the sub-document at {}: {}", path, msg);
+        value.setattr("args", (msg,)).ok();
+    }
+    err
 }
 
 /// Loads all subdocuments for doc, according to the specification.
@@ -411,10 +1514,12 @@ pub(crate) fn load_subdocuments(
     doc: PyYamlConfigDocument,
     specs: Vec<SubdocSpec>,
     lookup_paths: &[String],
+    options: &LoadOptions,
 ) -> PyResult<()> {
     let mut doc_borrow = doc.borrow_mut(py);
+    let path = doc_borrow.path.clone();
     let args = [
-        doc_borrow.path.clone().into_py(py),
+        path.clone().into_py(py),
         doc.to_object(py),
         doc_borrow.already_loaded_docs.clone().into_py(py),
         doc_borrow.absolute_paths.clone().into_py(py),
@@ -422,36 +1527,43 @@ pub(crate) fn load_subdocuments(
     for spec in specs {
         spec.replace_at(
             &mut doc_borrow.doc,
-            |target| load_subdocument(py, target, args.clone(), spec.1.clone_ref(py), lookup_paths),
+            |path_in_parent, name, target| {
+                let identity_key = match target {
+                    Dict(d) => options.identity_map.key(spec.1.as_ref(py), &path, name, d),
+                    _ => None,
+                };
+                if let Some(key) = &identity_key {
+                    if let Some(shared) = options.identity_map.get(py, key) {
+                        options.stats.add(Stat::CacheHits, 1);
+                        // Keeps the parent of the place it was loaded at first.
+                        return Ok(Ycd(shared));
+                    }
+                }
+                let loaded = load_subdocument(
+                    py,
+                    target,
+                    path_in_parent,
+                    args.clone(),
+                    spec.1.clone_ref(py),
+                    lookup_paths,
+                    options,
+                )?;
+                if let (Some(key), Ycd(loaded)) = (identity_key, &loaded) {
+                    loaded.borrow_mut(py).shared = true;
+                    options.identity_map.insert(key, loaded.clone_ref(py));
+                }
+                Ok(loaded)
+            },
             py,
         )?;
     }
     Ok(())
 }
 
-/// Recursively removes all YamlConfigDocuments and replaces them by their doc dictionary.
+/// Removes all YamlConfigDocuments and replaces them by their doc dictionary.
 pub(crate) fn recursive_docs_to_dicts(input: YcdValueType, py: Python) -> PyResult<YcdValueType> {
-    match input {
-        Ycd(v) => recursive_docs_to_dicts(Dict(v.borrow(py).doc.clone()), py),
-        Dict(v) => match v
-            .into_iter()
-            .map(|(k, v)| match recursive_docs_to_dicts(v, py) {
-                Ok(vv) => Ok((k, vv)),
-                Err(e) => Err(e),
-            })
-            .collect::<PyResult<YcdDict>>()
-        {
-            Ok(v) => Ok(Dict(v)),
-            Err(e) => Err(e),
-        },
-        List(v) => match v
-            .into_iter()
-            .map(|v| recursive_docs_to_dicts(v, py))
-            .collect::<PyResult<Vec<YcdValueType>>>()
-        {
-            Ok(v) => Ok(List(v)),
-            Err(e) => Err(e),
-        },
-        _ => Ok(input),
-    }
+    rewrite(input, |value| match value {
+        Ycd(v) => Ok((Dict(v.borrow(py).doc.clone()), true)),
+        value => Ok((value, true)),
+    })
 }
@@ -1,12 +1,19 @@
-use crate::conv::{PyYamlConfigDocument, SimpleYcdValueType, YHashMap, YcdValueType};
-use crate::{YamlConfigDocument, FORCE_STRING};
+use crate::conv::{
+    decimal_to_f64, PyYamlConfigDocument, SimpleYcdValueType, YHashMap, YcdValueType,
+};
+use crate::write_back::{find_source, has_value};
+use crate::{YamlConfigDocument, VALUES, VARS};
 use minijinja::value::{Object, Primitive, Value};
 use minijinja::{Environment, Error, ErrorKind, State};
+use pyo3::exceptions::PyTypeError;
 use pyo3::types::PyTuple;
-use pyo3::{PyObject, PyResult, Python, ToPyObject};
+use pyo3::{IntoPy, PyAny, PyObject, PyResult, Python, ToPyObject};
 use serde::{Serialize, Serializer};
+use std::cell::Cell;
 use std::collections::HashMap;
 use std::fmt::{Display, Formatter};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 // https://github.com/rust-lang/rust/issues/70263
 macro_rules! typed_closure {
@@ -16,32 +23,117 @@ macro_rules! typed_closure {
     } }
 }
 
+/// Set by the ``helper`` decorator: The name of the helper in templates.
+const HELPER_NAME_ATTR: &str = "__helper_name";
+/// Set by the ``helper`` decorator: Whether the document is passed to the helper as first argument.
+const HELPER_PASS_DOCUMENT_ATTR: &str = "__helper_pass_document";
+
 type FuncFunc = dyn Fn(&State, Vec<Value>) -> Result<Value, Error> + Sync + Send + 'static;
 
+thread_local! {
+    /// Whether templates are rendered in safe mode on this thread (see SafeMode).
+    static SAFE_MODE: Cell<bool> = Cell::new(false);
+}
+
+/// Enables safe mode on the current thread until it is dropped. In safe mode, templates can only use
+/// the built-in filters and functions (and ``parent()`` and ``path_in_parent()``, which are then
+/// implemented without calling into Python). Calling any other variable helper is an error.
+/// Safe mode stays enabled until the outermost guard that enabled it is dropped.
+pub(crate) struct SafeMode(bool);
+
+impl SafeMode {
+    pub(crate) fn enter(safe: bool) -> Self {
+        Self(SAFE_MODE.with(|mode| mode.replace(mode.get() || safe)))
+    }
+
+    pub(crate) fn is_active() -> bool {
+        SAFE_MODE.with(Cell::get)
+    }
+}
+
+impl Drop for SafeMode {
+    fn drop(&mut self) {
+        SAFE_MODE.with(|mode| mode.set(self.0));
+    }
+}
+
 pub(crate) struct TemplateRenderer<'env> {
     env: Environment<'env>,
     document: PyYamlConfigDocument,
-    globals: HashMap<String, Box<FuncFunc>>,
+    /// Additional helpers (e.g. those passed to ``process_vars_for``), by name.
+    helpers: HashMap<String, PyObject>,
+    /// Path of the rendered value in the document.
+    item_path: Vec<ItemPathPart>,
+    /// Set by the ``str`` filter.
+    forced_string: Arc<AtomicBool>,
+    /// The names looked up by the template, if they are recorded (see record_names).
+    used_names: Option<UsedNames>,
+}
+
+/// A name a template looked up in its context (a variable or a helper).
+#[derive(Clone, Debug)]
+pub(crate) struct UsedName {
+    pub(crate) name: String,
+    /// The value of variables. None for helpers and the ``item_*`` functions.
+    pub(crate) value: Option<YcdValueType>,
+}
+
+type UsedNames = Arc<Mutex<Vec<UsedName>>>;
+
+/// The result of rendering a template.
+pub(crate) struct Rendered {
+    pub(crate) output: String,
+    /// Whether the ``str`` filter was used, so the output must not be converted into another type.
+    pub(crate) forced_string: bool,
+}
+
+/// A part of the path of a value in a document. Paths in $vars start with the key $vars.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum ItemPathPart {
+    Key(String),
+    Index(usize),
 }
 
 impl<'env> TemplateRenderer<'env> {
     const STR_FILTER: &'static str = "str";
     const SUBSTR_START_FILTER: &'static str = "substr_start";
     const STARTSWITH_FILTER: &'static str = "startswith";
+    const FORMAT_NUMBER_FILTER: &'static str = "format_number";
+    const FORMAT_BYTES_FILTER: &'static str = "format_bytes";
+    const FORMAT_DURATION_FILTER: &'static str = "format_duration";
     const TPL_NAME: &'static str = "tpl";
 
     pub(crate) fn new(document: PyYamlConfigDocument) -> PyResult<Self> {
         let mut slf = Self {
             env: Environment::new(),
             document,
-            globals: HashMap::new(),
+            helpers: HashMap::new(),
+            item_path: vec![],
+            forced_string: Arc::new(AtomicBool::new(false)),
+            used_names: None,
         };
 
-        slf.env.add_filter(Self::STR_FILTER, str_filter);
+        let forced_string = slf.forced_string.clone();
+        slf.env.add_filter(
+            Self::STR_FILTER,
+            typed_closure!(
+                (Fn(&State, Value) -> Result<String, Error> + Sync + Send + 'static),
+                move |_state: &State, value: Value| -> Result<String, Error> {
+                    forced_string.store(true, Ordering::Relaxed);
+                    Ok(value.to_string())
+                }
+            ),
+        );
         slf.env
             .add_filter(Self::STARTSWITH_FILTER, startswith_filter);
         slf.env
             .add_filter(Self::SUBSTR_START_FILTER, substr_start_filter);
+        slf.env
+            .add_filter(Self::FORMAT_NUMBER_FILTER, format_number_filter);
+        slf.env
+            .add_filter(Self::FORMAT_BYTES_FILTER, format_bytes_filter);
+        slf.env
+            .add_filter(Self::FORMAT_DURATION_FILTER, format_duration_filter);
 
         Ok(slf)
     }
@@ -50,40 +142,65 @@ impl<'env> TemplateRenderer<'env> {
         mut self,
         py: Python<'env>,
         input: &'env str,
-    ) -> Result<Option<String>, Error> {
+    ) -> Result<Option<Rendered>, Error> {
         if !input.contains('{') {
             // Shortcut if it doesn't contain any variables or control structures
             return Ok(None);
         }
         self.env.add_template(Self::TPL_NAME, input)?;
+        let _safe_mode = SafeMode::enter(self.document.borrow(py).safe);
+        let context = Value::from_object(RenderContext {
+            document: self.document.clone_ref(py),
+            helpers: self.helpers,
+            item_path: self.item_path,
+            used_names: self.used_names,
+        });
         let result = self
             .env
             .get_template(Self::TPL_NAME)?
-            .render_from_value(Self::build_context(self.document.clone_ref(py)))?;
+            .render_from_value(context)?;
         self.env.remove_template(Self::TPL_NAME);
-        Ok(Some(result))
+        Ok(Some(Rendered {
+            output: result,
+            forced_string: self.forced_string.load(Ordering::Relaxed),
+        }))
     }
 
-    pub(crate) fn add_helpers(&mut self, py: Python, helpers: Vec<PyObject>) {
-        self.globals.extend(helpers.into_iter().map(|f| {
-            (
-                f.getattr(py, "__name__").unwrap().extract(py).unwrap(),
-                Self::create_helper_fn(f),
-            )
-        }));
+    /// Sets the path of the rendered value in the document, for the ``item_*`` template functions.
+    pub(crate) fn at(mut self, path: &[ItemPathPart]) -> Self {
+        self.item_path = path.to_vec();
+        self
+    }
+
+    /// Records the names the template looks up in its context. The returned list is filled while
+    /// rendering, each name is only recorded once.
+    pub(crate) fn record_names(&mut self) -> UsedNames {
+        self.used_names.get_or_insert_with(Default::default).clone()
     }
 
-    #[inline]
-    fn build_context(document: PyYamlConfigDocument) -> Value {
-        Value::from_object(document)
+    pub(crate) fn add_helpers(&mut self, py: Python, helpers: Vec<PyObject>) -> PyResult<()> {
+        for f in helpers {
+            self.helpers.insert(helper_name(f.as_ref(py))?, f);
+        }
+        Ok(())
     }
 
-    pub fn create_helper_fn(pyf: PyObject) -> Box<FuncFunc> {
+    /// Wraps a Python helper function. If document is set, it is passed as first argument.
+    pub fn create_helper_fn(
+        pyf: PyObject,
+        document: Option<PyYamlConfigDocument>,
+    ) -> Box<FuncFunc> {
         Box::new(typed_closure!(
             (Fn(&State, Vec<Value>) -> Result<Value, Error> + Sync + Send + 'static),
             move |_state: &State, args: Vec<Value>| -> Result<Value, Error> {
                 Python::with_gil(|py| {
-                    let pyargs = PyTuple::new(py, args.into_iter().map(WValue));
+                    let document = document.as_ref().map(|d| d.clone_ref(py).into_py(py));
+                    let pyargs = PyTuple::new(
+                        py,
+                        document
+                            .into_iter()
+                            .chain(args.into_iter().map(|v| WValue(v).to_object(py))),
+                    );
 
                     match pyf.call1(py, pyargs) {
                         Ok(v) => match v.extract::<YcdValueType>(py) {
@@ -98,6 +215,40 @@ impl<'env> TemplateRenderer<'env> {
     }
 }
 
+/// Whether s contains a template expression or statement.
+pub(crate) fn is_template(s: &str) -> bool {
+    s.contains("{{") || s.contains("{%")
+}
+
+/// The name of a helper in templates: The name passed to the ``helper`` decorator or the
+/// ``__name__`` of the function. Lambdas and callables without a ``__name__``
+/// (e.g. ``functools.partial``) must be named with the decorator.
+pub(crate) fn helper_name(helper: &PyAny) -> PyResult<String> {
+    if let Ok(name) = helper.getattr(HELPER_NAME_ATTR) {
+        if !name.is_none() {
+            return name.extract();
+        }
+    }
+    if let Ok(name) = helper.getattr("__name__") {
+        let name: String = name.extract()?;
+        if name != "<lambda>" {
+            return Ok(name);
+        }
+    }
+    Err(PyTypeError::new_err(format!(
+        "Can not determine the name of the variable helper {}. Use @helper(name=...) to name it.",
+        helper.repr()?
+    )))
+}
+
+/// Whether the document must be passed to the helper as first argument.
+fn passes_document(helper: &PyAny) -> bool {
+    helper
+        .getattr(HELPER_PASS_DOCUMENT_ATTR)
+        .and_then(|v| v.extract())
+        .unwrap_or(false)
+}
+
 impl Display for PyYamlConfigDocument {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         Python::with_gil(
@@ -109,10 +260,6 @@ impl Display for PyYamlConfigDocument {
     }
 }
 
-fn str_filter(_state: &State, value: String) -> Result<String, Error> {
-    Ok(Value::from(format!("{}{}", FORCE_STRING, value)).to_string())
-}
-
 fn substr_start_filter(_state: &State, string: String, start: usize) -> Result<String, Error> {
     Ok(string[start..].to_string())
 }
@@ -121,6 +268,178 @@ fn startswith_filter(_state: &State, string: String, start: String) -> Result<bo
     Ok(string.starts_with(&start))
 }
 
+/// A number passed to a formatting filter.
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
+
+impl Number {
+    /// An integer, a float or a string containing one.
+    fn from_value(filter: &str, value: &Value) -> Result<Self, Error> {
+        let number = match value.as_primitive() {
+            Some(Primitive::I64(v)) => Some(Number::Int(v as i128)),
+            Some(Primitive::U64(v)) => Some(Number::Int(v as i128)),
+            Some(Primitive::I128(v)) => Some(Number::Int(v)),
+            Some(Primitive::U128(v)) => i128::try_from(v).ok().map(Number::Int),
+            Some(Primitive::F64(v)) => Some(Number::Float(v)),
+            Some(Primitive::Str(v)) => v
+                .trim()
+                .parse()
+                .map(Number::Int)
+                .or_else(|_| v.trim().parse().map(Number::Float))
+                .ok(),
+            _ => None,
+        };
+        number.ok_or_else(|| {
+            Error::new(
+                ErrorKind::ImpossibleOperation,
+                format!("The {} filter expects a number, got: {}", filter, value),
+            )
+        })
+    }
+
+    fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(v) => v as f64,
+            Number::Float(v) => v,
+        }
+    }
+}
+
+/// ``{{ value|format_number(decimals, thousands_separator, decimal_point) }}``: The number with
+/// thousands separators (default: ``,``) and the decimal point (default: ``.``). If decimals is
+/// not given, integers have no decimals and floats as many as needed.
+fn format_number_filter(
+    _state: &State,
+    value: Value,
+    decimals: Option<usize>,
+    thousands_separator: Option<String>,
+    decimal_point: Option<String>,
+) -> Result<String, Error> {
+    let text = match (Number::from_value("format_number", &value)?, decimals) {
+        (Number::Int(v), None | Some(0)) => v.to_string(),
+        (Number::Int(v), Some(d)) => format!("{}.{}", v, "0".repeat(d)),
+        (Number::Float(v), Some(d)) => format!("{:.*}", d, v),
+        (Number::Float(v), None) => v.to_string(),
+    };
+    Ok(group_digits(
+        &text,
+        thousands_separator.as_deref().unwrap_or(","),
+        decimal_point.as_deref().unwrap_or("."),
+    ))
+}
+
+/// Inserts the separator between the groups of three digits of the integer part of a formatted
+/// number and replaces its decimal point. Non-numbers (``inf``, ``NaN``) are returned as they are.
+fn group_digits(text: &str, separator: &str, decimal_point: &str) -> String {
+    let (sign, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => ("-", rest),
+        None => ("", text),
+    };
+    let (int_part, fraction) = match unsigned.split_once('.') {
+        Some((int_part, fraction)) => (int_part, Some(fraction)),
+        None => (unsigned, None),
+    };
+    if !int_part.chars().all(|c| c.is_ascii_digit()) {
+        return text.to_string();
+    }
+    let mut out = sign.to_string();
+    for (i, c) in int_part.chars().enumerate() {
+        if i > 0 && (int_part.len() - i) % 3 == 0 {
+            out.push_str(separator);
+        }
+        out.push(c);
+    }
+    if let Some(fraction) = fraction {
+        out.push_str(decimal_point);
+        out.push_str(fraction);
+    }
+    out
+}
+
+/// ``{{ value|format_bytes(base, decimals) }}``: A number of bytes with the largest unit, that
+/// the value is at least one of: ``1.5 KiB`` with base 1024 (the default), ``1.5 kB`` with
+/// base 1000. Sizes in bytes have no decimals, others one (or decimals).
+fn format_bytes_filter(
+    _state: &State,
+    value: Value,
+    base: Option<i64>,
+    decimals: Option<usize>,
+) -> Result<String, Error> {
+    let base = base.unwrap_or(1024);
+    let units = match base {
+        1024 => ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+        1000 => ["B", "kB", "MB", "GB", "TB", "PB", "EB"],
+        other => {
+            return Err(Error::new(
+                ErrorKind::ImpossibleOperation,
+                format!(
+                    "The base of the format_bytes filter must be 1024 or 1000, got: {}",
+                    other
+                ),
+            ))
+        }
+    };
+    let base = base as f64;
+    let decimals = decimals.unwrap_or(1);
+    let bytes = Number::from_value("format_bytes", &value)?.as_f64();
+    let sign = if bytes < 0.0 { "-" } else { "" };
+    let mut size = bytes.abs();
+    let mut unit = 0;
+    while unit < units.len() - 1 {
+        // The size as it's written, so that e.g. 1023.99 KiB becomes 1.0 MiB.
+        let scale = if unit == 0 {
+            1.0
+        } else {
+            10f64.powi(decimals as i32)
+        };
+        if (size * scale).round() / scale < base {
+            break;
+        }
+        size /= base;
+        unit += 1;
+    }
+    Ok(if unit == 0 {
+        format!("{}{} {}", sign, size.round(), units[0])
+    } else {
+        format!("{}{:.*} {}", sign, decimals, size, units[unit])
+    })
+}
+
+/// ``{{ value|format_duration }}``: A duration in seconds as days, hours, minutes and seconds
+/// (``1d 2h 5s``). Durations below one second are written in milliseconds (``250ms``).
+fn format_duration_filter(_state: &State, value: Value) -> Result<String, Error> {
+    let seconds = Number::from_value("format_duration", &value)?.as_f64();
+    if !seconds.is_finite() {
+        return Err(Error::new(
+            ErrorKind::ImpossibleOperation,
+            format!(
+                "The format_duration filter expects a finite number, got: {}",
+                value
+            ),
+        ));
+    }
+    let sign = if seconds < 0.0 { "-" } else { "" };
+    let millis = (seconds.abs() * 1000.0).round() as u64;
+    if millis > 0 && millis < 1000 {
+        return Ok(format!("{}{}ms", sign, millis));
+    }
+    let mut rest = seconds.abs().round() as u64;
+    let mut parts = vec![];
+    for (unit_seconds, suffix) in [(86400, "d"), (3600, "h"), (60, "m"), (1, "s")] {
+        if rest >= unit_seconds {
+            parts.push(format!("{}{}", rest / unit_seconds, suffix));
+            rest %= unit_seconds;
+        }
+    }
+    if parts.is_empty() {
+        return Ok("0s".to_string());
+    }
+    Ok(format!("{}{}", sign, parts.join(" ")))
+}
+
 fn convert_pyerr<_T>(in_e: pyo3::PyErr) -> Result<_T, Error> {
     Err(Error::new(
         ErrorKind::ImpossibleOperation,
@@ -150,10 +469,11 @@ impl From<YcdValueType> for Value {
                 .map(|v| v.into())
                 .collect::<Vec<Value>>()
                 .into(),
-            YcdValueType::YString(v) => Value::from(v),
+            YcdValueType::YString(v) | YcdValueType::ForcedString(v) => Value::from(v),
             YcdValueType::Bool(v) => Value::from(v),
             YcdValueType::Int(v) => Value::from(v),
             YcdValueType::Float(v) => Value::from(v),
+            YcdValueType::Decimal(v) => Value::from(decimal_to_f64(&v)),
             YcdValueType::Ycd(v) => Value::from_object(v),
         }
     }
@@ -164,10 +484,11 @@ impl From<&YcdValueType> for Value {
         match in_v {
             YcdValueType::Dict(v) => Value::from_object(YHashMap(v.clone())), // TODO: Not ideal
             YcdValueType::List(v) => v.iter().map(|v| v.into()).collect::<Vec<Value>>().into(),
-            YcdValueType::YString(v) => Value::from(v.clone()),
+            YcdValueType::YString(v) | YcdValueType::ForcedString(v) => Value::from(v.clone()),
             YcdValueType::Bool(v) => Value::from(*v),
             YcdValueType::Int(v) => Value::from(*v),
             YcdValueType::Float(v) => Value::from(*v),
+            YcdValueType::Decimal(v) => Value::from(decimal_to_f64(v)),
             YcdValueType::Ycd(v) => Python::with_gil(|py| Value::from_object(v.clone_ref(py))),
         }
     }
@@ -195,8 +516,36 @@ impl ToPyObject for WValue {
     }
 }
 
+/// A helper bound to a document, the document is passed to it if requested by the helper.
 #[derive(Debug)]
-struct VariableHelper(PyObject);
+struct VariableHelper(PyObject, PyYamlConfigDocument);
+
+impl VariableHelper {
+    fn call_helper(
+        py: Python,
+        helper: &PyObject,
+        document: &PyYamlConfigDocument,
+        state: &State,
+        args: Vec<Value>,
+    ) -> Result<Value, Error> {
+        if SafeMode::is_active() {
+            return Err(Error::new(
+                ErrorKind::ImpossibleOperation,
+                format!(
+                    "The variable helper {} can not be called in safe mode.",
+                    helper_name(helper.as_ref(py))
+                        .unwrap_or_else(|_| helper.as_ref(py).to_string())
+                ),
+            ));
+        }
+        let document = if passes_document(helper.as_ref(py)) {
+            Some(document.clone_ref(py))
+        } else {
+            None
+        };
+        TemplateRenderer::create_helper_fn(helper.clone_ref(py), document)(state, args)
+    }
+}
 
 impl Display for VariableHelper {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -206,56 +555,321 @@ impl Display for VariableHelper {
 
 impl Object for VariableHelper {
     fn call(&self, state: &State, args: Vec<Value>) -> Result<Value, Error> {
-        Python::with_gil(|py| TemplateRenderer::create_helper_fn(self.0.clone_ref(py))(state, args))
+        Python::with_gil(|py| Self::call_helper(py, &self.0, &self.1, state, args))
+    }
+}
+
+/// The context templates are rendered with: The document, the additional helpers of the renderer,
+/// the ``item_*`` functions and ``source_of``. Keys of the document (and everything else the
+/// document provides) take priority.
+#[derive(Debug)]
+struct RenderContext {
+    document: PyYamlConfigDocument,
+    helpers: HashMap<String, PyObject>,
+    item_path: Vec<ItemPathPart>,
+    used_names: Option<UsedNames>,
+}
+
+impl RenderContext {
+    const ITEM_INDEX: &'static str = "item_index";
+    const ITEM_KEY: &'static str = "item_key";
+    const ITEM_COLLECTION: &'static str = "item_collection";
+    const SOURCE_OF: &'static str = "source_of";
+
+    /// The path of the item the rendered value belongs to: The innermost list entry or dict entry
+    /// containing the value. The key of the value itself is not part of it, but its index is
+    /// (if it is directly in a list).
+    fn item(&self) -> &[ItemPathPart] {
+        match self.item_path.split_last() {
+            Some((ItemPathPart::Key(_), rest)) => rest,
+            _ => &self.item_path,
+        }
+    }
+
+    /// Index of the innermost list entry containing the rendered value.
+    fn item_index(&self) -> Value {
+        self.item()
+            .iter()
+            .rev()
+            .find_map(|p| match p {
+                ItemPathPart::Index(i) => Some(Value::from(*i)),
+                _ => None,
+            })
+            .unwrap_or_else(|| Value::from(()))
+    }
+
+    /// Key of the innermost dict entry containing the rendered value (excluding its own key).
+    fn item_key(&self) -> Value {
+        self.item()
+            .iter()
+            .rev()
+            .find_map(|p| match p {
+                ItemPathPart::Key(k) => Some(Value::from(k.clone())),
+                _ => None,
+            })
+            .unwrap_or_else(|| Value::from(()))
+    }
+
+    /// The list or dict containing the item. This is the state before the current processing pass.
+    fn item_collection(&self, py: Python) -> Value {
+        let path = match self.item().split_last() {
+            None => return Value::from(()),
+            Some((_, path)) => path,
+        };
+        let bow = self.document.borrow(py);
+        let (root, path) = match path.split_first() {
+            Some((ItemPathPart::Key(k), rest)) if k == VARS => (&bow.local_vars, rest),
+            _ => (&bow.doc, path),
+        };
+        let mut current = match path.first() {
+            None => return Value::from_object(YHashMap(root.clone())),
+            Some(ItemPathPart::Key(k)) => root.get(k),
+            Some(ItemPathPart::Index(_)) => None,
+        };
+        for part in path.iter().skip(1) {
+            current = match (current, part) {
+                (Some(YcdValueType::Dict(d)), ItemPathPart::Key(k)) => d.get(k),
+                (Some(YcdValueType::List(l)), ItemPathPart::Index(i)) => l.get(*i),
+                _ => None,
+            }
+        }
+        current.map(|v| v.into()).unwrap_or_else(|| Value::from(()))
+    }
+
+    fn record_name(&self, name: &str) {
+        let used_names = match &self.used_names {
+            None => return,
+            Some(used_names) => used_names,
+        };
+        let mut used_names = used_names.lock().unwrap();
+        if used_names.iter().all(|n| n.name != name) {
+            used_names.push(UsedName {
+                name: name.to_string(),
+                value: Python::with_gil(|py| self.document.template_variable(py, name)),
+            });
+        }
+    }
+}
+
+impl Display for RenderContext {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.document.fmt(f)
+    }
+}
+
+impl Object for RenderContext {
+    fn get_attr(&self, name: &str) -> Option<Value> {
+        let value = self.document.get_attr(name).or_else(|| {
+            Python::with_gil(|py| match name {
+                Self::ITEM_INDEX => Some(Value::from_object(ValueFunction(self.item_index()))),
+                Self::ITEM_KEY => Some(Value::from_object(ValueFunction(self.item_key()))),
+                Self::ITEM_COLLECTION => {
+                    Some(Value::from_object(ValueFunction(self.item_collection(py))))
+                }
+                Self::SOURCE_OF => Some(Value::from_object(SourceOf(self.document.clone_ref(py)))),
+                _ => self.helpers.get(name).map(|x| {
+                    Value::from_object(VariableHelper(x.clone_ref(py), self.document.clone_ref(py)))
+                }),
+            })
+        });
+        if value.is_some() {
+            self.record_name(name);
+        }
+        value
+    }
+}
+
+/// A template function returning a precomputed value: The ``item_*`` functions and the built-in
+/// helpers in safe mode.
+#[derive(Debug)]
+struct ValueFunction(Value);
+
+impl Display for ValueFunction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Object for ValueFunction {
+    fn call(&self, _state: &State, _args: Vec<Value>) -> Result<Value, Error> {
+        Ok(self.0.clone())
+    }
+}
+
+/// The ``source_of(path)`` template function: The file that supplied the value at path in the
+/// document (see ``YamlConfigDocument.source_file``), or none if it wasn't loaded from a file or
+/// doesn't exist.
+#[derive(Debug)]
+struct SourceOf(PyYamlConfigDocument);
+
+impl Display for SourceOf {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", RenderContext::SOURCE_OF)
+    }
+}
+
+impl Object for SourceOf {
+    fn call(&self, _state: &State, args: Vec<Value>) -> Result<Value, Error> {
+        let path = match args.as_slice() {
+            [path] => match path.as_primitive() {
+                Some(Primitive::Str(path)) => path.to_string(),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::ImpossibleOperation,
+                        format!("source_of expects a path (a string), got: {}", path),
+                    ))
+                }
+            },
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::ImpossibleOperation,
+                    "source_of expects exactly one argument: The path of the value.",
+                ))
+            }
+        };
+        Python::with_gil(|py| {
+            // Unlike source_file, there is no file for values that don't exist yet.
+            if !has_value(py, &self.0, &path) {
+                return Ok(Value::from(()));
+            }
+            match find_source(py, &self.0, &path) {
+                Ok(source) => Ok(source.map_or_else(|| Value::from(()), |s| Value::from(s.file))),
+                Err(e) => convert_pyerr(e),
+            }
+        })
+    }
+}
+
+impl PyYamlConfigDocument {
+    /// The value of the variable name in templates of this document: A key of the document, a
+    /// $var, ``values`` or a global (in that order). None if it's none of them (e.g. a helper).
+    fn template_variable(&self, py: Python, name: &str) -> Option<YcdValueType> {
+        let bow = self.0.borrow(py);
+        bow.doc
+            .get(name)
+            .or_else(|| bow.local_vars.get(name))
+            .cloned()
+            .or_else(|| match &bow.values {
+                Some(values) if name == VALUES => Some(YcdValueType::Dict(values.clone())),
+                _ => None,
+            })
+            .or_else(|| {
+                bow.globals
+                    .as_ref()
+                    .and_then(|globals| globals.get(name))
+                    .cloned()
+            })
+    }
+
+    /// The result of the built-in helper name (``parent`` or ``path_in_parent``), computed without
+    /// calling into Python. Used in safe mode.
+    fn builtin_helper(&self, py: Python, name: &str) -> Option<Value> {
+        let bow = self.0.borrow(py);
+        Some(match name {
+            "parent" => match &bow.parent_doc {
+                None => Value::from_object(self.clone_ref(py)),
+                Some(parent) => Value::from_object(PyYamlConfigDocument(parent.clone_ref(py))),
+            },
+            "path_in_parent" => match &bow.path_in_parent {
+                None => Value::from(()),
+                Some(path) => Value::from(path.clone()),
+            },
+            _ => return None,
+        })
     }
 }
 
 impl Object for PyYamlConfigDocument {
     fn get_attr(&self, name: &str) -> Option<Value> {
         Python::with_gil(|py| {
-            let mut bow = self.0.borrow(py);
-            bow.doc.get(name).map(|x| x.into()).or_else(|| {
-                if bow.bound_helpers.is_empty() {
+            let bow = self.0.borrow(py);
+            bow.doc
+                .get(name)
+                .or_else(|| bow.local_vars.get(name))
+                .map(|x| x.into())
+                .or_else(|| match &bow.values {
+                    Some(values) if name == VALUES => {
+                        Some(Value::from_object(YHashMap(values.clone())))
+                    }
+                    _ => None,
+                })
+                .or_else(|| {
+                    bow.globals
+                        .as_ref()
+                        .and_then(|globals| globals.get(name))
+                        .map(|x| x.into())
+                })
+                .or_else(|| {
                     drop(bow);
-                    YamlConfigDocument::collect_bound_variable_helpers(
-                        self.0.clone_ref(py).as_ref(py),
-                        py,
-                    )
-                    .ok();
-                    bow = self.0.borrow(py);
-                }
-                bow.bound_helpers
-                    .get(name)
-                    .map(|x| Value::from_object(VariableHelper(x.clone_ref(py))))
-            })
+                    SafeMode::is_active()
+                        .then(|| self.builtin_helper(py, name))
+                        .flatten()
+                        .map(|x| Value::from_object(ValueFunction(x)))
+                })
+                .or_else(|| {
+                    find_bound_helper(py, self, name)
+                        .ok()
+                        .flatten()
+                        .map(|x| Value::from_object(VariableHelper(x, self.clone_ref(py))))
+                })
         })
     }
 
     fn call_method(&self, state: &State, name: &str, args: Vec<Value>) -> Result<Value, Error> {
         Python::with_gil(|py| {
-            let mut bow = self.0.borrow(py);
-            if bow.bound_helpers.is_empty() {
-                drop(bow);
-                YamlConfigDocument::collect_bound_variable_helpers(
-                    self.0.clone_ref(py).as_ref(py),
-                    py,
-                )
-                .map_err(|e| convert_pyerr::<bool>(e).unwrap_err())?;
-                bow = self.0.borrow(py);
+            if SafeMode::is_active() {
+                if let Some(value) = self.builtin_helper(py, name) {
+                    return Ok(value);
+                }
             }
-            match bow.bound_helpers.get(name) {
+            match find_bound_helper(py, self, name)
+                .map_err(|e| convert_pyerr::<bool>(e).unwrap_err())?
+            {
                 None => Err(Error::new(
                     ErrorKind::ImpossibleOperation,
                     format!("Method {} not found on object", name),
                 )),
-                Some(helper) => {
-                    TemplateRenderer::create_helper_fn(helper.clone_ref(py))(state, args)
-                }
+                Some(helper) => VariableHelper::call_helper(py, &helper, self, state, args),
             }
         })
     }
 }
 
+/// Returns the variable helper with the given name of document. If the document doesn't have it,
+/// it is looked up in the parent documents (unless disabled with ``inherit_helpers=False``).
+/// Helpers are collected on first use.
+fn find_bound_helper(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    name: &str,
+) -> PyResult<Option<PyObject>> {
+    let inherit = document.borrow(py).inherit_helpers;
+    let mut current = document.0.clone_ref(py);
+    loop {
+        let needs_collecting = current.borrow(py).bound_helpers.is_empty();
+        let collected = if needs_collecting {
+            Some(YamlConfigDocument::collect_bound_variable_helpers(
+                current.as_ref(py),
+                py,
+            )?)
+        } else {
+            None
+        };
+        let parent = {
+            let bow = current.borrow(py);
+            let helpers = collected.as_ref().unwrap_or(&bow.bound_helpers);
+            if let Some(helper) = helpers.get(name) {
+                return Ok(Some(helper.clone_ref(py)));
+            }
+            match &bow.parent_doc {
+                Some(parent) if inherit => parent.clone_ref(py),
+                _ => return Ok(None),
+            }
+        };
+        current = parent;
+    }
+}
+
 struct YHashMapItem<'a>(String, &'a YcdValueType);
 impl<'a> Serialize for YHashMapItem<'a> {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
@@ -0,0 +1,277 @@
+//! Operations on nested values (dicts and lists in dicts and lists), that work with an explicit
+//! stack instead of recursion, so deeply nested documents can't overflow the stack.
+use crate::conv::YcdValueType::{
+    Bool, Decimal, Dict, Float, ForcedString, Int, List, YString, Ycd,
+};
+use crate::conv::{YcdDict, YcdList, YcdValueType};
+use crate::minijinja::ItemPathPart;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+/// A value split by expand (see build): Either a finished value or the sources of the entries of
+/// a dict or list.
+pub(crate) enum Expanded<S> {
+    Value(YcdValueType),
+    Dict(Vec<(String, S)>),
+    List(Vec<S>),
+}
+
+/// A dict or list, of which some entries are built (see build).
+enum Partial<S> {
+    Dict {
+        entries: std::vec::IntoIter<(String, S)>,
+        /// The key of the entry that is built, if any.
+        key: Option<String>,
+        done: YcdDict,
+    },
+    List {
+        entries: std::vec::IntoIter<S>,
+        done: YcdList,
+    },
+}
+
+impl<S> Partial<S> {
+    fn next_source(&mut self) -> Option<S> {
+        match self {
+            Partial::Dict { entries, key, .. } => entries.next().map(|(k, source)| {
+                *key = Some(k);
+                source
+            }),
+            Partial::List { entries, .. } => entries.next(),
+        }
+    }
+
+    fn push(&mut self, value: YcdValueType) {
+        match self {
+            Partial::Dict { key, done, .. } => {
+                if let Some(key) = key.take() {
+                    done.insert(key, value);
+                }
+            }
+            Partial::List { done, .. } => done.push(value),
+        }
+    }
+
+    fn finish(self) -> YcdValueType {
+        match self {
+            Partial::Dict { done, .. } => Dict(done),
+            Partial::List { done, .. } => List(done),
+        }
+    }
+}
+
+/// Builds a value from source: expand either returns the finished value of a source, or the
+/// sources of the entries of a dict or list, which are built the same way. Entries keep their order.
+pub(crate) fn build<S>(
+    source: S,
+    mut expand: impl FnMut(S) -> PyResult<Expanded<S>>,
+) -> PyResult<YcdValueType> {
+    let mut stack: Vec<Partial<S>> = vec![];
+    let mut next = expand(source)?;
+    loop {
+        let mut finished = match next {
+            Expanded::Value(value) => Some(value),
+            Expanded::Dict(entries) => {
+                stack.push(Partial::Dict {
+                    done: YcdDict::with_capacity(entries.len()),
+                    entries: entries.into_iter(),
+                    key: None,
+                });
+                None
+            }
+            Expanded::List(entries) => {
+                stack.push(Partial::List {
+                    done: YcdList::with_capacity(entries.len()),
+                    entries: entries.into_iter(),
+                });
+                None
+            }
+        };
+        next = loop {
+            let partial = match stack.last_mut() {
+                Some(partial) => partial,
+                // Only the built value itself doesn't belong to a dict or list.
+                None => return Ok(finished.take().expect("The value must be finished.")),
+            };
+            if let Some(value) = finished.take() {
+                partial.push(value);
+            }
+            match partial.next_source() {
+                Some(source) => break expand(source)?,
+                None => finished = stack.pop().map(Partial::finish),
+            }
+        };
+    }
+}
+
+/// Builds a new value from value (see build): f is called with value and all values in it
+/// (dicts and lists before their entries). f returns the new value and whether its entries are
+/// passed to f as well (``true``), or it is kept as it is (``false``). The entries of dicts and
+/// lists are passed to f in order.
+pub(crate) fn rewrite(
+    value: YcdValueType,
+    mut f: impl FnMut(YcdValueType) -> PyResult<(YcdValueType, bool)>,
+) -> PyResult<YcdValueType> {
+    build(value, |value| {
+        Ok(match f(value)? {
+            (Dict(d), true) => Expanded::Dict(d.into_iter().collect()),
+            (List(l), true) => Expanded::List(l),
+            (value, _) => Expanded::Value(value),
+        })
+    })
+}
+
+/// The entries of a dict or list, that are visited by visit_mut.
+enum EntriesMut<'a> {
+    Dict(indexmap::map::IterMut<'a, String, YcdValueType>),
+    List(std::iter::Enumerate<std::slice::IterMut<'a, YcdValueType>>),
+}
+
+/// Calls f with value and all values in it, with their paths (the path of value is path).
+/// Dicts and lists are passed to f before their entries, f can change them (e.g. remove entries)
+/// before the entries are visited. The values of sub-documents are not visited.
+pub(crate) fn visit_mut<'a>(
+    value: &'a mut YcdValueType,
+    path: &mut Vec<ItemPathPart>,
+    mut f: impl FnMut(&mut YcdValueType, &[ItemPathPart]) -> PyResult<()>,
+) -> PyResult<()> {
+    let root_len = path.len();
+    let mut stack: Vec<EntriesMut<'a>> = vec![];
+    let mut next = Some(value);
+    loop {
+        if let Some(value) = next.take() {
+            f(value, path)?;
+            match value {
+                Dict(d) => stack.push(EntriesMut::Dict(d.iter_mut())),
+                List(l) => stack.push(EntriesMut::List(l.iter_mut().enumerate())),
+                _ => {
+                    if path.len() > root_len {
+                        path.pop();
+                    }
+                }
+            }
+        }
+        let entry = match stack.last_mut() {
+            None => return Ok(()),
+            Some(EntriesMut::Dict(entries)) => entries
+                .next()
+                .map(|(k, v)| (ItemPathPart::Key(k.clone()), v)),
+            Some(EntriesMut::List(entries)) => {
+                entries.next().map(|(i, v)| (ItemPathPart::Index(i), v))
+            }
+        };
+        match entry {
+            Some((part, value)) => {
+                path.push(part);
+                next = Some(value);
+            }
+            None => {
+                stack.pop();
+                if path.len() > root_len {
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+/// Calls f with all values in value (including value itself), dicts and lists before their
+/// entries. The values of sub-documents are not visited.
+pub(crate) fn visit<E>(
+    value: &YcdValueType,
+    mut f: impl FnMut(&YcdValueType) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        f(value)?;
+        match value {
+            Dict(d) => stack.extend(d.values().rev()),
+            List(l) => stack.extend(l.iter().rev()),
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// A copy of value (YcdValueType::clone).
+pub(crate) fn clone_value(value: &YcdValueType) -> YcdValueType {
+    build(value, |value| {
+        Ok(match value {
+            Dict(d) => Expanded::Dict(d.iter().map(|(k, v)| (k.clone(), v)).collect()),
+            List(l) => Expanded::List(l.iter().collect()),
+            Ycd(doc) => Expanded::Value(Ycd(doc.clone())),
+            YString(s) => Expanded::Value(YString(s.clone())),
+            ForcedString(s) => Expanded::Value(ForcedString(s.clone())),
+            Bool(b) => Expanded::Value(Bool(*b)),
+            Int(i) => Expanded::Value(Int(*i)),
+            Float(f) => Expanded::Value(Float(*f)),
+            Decimal(d) => Expanded::Value(Decimal(d.clone())),
+        })
+    })
+    .expect("Copying a value can not fail.")
+}
+
+/// Drops value. The entries of dicts and lists are moved out of them before they are dropped, so
+/// dropping doesn't recurse into deeply nested values.
+pub(crate) fn drop_value(value: YcdValueType) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Dict(d) => stack.extend(d.into_iter().map(|(_, v)| v)),
+            List(l) => stack.extend(l),
+            _ => {}
+        }
+    }
+}
+
+/// Converts value into a Python object. shallow converts all values except dicts and lists, for
+/// which it returns None: They are created with None entries first (to keep the order of dicts),
+/// which are replaced after their values were converted.
+pub(crate) fn to_object(
+    py: Python,
+    value: &YcdValueType,
+    shallow: impl Fn(&YcdValueType) -> Option<PyObject>,
+) -> PyObject {
+    let mut pending = vec![];
+    let root = container_to_object(py, value, &shallow, &mut pending);
+    while let Some((container, key, value)) = pending.pop() {
+        let object = container_to_object(py, value, &shallow, &mut pending);
+        container
+            .as_ref(py)
+            .set_item(key, object)
+            .expect("Failed to set_item on dict or list");
+    }
+    root
+}
+
+/// Converts value (see to_object). The entries of dicts and lists are added to pending, with the
+/// dict or list and their key or index.
+fn container_to_object<'a>(
+    py: Python,
+    value: &'a YcdValueType,
+    shallow: &impl Fn(&YcdValueType) -> Option<PyObject>,
+    pending: &mut Vec<(PyObject, PyObject, &'a YcdValueType)>,
+) -> PyObject {
+    if let Some(object) = shallow(value) {
+        return object;
+    }
+    match value {
+        Dict(d) => {
+            let dict = PyDict::new(py);
+            for (k, v) in d {
+                dict.set_item(k, py.None())
+                    .expect("Failed to set_item on dict");
+                pending.push((dict.to_object(py), k.to_object(py), v));
+            }
+            dict.to_object(py)
+        }
+        List(l) => {
+            let list = PyList::new(py, l.iter().map(|_| py.None()));
+            for (i, v) in l.iter().enumerate() {
+                pending.push((list.to_object(py), i.to_object(py), v));
+            }
+            list.to_object(py)
+        }
+        _ => py.None(),
+    }
+}
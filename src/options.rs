@@ -0,0 +1,318 @@
+use crate::cancellation::Deadline;
+use crate::context::ResolutionContext;
+use crate::conv::YcdValueType::Dict;
+use crate::conv::{YcdDict, YcdValueType};
+use crate::features::imposed_features;
+use crate::identity::IdentityMap;
+use crate::limits::ParseLimits;
+use crate::loader::load_yaml_file;
+use crate::merger::{delete_remove_markers, merge_dicts};
+use crate::policy::DirectivePolicy;
+use crate::progress::Progress;
+use crate::registry::ContextRegistry;
+use crate::signing::TrustedKeys;
+use crate::stats::Stats;
+use crate::validation::ValidationPhases;
+use crate::virtual_files::VirtualFiles;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::Arc;
+
+/// Options that control how documents are read from disk.
+/// Passed as keyword arguments to `from_yaml`, `load_multiple_yml` and
+/// `resolve_and_merge_references`.
+#[derive(Clone, Debug)]
+pub(crate) struct LoadOptions {
+    /// Whether values in INI files are converted into booleans, integers and floats where possible.
+    /// If false, all INI values are read as strings.
+    pub(crate) ini_infer_types: bool,
+    /// Directive prefix and disabled directives of the loaded files.
+    pub(crate) directive_policy: Option<DirectivePolicy>,
+    /// What happens if a document changes a key locked with $lock.
+    pub(crate) lock_violation: LockViolation,
+    /// The profile, whose section in ``profiles`` is merged over the body of every loaded file.
+    pub(crate) profile: Option<String>,
+    /// Templates and documents of the resolution context, if resolving with one.
+    pub(crate) context: Option<Arc<ContextRegistry>>,
+    /// Globals of the resolution context and the passed globals (which take priority), available in
+    /// templated $ref targets and the conditions of lookup paths.
+    pub(crate) globals: Option<YcdDict>,
+    /// Whether ``${VAR}`` expressions in strings are replaced with environment variables directly
+    /// after reading a file.
+    pub(crate) env_interpolation: bool,
+    /// Whether scalars in YAML files are typed with the YAML 1.2 core schema (and INI values like
+    /// YAML 1.2 scalars).
+    pub(crate) core_schema: bool,
+    /// Whether floats in loaded files are converted to decimals (``decimal.Decimal`` in Python),
+    /// with as few digits as possible.
+    pub(crate) decimal_floats: bool,
+    /// What happens if a mapping in a YAML file contains the same key more than once.
+    pub(crate) duplicate_keys: DuplicateKeys,
+    /// Whether a CoercionWarning is emitted for every scalar in a YAML file, that would be typed
+    /// differently with the YAML 1.2 core schema.
+    pub(crate) report_coercions: bool,
+    /// When documents are validated while loading.
+    pub(crate) validation: ValidationPhases,
+    /// Directory remote lookup paths are cached in (default: see ``configcrunch._lookup``).
+    pub(crate) cache_dir: Option<String>,
+    /// Limits for the size and structure of loaded files.
+    pub(crate) limits: ParseLimits,
+    /// Contents of files, that are used instead of the files on disk (or if they don't exist).
+    pub(crate) virtual_files: VirtualFiles,
+    /// Public keys, whose signatures the files in some directories must have.
+    pub(crate) trusted_keys: TrustedKeys,
+    /// When loading and resolving is aborted.
+    pub(crate) deadline: Deadline,
+    /// Reports loaded files and merged documents.
+    pub(crate) progress: Progress,
+    /// Whether templates rendered while loading and resolving can only use the built-in filters and
+    /// functions (see SafeMode).
+    pub(crate) safe: bool,
+    /// Sub-documents, that only reference a file, loaded so far (see IdentityMap).
+    pub(crate) identity_map: IdentityMap,
+    /// The statistics of the loaded or resolved document.
+    pub(crate) stats: Stats,
+    /// Features imposed on every loaded file, in addition to the ``$features`` of the file.
+    pub(crate) features: Vec<String>,
+}
+
+/// What happens if a document changes a key locked with $lock.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum LockViolation {
+    /// Raise a LockViolationError.
+    Error,
+    /// Keep the locked value.
+    Ignore,
+}
+
+/// What happens if a mapping in a YAML file contains the same key more than once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DuplicateKeys {
+    /// Raise a DuplicateKeyError.
+    Error,
+    /// Emit a DuplicateKeyWarning.
+    Warn,
+    /// The later key wins.
+    Ignore,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            ini_infer_types: true,
+            directive_policy: None,
+            lock_violation: LockViolation::Error,
+            profile: None,
+            context: None,
+            globals: None,
+            env_interpolation: false,
+            core_schema: false,
+            decimal_floats: false,
+            duplicate_keys: DuplicateKeys::Ignore,
+            report_coercions: false,
+            validation: ValidationPhases::default(),
+            cache_dir: None,
+            limits: ParseLimits::default(),
+            virtual_files: VirtualFiles::default(),
+            trusted_keys: TrustedKeys::default(),
+            deadline: Deadline::default(),
+            progress: Progress::default(),
+            safe: false,
+            identity_map: IdentityMap::default(),
+            stats: Stats::default(),
+            features: vec![],
+        }
+    }
+}
+
+impl LoadOptions {
+    pub(crate) fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut slf = Self::default();
+        if let Some(kwargs) = kwargs {
+            for (key, value) in kwargs.iter() {
+                let key: &str = key.extract()?;
+                match key {
+                    "ini_infer_types" => slf.ini_infer_types = value.extract()?,
+                    "directive_policy" => slf.directive_policy = value.extract()?,
+                    "profile" => slf.profile = value.extract()?,
+                    "env_interpolation" => slf.env_interpolation = value.extract()?,
+                    "core_schema" => slf.core_schema = value.extract()?,
+                    "decimal_floats" => slf.decimal_floats = value.extract()?,
+                    "report_coercions" => slf.report_coercions = value.extract()?,
+                    "validation" => slf.validation = ValidationPhases::parse(value.extract()?)?,
+                    "cache_dir" => slf.cache_dir = value.extract()?,
+                    "max_file_size" => slf.limits.max_file_size = value.extract()?,
+                    "max_depth" => slf.limits.max_depth = value.extract()?,
+                    "max_nodes" => slf.limits.max_nodes = value.extract()?,
+                    "virtual_files" => {
+                        if let Some(files) = value.extract()? {
+                            slf.virtual_files = VirtualFiles::new(files);
+                        }
+                    }
+                    "timeout" => slf.deadline.set_timeout(value.extract()?)?,
+                    "cancel_token" => slf.deadline.set_token(value.extract()?),
+                    "safe" => slf.safe = value.extract()?,
+                    "identity_map" => slf.identity_map = IdentityMap::new(value.extract()?),
+                    "progress" => slf.progress = Progress::new(value.extract()?),
+                    "trusted_keys" => {
+                        if let Some(keys) = value.extract()? {
+                            slf.trusted_keys = TrustedKeys::new(keys)?;
+                        }
+                    }
+                    "features" => {
+                        slf.features =
+                            imposed_features(value.extract::<Option<_>>()?.unwrap_or_default())?
+                    }
+                    "context" => {
+                        if let Some(c) = value.extract::<Option<PyRef<ResolutionContext>>>()? {
+                            slf.context = Some(c.registry.clone());
+                            let mut globals = c.globals.clone();
+                            // Passed globals take priority.
+                            globals.extend(slf.globals.take().unwrap_or_default());
+                            slf.globals = Some(globals);
+                        }
+                    }
+                    "globals" => {
+                        if let Some(passed) = value.extract::<Option<YcdDict>>()? {
+                            slf.globals.get_or_insert_with(YcdDict::new).extend(passed);
+                        }
+                    }
+                    "duplicate_keys" => {
+                        slf.duplicate_keys = match value.extract::<&str>()? {
+                            "error" => DuplicateKeys::Error,
+                            "warn" => DuplicateKeys::Warn,
+                            "ignore" => DuplicateKeys::Ignore,
+                            other => {
+                                return Err(exceptions::PyValueError::new_err(format!(
+                                    "Invalid value for duplicate_keys: {}. Expected 'error', 'warn' or 'ignore'.",
+                                    other
+                                )))
+                            }
+                        }
+                    }
+                    "lock_violation" => {
+                        slf.lock_violation = match value.extract::<&str>()? {
+                            "error" => LockViolation::Error,
+                            "ignore" => LockViolation::Ignore,
+                            other => {
+                                return Err(exceptions::PyValueError::new_err(format!(
+                            "Invalid value for lock_violation: {}. Expected 'error' or 'ignore'.",
+                            other
+                        )))
+                            }
+                        }
+                    }
+                    _ => {
+                        return Err(exceptions::PyTypeError::new_err(format!(
+                            "Unexpected keyword argument: {}",
+                            key
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(slf)
+    }
+}
+
+/// Options that control how variables are processed.
+/// Passed as keyword arguments to `process_vars`.
+#[derive(Clone, Debug)]
+pub(crate) struct VariableOptions {
+    /// Whether Docker Compose style variables (``${VAR:-default}``) are interpolated using environment
+    /// variables before the templates are rendered.
+    pub(crate) compose_interpolation: bool,
+    /// Whether the $vars of parent documents are available in sub-documents.
+    pub(crate) inherit_vars: bool,
+    /// The merged values files, available in templates as ``values``.
+    pub(crate) values: Option<YcdDict>,
+    /// Variables available in all templates, e.g. those of a resolution context.
+    pub(crate) globals: Option<YcdDict>,
+    /// Whether sub-documents can call the variable helpers of their parent documents.
+    pub(crate) inherit_helpers: bool,
+    /// Whether errors in sub-documents are collected and raised together, after all documents were processed.
+    pub(crate) collect_errors: bool,
+    /// When processing is aborted.
+    pub(crate) deadline: Deadline,
+    /// Reports rendered templates.
+    pub(crate) progress: Progress,
+    /// Whether rendered templates are recorded in the audit log of their document.
+    pub(crate) audit: bool,
+    /// Whether templates can only use the built-in filters and functions (see SafeMode).
+    pub(crate) safe: bool,
+    /// The statistics of the processed document.
+    pub(crate) stats: Stats,
+}
+
+impl Default for VariableOptions {
+    fn default() -> Self {
+        Self {
+            compose_interpolation: false,
+            inherit_vars: false,
+            values: None,
+            globals: None,
+            inherit_helpers: true,
+            collect_errors: false,
+            deadline: Deadline::default(),
+            progress: Progress::default(),
+            audit: false,
+            safe: false,
+            stats: Stats::default(),
+        }
+    }
+}
+
+impl VariableOptions {
+    pub(crate) fn from_kwargs(kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut slf = Self::default();
+        if let Some(kwargs) = kwargs {
+            for (key, value) in kwargs.iter() {
+                let key: &str = key.extract()?;
+                match key {
+                    "compose_interpolation" => slf.compose_interpolation = value.extract()?,
+                    "inherit_vars" => slf.inherit_vars = value.extract()?,
+                    "values" => slf.values = Self::load_values(kwargs.py(), value.extract()?)?,
+                    "globals" => slf.globals = value.extract()?,
+                    "inherit_helpers" => slf.inherit_helpers = value.extract()?,
+                    "collect_errors" => slf.collect_errors = value.extract()?,
+                    "timeout" => slf.deadline.set_timeout(value.extract()?)?,
+                    "cancel_token" => slf.deadline.set_token(value.extract()?),
+                    "progress" => slf.progress = Progress::new(value.extract()?),
+                    "audit" => slf.audit = value.extract()?,
+                    "safe" => slf.safe = value.extract()?,
+                    _ => {
+                        return Err(exceptions::PyTypeError::new_err(format!(
+                            "Unexpected keyword argument: {}",
+                            key
+                        )))
+                    }
+                }
+            }
+        }
+        Ok(slf)
+    }
+
+    /// Loads and merges values files (paths of YAML files or dicts). Later ones take priority.
+    fn load_values(py: Python, sources: Option<Vec<YcdValueType>>) -> PyResult<Option<YcdDict>> {
+        let sources = match sources {
+            None => return Ok(None),
+            Some(sources) => sources,
+        };
+        let mut values = YcdDict::new();
+        for source in sources {
+            let source = match source {
+                YcdValueType::YString(path) => load_yaml_file(&path, &LoadOptions::default())?,
+                Dict(d) => d,
+                v => {
+                    return Err(exceptions::PyTypeError::new_err(format!(
+                        "Values must be paths to files or dicts, got: {}",
+                        v
+                    )))
+                }
+            };
+            values = merge_dicts(py, values, source, &LoadOptions::default())?;
+        }
+        Ok(Some(delete_remove_markers(py, Dict(values))?.unwrap_dict()))
+    }
+}
@@ -0,0 +1,192 @@
+use crate::conv::YcdValueType::{Bool, Dict, Float, Int, List, YString};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::INDEX_PREFIX;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::collections::BTreeMap;
+use std::mem::take;
+
+#[pyfunction]
+/// Parses ``key.path=value`` assignments (e.g. from ``--set`` command line flags) into an override
+/// dict, that can be merged over a document with ``overlay``::
+///
+///     doc = load_multiple_yml(MyDoc, "base.yml", "prod.yml")
+///     doc.overlay(parse_set_overrides(["server.port=8080", "hosts[0]=example.com"]))
+///
+/// Keys are separated by ``.`` (use ``\.`` for a literal dot), list entries are addressed
+/// with ``[index]``. List entries become ``$index::<index>`` entries (see the merging documentation),
+/// so they are merged over the existing entries of the lists of the document. Indexes that the
+/// list doesn't have raise an InvalidDocumentError when the override is merged.
+/// Otherwise the override is merged like any other document, ``key=$remove`` removes a key.
+///
+/// Values are converted to booleans (``true``/``false``), integers and floats where possible.
+/// Values in single or double quotes are always strings.
+/// If the same path is assigned multiple times, the last assignment wins.
+pub(crate) fn parse_set_overrides(assignments: Vec<String>) -> PyResult<YcdDict> {
+    let mut root = BTreeMap::new();
+    for assignment in &assignments {
+        let invalid = |e: &str| {
+            exceptions::PyValueError::new_err(format!("Invalid override {:?}: {}", assignment, e))
+        };
+        let (path, value) = parse_assignment(assignment).map_err(|e| invalid(&e))?;
+        match path.split_first() {
+            Some((PathPart::Key(key), rest)) => root
+                .entry(key.clone())
+                .or_insert_with(|| OverrideNode::Dict(BTreeMap::new()))
+                .insert(rest, value),
+            _ => return Err(invalid("List index without a key.")),
+        }
+    }
+    Ok(dict_into_value(root))
+}
+
+/// A part of the path of an assignment.
+#[derive(Debug)]
+enum PathPart {
+    Key(String),
+    Index(usize),
+}
+
+/// The override document while it is being built. Lists are stored by index, so that
+/// their entries can be assigned in any order.
+enum OverrideNode {
+    Dict(BTreeMap<String, OverrideNode>),
+    List(BTreeMap<usize, OverrideNode>),
+    Value(YcdValueType),
+}
+
+impl OverrideNode {
+    fn insert(&mut self, path: &[PathPart], value: YcdValueType) {
+        let (part, rest) = match path.split_first() {
+            None => {
+                *self = OverrideNode::Value(value);
+                return;
+            }
+            Some(v) => v,
+        };
+        match part {
+            PathPart::Key(key) => {
+                if !matches!(self, OverrideNode::Dict(_)) {
+                    *self = OverrideNode::Dict(BTreeMap::new());
+                }
+                if let OverrideNode::Dict(d) = self {
+                    d.entry(key.clone())
+                        .or_insert_with(|| OverrideNode::Dict(BTreeMap::new()))
+                        .insert(rest, value);
+                }
+            }
+            PathPart::Index(index) => {
+                if !matches!(self, OverrideNode::List(_)) {
+                    *self = OverrideNode::List(BTreeMap::new());
+                }
+                if let OverrideNode::List(l) = self {
+                    l.entry(*index)
+                        .or_insert_with(|| OverrideNode::Dict(BTreeMap::new()))
+                        .insert(rest, value);
+                }
+            }
+        }
+    }
+
+    fn into_value(self) -> YcdValueType {
+        match self {
+            OverrideNode::Value(v) => v,
+            OverrideNode::Dict(d) => Dict(dict_into_value(d)),
+            OverrideNode::List(l) => List(
+                l.into_iter()
+                    .map(|(i, v)| {
+                        let mut entry = YcdDict::with_capacity(1);
+                        entry.insert(format!("{}{}", INDEX_PREFIX, i), v.into_value());
+                        Dict(entry)
+                    })
+                    .collect(),
+            ),
+        }
+    }
+}
+
+fn dict_into_value(dict: BTreeMap<String, OverrideNode>) -> YcdDict {
+    dict.into_iter().map(|(k, v)| (k, v.into_value())).collect()
+}
+
+/// Splits an assignment into its path and its (converted) value.
+fn parse_assignment(assignment: &str) -> Result<(Vec<PathPart>, YcdValueType), String> {
+    let mut path = vec![];
+    let mut key = String::new();
+    // Whether the current key was closed by an index (``a[0]``), so it must be followed by "." or "=".
+    let mut after_index = false;
+    let mut chars = assignment.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some((_, escaped)) => key.push(escaped),
+                None => return Err("Trailing backslash.".to_string()),
+            },
+            '.' | '=' => {
+                if !after_index {
+                    if key.is_empty() {
+                        return Err("Empty key.".to_string());
+                    }
+                    path.push(PathPart::Key(take(&mut key)));
+                }
+                after_index = false;
+                if c == '=' {
+                    return Ok((path, infer_value(&assignment[i + 1..])));
+                }
+            }
+            '[' => {
+                if !key.is_empty() {
+                    path.push(PathPart::Key(take(&mut key)));
+                } else if !after_index {
+                    return Err("List index without a key.".to_string());
+                }
+                let rest = &assignment[i + 1..];
+                let end = rest.find(']').ok_or("Unterminated list index.")?;
+                let index = rest[..end]
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid list index: {:?}.", &rest[..end]))?;
+                path.push(PathPart::Index(index));
+                for _ in 0..=end {
+                    chars.next();
+                }
+                after_index = true;
+            }
+            _ => {
+                if after_index {
+                    return Err("Expected '.', '[' or '=' after a list index.".to_string());
+                }
+                key.push(c)
+            }
+        }
+    }
+    Err("Expected key.path=value.".to_string())
+}
+
+/// Converts the value of an assignment into a boolean, integer or float, if possible.
+fn infer_value(value: &str) -> YcdValueType {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return YString(value[1..value.len() - 1].to_string());
+        }
+    }
+    match value {
+        "true" => return Bool(true),
+        "false" => return Bool(false),
+        _ => {}
+    }
+    if let Ok(v) = value.parse::<i64>() {
+        return Int(v);
+    }
+    // Only plain decimal notation, so that e.g. "inf" or "NaN" stay strings.
+    if value
+        .chars()
+        .all(|c| c.is_ascii_digit() || "+-.eE".contains(c))
+        && value.chars().any(|c| c.is_ascii_digit())
+    {
+        if let Ok(v) = value.parse::<f64>() {
+            return Float(v);
+        }
+    }
+    YString(value.to_string())
+}
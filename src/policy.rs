@@ -0,0 +1,173 @@
+use crate::conv::YcdValueType::{Dict, List, YString};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::nested::rewrite;
+use crate::registry::is_registered_directive;
+use crate::{
+    InvalidDocumentError, DIRECTIVES, INDEX_PREFIX, INSERT_AFTER_PREFIX, INSERT_BEFORE_PREFIX,
+    REMOVE,
+};
+use pyo3::prelude::*;
+
+/// Prepended (internally) to keys and values that look like directives, but are not
+/// (because a different directive prefix is used). Removed again after resolving and when
+/// documents are exported (see unescape_all).
+pub(crate) const ESCAPE: &str = "\u{1}";
+
+/// Controls which directives are recognized in loaded files.
+///
+/// :param prefix:   The prefix of directives in the files (default: ``$``). If this is not ``$``,
+///                  keys starting with ``$`` are treated as normal keys.
+/// :param disabled: Names of directives (without prefix, e.g. ``remove``) that are not allowed.
+///                  Loading a file that contains them fails with an InvalidDocumentError.
+/// :param allow_exec: Whether the ``$exec`` directive may be used (default: no). It runs commands
+///                  while loading, so only enable it for trusted files.
+#[pyclass(module = "_main")]
+#[derive(Clone, Debug)]
+pub(crate) struct DirectivePolicy {
+    #[pyo3(get)]
+    pub(crate) prefix: String,
+    #[pyo3(get)]
+    pub(crate) disabled: Vec<String>,
+    #[pyo3(get)]
+    pub(crate) allow_exec: bool,
+}
+
+#[pymethods]
+impl DirectivePolicy {
+    #[new]
+    #[args(prefix = "\"$\"", disabled = "None", allow_exec = "false")]
+    fn new(prefix: &str, disabled: Option<Vec<String>>, allow_exec: bool) -> PyResult<Self> {
+        if prefix.is_empty() {
+            return Err(pyo3::exceptions::PyValueError::new_err(
+                "The directive prefix must not be empty.",
+            ));
+        }
+        Ok(Self {
+            prefix: prefix.to_string(),
+            disabled: disabled.unwrap_or_default(),
+            allow_exec,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "DirectivePolicy(prefix={:?}, disabled={:?}, allow_exec={})",
+            self.prefix,
+            self.disabled,
+            if self.allow_exec { "True" } else { "False" }
+        )
+    }
+}
+
+impl DirectivePolicy {
+    /// Translates the directives of a loaded file into the internal ($-prefixed) directives
+    /// and checks that no disabled directive is used.
+    pub(crate) fn apply(&self, py: Python, path: &str, dict: YcdDict) -> PyResult<YcdDict> {
+        match self.apply_value(py, path, Dict(dict))? {
+            Dict(d) => Ok(d),
+            _ => unreachable!(),
+        }
+    }
+
+    fn apply_value(&self, py: Python, path: &str, value: YcdValueType) -> PyResult<YcdValueType> {
+        Ok(match value {
+            Dict(d) => Dict(
+                d.into_iter()
+                    .map(|(k, v)| {
+                        Ok((
+                            self.translate_key(py, path, k)?,
+                            self.apply_value(py, path, v)?,
+                        ))
+                    })
+                    .collect::<PyResult<YcdDict>>()?,
+            ),
+            List(l) => List(
+                l.into_iter()
+                    .map(|v| self.apply_value(py, path, v))
+                    .collect::<PyResult<Vec<YcdValueType>>>()?,
+            ),
+            YString(s) => YString(self.translate_remove(path, s)?),
+            v => v,
+        })
+    }
+
+    fn translate_key(&self, py: Python, path: &str, key: String) -> PyResult<String> {
+        if let Some(name) = key.strip_prefix(&self.prefix) {
+            let directive = format!("${}", name);
+            if DIRECTIVES.contains(&directive.as_str()) || is_registered_directive(py, &directive)?
+            {
+                self.check_enabled(path, name)?;
+                return Ok(directive);
+            }
+            for prefix in [INSERT_BEFORE_PREFIX, INSERT_AFTER_PREFIX, INDEX_PREFIX] {
+                if directive.starts_with(prefix) {
+                    self.check_enabled(path, &prefix[1..prefix.len() - 2])?;
+                    return Ok(directive);
+                }
+            }
+        }
+        Ok(self.escape(key))
+    }
+
+    /// $remove is the only directive that is used as a value.
+    fn translate_remove(&self, path: &str, value: String) -> PyResult<String> {
+        let remove = format!("{}remove", self.prefix);
+        if let Some(rest) = value.strip_prefix(&remove) {
+            if rest.is_empty() || rest.starts_with("::") {
+                self.check_enabled(path, "remove")?;
+                return Ok(format!("{}{}", REMOVE, rest));
+            }
+        }
+        if value.starts_with(REMOVE) {
+            return Ok(self.escape(value));
+        }
+        Ok(value)
+    }
+
+    fn escape(&self, value: String) -> String {
+        if self.prefix != "$" && value.starts_with('$') {
+            format!("{}{}", ESCAPE, value)
+        } else {
+            value
+        }
+    }
+
+    fn check_enabled(&self, path: &str, name: &str) -> PyResult<()> {
+        if self.disabled.iter().any(|d| d == name) {
+            return Err(InvalidDocumentError::new_err(format!(
+                "The directive {}{} is not allowed in {}.",
+                self.prefix, name, path
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Removes the escaping of keys and values done by DirectivePolicy.
+pub(crate) fn unescape(value: String) -> String {
+    match value.strip_prefix(ESCAPE) {
+        None => value,
+        Some(v) => v.to_string(),
+    }
+}
+
+/// value without the escaping done by DirectivePolicy (see unescape).
+pub(crate) fn unescaped(value: &str) -> &str {
+    value.strip_prefix(ESCAPE).unwrap_or(value)
+}
+
+/// Removes the escaping done by DirectivePolicy from all keys and strings in value. Only resolving
+/// removes the escaping from documents, documents that are not resolved yet are exported with this.
+pub(crate) fn unescape_all(value: YcdValueType) -> YcdValueType {
+    rewrite(value, |value| {
+        Ok(match value {
+            Dict(d) => (
+                Dict(d.into_iter().map(|(k, v)| (unescape(k), v)).collect()),
+                true,
+            ),
+            YString(s) => (YString(unescape(s)), false),
+            v => (v, true),
+        })
+    })
+    .expect("Unescaping a value can not fail.")
+}
@@ -0,0 +1,62 @@
+//! Progress reporting while loading, resolving and processing variables (the ``progress`` option).
+use pyo3::prelude::*;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// Reports events to the Python callback passed as ``progress``, if any. Clones share the counts.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Progress(Option<Arc<ProgressState>>);
+
+#[derive(Debug)]
+struct ProgressState {
+    callback: PyObject,
+    counts: [AtomicUsize; 3],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum ProgressEvent {
+    /// A file was loaded.
+    FileLoaded,
+    /// A document was merged into another one.
+    DocumentMerged,
+    /// A template was rendered.
+    TemplateRendered,
+}
+
+impl ProgressEvent {
+    fn name(self) -> &'static str {
+        match self {
+            ProgressEvent::FileLoaded => "file_loaded",
+            ProgressEvent::DocumentMerged => "document_merged",
+            ProgressEvent::TemplateRendered => "template_rendered",
+        }
+    }
+}
+
+impl Progress {
+    pub(crate) fn new(callback: Option<PyObject>) -> Self {
+        Self(callback.map(|callback| {
+            Arc::new(ProgressState {
+                callback,
+                counts: Default::default(),
+            })
+        }))
+    }
+
+    /// Calls the callback with the name of the event, the number of events of that kind so far
+    /// (including this one) and detail (a path or None).
+    pub(crate) fn report(
+        &self,
+        py: Python,
+        event: ProgressEvent,
+        detail: Option<&str>,
+    ) -> PyResult<()> {
+        let state = match &self.0 {
+            None => return Ok(()),
+            Some(state) => state,
+        };
+        let count = state.counts[event as usize].fetch_add(1, Ordering::SeqCst) + 1;
+        state.callback.call1(py, (event.name(), count, detail))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! Tracks which files the values of documents were loaded from.
+use crate::conv::YcdValueType::{Dict, List, Ycd};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::{YamlConfigDocument, DEFAULT};
+use path_absolutize::Absolutize;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// The files the values of a document were loaded from, by key path (parts separated by ".").
+/// There are entries for dicts and for all values in them, but not for the entries of lists: The
+/// entry of a list names all files that added entries to it.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Provenance(HashMap<String, ProvenanceEntry>);
+
+#[derive(Clone, Debug)]
+struct ProvenanceEntry {
+    /// The files, lowest priority first. Empty if the value was not loaded from a file. The values
+    /// of sub-documents without files are looked up in the parent document instead.
+    files: Vec<String>,
+    is_list: bool,
+    /// Whether the value is set by a $default.
+    default: bool,
+}
+
+impl Provenance {
+    /// The provenance of a document body loaded from file (or not loaded from a file).
+    pub(crate) fn from_body(body: &YcdDict, file: Option<&str>) -> Self {
+        let mut slf = Self::default();
+        let files: Vec<String> = file.map(|f| vec![f.to_string()]).unwrap_or_default();
+        slf.add_dict(body, "", &files, false);
+        slf
+    }
+
+    fn add_dict(&mut self, dict: &YcdDict, path: &str, files: &[String], default: bool) {
+        for (key, value) in dict {
+            if key == DEFAULT {
+                // The defaults are set in the dict containing them.
+                if let Dict(defaults) = value {
+                    self.add_dict(defaults, path, files, true);
+                }
+                continue;
+            }
+            let key_path = join(path, key);
+            // If the key is also set directly in the same body, its value is not a default.
+            if !default || self.0.get(&key_path).map_or(true, |e| e.default) {
+                self.0.insert(
+                    key_path.clone(),
+                    ProvenanceEntry {
+                        files: files.to_vec(),
+                        is_list: matches!(value, List(_)),
+                        default,
+                    },
+                );
+            }
+            if let Dict(d) = value {
+                self.add_dict(d, &key_path, files, default);
+            }
+        }
+    }
+
+    /// Merges the provenance of the document merged over (source) into this one (the provenance of
+    /// the document that takes priority), like merge_documents merges the bodies.
+    pub(crate) fn merge_over(&mut self, source: &Provenance) {
+        let mut merged = source.0.clone();
+        for (path, entry) in self.0.drain() {
+            match merged.get_mut(&path) {
+                Some(existing) if entry.default && !existing.default => {}
+                Some(existing) if entry.is_list && existing.is_list && !entry.files.is_empty() => {
+                    for file in entry.files {
+                        if !existing.files.contains(&file) {
+                            existing.files.push(file);
+                        }
+                    }
+                    existing.default = entry.default;
+                }
+                _ => {
+                    merged.insert(path, entry);
+                }
+            }
+        }
+        self.0 = merged;
+    }
+
+    /// The files of the value at path or, if there is no entry for it, of the closest dict
+    /// containing it. Lowest priority first.
+    pub(crate) fn files(&self, path: &str) -> &[String] {
+        self.lookup(path).map_or(&[], |entry| &entry.files)
+    }
+
+    /// The entry for path or, if there is none, for the closest dict containing it.
+    fn lookup(&self, path: &str) -> Option<&ProvenanceEntry> {
+        let mut path = path;
+        loop {
+            if let Some(entry) = self.0.get(path) {
+                return Some(entry);
+            }
+            match path.rsplit_once('.') {
+                None => return None,
+                Some((parent, _)) => path = parent,
+            }
+        }
+    }
+}
+
+/// A document whose values are looked up, with the documents containing it.
+pub(crate) struct Scope<'a> {
+    provenance: &'a Provenance,
+    /// Path of the document in the parent document.
+    path_in_parent: String,
+    parent: Option<&'a Scope<'a>>,
+}
+
+impl<'a> Scope<'a> {
+    /// The scope of a document at path_in_parent in the document of the parent scope (if any).
+    pub(crate) fn new(
+        provenance: &'a Provenance,
+        path_in_parent: &str,
+        parent: Option<&'a Scope<'a>>,
+    ) -> Self {
+        Self {
+            provenance,
+            path_in_parent: path_in_parent.to_string(),
+            parent,
+        }
+    }
+
+    /// The files of the value at path (parts separated by ".") in the document.
+    pub(crate) fn files(&self, path: &str) -> Vec<String> {
+        match self.provenance.lookup(path) {
+            Some(entry) if !entry.files.is_empty() => entry.files.clone(),
+            _ => match self.parent {
+                Some(parent) => parent.files(&join(&self.path_in_parent, path)),
+                None => vec![],
+            },
+        }
+    }
+}
+
+/// The files of the value at path (parts separated by ".") in document, lowest priority first. Like
+/// Scope::files, values of sub-documents without files are looked up in their parents.
+pub(crate) fn document_files(
+    py: Python,
+    document: &Py<YamlConfigDocument>,
+    path: &str,
+) -> Vec<String> {
+    let mut document = document.clone_ref(py);
+    let mut path = path.to_string();
+    loop {
+        let parent = {
+            let doc = document.borrow(py);
+            let files = doc.provenance.files(&path);
+            if !files.is_empty() {
+                return files.to_vec();
+            }
+            match (&doc.parent_doc, &doc.path_in_parent) {
+                (Some(parent), Some(path_in_parent)) => {
+                    path = join(&path_in_parent.replace('/', "."), &path);
+                    parent.clone_ref(py)
+                }
+                _ => return vec![],
+            }
+        };
+        document = parent;
+    }
+}
+
+/// Returns the files of all values (lists are not split up, empty dicts are values) of the document,
+/// including those of sub-documents, by their path. Paths are sorted. Values not loaded from
+/// files have no files.
+pub(crate) fn value_files(
+    py: Python,
+    body: &YcdDict,
+    provenance: &Provenance,
+) -> Vec<(String, Vec<String>)> {
+    let scope = Scope::new(provenance, "", None);
+    let mut out = vec![];
+    collect_dict(py, body, "", "", &scope, &mut out);
+    out.sort_by(|a, b| a.0.cmp(&b.0));
+    out
+}
+
+fn collect_dict(
+    py: Python,
+    dict: &YcdDict,
+    path: &str,
+    path_in_doc: &str,
+    scope: &Scope,
+    out: &mut Vec<(String, Vec<String>)>,
+) {
+    for (key, value) in dict {
+        collect_value(
+            py,
+            value,
+            &join(path, key),
+            &join(path_in_doc, key),
+            scope,
+            out,
+        );
+    }
+}
+
+fn collect_value(
+    py: Python,
+    value: &YcdValueType,
+    path: &str,
+    path_in_doc: &str,
+    scope: &Scope,
+    out: &mut Vec<(String, Vec<String>)>,
+) {
+    match value {
+        Ycd(v) => {
+            let sub = v.borrow(py);
+            let sub_scope = Scope::new(&sub.provenance, path_in_doc, Some(scope));
+            collect_dict(py, &sub.doc, path, "", &sub_scope, out);
+        }
+        Dict(d) if !d.is_empty() => collect_dict(py, d, path, path_in_doc, scope, out),
+        _ => out.push((path.to_string(), scope.files(path_in_doc))),
+    }
+}
+
+/// Whether both paths name the same file.
+pub(crate) fn same_file(a: &str, b: &str) -> bool {
+    let normalize = |p: &str| {
+        Path::new(p)
+            .absolutize()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|_| p.into())
+    };
+    a == b || normalize(a) == normalize(b)
+}
+
+/// The path of file relative to the directory dir, if it is inside of it.
+pub(crate) fn relative_file(file: &str, dir: Option<&str>) -> String {
+    let dir = match dir.and_then(|d| Path::new(d).absolutize().ok()) {
+        None => return file.to_string(),
+        Some(d) => d.to_path_buf(),
+    };
+    match Path::new(file).absolutize() {
+        Ok(f) => match f.strip_prefix(&dir) {
+            Ok(rel) => rel.to_string_lossy().into_owned(),
+            Err(_) => file.to_string(),
+        },
+        Err(_) => file.to_string(),
+    }
+}
+
+pub(crate) fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
@@ -0,0 +1,222 @@
+//! Redaction of secret values in output meant for humans (see ``YamlConfigDocument.secret_paths``).
+use crate::conv::YcdValueType::{Dict, List, YString, Ycd};
+use crate::conv::{dict_to_object, PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::export::document_body;
+use crate::schema::schema_of;
+use pyo3::prelude::*;
+
+/// Replaces redacted values.
+pub(crate) const REDACTED: &str = "****";
+
+/// Returns the patterns of the paths of all secret values of the document and its sub-documents.
+/// Parts are separated by "." and "*" matches any key or list index.
+pub(crate) fn secret_patterns(
+    py: Python,
+    document: &PyYamlConfigDocument,
+) -> PyResult<Vec<String>> {
+    let mut out = vec![];
+    collect_document(py, document, "", &mut out)?;
+    Ok(out)
+}
+
+/// Returns the patterns of the secret values of the body of the document (without those of
+/// sub-documents): The ``secret_paths`` of the document type and the paths of all values with
+/// a ``Secret`` schema.
+pub(crate) fn own_secret_patterns(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    body: &YcdDict,
+) -> PyResult<Vec<String>> {
+    let mut patterns: Vec<String> = document
+        .getattr(py, "secret_paths")?
+        .call0(py)?
+        .extract(py)?;
+    let schema = schema_of(py, document.0.as_ref(py).get_type())?;
+    let paths: Vec<Vec<&PyAny>> = py
+        .import("configcrunch._util")?
+        .getattr("find_secret_paths")?
+        .call1((schema, dict_to_object(py, body)))?
+        .extract()?;
+    for path in paths {
+        patterns.push(
+            path.iter()
+                .map(|part| Ok(part.str()?.to_string()))
+                .collect::<PyResult<Vec<String>>>()?
+                .join("."),
+        );
+    }
+    Ok(patterns)
+}
+
+fn collect_document(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    path: &str,
+    out: &mut Vec<String>,
+) -> PyResult<()> {
+    let body = document_body(py, document)?;
+    for pattern in own_secret_patterns(py, document, &body)? {
+        out.push(join(path, &pattern));
+    }
+    for (key, value) in &body {
+        collect_value(py, value, &join(path, key), out)?;
+    }
+    Ok(())
+}
+
+fn collect_value(
+    py: Python,
+    value: &YcdValueType,
+    path: &str,
+    out: &mut Vec<String>,
+) -> PyResult<()> {
+    match value {
+        Ycd(doc) => collect_document(py, doc, path, out),
+        Dict(d) => {
+            for (key, value) in d {
+                collect_value(py, value, &join(path, key), out)?;
+            }
+            Ok(())
+        }
+        List(l) => {
+            for (i, value) in l.iter().enumerate() {
+                collect_value(py, value, &join(path, &i.to_string()), out)?;
+            }
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Runs f and replaces the secret values of the document in the message of its error with
+/// REDACTED (see redact_error). The values are collected before and after f ran, since f
+/// may change them (e.g. coercions while validating).
+pub(crate) fn with_redacted_errors<T>(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    f: impl FnOnce() -> PyResult<T>,
+) -> PyResult<T> {
+    let mut secrets = secret_values(py, document).unwrap_or_default();
+    f().map_err(|err| {
+        secrets.extend(secret_values(py, document).unwrap_or_default());
+        redact_error(py, err, secrets)
+    })
+}
+
+/// Replaces the secrets (and their representations) in the message of err and its causes
+/// with REDACTED. If that fails, err is returned unchanged.
+pub(crate) fn redact_error(py: Python, err: PyErr, secrets: Vec<PyObject>) -> PyErr {
+    if secrets.is_empty() {
+        return err;
+    }
+    let redacted = py.import("configcrunch._util").and_then(|util| {
+        util.getattr("redact_error")?
+            .call1((err.pvalue(py), secrets, REDACTED))
+    });
+    if let Err(e) = redacted {
+        e.print(py);
+    }
+    err
+}
+
+/// The values at secret paths of the document and its sub-documents. Of secret dicts and lists
+/// all values in them are returned.
+fn secret_values(py: Python, document: &PyYamlConfigDocument) -> PyResult<Vec<PyObject>> {
+    let patterns = secret_patterns(py, document)?;
+    let mut out = vec![];
+    if !patterns.is_empty() {
+        for (key, value) in &document_body(py, document)? {
+            collect_secret_values(py, value, key, &patterns, false, &mut out)?;
+        }
+    }
+    Ok(out)
+}
+
+fn collect_secret_values(
+    py: Python,
+    value: &YcdValueType,
+    path: &str,
+    patterns: &[String],
+    secret: bool,
+    out: &mut Vec<PyObject>,
+) -> PyResult<()> {
+    let secret = secret || is_secret(path, patterns);
+    match value {
+        Ycd(doc) => {
+            for (key, value) in &document_body(py, doc)? {
+                collect_secret_values(py, value, &join(path, key), patterns, secret, out)?;
+            }
+        }
+        Dict(d) => {
+            for (key, value) in d {
+                collect_secret_values(py, value, &join(path, key), patterns, secret, out)?;
+            }
+        }
+        List(l) => {
+            for (i, value) in l.iter().enumerate() {
+                collect_secret_values(
+                    py,
+                    value,
+                    &join(path, &i.to_string()),
+                    patterns,
+                    secret,
+                    out,
+                )?;
+            }
+        }
+        v if secret => out.push(v.to_object(py)),
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Whether the value at path matches one of the patterns.
+pub(crate) fn is_secret(path: &str, patterns: &[String]) -> bool {
+    patterns.iter().any(|pattern| matches_path(path, pattern))
+}
+
+/// Whether path matches the pattern. Parts are separated by "." and "*" matches any part.
+pub(crate) fn matches_path(path: &str, pattern: &str) -> bool {
+    let mut parts = path.split('.');
+    let mut pattern_parts = pattern.split('.');
+    loop {
+        match (parts.next(), pattern_parts.next()) {
+            (None, None) => return true,
+            (Some(part), Some(pattern_part)) if pattern_part == "*" || part == pattern_part => {}
+            _ => return false,
+        }
+    }
+}
+
+/// Replaces all values at paths matching the patterns with REDACTED. The values of
+/// sub-documents are not changed (only sub-documents as a whole are redacted).
+pub(crate) fn redact(value: YcdValueType, path: &str, patterns: &[String]) -> YcdValueType {
+    if !path.is_empty() && is_secret(path, patterns) {
+        return YString(REDACTED.to_string());
+    }
+    match value {
+        Dict(d) => Dict(
+            d.into_iter()
+                .map(|(key, value)| {
+                    let value = redact(value, &join(path, &key), patterns);
+                    (key, value)
+                })
+                .collect(),
+        ),
+        List(l) => List(
+            l.into_iter()
+                .enumerate()
+                .map(|(i, value)| redact(value, &join(path, &i.to_string()), patterns))
+                .collect(),
+        ),
+        v => v,
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        key.to_string()
+    } else {
+        format!("{}.{}", path, key)
+    }
+}
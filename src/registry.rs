@@ -0,0 +1,498 @@
+use crate::conv::YcdValueType::{Dict, YString, Ycd};
+use crate::conv::{YcdDict, YcdValueType};
+use crate::loader::parse_yaml_str;
+use crate::{recursive_docs_to_dicts, InvalidDocumentError, DIRECTIVES, SCHEMA};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::Mutex;
+
+/// Label used in place of a lookup path for registered documents.
+pub(crate) const REGISTERED_DOCUMENTS: &str = "<registered>";
+/// Name of the InterpreterRegistry in the ``_main`` module.
+pub(crate) const INTERPRETER_REGISTRY: &str = "_interpreter_registry";
+
+/// Everything registered with the ``register_*`` functions (templates, documents, schemas, directive
+/// handlers, policies, transformers and the key provider). Python objects must not be shared
+/// between (sub-)interpreters and registrations of one interpreter must not be visible to others, so
+/// they are not stored in statics, but in the ``_main`` module, which every interpreter creates
+/// separately when importing configcrunch.
+#[pyclass(module = "_main")]
+#[derive(Default)]
+pub(crate) struct InterpreterRegistry {
+    /// Templates that can be used with $extends, by name.
+    templates: BTreeMap<String, YcdDict>,
+    /// Documents (file contents, with header) that can be referenced with $ref, by name.
+    documents: BTreeMap<String, YcdDict>,
+    /// Schemas that can be used with $schema, by name.
+    schemas: BTreeMap<String, PyObject>,
+    /// Handlers of user-defined directives, by directive name (with $ prefix).
+    directive_handlers: BTreeMap<String, PyObject>,
+    /// Policies checked after resolving documents, by name.
+    policies: BTreeMap<String, Policy>,
+    /// Functions rewriting the content of loaded files, by name.
+    transformers: BTreeMap<String, Transformer>,
+    /// Encrypts and decrypts encrypted values (see ``register_key_provider``).
+    key_provider: Option<PyObject>,
+}
+
+/// A policy registered with ``register_policy``.
+pub(crate) struct Policy {
+    /// A template expression or a callable.
+    pub(crate) rule: PyObject,
+    /// The document type the policy applies to (None: only the resolved documents themselves).
+    pub(crate) doc_type: Option<PyObject>,
+}
+
+/// A transformer registered with ``register_transformer``.
+struct Transformer {
+    function: PyObject,
+    order: i64,
+    enabled: bool,
+}
+
+/// The InterpreterRegistry of the current interpreter.
+/// Objects removed from it must be dropped after the borrow ends, since dropping them can run
+/// Python code.
+fn interpreter_registry(py: Python) -> PyResult<&PyCell<InterpreterRegistry>> {
+    Ok(py
+        .import("configcrunch._main")?
+        .getattr(INTERPRETER_REGISTRY)?
+        .downcast()?)
+}
+
+/// Templates and documents registered in a ResolutionContext. They are only visible when resolving
+/// with that context and take priority over the globally registered ones.
+#[derive(Debug, Default)]
+pub(crate) struct ContextRegistry {
+    pub(crate) templates: Mutex<BTreeMap<String, YcdDict>>,
+    pub(crate) documents: Mutex<BTreeMap<String, YcdDict>>,
+}
+
+#[pyfunction]
+/// Registers a template under a name, so that documents can extend it with ``$extends: <name>``.
+///
+/// The template is either a dict (the document body, without header) or a YamlConfigDocument.
+/// A template with the same name is replaced. Templates are registered for the current
+/// (sub-)interpreter only.
+pub(crate) fn register_template(py: Python, name: String, template: YcdValueType) -> PyResult<()> {
+    let body = template_body(py, template)?;
+    let _replaced = interpreter_registry(py)?
+        .borrow_mut()
+        .templates
+        .insert(name, body);
+    Ok(())
+}
+
+/// Converts a template passed to ``register_template`` into the body of the template.
+pub(crate) fn template_body(py: Python, template: YcdValueType) -> PyResult<YcdDict> {
+    match recursive_docs_to_dicts(template, py)? {
+        Dict(body) => Ok(body),
+        _ => Err(exceptions::PyTypeError::new_err(
+            "A template must be a dict or a YamlConfigDocument.",
+        )),
+    }
+}
+
+#[pyfunction]
+/// Removes a template registered with ``register_template``. Returns whether it existed.
+pub(crate) fn unregister_template(py: Python, name: &str) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?
+        .borrow_mut()
+        .templates
+        .remove(name);
+    Ok(removed.is_some())
+}
+
+/// Returns a copy of the body of the registered template with the given name.
+/// Templates registered in the context take priority.
+pub(crate) fn get_template(
+    py: Python,
+    name: &str,
+    context: Option<&ContextRegistry>,
+) -> PyResult<Option<YcdDict>> {
+    if let Some(template) = context.and_then(|c| c.templates.lock().unwrap().get(name).cloned()) {
+        return Ok(Some(template));
+    }
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .templates
+        .get(name)
+        .cloned())
+}
+
+#[pyfunction]
+/// Registers a document under a name (a path like ``base/service``), so that documents can reference it
+/// with ``$ref: /base/service`` (or ``$extends``, ``$ref_first`` and ``$import_all``) without it existing as
+/// a file in the lookup paths.
+///
+/// The document is either YAML content (a string), a dict with the same structure as a file (with header)
+/// or a YamlConfigDocument. A document with the same name is replaced.
+///
+/// A registered document is treated like a file in an additional lookup path with the lowest
+/// priority: Files with the same path in the lookup paths are merged over it. Documents are
+/// registered for the current (sub-)interpreter only.
+pub(crate) fn register_document(py: Python, name: &str, document: YcdValueType) -> PyResult<()> {
+    let content = document_content(py, name, document)?;
+    let _replaced = interpreter_registry(py)?
+        .borrow_mut()
+        .documents
+        .insert(document_name(name), content);
+    Ok(())
+}
+
+/// Converts a document passed to ``register_document`` into the content of a file (with header).
+pub(crate) fn document_content(
+    py: Python,
+    name: &str,
+    document: YcdValueType,
+) -> PyResult<YcdDict> {
+    match document {
+        YString(yaml) => parse_yaml_str(&registered_document_path(name), &yaml),
+        Dict(_) => Ok(recursive_docs_to_dicts(document, py)?.unwrap_dict()),
+        Ycd(doc) => {
+            let header: String = doc.getattr(py, "header")?.call0(py)?.extract(py)?;
+            let body = recursive_docs_to_dicts(Ycd(doc), py)?;
+            Ok(YcdDict::from_iter([(header, body)]))
+        }
+        _ => Err(exceptions::PyTypeError::new_err(
+            "A document must be a string (YAML), a dict or a YamlConfigDocument.",
+        )),
+    }
+}
+
+#[pyfunction]
+/// Removes a document registered with ``register_document``. Returns whether it existed.
+pub(crate) fn unregister_document(py: Python, name: &str) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?
+        .borrow_mut()
+        .documents
+        .remove(&document_name(name));
+    Ok(removed.is_some())
+}
+
+/// Returns a copy of the content of the registered document at the path (absolute to the repositories).
+/// Documents registered in the context take priority.
+pub(crate) fn get_document(
+    py: Python,
+    path: &str,
+    context: Option<&ContextRegistry>,
+) -> PyResult<Option<YcdDict>> {
+    let name = document_name(path);
+    if let Some(document) = context.and_then(|c| c.documents.lock().unwrap().get(&name).cloned()) {
+        return Ok(Some(document));
+    }
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .documents
+        .get(&name)
+        .cloned())
+}
+
+/// Whether a document is registered at the path (absolute to the repositories), globally or in the context.
+pub(crate) fn has_document(
+    py: Python,
+    path: &str,
+    context: Option<&ContextRegistry>,
+) -> PyResult<bool> {
+    let name = document_name(path);
+    if context.map_or(false, |c| c.documents.lock().unwrap().contains_key(&name)) {
+        return Ok(true);
+    }
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .documents
+        .contains_key(&name))
+}
+
+/// Names of all registered documents, globally and in the context.
+pub(crate) fn document_names(
+    py: Python,
+    context: Option<&ContextRegistry>,
+) -> PyResult<Vec<String>> {
+    let mut names: BTreeSet<String> = interpreter_registry(py)?
+        .borrow()
+        .documents
+        .keys()
+        .cloned()
+        .collect();
+    if let Some(c) = context {
+        names.extend(c.documents.lock().unwrap().keys().cloned());
+    }
+    Ok(names.into_iter().collect())
+}
+
+/// Path of a registered document, used in place of the path of a file.
+pub(crate) fn registered_document_path(name: &str) -> String {
+    format!("{}/{}", REGISTERED_DOCUMENTS, document_name(name))
+}
+
+pub(crate) fn document_name(name: &str) -> String {
+    name.trim_start_matches('/').to_string()
+}
+
+#[pyfunction]
+/// Registers a schema under a name, so that files can declare ``$schema: <name>`` to be validated
+/// against it directly after loading.
+///
+/// The schema is a ``schema.Schema`` (or any object with a ``validate`` method) or a YamlConfigDocument
+/// class, whose ``schema`` is used. A schema with the same name is replaced.
+/// Schemas are registered for the current (sub-)interpreter only.
+pub(crate) fn register_schema(py: Python, name: String, schema: PyObject) -> PyResult<()> {
+    let _replaced = interpreter_registry(py)?
+        .borrow_mut()
+        .schemas
+        .insert(name, schema);
+    Ok(())
+}
+
+#[pyfunction]
+/// Removes a schema registered with ``register_schema``. Returns whether it existed.
+pub(crate) fn unregister_schema(py: Python, name: &str) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?.borrow_mut().schemas.remove(name);
+    Ok(removed.is_some())
+}
+
+/// Returns the schema registered with the given name. Names are never imported, so files can't
+/// make configcrunch import (and run) arbitrary modules.
+pub(crate) fn get_schema<'py>(py: Python<'py>, name: &str) -> PyResult<&'py PyAny> {
+    let registered = interpreter_registry(py)?
+        .borrow()
+        .schemas
+        .get(name)
+        .map(|schema| schema.clone_ref(py));
+    let schema: &PyAny = match registered {
+        Some(schema) => schema.into_ref(py),
+        None => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "Unknown {}: {}. Register it with register_schema.",
+                SCHEMA, name
+            )))
+        }
+    };
+    if !schema.hasattr("validate")? && schema.hasattr("schema")? {
+        // A YamlConfigDocument class
+        return schema.call_method0("schema");
+    }
+    Ok(schema)
+}
+
+#[pyfunction]
+/// Registers a handler for a user-defined directive, e.g. ``$lookup_dns``.
+///
+/// A dict containing only the directive as key (``{$lookup_dns: example.com}``) is replaced by the
+/// return value of ``handler(value, path, document)`` after the document was merged, where value is the
+/// value of the directive, path is the path of the dict in the document (parts separated by "/") and
+/// document is the YamlConfigDocument that contains it. Directives nested inside the value are
+/// handled first.
+///
+/// Directives are registered for the current (sub-)interpreter only.
+pub(crate) fn register_directive(py: Python, name: &str, handler: PyObject) -> PyResult<()> {
+    let name = directive_name(name);
+    if DIRECTIVES.contains(&name.as_str()) {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "{} is a built-in directive.",
+            name
+        )));
+    }
+    let _replaced = interpreter_registry(py)?
+        .borrow_mut()
+        .directive_handlers
+        .insert(name, handler);
+    Ok(())
+}
+
+#[pyfunction]
+/// Removes a directive registered with ``register_directive``. Returns whether it existed.
+pub(crate) fn unregister_directive(py: Python, name: &str) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?
+        .borrow_mut()
+        .directive_handlers
+        .remove(&directive_name(name));
+    Ok(removed.is_some())
+}
+
+/// Returns the handler of the user-defined directive (with $ prefix), if registered.
+pub(crate) fn get_directive_handler(py: Python, name: &str) -> PyResult<Option<PyObject>> {
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .directive_handlers
+        .get(name)
+        .map(|handler| handler.clone_ref(py)))
+}
+
+/// Whether a user-defined directive (with $ prefix) is registered.
+pub(crate) fn is_registered_directive(py: Python, name: &str) -> PyResult<bool> {
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .directive_handlers
+        .contains_key(name))
+}
+
+/// Whether any user-defined directives are registered.
+pub(crate) fn has_directive_handlers(py: Python) -> PyResult<bool> {
+    Ok(!interpreter_registry(py)?
+        .borrow()
+        .directive_handlers
+        .is_empty())
+}
+
+fn directive_name(name: &str) -> String {
+    format!("${}", name.trim_start_matches('$'))
+}
+
+#[pyfunction]
+#[args(doc_type = "None")]
+/// Registers a policy, that every document must satisfy after its references were resolved
+/// (``resolve_and_merge_references``). Documents violating policies are vetoed with a
+/// PolicyViolationError, that lists all violations.
+///
+/// The rule is either a template expression (evaluated like validation rules, eg.
+/// ``"not (privileged and env == 'prod')"``), that must be true, or a callable, that is called with the
+/// document. It returns None or True if the document satisfies the policy, otherwise False or the
+/// message(s) of the violation (a string or a list of strings).
+///
+/// If ``doc_type`` (a YamlConfigDocument class) is given, the policy applies to all documents of that type,
+/// including sub-documents. Otherwise it only applies to the resolved documents themselves.
+/// A policy with the same name is replaced. Policies are registered for the current (sub-)interpreter only.
+pub(crate) fn register_policy(
+    py: Python,
+    name: String,
+    rule: PyObject,
+    doc_type: Option<PyObject>,
+) -> PyResult<()> {
+    let _replaced = interpreter_registry(py)?
+        .borrow_mut()
+        .policies
+        .insert(name, Policy { rule, doc_type });
+    Ok(())
+}
+
+#[pyfunction]
+/// Removes a policy registered with ``register_policy``. Returns whether it existed.
+pub(crate) fn unregister_policy(py: Python, name: &str) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?.borrow_mut().policies.remove(name);
+    Ok(removed.is_some())
+}
+
+/// Returns all registered policies with their names, sorted by name.
+pub(crate) fn get_policies(py: Python) -> PyResult<Vec<(String, Policy)>> {
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .policies
+        .iter()
+        .map(|(name, policy)| {
+            let policy = Policy {
+                rule: policy.rule.clone_ref(py),
+                doc_type: policy.doc_type.as_ref().map(|t| t.clone_ref(py)),
+            };
+            (name.clone(), policy)
+        })
+        .collect())
+}
+
+#[pyfunction]
+#[args(order = "0", enabled = "true")]
+/// Registers a transformer, that rewrites the content of every loaded file before it is used, e.g. to
+/// normalize legacy layouts, inject defaults or strip vendor sections.
+///
+/// The transformer is called with the content of the file (a dict with the header as key, before the
+/// header is checked) and the absolute path of the file. It returns the new content or None, if it
+/// changed the content in place. Any callable can be used, including functions implemented in Rust.
+///
+/// Transformers run in ascending ``order`` (by name, if equal). Disabled transformers are skipped
+/// (see ``set_transformer_enabled``). A transformer with the same name is replaced.
+/// Transformers are registered for the current (sub-)interpreter only.
+pub(crate) fn register_transformer(
+    py: Python,
+    name: String,
+    transformer: PyObject,
+    order: i64,
+    enabled: bool,
+) -> PyResult<()> {
+    let _replaced = interpreter_registry(py)?.borrow_mut().transformers.insert(
+        name,
+        Transformer {
+            function: transformer,
+            order,
+            enabled,
+        },
+    );
+    Ok(())
+}
+
+#[pyfunction]
+/// Removes a transformer registered with ``register_transformer``. Returns whether it existed.
+pub(crate) fn unregister_transformer(py: Python, name: &str) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?
+        .borrow_mut()
+        .transformers
+        .remove(name);
+    Ok(removed.is_some())
+}
+
+#[pyfunction]
+/// Enables or disables a transformer registered with ``register_transformer``. Returns whether it exists.
+pub(crate) fn set_transformer_enabled(py: Python, name: &str, enabled: bool) -> PyResult<bool> {
+    Ok(
+        match interpreter_registry(py)?
+            .borrow_mut()
+            .transformers
+            .get_mut(name)
+        {
+            Some(transformer) => {
+                transformer.enabled = enabled;
+                true
+            }
+            None => false,
+        },
+    )
+}
+
+/// Returns the enabled transformers with their names, in the order they run.
+pub(crate) fn get_transformers(py: Python) -> PyResult<Vec<(String, PyObject)>> {
+    let registry = interpreter_registry(py)?.borrow();
+    let mut transformers: Vec<(&String, &Transformer)> = registry
+        .transformers
+        .iter()
+        .filter(|(_, t)| t.enabled)
+        .collect();
+    // Stable, so transformers with the same order stay sorted by name.
+    transformers.sort_by_key(|(_, t)| t.order);
+    Ok(transformers
+        .into_iter()
+        .map(|(name, t)| (name.clone(), t.function.clone_ref(py)))
+        .collect())
+}
+
+#[pyfunction]
+/// Registers the key provider for encrypted values (``password: !encrypted AGE-...`` or
+/// ``password: {$encrypted: AGE-...}``). It is an object with the methods ``decrypt(ciphertext) -> str``,
+/// called when the ``value`` of an EncryptedValue is accessed, and ``encrypt(plaintext) -> str``, called
+/// when a new or changed EncryptedValue is stored in a document.
+///
+/// A previously registered key provider is replaced. The key provider is registered for the current
+/// (sub-)interpreter only.
+pub(crate) fn register_key_provider(py: Python, provider: PyObject) -> PyResult<()> {
+    let _replaced = interpreter_registry(py)?
+        .borrow_mut()
+        .key_provider
+        .replace(provider);
+    Ok(())
+}
+
+#[pyfunction]
+/// Removes the key provider registered with ``register_key_provider``. Returns whether one was registered.
+pub(crate) fn unregister_key_provider(py: Python) -> PyResult<bool> {
+    let removed = interpreter_registry(py)?.borrow_mut().key_provider.take();
+    Ok(removed.is_some())
+}
+
+/// Returns the registered key provider, if any.
+pub(crate) fn get_key_provider(py: Python) -> PyResult<Option<PyObject>> {
+    Ok(interpreter_registry(py)?
+        .borrow()
+        .key_provider
+        .as_ref()
+        .map(|provider| provider.clone_ref(py)))
+}
@@ -0,0 +1,392 @@
+//! Typed schemas (``YamlConfigDocument.typed_schema``): Required keys, types and sub-documents,
+//! checked natively after resolving and in ``validate``, with the paths of all invalid keys.
+use crate::conv::YcdValueType::{
+    Bool, Decimal, Dict, Float, ForcedString, Int, List, YString, Ycd,
+};
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::encryption::encrypted_ciphertext;
+use crate::minijinja::is_template;
+use crate::provenance::join;
+use crate::redaction::with_redacted_errors;
+use crate::{DocReference, InvalidDocumentError, YamlConfigDocument};
+use indexmap::IndexMap;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyType};
+
+/// The type of a value in a typed schema.
+#[derive(Clone, Debug)]
+enum SchemaType {
+    Any,
+    Str,
+    Int,
+    /// Ints are floats too.
+    Float,
+    Bool,
+    List(Box<SchemaType>),
+    /// A dict with the given keys.
+    Dict(IndexMap<String, SchemaField>),
+    /// A dict with any keys and values of the given type.
+    Map(Box<SchemaType>),
+    /// A document of the type, validated with its own typed schema.
+    Document(Py<PyType>),
+}
+
+#[derive(Clone, Debug)]
+struct SchemaField {
+    value_type: SchemaType,
+    required: bool,
+}
+
+/// An invalid value: Its path in the document and what's wrong with it.
+type SchemaViolation = (String, String);
+
+impl SchemaType {
+    /// Parses the spec of a value (see ``TypedSchema``).
+    fn parse(py: Python, spec: &PyAny) -> PyResult<Self> {
+        if let Ok(reference) = spec.extract::<PyRef<DocReference>>() {
+            let document = SchemaType::Document(reference.referenced_type.clone_ref(py));
+            return Ok(match reference.container.as_deref() {
+                None => document,
+                Some("list") => SchemaType::List(Box::new(document)),
+                Some(_) => SchemaType::Map(Box::new(document)),
+            });
+        }
+        if let Ok(list) = spec.downcast::<PyList>() {
+            if let (1, Some(entry)) = (list.len(), list.iter().next()) {
+                return Ok(SchemaType::List(Box::new(Self::parse(py, entry)?)));
+            }
+        } else if let Ok(dict) = spec.downcast::<PyDict>() {
+            if let (1, Some(value)) = (dict.len(), dict.get_item(py.get_type::<PyString>())) {
+                return Ok(SchemaType::Map(Box::new(Self::parse(py, value)?)));
+            }
+            return Ok(SchemaType::Dict(parse_fields(py, dict)?));
+        } else if let Ok(ty) = spec.downcast::<PyType>() {
+            let builtins = [
+                (py.get_type::<PyString>(), SchemaType::Str),
+                (py.get_type::<PyBool>(), SchemaType::Bool),
+                (py.get_type::<PyLong>(), SchemaType::Int),
+                (py.get_type::<PyFloat>(), SchemaType::Float),
+                (
+                    py.get_type::<PyList>(),
+                    SchemaType::List(Box::new(SchemaType::Any)),
+                ),
+                (
+                    py.get_type::<PyDict>(),
+                    SchemaType::Map(Box::new(SchemaType::Any)),
+                ),
+                (py.get_type::<PyAny>(), SchemaType::Any),
+            ];
+            if let Some((_, value_type)) = builtins.into_iter().find(|(b, _)| ty.is(*b)) {
+                return Ok(value_type);
+            }
+            if ty.is_subclass::<YamlConfigDocument>()? {
+                return Ok(SchemaType::Document(spec.extract()?));
+            }
+        }
+        Err(exceptions::PyValueError::new_err(format!(
+            "Invalid typed schema: {}. Expected str, int, float, bool, list, dict, object, a \
+             document type, a DocReference, a list with one entry or a dict.",
+            spec.repr()?
+        )))
+    }
+
+    fn name(&self, py: Python) -> String {
+        match self {
+            SchemaType::Any => "any value".to_string(),
+            SchemaType::Str => "str".to_string(),
+            SchemaType::Int => "int".to_string(),
+            SchemaType::Float => "float".to_string(),
+            SchemaType::Bool => "bool".to_string(),
+            SchemaType::List(_) => "list".to_string(),
+            SchemaType::Dict(_) | SchemaType::Map(_) => "dict".to_string(),
+            SchemaType::Document(ty) => {
+                format!("{} document", ty.as_ref(py).name().unwrap_or("a"))
+            }
+        }
+    }
+}
+
+fn parse_fields(py: Python, spec: &PyDict) -> PyResult<IndexMap<String, SchemaField>> {
+    spec.iter()
+        .map(|(key, value)| {
+            let key: String = match key.extract() {
+                Ok(key) => key,
+                Err(_) => {
+                    return Err(exceptions::PyValueError::new_err(format!(
+                        "Invalid typed schema: The keys of dicts must be strings, got: {}",
+                        key.repr()?
+                    )))
+                }
+            };
+            let field = match value.extract::<PyRef<Field>>() {
+                Ok(field) => field.0.clone(),
+                Err(_) => SchemaField {
+                    value_type: SchemaType::parse(py, value)?,
+                    required: true,
+                },
+            };
+            Ok((key, field))
+        })
+        .collect()
+}
+
+/// A typed schema of documents (see ``YamlConfigDocument.typed_schema``). spec is a dict of the keys
+/// of the document body to the specs of their values:
+///
+/// - ``str``, ``int``, ``float`` (also accepts ints), ``bool``, ``list``, ``dict`` or ``object``
+///   (any value).
+/// - A document type or a ``DocReference`` (also with ``list_of`` and ``map_of``): Sub-documents,
+///   that are checked with the typed schema of their own type.
+/// - A list with one entry: A list of values of that spec (``[str]``).
+/// - A dict: A dict with exactly these keys (``{'host': str, 'port': int}``), or with any keys, if
+///   its only key is ``str`` (``{str: int}``).
+/// - ``Field(spec, required=False)``: An optional key. All other keys are required.
+///
+/// Keys that are not part of the spec are invalid, unless ``allow_unknown`` is true. Directives
+/// (keys starting with ``$``) and strings containing templates are never checked.
+///
+///     TypedSchema({
+///         'name': str,
+///         'ports': [int],
+///         'tls': Field({'cert': str, 'key': str}, required=False),
+///         'services': DocReference.map_of(Service),
+///     })
+#[pyclass(module = "_main")]
+pub(crate) struct TypedSchema {
+    fields: IndexMap<String, SchemaField>,
+    #[pyo3(get)]
+    allow_unknown: bool,
+}
+
+#[pymethods]
+impl TypedSchema {
+    #[new]
+    #[args(allow_unknown = "false")]
+    fn new(py: Python, spec: &PyDict, allow_unknown: bool) -> PyResult<Self> {
+        Ok(Self {
+            fields: parse_fields(py, spec)?,
+            allow_unknown,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "TypedSchema(keys={:?}, allow_unknown={})",
+            self.fields.keys().collect::<Vec<_>>(),
+            if self.allow_unknown { "True" } else { "False" }
+        )
+    }
+}
+
+/// A key of a ``TypedSchema`` with the value spec, that may be missing if not required.
+#[pyclass(module = "_main")]
+pub(crate) struct Field(SchemaField);
+
+#[pymethods]
+impl Field {
+    #[new]
+    #[args(required = "true")]
+    fn new(py: Python, spec: &PyAny, required: bool) -> PyResult<Self> {
+        Ok(Self(SchemaField {
+            value_type: SchemaType::parse(py, spec)?,
+            required,
+        }))
+    }
+
+    #[getter]
+    fn required(&self) -> bool {
+        self.0.required
+    }
+
+    fn __repr__(&self, py: Python) -> String {
+        format!(
+            "Field({}, required={})",
+            self.0.value_type.name(py),
+            if self.0.required { "True" } else { "False" }
+        )
+    }
+}
+
+/// The ``schema`` of the document type cls, or None, if the type has a typed schema and doesn't
+/// implement ``schema``. The helpers of ``configcrunch._util`` treat None like a schema without keys.
+pub(crate) fn schema_of<'py>(py: Python<'py>, cls: &'py PyAny) -> PyResult<&'py PyAny> {
+    py.import("configcrunch._util")?
+        .getattr("implemented_schema")?
+        .call1((cls,))
+}
+
+/// Checks the document and its sub-documents against their typed schemas (if their types have
+/// one). Raises an InvalidDocumentError listing the paths of all invalid keys (also available
+/// via its ``violations`` attribute, a list of (path, message) tuples).
+pub(crate) fn enforce_typed_schema(py: Python, document: &PyYamlConfigDocument) -> PyResult<()> {
+    with_redacted_errors(py, document, || check_typed_schema(py, document))
+}
+
+fn check_typed_schema(py: Python, document: &PyYamlConfigDocument) -> PyResult<()> {
+    let mut violations = vec![];
+    check_document(py, document, &document_path(py, document)?, &mut violations)?;
+    if violations.is_empty() {
+        return Ok(());
+    }
+    let class_name: String = document
+        .getattr(py, "__class__")?
+        .getattr(py, "__name__")?
+        .extract(py)?;
+    let err = InvalidDocumentError::new_err(format!(
+        "The {} document does not match its typed schema:\n{}",
+        class_name,
+        violations
+            .iter()
+            .map(|(path, message)| format!("- {}: {}", path, message))
+            .collect::<Vec<String>>()
+            .join("\n")
+    ));
+    err.pvalue(py).setattr("violations", violations)?;
+    Err(err)
+}
+
+/// The path that violations in document start with: The header for documents without a parent,
+/// otherwise the path of the parent (see document_path) and the path of document in it.
+fn document_path(py: Python, document: &PyYamlConfigDocument) -> PyResult<String> {
+    let mut parts = vec![];
+    let mut current: Py<YamlConfigDocument> = document.0.clone_ref(py);
+    loop {
+        let next = {
+            let current = current.borrow(py);
+            match (&current.parent_doc, &current.path_in_parent) {
+                (Some(parent), Some(path)) => Some((parent.clone_ref(py), path.replace('/', "."))),
+                _ => None,
+            }
+        };
+        match next {
+            Some((parent, path)) => {
+                parts.push(path);
+                current = parent;
+            }
+            None => {
+                parts.push(current.getattr(py, "header")?.call0(py)?.extract(py)?);
+                break;
+            }
+        }
+    }
+    parts.reverse();
+    Ok(parts.join("."))
+}
+
+fn check_document(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    path: &str,
+    out: &mut Vec<SchemaViolation>,
+) -> PyResult<()> {
+    let schema = document.getattr(py, "typed_schema")?.call0(py)?;
+    if schema.is_none(py) {
+        return Ok(());
+    }
+    let schema: PyRef<TypedSchema> = schema.extract(py)?;
+    let checks = Checks {
+        py,
+        allow_unknown: schema.allow_unknown,
+    };
+    checks.fields(&document.borrow(py).doc, &schema.fields, path, out)
+}
+
+/// Checks values against the specs of a typed schema.
+#[derive(Clone, Copy)]
+struct Checks<'py> {
+    py: Python<'py>,
+    allow_unknown: bool,
+}
+
+impl Checks<'_> {
+    fn fields(
+        self,
+        dict: &YcdDict,
+        fields: &IndexMap<String, SchemaField>,
+        path: &str,
+        out: &mut Vec<SchemaViolation>,
+    ) -> PyResult<()> {
+        for (key, field) in fields {
+            match dict.get(key) {
+                Some(value) => self.value(value, &field.value_type, &join(path, key), out)?,
+                None if field.required => {
+                    out.push((join(path, key), "The required key is missing.".to_string()))
+                }
+                None => {}
+            }
+        }
+        if !self.allow_unknown {
+            for key in dict.keys() {
+                if !key.starts_with('$') && !fields.contains_key(key) {
+                    out.push((
+                        join(path, key),
+                        "The key is not part of the schema.".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn value(
+        self,
+        value: &YcdValueType,
+        value_type: &SchemaType,
+        path: &str,
+        out: &mut Vec<SchemaViolation>,
+    ) -> PyResult<()> {
+        let valid = match (value_type, value) {
+            // Rendered later, the result is checked by validate.
+            (_, YString(s)) if is_template(s) => true,
+            (_, Dict(d)) if encrypted_ciphertext(d).is_some() => true,
+            (SchemaType::Any, _) => true,
+            (SchemaType::Str, YString(_) | ForcedString(_)) => true,
+            (SchemaType::Int, Int(_)) => true,
+            (SchemaType::Float, Float(_) | Decimal(_) | Int(_)) => true,
+            (SchemaType::Bool, Bool(_)) => true,
+            (SchemaType::List(entry_type), List(list)) => {
+                for (i, entry) in list.iter().enumerate() {
+                    self.value(entry, entry_type, &join(path, &i.to_string()), out)?;
+                }
+                true
+            }
+            (SchemaType::Dict(fields), Dict(dict)) => {
+                self.fields(dict, fields, path, out)?;
+                true
+            }
+            (SchemaType::Map(entry_type), Dict(dict)) => {
+                for (key, entry) in dict.iter().filter(|(k, _)| !k.starts_with('$')) {
+                    self.value(entry, entry_type, &join(path, key), out)?;
+                }
+                true
+            }
+            (SchemaType::Document(ty), Ycd(document)) => {
+                let valid = ty.as_ref(self.py).is_instance(document.0.as_ref(self.py))?;
+                if valid {
+                    check_document(self.py, document, path, out)?;
+                }
+                valid
+            }
+            _ => false,
+        };
+        if !valid {
+            out.push((
+                path.to_string(),
+                format!(
+                    "Expected {}, got: {}",
+                    value_type.name(self.py),
+                    describe(value)
+                ),
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn describe(value: &YcdValueType) -> String {
+    match value {
+        YString(s) | ForcedString(s) => format!("{:?}", s),
+        v => v.to_string(),
+    }
+}
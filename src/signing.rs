@@ -0,0 +1,188 @@
+//! Verification of detached minisign signatures of loaded files (the ``trusted_keys`` loading option).
+use crate::lookup::needs_lookup_dir;
+use crate::virtual_files::VirtualFiles;
+use crate::SignatureError;
+use blake2::{Blake2b512, Digest};
+use ed25519_dalek::{PublicKey, Signature, Verifier};
+use path_absolutize::Absolutize;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// Extension of the signature file next to a signed file.
+pub(crate) const SIGNATURE_EXTENSION: &str = "minisig";
+
+/// The public keys, whose signatures files inside a directory (and its sub-directories) must have.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct TrustedKeys {
+    /// Keys of directories, the most specific directory first.
+    dirs: Arc<Mutex<Vec<(PathBuf, TrustedKey)>>>,
+    /// Keys of lookup paths, that are not directories (see lookup::needs_lookup_dir). They are
+    /// added to dirs, once the lookup path is resolved to its directory (see add_lookup_dir).
+    lookup_paths: Arc<HashMap<String, TrustedKey>>,
+}
+
+#[derive(Clone, Debug)]
+pub(crate) struct TrustedKey {
+    key_id: [u8; 8],
+    key: PublicKey,
+}
+
+impl TrustedKeys {
+    /// Creates the trusted keys from a mapping of directories or lookup paths to minisign public
+    /// keys (either the base64 encoded key or the content of a ``.pub`` file).
+    pub(crate) fn new(keys: HashMap<String, String>) -> PyResult<Self> {
+        let mut dirs = vec![];
+        let mut lookup_paths = HashMap::new();
+        for (dir, key) in keys {
+            let key = TrustedKey::parse(&key).map_err(|e| {
+                exceptions::PyValueError::new_err(format!("Invalid public key for {}: {}", dir, e))
+            })?;
+            if needs_lookup_dir(&dir) {
+                lookup_paths.insert(dir, key);
+            } else {
+                dirs.push((normalize(Path::new(&dir)), key));
+            }
+        }
+        sort_dirs(&mut dirs);
+        Ok(Self {
+            dirs: Arc::new(Mutex::new(dirs)),
+            lookup_paths: Arc::new(lookup_paths),
+        })
+    }
+
+    /// Whether files of the lookup path (that is not a directory) must be signed.
+    pub(crate) fn has_lookup_path(&self, lookup_path: &str) -> bool {
+        self.lookup_paths.contains_key(lookup_path)
+    }
+
+    /// Called when a lookup path (that is not a directory) was resolved to the directory dir: The
+    /// files in dir must then be signed with the key of the lookup path.
+    pub(crate) fn add_lookup_dir(&self, lookup_path: &str, dir: &str) {
+        if let Some(key) = self.lookup_paths.get(lookup_path) {
+            let dir = normalize(Path::new(dir));
+            let mut dirs = self.dirs.lock().unwrap();
+            if !dirs.iter().any(|(d, _)| d == &dir) {
+                dirs.push((dir, key.clone()));
+                sort_dirs(&mut dirs);
+            }
+        }
+    }
+
+    /// Raises a SignatureError, if the file at path is inside a directory with a trusted key and
+    /// doesn't have a valid signature of that key. content is the content of the file, which must be
+    /// the content that is then parsed. The signature is read from ``<path>.minisig``.
+    pub(crate) fn verify(
+        &self,
+        path: &str,
+        content: &[u8],
+        virtual_files: &VirtualFiles,
+    ) -> PyResult<()> {
+        let normalized = normalize(Path::new(path));
+        let key = match self
+            .dirs
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(dir, _)| normalized.starts_with(dir))
+        {
+            None => return Ok(()),
+            Some((_, key)) => key.clone(),
+        };
+        let signature_path = format!("{}.{}", path, SIGNATURE_EXTENSION);
+        let signature = match read(&signature_path, virtual_files) {
+            Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+            Err(_) => {
+                return Err(SignatureError::new_err(format!(
+                    "{} is not signed: {} not found.",
+                    path, signature_path
+                )))
+            }
+        };
+        key.verify(content, &signature)
+            .map_err(|e| SignatureError::new_err(format!("Invalid signature of {}: {}", path, e)))
+    }
+}
+
+/// Sorts the keys of directories, so that the most specific directory wins.
+fn sort_dirs(dirs: &mut [(PathBuf, TrustedKey)]) {
+    dirs.sort_by_key(|(dir, _)| std::cmp::Reverse(dir.components().count()));
+}
+
+impl TrustedKey {
+    fn parse(key: &str) -> Result<Self, String> {
+        let encoded = key
+            .lines()
+            .map(str::trim)
+            .find(|l| !l.is_empty() && !l.starts_with("untrusted comment:"))
+            .ok_or("The key is empty.")?;
+        let bytes = decode(encoded, 42)?;
+        if &bytes[0..2] != b"Ed" {
+            return Err("Not an Ed25519 key.".to_string());
+        }
+        let mut key_id = [0; 8];
+        key_id.copy_from_slice(&bytes[2..10]);
+        let key = PublicKey::from_bytes(&bytes[10..]).map_err(|e| e.to_string())?;
+        Ok(Self { key_id, key })
+    }
+
+    /// Verifies content against the content of a minisign signature file: The signature of the
+    /// content and the global signature of the trusted comment.
+    fn verify(&self, content: &[u8], signature_file: &str) -> Result<(), String> {
+        let lines: Vec<&str> = signature_file.lines().map(str::trim).collect();
+        if lines.len() < 4 || !lines[0].starts_with("untrusted comment:") {
+            return Err("Not a minisign signature.".to_string());
+        }
+        let signature = decode(lines[1], 74)?;
+        if signature[2..10] != self.key_id {
+            return Err("Signed with a different key.".to_string());
+        }
+        let signed = match &signature[0..2] {
+            b"Ed" => content.to_vec(),
+            b"ED" => Blake2b512::digest(content).to_vec(),
+            _ => return Err("Unsupported signature algorithm.".to_string()),
+        };
+        self.check(&signed, &signature[10..])?;
+
+        let trusted_comment = lines[2]
+            .strip_prefix("trusted comment: ")
+            .ok_or("The trusted comment is missing.")?;
+        let global_signature = decode(lines[3], 64)?;
+        let mut signed = signature[10..].to_vec();
+        signed.extend_from_slice(trusted_comment.as_bytes());
+        self.check(&signed, &global_signature)
+            .map_err(|_| "The trusted comment was changed.".to_string())
+    }
+
+    fn check(&self, message: &[u8], signature: &[u8]) -> Result<(), String> {
+        let signature = Signature::from_bytes(signature).map_err(|e| e.to_string())?;
+        self.key
+            .verify(message, &signature)
+            .map_err(|_| "The content doesn't match the signature.".to_string())
+    }
+}
+
+fn decode(encoded: &str, len: usize) -> Result<Vec<u8>, String> {
+    let bytes = base64::decode(encoded).map_err(|e| format!("Invalid base64: {}", e))?;
+    if bytes.len() != len {
+        return Err(format!("Expected {} bytes, got {}.", len, bytes.len()));
+    }
+    Ok(bytes)
+}
+
+fn read(path: &str, virtual_files: &VirtualFiles) -> std::io::Result<Vec<u8>> {
+    match virtual_files.get(path) {
+        Some(content) => Ok(content.as_bytes().to_vec()),
+        None => fs::read(path),
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    match path.absolutize() {
+        Ok(p) => p.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    }
+}
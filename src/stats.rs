@@ -0,0 +1,130 @@
+//! Statistics of loading, resolving and processing a document (``merge_stats``), e.g. to publish
+//! them as metrics.
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Stat {
+    /// A file was loaded.
+    DocumentsLoaded,
+    /// A $ref was resolved.
+    RefsResolved,
+    /// A value was replaced by the value of a document merged over it.
+    KeysOverridden,
+    /// A key was removed with $remove.
+    KeysRemoved,
+    /// An entry of a list was merged into the list of the document merged over.
+    ListEntriesMerged,
+    /// A template was rendered.
+    TemplatesRendered,
+    /// A sub-document was taken from the identity map instead of loading it again.
+    CacheHits,
+}
+
+const STATS: [Stat; 7] = [
+    Stat::DocumentsLoaded,
+    Stat::RefsResolved,
+    Stat::KeysOverridden,
+    Stat::KeysRemoved,
+    Stat::ListEntriesMerged,
+    Stat::TemplatesRendered,
+    Stat::CacheHits,
+];
+
+impl Stat {
+    fn name(self) -> &'static str {
+        match self {
+            Stat::DocumentsLoaded => "documents_loaded",
+            Stat::RefsResolved => "refs_resolved",
+            Stat::KeysOverridden => "keys_overridden",
+            Stat::KeysRemoved => "keys_removed",
+            Stat::ListEntriesMerged => "list_entries_merged",
+            Stat::TemplatesRendered => "templates_rendered",
+            Stat::CacheHits => "cache_hits",
+        }
+    }
+}
+
+/// The counters of a document. Clones share the counts.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Stats(Arc<[AtomicUsize; 7]>);
+
+impl Stats {
+    pub(crate) fn add(&self, stat: Stat, count: usize) {
+        self.0[stat as usize].fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> MergeStats {
+        MergeStats(STATS.map(|stat| self.0[stat as usize].load(Ordering::Relaxed)))
+    }
+}
+
+/// Statistics of loading, resolving and processing a document (see ``merge_stats``). The counts
+/// are the totals of all calls so far:
+///
+/// - ``documents_loaded``: Files loaded (the document itself and all referenced files).
+/// - ``refs_resolved``: $refs resolved (also those of referenced documents and sub-documents).
+/// - ``keys_overridden``: Values replaced by the value of a document merged over them.
+/// - ``keys_removed``: Keys removed with $remove.
+/// - ``list_entries_merged``: Entries of lists merged into the list of a document merged over.
+/// - ``templates_rendered``: Templates rendered by ``process_vars``.
+/// - ``cache_hits``: Sub-documents taken from the identity map (see the ``identity_map`` loading option).
+#[pyclass(module = "_main")]
+pub(crate) struct MergeStats([usize; 7]);
+
+#[pymethods]
+impl MergeStats {
+    #[getter]
+    fn documents_loaded(&self) -> usize {
+        self.0[Stat::DocumentsLoaded as usize]
+    }
+
+    #[getter]
+    fn refs_resolved(&self) -> usize {
+        self.0[Stat::RefsResolved as usize]
+    }
+
+    #[getter]
+    fn keys_overridden(&self) -> usize {
+        self.0[Stat::KeysOverridden as usize]
+    }
+
+    #[getter]
+    fn keys_removed(&self) -> usize {
+        self.0[Stat::KeysRemoved as usize]
+    }
+
+    #[getter]
+    fn list_entries_merged(&self) -> usize {
+        self.0[Stat::ListEntriesMerged as usize]
+    }
+
+    #[getter]
+    fn templates_rendered(&self) -> usize {
+        self.0[Stat::TemplatesRendered as usize]
+    }
+
+    #[getter]
+    fn cache_hits(&self) -> usize {
+        self.0[Stat::CacheHits as usize]
+    }
+
+    /// All counts, by their name.
+    fn to_dict<'p>(&self, py: Python<'p>) -> PyResult<&'p PyDict> {
+        let dict = PyDict::new(py);
+        for stat in STATS {
+            dict.set_item(stat.name(), self.0[stat as usize])?;
+        }
+        Ok(dict)
+    }
+
+    fn __repr__(&self) -> String {
+        let counts: Vec<String> = STATS
+            .iter()
+            .map(|&stat| format!("{}={}", stat.name(), self.0[stat as usize]))
+            .collect();
+        format!("MergeStats({})", counts.join(", "))
+    }
+}
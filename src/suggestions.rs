@@ -0,0 +1,130 @@
+//! "Did you mean" suggestions for unknown keys and invalid headers.
+use crate::conv::YcdDict;
+use crate::InvalidHeaderError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyType};
+use std::collections::{BTreeMap, HashMap};
+
+/// Maximum number of suggestions for a name.
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Candidates that are close to name (by edit distance, ignoring case), closest first.
+/// A candidate is close if at most a third of the characters of name (but at least one) differ.
+pub(crate) fn did_you_mean<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let name_lower = name.to_lowercase();
+    let max_distance = (name.chars().count() / 3).max(1);
+    let mut close: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|c| *c != name)
+        .map(|c| (edit_distance(&name_lower, &c.to_lowercase()), c))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+    close.sort();
+    close.dedup_by(|a, b| a.1 == b.1);
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c.to_string())
+        .collect()
+}
+
+/// The message suffix for suggestions: `` (did you mean a or b?)``, empty if there are none.
+pub(crate) fn suggestion_hint(suggestions: &[String]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [single] => format!(" (did you mean {}?)", single),
+        [first @ .., last] => format!(" (did you mean {} or {}?)", first.join(", "), last),
+    }
+}
+
+/// Sets the ``suggestions`` attribute of the exception: A dict of the unknown names to their
+/// suggestions (only names with suggestions).
+pub(crate) fn with_suggestions(
+    py: Python,
+    err: PyErr,
+    suggestions: &BTreeMap<String, Vec<String>>,
+) -> PyErr {
+    let dict = PyDict::new(py);
+    for (name, names) in suggestions {
+        if !names.is_empty() {
+            dict.set_item(name, names).ok();
+        }
+    }
+    err.pvalue(py).setattr("suggestions", dict).ok();
+    err
+}
+
+/// An InvalidHeaderError for the entire document dict (that doesn't contain the header of cls).
+/// The keys of dict, that are close to the header (or an alias) of cls or are the header of another
+/// document type, are listed after message with suggestions.
+pub(crate) fn invalid_header_error(
+    py: Python,
+    cls: &PyType,
+    dict: &YcdDict,
+    message: String,
+) -> PyResult<PyErr> {
+    let header: String = cls.getattr("header")?.call0()?.extract()?;
+    let aliases: Vec<String> = cls.getattr("header_aliases")?.call0()?.extract()?;
+    let other_types: HashMap<String, String> = py
+        .import("configcrunch._util")?
+        .getattr("document_headers")?
+        .call0()?
+        .extract()?;
+    let mut found = vec![];
+    let mut suggestions = BTreeMap::new();
+    for key in dict.keys().filter(|k| !k.starts_with('$')) {
+        let close = did_you_mean(
+            key,
+            [header.as_str()]
+                .into_iter()
+                .chain(aliases.iter().map(String::as_str)),
+        );
+        match other_types.get(key) {
+            Some(type_name) if close.is_empty() => {
+                found.push(format!("{} (the header of {} documents)", key, type_name))
+            }
+            _ => found.push(format!("{}{}", key, suggestion_hint(&close))),
+        }
+        suggestions.insert(key.clone(), close);
+    }
+    let message = match (found.is_empty(), message.ends_with('.')) {
+        (true, _) => message,
+        (false, true) => format!("{} Found: {}", message, found.join(", ")),
+        (false, false) => format!("{}. Found: {}", message, found.join(", ")),
+    };
+    Ok(with_suggestions(
+        py,
+        InvalidHeaderError::new_err(message),
+        &suggestions,
+    ))
+}
+
+/// Number of single character insertions, deletions, substitutions and transpositions of adjacent
+/// characters to change a into b (optimal string alignment distance).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // d[i][j]: Distance between the first i characters of a and the first j characters of b.
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in d[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
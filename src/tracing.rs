@@ -0,0 +1,49 @@
+use pyo3::prelude::*;
+use pyo3::types::IntoPyDict;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Number of interpreters, that enabled tracing. Python is only asked whether tracing is enabled
+/// (and the span attributes are only built), if any did.
+static TRACING_INTERPRETERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Called by ``configcrunch._tracing``, when tracing is enabled or disabled in this interpreter.
+#[pyfunction]
+pub(crate) fn set_tracing_enabled(enabled: bool) {
+    if enabled {
+        TRACING_INTERPRETERS.fetch_add(1, Ordering::Relaxed);
+    } else {
+        let _ = TRACING_INTERPRETERS
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1));
+    }
+}
+
+/// Runs f inside of an OpenTelemetry span, if tracing was enabled from Python
+/// (see ``configcrunch._tracing``). Errors returned by f are recorded on the span.
+/// attributes is only called, if a span is created.
+pub(crate) fn with_span<T, A, F>(py: Python, name: &str, attributes: A, f: F) -> PyResult<T>
+where
+    A: FnOnce() -> PyResult<Vec<(&'static str, String)>>,
+    F: FnOnce() -> PyResult<T>,
+{
+    if TRACING_INTERPRETERS.load(Ordering::Relaxed) == 0 {
+        return f();
+    }
+    let tracing = py.import("configcrunch._tracing")?;
+    if !tracing.getattr("is_enabled")?.call0()?.is_true()? {
+        return f();
+    }
+    let span = tracing
+        .getattr("span")?
+        .call1((name, attributes()?.into_py_dict(py)))?;
+    span.call_method0("__enter__")?;
+    let result = f();
+    let exited = match &result {
+        Ok(_) => span.call_method1("__exit__", (py.None(), py.None(), py.None())),
+        Err(e) => span.call_method1("__exit__", (e.ptype(py), e.pvalue(py), e.ptraceback(py))),
+    };
+    match (result, exited) {
+        (Ok(_), Err(e)) => Err(e),
+        // The error of f is more helpful than one of exiting the span.
+        (result, _) => result,
+    }
+}
@@ -0,0 +1,144 @@
+//! Schema validation while loading (``validation`` loading option): Of every layer (loaded file)
+//! before it is merged, of the merged document after resolving, or both.
+use crate::conv::YcdValueType::{Dict, List, YString};
+use crate::conv::{dict_to_object, PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::encryption::encrypted_ciphertext;
+use crate::merger::{index_directive, insert_directive};
+use crate::minijinja::is_template;
+use crate::options::LoadOptions;
+use crate::redaction::with_redacted_errors;
+use crate::schema::schema_of;
+use crate::{
+    LayerValidationError, MergedValidationError, SchemaError, YamlConfigDocument, REMOVE,
+    REMOVE_FROM_LIST_PREFIX,
+};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+
+/// When documents are validated while loading.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct ValidationPhases {
+    /// Every loaded file, before it is merged.
+    pub(crate) layers: bool,
+    /// The document after resolving (``resolve_and_merge_references``).
+    pub(crate) merged: bool,
+}
+
+impl ValidationPhases {
+    pub(crate) fn parse(phases: Option<&str>) -> PyResult<Self> {
+        let (layers, merged) = match phases {
+            None => (false, false),
+            Some("layers") => (true, false),
+            Some("merged") => (false, true),
+            Some("both") => (true, true),
+            Some(other) => {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "Invalid value for validation: {}. Expected 'layers', 'merged' or 'both'.",
+                    other
+                )))
+            }
+        };
+        Ok(Self { layers, merged })
+    }
+}
+
+/// Validates the body of a document loaded from file on its own, if layers are validated.
+/// All keys of the schema are optional, since other layers may set them. Directives, removed
+/// values and values containing templates are not validated. Raises a LayerValidationError.
+pub(crate) fn validate_layer(
+    py: Python,
+    doc: &Py<YamlConfigDocument>,
+    file: &str,
+    options: &LoadOptions,
+) -> PyResult<()> {
+    if !options.validation.layers {
+        return Ok(());
+    }
+    let document: PyYamlConfigDocument = doc.clone_ref(py).into();
+    with_redacted_errors(py, &document, || {
+        let body = layer_body(&doc.borrow(py).doc);
+        let schema = schema_of(py, doc.as_ref(py).get_type())?;
+        if schema.is_none() {
+            return Ok(());
+        }
+        py.import("configcrunch._util")?
+            .getattr("partial_schema")?
+            .call1((schema,))?
+            .call_method1("validate", (dict_to_object(py, &body),))
+            .map_err(|e| {
+                validation_error(py, e, |msg| {
+                    LayerValidationError::new_err(format!("The layer {} is invalid: {}", file, msg))
+                })
+            })?;
+        Ok(())
+    })
+}
+
+/// Validates the resolved document (see ``YamlConfigDocument.validate``), if merged documents are
+/// validated. Raises a MergedValidationError.
+pub(crate) fn validate_merged(
+    py: Python,
+    doc: &Py<YamlConfigDocument>,
+    options: &LoadOptions,
+) -> PyResult<()> {
+    if !options.validation.merged {
+        return Ok(());
+    }
+    let path = doc
+        .borrow(py)
+        .absolute_paths
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "?".to_string());
+    YamlConfigDocument::validate(doc.as_ref(py), py, None).map_err(|e| {
+        validation_error(py, e, |msg| {
+            MergedValidationError::new_err(format!(
+                "The merged document {} is invalid: {}",
+                path, msg
+            ))
+        })
+    })?;
+    Ok(())
+}
+
+/// Converts a SchemaError into the error created by new_err (with the message of the SchemaError).
+/// The SchemaError is its cause. Other errors are returned as they are.
+fn validation_error(py: Python, err: PyErr, new_err: impl FnOnce(String) -> PyErr) -> PyErr {
+    if !err.is_instance::<SchemaError>(py) {
+        return err;
+    }
+    let cause = err.pvalue(py);
+    let msg = cause.str().map(|s| s.to_string()).unwrap_or_default();
+    let new = new_err(msg);
+    new.pvalue(py).setattr("__cause__", cause).ok();
+    new
+}
+
+/// The body of a layer without directives, values removed with $remove and $remove::,
+/// $insert_before / $insert_after entries and values containing templates.
+fn layer_body(body: &YcdDict) -> YcdDict {
+    body.iter()
+        .filter(|(k, v)| !k.starts_with('$') && !is_marker(v))
+        .map(|(k, v)| (k.clone(), layer_value(v)))
+        .collect()
+}
+
+fn layer_value(value: &YcdValueType) -> YcdValueType {
+    match value {
+        Dict(d) if encrypted_ciphertext(d).is_none() => Dict(layer_body(d)),
+        List(l) => List(
+            l.iter()
+                .filter(|v| {
+                    !is_marker(v) && insert_directive(v).is_none() && index_directive(v).is_none()
+                })
+                .map(layer_value)
+                .collect(),
+        ),
+        v => v.clone(),
+    }
+}
+
+/// Whether the value is a $remove marker or contains a template.
+fn is_marker(value: &YcdValueType) -> bool {
+    matches!(value, YString(s) if s == REMOVE || s.starts_with(REMOVE_FROM_LIST_PREFIX) || is_template(s))
+}
@@ -1,69 +1,167 @@
-use crate::conv::YcdValueType::{Dict, Int, List, YString, Ycd};
-use crate::conv::{PyYamlConfigDocument, YcdValueType};
-use crate::minijinja::TemplateRenderer;
-use crate::variables::DocumentTraverserCallbackType::{CurrentDoc, SubDoc};
-use crate::{VariableProcessingError, FORCE_STRING};
-use pyo3::{exceptions, PyAny, PyObject, PyResult, Python, ToPyObject};
+use crate::audit::AuditEntry;
+use crate::conv::YcdValueType::{Bool, Dict, ForcedString, Int, List, YString, Ycd};
+use crate::conv::{rename_key, PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::interpolation::interpolate_compose;
+use crate::merger::evaluate_condition;
+use crate::minijinja::{is_template, ItemPathPart, TemplateRenderer, UsedName};
+use crate::nested::visit_mut;
+use crate::options::VariableOptions;
+use crate::progress::ProgressEvent;
+use crate::redaction::secret_patterns;
+use crate::stats::Stat;
+use crate::tracing::with_span;
+use crate::variables::DocumentTraverserCallbackType::{ComposeInterpolation, CurrentDoc, SubDoc};
+use crate::{
+    InvalidDocumentError, ResolutionCancelledError, VariableProcessingError, YamlConfigDocument,
+    REMOVE_IF, VARS,
+};
+use pyo3::{exceptions, PyAny, PyErr, PyObject, PyResult, Python, ToPyObject};
+use std::cell::RefCell;
+use std::mem::take;
+use std::sync::{Arc, Mutex};
 
 struct DocumentTraverser;
 
-enum DocumentTraverserCallbackType {
-    SubDoc,
-    CurrentDoc(PyYamlConfigDocument),
+/// Errors of sub-documents (by path in the document), if processing with ``collect_errors``.
+type SubdocErrors = RefCell<Vec<(String, PyErr)>>;
+
+enum DocumentTraverserCallbackType<'a> {
+    SubDoc(&'a VariableOptions, &'a SubdocErrors),
+    CurrentDoc(PyYamlConfigDocument, &'a VariableOptions),
+    ComposeInterpolation(PyYamlConfigDocument),
 }
 
 impl DocumentTraverser {
-    pub(crate) fn run_subdoc_callback(py: Python, subdoc: &mut YcdValueType) -> PyResult<bool> {
-        Self::traverse(py, &SubDoc, subdoc)
+    pub(crate) fn run_subdoc_callback(
+        py: Python,
+        subdoc: &mut YcdValueType,
+        options: &VariableOptions,
+        errors: &SubdocErrors,
+    ) -> PyResult<bool> {
+        Self::traverse(py, &SubDoc(options, errors), subdoc, &mut vec![])
+    }
+
+    pub(crate) fn run_compose_interpolation_callback(
+        py: Python,
+        subdoc: &mut YcdValueType,
+        document: PyYamlConfigDocument,
+    ) -> PyResult<bool> {
+        Self::traverse(py, &ComposeInterpolation(document), subdoc, &mut vec![])
     }
 
+    /// root is the path of subdoc in the document (for the ``item_*`` template functions).
+    /// Rendering is aborted when the deadline of the options is reached.
     pub(crate) fn run_current_doc_callback(
         py: Python,
         subdoc: &mut YcdValueType,
         document: PyYamlConfigDocument,
+        mut root: Vec<ItemPathPart>,
+        options: &VariableOptions,
     ) -> PyResult<bool> {
-        Self::traverse(py, &CurrentDoc(document), subdoc)
+        Self::traverse(py, &CurrentDoc(document, options), subdoc, &mut root)
     }
 
+    /// path is the path of input_node. Returns whether any value was changed.
     fn traverse(
         py: Python,
         callback_type: &DocumentTraverserCallbackType,
         input_node: &mut YcdValueType,
+        path: &mut Vec<ItemPathPart>,
     ) -> PyResult<bool> {
-        match input_node {
-            Dict(in_dict) => {
-                let mut changed = false;
-                for v in in_dict.values_mut() {
-                    changed |= Self::traverse(py, callback_type, v)?;
-                }
-                Ok(changed)
+        let mut changed = false;
+        visit_mut(input_node, path, |value, path| {
+            if !matches!(value, Dict(_) | List(_)) {
+                changed |= Self::process_value(py, callback_type, value, path)?;
             }
-            List(in_list) => {
-                let mut changed = false;
-                for v in in_list.iter_mut() {
-                    changed |= Self::traverse(py, callback_type, v)?;
-                }
-                Ok(changed)
+            Ok(())
+        })?;
+        Ok(changed)
+    }
+
+    /// Processes a value that is not a dict or list.
+    fn process_value(
+        py: Python,
+        callback_type: &DocumentTraverserCallbackType,
+        input_node: &mut YcdValueType,
+        path: &[ItemPathPart],
+    ) -> PyResult<bool> {
+        match callback_type {
+            SubDoc(options, errors) => {
+                Self::process_variables_for_subdoc(py, input_node, options, errors, path)
+            }
+            CurrentDoc(base, options) => {
+                options.deadline.check()?;
+                Self::process_variables_current_doc(
+                    py,
+                    input_node,
+                    base.clone_ref(py),
+                    path,
+                    options,
+                )
+            }
+            ComposeInterpolation(base) => {
+                Self::process_compose_interpolation(py, input_node, base.clone_ref(py))
             }
-            _ => match callback_type {
-                SubDoc => Self::process_variables_for_subdoc(py, input_node),
-                CurrentDoc(base) => {
-                    Self::process_variables_current_doc(py, input_node, base.clone_ref(py))
-                }
-            },
         }
     }
 
-    fn process_variables_for_subdoc(py: Python, input_node: &mut YcdValueType) -> PyResult<bool> {
+    fn process_variables_for_subdoc(
+        py: Python,
+        input_node: &mut YcdValueType,
+        options: &VariableOptions,
+        errors: &SubdocErrors,
+        path: &[ItemPathPart],
+    ) -> PyResult<bool> {
         match input_node {
             Ycd(in_ycd) => {
-                process_variables(py, in_ycd.clone_ref(py))?;
+                if !options.collect_errors {
+                    process_variables(py, in_ycd.clone_ref(py), options)?;
+                    return Ok(true);
+                }
+                let location = format_item_path(path);
+                let nested = RefCell::new(vec![]);
+                let result =
+                    process_variables_collecting(py, in_ycd.clone_ref(py), options, &nested);
+                let mut errors = errors.borrow_mut();
+                for (nested_location, e) in nested.into_inner() {
+                    errors.push((format!("{}.{}", location, nested_location), e));
+                }
+                match result {
+                    // Cancellation aborts processing, also when collecting errors.
+                    Err(e) if e.is_instance::<ResolutionCancelledError>(py) => return Err(e),
+                    Err(e) => errors.push((location, e)),
+                    Ok(()) => {}
+                }
                 Ok(true)
             }
             _ => Ok(false),
         }
     }
 
+    /// Interpolates Docker Compose style variables in strings.
+    fn process_compose_interpolation(
+        py: Python,
+        input_node: &mut YcdValueType,
+        document: PyYamlConfigDocument,
+    ) -> PyResult<bool> {
+        match input_node {
+            YString(in_str) => match interpolate_compose(in_str) {
+                Ok(new_value) => {
+                    let changed = &new_value != in_str;
+                    *in_str = new_value;
+                    Ok(changed)
+                }
+                Err(e) => Err(VariableProcessingError::new_err(format!(
+                    "Error interpolating a variable for document: {}. Original value was {}. Document path: {}.",
+                    e,
+                    in_str,
+                    document.borrow(py).absolute_paths.first().map(String::as_str).unwrap_or("?")
+                ))),
+            },
+            _ => Ok(false),
+        }
+    }
+
     /// Recursive process variables
     /// The input node is changed in place immediately for dict entries and after processing
     /// the entire list for list entries.
@@ -72,18 +170,30 @@ impl DocumentTraverser {
         py: Python,
         input_node: &mut YcdValueType,
         document: PyYamlConfigDocument,
+        path: &[ItemPathPart],
+        options: &VariableOptions,
     ) -> PyResult<bool> {
         match input_node {
             YString(in_str) => {
-                match apply_variable_resolution(
-                    py,
-                    in_str,
-                    TemplateRenderer::new(document.clone_ref(py))?,
-                ) {
+                let mut renderer = TemplateRenderer::new(document.clone_ref(py))?.at(path);
+                let audited = options.audit && is_template(in_str);
+                let used_names = audited.then(|| renderer.record_names());
+                match apply_variable_resolution(py, in_str, renderer) {
                     Ok(opt_new_value) => {
                         if let Some(new_value) = opt_new_value {
+                            options.stats.add(Stat::TemplatesRendered, 1);
+                            options.progress.report(
+                                py,
+                                ProgressEvent::TemplateRendered,
+                                Some(&format_item_path(path)),
+                            )?;
+                            if let Some(used_names) = used_names {
+                                record_template(
+                                    py, &document, path, in_str, &new_value, used_names,
+                                );
+                            }
                             let mut changed = false;
-                            if let YString(snv) = &new_value {
+                            if let YString(snv) | ForcedString(snv) = &new_value {
                                 changed = snv != in_str;
                             }
                             *input_node = new_value;
@@ -116,15 +226,14 @@ fn apply_variable_resolution<'env>(
     template_renderer: TemplateRenderer<'env>,
 ) -> PyResult<Option<YcdValueType>> {
     match template_renderer.render(py, input_str) {
-        Ok(opt_result) => Ok(opt_result.map(|result| {
-            if input_str != result {
+        Ok(opt_result) => Ok(opt_result.map(|rendered| {
+            if rendered.forced_string {
+                ForcedString(rendered.output)
+            } else if input_str != rendered.output {
                 // Allow parsed ints to be read as such
-                match result.strip_prefix(FORCE_STRING) {
-                    None => match result.parse::<i64>().ok() {
-                        None => YString(result),
-                        Some(parsed) => Int(parsed),
-                    },
-                    Some(stripped) => YString(stripped.to_string()),
+                match rendered.output.parse::<i64>().ok() {
+                    None => YString(rendered.output),
+                    Some(parsed) => Int(parsed),
                 }
             } else {
                 YString(input_str.to_string())
@@ -137,25 +246,409 @@ fn apply_variable_resolution<'env>(
     }
 }
 
+/// Adds a rendered template to the audit log of the document. Secret values are redacted.
+fn record_template(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    path: &[ItemPathPart],
+    template: &str,
+    result: &YcdValueType,
+    used_names: Arc<Mutex<Vec<UsedName>>>,
+) {
+    let used_names = take(&mut *used_names.lock().unwrap());
+    let mut bow = document.borrow_mut(py);
+    let entry = AuditEntry::redacted(path, template, result, used_names, &bow.audit_secrets);
+    bow.template_audit.push(entry);
+}
+
 /// Process all variables in a document
-pub(crate) fn process_variables(py: Python, ycd: PyYamlConfigDocument) -> PyResult<()> {
+pub(crate) fn process_variables(
+    py: Python,
+    ycd: PyYamlConfigDocument,
+    options: &VariableOptions,
+) -> PyResult<()> {
+    let errors = RefCell::new(vec![]);
+    process_variables_collecting(py, ycd, options, &errors)?;
+    let errors = errors.into_inner();
+    if errors.is_empty() {
+        return Ok(());
+    }
+    let err = VariableProcessingError::new_err(format!(
+        "Error processing the variables of {} sub-document(s):\n{}",
+        errors.len(),
+        errors
+            .iter()
+            .map(|(location, e)| format!("- {}: {}", location, e))
+            .collect::<Vec<String>>()
+            .join("\n")
+    ));
+    let errors: Vec<(String, PyObject)> = errors
+        .into_iter()
+        .map(|(location, e)| (location, e.to_object(py)))
+        .collect();
+    err.pvalue(py).setattr("errors", errors)?;
+    Err(err)
+}
+
+/// Process all variables in a document. With ``collect_errors``, errors of sub-documents are added to
+/// errors instead of being returned.
+fn process_variables_collecting(
+    py: Python,
+    ycd: PyYamlConfigDocument,
+    options: &VariableOptions,
+    errors: &SubdocErrors,
+) -> PyResult<()> {
+    let document = ycd.0.clone_ref(py);
+    let attributes = move || YamlConfigDocument::span_attributes(&document, py);
+    with_span(py, "configcrunch.process_vars", attributes, || {
+        process_variables_in_span(py, ycd, options, errors)
+    })
+}
+
+/// Formats a path like ``services.web.ports[0]``.
+fn format_item_path(path: &[ItemPathPart]) -> String {
+    let mut out = String::new();
+    for part in path {
+        match part {
+            ItemPathPart::Key(k) if out.is_empty() => out.push_str(k),
+            ItemPathPart::Key(k) => {
+                out.push('.');
+                out.push_str(k);
+            }
+            ItemPathPart::Index(i) => out.push_str(&format!("[{}]", i)),
+        }
+    }
+    out
+}
+
+fn process_variables_in_span(
+    py: Python,
+    ycd: PyYamlConfigDocument,
+    options: &VariableOptions,
+    errors: &SubdocErrors,
+) -> PyResult<()> {
+    options.deadline.check()?;
+    {
+        let mut ycd_mut = ycd.borrow_mut(py);
+        ycd_mut.values = options.values.clone();
+        ycd_mut.globals = options.globals.clone();
+        ycd_mut.inherit_helpers = options.inherit_helpers;
+        ycd_mut.safe = options.safe;
+        ycd_mut.template_audit = vec![];
+    }
+    if options.audit {
+        ycd.borrow_mut(py).audit_secrets = secret_patterns(py, &ycd)?;
+    }
+    resolve_local_vars(py, ycd.clone_ref(py), options)?;
     // TODO: The algorithm isn't very smart. It just runs over the
     //       document, replacing variables, until no replacements have been done.
     //       This should be improved in future versions.
     let mut doc = Dict(ycd.borrow(py).doc.clone());
-    DocumentTraverser::run_subdoc_callback(py, &mut doc)?;
+    DocumentTraverser::run_subdoc_callback(py, &mut doc, options, errors)?;
+    if options.compose_interpolation {
+        // Only done once, since $$ would otherwise be unescaped multiple times.
+        // The result is stored immediately, so that templates already see the interpolated values.
+        DocumentTraverser::run_compose_interpolation_callback(py, &mut doc, ycd.clone_ref(py))?;
+        ycd.borrow_mut(py).set_doc(doc.unwrap_dict());
+        doc = Dict(ycd.borrow(py).doc.clone());
+    }
     doc = Dict(doc.unwrap_dict());
     loop {
-        let changed = DocumentTraverser::run_current_doc_callback(py, &mut doc, ycd.clone_ref(py))?;
-        ycd.borrow_mut(py).doc = doc.unwrap_dict();
+        let changed = DocumentTraverser::run_current_doc_callback(
+            py,
+            &mut doc,
+            ycd.clone_ref(py),
+            vec![],
+            options,
+        )?;
+        ycd.borrow_mut(py).set_doc(doc.unwrap_dict());
         if !changed {
             break;
         }
         doc = Dict(ycd.borrow_mut(py).doc.clone());
     }
+    let mut doc = Dict(ycd.borrow(py).doc.clone());
+    render_keys(py, &mut doc, &ycd, &mut vec![], options)?;
+    apply_remove_if(py, &mut doc, &ycd)?;
+    ycd.borrow_mut(py).set_doc(doc.unwrap_dict());
     Ok(())
 }
 
+/// Renders the templates in the keys of all dicts in value (but not in sub-documents, they render
+/// their own keys). This is done after all values are rendered.
+/// If a rendered key already exists in its dict, a VariableProcessingError is raised.
+fn render_keys(
+    py: Python,
+    value: &mut YcdValueType,
+    document: &PyYamlConfigDocument,
+    path: &mut Vec<ItemPathPart>,
+    options: &VariableOptions,
+) -> PyResult<()> {
+    visit_mut(value, path, |value, path| match value {
+        Dict(in_dict) => render_dict_keys(py, in_dict, document, path, options),
+        _ => Ok(()),
+    })
+}
+
+/// Renders the templates in the keys of in_dict (at path), but not in the dicts in it.
+fn render_dict_keys(
+    py: Python,
+    in_dict: &mut YcdDict,
+    document: &PyYamlConfigDocument,
+    path: &[ItemPathPart],
+    options: &VariableOptions,
+) -> PyResult<()> {
+    let mut key_path = path.to_vec();
+    let templated: Vec<String> = in_dict.keys().filter(|k| is_template(k)).cloned().collect();
+    for key in templated {
+        key_path.push(ItemPathPart::Key(key.clone()));
+        let mut renderer = TemplateRenderer::new(document.clone_ref(py))?.at(&key_path);
+        let used_names = options.audit.then(|| renderer.record_names());
+        let rendered = renderer.render(py, &key);
+        if let (Some(used_names), Ok(Some(rendered))) = (used_names, &rendered) {
+            let result = YString(rendered.output.clone());
+            record_template(py, document, &key_path, &key, &result, used_names);
+        }
+        key_path.pop();
+        let rendered = match rendered {
+            Ok(Some(rendered)) => rendered.output,
+            Ok(None) => continue,
+            Err(e) => {
+                return Err(VariableProcessingError::new_err(format!(
+                    "Error processing the key {} for document: {:?}. Document path: {}.",
+                    key,
+                    e,
+                    document_path(py, document)
+                )))
+            }
+        };
+        if in_dict.contains_key(&rendered) {
+            return Err(VariableProcessingError::new_err(format!(
+                "The key {} was rendered to {}, which already exists. Document path: {}.",
+                key,
+                rendered,
+                document_path(py, document)
+            )));
+        }
+        rename_key(in_dict, &key, &rendered);
+    }
+    Ok(())
+}
+
+fn document_path(py: Python, document: &PyYamlConfigDocument) -> String {
+    document
+        .borrow(py)
+        .absolute_paths
+        .first()
+        .cloned()
+        .unwrap_or_else(|| "?".to_string())
+}
+
+/// Removes all dict entries and list entries, that are dicts or documents containing a $remove_if
+/// condition that is true. The $remove_if keys of all other entries are removed.
+/// Conditions of dicts are evaluated in the context of document, conditions of sub-documents in the
+/// context of the sub-document. The $remove_if of the document itself is not evaluated, this
+/// is done by the parent.
+fn apply_remove_if(
+    py: Python,
+    value: &mut YcdValueType,
+    document: &PyYamlConfigDocument,
+) -> PyResult<()> {
+    visit_mut(value, &mut vec![], |value, _| {
+        remove_entries_if(py, value, document)
+    })
+}
+
+/// Removes the entries of the dict or list value, that contain a $remove_if condition that is
+/// true (see apply_remove_if). The values in the entries are not processed.
+fn remove_entries_if(
+    py: Python,
+    value: &mut YcdValueType,
+    document: &PyYamlConfigDocument,
+) -> PyResult<()> {
+    match value {
+        Dict(in_dict) => {
+            let mut to_remove = vec![];
+            for (k, v) in in_dict.iter_mut() {
+                if take_remove_if(py, v, document)? {
+                    to_remove.push(k.clone());
+                }
+            }
+            for k in to_remove {
+                in_dict.shift_remove(&k);
+            }
+        }
+        List(in_list) => {
+            let mut new_list = Vec::with_capacity(in_list.len());
+            for mut v in in_list.drain(..) {
+                if !take_remove_if(py, &mut v, document)? {
+                    new_list.push(v);
+                }
+            }
+            *in_list = new_list;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Removes the $remove_if key of value (if it is a dict or a document) and evaluates the condition.
+/// Returns whether the value should be removed.
+fn take_remove_if(
+    py: Python,
+    value: &mut YcdValueType,
+    document: &PyYamlConfigDocument,
+) -> PyResult<bool> {
+    let (condition, context) = match value {
+        Dict(in_dict) => match in_dict.shift_remove(REMOVE_IF) {
+            None => return Ok(false),
+            Some(condition) => (condition, document.clone_ref(py)),
+        },
+        Ycd(in_ycd) => match in_ycd.borrow_mut(py).doc.shift_remove(REMOVE_IF) {
+            None => return Ok(false),
+            Some(condition) => (condition, in_ycd.clone_ref(py)),
+        },
+        _ => return Ok(false),
+    };
+    match condition {
+        Bool(b) => Ok(b),
+        YString(condition) => evaluate_condition(py, &context, REMOVE_IF, &condition),
+        v => Err(InvalidDocumentError::new_err(format!(
+            "{} must be a string containing an expression or a boolean, got: {}",
+            REMOVE_IF, v
+        ))),
+    }
+}
+
+/// Removes the $vars entry from the document and resolves the variables in it, before
+/// anything else in the document (including sub-documents) is processed.
+/// The result is stored as the local variables of the document, which templates can access by name.
+fn resolve_local_vars(
+    py: Python,
+    ycd: PyYamlConfigDocument,
+    options: &VariableOptions,
+) -> PyResult<()> {
+    let mut local_vars = match ycd.borrow_mut(py).doc.shift_remove(VARS) {
+        None => YcdDict::new(),
+        Some(Dict(v)) => v,
+        Some(v) => {
+            return Err(InvalidDocumentError::new_err(format!(
+                "{} must be a dict, got: {}",
+                VARS, v
+            )))
+        }
+    };
+    if options.inherit_vars {
+        if let Some(parent) = &ycd.borrow(py).parent_doc {
+            for (k, v) in parent.borrow(py).local_vars.iter() {
+                local_vars.entry(k.clone()).or_insert_with(|| v.clone());
+            }
+        }
+    }
+    ycd.borrow_mut(py).local_vars = local_vars.clone();
+    let mut vars = Dict(local_vars);
+    loop {
+        let changed = DocumentTraverser::run_current_doc_callback(
+            py,
+            &mut vars,
+            ycd.clone_ref(py),
+            vec![ItemPathPart::Key(VARS.to_string())],
+            options,
+        )?;
+        ycd.borrow_mut(py).local_vars = vars.unwrap_dict();
+        if !changed {
+            break;
+        }
+        vars = Dict(ycd.borrow(py).local_vars.clone());
+    }
+    Ok(())
+}
+
+/// Renders the templates of the value at path (keys separated by ".", list entries addressed by their
+/// index) again, against the current state of the document, and stores the result in the document.
+/// Templates inside sub-documents are rendered in the context of the sub-document.
+pub(crate) fn process_variables_at(
+    py: Python,
+    ycd: PyYamlConfigDocument,
+    path: &str,
+) -> PyResult<YcdValueType> {
+    let (document, path_in_document) = locate_path(py, ycd, path)?;
+    loop {
+        let mut root = Dict(document.borrow(py).doc.clone());
+        let mut value = value_at(&root, &path_in_document).unwrap().clone();
+        let changed = DocumentTraverser::run_current_doc_callback(
+            py,
+            &mut value,
+            document.clone_ref(py),
+            path_in_document.clone(),
+            &VariableOptions::default(),
+        )?;
+        *value_at_mut(&mut root, &path_in_document).unwrap() = value;
+        document.borrow_mut(py).set_doc(root.unwrap_dict());
+        if !changed {
+            break;
+        }
+    }
+    if path_in_document.is_empty() {
+        return Ok(Ycd(document));
+    }
+    let root = Dict(document.borrow(py).doc.clone());
+    Ok(value_at(&root, &path_in_document).unwrap().clone())
+}
+
+/// Finds the document containing the value at path (the innermost sub-document) and the path of
+/// the value in it. If the value is a sub-document, the path is empty.
+fn locate_path(
+    py: Python,
+    ycd: PyYamlConfigDocument,
+    path: &str,
+) -> PyResult<(PyYamlConfigDocument, Vec<ItemPathPart>)> {
+    let mut document = ycd;
+    let mut path_in_document = vec![];
+    for part in path.split('.') {
+        let root = Dict(document.borrow(py).doc.clone());
+        let part = match value_at(&root, &path_in_document) {
+            Some(Dict(d)) if d.contains_key(part) => ItemPathPart::Key(part.to_string()),
+            Some(List(l)) if part.parse::<usize>().map_or(false, |i| i < l.len()) => {
+                ItemPathPart::Index(part.parse().unwrap())
+            }
+            _ => {
+                return Err(exceptions::PyKeyError::new_err(format!(
+                    "Path not found: {}",
+                    path
+                )))
+            }
+        };
+        path_in_document.push(part);
+        if let Some(Ycd(sub)) = value_at(&root, &path_in_document) {
+            document = sub.clone_ref(py);
+            path_in_document = vec![];
+        }
+    }
+    Ok((document, path_in_document))
+}
+
+fn value_at<'a>(value: &'a YcdValueType, path: &[ItemPathPart]) -> Option<&'a YcdValueType> {
+    path.iter()
+        .try_fold(value, |value, part| match (value, part) {
+            (Dict(d), ItemPathPart::Key(k)) => d.get(k),
+            (List(l), ItemPathPart::Index(i)) => l.get(*i),
+            _ => None,
+        })
+}
+
+fn value_at_mut<'a>(
+    value: &'a mut YcdValueType,
+    path: &[ItemPathPart],
+) -> Option<&'a mut YcdValueType> {
+    path.iter()
+        .try_fold(value, |value, part| match (value, part) {
+            (Dict(d), ItemPathPart::Key(k)) => d.get_mut(k),
+            (List(l), ItemPathPart::Index(i)) => l.get_mut(*i),
+            _ => None,
+        })
+}
+
 #[inline]
 pub(crate) fn process_variables_for(
     py: Python,
@@ -164,7 +657,7 @@ pub(crate) fn process_variables_for(
     additional_helpers: Vec<PyObject>,
 ) -> PyResult<YcdValueType> {
     let mut renderer: TemplateRenderer = TemplateRenderer::new(ycd.clone_ref(py))?;
-    renderer.add_helpers(py, additional_helpers);
+    renderer.add_helpers(py, additional_helpers)?;
     Ok(match apply_variable_resolution(py, target, renderer)? {
         None => YString(target.to_string()),
         Some(s) => s,
@@ -0,0 +1,433 @@
+//! ``DocumentView``: Copy-on-write views of documents (``YamlConfigDocument.view``), so that values
+//! can be changed without copying the entire document.
+use crate::conv::YcdValueType::{Dict, List, Ycd};
+use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
+use crate::merger::recursive_docs_to_dicts;
+use crate::minijinja::ItemPathPart;
+use crate::minijinja::ItemPathPart::{Index, Key};
+use indexmap::IndexMap;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+use pyo3::PyIterProtocol;
+use std::sync::{Arc, Mutex};
+
+/// A change made on a view.
+#[derive(Debug)]
+enum Patch {
+    /// The value was replaced (with a value without documents, see recursive_docs_to_dicts).
+    Set(YcdValueType),
+    /// The key was removed.
+    Removed,
+    /// Entries of the dict, document or list were changed.
+    Nested(Patches),
+}
+
+type Patches = IndexMap<ItemPathPart, Patch>;
+
+/// The viewed document and the changes made on the view (and all views of its values).
+#[derive(Debug)]
+struct ViewState {
+    document: PyYamlConfigDocument,
+    patches: Patches,
+}
+
+/// The value at a path of a view.
+enum Node {
+    Missing,
+    Scalar(YcdValueType),
+    /// A dict or document, with its keys.
+    Dict(Vec<String>),
+    /// A list, with its length.
+    List(usize),
+}
+
+/// Where the value at a path of a view is stored.
+enum Location<'a> {
+    Removed,
+    /// In the value set at a path: The value and the rest of the path in it.
+    Set(&'a YcdValueType, &'a [ItemPathPart]),
+    /// In the document, with the changes of its entries, if any.
+    Document(Option<&'a Patches>),
+}
+
+impl ViewState {
+    fn locate<'a>(&'a self, path: &'a [ItemPathPart]) -> Location<'a> {
+        let mut patches = Some(&self.patches);
+        for (i, part) in path.iter().enumerate() {
+            match patches.and_then(|p| p.get(part)) {
+                Some(Patch::Set(value)) => return Location::Set(value, &path[i + 1..]),
+                Some(Patch::Removed) => return Location::Removed,
+                Some(Patch::Nested(nested)) => patches = Some(nested),
+                None => patches = None,
+            }
+        }
+        Location::Document(patches)
+    }
+
+    fn node(&self, py: Python, path: &[ItemPathPart]) -> Node {
+        match self.locate(path) {
+            Location::Removed => Node::Missing,
+            Location::Set(value, rest) => value_node(py, value, rest),
+            Location::Document(patches) => {
+                let node = value_node(py, &Ycd(self.document.clone_ref(py)), path);
+                match (node, patches) {
+                    (Node::Dict(keys), Some(patches)) => Node::Dict(patched_keys(keys, patches)),
+                    (node, _) => node,
+                }
+            }
+        }
+    }
+
+    /// The value at path with all changes, without documents (see recursive_docs_to_dicts).
+    fn value(&self, py: Python, path: &[ItemPathPart]) -> PyResult<Option<YcdValueType>> {
+        let value = match self.locate(path) {
+            Location::Removed => None,
+            Location::Set(value, rest) => value_at(py, value, rest),
+            Location::Document(None) => value_at(py, &Ycd(self.document.clone_ref(py)), path),
+            Location::Document(Some(_)) => return self.patched_value(py, path),
+        };
+        value.map(|v| recursive_docs_to_dicts(v, py)).transpose()
+    }
+
+    /// The value of a dict, document or list of the document, whose entries were changed.
+    fn patched_value(&self, py: Python, path: &[ItemPathPart]) -> PyResult<Option<YcdValueType>> {
+        let mut entry_path = path.to_vec();
+        Ok(match self.node(py, path) {
+            Node::Missing => None,
+            Node::Scalar(v) => Some(v),
+            Node::Dict(keys) => {
+                let mut dict = YcdDict::with_capacity(keys.len());
+                for key in keys {
+                    entry_path.push(Key(key.clone()));
+                    if let Some(v) = self.value(py, &entry_path)? {
+                        dict.insert(key, v);
+                    }
+                    entry_path.pop();
+                }
+                Some(Dict(dict))
+            }
+            Node::List(len) => {
+                let mut list = Vec::with_capacity(len);
+                for i in 0..len {
+                    entry_path.push(Index(i));
+                    list.extend(self.value(py, &entry_path)?);
+                    entry_path.pop();
+                }
+                Some(List(list))
+            }
+        })
+    }
+
+    /// Sets (or removes) the value at path, copying only the changed value.
+    fn patch(&mut self, py: Python, path: &[ItemPathPart], patch: Patch) -> PyResult<()> {
+        let (last, parents) = path.split_last().unwrap();
+        match (self.node(py, parents), last) {
+            (Node::Dict(keys), Key(k)) => {
+                if matches!(patch, Patch::Removed) && !keys.contains(k) {
+                    return Err(exceptions::PyKeyError::new_err(k.clone()));
+                }
+            }
+            (Node::List(_), Index(_)) if matches!(patch, Patch::Removed) => {
+                return Err(exceptions::PyTypeError::new_err(
+                    "Entries can't be removed from lists of a view. Set the entire list instead.",
+                ))
+            }
+            (Node::List(len), Index(i)) if *i < len => {}
+            (Node::List(_), Index(_)) => {
+                return Err(exceptions::PyIndexError::new_err(
+                    "list assignment index out of range",
+                ))
+            }
+            _ => {
+                return Err(exceptions::PyTypeError::new_err(
+                    "Values can only be set in dicts (by key) and lists (by index).",
+                ))
+            }
+        }
+        let mut patches = &mut self.patches;
+        for (i, part) in parents.iter().enumerate() {
+            let entry = patches
+                .entry(part.clone())
+                .or_insert_with(|| Patch::Nested(Patches::new()));
+            match entry {
+                Patch::Set(value) => return patch_value(value, &path[i + 1..], patch),
+                Patch::Nested(nested) => patches = nested,
+                // Removed values have no entries (checked above).
+                Patch::Removed => unreachable!(),
+            }
+        }
+        patches.insert(last.clone(), patch);
+        Ok(())
+    }
+}
+
+/// The keys of a dict or document of the document, after the changes of its entries.
+fn patched_keys(mut keys: Vec<String>, patches: &Patches) -> Vec<String> {
+    keys.retain(|k| !matches!(patches.get(&Key(k.clone())), Some(Patch::Removed)));
+    let added: Vec<String> = patches
+        .iter()
+        .filter_map(|(part, patch)| match (part, patch) {
+            (Key(k), Patch::Set(_)) if !keys.contains(k) => Some(k.clone()),
+            _ => None,
+        })
+        .collect();
+    keys.extend(added);
+    keys
+}
+
+fn value_node(py: Python, value: &YcdValueType, path: &[ItemPathPart]) -> Node {
+    let (part, rest) = match path.split_first() {
+        None => {
+            return match value {
+                Dict(d) => Node::Dict(d.keys().cloned().collect()),
+                Ycd(doc) => Node::Dict(doc.borrow(py).doc.keys().cloned().collect()),
+                List(l) => Node::List(l.len()),
+                v => Node::Scalar(v.clone()),
+            }
+        }
+        Some(split) => split,
+    };
+    match (value, part) {
+        (Dict(d), Key(k)) => d.get(k).map_or(Node::Missing, |v| value_node(py, v, rest)),
+        (Ycd(doc), Key(k)) => {
+            let doc = doc.borrow(py);
+            doc.doc
+                .get(k)
+                .map_or(Node::Missing, |v| value_node(py, v, rest))
+        }
+        (List(l), Index(i)) => l.get(*i).map_or(Node::Missing, |v| value_node(py, v, rest)),
+        _ => Node::Missing,
+    }
+}
+
+/// A copy of the value at path in value.
+fn value_at(py: Python, value: &YcdValueType, path: &[ItemPathPart]) -> Option<YcdValueType> {
+    let (part, rest) = match path.split_first() {
+        None => return Some(value.clone()),
+        Some(split) => split,
+    };
+    match (value, part) {
+        (Dict(d), Key(k)) => value_at(py, d.get(k)?, rest),
+        (Ycd(doc), Key(k)) => value_at(py, doc.borrow(py).doc.get(k)?, rest),
+        (List(l), Index(i)) => value_at(py, l.get(*i)?, rest),
+        _ => None,
+    }
+}
+
+/// Applies patch at path in a value set on the view.
+fn patch_value(value: &mut YcdValueType, path: &[ItemPathPart], patch: Patch) -> PyResult<()> {
+    let (last, parents) = path.split_last().unwrap();
+    let mut value = value;
+    for part in parents {
+        value = match (value, part) {
+            (Dict(d), Key(k)) => d.get_mut(k),
+            (List(l), Index(i)) => l.get_mut(*i),
+            _ => None,
+        }
+        .unwrap();
+    }
+    match (value, last, patch) {
+        (Dict(d), Key(k), Patch::Removed) => {
+            d.shift_remove(k);
+        }
+        (Dict(d), Key(k), Patch::Set(v)) => {
+            d.insert(k.clone(), v);
+        }
+        (List(l), Index(i), Patch::Set(v)) => l[*i] = v,
+        _ => unreachable!(),
+    }
+    Ok(())
+}
+
+/// A copy-on-write view of a document (see ``YamlConfigDocument.view``), or of a dict or list in it.
+///
+/// Reading a view reads the values of the document, no data is copied. Values can be set and
+/// removed like in dicts and lists (``view['services']['web']['image'] = 'nginx'``), the changes are
+/// only visible in the view (and all views of the same ``view()`` call). Reading a dict or list
+/// returns a view of it, reading other values the value. Sub-documents are viewed like dicts.
+///
+/// The view reflects changes of the document made afterwards, except for the values changed on
+/// the view. Entries can't be added to or removed from lists, set the entire list instead.
+#[pyclass(module = "_main")]
+pub(crate) struct DocumentView {
+    state: Arc<Mutex<ViewState>>,
+    path: Vec<ItemPathPart>,
+}
+
+impl DocumentView {
+    pub(crate) fn new(document: PyYamlConfigDocument) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(ViewState {
+                document,
+                patches: Patches::new(),
+            })),
+            path: vec![],
+        }
+    }
+
+    fn node(&self, py: Python) -> Node {
+        self.state.lock().unwrap().node(py, &self.path)
+    }
+
+    /// The path of the entry key (a string for dicts, an int for lists, which may be negative).
+    fn entry_path(&self, py: Python, key: &PyAny) -> PyResult<Vec<ItemPathPart>> {
+        let part = match self.node(py) {
+            Node::List(len) => {
+                let i: isize = key.extract()?;
+                let i = if i < 0 { i + len as isize } else { i };
+                if i < 0 || i >= len as isize {
+                    return Err(exceptions::PyIndexError::new_err("list index out of range"));
+                }
+                Index(i as usize)
+            }
+            Node::Dict(_) => Key(key.extract()?),
+            Node::Missing | Node::Scalar(_) => {
+                return Err(exceptions::PyKeyError::new_err(key.to_object(py)))
+            }
+        };
+        let mut path = self.path.clone();
+        path.push(part);
+        Ok(path)
+    }
+
+    fn to_python(&self, py: Python) -> PyResult<PyObject> {
+        let value = self.state.lock().unwrap().value(py, &self.path)?;
+        Ok(value.to_object(py))
+    }
+}
+
+#[pymethods]
+impl DocumentView {
+    /// The path of the viewed value in the document, the parts are separated by ".". Empty for the
+    /// document itself.
+    #[getter]
+    fn path(&self) -> String {
+        let parts: Vec<String> = self
+            .path
+            .iter()
+            .map(|part| match part {
+                Key(k) => k.clone(),
+                Index(i) => i.to_string(),
+            })
+            .collect();
+        parts.join(".")
+    }
+
+    /// The viewed document.
+    #[getter]
+    fn document(&self, py: Python) -> PyYamlConfigDocument {
+        self.state.lock().unwrap().document.clone_ref(py)
+    }
+
+    /// Whether values were changed on the view (or any view of the same ``view()`` call).
+    #[getter]
+    fn modified(&self) -> bool {
+        !self.state.lock().unwrap().patches.is_empty()
+    }
+
+    fn __getitem__(&self, py: Python, key: &PyAny) -> PyResult<PyObject> {
+        let path = self.entry_path(py, key)?;
+        let node = self.state.lock().unwrap().node(py, &path);
+        match node {
+            Node::Missing => Err(exceptions::PyKeyError::new_err(key.to_object(py))),
+            Node::Scalar(v) => Ok(v.to_object(py)),
+            Node::Dict(_) | Node::List(_) => Ok(Py::new(
+                py,
+                Self {
+                    state: self.state.clone(),
+                    path,
+                },
+            )?
+            .into_py(py)),
+        }
+    }
+
+    fn __setitem__(&self, py: Python, key: &PyAny, value: YcdValueType) -> PyResult<()> {
+        let path = self.entry_path(py, key)?;
+        let value = recursive_docs_to_dicts(value, py)?;
+        self.state
+            .lock()
+            .unwrap()
+            .patch(py, &path, Patch::Set(value))
+    }
+
+    fn __delitem__(&self, py: Python, key: &PyAny) -> PyResult<()> {
+        let path = self.entry_path(py, key)?;
+        self.state.lock().unwrap().patch(py, &path, Patch::Removed)
+    }
+
+    fn __len__(&self, py: Python) -> usize {
+        match self.node(py) {
+            Node::Dict(keys) => keys.len(),
+            Node::List(len) => len,
+            Node::Missing | Node::Scalar(_) => 0,
+        }
+    }
+
+    /// For dicts: Whether the key exists. For lists: Whether an entry is equal to value.
+    fn __contains__(&self, py: Python, value: &PyAny) -> PyResult<bool> {
+        match self.node(py) {
+            Node::Dict(keys) => Ok(match value.extract::<&str>() {
+                Ok(key) => keys.iter().any(|k| k == key),
+                Err(_) => false,
+            }),
+            _ => self.to_python(py)?.as_ref(py).contains(value),
+        }
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        let document = self.document(py);
+        Ok(format!(
+            "DocumentView({}, path={:?})",
+            document.0.as_ref(py).repr()?,
+            self.path()
+        ))
+    }
+
+    /// The keys of the viewed dict (or document).
+    fn keys(&self, py: Python) -> PyResult<Vec<String>> {
+        match self.node(py) {
+            Node::Dict(keys) => Ok(keys),
+            _ => Err(exceptions::PyTypeError::new_err(
+                "The view is not a view of a dict.",
+            )),
+        }
+    }
+
+    /// The value of key, or default if it doesn't exist.
+    #[args(default = "None")]
+    fn get(&self, py: Python, key: &PyAny, default: Option<PyObject>) -> PyResult<PyObject> {
+        match self.__getitem__(py, key) {
+            Err(e) if e.is_instance::<exceptions::PyKeyError>(py) => {
+                Ok(default.unwrap_or_else(|| py.None()))
+            }
+            result => result,
+        }
+    }
+
+    /// A copy of the viewed value with all changes, as dict (or list). Sub-documents are converted
+    /// into dicts.
+    fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        self.to_python(py)
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for DocumentView {
+    /// Iterates over the keys of dicts and the entries of lists.
+    fn __iter__(slf: PyRef<Self>) -> PyResult<PyObject> {
+        let py = slf.py();
+        let entries = match slf.node(py) {
+            Node::Dict(keys) => PyList::new(py, keys),
+            Node::List(len) => {
+                let entries = (0..len)
+                    .map(|i| slf.__getitem__(py, i.into_py(py).into_ref(py)))
+                    .collect::<PyResult<Vec<PyObject>>>()?;
+                PyList::new(py, entries)
+            }
+            Node::Missing | Node::Scalar(_) => PyList::empty(py),
+        };
+        Ok(entries.call_method0("__iter__")?.into())
+    }
+}
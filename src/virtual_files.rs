@@ -0,0 +1,56 @@
+//! Virtual files: file contents passed with the ``virtual_files`` loading option, that are used
+//! instead of (or in addition to) the files on disk.
+use path_absolutize::Absolutize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// Contents of virtual files by their normalized absolute path.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct VirtualFiles(Arc<HashMap<PathBuf, String>>);
+
+impl VirtualFiles {
+    /// Creates the virtual files from a mapping of paths to contents. Relative paths are relative
+    /// to the current working directory.
+    pub(crate) fn new(files: HashMap<String, String>) -> Self {
+        Self(Arc::new(
+            files
+                .into_iter()
+                .map(|(path, content)| (normalize(Path::new(&path)), content))
+                .collect(),
+        ))
+    }
+
+    /// The content of the virtual file at path, if there is one.
+    pub(crate) fn get<P: AsRef<Path>>(&self, path: P) -> Option<&str> {
+        if self.0.is_empty() {
+            return None;
+        }
+        self.0.get(&normalize(path.as_ref())).map(String::as_str)
+    }
+
+    /// Whether there is a virtual or a real file at path.
+    pub(crate) fn is_file<P: AsRef<Path>>(&self, path: P) -> bool {
+        self.get(&path).is_some() || path.as_ref().is_file()
+    }
+
+    /// The paths of all virtual files directly in the directory dir.
+    pub(crate) fn files_in_dir<P: AsRef<Path>>(&self, dir: P) -> Vec<PathBuf> {
+        if self.0.is_empty() {
+            return vec![];
+        }
+        let dir = normalize(dir.as_ref());
+        self.0
+            .keys()
+            .filter(|path| path.parent() == Some(dir.as_path()))
+            .cloned()
+            .collect()
+    }
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    match path.absolutize() {
+        Ok(p) => p.to_path_buf(),
+        Err(_) => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,251 @@
+//! Visiting all nodes of a document in a single pass, as a stream of events (``walk``), e.g. for
+//! exporters that don't need the document as a (flattened) dict.
+use crate::conv::YcdValueType::{
+    Bool, Decimal, Dict, Float, ForcedString, Int, List, YString, Ycd,
+};
+use crate::conv::{PyYamlConfigDocument, YcdValueType};
+use crate::encryption::encrypted_ciphertext;
+use crate::provenance::{join, Scope};
+use crate::YamlConfigDocument;
+use pyo3::prelude::*;
+use std::fmt::{Display, Formatter};
+
+/// The kinds of events of walk.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WalkEvent {
+    /// A document, dict or list starts. Its entries follow.
+    Enter,
+    /// A scalar value.
+    Value,
+    /// The document, dict or list ends.
+    Exit,
+}
+
+/// The types of nodes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NodeType {
+    Document,
+    Dict,
+    List,
+    String,
+    Bool,
+    Int,
+    Float,
+    Decimal,
+    /// An encrypted value (see ``EncryptedValue``).
+    Encrypted,
+}
+
+/// A node of a document, passed to the visitor of walk.
+pub struct Node<'a> {
+    pub event: WalkEvent,
+    /// Path of the node in the walked document. The parts are separated by ".", list entries are
+    /// addressed by their index (``ports.0``). Empty for the walked document itself.
+    pub path: &'a str,
+    pub node_type: NodeType,
+    /// The files the value was loaded from, lowest priority first (see ``affected_paths``).
+    /// For documents, the files of the document.
+    pub files: Vec<String>,
+    value: &'a YcdValueType,
+}
+
+impl<'a> Node<'a> {
+    /// The value as Python object: The value of scalars and the document of documents.
+    /// None for dicts and lists, their entries are visited separately.
+    pub fn value(&self, py: Python) -> Option<PyObject> {
+        match (self.node_type, self.value) {
+            (NodeType::Dict | NodeType::List, _) => None,
+            (_, v) => Some(v.to_object(py)),
+        }
+    }
+}
+
+/// Calls visitor for every node of the document (a YamlConfigDocument) in document order, including
+/// the entries of sub-documents: For documents, dicts and lists it is called with an Enter event
+/// before and an Exit event after their entries, for scalars with a Value event.
+///
+/// If the visitor returns false for an Enter event, the entries and the Exit event of the node
+/// are skipped.
+pub fn walk<F>(py: Python, document: &PyAny, mut visitor: F) -> PyResult<()>
+where
+    F: FnMut(&Node) -> PyResult<bool>,
+{
+    let document: Py<YamlConfigDocument> = document.extract()?;
+    let doc = PyYamlConfigDocument(document);
+    let value = Ycd(doc.clone_ref(py));
+    walk_document(py, &value, &doc, "", "", None, &mut visitor)
+}
+
+fn walk_document<F>(
+    py: Python,
+    value: &YcdValueType,
+    doc: &PyYamlConfigDocument,
+    path: &str,
+    path_in_doc: &str,
+    scope: Option<&Scope>,
+    visitor: &mut F,
+) -> PyResult<()>
+where
+    F: FnMut(&Node) -> PyResult<bool>,
+{
+    let doc = doc.borrow(py);
+    let sub_scope = Scope::new(&doc.provenance, path_in_doc, scope);
+    let mut node = Node {
+        event: WalkEvent::Enter,
+        path,
+        node_type: NodeType::Document,
+        files: doc.absolute_paths.clone(),
+        value,
+    };
+    if visitor(&node)? {
+        for (key, v) in &doc.doc {
+            walk_value(py, v, &join(path, key), key, &sub_scope, visitor)?;
+        }
+        node.event = WalkEvent::Exit;
+        visitor(&node)?;
+    }
+    Ok(())
+}
+
+fn walk_value<F>(
+    py: Python,
+    value: &YcdValueType,
+    path: &str,
+    path_in_doc: &str,
+    scope: &Scope,
+    visitor: &mut F,
+) -> PyResult<()>
+where
+    F: FnMut(&Node) -> PyResult<bool>,
+{
+    let node_type = match value {
+        Ycd(doc) => return walk_document(py, value, doc, path, path_in_doc, Some(scope), visitor),
+        Dict(d) if encrypted_ciphertext(d).is_some() => NodeType::Encrypted,
+        Dict(_) => NodeType::Dict,
+        List(_) => NodeType::List,
+        YString(_) | ForcedString(_) => NodeType::String,
+        Bool(_) => NodeType::Bool,
+        Int(_) => NodeType::Int,
+        Float(_) => NodeType::Float,
+        Decimal(_) => NodeType::Decimal,
+    };
+    let mut node = Node {
+        event: WalkEvent::Value,
+        path,
+        node_type,
+        files: scope.files(path_in_doc),
+        value,
+    };
+    match (node_type, value) {
+        (NodeType::Dict, Dict(d)) => {
+            node.event = WalkEvent::Enter;
+            if visitor(&node)? {
+                for (key, v) in d {
+                    let child_path = join(path, key);
+                    walk_value(py, v, &child_path, &join(path_in_doc, key), scope, visitor)?;
+                }
+                node.event = WalkEvent::Exit;
+                visitor(&node)?;
+            }
+        }
+        (NodeType::List, List(l)) => {
+            node.event = WalkEvent::Enter;
+            if visitor(&node)? {
+                for (i, v) in l.iter().enumerate() {
+                    let index = i.to_string();
+                    let child_path = join(path, &index);
+                    walk_value(
+                        py,
+                        v,
+                        &child_path,
+                        &join(path_in_doc, &index),
+                        scope,
+                        visitor,
+                    )?;
+                }
+                node.event = WalkEvent::Exit;
+                visitor(&node)?;
+            }
+        }
+        _ => {
+            visitor(&node)?;
+        }
+    }
+    Ok(())
+}
+
+impl Display for WalkEvent {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            WalkEvent::Enter => "enter",
+            WalkEvent::Value => "value",
+            WalkEvent::Exit => "exit",
+        })
+    }
+}
+
+impl Display for NodeType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            NodeType::Document => "document",
+            NodeType::Dict => "dict",
+            NodeType::List => "list",
+            NodeType::String => "str",
+            NodeType::Bool => "bool",
+            NodeType::Int => "int",
+            NodeType::Float => "float",
+            NodeType::Decimal => "decimal",
+            NodeType::Encrypted => "encrypted",
+        })
+    }
+}
+
+/// A node of a document, passed to the callback of ``YamlConfigDocument.walk``.
+///
+/// - ``event``: ``"enter"`` and ``"exit"`` for documents, dicts and lists (before and after their
+///   entries), ``"value"`` for all other values.
+/// - ``path``: Path of the node, the parts are separated by "." and list entries are addressed by
+///   their index (``ports.0``). Empty for the walked document.
+/// - ``type``: ``"document"``, ``"dict"``, ``"list"``, ``"str"``, ``"bool"``, ``"int"``, ``"float"``,
+///   ``"decimal"`` or ``"encrypted"``.
+/// - ``value``: The value of scalars, the document of documents, None for dicts and lists.
+/// - ``files``: The files the value was loaded from, lowest priority first (see ``affected_paths``).
+#[pyclass(module = "_main")]
+pub(crate) struct WalkNode {
+    #[pyo3(get)]
+    event: String,
+    #[pyo3(get)]
+    path: String,
+    node_type: String,
+    #[pyo3(get)]
+    value: Option<PyObject>,
+    #[pyo3(get)]
+    files: Vec<String>,
+}
+
+#[pymethods]
+impl WalkNode {
+    #[getter(type)]
+    fn node_type(&self) -> &str {
+        &self.node_type
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "WalkNode(event={:?}, path={:?}, type={:?})",
+            self.event, self.path, self.node_type
+        )
+    }
+}
+
+impl WalkNode {
+    pub(crate) fn new(py: Python, node: &Node) -> Self {
+        Self {
+            event: node.event.to_string(),
+            path: node.path.to_string(),
+            node_type: node.node_type.to_string(),
+            value: node.value(py),
+            files: node.files.clone(),
+        }
+    }
+}
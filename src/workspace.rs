@@ -0,0 +1,237 @@
+//! Workspaces: Several root documents of different types (e.g. the project, its services and
+//! commands), that are loaded, resolved and validated together (see ``Workspace.load``).
+use crate::conv::YcdValueType::Ycd;
+use crate::conv::{PyYamlConfigDocument, YcdDict};
+use crate::graph::ReferenceGraph;
+use crate::loader::load_multiple_yml;
+use crate::lookup::LookupPathEntry;
+use crate::provenance::{same_file, value_files};
+use crate::{SchemaError, WorkspaceValidationError, YamlConfigDocument};
+use indexmap::IndexMap;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple, PyType};
+use std::collections::BTreeSet;
+
+/// The files of a document in a workspace: One path or a list of paths (see ``load_multiple_yml``).
+#[derive(FromPyObject)]
+enum DocumentFiles {
+    One(String),
+    Many(Vec<String>),
+}
+
+/// Several root documents, loaded and resolved together by ``Workspace.load``. The documents are
+/// accessed by their name (``workspace['services']``).
+#[pyclass(module = "_main")]
+pub(crate) struct Workspace {
+    documents: IndexMap<String, PyYamlConfigDocument>,
+}
+
+#[pymethods]
+impl Workspace {
+    #[staticmethod]
+    #[args(
+        lookup_paths = "None",
+        process_vars = "true",
+        validate = "true",
+        kwargs = "**"
+    )]
+    /// Loads several root documents, each of them from one or more files (like
+    /// ``load_multiple_yml``), and resolves their references (``resolve_and_merge_references``)::
+    ///
+    ///     workspace = Workspace.load({
+    ///         'project': (Project, 'project.yml'),
+    ///         'services': (Services, ['services.yml', 'services.local.yml']),
+    ///         'commands': (Commands, 'commands.yml'),
+    ///     }, lookup_paths=['repo'])
+    ///     workspace['services']
+    ///
+    /// Then, unless ``process_vars`` is False, the variables of the documents are processed (see
+    /// ``Workspace.process_vars``), so that templates can reference the other documents by their name
+    /// (``{{ project.name }}``). Unless ``validate`` is False, all documents are validated afterwards (see
+    /// ``Workspace.validate``).
+    ///
+    /// :param documents:    The documents by name, as ``(document type, path or list of paths)`` tuples.
+    /// :param lookup_paths: Paths to the repositories, where the references of all documents are looked up.
+    /// :param process_vars: Whether to process the variables of the documents.
+    /// :param validate:     Whether to validate the documents.
+    ///
+    /// Keyword arguments are loading options, see ``from_yaml``. They apply to all documents.
+    fn load(
+        py: Python,
+        documents: IndexMap<String, (&PyType, DocumentFiles)>,
+        lookup_paths: Option<Vec<LookupPathEntry>>,
+        process_vars: bool,
+        validate: bool,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        let lookup_paths = lookup_paths.unwrap_or_default();
+        let mut loaded = IndexMap::new();
+        for (name, (doc_type, files)) in documents {
+            let files = match files {
+                DocumentFiles::One(file) => vec![file],
+                DocumentFiles::Many(files) => files,
+            };
+            let doc = load_multiple_yml(py, doc_type, PyTuple::new(py, files), kwargs)?;
+            doc.0.call_method(
+                py,
+                "resolve_and_merge_references",
+                (lookup_paths.clone(),),
+                kwargs,
+            )?;
+            loaded.insert(name, doc);
+        }
+        let slf = Self { documents: loaded };
+        if process_vars {
+            slf.process_vars(py, None)?;
+        }
+        if validate {
+            slf.validate(py)?;
+        }
+        Ok(slf)
+    }
+
+    /// Processes the variables of all documents (see ``process_vars``), in the order they were passed
+    /// to ``load``. The other documents of the workspace are available in all templates by their name,
+    /// so templates should only reference values of documents processed before them (otherwise they see
+    /// the unprocessed values). Globals passed as keyword argument take priority.
+    #[args(kwargs = "**")]
+    fn process_vars(&self, py: Python, kwargs: Option<&PyDict>) -> PyResult<()> {
+        let kwargs = match kwargs {
+            None => PyDict::new(py),
+            Some(kwargs) => kwargs.copy()?,
+        };
+        let passed: YcdDict = match kwargs.get_item("globals") {
+            None => YcdDict::new(),
+            Some(globals) => globals.extract()?,
+        };
+        for (name, doc) in &self.documents {
+            let mut globals: YcdDict = self
+                .documents
+                .iter()
+                .filter(|(other, _)| *other != name)
+                .map(|(other, d)| (other.clone(), Ycd(d.clone_ref(py))))
+                .collect();
+            globals.extend(passed.clone());
+            kwargs.set_item("globals", globals.to_object(py))?;
+            doc.0.call_method(py, "process_vars", (), Some(kwargs))?;
+        }
+        Ok(())
+    }
+
+    /// Validates all documents (see ``validate``). If any of them is invalid, a WorkspaceValidationError
+    /// is raised, whose ``errors`` attribute contains the errors of all invalid documents as
+    /// ``(name, exception)`` tuples.
+    fn validate(&self, py: Python) -> PyResult<bool> {
+        let mut errors = vec![];
+        for (name, doc) in &self.documents {
+            if let Err(e) = YamlConfigDocument::validate(doc.0.as_ref(py), py, None) {
+                if !e.is_instance::<SchemaError>(py) {
+                    return Err(e);
+                }
+                errors.push((name.clone(), e));
+            }
+        }
+        if errors.is_empty() {
+            return Ok(true);
+        }
+        let err = WorkspaceValidationError::new_err(format!(
+            "{} document(s) of the workspace are invalid:\n{}",
+            errors.len(),
+            errors
+                .iter()
+                .map(|(name, e)| format!("- {}: {}", name, e))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ));
+        let errors: Vec<(String, PyObject)> = errors
+            .into_iter()
+            .map(|(name, e)| (name, e.to_object(py)))
+            .collect();
+        err.pvalue(py).setattr("errors", errors)?;
+        Err(err)
+    }
+
+    #[getter]
+    /// The documents by name, in the order they were passed to ``load``.
+    fn documents(&self, py: Python) -> PyObject {
+        self.documents.to_object(py)
+    }
+
+    /// All files the documents were loaded from (including the files loaded for references), sorted.
+    fn files(&self, py: Python) -> Vec<String> {
+        let mut files = BTreeSet::new();
+        for doc in self.documents.values() {
+            let doc = doc.borrow(py);
+            files.extend(doc.absolute_paths.iter().cloned());
+            for (_, value_files) in value_files(py, &doc.doc, &doc.provenance) {
+                files.extend(value_files);
+            }
+        }
+        files.into_iter().collect()
+    }
+
+    /// Returns the paths of all values loaded from ``changed_file`` (see ``affected_paths``), by the name
+    /// of the document. Only documents with affected values are included.
+    fn affected_paths(&self, py: Python, changed_file: &str) -> IndexMap<String, Vec<String>> {
+        self.documents
+            .iter()
+            .map(|(name, doc)| {
+                let doc = doc.borrow(py);
+                let paths: Vec<String> = value_files(py, &doc.doc, &doc.provenance)
+                    .into_iter()
+                    .filter(|(_, files)| files.iter().any(|f| same_file(f, changed_file)))
+                    .map(|(path, _)| path)
+                    .collect();
+                (name.clone(), paths)
+            })
+            .filter(|(_, paths)| !paths.is_empty())
+            .collect()
+    }
+
+    /// Returns the graph of all references and sub-documents of all documents (see ``reference_graph``).
+    /// Files referenced by several documents are a single node.
+    fn reference_graph(&self, py: Python) -> PyResult<PyObject> {
+        self.graph(py)?.to_python(py)
+    }
+
+    /// Renders the graph returned by ``reference_graph`` as text (see ``render_reference_graph``).
+    #[args(format = "\"dot\"")]
+    fn render_reference_graph(&self, py: Python, format: &str) -> PyResult<String> {
+        self.graph(py)?.render(format)
+    }
+
+    fn __getitem__(&self, py: Python, name: &str) -> PyResult<PyObject> {
+        match self.documents.get(name) {
+            Some(doc) => Ok(doc.to_object(py)),
+            None => Err(exceptions::PyKeyError::new_err(name.to_string())),
+        }
+    }
+
+    fn __contains__(&self, name: &str) -> bool {
+        self.documents.contains_key(name)
+    }
+
+    fn __len__(&self) -> usize {
+        self.documents.len()
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Workspace({})",
+            self.documents
+                .keys()
+                .cloned()
+                .collect::<Vec<String>>()
+                .join(", ")
+        )
+    }
+}
+
+impl Workspace {
+    fn graph(&self, py: Python) -> PyResult<ReferenceGraph> {
+        let roots: Vec<PyYamlConfigDocument> =
+            self.documents.values().map(|d| d.clone_ref(py)).collect();
+        ReferenceGraph::build_all(py, &roots)
+    }
+}
@@ -0,0 +1,623 @@
+//! Writing a changed value back into the file it was loaded from (``YamlConfigDocument.write_back``).
+//! The file with the highest priority for the value is found via the provenance of the document.
+//! Only the text of the value is replaced (or the new entry is inserted), the formatting and the
+//! comments of the rest of the file are kept. The lines of values in their files (for
+//! ``YamlConfigDocument.origins``) are found the same way.
+use crate::conv::YcdValueType::{Dict, List, Ycd};
+use crate::conv::{PyYamlConfigDocument, YcdValueType};
+use crate::export::{canonical_entry, canonical_value, flow_value, Canonical};
+use crate::formats::{file_content, FileFormat};
+use crate::options::LoadOptions;
+use crate::InvalidDocumentError;
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+use yaml_rust::parser::{Event, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle};
+
+/// Where a value of a document is defined.
+pub(crate) struct Source {
+    /// The file with the highest priority.
+    pub(crate) file: String,
+    /// Path of the value in the file, starting with the header of the document containing it.
+    pub(crate) path: Vec<String>,
+    /// Aliases of that header (see ``header_aliases``), the file may use one of them instead.
+    pub(crate) header_aliases: Vec<String>,
+    /// Whether the file must not be written to (see ``MergeSource``).
+    pub(crate) read_only: bool,
+}
+
+/// Finds the file, that defines the value at path (parts separated by ".", list entries are
+/// addressed by their index) of the document with the highest priority. For values that are not
+/// in the document, the file of the closest dict containing them (or of the document) is used.
+/// Values of sub-documents are looked up in the files of the sub-document first, then in the files
+/// of the documents containing it. None if the value was not loaded from a file.
+pub(crate) fn find_source(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    path: &str,
+) -> PyResult<Option<Source>> {
+    let parts: Vec<&str> = path.split('.').collect();
+    // The documents containing the value, outermost first, with the number of parts of path
+    // leading to them.
+    let mut documents = vec![(document.clone_ref(py), 0)];
+    loop {
+        let (current, start) = documents.last().unwrap();
+        let next = match sub_document_on_path(py, current, &parts[*start..]) {
+            Some((doc, len)) => (doc, start + len),
+            None => break,
+        };
+        documents.push(next);
+    }
+    for (doc, start) in documents.iter().rev() {
+        let rest = &parts[*start..];
+        let borrowed = doc.borrow(py);
+        let file = match borrowed.provenance.files(&rest.join(".")).last() {
+            Some(file) => Some(file.clone()),
+            // The file of the document itself. Sub-documents share the files of their parent.
+            None if *start == 0 => borrowed.absolute_paths.first().cloned(),
+            None => None,
+        };
+        if let Some(file) = file {
+            let read_only = documents.iter().any(|(doc, _)| {
+                doc.borrow(py)
+                    .sources
+                    .iter()
+                    .any(|source| source.read_only && source.path == file)
+            });
+            let header: String = doc.getattr(py, "header")?.call0(py)?.extract(py)?;
+            let header_aliases = doc.getattr(py, "header_aliases")?.call0(py)?.extract(py)?;
+            return Ok(Some(Source {
+                file,
+                path: std::iter::once(header)
+                    .chain(rest.iter().map(|p| p.to_string()))
+                    .collect(),
+                header_aliases,
+                read_only,
+            }));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether the value at path (parts separated by ".", see find_source) exists in document.
+pub(crate) fn has_value(py: Python, document: &PyYamlConfigDocument, path: &str) -> bool {
+    let parts: Vec<&str> = path.split('.').collect();
+    let mut document = document.clone_ref(py);
+    let mut start = 0;
+    while let Some((doc, len)) = sub_document_on_path(py, &document, &parts[start..]) {
+        document = doc;
+        start += len;
+    }
+    let document = document.borrow(py);
+    let mut value: Option<&YcdValueType> = None;
+    for part in &parts[start..] {
+        let child = match value {
+            None => document.doc.get(*part),
+            Some(Dict(d)) => d.get(*part),
+            Some(List(l)) => part.parse::<usize>().ok().and_then(|i| l.get(i)),
+            Some(_) => None,
+        };
+        match child {
+            Some(v) => value = Some(v),
+            None => return false,
+        }
+    }
+    true
+}
+
+/// The first sub-document of document on the path (not the value at path itself), with the
+/// number of parts of path leading to it.
+fn sub_document_on_path(
+    py: Python,
+    document: &PyYamlConfigDocument,
+    path: &[&str],
+) -> Option<(PyYamlConfigDocument, usize)> {
+    let document = document.borrow(py);
+    let mut value: Option<&YcdValueType> = None;
+    for (i, part) in path.iter().enumerate().take(path.len().saturating_sub(1)) {
+        let child = match value {
+            None => document.doc.get(*part),
+            Some(Dict(d)) => d.get(*part),
+            Some(List(l)) => part.parse::<usize>().ok().and_then(|i| l.get(i)),
+            Some(_) => None,
+        };
+        match child {
+            Some(Ycd(doc)) => return Some((doc.clone_ref(py), i + 1)),
+            Some(v) => value = Some(v),
+            None => return None,
+        }
+    }
+    None
+}
+
+/// Sets the value at the path of source in its file.
+/// Only YAML files can be edited, the other formats are rejected. The edited content is written
+/// to a temporary file next to it first, which then replaces the file, so the file is never left
+/// partially written.
+pub(crate) fn write_to_source(py: Python, source: &Source, value: &YcdValueType) -> PyResult<()> {
+    let format = FileFormat::of(&source.file);
+    if format != FileFormat::Yaml {
+        return Err(exceptions::PyValueError::new_err(format!(
+            "Unable to write {} to {}: Only YAML files can be written, this is a {} file.",
+            source.path.join("."),
+            source.file,
+            format.name()
+        )));
+    }
+    let content = fs::read_to_string(&source.file).map_err(|e| {
+        exceptions::PyIOError::new_err(format!("Unable to read YAML file {}: {:?}", source.file, e))
+    })?;
+    let edited = edit_yaml(py, source, &content, value)?;
+    replace_file(Path::new(&source.file), &edited).map_err(|e| {
+        exceptions::PyIOError::new_err(format!(
+            "Unable to write YAML file {}: {:?}",
+            source.file, e
+        ))
+    })
+}
+
+/// Replaces the content of the file at path by writing it to a temporary file in the same directory
+/// (with the permissions of the file) and renaming that over the file.
+fn replace_file(path: &Path, content: &str) -> io::Result<()> {
+    let name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("document");
+    let tmp = path.with_file_name(format!(".{}.{}.tmp", name, process::id()));
+    let result = fs::write(&tmp, content)
+        .and_then(|_| fs::set_permissions(&tmp, fs::metadata(path)?.permissions()))
+        .and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// A node of a YAML file with its position. Positions are indexes of characters in the file.
+struct Located {
+    kind: Kind,
+    start: usize,
+    line: usize,
+    col: usize,
+    /// End of the node (exclusive), without trailing comments and empty lines.
+    end: usize,
+}
+
+enum Kind {
+    /// A scalar or an alias. Empty scalars (null values without text) have no characters.
+    Scalar(String),
+    Mapping {
+        entries: Vec<(Located, Located)>,
+        flow: bool,
+    },
+    Sequence(Vec<Located>),
+}
+
+/// The content of the file with the value at the path of source replaced (or inserted).
+fn edit_yaml(py: Python, source: &Source, content: &str, value: &YcdValueType) -> PyResult<String> {
+    let chars: Vec<char> = content.chars().collect();
+    let root = parse_located(&source.file, &chars, content)?;
+    let error = |message: &str| {
+        exceptions::PyValueError::new_err(format!(
+            "Unable to write {} to {}: {}",
+            source.path.join("."),
+            source.file,
+            message
+        ))
+    };
+    let mut node = &root;
+    let mut key = None;
+    for (i, part) in source.path.iter().enumerate() {
+        let child = match &node.kind {
+            Kind::Mapping { entries, .. } => {
+                find_entry(entries, source, i).map(|(k, v)| (Some(k), v))
+            }
+            Kind::Sequence(entries) => {
+                match part.parse::<usize>().ok().and_then(|i| entries.get(i)) {
+                    Some(v) => Some((None, v)),
+                    None => return Err(error(&format!("The list has no entry {}.", part))),
+                }
+            }
+            Kind::Scalar(_) => {
+                return Err(error(&format!(
+                    "The value containing {} is not a dict.",
+                    part
+                )))
+            }
+        };
+        match child {
+            Some((k, v)) => {
+                key = k;
+                node = v;
+            }
+            None => {
+                let edit = insert_entry(py, &chars, node, &source.path[i..], value)?;
+                return Ok(edit.apply(&chars));
+            }
+        }
+    }
+    Ok(replace_node(py, &chars, key, node, value)?.apply(&chars))
+}
+
+/// The entry of a mapping for the part i of the path of source.
+fn find_entry<'a>(
+    entries: &'a [(Located, Located)],
+    source: &Source,
+    i: usize,
+) -> Option<&'a (Located, Located)> {
+    entries.iter().find(|(k, _)| match &k.kind {
+        Kind::Scalar(s) => s == &source.path[i] || (i == 0 && source.header_aliases.contains(s)),
+        _ => false,
+    })
+}
+
+/// The lines values are defined on in their files (see ``YamlConfigDocument.origins``). Every file
+/// is read (from the virtual files of options, if there is one) and parsed once.
+pub(crate) struct SourceLines<'a> {
+    options: &'a LoadOptions,
+    files: HashMap<String, Option<Located>>,
+}
+
+impl<'a> SourceLines<'a> {
+    pub(crate) fn new(options: &'a LoadOptions) -> Self {
+        Self {
+            options,
+            files: HashMap::new(),
+        }
+    }
+
+    /// The line (starting at 1) of the key of the value at the path of source in its file. None if
+    /// the file can't be read as YAML (e.g. INI files) or the value isn't written there (e.g. if it's
+    /// set by a $default).
+    pub(crate) fn line(&mut self, source: &Source) -> Option<usize> {
+        let options = self.options;
+        let root = self
+            .files
+            .entry(source.file.clone())
+            .or_insert_with(|| {
+                let content = file_content(&source.file, options).ok()?;
+                let chars: Vec<char> = content.chars().collect();
+                parse_located(&source.file, &chars, &content).ok()
+            })
+            .as_ref()?;
+        let mut node = root;
+        let mut line = None;
+        // The paths of origins don't lead into lists (see value_files).
+        for i in 0..source.path.len() {
+            node = match &node.kind {
+                Kind::Mapping { entries, .. } => {
+                    let (key, value) = find_entry(entries, source, i)?;
+                    line = Some(key.line);
+                    value
+                }
+                Kind::Sequence(_) | Kind::Scalar(_) => return None,
+            };
+        }
+        line
+    }
+}
+
+/// Replaces the characters from start to end with text.
+struct TextEdit {
+    start: usize,
+    end: usize,
+    text: String,
+}
+
+impl TextEdit {
+    fn apply(self, chars: &[char]) -> String {
+        chars[..self.start]
+            .iter()
+            .copied()
+            .chain(self.text.chars())
+            .chain(chars[self.end..].iter().copied())
+            .collect()
+    }
+}
+
+/// Replaces the node (the value of key, or an entry of a list if there is no key) with value.
+/// Dicts and lists are written as blocks, other values in the line of the key.
+fn replace_node(
+    py: Python,
+    chars: &[char],
+    key: Option<&Located>,
+    node: &Located,
+    value: &YcdValueType,
+) -> PyResult<TextEdit> {
+    let in_key_line = key.map_or(true, |k| k.line == node.line);
+    let empty = node.start == node.end;
+    Ok(match (canonical_value(py, value)?, key) {
+        (Canonical::Inline(s), Some(key)) if !in_key_line || empty => TextEdit {
+            start: colon_end(chars, key),
+            end: node.end,
+            text: format!(" {}", s),
+        },
+        (Canonical::Inline(s), _) => TextEdit {
+            start: node.start,
+            end: node.end,
+            text: s,
+        },
+        (Canonical::Block(lines), Some(key)) if in_key_line => {
+            let indent = key.col + 2;
+            TextEdit {
+                start: colon_end(chars, key),
+                end: node.end,
+                text: format!("\n{}{}", " ".repeat(indent), block_text(&lines, indent)),
+            }
+        }
+        (Canonical::Block(lines), _) => TextEdit {
+            start: node.start,
+            end: node.end,
+            text: block_text(&lines, node.col),
+        },
+    })
+}
+
+/// Inserts the entry for the first part of path (the key missing in mapping). If path has more
+/// parts, the entry is a dict containing them.
+fn insert_entry(
+    py: Python,
+    chars: &[char],
+    mapping: &Located,
+    path: &[String],
+    value: &YcdValueType,
+) -> PyResult<TextEdit> {
+    let (key, rest) = path.split_first().unwrap();
+    let value = rest.iter().rev().fold(value.clone(), |v, k| {
+        Dict(std::iter::once((k.clone(), v)).collect())
+    });
+    Ok(match &mapping.kind {
+        Kind::Mapping {
+            entries,
+            flow: true,
+        } => {
+            let entry = flow_value(py, &Dict(std::iter::once((key.clone(), value)).collect()))?;
+            // Without the braces.
+            let entry = &entry[1..entry.len() - 1];
+            // Before the closing brace.
+            let position = mapping.end - 1;
+            TextEdit {
+                start: position,
+                end: position,
+                text: if entries.is_empty() {
+                    entry.to_string()
+                } else {
+                    format!(", {}", entry)
+                },
+            }
+        }
+        _ => TextEdit {
+            start: mapping.end,
+            end: mapping.end,
+            text: format!(
+                "\n{}{}",
+                " ".repeat(mapping.col),
+                block_text(&canonical_entry(py, key, &value)?, mapping.col)
+            ),
+        },
+    })
+}
+
+/// The lines joined by newlines, all but the first one indented by indent spaces.
+fn block_text(lines: &[String], indent: usize) -> String {
+    lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| match i {
+            0 => line.clone(),
+            _ => format!("{}{}", " ".repeat(indent), line),
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Position after the colon following the key.
+fn colon_end(chars: &[char], key: &Located) -> usize {
+    let mut position = key.end;
+    while let Some(' ') | Some('\t') = chars.get(position) {
+        position += 1;
+    }
+    if chars.get(position) == Some(&':') {
+        position + 1
+    } else {
+        key.end
+    }
+}
+
+/// Parses the content (chars) of the YAML file at path into a tree of located nodes.
+fn parse_located(path: &str, chars: &[char], content: &str) -> PyResult<Located> {
+    let mut parser = Parser::new(content.chars());
+    let mut events = vec![];
+    loop {
+        let (event, mark) = parser.next().map_err(|e| {
+            InvalidDocumentError::new_err(format!("Unable to read YAML file {}: {}", path, e))
+        })?;
+        let end = event == Event::StreamEnd;
+        events.push((event, mark));
+        if end {
+            break;
+        }
+    }
+    let start = events
+        .iter()
+        .position(|(e, _)| *e == Event::DocumentStart)
+        .map(|i| i + 1)
+        .filter(|i| matches!(events[*i].0, Event::MappingStart(_)))
+        .ok_or_else(|| {
+            InvalidDocumentError::new_err(format!("The YAML file {} contains no dict.", path))
+        })?;
+    let mut builder = TreeBuilder {
+        chars,
+        events,
+        position: start,
+    };
+    Ok(builder.node())
+}
+
+struct TreeBuilder<'a> {
+    chars: &'a [char],
+    events: Vec<(Event, Marker)>,
+    /// Index of the next event.
+    position: usize,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn node(&mut self) -> Located {
+        let (event, mark) = self.events[self.position].clone();
+        self.position += 1;
+        let start = mark.index();
+        let (kind, end) = match event {
+            Event::Scalar(value, style, ..) => {
+                let end = self.scalar_end(&value, style, start);
+                (Kind::Scalar(value), end)
+            }
+            Event::Alias(_) => {
+                let length = self.chars[start..]
+                    .iter()
+                    .take_while(|c| !c.is_whitespace() && !",]}".contains(**c))
+                    .count();
+                (Kind::Scalar(String::new()), start + length)
+            }
+            Event::MappingStart(_) => {
+                let mut entries = vec![];
+                while !self.at(Event::MappingEnd) {
+                    let key = self.node();
+                    let mut value = self.node();
+                    if value.start == value.end {
+                        // Empty values are written after the colon.
+                        value.start = colon_end(self.chars, &key);
+                        value.end = value.start;
+                        value.line = key.line;
+                    }
+                    entries.push((key, value));
+                }
+                let flow = self.chars.get(start) == Some(&'{');
+                let end = self.collection_end(flow, entries.last().map(|(_, v)| v));
+                (Kind::Mapping { entries, flow }, end)
+            }
+            Event::SequenceStart(_) => {
+                let mut entries = vec![];
+                while !self.at(Event::SequenceEnd) {
+                    entries.push(self.node());
+                }
+                let flow = self.chars.get(start) == Some(&'[');
+                let end = self.collection_end(flow, entries.last());
+                (Kind::Sequence(entries), end)
+            }
+            _ => (Kind::Scalar(String::new()), start),
+        };
+        Located {
+            kind,
+            start,
+            line: mark.line(),
+            col: mark.col(),
+            end: end.max(start),
+        }
+    }
+
+    /// Whether the next event is event (the end of a collection, which is then consumed).
+    fn at(&mut self, event: Event) -> bool {
+        match self.events.get(self.position) {
+            Some((e, _)) if *e == event => {
+                self.position += 1;
+                true
+            }
+            None | Some((Event::StreamEnd, _)) => true,
+            _ => false,
+        }
+    }
+
+    /// The end of a collection, whose end event was just consumed.
+    fn collection_end(&self, flow: bool, last: Option<&Located>) -> usize {
+        let end_mark = self.events[self.position - 1].1;
+        match (flow, last) {
+            // After the closing bracket.
+            (true, _) => end_mark.index() + 1,
+            (false, Some(last)) => last.end,
+            (false, None) => end_mark.index(),
+        }
+    }
+
+    fn scalar_end(&self, value: &str, style: TScalarStyle, start: usize) -> usize {
+        let text = &self.chars[start..];
+        match style {
+            TScalarStyle::Plain => {
+                let length = value.chars().count();
+                if text.iter().take(length).copied().eq(value.chars()) {
+                    start + length
+                } else if value == "~" {
+                    // An empty value, its position is that of the next token.
+                    start
+                } else {
+                    self.lines_end(start)
+                }
+            }
+            TScalarStyle::SingleQuoted => {
+                let mut i = 1;
+                while i < text.len() {
+                    match (text[i], text.get(i + 1)) {
+                        ('\'', Some('\'')) => i += 2,
+                        ('\'', _) => return start + i + 1,
+                        _ => i += 1,
+                    }
+                }
+                start + text.len()
+            }
+            TScalarStyle::DoubleQuoted => {
+                let mut i = 1;
+                while i < text.len() {
+                    match text[i] {
+                        '\\' => i += 2,
+                        '"' => return start + i + 1,
+                        _ => i += 1,
+                    }
+                }
+                start + text.len()
+            }
+            _ => self.lines_end(start),
+        }
+    }
+
+    /// The end of the last line of a node starting at start, that ends before the next event:
+    /// Trailing empty lines and lines only containing comments are not part of the node.
+    fn lines_end(&self, start: usize) -> usize {
+        let chars = self.chars;
+        let next = self
+            .events
+            .get(self.position)
+            .map_or(chars.len(), |(_, mark)| mark.index());
+        let mut end = if next >= chars.len() {
+            chars.len()
+        } else {
+            line_start(chars, next)
+        };
+        loop {
+            while end > start && matches!(chars[end - 1], '\n' | '\r') {
+                end -= 1;
+            }
+            let line_start = line_start(chars, end);
+            if line_start <= start {
+                return end;
+            }
+            let line: String = chars[line_start..end].iter().collect();
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                return end;
+            }
+            end = line_start;
+        }
+    }
+}
+
+/// The start of the line containing the character at position.
+fn line_start(chars: &[char], position: usize) -> usize {
+    chars[..position]
+        .iter()
+        .rposition(|c| *c == '\n')
+        .map_or(0, |i| i + 1)
+}
@@ -0,0 +1,691 @@
+//! A YAML loader working directly on the events of the YAML parser. YAML files are loaded with it,
+//! with an explicit stack instead of recursion, so deeply nested files can't overflow the stack.
+//! It is also used where the raw text of scalars is needed: For typing scalars with the YAML 1.2
+//! core schema (``core_schema`` loading option) and for finding the scalars, that are typed
+//! differently by the default typing (``report_coercions`` loading option). Tags are also only
+//! available here (scalars tagged with ``!encrypted``).
+use crate::conv::SimpleYcdValueType::{Bool, Dict, Float, Int, List, YString};
+use crate::conv::{SimpleYcdValueType, YcdDict, YcdList, YcdValueType};
+use crate::limits::ParseLimits;
+use crate::minijinja::ItemPathPart;
+use crate::nested::{clone_value, drop_value, visit};
+use crate::{DocumentLimitError, InvalidDocumentError};
+use pyo3::prelude::*;
+use std::collections::HashMap;
+use std::str::Chars;
+use yaml_rust::parser::{Event, Parser};
+use yaml_rust::scanner::{Marker, TScalarStyle, TokenType};
+
+/// Parses the YAML content of the file at path. Parsing stops as soon as a limit is exceeded.
+///
+/// With core_schema, plain scalars are typed with the YAML 1.2 core schema: Only ``true`` and
+/// ``false`` are booleans (``yes``, ``no``, ``on`` and ``off`` are strings), integers are decimal,
+/// octal with ``0o`` or hexadecimal with ``0x`` (a leading zero does not make a number octal) and
+/// only ``.inf`` and ``.nan`` are special floats. Otherwise they are typed by the default typing
+/// (see resolve_default).
+pub(crate) fn parse_yaml(
+    path: &str,
+    content: &str,
+    limits: &ParseLimits,
+    core_schema: bool,
+) -> PyResult<YcdDict> {
+    let mut loader = EventLoader {
+        path,
+        parser: Parser::new(content.chars()),
+        anchors: HashMap::new(),
+        limits,
+        core_schema,
+        nodes: 0,
+        stack: vec![],
+    };
+    match loader.document()? {
+        YcdValueType::Dict(v) => Ok(v),
+        value => {
+            drop_value(value);
+            Err(InvalidDocumentError::new_err(format!(
+                "Unable to read YAML file {}: Expected a dictionary.",
+                path
+            )))
+        }
+    }
+}
+
+/// A dict or list, of which the entries are being loaded.
+enum Collection {
+    Dict {
+        anchor: usize,
+        done: YcdDict,
+        /// The key of the entry that is loaded, if any.
+        key: Option<String>,
+    },
+    List {
+        anchor: usize,
+        done: YcdList,
+    },
+}
+
+impl Collection {
+    fn push(&mut self, value: YcdValueType) {
+        match self {
+            Collection::Dict { done, key, .. } => {
+                if let Some(key) = key.take() {
+                    done.insert(key, value);
+                }
+            }
+            Collection::List { done, .. } => done.push(value),
+        }
+    }
+
+    /// The loaded value and its anchor ID (0 for none).
+    fn finish(self) -> (YcdValueType, usize) {
+        match self {
+            Collection::Dict { anchor, done, .. } => (YcdValueType::Dict(done), anchor),
+            Collection::List { anchor, done } => (YcdValueType::List(done), anchor),
+        }
+    }
+}
+
+/// Maximum nesting depth of dicts and lists, if the ``max_depth`` limit is not set.
+const DEFAULT_MAX_DEPTH: usize = 128;
+
+struct EventLoader<'a> {
+    path: &'a str,
+    parser: Parser<Chars<'a>>,
+    /// Values of anchors, by anchor ID.
+    anchors: HashMap<usize, YcdValueType>,
+    limits: &'a ParseLimits,
+    core_schema: bool,
+    /// Number of values loaded so far.
+    nodes: usize,
+    /// The dicts and lists being loaded, the innermost last. A dict or list at depth (the top
+    /// level of the file has depth 1) contains values at depth + 1.
+    stack: Vec<Collection>,
+}
+
+impl<'a> EventLoader<'a> {
+    fn next(&mut self) -> PyResult<(Event, Marker)> {
+        self.parser.next().map_err(|e| {
+            InvalidDocumentError::new_err(format!("Unable to read YAML file {}: {}", self.path, e))
+        })
+    }
+
+    fn error(&self, mark: Marker, message: &str) -> PyErr {
+        InvalidDocumentError::new_err(format!(
+            "Unable to read YAML file {} (line {}): {}",
+            self.path,
+            mark.line(),
+            message
+        ))
+    }
+
+    /// Counts loaded values.
+    fn count(&mut self, nodes: usize) -> PyResult<()> {
+        self.nodes += nodes;
+        match self.limits.nodes_exceeded(self.nodes) {
+            Some(message) => Err(DocumentLimitError::new_err(format!(
+                "{} exceeds the limits: {}",
+                self.path, message
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the depth of a dict or list. Without the ``max_depth`` limit, values may be nested
+    /// DEFAULT_MAX_DEPTH levels deep (like with serde_yaml), since loading them recurses.
+    fn check_depth(&self, depth: usize) -> PyResult<()> {
+        let limits = ParseLimits {
+            max_depth: self.limits.max_depth.or(Some(DEFAULT_MAX_DEPTH)),
+            ..*self.limits
+        };
+        match limits.depth_exceeded(depth) {
+            Some(message) => Err(DocumentLimitError::new_err(format!(
+                "{} exceeds the limits: {}",
+                self.path, message
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// Loads the first document of the file.
+    fn document(&mut self) -> PyResult<YcdValueType> {
+        loop {
+            let (event, mark) = self.next()?;
+            let expects_key = matches!(self.stack.last(), Some(Collection::Dict { key: None, .. }));
+            let (value, anchor) = match event {
+                Event::StreamStart | Event::DocumentStart => continue,
+                Event::StreamEnd => {
+                    return Err(self.error(mark, "The file does not contain a document."))
+                }
+                Event::MappingEnd | Event::SequenceEnd => match self.stack.pop() {
+                    Some(collection) => collection.finish(),
+                    None => return Err(self.error(mark, "Unexpected YAML event.")),
+                },
+                Event::Scalar(k, ..) if expects_key => {
+                    if let Some(Collection::Dict { key, .. }) = self.stack.last_mut() {
+                        *key = Some(k);
+                    }
+                    continue;
+                }
+                _ if expects_key => return Err(self.error(mark, "Keys must be strings.")),
+                Event::Scalar(v, style, anchor, tag) => {
+                    self.count(1)?;
+                    (self.scalar(v, style, tag, mark)?, anchor)
+                }
+                Event::SequenceStart(anchor) => {
+                    self.count(1)?;
+                    self.check_depth(self.stack.len() + 1)?;
+                    self.stack.push(Collection::List {
+                        anchor,
+                        done: YcdList::new(),
+                    });
+                    continue;
+                }
+                Event::MappingStart(anchor) => {
+                    self.count(1)?;
+                    self.check_depth(self.stack.len() + 1)?;
+                    self.stack.push(Collection::Dict {
+                        anchor,
+                        done: YcdDict::new(),
+                        key: None,
+                    });
+                    continue;
+                }
+                Event::Alias(id) => {
+                    let value = match self.anchors.get(&id) {
+                        Some(value) => clone_value(value),
+                        None => return Err(self.error(mark, "Unknown alias.")),
+                    };
+                    self.count(count_values(&value))?;
+                    if let Some(levels) = nesting(&value) {
+                        self.check_depth(self.stack.len() + levels)?;
+                    }
+                    (value, 0)
+                }
+                _ => return Err(self.error(mark, "Unexpected YAML event.")),
+            };
+            if anchor > 0 {
+                self.anchors.insert(anchor, clone_value(&value));
+            }
+            match self.stack.last_mut() {
+                Some(collection) => collection.push(value),
+                None => return Ok(value),
+            }
+        }
+    }
+
+    fn scalar(
+        &self,
+        value: String,
+        style: TScalarStyle,
+        tag: Option<TokenType>,
+        mark: Marker,
+    ) -> PyResult<YcdValueType> {
+        if style != TScalarStyle::Plain {
+            return Ok(YcdValueType::YString(value));
+        }
+        match &tag {
+            Some(TokenType::Tag(handle, suffix))
+                if handle == "!!"
+                    && ["bool", "int", "float", "null"].contains(&suffix.as_str()) => {}
+            // Other tags (e.g. ``!!str`` or ``!encrypted``) make scalars strings.
+            Some(_) => return Ok(YcdValueType::YString(value)),
+            None => {}
+        }
+        let resolved = if self.core_schema {
+            resolve_core_schema(&value)
+        } else {
+            resolve_default(&value)
+        };
+        match resolved {
+            Some(resolved) => Ok(resolved.into()),
+            None => Err(self.error(mark, "Null values are not supported.")),
+        }
+    }
+}
+
+impl Drop for EventLoader<'_> {
+    /// Drops the values of a partially loaded file without recursing (see drop_value).
+    fn drop(&mut self) {
+        for collection in self.stack.drain(..) {
+            drop_value(collection.finish().0);
+        }
+        for (_, value) in self.anchors.drain() {
+            drop_value(value);
+        }
+    }
+}
+
+/// Types a plain scalar with the default typing: Only ``true`` and ``false`` are booleans, integers
+/// are decimal, hexadecimal with ``0x``, octal with ``0o`` or binary with ``0b``, digits with a
+/// leading zero are strings and integers larger than the largest 64 bit integer are floats.
+/// Returns None for null.
+fn resolve_default(value: &str) -> Option<SimpleYcdValueType> {
+    match value {
+        "~" | "null" => return None,
+        "true" => return Some(Bool(true)),
+        "false" => return Some(Bool(false)),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+            return Some(Float(f64::INFINITY))
+        }
+        "-.inf" | "-.Inf" | "-.INF" => return Some(Float(f64::NEG_INFINITY)),
+        ".nan" | ".NaN" | ".NAN" => return Some(Float(f64::NAN)),
+        _ => {}
+    }
+    let digits = |s: &str, radix: u32| !s.is_empty() && s.chars().all(|c| c.is_digit(radix));
+    let (negative, unsigned) = match value.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, value.strip_prefix('+').unwrap_or(value)),
+    };
+    for (prefix, radix) in [("0x", 16), ("0o", 8), ("0b", 2)] {
+        if let Some(rest) = unsigned.strip_prefix(prefix).filter(|s| digits(s, radix)) {
+            if let Some(v) = u64::from_str_radix(rest, radix)
+                .ok()
+                .and_then(|v| signed_int(negative, v))
+            {
+                return Some(v);
+            }
+        }
+    }
+    if digits(unsigned, 10) {
+        // Leading zeros don't make a number octal, the digits are a string.
+        if unsigned.len() > 1 && unsigned.starts_with('0') {
+            return Some(YString(value.to_string()));
+        }
+        if let Some(v) = unsigned.parse().ok().and_then(|v| signed_int(negative, v)) {
+            return Some(v);
+        }
+    }
+    match value.parse::<f64>() {
+        Ok(v) if v.is_finite() => Some(Float(v)),
+        _ => Some(YString(value.to_string())),
+    }
+}
+
+/// The integer with the absolute value v. Positive integers larger than the largest 64 bit integer
+/// are floats, negative ones are None.
+fn signed_int(negative: bool, v: u64) -> Option<SimpleYcdValueType> {
+    if negative {
+        i64::try_from(-(v as i128)).ok().map(Int)
+    } else {
+        Some(i64::try_from(v).map_or(Float(v as f64), Int))
+    }
+}
+
+/// Types a plain scalar with the YAML 1.2 core schema. Returns None for null.
+fn resolve_core_schema(value: &str) -> Option<SimpleYcdValueType> {
+    match value {
+        "" | "~" | "null" | "Null" | "NULL" => return None,
+        "true" | "True" | "TRUE" => return Some(Bool(true)),
+        "false" | "False" | "FALSE" => return Some(Bool(false)),
+        ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+            return Some(Float(f64::INFINITY))
+        }
+        "-.inf" | "-.Inf" | "-.INF" => return Some(Float(f64::NEG_INFINITY)),
+        ".nan" | ".NaN" | ".NAN" => return Some(Float(f64::NAN)),
+        _ => {}
+    }
+    let digits = |s: &str, radix: u32| !s.is_empty() && s.chars().all(|c| c.is_digit(radix));
+    if let Some(octal) = value.strip_prefix("0o").filter(|s| digits(s, 8)) {
+        if let Ok(v) = i64::from_str_radix(octal, 8) {
+            return Some(Int(v));
+        }
+    }
+    if let Some(hex) = value.strip_prefix("0x").filter(|s| digits(s, 16)) {
+        if let Ok(v) = i64::from_str_radix(hex, 16) {
+            return Some(Int(v));
+        }
+    }
+    let unsigned = value.strip_prefix(['-', '+']).unwrap_or(value);
+    if digits(unsigned, 10) {
+        if let Ok(v) = value.parse::<i64>() {
+            return Some(Int(v));
+        }
+    }
+    if is_core_schema_float(unsigned) {
+        if let Ok(v) = value.parse::<f64>() {
+            return Some(Float(v));
+        }
+    }
+    Some(YString(value.to_string()))
+}
+
+/// Whether value (without sign) matches ``(\.[0-9]+|[0-9]+(\.[0-9]*)?)([eE][-+]?[0-9]+)?``.
+fn is_core_schema_float(value: &str) -> bool {
+    let (mantissa, exponent) = match value.split_once(['e', 'E']) {
+        Some((m, e)) => (m, Some(e.strip_prefix(['-', '+']).unwrap_or(e))),
+        None => (value, None),
+    };
+    let is_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let mantissa_ok = match mantissa.split_once('.') {
+        Some((int, frac)) => {
+            is_digits(int) && is_digits(frac) && !(int.is_empty() && frac.is_empty())
+        }
+        None => !mantissa.is_empty() && is_digits(mantissa),
+    };
+    mantissa_ok && exponent.map_or(true, |e| !e.is_empty() && is_digits(e))
+}
+
+/// Number of values in value (including value itself).
+fn count_values(value: &YcdValueType) -> usize {
+    let mut count = 0;
+    visit(value, |_| -> Result<(), ()> {
+        count += 1;
+        Ok(())
+    })
+    .expect("Counting values can not fail.");
+    count
+}
+
+/// How many levels of dicts and lists are nested in value (None for a scalar).
+fn nesting(value: &YcdValueType) -> Option<usize> {
+    let mut levels = None;
+    let mut stack = vec![(value, 1)];
+    while let Some((value, level)) = stack.pop() {
+        match value {
+            YcdValueType::Dict(d) => stack.extend(d.values().map(|v| (v, level + 1))),
+            YcdValueType::List(l) => stack.extend(l.iter().map(|v| (v, level + 1))),
+            _ => continue,
+        }
+        levels = levels.max(Some(level));
+    }
+    levels
+}
+
+/// A key that occurs more than once in the same mapping of a YAML file.
+pub(crate) struct DuplicateKey {
+    /// Path of the key in the file ("base.more.key").
+    pub(crate) path: String,
+    /// Line of the first occurrence.
+    pub(crate) first_line: usize,
+    /// Line of the later occurrence, that would override the first one.
+    pub(crate) line: usize,
+}
+
+enum Frame {
+    Mapping {
+        /// Lines of the keys seen so far.
+        keys: HashMap<String, usize>,
+        /// The last key, while its value is being read.
+        key: Option<String>,
+        expects_value: bool,
+    },
+    Sequence {
+        index: usize,
+    },
+}
+
+impl Frame {
+    /// Called when a node starts inside this frame. Returns the line of the first occurrence,
+    /// if node is a key that already occurred.
+    fn begin_node(&mut self, scalar: Option<&str>, line: usize) -> Option<usize> {
+        match self {
+            Frame::Mapping {
+                keys,
+                key,
+                expects_value: false,
+            } => {
+                *key = scalar.map(|s| s.to_string());
+                let k = scalar?;
+                match keys.get(k) {
+                    Some(first_line) => Some(*first_line),
+                    None => {
+                        keys.insert(k.to_string(), line);
+                        None
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Called when a node inside this frame ended.
+    fn end_node(&mut self) {
+        match self {
+            Frame::Mapping { expects_value, .. } => *expects_value = !*expects_value,
+            Frame::Sequence { index } => *index += 1,
+        }
+    }
+}
+
+/// Path of the current position in the file, with key as the last part.
+fn frames_path(frames: &[Frame], key: &str) -> String {
+    let mut out = node_path(frames);
+    if !out.is_empty() {
+        out.push('.');
+    }
+    out.push_str(key);
+    out
+}
+
+/// Path of the node (a value, not a key) at the current position in the file.
+fn node_path(frames: &[Frame]) -> String {
+    let mut out = String::new();
+    for frame in frames {
+        match frame {
+            Frame::Mapping {
+                key: Some(k),
+                expects_value: true,
+                ..
+            } => {
+                if !out.is_empty() {
+                    out.push('.');
+                }
+                out.push_str(k);
+            }
+            Frame::Sequence { index } => out.push_str(&format!("[{}]", index)),
+            _ => {}
+        }
+    }
+    out
+}
+
+/// Path of the node (a value, not a key) at the current position in the file, as parts.
+fn node_parts(frames: &[Frame]) -> Vec<ItemPathPart> {
+    frames
+        .iter()
+        .filter_map(|frame| match frame {
+            Frame::Mapping {
+                key: Some(k),
+                expects_value: true,
+                ..
+            } => Some(ItemPathPart::Key(k.clone())),
+            Frame::Sequence { index } => Some(ItemPathPart::Index(*index)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// A scalar found by walk_scalars.
+struct ScalarNode<'a> {
+    value: &'a str,
+    style: TScalarStyle,
+    tag: &'a Option<TokenType>,
+    line: usize,
+    /// Whether the scalar is a key of a mapping.
+    is_key: bool,
+    /// The line of the first occurrence, if the scalar is a key that already occurred in its mapping.
+    first_line: Option<usize>,
+}
+
+/// Reads the events of the YAML content of the file at path and calls f for every scalar, with
+/// the frames of the mappings and sequences containing it (see node_path and frames_path).
+fn walk_scalars(
+    path: &str,
+    content: &str,
+    mut f: impl FnMut(&[Frame], ScalarNode),
+) -> PyResult<()> {
+    let mut parser = Parser::new(content.chars());
+    let mut frames: Vec<Frame> = vec![];
+    loop {
+        let (event, mark) = parser.next().map_err(|e| {
+            InvalidDocumentError::new_err(format!("Unable to read YAML file {}: {}", path, e))
+        })?;
+        match &event {
+            Event::StreamEnd => return Ok(()),
+            Event::Alias(_) | Event::MappingStart(_) | Event::SequenceStart(_) => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.begin_node(None, mark.line());
+                }
+            }
+            Event::Scalar(v, style, _, tag) => {
+                let is_key = matches!(
+                    frames.last(),
+                    Some(Frame::Mapping {
+                        expects_value: false,
+                        ..
+                    })
+                );
+                let first_line = frames
+                    .last_mut()
+                    .and_then(|frame| frame.begin_node(Some(v.as_str()), mark.line()));
+                f(
+                    &frames,
+                    ScalarNode {
+                        value: v,
+                        style: *style,
+                        tag,
+                        line: mark.line(),
+                        is_key,
+                        first_line,
+                    },
+                );
+            }
+            _ => {}
+        }
+        match event {
+            Event::MappingStart(_) => frames.push(Frame::Mapping {
+                keys: HashMap::new(),
+                key: None,
+                expects_value: false,
+            }),
+            Event::SequenceStart(_) => frames.push(Frame::Sequence { index: 0 }),
+            Event::MappingEnd | Event::SequenceEnd => {
+                frames.pop();
+                if let Some(frame) = frames.last_mut() {
+                    frame.end_node();
+                }
+            }
+            Event::Scalar(..) | Event::Alias(_) => {
+                if let Some(frame) = frames.last_mut() {
+                    frame.end_node();
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Finds all keys that occur more than once in the same mapping of the YAML content of the file
+/// at path.
+pub(crate) fn find_duplicate_keys(path: &str, content: &str) -> PyResult<Vec<DuplicateKey>> {
+    let mut out = vec![];
+    walk_scalars(path, content, |frames, node| {
+        if let Some(first_line) = node.first_line {
+            out.push(DuplicateKey {
+                path: frames_path(&frames[..frames.len() - 1], node.value),
+                first_line,
+                line: node.line,
+            });
+        }
+    })?;
+    Ok(out)
+}
+
+/// A plain scalar, that is typed differently by the default (YAML 1.1) typing than by the YAML 1.2
+/// core schema (e.g. ``no`` is false instead of a string, ``022`` is 18 instead of 22).
+pub(crate) struct Coercion {
+    /// Path of the value in the file ("base.more.key", "base.list[0]").
+    pub(crate) path: String,
+    pub(crate) line: usize,
+    /// The scalar as written in the file.
+    pub(crate) text: String,
+    /// The value with the default typing.
+    pub(crate) value: SimpleYcdValueType,
+    /// The value with the YAML 1.2 core schema.
+    pub(crate) core_value: SimpleYcdValueType,
+}
+
+/// Finds all plain scalars (not keys) in the YAML content of the file at path, whose value
+/// depends on whether the YAML 1.2 core schema is used (see parse_yaml).
+pub(crate) fn find_coercions(path: &str, content: &str) -> PyResult<Vec<Coercion>> {
+    let mut out = vec![];
+    walk_scalars(path, content, |frames, node| {
+        if !node.is_key && node.style == TScalarStyle::Plain && node.tag.is_none() {
+            if let Some((value, core_value)) = coerced(node.value) {
+                out.push(Coercion {
+                    path: node_path(frames),
+                    line: node.line,
+                    text: node.value.to_string(),
+                    value,
+                    core_value,
+                });
+            }
+        }
+    })?;
+    Ok(out)
+}
+
+/// Finds the paths of all scalars (not keys) tagged with ``!encrypted`` in the YAML content of
+/// the file at path (see ``encryption``).
+pub(crate) fn find_encrypted_tags(path: &str, content: &str) -> PyResult<Vec<Vec<ItemPathPart>>> {
+    let mut out = vec![];
+    walk_scalars(path, content, |frames, node| {
+        let encrypted = matches!(
+            node.tag,
+            Some(TokenType::Tag(handle, suffix)) if handle == "!" && suffix == "encrypted"
+        );
+        if !node.is_key && encrypted {
+            out.push(node_parts(frames));
+        }
+    })?;
+    Ok(out)
+}
+
+/// Finds the paths and the text of all plain scalars (not keys) in the YAML content of the file at
+/// path, that may be floats (see floats_to_source_decimals).
+pub(crate) fn find_float_texts(
+    path: &str,
+    content: &str,
+) -> PyResult<Vec<(Vec<ItemPathPart>, String)>> {
+    let mut out = vec![];
+    walk_scalars(path, content, |frames, node| {
+        let numeric = node.value.chars().any(|c| c.is_ascii_digit())
+            && node
+                .value
+                .chars()
+                .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+        if !node.is_key && node.style == TScalarStyle::Plain && numeric {
+            out.push((node_parts(frames), node.value.to_string()));
+        }
+    })?;
+    Ok(out)
+}
+
+/// The default typing and the core schema typing of a plain scalar, if they differ.
+fn coerced(text: &str) -> Option<(SimpleYcdValueType, SimpleYcdValueType)> {
+    let core_value = resolve_core_schema(text)?;
+    let value = resolve_default(text)?;
+    let same = match (&value, &core_value) {
+        (YString(a), YString(b)) => a == b,
+        (Bool(a), Bool(b)) => a == b,
+        (Int(a), Int(b)) => a == b,
+        (Float(a), Float(b)) => a == b || (a.is_nan() && b.is_nan()),
+        _ => false,
+    };
+    if same {
+        None
+    } else {
+        Some((value, core_value))
+    }
+}
+
+/// Describes a scalar value for messages, e.g. ``the boolean false``.
+pub(crate) fn describe_scalar(value: &SimpleYcdValueType) -> String {
+    match value {
+        YString(v) => format!("the string \"{}\"", v),
+        Bool(v) => format!("the boolean {}", v),
+        Int(v) => format!("the integer {}", v),
+        Float(v) => format!("the float {}", v),
+        Dict(_) | List(_) => "a collection".to_string(),
+    }
+}
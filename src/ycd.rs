@@ -1,22 +1,61 @@
-use crate::conv::YcdValueType::Dict;
-use crate::conv::{PyYamlConfigDocument, YcdDict, YcdValueType};
-use crate::variables::{process_variables, process_variables_for};
+use crate::anchors::resolve_anchors;
+use crate::audit::{audit_log, AuditEntry, TemplateAuditEntry};
+use crate::conv::YcdValueType::{Dict, Ycd};
+use crate::conv::{
+    dict_to_object, rename_key, reorder_keys, traverse_dict, PyYamlConfigDocument,
+    SimpleYcdValueType, YHashMap, YcdDict, YcdValueType,
+};
+use crate::diff::extract_override;
+use crate::enforcement::{check_policies, enforce_policies, Violation};
+use crate::export::{
+    document_body, render_yaml, round_floats, to_canonical_yaml, to_env_file, to_env_vars,
+    Annotations, Rendering,
+};
+use crate::features::file_features;
+use crate::graph::{
+    collect_subdocuments, mark_as_override, MergeRole, MergeSource, ReferenceGraph,
+    ResolvedReference,
+};
+use crate::lint::{lint_dict, LintFinding, UNKNOWN_KEY};
+use crate::lookup::{select_lookup_paths, LookupPathEntry};
+use crate::merger::evaluate_condition;
+use crate::minijinja::{helper_name, SafeMode};
+use crate::nested::drop_value;
+use crate::options::{LoadOptions, VariableOptions};
+use crate::policy::unescape_all;
+use crate::provenance::{document_files, relative_file, same_file, value_files, Provenance};
+use crate::redaction::{own_secret_patterns, redact, secret_patterns, with_redacted_errors};
+use crate::schema::{enforce_typed_schema, schema_of, TypedSchema};
+use crate::stats::{MergeStats, Stats};
+use crate::suggestions::{did_you_mean, invalid_header_error, suggestion_hint, with_suggestions};
+use crate::tracing::with_span;
+use crate::validation::{validate_layer, validate_merged};
+use crate::variables::{process_variables, process_variables_at, process_variables_for};
+use crate::view::DocumentView;
+use crate::walk::{walk, WalkNode};
+use crate::write_back::{find_source, write_to_source, SourceLines};
+use crate::ycd_list::PyYcdList;
 use crate::{
-    construct_new_ycd, delete_remove_markers, load_subdocuments, load_yaml_file,
-    recursive_docs_to_dicts, resolve_and_merge, CircularDependencyError, InvalidDocumentError,
-    InvalidHeaderError, SchemaError, REF,
+    apply_custom_directives, apply_defaults, construct_new_ycd, delete_metadata,
+    delete_remove_markers, load_document_file, load_subdocuments, merge_documents,
+    prepare_document_dict, recursive_docs_to_dicts, resolve_and_merge, CircularDependencyError,
+    InvalidDocumentError, InvalidHeaderError, RuleViolationError, SchemaError, UnknownKeyError,
+    UnknownKeyWarning, COMMENT, DIRECTIVES, FEATURES, REF,
 };
+use indexmap::IndexMap;
+use pyo3::class::gc::{PyGCProtocol, PyTraverseError, PyVisit};
 pub(crate) use pyo3::exceptions;
 pub(crate) use pyo3::prelude::*;
-use pyo3::types::{PyDict, PyList, PyTuple, PyType};
+use pyo3::types::{PyBytes, PyDict, PyList, PyTuple, PyType};
 use pyo3::PyIterProtocol;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
 use std::mem::take;
 
 /// A document represented by a dictionary, that can be validated,
 /// can contain references to other (sub-)documents, which can be resolved,
 /// and variables that can be parsed.
-#[pyclass(module = "_main", subclass)]
+#[pyclass(module = "_main", subclass, gc, weakref)]
 #[derive(Clone, Debug)]
 pub(crate) struct YamlConfigDocument {
     pub(crate) doc: YcdDict,
@@ -26,12 +65,48 @@ pub(crate) struct YamlConfigDocument {
     pub(crate) path: Option<String>,
     #[pyo3(get, set)]
     pub(crate) parent_doc: Option<Py<YamlConfigDocument>>,
+    /// Path of this document in the parent document (parts separated by "/"), if it is a
+    /// sub-document.
+    pub(crate) path_in_parent: Option<String>,
     #[pyo3(get, set)]
     pub(crate) absolute_paths: Vec<String>,
     pub(crate) bound_helpers: HashMap<String, PyObject>,
     pub(crate) already_loaded_docs: Option<Vec<String>>,
+    /// All $refs that were resolved for this document (including those of referenced documents).
+    pub(crate) references: Vec<ResolvedReference>,
+    /// The resolved $vars visible to templates of this document. Set by process_vars.
+    pub(crate) local_vars: YcdDict,
+    /// The merged values files, visible to templates as ``values``. Set by process_vars.
+    pub(crate) values: Option<YcdDict>,
+    /// Variables visible to all templates (e.g. of a resolution context). Set by process_vars.
+    pub(crate) globals: Option<YcdDict>,
+    /// Whether templates can call the variable helpers of parent documents. Set by process_vars.
+    pub(crate) inherit_helpers: bool,
+    /// Whether templates are rendered in safe mode (see SafeMode). Set by process_vars.
+    pub(crate) safe: bool,
+    /// The $features declared by the file this document was loaded from.
+    pub(crate) features: Vec<String>,
+    /// The files that were merged into this document, in merge order (lowest priority first).
+    pub(crate) sources: Vec<MergeSource>,
+    /// The files the values of this document were loaded from.
+    pub(crate) provenance: Provenance,
+    /// The templates rendered by the last process_vars call, if processed with ``audit``.
+    pub(crate) template_audit: Vec<AuditEntry>,
+    /// The patterns of the secret values (see secret_patterns) redacted in the audit log.
+    pub(crate) audit_secrets: Vec<String>,
+    /// The statistics of loading, resolving and processing this document (see merge_stats).
+    pub(crate) stats: Stats,
+    /// Whether the document is shared by several places via the identity map. Shared documents
+    /// are copied before they are merged into.
+    pub(crate) shared: bool,
 }
 
+// Documents are shared between Python threads, so no field may be tied to the thread that created it.
+const _: fn() = || {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<YamlConfigDocument>();
+};
+
 #[pymethods]
 impl YamlConfigDocument {
     /// Constructs a YamlConfigDocument
@@ -64,13 +139,27 @@ impl YamlConfigDocument {
         };
 
         let mut slf = Self {
+            provenance: Provenance::from_body(&document, None),
             doc: document,
             frozen: None,
             path,
             bound_helpers: HashMap::new(),
             absolute_paths,
             parent_doc,
+            path_in_parent: None,
             already_loaded_docs: None,
+            references: vec![],
+            local_vars: YcdDict::new(),
+            values: None,
+            globals: None,
+            inherit_helpers: true,
+            safe: false,
+            features: vec![],
+            sources: vec![],
+            template_audit: vec![],
+            audit_secrets: vec![],
+            stats: Stats::default(),
+            shared: false,
         };
 
         slf.infinite_recursion_check(already_loaded_docs)?;
@@ -82,40 +171,118 @@ impl YamlConfigDocument {
     /// Expects the content to be a dictionary with one key (defined in the
     /// header method) and it's value is the body of the document,
     /// validated by the schema method.
+    ///
+    /// Files ending in .ini or .cfg are read as INI files instead. Sections are mapped to dicts
+    /// (dots in section names create nested dicts), so the header is the first section name part.
+    /// Files ending in .json and .toml are read as JSON and TOML files, with the header as top
+    /// level key (or table).
+    ///
+    /// If the body contains ``$schema: <name>``, it is validated against that schema directly after
+    /// loading (see ``register_schema``; only registered names are used). This also applies to all files
+    /// loaded via $ref.
+    ///
+    /// A file can restrict what its documents may do with a ``$features`` list next to the header
+    /// (e.g. ``$features: [no-templates, strict-types]``), for example for contributed documents:
+    ///
+    /// - ``no-templates``: Strings must not contain templates.
+    /// - ``no-references``: $ref, $ref_first, $extends and $import_all must not be used.
+    /// - ``no-remove``: $remove, ``$remove::`` and $remove_if must not be used.
+    /// - ``strict-types``: Values must have the same type as the values they override while merging.
+    ///
+    /// Violations raise an InvalidDocumentError. This also applies to all files loaded via $ref. The
+    /// ``features`` loading option imposes features on all loaded files, a file can't lift them.
+    ///
+    /// Keyword arguments are loading options:
+    ///
+    /// - ``ini_infer_types`` (default True): Convert INI values into bools, ints and floats where possible.
+    /// - ``directive_policy`` (default None): A DirectivePolicy, to use a different directive prefix or
+    ///   to forbid some directives in the loaded files.
+    /// - ``profile`` (default None): If set, the section of that profile in ``profiles`` (a mapping of profile names
+    ///   to overrides in the body of a file) is merged over the body of every loaded file. ``profiles`` is then a
+    ///   reserved key and removed from all files. Without a profile, ``profiles`` is a normal key.
+    /// - ``lock_violation`` (default ``"error"``): What happens if a document changes a key locked with
+    ///   $lock while merging: ``"error"`` raises a LockViolationError, ``"ignore"`` keeps the locked value.
+    /// - ``context`` (default None): A ResolutionContext, whose registered templates and documents are used
+    ///   in addition to the globally registered ones.
+    /// - ``globals`` (default None): A dict of variables available in the conditions of lookup paths (see
+    ///   ``resolve_and_merge_references``) and in templated $ref targets. They take priority over the globals
+    ///   of the ``context``.
+    /// - ``env_interpolation`` (default False): Replace ``${VAR}`` expressions (with the modifiers of
+    ///   ``compose_interpolation`` of ``process_vars``) in all strings with environment variables, directly
+    ///   after reading a file and before anything is merged. This can be used to select reference targets
+    ///   (``$ref: /overrides/${REGION}``). ``$${`` is a literal ``${``.
+    /// - ``core_schema`` (default False): Type the scalars of YAML files with the YAML 1.2 core schema: Only
+    ///   ``true`` and ``false`` are booleans (``yes``, ``no``, ``on`` and ``off`` stay strings, also in INI
+    ///   files), a leading zero doesn't make a number octal (use ``0o17``) and only ``.inf`` and ``.nan`` are
+    ///   special floats.
+    /// - ``decimal_floats`` (default False): Convert the floats of loaded files to ``decimal.Decimal``, written
+    ///   exactly like in YAML files (``0.1`` instead of ``0.1000000000000000055511151231257827``, no digits are
+    ///   lost). The floats of other files are written with as few digits as possible. Decimals are
+    ///   written like they were loaded by ``to_yaml`` and as floats by ``to_json`` and ``to_msgpack``.
+    /// - ``duplicate_keys`` (default ``"ignore"``): What happens if a mapping in a YAML file contains the same
+    ///   key more than once: ``"error"`` raises a DuplicateKeyError, ``"warn"`` emits a DuplicateKeyWarning
+    ///   (both name the lines of both occurrences), ``"ignore"`` lets the later key win.
+    /// - ``report_coercions`` (default False): Emit a CoercionWarning for every scalar in a YAML file, that
+    ///   is typed differently than with ``core_schema`` (e.g. ``no`` is false instead of the string ``"no"``,
+    ///   ``022`` is 18 instead of 22), naming the path, file and line. Use this to find the values that
+    ///   change when switching to ``core_schema``.
+    /// - ``validation`` (default None): Validate against the schema while loading. ``"layers"`` validates every
+    ///   loaded file (also those loaded via $ref, $extends and $import_all) on its own, before it is merged, and
+    ///   raises a LayerValidationError naming the file. All keys are optional in this phase and directives,
+    ///   removed values and values containing templates are skipped. ``"merged"`` validates the document at the
+    ///   end of ``resolve_and_merge_references`` like ``validate`` and raises a MergedValidationError (templates
+    ///   are not rendered yet at that point). ``"both"`` does both. Both errors are SchemaErrors, caused by the
+    ///   original error.
+    /// - ``max_file_size``, ``max_depth`` and ``max_nodes`` (default None): Limits for every loaded file: Its
+    ///   size in bytes, how deeply dicts and lists are nested (the top level of a file has depth 1) and the
+    ///   number of values (aliases count every time they are used). Parsing stops as soon as a limit is
+    ///   exceeded and a DocumentLimitError is raised. Use these when loading user-supplied files.
+    /// - ``virtual_files`` (default None): A dict of file paths to contents. The contents are used instead of the
+    ///   files at these paths (which don't have to exist), when loading files, looking up references and matching
+    ///   $import_all patterns. Relative paths are relative to the working directory. Use this to preview the result
+    ///   of changing files without touching the file system.
+    /// - ``cache_dir`` (default None): Directory the files of remote lookup paths are cached in. Defaults to
+    ///   ``$CONFIGCRUNCH_CACHE_DIR`` or ``configcrunch`` in the user's cache directory.
+    /// - ``trusted_keys`` (default None): A dict of directories or lookup paths to minisign public keys (the
+    ///   base64 encoded key or the content of the ``.pub`` file). Every file loaded from inside such a directory
+    ///   must have a valid detached signature of that key in ``<file>.minisig`` (for nested directories, the key
+    ///   of the innermost one is used). Otherwise a SignatureError is raised before the file is parsed. For
+    ///   HTTPS, git and package lookup paths, the key applies to the directory they are resolved to, the
+    ///   signatures of files of HTTPS lookup paths are fetched next to them.
+    /// - ``timeout`` (default None) and ``cancel_token`` (default None): Abort loading and resolving with a
+    ///   ResolutionCancelledError after ``timeout`` seconds or when the CancellationToken is cancelled. This is
+    ///   checked before every file is loaded and every document is merged or resolved.
+    /// - ``progress`` (default None): A callable, that is called with ``(event, count, detail)`` after every
+    ///   loaded file (event ``"file_loaded"``, detail is the path) and every merged document (event
+    ///   ``"document_merged"``, detail is the path of the document merged into). ``count`` is the number of events
+    ///   of that kind so far. The total isn't known in advance, since references are only discovered while
+    ///   resolving. Exceptions raised by the callable abort loading.
+    /// - ``safe`` (default False): Render the templates evaluated while loading and resolving (templated $ref
+    ///   targets, $ref_first and lookup path conditions, the ``if`` of manifest layers) in safe mode, like
+    ///   ``safe`` of ``process_vars``: Calling variable helpers other than ``parent()`` and ``path_in_parent()``
+    ///   raises an error. Use this, together with a DirectivePolicy, when loading untrusted files.
+    /// - ``identity_map`` (default False): Sub-documents of the same type, that consist of nothing but a $ref to
+    ///   the same file (without parameters or templates) and, in dicts, have the same key, are loaded once per
+    ///   resolution and are the same document object. Its ``parent()`` and ``path_in_parent()`` refer to the
+    ///   place it was loaded at first, ``process_vars`` renders it once (at the first place it processes) and
+    ///   changes through one place (e.g. ``internal_set``) are visible at all places. Documents merged over a
+    ///   shared document (e.g. with ``overlay``) are merged into a copy of it, the other places keep the
+    ///   shared document.
+    /// - ``features`` (default None): A list of features (see above), that every loaded file (also those loaded
+    ///   via $ref, registered documents and the dicts of ``documents_from_dicts``) has, as if it declared them in
+    ///   its ``$features``. The ``$features`` of a file can only add more features.
     #[classmethod]
+    #[args(kwargs = "**")]
     pub(crate) fn from_yaml(
         cls: &PyType,
         py: Python,
         path_to_yaml: String,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<PyYamlConfigDocument> {
-        let mut entire_document = load_yaml_file(&path_to_yaml)?;
-        let header = cls.getattr("header")?.call0()?;
-        let header: &str = header.extract()?;
-        if !entire_document.contains_key(header) {
-            return Err(InvalidHeaderError::new_err(format!(
-                "The document does not have a valid header. Expected was: {}",
-                header
-            )));
-        }
-        let content = entire_document.remove(header).unwrap();
-        match content {
-            YcdValueType::Dict(c) => construct_new_ycd(
-                py,
-                cls,
-                [
-                    cls.into_py(py),
-                    c.into_py(py),
-                    py.None(),
-                    py.None(),
-                    py.None(),
-                    vec![path_to_yaml].into_py(py),
-                ],
-            ),
-            _ => Err(InvalidDocumentError::new_err(format!(
-                "The document at {} is invalid",
-                path_to_yaml
-            ))),
-        }
+        let options = LoadOptions::from_kwargs(kwargs)?;
+        let doc = Self::from_yaml_internal(cls, py, path_to_yaml, &options)?;
+        doc.borrow_mut(py).stats = options.stats;
+        Ok(doc)
     }
 
     #[classmethod]
@@ -138,6 +305,81 @@ impl YamlConfigDocument {
         )
     }
 
+    /// Constructs a YamlConfigDocument from MessagePack data created by ``to_msgpack``.
+    ///
+    /// Sub-documents are restored as dicts, call ``resolve_and_merge_references`` to convert them into
+    /// documents again.
+    #[classmethod]
+    pub(crate) fn from_msgpack(
+        cls: &PyType,
+        py: Python,
+        data: &[u8],
+    ) -> PyResult<PyYamlConfigDocument> {
+        match rmp_serde::from_slice::<IndexMap<String, SimpleYcdValueType>>(data) {
+            Ok(v) => Self::from_entire_document(cls, py, YHashMap(v).into(), "<msgpack>", None),
+            Err(e) => Err(InvalidDocumentError::new_err(format!(
+                "Unable to read MessagePack data: {:?}",
+                e
+            ))),
+        }
+    }
+
+    /// Constructs many documents from already parsed data (e.g. read from a database), without
+    /// reading or parsing any files. Each dict must contain the header, like the content of a file.
+    ///
+    /// The dicts are prepared exactly like loaded files (directive policy, $features, profiles, inline
+    /// schemas and ``env_interpolation``). If ``lookup_paths`` is given, the references of every document
+    /// are resolved (see ``resolve_and_merge_references``).
+    ///
+    /// Keyword arguments are loading options, see ``from_yaml``.
+    ///
+    ///  :returns: The documents, in the order of the dicts.
+    #[classmethod]
+    #[args(lookup_paths = "None", kwargs = "**")]
+    pub(crate) fn documents_from_dicts(
+        cls: &PyType,
+        py: Python,
+        dicts: Vec<YcdDict>,
+        lookup_paths: Option<Vec<LookupPathEntry>>,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<Vec<PyYamlConfigDocument>> {
+        let mut options = LoadOptions::from_kwargs(kwargs)?;
+        let _safe_mode = SafeMode::enter(options.safe);
+        let lookup_paths = match lookup_paths {
+            None => None,
+            Some(entries) => Some(select_lookup_paths(py, cls, entries, &options)?),
+        };
+        let mut docs = Vec::with_capacity(dicts.len());
+        for (i, mut dict) in dicts.into_iter().enumerate() {
+            let source = format!("<dicts[{}]>", i);
+            let header = Self::canonicalize_header(cls, &mut dict, &source)?;
+            if !dict.contains_key(&header) {
+                return Err(invalid_header_error(
+                    py,
+                    cls,
+                    &dict,
+                    format!(
+                        "The document {} does not have a valid header. Expected was: {}",
+                        source, header
+                    ),
+                )?);
+            }
+            let dict = prepare_document_dict(py, &source, dict, &options)?;
+            let doc = Self::from_entire_document(cls, py, dict, &source, None)?;
+            validate_layer(py, &doc.0, &source, &options)?;
+            if let Some(lookup_paths) = &lookup_paths {
+                Self::resolve_document(
+                    doc.0.clone_ref(py),
+                    py,
+                    lookup_paths.clone(),
+                    &mut options,
+                )?;
+            }
+            docs.push(doc);
+        }
+        Ok(docs)
+    }
+
     /// Header that YAML-documents must contain.
     #[classmethod]
     pub(crate) fn header(_cls: &PyType) -> PyResult<String> {
@@ -150,7 +392,32 @@ impl YamlConfigDocument {
         ))
     }
 
-    /// Schema that the document should be validated against.
+    /// Alternative headers, that are accepted instead of ``header`` in loaded documents, e.g. the old
+    /// header after renaming a document type, or vendor-prefixed headers. The alias is replaced by
+    /// ``header`` when the document is loaded. A document may only contain one of them.
+    ///
+    /// By default this is empty. Can be extended by subclasses.
+    #[classmethod]
+    fn header_aliases(_cls: &PyType) -> Vec<String> {
+        vec![]
+    }
+
+    /// Whether loaded documents without the header (and without one of the ``header_aliases``)
+    /// are accepted. If so, the entire file is the body of the document, as if it was placed under
+    /// the header. Use this to load plain YAML files, e.g. written by other tools. ``$features``
+    /// on the top level of such files are still the features of the file.
+    ///
+    /// Note that a headerless file, that contains the header as a top-level key, is read as a
+    /// document with header.
+    ///
+    /// By default this is False. Can be extended by subclasses.
+    #[classmethod]
+    fn allow_headerless(_cls: &PyType) -> bool {
+        false
+    }
+
+    /// Schema that the document should be validated against. Must be implemented, unless the document
+    /// type has a ``typed_schema``: Then ``validate`` only checks the typed schema and the validation rules.
     #[classmethod]
     pub(crate) fn schema(_cls: &PyType) -> PyResult<PyObject> {
         debug_assert!(
@@ -168,6 +435,8 @@ impl YamlConfigDocument {
     /// - The first element is the path to the element, with part pieces (nested dicts) seperated by "/".
     ///   If the path ends with [] and at that location is either a list or a dict, then all values will be converted.
     ///   Otherwise only the exact specified path will be converted, it must be a dict, matching the schema.
+    ///   If the path ends with [list] or [map] instead, the element must be a list or a dict respectively.
+    ///   The dicts on the path must exist, unless their key is followed by "?" (``"a?/b"``).
     /// - The second element is the referenced document type
     ///
     /// Example for tuples for a given dict::
@@ -176,102 +445,455 @@ impl YamlConfigDocument {
     ///     single = ("a/b": ...)
     ///     on_list = ("a/c[]": ...)
     ///     on_dict = ("a/d[]": ...)
+    ///     only_on_list = ("a/c[list]": ...)
+    ///     only_on_dict = ("a/d[map]": ...)
+    ///
+    /// In the schema, lists and dicts of sub-documents can be validated with ``DocReference.list_of``
+    /// and ``DocReference.map_of``.
+    ///
+    /// By default the locations are taken from the ``DocReference`` entries in the schema: Under
+    /// literal keys (also ``Optional`` etc.), in single element lists (``[DocReference(X)]``),
+    /// as values of dicts with non-literal keys (``{str: DocReference(X)}``), in ``And`` and with
+    /// ``DocReference.list_of`` / ``DocReference.map_of``. Other sub-documents are not found.
+    /// The dicts containing them may be missing.
+    /// Override this to use other locations (or return an empty list to not load any).
     #[classmethod]
-    fn subdocuments(_cls: &PyType) -> PyResult<PyObject> {
-        debug_assert!(
-            false,
-            "The class method subdocuments must be implemented. Do not call the parent method."
-        );
-        Err(exceptions::PyTypeError::new_err(
-            "The class method subdocuments must be implemented. Do not call the parent method.",
-        ))
+    fn subdocuments(cls: &PyType, py: Python) -> PyResult<PyObject> {
+        let schema = schema_of(py, cls)?;
+        Ok(py
+            .import("configcrunch._util")?
+            .getattr("find_subdocument_specs")?
+            .call1((schema,))?
+            .into())
+    }
+
+    /// Keys that contain metadata (notes, ownership info, ...) for the authors of documents.
+    /// Their values must be strings. They are removed from the document
+    /// while resolving (``resolve_and_merge_references``).
+    ///
+    /// By default this is only ``$comment``. Can be extended by subclasses.
+    #[classmethod]
+    fn metadata_keys(_cls: &PyType) -> Vec<&'static str> {
+        vec![COMMENT]
+    }
+
+    /// Keys of the document body, that ``normalize`` writes first, in this order. All other keys
+    /// follow in alphabetical order.
+    ///
+    /// By default this is empty. Can be extended by subclasses.
+    #[classmethod]
+    fn key_order(_cls: &PyType) -> Vec<String> {
+        vec![]
+    }
+
+    /// Patterns of the paths of secret values in the document body. Parts are separated by "." and
+    /// ``*`` matches any key or list index (e.g. ``"databases.*.password"``). Values with a ``Secret``
+    /// schema are secret as well. Values of sub-documents are secret according to their own type.
+    ///
+    /// Secret values are redacted (replaced by ``****``) by ``__str__``, ``to_yaml``, ``to_json``,
+    /// ``to_env``, ``render_annotated_yaml`` and in the messages of ``lint_override`` and of
+    /// validation errors (schema, coercion and typed schema errors). ``to_dict``, ``doc``,
+    /// ``normalize``, ``to_msgpack`` and ``to_yaml``/``to_json``/``to_env`` with ``redact=False``
+    /// always contain all values.
+    ///
+    /// By default this is empty. Can be extended by subclasses.
+    #[classmethod]
+    fn secret_paths(_cls: &PyType) -> Vec<String> {
+        vec![]
+    }
+
+    /// How lists (and dicts) are merged when documents of this type are merged. A dict of path
+    /// patterns (parts separated by "." and ``*`` matches any key or list index, like
+    /// ``secret_paths``) to strategies:
+    ///
+    /// - ``"append"``: Entries of the overriding list are appended (default for lists).
+    /// - ``"set"``: Entries of the overriding list are appended, if the list doesn't contain them yet.
+    /// - ``"keyed:<key>"``: Entries of the overriding list (dicts), that have the same value of ``<key>``
+    ///   as an entry of the base list, are merged over it. Other entries are appended.
+    /// - ``"replace"``: The overriding value replaces the value of the base (also dicts and lists).
+    ///
+    /// Scalars are always replaced. ``$remove::``, ``$insert_before::`` and ``$insert_after::`` entries
+    /// work with all list strategies. Values of sub-documents are merged according to their own type.
+    ///
+    /// By default the strategies are taken from the schema: ``"set"`` for ``UniqueList``,
+    /// ``"keyed:<key>"`` for ``KeyedList``. They are derived once per document type, types without
+    /// a schema have no strategies. Override this to use other strategies.
+    #[classmethod]
+    fn merge_strategies(cls: &PyType, py: Python) -> PyResult<PyObject> {
+        Ok(py
+            .import("configcrunch._util")?
+            .getattr("default_merge_strategies")?
+            .call1((cls,))?
+            .into())
+    }
+
+    /// Validation rules spanning multiple fields, checked by ``validate`` after the schema.
+    ///
+    /// A dict of rule names to rules. A rule is either a template expression (evaluated like
+    /// variables, eg. ``"not tls.enabled or tls.cert"``) that must be true, or a callable that is
+    /// called with the document body (as dict) and must return True (or raise a SchemaError).
+    ///
+    /// By default there are no rules. Can be extended by subclasses.
+    #[classmethod]
+    fn validation_rules(_cls: &PyType, py: Python) -> PyObject {
+        PyDict::new(py).into()
+    }
+
+    /// What happens with keys that are not part of the schema (see ``validate``).
+    /// Either ``None`` (default; up to the schema), ``"error"``, ``"warn"`` or ``"keep"``.
+    /// Can be changed by subclasses.
+    #[classmethod]
+    fn unknown_keys(_cls: &PyType) -> Option<&'static str> {
+        None
+    }
+
+    /// A ``TypedSchema`` of the document body: Required keys, the types of values and sub-documents.
+    /// It is checked natively at the end of ``resolve_and_merge_references`` and by ``validate``
+    /// (before the schema, which document types with a typed schema don't need to implement). If the
+    /// document or its sub-documents don't match their typed schemas, an InvalidDocumentError is raised,
+    /// that lists the paths of all invalid keys (also available via its ``violations`` attribute, a list
+    /// of (path, message) tuples). Paths start with the header of the document, or with the path of a
+    /// sub-document in its parent::
+    ///
+    ///     @classmethod
+    ///     def typed_schema(cls):
+    ///         return TypedSchema({'name': str, 'port': Field(int, required=False)})
+    ///
+    /// By default there is no typed schema (``None``). Can be changed by subclasses.
+    #[classmethod]
+    fn typed_schema(_cls: &PyType) -> Option<TypedSchema> {
+        None
     }
 
-    /// Validates the document against the Schema.
-    pub(crate) fn validate(slf: &PyCell<Self>, py: Python) -> PyResult<bool> {
+    /// Validates the document against the Schema and the validation rules (see ``validation_rules``).
+    /// If rules are violated, a RuleViolationError (a SchemaError) is raised, that lists all violated rules.
+    /// Their names are also available via it's ``rules`` attribute.
+    ///
+    /// Before validating, values with a ``Coerce`` schema are coerced (this changes the document).
+    ///
+    /// :param unknown_keys: What happens with keys that are not part of the schema. Defaults to
+    ///                      the ``unknown_keys`` class method. ``"error"`` raises an UnknownKeyError
+    ///                      (a SchemaError), ``"warn"`` emits an UnknownKeyWarning, ``"keep"`` silently keeps them.
+    ///                      Messages name the file that set each key and suggest close keys of the schema
+    ///                      ("did you mean"). The ``suggestions`` attribute of the UnknownKeyError is a dict
+    ///                      of the paths of the unknown keys to them.
+    ///                      If ``None``, the schema decides.
+    #[args(unknown_keys = "None")]
+    pub(crate) fn validate(
+        slf: &PyCell<Self>,
+        py: Python,
+        unknown_keys: Option<&str>,
+    ) -> PyResult<bool> {
         if slf.borrow().frozen.is_some() {
             return Err(exceptions::PyRuntimeError::new_err(
                 "Document is already frozen.",
             ));
         }
-        let self_: PyRef<Self> = slf.borrow();
-        let args = PyTuple::new(py, [self_.doc.to_object(py)]);
-        slf.getattr("schema")?
-            .call0()?
-            .getattr("validate")?
-            .call1(args)?;
-        Ok(true)
+        let doc: PyYamlConfigDocument = Py::<Self>::from(slf).into();
+        with_redacted_errors(py, &doc, || {
+            let schema = schema_of(py, slf.getattr("__class__")?)?;
+            let self_: PyRef<Self> = slf.borrow();
+            let mut data = dict_to_object(py, &self_.doc);
+            drop(self_);
+            data = py
+                .import("configcrunch._util")?
+                .getattr("apply_coercions")?
+                .call1((schema, data))?
+                .into();
+            slf.borrow_mut().set_doc(data.extract(py)?);
+            enforce_typed_schema(py, &doc)?;
+            let unknown_keys: Option<String> = match unknown_keys {
+                Some(policy) => Some(policy.to_string()),
+                None => slf.getattr("unknown_keys")?.call0()?.extract()?,
+            };
+            if let Some(policy) = unknown_keys {
+                data = Self::handle_unknown_keys(slf, py, schema, data, &policy)?;
+            }
+            let args = PyTuple::new(py, [data]);
+            if !schema.is_none() {
+                schema.getattr("validate")?.call1(args)?;
+            }
+            Self::check_validation_rules(slf, py, args)?;
+            Ok(true)
+        })
     }
 
     /// Resolve the $ref entry at the beginning of the document body and merge with referenced documents
     /// (changes this document in place).
     ///
+    /// Before that, the templates named in the $extends entry (a name or a list of names) are merged
+    /// into the document. Templates are either registered with ``register_template`` or looked up
+    /// as documents in the repositories. Values from the document take priority over templates,
+    /// which take priority over the $ref.
+    ///
+    /// The $import_all entry (a path pattern like ``conf.d/*`` or a list of patterns) merges all matching
+    /// documents from all repositories into the document, with a lower priority than the $extends templates.
+    ///
+    /// Keys can be marked as deprecated with a ``$deprecated`` mapping (key -> message) in the dict
+    /// containing them. If a document overrides such a key while merging, a DeprecatedKeyWarning is emitted.
+    ///
+    /// The keys of ``$default`` mappings are only set if no document sets them.
+    /// ``$lock: true`` in a dict (or ``$lock: [keys...]``) prevents documents merged later from changing it (or the keys).
+    ///
+    /// Finally, ``$alias: <name>`` entries are replaced by copies of the dicts marked with ``$anchor: <name>``.
+    /// Anchors work across all files and sub-documents of the document.
+    ///
+    /// The $ref can also be written as a mapping with parameters: ``$ref: {path: ..., with: {...}}``.
+    /// The parameters are available as variables (like $vars) in the templates of the referenced document.
+    ///
+    /// Instead of $ref, $ref_first can contain a list of candidates, of which the first one that exists is used.
+    /// Candidates can be gated on a template expression: ``{path: ..., if: "expression"}``.
+    ///
+    /// The path of a $ref can contain a template (``$ref: "/variants/{{ variant }}"``). It is rendered before
+    /// the lookup, with the keys of the document (before merging), its $vars (not rendered), the globals of the
+    /// resolution context and ``parent()``.
+    ///
+    /// Afterwards, the document and its sub-documents are checked against the policies registered with
+    /// ``register_policy``. If they violate any, a PolicyViolationError is raised.
+    ///
     /// :param lookup_paths: Paths to the repositories, where referenced should be looked up.
+    ///                      An entry can also be an HTTPS base URL (``https://example.org/configs``): The
+    ///                      referenced files are fetched from there and cached on disk (see the ``cache_dir``
+    ///                      loading option). Cached files are revalidated with ETag / Last-Modified once per
+    ///                      process and used as they are if the server can't be reached. $import_all patterns
+    ///                      don't match the files of URLs.
+    ///                      An entry can also be a git repository: ``git+<url>[@<ref>][//<subdir>]``, e.g.
+    ///                      ``git+https://example.org/configs.git@v1.2//base``. The ref (a tag, branch or commit,
+    ///                      default: the default branch) is fetched once per process into the cache directory and
+    ///                      the references are looked up in the sub-directory of the checkout. If fetching fails,
+    ///                      an existing checkout is used.
+    ///                      An entry can also be the data of a Python package: ``pkg://<package>[/<subdir>]``,
+    ///                      e.g. ``pkg://mypackage/configs``. The directory is found with importlib.resources, so
+    ///                      libraries can ship documents in their wheels.
+    ///                      An entry can also be a dict with the ``path`` and a ``when`` condition (a template
+    ///                      expression), e.g. ``{'path': 'repo/linux', 'when': "platform == 'linux'"}``. The path
+    ///                      is only searched if the condition is true. Conditions can use ``platform``
+    ///                      (``sys.platform``), ``hostname`` and the ``globals`` loading option.
+    ///
+    /// Keyword arguments are loading options for the referenced documents, see ``from_yaml``.
     ///
     ///  :final: Since 0.2.0 this function must not be extended. Starting with 1.0.0, subclasses
     ///          overriding this method will be ignored.
     ///
     ///  :returns: self
+    #[args(kwargs = "**")]
     pub(crate) fn resolve_and_merge_references(
         slf: Py<Self>,
         py: Python,
-        lookup_paths: Vec<String>,
+        lookup_paths: Vec<LookupPathEntry>,
+        kwargs: Option<&PyDict>,
     ) -> PyResult<Py<YamlConfigDocument>> {
+        let mut options = LoadOptions::from_kwargs(kwargs)?;
+        let _safe_mode = SafeMode::enter(options.safe);
+        let cls: &PyType = slf.getattr(py, "__class__")?.extract(py)?;
+        let lookup_paths = select_lookup_paths(py, cls, lookup_paths, &options)?;
+        Self::resolve_document(slf, py, lookup_paths, &mut options)
+    }
+
+    /// Merges ``layer`` (a dict containing the document body or a YamlConfigDocument) over this document,
+    /// exactly like another file passed to ``load_multiple_yml`` (changes this document in place).
+    /// Values of the layer take priority and it may use $remove. The layer is not changed.
+    ///
+    /// Keyword arguments are loading options, see ``from_yaml``.
+    ///
+    ///  :returns: self
+    #[args(kwargs = "**")]
+    fn overlay(
+        slf: Py<Self>,
+        py: Python,
+        layer: YcdValueType,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<Py<Self>> {
         if slf.borrow(py).frozen.is_some() {
             return Err(exceptions::PyRuntimeError::new_err(
                 "Document is already frozen.",
             ));
         }
-        let slf_clone = slf.clone_ref(py);
+        let mut options = LoadOptions::from_kwargs(kwargs)?;
+        options.stats = slf.borrow(py).stats.clone();
+        let cls: Py<PyType> = slf.getattr(py, "__class__")?.extract(py)?;
+        let layer = Self::layer_to_document(cls.as_ref(py), py, layer)?;
+        Self::overlay_internal(slf, py, layer, &options)
+    }
 
-        if let Ok(cb) = slf.getattr(py, "_initialize_data_before_merge") {
-            let mut mref = slf.borrow_mut(py);
-            let args = PyTuple::new(py, [take(&mut mref.doc)]);
-            drop(mref);
-            let tmp = cb.call1(py, args)?.extract(py)?;
-            let mut mref = slf.borrow_mut(py);
-            mref.doc = tmp;
-            drop(mref);
+    /// Constructs a new document by merging all ``layers`` (dicts containing the document body
+    /// or YamlConfigDocuments), in order. Later layers take priority, like with ``load_multiple_yml``.
+    /// The layers are not changed.
+    ///
+    /// Keyword arguments are loading options, see ``from_yaml``.
+    #[classmethod]
+    #[args(kwargs = "**")]
+    fn compose(
+        cls: &PyType,
+        py: Python,
+        layers: Vec<YcdValueType>,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let mut options = LoadOptions::from_kwargs(kwargs)?;
+        let mut layers = layers.into_iter();
+        let doc: Py<Self> = match layers.next() {
+            None => {
+                return Err(exceptions::PyValueError::new_err(
+                    "At least one layer must be passed.",
+                ))
+            }
+            Some(layer) => Self::layer_to_document(cls, py, layer)?.into(),
+        };
+        options.stats = doc.borrow(py).stats.clone();
+        for layer in layers {
+            let layer = Self::layer_to_document(cls, py, layer)?;
+            Self::overlay_internal(doc.clone_ref(py), py, layer, &options)?;
         }
+        Ok(doc.into())
+    }
 
-        resolve_and_merge(py, slf.clone_ref(py).into(), &lookup_paths)?;
+    /// Lints ``layer`` (a dict containing the document body or a YamlConfigDocument, like for ``overlay``),
+    /// an override that is merged over this document. Neither is changed.
+    ///
+    /// Returns the findings as ``(kind, path, message)`` tuples, sorted by path. The parts of the paths are
+    /// separated by ".". Kinds are:
+    ///
+    /// - ``"dead_override"``: The value is the same as in this document (or an empty dict or list), so it has no effect.
+    /// - ``"nonexistent_remove"``: $remove of a key or $remove:: of a list entry that doesn't exist in this document.
+    /// - ``"unknown_key"``: The key is not part of the schema.
+    /// - ``"shadowed_key"``: The value is replaced (or removed) by one of ``later_layers``, the layers that are
+    ///   merged over the override afterwards (e.g. the later files passed to ``load_multiple_yml``).
+    ///
+    /// This document should be resolved (``resolve_and_merge_references``).
+    ///
+    /// The same is available on the command line (the document type is given as ``module:class``)::
+    ///
+    ///     python -m configcrunch lint-override myapp.config:Config base.yml override.yml [later.yml ...] -l ./repo
+    #[args(later_layers = "None")]
+    fn lint_override(
+        slf: Py<Self>,
+        py: Python,
+        layer: YcdValueType,
+        later_layers: Option<Vec<YcdValueType>>,
+    ) -> PyResult<Vec<LintFinding>> {
+        let base = recursive_docs_to_dicts(Dict(slf.borrow(py).doc.clone()), py)?.unwrap_dict();
+        let secrets = secret_patterns(py, &slf.clone_ref(py).into())?;
+        let layer = Self::layer_body(py, layer)?;
+        let later = later_layers
+            .unwrap_or_default()
+            .into_iter()
+            .map(|l| Self::layer_body(py, l))
+            .collect::<PyResult<Vec<YcdDict>>>()?;
+        let mut findings = vec![];
+        lint_dict(
+            Some(&base),
+            &layer,
+            &later.iter().collect::<Vec<&YcdDict>>(),
+            "",
+            &secrets,
+            &mut findings,
+        );
 
-        if let Ok(cb) = slf.getattr(py, "_initialize_data_after_merge") {
-            let mut mref = slf.borrow_mut(py);
-            let args = PyTuple::new(py, [take(&mut mref.doc).into_py(py)]);
-            drop(mref);
-            let tmp = cb.call1(py, args)?.extract(py)?;
-            let mut mref = slf.borrow_mut(py);
-            mref.doc = tmp;
-            drop(mref);
+        let schema = schema_of(py, slf.as_ref(py).get_type())?;
+        let util = py.import("configcrunch._util")?;
+        let unknown: Vec<Vec<String>> = util
+            .getattr("find_unknown_keys")?
+            .call1((schema, dict_to_object(py, &layer)))?
+            .extract()?;
+        for path in unknown {
+            if !path.iter().any(|k| DIRECTIVES.contains(&k.as_str())) {
+                let (key, parent) = path.split_last().unwrap();
+                let known: Vec<String> = util
+                    .getattr("schema_keys_at")?
+                    .call1((schema, parent.to_vec()))?
+                    .extract()?;
+                let close = did_you_mean(key, known.iter().map(String::as_str));
+                findings.push((
+                    UNKNOWN_KEY,
+                    path.join("."),
+                    format!(
+                        "The key is not part of the schema{}.",
+                        suggestion_hint(&close)
+                    ),
+                ));
+            }
         }
+        findings.sort_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)));
+        Ok(findings)
+    }
 
-        let subdoc_spec = slf.call_method0(py, "subdocuments")?.extract(py)?;
-        load_subdocuments(py, slf.clone_ref(py).into(), subdoc_spec, &lookup_paths)?;
-
-        let mut self_: PyRefMut<Self> = slf.borrow_mut(py);
-        let d = take(&mut self_.doc);
-        match delete_remove_markers(py, Dict(d))? {
-            Dict(dd) => self_.doc = dd,
-            _ => {
-                return Err(exceptions::PyRuntimeError::new_err(
-                    "Internal algorithm failure.",
-                ))
+    /// Computes the smallest override (a dict containing a document body) that changes this document
+    /// into ``target`` (a dict containing the desired body, or a YamlConfigDocument) when merged over it,
+    /// eg. with ``overlay``. Keys missing in ``target`` are removed via $remove.
+    ///
+    /// Lists can only be changed by appending entries and removing strings (via $remove::). If a list
+    /// was changed in a different way, a ValueError is raised.
+    ///
+    /// This document should be resolved (``resolve_and_merge_references``). Sub-documents are compared
+    /// by their content.
+    fn extract_override(slf: Py<Self>, py: Python, target: YcdValueType) -> PyResult<YcdDict> {
+        let base = recursive_docs_to_dicts(Dict(slf.borrow(py).doc.clone()), py)?;
+        let target = match target {
+            Dict(_) | YcdValueType::Ycd(_) => recursive_docs_to_dicts(target, py)?,
+            v => {
+                return Err(exceptions::PyTypeError::new_err(format!(
+                    "The target must be a dict or a YamlConfigDocument, got: {}",
+                    v
+                )))
             }
-        }
-        Ok(slf_clone)
+        };
+        Ok(match extract_override(&base, &target, "")? {
+            Some(Dict(d)) => d,
+            _ => YcdDict::new(),
+        })
     }
 
     /// Process all {{ variables }} inside this document and all sub-documents.
     ///  All references must be resolved beforehand to work correctly (resolve_and_merge_references).
     ///  Changes this document in place.
-    fn process_vars(slf: Py<Self>, py: Python) -> PyResult<Py<Self>> {
+    ///  Sub-documents are processed first, with the same options (so they share ``values`` and ``globals``).
+    ///
+    ///  Templates in the keys of dicts are rendered after all values. If a rendered key already exists,
+    ///  a VariableProcessingError is raised.
+    ///
+    ///  Dicts and sub-documents containing ``$remove_if: "<expression>"`` are removed (from their parent
+    ///  dict or list) after the variables were processed, if the expression is true.
+    ///
+    ///  Keyword arguments:
+    ///
+    ///  - ``compose_interpolation`` (default False): Before rendering the templates, interpolate
+    ///    Docker Compose style variables (``$VAR``, ``${VAR}``, ``${VAR:-default}``, ``${VAR:?error}``, ...)
+    ///    using the environment variables. ``$$`` can be used to get a literal ``$``.
+    ///  - ``inherit_vars`` (default False): Make the $vars of parent documents available to the
+    ///    templates of sub-documents. Variables of the sub-document take priority.
+    ///  - ``values`` (default None): A list of values files (paths to YAML files without header) or dicts.
+    ///    They are merged in order (later ones take priority) and are available in all templates as ``values``.
+    ///  - ``globals`` (default None): A dict of variables available in all templates. Keys of the document,
+    ///    $vars and ``values`` take priority.
+    ///  - ``collect_errors`` (default False): If processing a sub-document fails, continue with the other
+    ///    documents. Afterwards a VariableProcessingError is raised, whose ``errors`` attribute contains
+    ///    the errors of the sub-documents as ``(path, exception)`` tuples. Errors in this document itself
+    ///    are still raised immediately.
+    ///  - ``inherit_helpers`` (default True): If a sub-document doesn't have a variable helper, the
+    ///    helpers of its parent documents are used. The sub-document is still the document passed to
+    ///    helpers declared with ``pass_document=True``.
+    ///  - ``timeout`` (default None) and ``cancel_token`` (default None): Abort processing with a
+    ///    ResolutionCancelledError after ``timeout`` seconds or when the CancellationToken is cancelled.
+    ///    This is checked before every template is rendered. The document is left partially processed.
+    ///  - ``progress`` (default None): A callable, that is called with ``("template_rendered", count, path)``
+    ///    after every rendered template. ``count`` is the number of rendered templates so far and ``path``
+    ///    the path of the value in its (sub-)document, like ``services.web.ports[0]``.
+    ///  - ``audit`` (default False): Record every rendered template with its result and the variables and
+    ///    helpers it used, see ``template_audit_log``.
+    ///  - ``safe`` (default False): Render in safe mode, e.g. for configuration from untrusted sources: Templates
+    ///    can only use the built-in filters and functions, ``parent()`` and ``path_in_parent()``. Calling any
+    ///    other variable helper raises a VariableProcessingError, so no Python code is run by templates.
+    ///    The document keeps rendering in safe mode afterwards (e.g. with ``process_vars_for``).
+    #[args(kwargs = "**")]
+    fn process_vars(slf: Py<Self>, py: Python, kwargs: Option<&PyDict>) -> PyResult<Py<Self>> {
         if slf.borrow(py).frozen.is_some() {
             return Err(exceptions::PyRuntimeError::new_err(
                 "Document is already frozen.",
             ));
         }
-        process_variables(py, slf.clone_ref(py).into())?;
+        let mut options = VariableOptions::from_kwargs(kwargs)?;
+        options.stats = slf.borrow(py).stats.clone();
+        process_variables(py, slf.clone_ref(py).into(), &options)?;
         if let Ok(cb) = slf.getattr(py, "_initialize_data_after_variables") {
             let mut mref = slf.borrow_mut(py);
             let args = PyTuple::new(py, take(&mut mref.doc));
@@ -283,10 +905,68 @@ impl YamlConfigDocument {
         Ok(slf)
     }
 
+    /// Renders the templates of the value at path again, against the current state of the document,
+    /// and returns the result. The result is also stored in the document. Nothing else is re-rendered,
+    /// which makes this useful to preview the effect of changing a single value::
+    ///
+    ///     web = doc.internal_get('services')['web']
+    ///     web.internal_set('command', 'run {{ parent().name }}')
+    ///     doc.process_vars_at('services.web.command')
+    ///
+    /// The parts of path are separated by ".", list entries are addressed by their index (``ports.0``).
+    /// Templates in sub-documents are rendered in the context of the sub-document. The ``values`` and
+    /// ``globals`` of the last ``process_vars`` call are used.
+    ///
+    /// Raises a KeyError if the path doesn't exist.
+    fn process_vars_at(slf: Py<Self>, py: Python, path: &str) -> PyResult<YcdValueType> {
+        if slf.borrow(py).frozen.is_some() {
+            return Err(exceptions::PyRuntimeError::new_err(
+                "Document is already frozen.",
+            ));
+        }
+        process_variables_at(py, slf.into(), path)
+    }
+
+    /// Returns the templates rendered by the last ``process_vars`` call with ``audit=True`` as
+    /// ``TemplateAuditEntry`` objects, including those of sub-documents, in the order they were
+    /// rendered. A value whose rendered result contains a template again has an entry per rendering.
+    /// Useful to find out why a value became what it is::
+    ///
+    ///     doc.process_vars(audit=True)
+    ///     for entry in doc.template_audit_log():
+    ///         print(entry.path, entry.template, '->', entry.result, entry.variables, entry.helpers)
+    ///
+    /// Empty if the document was processed without ``audit``.
+    fn template_audit_log(slf: Py<Self>, py: Python) -> Vec<TemplateAuditEntry> {
+        audit_log(py, &slf.into())
+    }
+
+    /// Statistics of loading, resolving and processing this document, e.g. to publish them as metrics
+    /// and watch the complexity of the configuration grow. The counts are the totals of ``from_yaml``
+    /// (or ``load_multiple_yml``), ``resolve_and_merge_references``, ``overlay`` and ``process_vars``
+    /// so far, including the referenced documents and sub-documents. See ``MergeStats``.
+    fn merge_stats(&self) -> MergeStats {
+        self.stats.snapshot()
+    }
+
+    /// A copy-on-write view of the document (see ``DocumentView``): Values changed on the view are
+    /// only changed in the view, copying nothing but the changed values. Useful for plugins that
+    /// tweak a few values of a large document::
+    ///
+    ///     view = doc.view()
+    ///     view['services']['web']['image'] = 'nginx:latest'
+    ///     print(view['services']['web']['image'], doc.internal_get('services')['web']['image'])
+    ///
+    /// The document itself is not changed.
+    fn view(slf: Py<Self>) -> DocumentView {
+        DocumentView::new(slf.into())
+    }
+
     /// Process all {{ variables }} inside the specified string as if it were part of this document.
     //  All references must be resolved beforehand to work correctly (resolve_and_merge_references).
     //
-    //  additional_helpers may contain additional variable helper functions to use.
+    //  additional_helpers may contain additional variable helper functions to use. They are available
+    //  under their __name__ or the name given to the helper decorator.
     fn process_vars_for(
         slf: Py<Self>,
         py: Python,
@@ -316,17 +996,43 @@ impl YamlConfigDocument {
         })
     }
 
+    /// .. admonition:: Variable Helper
+    ///
+    ///     Can be used inside configuration files.
+    ///
+    /// Returns the path of this document in the parent document (parts separated by "/", list
+    /// entries use their index), or None if this is not a sub-document. It is set when the
+    /// sub-documents are loaded (``resolve_and_merge_references``) and kept up to date when
+    /// documents are merged.
+    ///
+    ///  Example usage::
+    ///
+    ///      something: '{{ path_in_parent() }}'
+    ///
+    ///  Example result::
+    ///
+    ///      something: 'level_dict/xyz'
+    fn path_in_parent(&self) -> Option<String> {
+        self.path_in_parent.clone()
+    }
+
     /// Copies the internal data to make it accessible via self.doc and self[...].
     /// You can not call resolve_and_merge_references, process_vars or validate on a frozen document.
     /// If you (still) need to use these, consider using the 'internal_*' methods instead.
+    ///
+    /// Frozen documents are not modified by rendering templates (e.g. with ``process_vars_for``),
+    /// so they can be shared between threads.
     fn freeze(slf: Py<YamlConfigDocument>, py: Python) -> PyResult<()> {
         recursive_ycd_do(
             slf.into(),
             |ycd| {
-                let mut borrow = ycd.borrow_mut(py);
-                borrow.frozen = Some(borrow.doc.to_object(py));
+                {
+                    let mut borrow = ycd.borrow_mut(py);
+                    let doc = unescape_all(Dict(borrow.doc.clone())).unwrap_dict();
+                    borrow.frozen = Some(dict_to_object(py, &doc));
+                }
+                Self::collect_bound_variable_helpers(ycd.as_ref(py), py).ok();
                 if let Ok(cb) = ycd.getattr(py, "_initialize_data_after_freeze") {
-                    drop(borrow);
                     cb.call0(py).ok();
                 };
             },
@@ -367,13 +1073,22 @@ impl YamlConfigDocument {
 
     pub(crate) fn __str__(slf: Py<Self>, py: Python) -> PyResult<String> {
         let typename = slf.getattr(py, "__class__")?.getattr(py, "__name__")?;
-        match &slf.borrow(py).frozen {
-            None => Ok(format!("{}(???)", typename)),
-            Some(_) => {
-                let dict = slf.getattr(py, "doc")?.getattr(py, "__str__")?.call0(py)?;
-                Ok(format!("{}({})", typename, dict))
-            }
+        let is_frozen = slf.borrow(py).frozen.is_some();
+        if !is_frozen {
+            return Ok(format!("{}(???)", typename));
         }
+        let doc: PyYamlConfigDocument = slf.into();
+        let body = document_body(py, &doc)?;
+        let patterns = own_secret_patterns(py, &doc, &body)?;
+        let dict = if patterns.is_empty() {
+            doc.getattr(py, "doc")?.getattr(py, "__str__")?.call0(py)?
+        } else {
+            redact(Dict(body), "", &patterns)
+                .to_object(py)
+                .getattr(py, "__str__")?
+                .call0(py)?
+        };
+        Ok(format!("{}({})", typename, dict))
     }
 
     fn __len__(slf: Py<Self>, py: Python) -> PyResult<usize> {
@@ -411,89 +1126,932 @@ impl YamlConfigDocument {
     }
 
     fn to_dict(slf: Py<Self>, py: Python) -> PyResult<PyObject> {
-        let frozen = &slf.borrow(py).frozen;
-        match frozen {
-            None => {
-                let self_: PyRef<Self> = slf.borrow(py);
-                let mut dict: YcdDict = HashMap::new();
-                dict.insert(
-                    slf.getattr(py, "header")?.call0(py)?.extract(py)?,
-                    Dict(self_.doc.clone()),
-                );
-                Ok(recursive_docs_to_dicts(Dict(dict), py)?.into_py(py))
-            }
-            Some(_) => {
-                // We are doing this from Python code for better readability
-                let args = PyTuple::new(py, [slf.clone_ref(py)]);
-                Ok(py
-                    .import("configcrunch._util")?
-                    .getattr("frozen_ycd_to_dict")?
-                    .call1(args)?
-                    .into_py(py))
-            }
+        let is_frozen = slf.borrow(py).frozen.is_some();
+        if is_frozen {
+            // We are doing this from Python code for better readability
+            let args = PyTuple::new(py, [slf.clone_ref(py)]);
+            return Ok(py
+                .import("configcrunch._util")?
+                .getattr("frozen_ycd_to_dict")?
+                .call1(args)?
+                .into_py(py));
         }
+        let header = slf.getattr(py, "header")?.call0(py)?.extract(py)?;
+        let mut dict = YcdDict::new();
+        dict.insert(header, Dict(slf.borrow(py).doc.clone()));
+        Ok(unescape_all(recursive_docs_to_dicts(Dict(dict), py)?).into_py(py))
     }
 
-    /// If not frozen: Returns a COPY of the key at the specified location
-    /// Otherwise returns it from the frozen `self.doc`, it may or may not be a copy.
-    fn internal_get(slf: &PyCell<Self>, key: &str) -> PyResult<PyObject> {
-        Ok(match &slf.borrow().frozen {
-            None => slf.borrow().doc.get(key).to_object(slf.py()),
-            Some(f) => f
-                .extract::<&PyDict>(slf.py())?
-                .get_item(key)
-                .to_object(slf.py()),
-        })
+    /// Returns the document (including the header) in a canonical YAML form, e.g. for hashing or
+    /// snapshot tests. Equal documents always result in the same text:
+    ///
+    /// - Keys are sorted alphabetically (keys in ``key_order`` of the document type first).
+    /// - Strings (including those forced with the ``str`` filter) are always double-quoted,
+    ///   keys only if they could be read as something else than a string.
+    /// - Floats always contain a ``.`` and infinity and NaN are written as ``.inf`` and ``.nan``.
+    fn normalize(slf: Py<Self>, py: Python) -> PyResult<String> {
+        to_canonical_yaml(py, &slf.into())
     }
 
-    /// If not frozen: Sets the value at the specified location in the internal document.
-    /// Otherwise sets it it in the frozen `self.doc`.
-    fn internal_set(slf: &PyCell<Self>, key: String, val: YcdValueType) -> PyResult<()> {
-        match &slf.borrow().frozen {
-            None => { /*Drop borrow*/ }
-            Some(f) => {
-                f.extract::<&PyDict>(slf.py())?
-                    .set_item(key, val.to_object(slf.py()))?;
-                return Ok(());
-            }
-        }
-        // None:
-        slf.borrow_mut().doc.insert(key, val);
-        Ok(())
+    /// Returns the document like ``normalize``, but every line containing a value ends with a comment
+    /// naming the file the value was loaded from, e.g. to explain to users where their configuration
+    /// comes from. Lists are annotated as a whole (on the line of their key), with all files that added
+    /// entries to them. Values that were not loaded from files are not annotated.
+    /// Secret values are redacted (see ``secret_paths``).
+    ///
+    /// Paths of files inside ``relative_to`` (a directory) are written relative to it.
+    ///
+    /// References must be resolved first (resolve_and_merge_references).
+    #[args(relative_to = "None")]
+    fn render_annotated_yaml(
+        slf: Py<Self>,
+        py: Python,
+        relative_to: Option<&str>,
+    ) -> PyResult<String> {
+        let annotations: Annotations = {
+            let self_ = slf.borrow(py);
+            value_files(py, &self_.doc, &self_.provenance)
+                .into_iter()
+                .map(|(path, files)| {
+                    let files = files
+                        .into_iter()
+                        .map(|f| relative_file(&f, relative_to))
+                        .collect();
+                    (path, files)
+                })
+                .collect()
+        };
+        let doc: PyYamlConfigDocument = slf.into();
+        let secrets = secret_patterns(py, &doc)?;
+        render_yaml(
+            py,
+            &doc,
+            Rendering {
+                annotations: Some(&annotations),
+                secrets: &secrets,
+                float_precision: None,
+            },
+        )
     }
 
-    /// If not frozen: Returns whether the internal document contains `key`.
-    /// Otherwise returns whether the frozen `self.doc` contains `key`.
-    fn internal_contains(slf: &PyCell<Self>, key: &str) -> PyResult<bool> {
-        Ok(match &slf.borrow().frozen {
-            None => slf.borrow().doc.contains_key(key),
-            Some(f) => f.extract::<&PyDict>(slf.py())?.contains(key)?,
-        })
+    /// Returns the document (including the header) as YAML, in the canonical form of ``normalize``.
+    /// If ``redact`` is true, secret values are redacted (see ``secret_paths``).
+    /// If ``float_precision`` is given, floats are rounded to that number of digits after the
+    /// decimal point (e.g. ``0.1`` instead of ``0.30000000000000004 - 0.2``).
+    /// Numbers are written the same on all platforms and in all locales: Floats with as few digits
+    /// as needed and very large and small floats with an exponent (``1.0e+20``).
+    #[args(redact = "true", float_precision = "None")]
+    fn to_yaml(
+        slf: Py<Self>,
+        py: Python,
+        redact: bool,
+        float_precision: Option<u32>,
+    ) -> PyResult<String> {
+        let doc: PyYamlConfigDocument = slf.into();
+        let secrets = if redact {
+            secret_patterns(py, &doc)?
+        } else {
+            vec![]
+        };
+        render_yaml(
+            py,
+            &doc,
+            Rendering {
+                annotations: None,
+                secrets: &secrets,
+                float_precision,
+            },
+        )
     }
 
-    /// If not frozen: Deletes a value from the internal document at `key`.
-    /// Otherwise deletes a value from `self.doc` at `key`.
-    fn internal_delete(slf: &PyCell<Self>, key: &str) -> PyResult<()> {
-        match &slf.borrow().frozen {
-            None => { /* Drop borrow*/ }
-            Some(f) => {
-                f.extract::<&PyDict>(slf.py())?.del_item(key).ok();
-                return Ok(());
-            }
+    /// Returns the document (including the header) as JSON, with the keys in document order.
+    /// If ``redact`` is true, secret values are redacted (see ``secret_paths``).
+    /// Floats are rounded like with ``to_yaml``. Decimals are written as floats and encrypted values
+    /// as ``{"$encrypted": <ciphertext>}``.
+    #[args(redact = "true", float_precision = "None")]
+    fn to_json(
+        slf: Py<Self>,
+        py: Python,
+        redact: bool,
+        float_precision: Option<u32>,
+    ) -> PyResult<String> {
+        let secrets = if redact {
+            secret_patterns(py, &slf.clone_ref(py).into())?
+        } else {
+            vec![]
         };
-        // None:
-        slf.borrow_mut().doc.remove(key);
-        Ok(())
+        let header: String = slf.getattr(py, "header")?.call0(py)?.extract(py)?;
+        let mut dict: YcdDict = Self::to_dict(slf, py)?.extract(py)?;
+        if let Some(body) = dict.shift_remove(&header) {
+            let body = crate::redaction::redact(body, "", &secrets);
+            dict.insert(header, round_floats(body, float_precision));
+        }
+        let kwargs = PyDict::new(py);
+        kwargs.set_item(
+            "default",
+            py.import("configcrunch._util")?.getattr("json_default")?,
+        )?;
+        py.import("json")?
+            .getattr("dumps")?
+            .call((dict_to_object(py, &dict),), Some(kwargs))?
+            .extract()
     }
 
-    /// Freezes the document temporarily (as long as the context is active, and synchronizes all
-    /// data back into the internal document afterwards. Document must not already be frozen.
-    fn internal_access(slf: Py<Self>) -> InternalAccessContext {
+    /// Serializes the document (including the header) to MessagePack.
+    /// The result can be loaded again with ``from_msgpack``.
+    fn to_msgpack(slf: Py<Self>, py: Python) -> PyResult<PyObject> {
+        let dict: YcdDict = Self::to_dict(slf, py)?.extract(py)?;
+        match rmp_serde::to_vec(&dict) {
+            Ok(v) => Ok(PyBytes::new(py, &v).into()),
+            Err(e) => Err(exceptions::PyValueError::new_err(format!(
+                "Unable to serialize document to MessagePack: {:?}",
+                e
+            ))),
+        }
+    }
+
+    #[getter]
+    /// The files that were merged into this document as ``(path, role)`` tuples, in merge order (lowest
+    /// priority first). The role is ``"base"`` (the file the document was loaded from), ``"override"``
+    /// (files merged over it by ``load_multiple_yml`` or ``overlay``) or ``"referenced"`` (files loaded for
+    /// $ref, $ref_first, $extends and $import_all).
+    fn merge_sources(&self) -> Vec<(String, &'static str)> {
+        self.sources
+            .iter()
+            .map(|s| (s.path.clone(), s.role.as_str()))
+            .collect()
+    }
+
+    /// Renders the graph of all resolved $ref references and sub-documents of this document
+    /// as text. For each reference the lookup path that contained the referenced file is included.
+    /// References must be resolved first (resolve_and_merge_references).
+    ///
+    /// :param format: Either "dot" (Graphviz) or "mermaid".
+    #[args(format = "\"dot\"")]
+    fn render_reference_graph(slf: Py<Self>, py: Python, format: &str) -> PyResult<String> {
+        ReferenceGraph::build(py, &slf.into())?.render(format)
+    }
+
+    /// Returns the graph of all resolved $ref references and sub-documents of this document (the same
+    /// graph as rendered by ``render_reference_graph``) as a dict with the lists ``nodes`` and ``edges``.
+    ///
+    /// Nodes are dicts with an ``id`` and a ``kind``: Documents (``kind`` ``"document"``) have the
+    /// ``type`` (class name), the ``file`` they were loaded from (or None) and the ``document`` itself.
+    /// Files loaded for $refs (``kind`` ``"file"``) have their absolute path as ``file``.
+    ///
+    /// Edges are dicts with the ids ``from`` and ``to`` and a ``kind``: ``"ref"`` edges point from a
+    /// document (or file) to a file loaded for a $ref, with the ``reference`` and the ``lookup_path``
+    /// the file was found in. ``"subdocument"`` edges point from a document to a sub-document, with its
+    /// ``path`` (parts separated by "/").
+    ///
+    /// Use this for impact analysis: All documents, from which a file can be reached, depend on it.
+    /// References must be resolved first (resolve_and_merge_references).
+    fn reference_graph(slf: Py<Self>, py: Python) -> PyResult<PyObject> {
+        ReferenceGraph::build(py, &slf.into())?.to_python(py)
+    }
+
+    /// Returns the paths of all values of this document (and its sub-documents) that were loaded from
+    /// ``changed_file``, sorted. Use this to find out which parts of a resolved document depend on a file,
+    /// e.g. to only run the checks relevant to a change.
+    ///
+    /// The parts of the paths are separated by "." (like for ``process_vars_at``). Lists are not split up:
+    /// A list is affected if the file added any entries to it. Values set by a $default are loaded from the
+    /// file containing the $default. Values that were not loaded from files (e.g. from ``from_dict`` or
+    /// registered templates) are never affected.
+    ///
+    /// References must be resolved first (resolve_and_merge_references).
+    fn affected_paths(slf: Py<Self>, py: Python, changed_file: &str) -> Vec<String> {
+        let self_ = slf.borrow(py);
+        value_files(py, &self_.doc, &self_.provenance)
+            .into_iter()
+            .filter(|(_, files)| files.iter().any(|f| same_file(f, changed_file)))
+            .map(|(path, _)| path)
+            .collect()
+    }
+
+    /// Returns the file ``write_back`` changes for the value at ``path``: The file that defines the value
+    /// with the highest priority. For values that don't exist yet, the file of the closest dict (or
+    /// sub-document) containing them is used. None if the value was not loaded from a file.
+    ///
+    /// The parts of the path are separated by "." and list entries are addressed by their index (like
+    /// for ``process_vars_at``). References must be resolved first (resolve_and_merge_references).
+    fn source_file(slf: Py<Self>, py: Python, path: &str) -> PyResult<Option<String>> {
+        Ok(find_source(py, &slf.into(), path)?.map(|source| source.file))
+    }
+
+    /// Returns where every value of this document (and its sub-documents) comes from: A dict of the
+    /// paths of the values to the file that won the merge for them (the file ``source_file`` returns),
+    /// or None if the value was not loaded from a file. Use this to debug why a value ended up in the
+    /// merged document. Paths are sorted and their parts are separated by "." (like for
+    /// ``process_vars_at``). Lists are not split up.
+    ///
+    /// The origins are derived after the fact from the provenance the merge records (the files per key
+    /// path, which ``source_file`` uses as well). The lines are found by reading the files again, so they
+    /// are wrong if the files changed since loading.
+    ///
+    /// If ``lines`` is true, the files are read again to find the lines (starting at 1) of the values
+    /// and the dict contains ``(file, line)`` tuples instead. The line is None if it can't be found,
+    /// e.g. for INI files or values set by a $default. Keyword arguments are loading options, see
+    /// ``from_yaml``; the ``virtual_files`` are read instead of the files on disk.
+    ///
+    /// References must be resolved first (resolve_and_merge_references).
+    #[args(lines = "false", kwargs = "**")]
+    fn origins(
+        slf: Py<Self>,
+        py: Python,
+        lines: bool,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyObject> {
+        let options = LoadOptions::from_kwargs(kwargs)?;
+        let paths = {
+            let self_ = slf.borrow(py);
+            value_files(py, &self_.doc, &self_.provenance)
+        };
+        let doc: PyYamlConfigDocument = slf.into();
+        let mut source_lines = SourceLines::new(&options);
+        let origins = PyDict::new(py);
+        for (path, _) in paths {
+            let origin = match find_source(py, &doc, &path)? {
+                None => py.None(),
+                Some(source) if lines => {
+                    let line = source_lines.line(&source);
+                    (source.file, line).to_object(py)
+                }
+                Some(source) => source.file.to_object(py),
+            };
+            origins.set_item(path, origin)?;
+        }
+        Ok(origins.into())
+    }
+
+    /// Writes ``value`` at ``path`` into the file returned by ``source_file`` and returns that file.
+    /// All other files (layers) are left untouched. Only the text of the changed value is replaced (or the
+    /// new key is inserted into the closest dict containing it), the formatting and comments of the rest
+    /// of the file are kept. New dicts and lists are written as blocks in canonical YAML (see ``normalize``).
+    ///
+    /// This document is not changed, load it again to see the change. Raises a ValueError if the value
+    /// was not loaded from a file, the file is not a YAML file (e.g. a JSON or TOML file), the file is a
+    /// virtual file or a cached file of a remote, git or package lookup path, or the path can not be found
+    /// in the file (e.g. a list entry, that another file added).
+    fn write_back(slf: Py<Self>, py: Python, path: &str, value: YcdValueType) -> PyResult<String> {
+        let source = find_source(py, &slf.into(), path)?.ok_or_else(|| {
+            exceptions::PyValueError::new_err(format!(
+                "Unable to write {}: The value was not loaded from a file.",
+                path
+            ))
+        })?;
+        if source.read_only {
+            return Err(exceptions::PyValueError::new_err(format!(
+                "Unable to write {} to {}: The file is a virtual file or belongs to a remote, git or package lookup path.",
+                path, source.file
+            )));
+        }
+        write_to_source(py, &source, &value)?;
+        Ok(source.file)
+    }
+
+    /// Calls ``callback`` with a ``WalkNode`` for every node of this document (including its
+    /// sub-documents) in document order, e.g. to export the document in a single pass without converting
+    /// it into a dict first. Documents, dicts and lists are visited with an ``"enter"`` event before and
+    /// an ``"exit"`` event after their entries, all other values with a ``"value"`` event. If the callback
+    /// returns False for an ``"enter"`` event, the entries of that node (and its ``"exit"`` event) are skipped.
+    ///
+    /// The document must not be modified and must not be frozen while walking it.
+    fn walk(slf: Py<Self>, py: Python, callback: PyObject) -> PyResult<()> {
+        if slf.borrow(py).frozen.is_some() {
+            return Err(exceptions::PyRuntimeError::new_err(
+                "Document is already frozen.",
+            ));
+        }
+        walk(py, slf.as_ref(py), |node| {
+            let result = callback.call1(py, (Py::new(py, WalkNode::new(py, node))?,))?;
+            Ok(result.extract::<bool>(py).unwrap_or(true))
+        })
+    }
+
+    /// Returns all violations of the policies registered with ``register_policy`` by this document and its
+    /// sub-documents, as ``(policy, path, message)`` tuples. The path is the path of the sub-document
+    /// (parts separated by "/"), or empty for this document. ``resolve_and_merge_references`` raises
+    /// a PolicyViolationError with these violations.
+    fn check_policies(slf: Py<Self>, py: Python) -> PyResult<Vec<Violation>> {
+        check_policies(py, &slf.into())
+    }
+
+    /// Returns the direct sub-documents of this document as a list of ``(path, sub-document)`` tuples.
+    /// The parts of the paths are separated by "/" (list entries use their index).
+    ///
+    /// Keys are visited in document order (see ``reorder``), list entries in list order.
+    /// Converting documents to dicts (``doc``, ``to_dict``, iteration, ``__str__``) and serializing them
+    /// uses the same key order.
+    fn subdocument_items(slf: Py<Self>, py: Python) -> Vec<(String, PyYamlConfigDocument)> {
+        let self_ = slf.borrow(py);
+        let mut out = vec![];
+        for (key, value) in &self_.doc {
+            collect_subdocuments(py, value, key, &mut out);
+        }
+        out
+    }
+
+    /// Changes the order of the keys of the dict at ``path`` (parts separated by ".", an empty path
+    /// for the document body itself). Sub-documents on the path are entered.
+    ///
+    /// The keys in ``key_order`` are moved to the front, in that order. All other keys keep their order
+    /// after them. Keys in ``key_order``, that the dict doesn't contain, are ignored.
+    ///
+    /// Documents keep the order of their keys, as loaded from the files. Keys added by merging are
+    /// inserted after the existing ones.
+    ///
+    ///  :returns: self
+    fn reorder(
+        slf: Py<Self>,
+        py: Python,
+        path: &str,
+        key_order: Vec<String>,
+    ) -> PyResult<Py<Self>> {
+        if slf.borrow(py).frozen.is_some() {
+            return Err(exceptions::PyRuntimeError::new_err(
+                "Document is already frozen.",
+            ));
+        }
+        let parts: Vec<&str> = if path.is_empty() {
+            vec![]
+        } else {
+            path.split('.').collect()
+        };
+        Self::reorder_at(py, slf.clone_ref(py).into(), &parts, &key_order)?;
+        Ok(slf)
+    }
+
+    /// Returns the document as a flat mapping of environment variable names to string values.
+    ///
+    /// Names are built from the upper-cased keys, joined by ``separator`` and prefixed by ``prefix``
+    /// (if given), eg. ``MYAPP__SERVICES__WEB__PORT``. List entries use their index as key.
+    /// Floats are rounded and written like with ``to_yaml``.
+    /// If ``redact`` is true, secret values are redacted (see ``secret_paths``).
+    #[args(
+        prefix = "None",
+        separator = "\"__\"",
+        float_precision = "None",
+        redact = "true"
+    )]
+    fn to_env(
+        slf: Py<Self>,
+        py: Python,
+        prefix: Option<&str>,
+        separator: &str,
+        float_precision: Option<u32>,
+        redact: bool,
+    ) -> PyResult<HashMap<String, String>> {
+        let header: String = slf.getattr(py, "header")?.call0(py)?.extract(py)?;
+        let secrets = if redact {
+            secret_patterns(py, &slf.clone_ref(py).into())?
+        } else {
+            vec![]
+        };
+        let mut dict: YcdDict = Self::to_dict(slf, py)?.extract(py)?;
+        Ok(match dict.shift_remove(&header) {
+            None => HashMap::new(),
+            Some(body) => {
+                let body = crate::redaction::redact(body, "", &secrets);
+                to_env_vars(py, &body, prefix, separator, float_precision)
+            }
+        })
+    }
+
+    /// Writes the document as a .env file to ``path``. See ``to_env`` for the arguments.
+    #[args(
+        prefix = "None",
+        separator = "\"__\"",
+        float_precision = "None",
+        redact = "true"
+    )]
+    fn write_env_file(
+        slf: Py<Self>,
+        py: Python,
+        path: &str,
+        prefix: Option<&str>,
+        separator: &str,
+        float_precision: Option<u32>,
+        redact: bool,
+    ) -> PyResult<()> {
+        let vars = Self::to_env(slf, py, prefix, separator, float_precision, redact)?;
+        fs::write(path, to_env_file(&vars)).map_err(|e| {
+            exceptions::PyIOError::new_err(format!("Unable to write env file {}: {:?}", path, e))
+        })
+    }
+
+    /// If not frozen: Returns a COPY of the key at the specified location.
+    /// Otherwise returns it from the frozen `self.doc`, it may or may not be a copy.
+    fn internal_get(slf: &PyCell<Self>, key: &str) -> PyResult<PyObject> {
+        Ok(match Self::frozen_dict(slf)? {
+            None => slf.borrow().doc.get(key).to_object(slf.py()),
+            Some(f) => f.get_item(key).to_object(slf.py()),
+        })
+    }
+
+    /// Like ``internal_get``, but if not frozen, lists are returned as (read-only) ``YcdList``, that
+    /// only convert the entries that are accessed. Use this to read a few entries of large lists.
+    fn internal_get_lazy(slf: &PyCell<Self>, key: &str) -> PyResult<PyObject> {
+        Ok(match Self::frozen_dict(slf)? {
+            None => match slf.borrow().doc.get(key) {
+                None => slf.py().None(),
+                Some(v) => PyYcdList::wrap(slf.py(), v)?,
+            },
+            Some(f) => f.get_item(key).to_object(slf.py()),
+        })
+    }
+
+    /// If not frozen: Sets the value at the specified location in the internal document.
+    /// Otherwise sets it it in the frozen `self.doc`.
+    fn internal_set(slf: &PyCell<Self>, key: String, val: YcdValueType) -> PyResult<()> {
+        match Self::frozen_dict(slf)? {
+            None => {
+                slf.borrow_mut().doc.insert(key, val);
+            }
+            Some(f) => f.set_item(key, val.to_object(slf.py()))?,
+        }
+        Ok(())
+    }
+
+    /// If not frozen: Returns whether the internal document contains `key`.
+    /// Otherwise returns whether the frozen `self.doc` contains `key`.
+    fn internal_contains(slf: &PyCell<Self>, key: &str) -> PyResult<bool> {
+        Ok(match Self::frozen_dict(slf)? {
+            None => slf.borrow().doc.contains_key(key),
+            Some(f) => f.contains(key)?,
+        })
+    }
+
+    /// If not frozen: Deletes a value from the internal document at `key`.
+    /// Otherwise deletes a value from `self.doc` at `key`.
+    fn internal_delete(slf: &PyCell<Self>, key: &str) -> PyResult<()> {
+        match Self::frozen_dict(slf)? {
+            None => {
+                slf.borrow_mut().doc.shift_remove(key);
+            }
+            Some(f) => {
+                f.del_item(key).ok();
+            }
+        }
+        Ok(())
+    }
+
+    /// Freezes the document temporarily (as long as the context is active, and synchronizes all
+    /// data back into the internal document afterwards. Document must not already be frozen.
+    fn internal_access(slf: Py<Self>) -> InternalAccessContext {
         InternalAccessContext(slf.into())
     }
 }
 
 impl YamlConfigDocument {
+    /// Replaces the body. The previous body is dropped with an explicit stack (see
+    /// nested::drop_value).
+    pub(crate) fn set_doc(&mut self, doc: YcdDict) {
+        drop_value(Dict(std::mem::replace(&mut self.doc, doc)));
+    }
+
+    /// Returns the header of cls. If dict (a loaded document, with header) contains one of the
+    /// ``header_aliases`` of cls instead of the header, it is renamed to the header. If it contains
+    /// neither and cls allows headerless documents, the dict is wrapped under the header.
+    pub(crate) fn canonicalize_header(
+        cls: &PyType,
+        dict: &mut YcdDict,
+        source: &str,
+    ) -> PyResult<String> {
+        let header: String = cls.getattr("header")?.call0()?.extract()?;
+        let aliases: Vec<String> = cls.getattr("header_aliases")?.call0()?.extract()?;
+        let mut found = aliases.iter().filter(|a| dict.contains_key(*a));
+        let alias = match found.next() {
+            None if !dict.contains_key(&header)
+                && cls.getattr("allow_headerless")?.call0()?.is_true()? =>
+            {
+                let mut body = take(dict);
+                if let Some(features) = body.shift_remove(FEATURES) {
+                    dict.insert(FEATURES.to_string(), features);
+                }
+                dict.insert(header.clone(), Dict(body));
+                return Ok(header);
+            }
+            None => return Ok(header),
+            Some(alias) => alias,
+        };
+        if dict.contains_key(&header) || found.next().is_some() {
+            let err = InvalidHeaderError::new_err(format!(
+                "The document {} contains more than one of the headers {}.",
+                source,
+                [header.as_str()]
+                    .into_iter()
+                    .chain(aliases.iter().map(String::as_str))
+                    .collect::<Vec<&str>>()
+                    .join(", ")
+            ));
+            return Err(with_suggestions(cls.py(), err, &BTreeMap::new()));
+        }
+        rename_key(dict, alias, &header);
+        Ok(header)
+    }
+
+    /// Implementation of reorder. parts is the remaining path inside of doc.
+    fn reorder_at(
+        py: Python,
+        doc: PyYamlConfigDocument,
+        parts: &[&str],
+        key_order: &[String],
+    ) -> PyResult<()> {
+        let mut doc_mut = doc.borrow_mut(py);
+        let mut dict = &mut doc_mut.doc;
+        for (i, part) in parts.iter().enumerate() {
+            match dict.get_mut(*part) {
+                Some(Dict(d)) => dict = d,
+                Some(Ycd(subdoc)) => {
+                    return Self::reorder_at(py, subdoc.clone_ref(py), &parts[i + 1..], key_order)
+                }
+                _ => {
+                    return Err(exceptions::PyKeyError::new_err(format!(
+                        "There is no dict at {}.",
+                        parts[..=i].join(".")
+                    )))
+                }
+            }
+        }
+        reorder_keys(dict, key_order);
+        Ok(())
+    }
+
+    /// Implementation of from_yaml with already parsed options.
+    pub(crate) fn from_yaml_internal(
+        cls: &PyType,
+        py: Python,
+        path_to_yaml: String,
+        options: &LoadOptions,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let entire_document = load_document_file(py, &path_to_yaml, cls, options)?;
+        let file = path_to_yaml.as_str();
+        let doc = Self::from_entire_document(cls, py, entire_document, file, Some(file))?;
+        if options.virtual_files.get(file).is_some() {
+            for source in doc.borrow_mut(py).sources.iter_mut() {
+                source.read_only = true;
+            }
+        }
+        validate_layer(py, &doc.0, file, options)?;
+        Ok(doc)
+    }
+
+    /// Constructs a document from a loaded dict that still contains the header.
+    /// source names the document in error messages, file is the path of the file it was loaded from
+    /// (None if it was not loaded from a file).
+    pub(crate) fn from_entire_document(
+        cls: &PyType,
+        py: Python,
+        mut entire_document: YcdDict,
+        source: &str,
+        file: Option<&str>,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let header = Self::canonicalize_header(cls, &mut entire_document, source)?;
+        if !entire_document.contains_key(&header) {
+            return Err(invalid_header_error(
+                py,
+                cls,
+                &entire_document,
+                format!(
+                    "The document does not have a valid header. Expected was: {}",
+                    header
+                ),
+            )?);
+        }
+        let content = entire_document.shift_remove(&header).unwrap();
+        match content {
+            YcdValueType::Dict(c) => {
+                let features = file_features(source, &entire_document)?;
+                let doc = construct_new_ycd(
+                    py,
+                    cls,
+                    [
+                        cls.into_py(py),
+                        c.into_py(py),
+                        py.None(),
+                        py.None(),
+                        py.None(),
+                        file.map(|f| vec![f.to_string()]).into_py(py),
+                    ],
+                )?;
+                let mut doc_mut = doc.borrow_mut(py);
+                doc_mut.features = features;
+                doc_mut.provenance = Provenance::from_body(&doc_mut.doc, file);
+                doc_mut.sources = file
+                    .map(|f| MergeSource {
+                        path: f.to_string(),
+                        role: MergeRole::Base,
+                        read_only: false,
+                    })
+                    .into_iter()
+                    .collect();
+                drop(doc_mut);
+                Ok(doc)
+            }
+            _ => Err(InvalidDocumentError::new_err(format!(
+                "The document at {} is invalid",
+                source
+            ))),
+        }
+    }
+
+    /// Resolves the references of the document and then its anchors, checks the policies and the typed
+    /// schema and validates it (``"merged"`` phase), like ``resolve_and_merge_references``. The
+    /// statistics are recorded in the document.
+    pub(crate) fn resolve_document(
+        slf: Py<Self>,
+        py: Python,
+        lookup_paths: Vec<String>,
+        options: &mut LoadOptions,
+    ) -> PyResult<Py<YamlConfigDocument>> {
+        options.stats = slf.borrow(py).stats.clone();
+        let slf = Self::resolve_and_merge_references_internal(slf, py, lookup_paths, options)?;
+        let doc: PyYamlConfigDocument = slf.clone_ref(py).into();
+        resolve_anchors(py, &doc)?;
+        enforce_policies(py, &doc)?;
+        enforce_typed_schema(py, &doc)?;
+        validate_merged(py, &slf, options)?;
+        Ok(slf)
+    }
+
+    /// Implementation of resolve_and_merge_references with already parsed options.
+    pub(crate) fn resolve_and_merge_references_internal(
+        slf: Py<Self>,
+        py: Python,
+        lookup_paths: Vec<String>,
+        options: &LoadOptions,
+    ) -> PyResult<Py<YamlConfigDocument>> {
+        let document = slf.clone_ref(py);
+        let attributes = move || Self::span_attributes(&document, py);
+        with_span(py, "configcrunch.resolve", attributes, || {
+            Self::resolve_and_merge_references_in_span(slf, py, lookup_paths, options)
+        })
+    }
+
+    fn resolve_and_merge_references_in_span(
+        slf: Py<Self>,
+        py: Python,
+        lookup_paths: Vec<String>,
+        options: &LoadOptions,
+    ) -> PyResult<Py<YamlConfigDocument>> {
+        if slf.borrow(py).frozen.is_some() {
+            return Err(exceptions::PyRuntimeError::new_err(
+                "Document is already frozen.",
+            ));
+        }
+        let slf_clone = slf.clone_ref(py);
+
+        if let Ok(cb) = slf.getattr(py, "_initialize_data_before_merge") {
+            let mut mref = slf.borrow_mut(py);
+            let args = PyTuple::new(py, [take(&mut mref.doc)]);
+            drop(mref);
+            let tmp = cb.call1(py, args)?.extract(py)?;
+            let mut mref = slf.borrow_mut(py);
+            mref.doc = tmp;
+            drop(mref);
+        }
+
+        resolve_and_merge(py, slf.clone_ref(py).into(), &lookup_paths, options)?;
+
+        if let Ok(cb) = slf.getattr(py, "_initialize_data_after_merge") {
+            let mut mref = slf.borrow_mut(py);
+            let args = PyTuple::new(py, [take(&mut mref.doc).into_py(py)]);
+            drop(mref);
+            let tmp = cb.call1(py, args)?.extract(py)?;
+            let mut mref = slf.borrow_mut(py);
+            mref.doc = tmp;
+            drop(mref);
+        }
+
+        apply_custom_directives(py, &slf.clone_ref(py).into())?;
+        {
+            let mut self_: PyRefMut<Self> = slf.borrow_mut(py);
+            let mut d = Dict(take(&mut self_.doc));
+            apply_defaults(&mut d)?;
+            self_.doc = d.unwrap_dict();
+        }
+
+        let subdoc_spec = slf.call_method0(py, "subdocuments")?.extract(py)?;
+        load_subdocuments(
+            py,
+            slf.clone_ref(py).into(),
+            subdoc_spec,
+            &lookup_paths,
+            options,
+        )?;
+
+        let metadata_keys: Vec<String> = slf.call_method0(py, "metadata_keys")?.extract(py)?;
+        let mut self_: PyRefMut<Self> = slf.borrow_mut(py);
+        let mut d = Dict(take(&mut self_.doc));
+        delete_metadata(&mut d, &metadata_keys)?;
+        match delete_remove_markers(py, d)? {
+            Dict(dd) => self_.doc = dd,
+            _ => {
+                return Err(exceptions::PyRuntimeError::new_err(
+                    "Internal algorithm failure.",
+                ))
+            }
+        }
+        Ok(slf_clone)
+    }
+
+    /// Finds the keys in data (the document body) that are not part of the schema and handles them
+    /// according to policy. Returns data without these keys.
+    fn handle_unknown_keys(
+        slf: &PyCell<Self>,
+        py: Python,
+        schema: &PyAny,
+        data: PyObject,
+        policy: &str,
+    ) -> PyResult<PyObject> {
+        if !["error", "warn", "keep"].contains(&policy) {
+            return Err(exceptions::PyValueError::new_err(format!(
+                "Invalid value for unknown_keys: {}. Expected 'error', 'warn' or 'keep'.",
+                policy
+            )));
+        }
+        let util = py.import("configcrunch._util")?;
+        let unknown: &PyList = util
+            .getattr("find_unknown_keys")?
+            .call1((schema, data.clone_ref(py)))?
+            .extract()?;
+        if unknown.is_empty() {
+            return Ok(data);
+        }
+        if policy != "keep" {
+            let document = Py::<Self>::from(slf);
+            let mut paths = Vec::with_capacity(unknown.len());
+            let mut suggestions = BTreeMap::new();
+            for path in unknown.iter() {
+                let path: Vec<String> = path.extract()?;
+                let (key, parent) = path.split_last().unwrap();
+                let known: Vec<String> = util
+                    .getattr("schema_keys_at")?
+                    .call1((schema, parent.to_vec()))?
+                    .extract()?;
+                let close = did_you_mean(key, known.iter().map(String::as_str));
+                let file = match document_files(py, &document, &path.join(".")).pop() {
+                    Some(file) => format!(" (in {})", file),
+                    None => String::new(),
+                };
+                let path = path.join("/");
+                paths.push(format!("{}{}{}", path, suggestion_hint(&close), file));
+                suggestions.insert(path, close);
+            }
+            let class_name: String = slf.getattr("__class__")?.getattr("__name__")?.extract()?;
+            let message = format!(
+                "Unknown keys in the {} document: {}",
+                class_name,
+                paths.join(", ")
+            );
+            if policy == "error" {
+                return Err(with_suggestions(
+                    py,
+                    UnknownKeyError::new_err(message),
+                    &suggestions,
+                ));
+            }
+            PyErr::warn(py, py.get_type::<UnknownKeyWarning>(), &message, 1)?;
+        }
+        Ok(util.getattr("without_keys")?.call1((data, unknown))?.into())
+    }
+
+    /// Checks the validation rules of the document. args contains the document body.
+    fn check_validation_rules(slf: &PyCell<Self>, py: Python, args: &PyTuple) -> PyResult<()> {
+        let rules: &PyDict = slf.getattr("validation_rules")?.call0()?.extract()?;
+        let pydoc: PyYamlConfigDocument = slf.extract::<Py<Self>>()?.into();
+        let mut violations: Vec<(String, String)> = vec![];
+        for (name, rule) in rules.iter() {
+            let name: String = name.extract()?;
+            if let Ok(expression) = rule.extract::<&str>() {
+                if !evaluate_condition(py, &pydoc, "validation rule", expression)? {
+                    violations.push((name, format!("{} is not true", expression)));
+                }
+            } else {
+                match rule.call1(args) {
+                    Ok(result) => {
+                        if !result.is_true()? {
+                            violations.push((name, "The check failed".to_string()));
+                        }
+                    }
+                    Err(e) if e.is_instance::<SchemaError>(py) => {
+                        violations.push((name, e.pvalue(py).to_string()));
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+        if violations.is_empty() {
+            return Ok(());
+        }
+        let class_name: String = slf.getattr("__class__")?.getattr("__name__")?.extract()?;
+        let err = RuleViolationError::new_err(format!(
+            "The {} document violates validation rules:\n{}",
+            class_name,
+            violations
+                .iter()
+                .map(|(name, message)| format!("- {}: {}", name, message))
+                .collect::<Vec<String>>()
+                .join("\n")
+        ));
+        let names: Vec<&String> = violations.iter().map(|(name, _)| name).collect();
+        err.pvalue(py).setattr("rules", names)?;
+        Err(err)
+    }
+
+    /// The body of a layer (a dict containing the body or a YamlConfigDocument), without sub-documents.
+    fn layer_body(py: Python, layer: YcdValueType) -> PyResult<YcdDict> {
+        match layer {
+            Dict(_) | YcdValueType::Ycd(_) => Ok(recursive_docs_to_dicts(layer, py)?.unwrap_dict()),
+            v => Err(exceptions::PyTypeError::new_err(format!(
+                "A layer must be a dict or a YamlConfigDocument, got: {}",
+                v
+            ))),
+        }
+    }
+
+    /// Constructs a new document of type cls containing a copy of the body of layer
+    /// (a dict or a document) for overlay and compose.
+    fn layer_to_document(
+        cls: &PyType,
+        py: Python,
+        layer: YcdValueType,
+    ) -> PyResult<PyYamlConfigDocument> {
+        let (body, path, absolute_paths, sources) = match layer {
+            Dict(body) => (body, None, vec![], vec![]),
+            YcdValueType::Ycd(layer) => {
+                let layer = layer.borrow(py);
+                (
+                    layer.doc.clone(),
+                    layer.path.clone(),
+                    layer.absolute_paths.clone(),
+                    layer.sources.clone(),
+                )
+            }
+            v => {
+                return Err(exceptions::PyTypeError::new_err(format!(
+                    "A layer must be a dict or a YamlConfigDocument, got: {}",
+                    v
+                )))
+            }
+        };
+        let doc = construct_new_ycd(
+            py,
+            cls,
+            [
+                cls.into_py(py),
+                body.into_py(py),
+                path.into_py(py),
+                py.None(),
+                py.None(),
+                absolute_paths.into_py(py),
+            ],
+        )?;
+        doc.borrow_mut(py).sources = sources;
+        Ok(doc)
+    }
+
+    /// Merges layer over slf and stores the result in slf.
+    fn overlay_internal(
+        slf: Py<Self>,
+        py: Python,
+        layer: PyYamlConfigDocument,
+        options: &LoadOptions,
+    ) -> PyResult<Py<Self>> {
+        mark_as_override(&mut layer.borrow_mut(py).sources);
+        let merged = merge_documents(py, layer, slf.clone_ref(py).into(), options)?;
+        let mut merged = merged.borrow_mut(py);
+        let mut self_: PyRefMut<Self> = slf.borrow_mut(py);
+        self_.set_doc(take(&mut merged.doc));
+        for path in merged.absolute_paths.iter() {
+            if !self_.absolute_paths.contains(path) {
+                self_.absolute_paths.push(path.clone());
+            }
+        }
+        self_.references = take(&mut merged.references);
+        self_.sources = take(&mut merged.sources);
+        self_.provenance = take(&mut merged.provenance);
+        drop(self_);
+        Ok(slf)
+    }
+
+    /// Attributes of OpenTelemetry spans for this document.
+    pub(crate) fn span_attributes(
+        slf: &Py<Self>,
+        py: Python,
+    ) -> PyResult<Vec<(&'static str, String)>> {
+        let class_name: String = slf
+            .getattr(py, "__class__")?
+            .getattr(py, "__name__")?
+            .extract(py)?;
+        let mut attributes = vec![("configcrunch.document", class_name)];
+        if let Some(file) = slf.borrow(py).absolute_paths.first() {
+            attributes.push(("configcrunch.file", file.clone()));
+        }
+        Ok(attributes)
+    }
+
     /// Infinite recursion check
     fn infinite_recursion_check(&mut self, mut already_loaded_docs: Vec<String>) -> PyResult<()> {
         if let Some(path) = &self.path {
@@ -509,27 +2067,45 @@ impl YamlConfigDocument {
         Ok(())
     }
 
+    /// The frozen representation of the document, if frozen. The document is not borrowed
+    /// afterwards, so Python code (e.g. ``__del__`` of replaced values) can't run while it is.
+    fn frozen_dict(slf: &PyCell<Self>) -> PyResult<Option<&PyDict>> {
+        let frozen = slf.borrow().frozen.as_ref().map(|f| f.clone_ref(slf.py()));
+        match frozen {
+            None => Ok(None),
+            Some(f) => Ok(Some(f.into_ref(slf.py()).extract()?)),
+        }
+    }
+
     /// Loads bound variable helper methods to this instance for use in variable processing.
-    pub(crate) fn collect_bound_variable_helpers<'py>(
-        slf: &'py PyCell<Self>,
-        py: Python<'py>,
-    ) -> PyResult<&'py PyCell<Self>> {
+    /// Returns the helpers. The document is not borrowed while Python code runs, so this is safe
+    /// to call while other threads use the document. If the document is in use (borrowed), the
+    /// helpers are not stored and collected again on next use.
+    pub(crate) fn collect_bound_variable_helpers(
+        slf: &PyCell<Self>,
+        py: Python,
+    ) -> PyResult<HashMap<String, PyObject>> {
         let inspect = py.import("inspect")?;
         let ismethod = inspect.getattr("ismethod")?;
         let args = PyTuple::new(py, [slf.to_object(py), ismethod.to_object(py)]);
         let members: &PyList = inspect.getattr("getmembers")?.call1(args)?.extract()?;
+        let mut helpers = HashMap::new();
         for tpl in members.iter() {
             let tpl: &PyTuple = tpl.extract()?;
             let itm = tpl.get_item(1)?;
-            let name: String = tpl.get_item(0)?.extract()?;
             if itm.hasattr("__is_variable_helper")? {
-                slf.borrow_mut().bound_helpers.insert(name, itm.into_py(py));
+                helpers.insert(helper_name(itm)?, itm.into_py(py));
             }
         }
-        slf.borrow_mut()
-            .bound_helpers
-            .insert("parent".to_string(), slf.getattr("parent")?.to_object(py));
-        Ok(slf)
+        helpers.insert("parent".to_string(), slf.getattr("parent")?.to_object(py));
+        helpers.insert(
+            "path_in_parent".to_string(),
+            slf.getattr("path_in_parent")?.to_object(py),
+        );
+        if let Ok(mut slf) = slf.try_borrow_mut() {
+            slf.bound_helpers = helpers.clone();
+        }
+        Ok(helpers)
     }
 
     #[inline]
@@ -547,6 +2123,53 @@ impl PyIterProtocol for YamlConfigDocument {
     }
 }
 
+/// Documents reference each other (sub-documents their parent, the parent its sub-documents) and
+/// themselves (the bound variable helpers), so they must take part in garbage collection. Otherwise
+/// resolved documents are never freed.
+#[pyproto]
+impl PyGCProtocol for YamlConfigDocument {
+    fn __traverse__(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        traverse_dict(&self.doc, &visit)?;
+        if let Some(frozen) = &self.frozen {
+            visit.call(frozen)?;
+        }
+        if let Some(parent) = &self.parent_doc {
+            visit.call(parent)?;
+        }
+        for helper in self.bound_helpers.values() {
+            visit.call(helper)?;
+        }
+        traverse_dict(&self.local_vars, &visit)?;
+        for dict in self.values.iter().chain(self.globals.iter()) {
+            traverse_dict(dict, &visit)?;
+        }
+        for entry in &self.template_audit {
+            entry.traverse(&visit)?;
+        }
+        Ok(())
+    }
+
+    fn __clear__(&mut self) {
+        self.set_doc(YcdDict::new());
+        self.frozen = None;
+        self.parent_doc = None;
+        self.bound_helpers.clear();
+        drop_value(Dict(take(&mut self.local_vars)));
+        self.values = None;
+        self.globals = None;
+        self.template_audit.clear();
+    }
+}
+
+/// The body and the $vars are dropped with an explicit stack (see nested::drop_value), so dropping
+/// deeply nested documents can't overflow the stack.
+impl Drop for YamlConfigDocument {
+    fn drop(&mut self) {
+        drop_value(Dict(take(&mut self.doc)));
+        drop_value(Dict(take(&mut self.local_vars)));
+    }
+}
+
 #[pyclass(module = "_main")]
 struct InternalAccessContext(PyYamlConfigDocument);
 
@@ -570,7 +2193,7 @@ impl InternalAccessContext {
                 match &borrow.frozen {
                     None => {}
                     Some(f) => {
-                        borrow.doc = f.extract(py).unwrap();
+                        borrow.set_doc(f.extract(py).unwrap());
                         borrow.frozen = None;
                     }
                 }
@@ -584,14 +2207,44 @@ impl InternalAccessContext {
 #[pyclass(module = "_main")]
 #[derive(Clone)]
 pub(crate) struct DocReference {
-    referenced_type: Py<PyType>, // Type[YamlConfigDocument]
+    #[pyo3(get)]
+    pub(crate) referenced_type: Py<PyType>, // Type[YamlConfigDocument]
+    #[pyo3(get)]
+    pub(crate) container: Option<String>, // None, "list" or "map"
 }
 
 #[pymethods]
 impl DocReference {
+    /// :param referenced_type: Type of the referenced document.
+    /// :param container:       If ``"list"``, a list of documents is expected, if ``"map"`` a dict of documents.
+    ///                         Each element is validated and errors contain the index or key of the element.
     #[new]
-    pub(crate) fn new(referenced_type: Py<PyType>) -> Self {
-        Self { referenced_type }
+    #[args(container = "None")]
+    pub(crate) fn new(referenced_type: Py<PyType>, container: Option<String>) -> PyResult<Self> {
+        if let Some(c) = &container {
+            if c != "list" && c != "map" {
+                return Err(exceptions::PyValueError::new_err(format!(
+                    "Invalid container: {}. Expected 'list' or 'map'.",
+                    c
+                )));
+            }
+        }
+        Ok(Self {
+            referenced_type,
+            container,
+        })
+    }
+
+    /// A reference to a list of documents of type referenced_type.
+    #[staticmethod]
+    fn list_of(referenced_type: Py<PyType>) -> PyResult<Self> {
+        Self::new(referenced_type, Some("list".to_string()))
+    }
+
+    /// A reference to a dict (string keys) of documents of type referenced_type.
+    #[staticmethod]
+    fn map_of(referenced_type: Py<PyType>) -> PyResult<Self> {
+        Self::new(referenced_type, Some("map".to_string()))
     }
 
     fn __repr__(&self, py: Python) -> PyResult<String> {
@@ -603,7 +2256,10 @@ impl DocReference {
             .referenced_type
             .extract::<&PyType>(py)?
             .getattr("__name__")?;
-        Ok(format!("DocReference<{:?}>", typename))
+        Ok(match &self.container {
+            None => format!("DocReference<{:?}>", typename),
+            Some(c) => format!("DocReference<{} of {:?}>", c, typename),
+        })
     }
 
     /// Validates. If the subdocument still contains $ref, it is not validated further,
@@ -611,6 +2267,39 @@ impl DocReference {
     /// according to it's schema.
     pub(crate) fn validate(slf: Py<Self>, data: &PyAny, py: Python) -> PyResult<bool> {
         let self_: PyRef<Self> = slf.borrow(py);
+        match self_.container.as_deref() {
+            None => self_.validate_single(data, py),
+            Some("list") => {
+                let list: &PyList = data.downcast().map_err(|_| {
+                    SchemaError::new_err(format!("Expected a list of documents, got: {:?}", data))
+                })?;
+                for (i, item) in list.iter().enumerate() {
+                    self_
+                        .validate_single(item, py)
+                        .map_err(|e| Self::element_error(py, e, format!("[{}]", i)))?;
+                }
+                Ok(true)
+            }
+            Some(_) => {
+                let dict: &PyDict = data.downcast().map_err(|_| {
+                    SchemaError::new_err(format!("Expected a dict of documents, got: {:?}", data))
+                })?;
+                for (key, item) in dict.iter() {
+                    let key: &str = key.extract().map_err(|_| {
+                        SchemaError::new_err(format!("Expected string keys, got: {:?}", key))
+                    })?;
+                    self_
+                        .validate_single(item, py)
+                        .map_err(|e| Self::element_error(py, e, format!("[{:?}]", key)))?;
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl DocReference {
+    fn validate_single(&self, data: &PyAny, py: Python) -> PyResult<bool> {
         if data.is_instance::<PyDict>()? {
             let data: &PyDict = data.extract()?;
             // If the reference still contains the $ref keyword, it is treated as an
@@ -620,8 +2309,7 @@ impl DocReference {
             }
             return Err(SchemaError::new_err(format!(
                 "Expected an instance of {:?} while validating, got {:?}: {:?}",
-                self_
-                    .referenced_type
+                self.referenced_type
                     .extract::<&PyType>(py)?
                     .getattr("__name__")?,
                 data.getattr("__class__")?.getattr("__name__")?,
@@ -629,7 +2317,7 @@ impl DocReference {
             )));
         }
 
-        let self_type = self_.referenced_type.extract::<&PyType>(py)?;
+        let self_type = self.referenced_type.extract::<&PyType>(py)?;
         if self_type.is_instance(data)? {
             let data_doc: &PyCell<YamlConfigDocument> = data.extract()?;
             if data_doc.borrow().doc.contains_key(REF) {
@@ -639,14 +2327,34 @@ impl DocReference {
         }
         Err(SchemaError::new_err(format!(
             "Expected an instance of {:?} while validating, got {:?}: {:?}",
-            self_
-                .referenced_type
+            self.referenced_type
                 .extract::<&PyType>(py)?
                 .getattr("__name__")?,
             data.getattr("__class__")?.getattr("__name__")?,
             data
         )))
     }
+
+    /// Prefixes the message of a validation error of an element with the path of the element. The
+    /// error keeps its type (a SchemaError, if it can't be created with a message) and attributes
+    /// (e.g. ``rules``), the original error is its cause.
+    fn element_error(py: Python, err: PyErr, path: String) -> PyErr {
+        let original = err.pvalue(py);
+        let message = format!("{}: {}", path, original);
+        let new_err = original
+            .get_type()
+            .call1((message.clone(),))
+            .map(PyErr::from_instance)
+            .unwrap_or_else(|_| SchemaError::new_err(message));
+        let value = new_err.pvalue(py);
+        if let (Ok(attributes), Ok(new_attributes)) =
+            (original.getattr("__dict__"), value.getattr("__dict__"))
+        {
+            new_attributes.call_method1("update", (attributes,)).ok();
+        }
+        value.setattr("__cause__", original).ok();
+        new_err
+    }
 }
 
 fn recursive_ycd_do<F>(ycd: PyYamlConfigDocument, cb: F, py: Python)
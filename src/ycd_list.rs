@@ -0,0 +1,152 @@
+//! ``YcdList``: Read-only lists of documents, whose entries are converted to Python objects
+//! only when they are accessed.
+use crate::conv::YcdValueType::List;
+use crate::conv::{YcdList, YcdValueType};
+use pyo3::basic::CompareOp;
+use pyo3::class::gc::{PyGCProtocol, PyTraverseError, PyVisit};
+use pyo3::exceptions;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PySlice};
+use pyo3::PyIterProtocol;
+
+/// A read-only copy of a list of a document, implementing the sequence protocol. Entries are
+/// converted to Python objects when they are accessed (nested lists are YcdLists again).
+/// Compares equal to lists with the same entries. Use ``list(...)`` to get a (mutable) list.
+#[pyclass(name = "YcdList", module = "_main", gc)]
+pub(crate) struct PyYcdList(YcdList);
+
+impl PyYcdList {
+    pub(crate) fn new(list: YcdList) -> Self {
+        Self(list)
+    }
+
+    pub(crate) fn list(&self) -> &YcdList {
+        &self.0
+    }
+
+    /// Converts value to a Python object. Lists are converted to YcdLists.
+    pub(crate) fn wrap(py: Python, value: &YcdValueType) -> PyResult<PyObject> {
+        Ok(match value {
+            List(l) => Py::new(py, Self::new(l.clone()))?.into_py(py),
+            v => v.to_object(py),
+        })
+    }
+
+    fn to_list<'py>(&self, py: Python<'py>) -> &'py PyList {
+        PyList::new(py, self.0.iter().map(|v| v.to_object(py)))
+    }
+}
+
+#[pymethods]
+impl PyYcdList {
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __getitem__(&self, py: Python, index: &PyAny) -> PyResult<PyObject> {
+        if let Ok(slice) = index.downcast::<PySlice>() {
+            let indices = slice.indices(self.0.len() as _)?;
+            let items = (0..indices.slicelength)
+                .map(|i| self.0[(indices.start + i * indices.step) as usize].clone())
+                .collect();
+            return Ok(Py::new(py, Self::new(items))?.into_py(py));
+        }
+        let len = self.0.len() as isize;
+        let i: isize = index.extract()?;
+        let i = if i < 0 { i + len } else { i };
+        if i < 0 || i >= len {
+            return Err(exceptions::PyIndexError::new_err("list index out of range"));
+        }
+        Self::wrap(py, &self.0[i as usize])
+    }
+
+    fn __contains__(&self, py: Python, value: &PyAny) -> PyResult<bool> {
+        Ok(self.count(py, value)? > 0)
+    }
+
+    fn __repr__(&self, py: Python) -> PyResult<String> {
+        Ok(self.to_list(py).repr()?.to_string())
+    }
+
+    fn __richcmp__(&self, py: Python, other: &PyAny, op: CompareOp) -> PyResult<PyObject> {
+        let other = match other.extract::<PyRef<Self>>() {
+            Ok(o) => o.to_list(py),
+            Err(_) => match other.downcast::<PyList>() {
+                Ok(o) => o,
+                Err(_) => return Ok(py.NotImplemented()),
+            },
+        };
+        Ok(self.to_list(py).rich_compare(other, op)?.into())
+    }
+
+    /// Number of entries equal to value.
+    fn count(&self, py: Python, value: &PyAny) -> PyResult<usize> {
+        let mut count = 0;
+        for entry in &self.0 {
+            if Self::wrap(py, entry)?.as_ref(py).eq(value)? {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Index of the first entry equal to value. Raises a ValueError if there is none.
+    fn index(&self, py: Python, value: &PyAny) -> PyResult<usize> {
+        for (i, entry) in self.0.iter().enumerate() {
+            if Self::wrap(py, entry)?.as_ref(py).eq(value)? {
+                return Ok(i);
+            }
+        }
+        Err(exceptions::PyValueError::new_err(format!(
+            "{} is not in list",
+            value.repr()?
+        )))
+    }
+}
+
+#[pyproto]
+impl PyIterProtocol for PyYcdList {
+    fn __iter__(slf: PyRef<Self>) -> PyResult<Py<YcdListIterator>> {
+        Py::new(
+            slf.py(),
+            YcdListIterator {
+                list: slf.into(),
+                index: 0,
+            },
+        )
+    }
+}
+
+#[pyproto]
+impl PyGCProtocol for PyYcdList {
+    fn __traverse__(&self, visit: PyVisit) -> Result<(), PyTraverseError> {
+        self.0.iter().try_for_each(|v| v.traverse(&visit))
+    }
+
+    fn __clear__(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[pyclass(module = "_main")]
+pub(crate) struct YcdListIterator {
+    list: Py<PyYcdList>,
+    index: usize,
+}
+
+#[pyproto]
+impl PyIterProtocol for YcdListIterator {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(mut slf: PyRefMut<Self>) -> PyResult<Option<PyObject>> {
+        let py = slf.py();
+        let value = match slf.list.borrow(py).0.get(slf.index) {
+            None => return Ok(None),
+            Some(v) => PyYcdList::wrap(py, v)?,
+        };
+        slf.index += 1;
+        Ok(Some(value))
+    }
+}